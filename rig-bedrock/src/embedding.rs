@@ -1,15 +1,19 @@
 use aws_smithy_types::Blob;
 use rig::embeddings::{self, Embedding, EmbeddingError};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{client::Client, types::errors::AwsSdkInvokeModelError};
 
+/// Request of the Amazon Titan embedding models.
+/// Note: `amazon.titan-embed-text-v1` does not support `dimensions` and `normalize`.
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EmbeddingRequest {
     pub input_text: String,
-    pub dimensions: usize,
-    pub normalize: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -19,6 +23,18 @@ pub struct EmbeddingResponse {
     pub input_text_token_count: usize,
 }
 
+/// Request of the Cohere embedding models
+#[derive(Serialize)]
+pub struct CohereEmbeddingRequest {
+    pub texts: Vec<String>,
+    pub input_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CohereEmbeddingResponse {
+    pub embeddings: Vec<Vec<f64>>,
+}
+
 /// `amazon.titan-embed-text-v1`
 pub const AMAZON_TITAN_EMBED_TEXT_V1: &str = "amazon.titan-embed-text-v1";
 /// `amazon.titan-embed-text-v2:0`
@@ -50,7 +66,39 @@ impl EmbeddingModel {
         &self,
         request: EmbeddingRequest,
     ) -> Result<EmbeddingResponse, EmbeddingError> {
-        let input_document = serde_json::to_string(&request).map_err(EmbeddingError::JsonError)?;
+        self.invoke_model(&request).await
+    }
+
+    /// Embed a batch of documents with a Cohere embedding model.
+    pub async fn documents_to_cohere_embeddings(
+        &self,
+        request: CohereEmbeddingRequest,
+    ) -> Result<CohereEmbeddingResponse, EmbeddingError> {
+        self.invoke_model(&request).await
+    }
+
+    fn is_cohere(&self) -> bool {
+        self.model.starts_with("cohere.")
+    }
+
+    fn titan_request(&self, document: String) -> EmbeddingRequest {
+        // Only Titan v2 supports custom dimensions and normalization
+        let (dimensions, normalize) = match (self.model.as_str(), self.ndims) {
+            (AMAZON_TITAN_EMBED_TEXT_V1, _) | (_, None) => (None, None),
+            (_, Some(ndims)) => (Some(ndims), Some(true)),
+        };
+        EmbeddingRequest {
+            input_text: document,
+            dimensions,
+            normalize,
+        }
+    }
+
+    async fn invoke_model<T: DeserializeOwned>(
+        &self,
+        request: &impl Serialize,
+    ) -> Result<T, EmbeddingError> {
+        let input_document = serde_json::to_string(request).map_err(EmbeddingError::JsonError)?;
 
         let model_response = self
             .client
@@ -70,15 +118,34 @@ impl EmbeddingModel {
         let response_str = String::from_utf8(response.body.into_inner())
             .map_err(|e| EmbeddingError::ResponseError(e.to_string()))?;
 
-        let result: EmbeddingResponse =
-            serde_json::from_str(&response_str).map_err(EmbeddingError::JsonError)?;
+        serde_json::from_str(&response_str).map_err(EmbeddingError::JsonError)
+    }
+}
 
-        Ok(result)
+/// Pair the documents with the embeddings of a Cohere response, which must have one embedding
+/// per document.
+fn cohere_embeddings(
+    documents: Vec<String>,
+    response: CohereEmbeddingResponse,
+) -> Result<Vec<Embedding>, EmbeddingError> {
+    if response.embeddings.len() != documents.len() {
+        return Err(EmbeddingError::ResponseError(format!(
+            "Response data length ({}) does not match input length ({})",
+            response.embeddings.len(),
+            documents.len()
+        )));
     }
+
+    Ok(documents
+        .into_iter()
+        .zip(response.embeddings)
+        .map(|(document, vec)| Embedding { document, vec })
+        .collect())
 }
 
 impl embeddings::EmbeddingModel for EmbeddingModel {
-    const MAX_DOCUMENTS: usize = 1024;
+    // Titan models embed one document per request, Cohere models up to 96
+    const MAX_DOCUMENTS: usize = 96;
 
     fn ndims(&self) -> usize {
         self.ndims.unwrap_or(0)
@@ -90,16 +157,23 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     ) -> Result<Vec<Embedding>, EmbeddingError> {
         let documents: Vec<_> = documents.into_iter().collect();
 
+        if self.is_cohere() {
+            let response = self
+                .documents_to_cohere_embeddings(CohereEmbeddingRequest {
+                    texts: documents.clone(),
+                    input_type: "search_document".to_string(),
+                })
+                .await?;
+
+            return cohere_embeddings(documents, response);
+        }
+
         let mut results = Vec::new();
         let mut errors = Vec::new();
 
         let mut iterator = documents.into_iter();
         while let Some(embedding) = iterator.next().map(|doc| async move {
-            let request = EmbeddingRequest {
-                input_text: doc.to_owned(),
-                dimensions: self.ndims(),
-                normalize: true,
-            };
+            let request = self.titan_request(doc.to_owned());
             self.document_to_embeddings(request)
                 .await
                 .map(|embeddings| Embedding {
@@ -119,3 +193,70 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aws_config::BehaviorVersion;
+    use serde_json::json;
+
+    use super::*;
+
+    fn model(model: &str, ndims: Option<usize>) -> EmbeddingModel {
+        let sdk_config = aws_sdk_bedrockruntime::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .build();
+        let client = Client {
+            aws_client: aws_sdk_bedrockruntime::Client::from_conf(sdk_config),
+        };
+        EmbeddingModel::new(client, model, ndims)
+    }
+
+    #[test]
+    fn titan_v1_request_has_no_dimensions() {
+        let request = model(AMAZON_TITAN_EMBED_TEXT_V1, Some(1536)).titan_request("doc".into());
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({"inputText": "doc"})
+        );
+    }
+
+    #[test]
+    fn titan_v2_request_has_dimensions() {
+        let request = model(AMAZON_TITAN_EMBED_TEXT_V2_0, Some(256)).titan_request("doc".into());
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({"inputText": "doc", "dimensions": 256, "normalize": true})
+        );
+    }
+
+    #[test]
+    fn cohere_models_are_batched() {
+        assert!(model(COHERE_EMBED_ENGLISH_V3, Some(1024)).is_cohere());
+        assert!(!model(AMAZON_TITAN_EMBED_TEXT_V2_0, Some(1024)).is_cohere());
+    }
+
+    #[test]
+    fn cohere_embeddings_match_documents() {
+        let documents = vec!["a".to_string(), "b".to_string()];
+
+        let embeddings = cohere_embeddings(
+            documents.clone(),
+            CohereEmbeddingResponse {
+                embeddings: vec![vec![1.0], vec![2.0]],
+            },
+        )
+        .unwrap();
+        assert_eq!(embeddings[1].document, "b");
+        assert_eq!(embeddings[1].vec, vec![2.0]);
+
+        assert!(matches!(
+            cohere_embeddings(
+                documents,
+                CohereEmbeddingResponse {
+                    embeddings: vec![vec![1.0]],
+                },
+            ),
+            Err(EmbeddingError::ResponseError(_))
+        ));
+    }
+}
@@ -48,10 +48,8 @@ impl StreamingCompletionModel for CompletionModel {
                     aws_bedrock::ConverseStreamOutput::ContentBlockDelta(event) => {
                         let delta = event.delta.ok_or(CompletionError::ProviderError("The delta for a content block is missing".into()))?;
                         match delta {
-                            aws_bedrock::ContentBlockDelta::Text(text) => {
-                                if current_tool_call.is_none() {
-                                    yield Ok(StreamingChoice::Message(text))
-                                }
+                            aws_bedrock::ContentBlockDelta::Text(text) if current_tool_call.is_none() => {
+                                yield Ok(StreamingChoice::Message(text))
                             },
                             aws_bedrock::ContentBlockDelta::ToolUse(tool) => {
                                 if let Some(ref mut tool_call) = current_tool_call {
@@ -31,6 +31,15 @@ impl AwsCompletionRequest {
                 inference_configuration.set_max_tokens(Some(*max_tokens as i32));
         }
 
+        if let Some(top_p) = &self.0.top_p {
+            inference_configuration = inference_configuration.set_top_p(Some(*top_p as f32));
+        }
+
+        if !self.0.stop.is_empty() {
+            inference_configuration =
+                inference_configuration.set_stop_sequences(Some(self.0.stop.clone()));
+        }
+
         Some(inference_configuration.build())
     }
 
@@ -86,7 +86,7 @@ mod tests {
             media_type: Some(DocumentMediaType::PDF),
         });
         let aws_document: Result<aws_bedrock::DocumentBlock, _> = rig_document.clone().try_into();
-        assert_eq!(aws_document.is_ok(), true);
+        assert!(aws_document.is_ok());
         let aws_document = aws_document.unwrap();
         assert_eq!(aws_document.format, aws_bedrock::DocumentFormat::Pdf);
         let document_data = BASE64_STANDARD.decode(rig_document.0.data).unwrap();
@@ -128,7 +128,7 @@ mod tests {
             .build()
             .unwrap();
         let rig_document: Result<RigDocument, _> = aws_document.clone().try_into();
-        assert_eq!(rig_document.is_ok(), true);
+        assert!(rig_document.is_ok());
         let rig_document = rig_document.unwrap().0;
         assert_eq!(rig_document.media_type.unwrap(), DocumentMediaType::PDF)
     }
@@ -144,7 +144,7 @@ mod tests {
             .build()
             .unwrap();
         let rig_document: Result<RigDocument, _> = aws_document.clone().try_into();
-        assert_eq!(rig_document.is_ok(), false);
+        assert!(rig_document.is_err());
         assert_eq!(
             rig_document.err().unwrap().to_string(),
             CompletionError::ProviderError("Unsupported media type xlsx".into()).to_string()
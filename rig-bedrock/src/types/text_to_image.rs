@@ -118,6 +118,7 @@ impl TryFrom<TextToImageResponse>
 
             return Ok(Self {
                 image: data,
+                url: None,
                 response: value,
             });
         }
@@ -0,0 +1,120 @@
+//! Benchmarks of the search modes of the in-memory vector store, on synthetic documents embedded
+//! by the mock embedding model.
+//!
+//! Run with `cargo bench -p rig-core --features metrics,hnsw`. Besides the criterion reports, the
+//! performance counters of each store (see `rig::vector_store::metrics`) are printed.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::executor::block_on;
+use rig::{
+    embeddings::EmbeddingsBuilder,
+    providers::mock::MockEmbeddingModel,
+    vector_store::{
+        hnsw::HnswConfig,
+        in_memory_store::InMemoryVectorStore,
+        metrics::{IntoMetered, Metered},
+        quantization::QuantizationConfig,
+        VectorStore, VectorStoreIndex,
+    },
+    Embed, OneOrMany,
+};
+
+const NDIMS: usize = 384;
+const DOCUMENTS: usize = 2_000;
+const TOP_N: usize = 10;
+
+const WORDS: &[&str] = &[
+    "alien", "ancient", "apple", "bridge", "castle", "cloud", "desert", "dragon", "engine",
+    "forest", "galaxy", "garden", "harbor", "island", "jungle", "kernel", "lantern", "machine",
+    "mountain", "nebula", "ocean", "planet", "quantum", "river", "rocket", "signal", "storm",
+    "temple", "tool", "valley", "volcano", "winter",
+];
+
+/// Deterministic text of 8 words, different for each `seed`.
+fn text(seed: usize) -> String {
+    let mut state = seed as u64 * 0x9e3779b97f4a7c15 + 1;
+    (0..8)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            WORDS[(state % WORDS.len() as u64) as usize]
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn embed<T: Embed + Send>(
+    model: &MockEmbeddingModel,
+    documents: impl IntoIterator<Item = T>,
+) -> Vec<(T, OneOrMany<rig::embeddings::Embedding>)> {
+    block_on(
+        EmbeddingsBuilder::new(model.clone())
+            .documents(documents)
+            .expect("documents should be embeddable")
+            .build(),
+    )
+    .expect("mock embeddings should not fail")
+}
+
+fn search(c: &mut Criterion) {
+    let model = MockEmbeddingModel::new(NDIMS);
+    let embeddings = embed(&model, (0..DOCUMENTS).map(text));
+    let queries = (0..100).map(|i| text(DOCUMENTS + i)).collect::<Vec<_>>();
+
+    let stores = [
+        (
+            "exact",
+            InMemoryVectorStore::from_documents(embeddings.clone()),
+        ),
+        (
+            "int8",
+            InMemoryVectorStore::from_documents(embeddings.clone())
+                .quantized(QuantizationConfig::int8()),
+        ),
+        (
+            "hnsw",
+            InMemoryVectorStore::from_documents(embeddings).hnsw(HnswConfig::default()),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("top_n_ids");
+    group.throughput(Throughput::Elements(1));
+    for (name, store) in stores {
+        let index = store.index(model.clone()).metered();
+        let mut queries = queries.iter().cycle();
+
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let query = queries.next().expect("queries are cycled");
+                block_on(index.top_n_ids(query, TOP_N)).expect("search should not fail")
+            })
+        });
+        println!("{name}: {}", index.metrics());
+    }
+    group.finish();
+}
+
+fn ingest(c: &mut Criterion) {
+    const BATCH: usize = 100;
+
+    let model = MockEmbeddingModel::new(NDIMS);
+    let batch = embed(&model, (0..BATCH).map(text))
+        .into_iter()
+        .enumerate()
+        .map(|(i, (document, embeddings))| (format!("doc{i}"), document, embeddings))
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("insert_documents");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    let mut store = Metered::new(InMemoryVectorStore::<String>::default());
+    group.bench_function("upsert", |b| {
+        b.iter(|| {
+            block_on(store.insert_documents(batch.clone(), true)).expect("insert should not fail")
+        })
+    });
+    println!("upsert: {}", store.metrics());
+    group.finish();
+}
+
+criterion_group!(benches, search, ingest);
+criterion_main!(benches);
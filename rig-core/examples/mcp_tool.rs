@@ -1,15 +1,15 @@
 use anyhow::Result;
 use mcp_core::{
-    client::ClientBuilder,
     server::Server,
     tool_text_content,
-    transport::{ClientSseTransportBuilder, ServerSseTransport},
-    types::{ClientCapabilities, Implementation, ServerCapabilities, ToolResponseContent},
+    transport::ServerSseTransport,
+    types::{ServerCapabilities, ToolResponseContent},
 };
 use mcp_core_macros::tool;
 use rig::{
     completion::Prompt,
     providers::{self},
+    tool::{mcp::McpClient, ToolDyn},
 };
 use serde_json::json;
 
@@ -40,40 +40,21 @@ async fn main() -> Result<(), anyhow::Error> {
         ServerSseTransport::new("127.0.0.1".to_string(), 3000, mcp_server_protocol);
     tokio::spawn(async move { Server::start(mcp_server_transport).await });
 
-    // Create the MCP client
-    let mcp_client = ClientBuilder::new(
-        ClientSseTransportBuilder::new("http://127.0.0.1:3000/sse".to_string()).build(),
-    )
-    .build();
-    // Start the MCP client
-    mcp_client.open().await?;
+    // Connect to the MCP server and discover its tools
+    let mcp_client = McpClient::sse("http://127.0.0.1:3000/sse").await?;
+    println!("Initialized: {:?}", mcp_client.server());
 
-    let init_res = mcp_client
-        .initialize(
-            Implementation {
-                name: "mcp-client".to_string(),
-                version: "0.1.0".to_string(),
-            },
-            ClientCapabilities::default(),
-        )
-        .await?;
-    println!("Initialized: {:?}", init_res);
-
-    let tools_list_res = mcp_client.list_tools(None, None).await?;
-    println!("Tools: {:?}", tools_list_res);
+    let tools = mcp_client.tools().await?;
+    println!(
+        "Tools: {:?}",
+        tools.iter().map(|tool| tool.name()).collect::<Vec<_>>()
+    );
 
     tracing::info!("Building RIG agent");
     let completion_model = providers::openai::Client::from_env();
-    let mut agent_builder = completion_model.agent("gpt-4o");
 
     // Add MCP tools to the agent
-    agent_builder = tools_list_res
-        .tools
-        .into_iter()
-        .fold(agent_builder, |builder, tool| {
-            builder.mcp_tool(tool, mcp_client.clone().into())
-        });
-    let agent = agent_builder.build();
+    let agent = completion_model.agent("gpt-4o").mcp_tools(tools).build();
 
     tracing::info!("Prompting RIG agent");
     let response = agent.prompt("Add 10 + 10").await?;
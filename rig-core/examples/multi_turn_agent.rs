@@ -62,7 +62,7 @@ impl<M: rig::completion::CompletionModel> MultiTurnAgent<M> {
                         current_prompt = Message::User {
                             content: OneOrMany::one(UserContent::tool_result(
                                 id,
-                                OneOrMany::one(ToolResultContent::text(tool_result)),
+                                OneOrMany::one(ToolResultContent::from_tool_output(tool_result)),
                             )),
                         };
 
@@ -1,10 +1,8 @@
 use anyhow::Result;
 use rig::{
     completion::{Prompt, ToolDefinition},
-    embeddings::EmbeddingsBuilder,
     providers::openai::{Client, TEXT_EMBEDDING_ADA_002},
     tool::{Tool, ToolEmbedding, ToolSet},
-    vector_store::in_memory_store::InMemoryVectorStore,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -150,17 +148,8 @@ async fn main() -> Result<(), anyhow::Error> {
         .dynamic_tool(Subtract)
         .build();
 
-    let embeddings = EmbeddingsBuilder::new(embedding_model.clone())
-        .documents(toolset.schemas()?)?
-        .build()
-        .await?;
-
-    // Create vector store with the embeddings
-    let vector_store =
-        InMemoryVectorStore::from_documents_with_id_f(embeddings, |tool| tool.name.clone());
-
-    // Create vector store index
-    let index = vector_store.index(embedding_model);
+    // Embed the tools in a vector store index
+    let index = toolset.index(embedding_model).await?;
 
     // Create RAG agent with a single context prompt and a dynamic tool source
     let calculator_rag = openai_client
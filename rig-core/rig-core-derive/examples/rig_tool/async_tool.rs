@@ -3,7 +3,6 @@ use rig::providers;
 use rig::tool::Tool;
 use rig_derive::rig_tool;
 use std::time::Duration;
-use tracing_subscriber;
 
 // Example demonstrating async tool usage
 #[rig_tool(
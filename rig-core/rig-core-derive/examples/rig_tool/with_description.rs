@@ -2,7 +2,6 @@ use rig::completion::Prompt;
 use rig::providers;
 use rig::tool::Tool;
 use rig_derive::rig_tool;
-use tracing_subscriber;
 
 // Example with description attribute
 #[rig_tool(description = "Perform basic arithmetic operations")]
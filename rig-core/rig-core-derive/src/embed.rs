@@ -73,15 +73,16 @@ impl StructParser for DataStruct {
 
                 let field_name = &field.ident;
 
+                // The texts of the field are tagged with its name
                 quote! {
-                    self.#field_name
+                    embedder.embed_field(stringify!(#field_name), &self.#field_name)?;
                 }
             })
             .collect::<Vec<_>>();
 
         (
             quote! {
-                #(#embed_targets.embed(embedder)?;)*
+                #(#embed_targets)*
             },
             embed_targets.len(),
         )
@@ -95,7 +96,9 @@ impl StructParser for DataStruct {
                 let field_name = &field.ident;
 
                 quote! {
-                    #custom_func_path(embedder, self.#field_name.clone())?;
+                    embedder.in_field(stringify!(#field_name), |embedder| {
+                        #custom_func_path(embedder, self.#field_name.clone())
+                    })?;
                 }
             })
             .collect::<Vec<_>>();
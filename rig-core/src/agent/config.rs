@@ -0,0 +1,235 @@
+//! Serializable configuration of agents, so agents can be defined in configuration files
+//! (e.g.: JSON or YAML) and reloaded without recompiling, instead of with hardcoded builders.
+//!
+//! An [AgentConfig] describes the model, preamble, generation parameters, few-shot examples and
+//! static context of an agent, as well as the names of its tools. Since tools are code, their implementations are
+//! given to [Agent::from_config] (or [AgentBuilder::from_config]) in a [ToolSet], from which the
+//! tools named in the configuration are picked.
+//!
+//! # Example
+//! ```rust
+//! use rig::{agent::{Agent, AgentConfig}, providers::openai, tool::ToolSet};
+//!
+//! let config = AgentConfig::from_json(r#"{
+//!     "model": "gpt-4o",
+//!     "preamble": "You are a calculator.",
+//!     "temperature": 0.0,
+//!     "examples": [{ "user": "2 + 2", "assistant": "4" }],
+//!     "context": ["Only integers are supported."],
+//!     "tools": ["add"],
+//!     "max_turns": 5
+//! }"#)?;
+//!
+//! let openai = openai::Client::from_env();
+//! let agent = Agent::from_config(&openai, &config, ToolSet::from_tools(vec![Adder, Subtract]))?;
+//! ```
+//!
+//! Any serde format can be used, e.g.: YAML with the `serde_yaml` crate:
+//! ```rust
+//! let config: AgentConfig = serde_yaml::from_str(&std::fs::read_to_string("agent.yaml")?)?;
+//! ```
+use serde::{Deserialize, Serialize};
+
+use super::{Agent, AgentBuilder};
+use crate::{
+    completion::{CompletionClient, CompletionModel, GenerationConfig},
+    tool::ToolSet,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AgentConfigError {
+    /// A tool named in the configuration is not in the given toolset
+    #[error("ToolNotFoundError: {0}")]
+    ToolNotFoundError(String),
+
+    /// Error parsing a configuration
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// A few-shot example of an [AgentConfig] (see [AgentBuilder::example]).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Example {
+    pub user: String,
+    pub assistant: String,
+}
+
+/// Serializable configuration of an agent (see the [module](self) documentation).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Name of the completion model (e.g.: `gpt-4o`)
+    pub model: String,
+    /// System prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preamble: Option<String>,
+    /// Generation parameters of the model (temperature, maximum number of tokens, etc.),
+    /// at the top level of the configuration
+    #[serde(flatten)]
+    pub generation: GenerationConfig,
+    /// Few-shot examples, sent as user/assistant message pairs before the chat history
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<Example>,
+    /// Static context documents
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<String>,
+    /// Names of the static tools
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+    /// Maximum number of tool call rounds before a final answer (see [AgentBuilder::max_turns])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<usize>,
+    /// Additional parameters to be passed to the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additional_params: Option<serde_json::Value>,
+}
+
+impl AgentConfig {
+    /// Parse a configuration from JSON
+    pub fn from_json(json: &str) -> Result<Self, AgentConfigError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+impl<M: CompletionModel> AgentBuilder<M> {
+    /// Create a builder configured with `config`, with a model of `client`. The tools named in
+    /// the configuration are taken from `tools` (the others are dropped).
+    ///
+    /// The builder can then be completed with what a configuration cannot describe
+    /// (e.g.: dynamic context, hooks).
+    pub fn from_config<C: CompletionClient<Model = M>>(
+        client: &C,
+        config: &AgentConfig,
+        mut tools: ToolSet,
+    ) -> Result<Self, AgentConfigError> {
+        let mut static_tools = ToolSet::default();
+        for name in &config.tools {
+            let tool = tools
+                .tools
+                .remove(name)
+                .ok_or_else(|| AgentConfigError::ToolNotFoundError(name.clone()))?;
+            static_tools.tools.insert(name.clone(), tool);
+        }
+
+        let mut builder = AgentBuilder::new(client.completion_model(&config.model))
+            .generation_config(config.generation.clone())
+            .tools(static_tools)
+            .expect("tools of a new builder should not conflict");
+        if let Some(preamble) = &config.preamble {
+            builder = builder.preamble(preamble);
+        }
+        for example in &config.examples {
+            builder = builder.example(&example.user, &example.assistant);
+        }
+        for doc in &config.context {
+            builder = builder.context(doc);
+        }
+        if let Some(max_turns) = config.max_turns {
+            builder = builder.max_turns(max_turns);
+        }
+        if let Some(params) = &config.additional_params {
+            builder = builder.additional_params(params.clone());
+        }
+
+        Ok(builder)
+    }
+}
+
+impl<M: CompletionModel> Agent<M> {
+    /// Create an agent configured with `config`, with a model of `client`. The tools named in the
+    /// configuration are taken from `tools` (the others are dropped).
+    pub fn from_config<C: CompletionClient<Model = M>>(
+        client: &C,
+        config: &AgentConfig,
+        tools: ToolSet,
+    ) -> Result<Self, AgentConfigError> {
+        Ok(AgentBuilder::from_config(client, config, tools)?.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        completion::{Message, Prompt, ToolDefinition},
+        providers::mock::MockCompletionModel,
+        tool::Tool,
+    };
+
+    #[derive(Deserialize)]
+    struct AddArgs {
+        x: i32,
+        y: i32,
+    }
+
+    struct Adder;
+
+    impl Tool for Adder {
+        const NAME: &'static str = "add";
+
+        type Error = std::convert::Infallible;
+        type Args = AddArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: json!({}),
+            }
+        }
+
+        async fn call(&self, args: AddArgs) -> Result<i32, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_from_config() {
+        let config = AgentConfig::from_json(
+            r#"{
+                "model": "mock",
+                "preamble": "You are a calculator.",
+                "temperature": 0.5,
+                "max_tokens": 100,
+                "examples": [{"user": "2 + 2?", "assistant": "4"}],
+                "context": ["Only integers are supported."],
+                "tools": ["add"],
+                "max_turns": 2
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.generation.temperature, Some(0.5));
+        assert_eq!(
+            serde_json::from_value::<AgentConfig>(serde_json::to_value(&config).unwrap()).unwrap(),
+            config
+        );
+
+        let client = MockCompletionModel::new()
+            .tool_call("add", json!({"x": 1, "y": 2}))
+            .text("3");
+        let agent = Agent::from_config(&client, &config, ToolSet::from_tools(vec![Adder])).unwrap();
+
+        assert_eq!(agent.prompt("1 + 2?").await.unwrap(), "3");
+
+        let request = &client.requests()[0];
+        assert_eq!(request.preamble.as_deref(), Some("You are a calculator."));
+        assert_eq!(request.temperature, Some(0.5));
+        assert_eq!(
+            request.chat_history,
+            vec![Message::user("2 + 2?"), Message::assistant("4")]
+        );
+        assert_eq!(request.documents[0].text, "Only integers are supported.");
+        assert_eq!(request.tools[0].name, "add");
+
+        let config = AgentConfig {
+            tools: vec!["subtract".to_string()],
+            ..config
+        };
+        assert!(matches!(
+            Agent::from_config(&client, &config, ToolSet::from_tools(vec![Adder])),
+            Err(AgentConfigError::ToolNotFoundError(name)) if name == "subtract"
+        ));
+    }
+}
@@ -1,3 +1,36 @@
+//! This module provides functionality for working with audio generation (text-to-speech)
+//! models. It provides traits and structs for generating speech generation requests,
+//! handling their responses, and defining audio generation models.
+//!
+//! Together with [transcription](crate::transcription) (speech-to-text) models, it allows
+//! building voice agents: transcribe the user's speech, prompt an agent with the transcript
+//! and speak its response.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     audio_generation::AudioGenerationModel,
+//!     completion::Prompt,
+//!     providers::openai,
+//!     transcription::TranscriptionModel,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let whisper = openai.transcription_model(openai::WHISPER_1);
+//! let tts = openai.audio_generation_model(openai::GPT_4O_MINI_TTS);
+//! let agent = openai.agent(openai::GPT_4O).build();
+//!
+//! let transcript = whisper.transcription_request().load_file("question.mp3").send().await?;
+//! let answer = agent.prompt(transcript.text.as_str()).await?;
+//!
+//! let speech = tts
+//!     .audio_generation_request()
+//!     .text(&answer)
+//!     .voice("alloy")
+//!     .send()
+//!     .await?;
+//! std::fs::write("answer.mp3", speech.audio)?;
+//! ```
 use serde_json::Value;
 use thiserror::Error;
 
@@ -11,15 +44,15 @@ pub enum AudioGenerationError {
     #[error("JsonError: {0}")]
     JsonError(#[from] serde_json::Error),
 
-    /// Error building the transcription request
+    /// Error building the audio generation request
     #[error("RequestError: {0}")]
     RequestError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
 
-    /// Error parsing the transcription response
+    /// Error parsing the audio generation response
     #[error("ResponseError: {0}")]
     ResponseError(String),
 
-    /// Error returned by the transcription model provider
+    /// Error returned by the audio generation model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
 }
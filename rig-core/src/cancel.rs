@@ -0,0 +1,269 @@
+//! This module provides the [CancellationToken] struct, used to abort long-running agent
+//! operations (see [Agent::cancellable](crate::agent::Agent::cancellable)): multi-step runs
+//! with retrieval, completions and tool calls.
+//!
+//! A token is cancelled explicitly with [CancellationToken::cancel] (e.g.: when the user
+//! closes the connection), or implicitly once its deadline is reached. The operations run with
+//! the token are then dropped, which cancels their in-flight HTTP requests and tool calls, and
+//! they fail with a [Cancelled] error.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{
+//!     cancel::{CancellationToken, Cancelled},
+//!     completion::{Prompt, PromptError},
+//! };
+//!
+//! let token = CancellationToken::new().timeout(Duration::from_secs(30));
+//!
+//! // Cancel the run from another task (e.g.: a "Stop" button)
+//! let stop = token.clone();
+//! tokio::spawn(async move { stop_button.clicked().await; stop.cancel() });
+//!
+//! match agent.cancellable(token).prompt("Plan my trip to Lisbon").await {
+//!     Err(PromptError::Cancelled(Cancelled::DeadlineExceeded)) => println!("Too slow"),
+//!     Err(PromptError::Cancelled(Cancelled::ByToken)) => println!("Stopped"),
+//!     response => println!("{:?}", response),
+//! }
+//! ```
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures::future::{self, Either};
+use web_time::Instant;
+
+/// Why an operation run with a [CancellationToken] was aborted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Cancelled {
+    /// The token was cancelled with [CancellationToken::cancel]
+    #[error("cancelled")]
+    ByToken,
+    /// The deadline of the token was reached
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Wakers of the tasks waiting for the cancellation
+    wakers: Mutex<Wakers>,
+}
+
+/// Wakers of the pending [Cancellation] futures of a token, by key.
+#[derive(Default)]
+struct Wakers {
+    next_key: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+/// Future completing when a token is cancelled explicitly. Its waker is registered while it is
+/// pending and deregistered when it is dropped, so tokens outliving many operations (e.g.: the
+/// token of a server) don't accumulate the wakers of the finished ones.
+struct Cancellation<'a> {
+    inner: &'a Inner,
+    /// Key of the registered waker, if any
+    key: Option<u64>,
+}
+
+impl Future for Cancellation<'_> {
+    type Output = Cancelled;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Cancelled> {
+        if self.inner.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(Cancelled::ByToken);
+        }
+        {
+            let mut wakers = self.inner.wakers.lock().expect("wakers lock poisoned");
+            let key = *self.key.get_or_insert_with(|| {
+                wakers.next_key += 1;
+                wakers.next_key
+            });
+            match wakers.wakers.get_mut(&key) {
+                Some(waker) if waker.will_wake(cx.waker()) => (),
+                _ => {
+                    wakers.wakers.insert(key, cx.waker().clone());
+                }
+            }
+        }
+        // The token may have been cancelled before the waker was registered
+        if self.inner.cancelled.load(Ordering::Acquire) {
+            Poll::Ready(Cancelled::ByToken)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Cancellation<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            if let Ok(mut wakers) = self.inner.wakers.lock() {
+                wakers.wakers.remove(&key);
+            }
+        }
+    }
+}
+
+/// Token used to abort the operations it is passed to, on demand or at a deadline.
+/// Clones of a token share its cancellation: cancelling one cancels all of them.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+    deadline: Option<Instant>,
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.inner.cancelled.load(Ordering::Acquire))
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the deadline of the token: operations still running at `deadline` are aborted with
+    /// [Cancelled::DeadlineExceeded]. The deadline only applies to this token and the tokens
+    /// cloned from it afterwards.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the deadline of the token to `timeout` from now (see [Self::deadline]).
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Cancel the token, aborting the operations run with it (or any of its clones).
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        let wakers = std::mem::take(
+            &mut self
+                .inner
+                .wakers
+                .lock()
+                .expect("wakers lock poisoned")
+                .wakers,
+        );
+        wakers.into_values().for_each(Waker::wake);
+    }
+
+    /// Why the token is cancelled, if it is (explicitly, or because its deadline is reached).
+    pub fn cancelled(&self) -> Option<Cancelled> {
+        if self.inner.cancelled.load(Ordering::Acquire) {
+            Some(Cancelled::ByToken)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some(Cancelled::DeadlineExceeded)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled().is_some()
+    }
+
+    /// Wait until the token is cancelled, explicitly or because its deadline is reached.
+    pub async fn wait(&self) -> Cancelled {
+        let cancelled = Cancellation {
+            inner: &self.inner,
+            key: None,
+        };
+
+        match self.deadline {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match future::select(pin!(cancelled), futures_timer::Delay::new(timeout)).await {
+                    Either::Left((cancelled, _)) => cancelled,
+                    Either::Right(_) => Cancelled::DeadlineExceeded,
+                }
+            }
+            None => cancelled.await,
+        }
+    }
+
+    /// Run `future` until it completes or the token is cancelled, in which case `future` is
+    /// dropped (cancelling the work in flight) and the reason of the cancellation is returned.
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, Cancelled> {
+        if let Some(cancelled) = self.cancelled() {
+            return Err(cancelled);
+        }
+
+        match future::select(pin!(future), pin!(self.wait())).await {
+            Either::Left((output, _)) => Ok(output),
+            Either::Right((cancelled, _)) => Err(cancelled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CancellationToken, Cancelled};
+
+    #[tokio::test]
+    async fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        assert_eq!(token.run(async { 42 }).await, Ok(42));
+
+        let clone = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            clone.cancel();
+        });
+        assert_eq!(
+            token.run(futures::future::pending::<()>()).await,
+            Err(Cancelled::ByToken)
+        );
+        assert_eq!(token.run(async { 42 }).await, Err(Cancelled::ByToken));
+
+        let token = CancellationToken::new().timeout(Duration::from_millis(20));
+        assert_eq!(
+            token.run(futures::future::pending::<()>()).await,
+            Err(Cancelled::DeadlineExceeded)
+        );
+        assert_eq!(token.cancelled(), Some(Cancelled::DeadlineExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_wakers_deregistered() {
+        let token = CancellationToken::new();
+        let wakers = |token: &CancellationToken| token.inner.wakers.lock().unwrap().wakers.len();
+
+        // The operations complete after the token was polled, registering their waker
+        for _ in 0..100 {
+            assert_eq!(token.run(tokio::task::yield_now()).await, Ok(()));
+        }
+        assert_eq!(wakers(&token), 0);
+
+        let clone = token.clone();
+        let pending =
+            tokio::spawn(async move { clone.run(futures::future::pending::<()>()).await });
+        while wakers(&token) == 0 {
+            tokio::task::yield_now().await;
+        }
+        token.cancel();
+        assert_eq!(pending.await.unwrap(), Err(Cancelled::ByToken));
+        assert_eq!(wakers(&token), 0);
+    }
+}
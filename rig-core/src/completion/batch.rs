@@ -0,0 +1,203 @@
+//! This module provides [CompletionModel::batch], which sends a set of completion requests with
+//! a bounded number of requests in flight, for high-throughput pipelines (e.g.: summarizing
+//! thousands of documents).
+//!
+//! Sending the requests one by one is slow, while sending them all at once exceeds the rate
+//! limits of the provider. A [BatchConfig] bounds the number of requests in flight and,
+//! optionally, applies a [RateLimiter] to them (see [rate_limit](crate::rate_limit)).
+//!
+//! The results are returned in the order of the requests: a failed request does not fail the
+//! other requests of the batch.
+//!
+//! Offline workloads which can wait for their results can also use the batch APIs of the
+//! providers, which are cheaper (e.g.: [openai::CompletionModel::run_batch](crate::providers::openai::CompletionModel::run_batch)).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     completion::{batch::BatchConfig, CompletionModel},
+//!     providers::openai,
+//!     rate_limit::{RateLimitConfig, RateLimiter},
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let model = openai.completion_model(openai::GPT_4O_MINI);
+//!
+//! let requests = documents
+//!     .iter()
+//!     .map(|document| {
+//!         model
+//!             .completion_request(format!("Summarize this document:\n{document}"))
+//!             .build()
+//!     })
+//!     .collect();
+//!
+//! let summaries = model
+//!     .batch(
+//!         requests,
+//!         BatchConfig::default()
+//!             .concurrency(16)
+//!             .rate_limit(RateLimiter::new(
+//!                 RateLimitConfig::default().requests_per_minute(500),
+//!             )?),
+//!     )
+//!     .await;
+//! ```
+use futures::{stream, StreamExt};
+
+use crate::rate_limit::RateLimiter;
+
+use super::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse};
+
+/// Configuration of the batches of completion requests sent with [CompletionModel::batch].
+///
+/// #### Default Values
+/// - `concurrency`: 8
+/// - `rate_limiter`: None
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    concurrency: usize,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            rate_limiter: None,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Set the maximum number of requests in flight (at least 1).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Send the requests within the limits of `limiter`. Clones of the limiter used by other
+    /// models of the provider share its limits.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+}
+
+/// Send `requests` to `model` according to `config`, returning the results in the order of
+/// the requests.
+pub(crate) async fn batch<M: CompletionModel>(
+    model: &M,
+    requests: Vec<CompletionRequest>,
+    config: BatchConfig,
+) -> Vec<Result<CompletionResponse<M::Response>, CompletionError>> {
+    match config.rate_limiter {
+        Some(limiter) => {
+            send_all(
+                &model.clone().with_rate_limit(limiter),
+                requests,
+                config.concurrency,
+            )
+            .await
+        }
+        None => send_all(model, requests, config.concurrency).await,
+    }
+}
+
+async fn send_all<M: CompletionModel>(
+    model: &M,
+    requests: Vec<CompletionRequest>,
+    concurrency: usize,
+) -> Vec<Result<CompletionResponse<M::Response>, CompletionError>> {
+    let count = requests.len();
+    let results = stream::iter(requests)
+        .map(|request| model.completion(request))
+        .buffered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    tracing::info!(target: "rig",
+        "Batch of {} completion requests done ({} failed)",
+        count,
+        results.iter().filter(|result| result.is_err()).count()
+    );
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        completion::{CompletionModel, CompletionRequest, CompletionResponse},
+        message::AssistantContent,
+        OneOrMany,
+    };
+
+    use super::{BatchConfig, CompletionError};
+
+    /// Model answering with the prompt of the request, slower for shorter prompts
+    #[derive(Clone, Default)]
+    struct EchoModel {
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl CompletionModel for EchoModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+
+            let prompt = request.prompt.rag_text().unwrap_or_default();
+            tokio::time::sleep(Duration::from_millis(50 - 5 * prompt.len() as u64)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            if prompt == "fail" {
+                return Err(CompletionError::ProviderError("Overloaded".into()));
+            }
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(prompt)),
+                raw_response: (),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let model = EchoModel::default();
+        let prompts = ["a", "bb", "fail", "ccc", "dddd", "eeeee"];
+        let requests = prompts
+            .iter()
+            .map(|prompt| model.completion_request(*prompt).build())
+            .collect();
+
+        let results = model
+            .batch(requests, BatchConfig::default().concurrency(2))
+            .await;
+
+        let texts = results
+            .iter()
+            .map(|result| match result {
+                Ok(response) => match response.choice.first() {
+                    AssistantContent::Text(text) => text.text.clone(),
+                    _ => panic!("Unexpected tool call"),
+                },
+                Err(_) => "fail".to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, prompts);
+        assert!(results[2].is_err());
+        assert_eq!(model.max_in_flight.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,466 @@
+//! This module provides the [CachedCompletionModel] struct, a completion model wrapper which
+//! caches the completions of the requests it sends, so identical requests only hit the
+//! provider once (e.g.: deterministic prompts in test suites and batch jobs).
+//!
+//! Completions are keyed by the name of the model and a SHA-256 hash of the model name and the
+//! request (preamble, chat history, prompt with its rendered documents, documents, tools,
+//! generation parameters and additional parameters). They
+//! are stored in a [CompletionCacheBackend]: in memory by default ([InMemoryCompletionCache]),
+//! or on disk ([DiskCompletionCache]) so they survive restarts. Other storages (e.g.: Redis)
+//! can be used by implementing [CompletionCacheBackend].
+//!
+//! Unlike the [SemanticCache](crate::completion::semantic_cache::SemanticCache), which matches
+//! prompts by meaning, only exactly identical requests are answered from this cache.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::{cache::{CachedCompletionModel, DiskCompletionCache}, Prompt},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let model = CachedCompletionModel::new(openai.completion_model(openai::GPT_4O))
+//!     .backend(DiskCompletionCache::new(".completions_cache")?)
+//!     .ttl(Duration::from_secs(24 * 60 * 60))
+//!     .deterministic_only(true);
+//!
+//! let agent = AgentBuilder::new(model)
+//!     .preamble("You are a helpful assistant.")
+//!     .temperature(0.0)
+//!     .build();
+//!
+//! // The second prompt is answered from the cache
+//! let response = agent.prompt("What is the capital of France?").await?;
+//! let response = agent.prompt("What is the capital of France?").await?;
+//! ```
+//!
+//! # Custom backends
+//! A Redis backend, for instance, only needs to get and set strings:
+//! ```rust
+//! use futures::future::BoxFuture;
+//! use redis::AsyncCommands;
+//! use rig::completion::cache::CompletionCacheBackend;
+//!
+//! struct RedisCompletionCache {
+//!     client: redis::Client,
+//! }
+//!
+//! impl CompletionCacheBackend for RedisCompletionCache {
+//!     fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<String>> {
+//!         Box::pin(async move {
+//!             let mut connection = self.client.get_multiplexed_async_connection().await.ok()?;
+//!             connection.get(key).await.ok()
+//!         })
+//!     }
+//!
+//!     fn insert<'a>(&'a self, key: &'a str, value: &'a str) -> BoxFuture<'a, ()> {
+//!         Box::pin(async move {
+//!             if let Ok(mut connection) = self.client.get_multiplexed_async_connection().await {
+//!                 let _: redis::RedisResult<()> = connection.set(key, value).await;
+//!             }
+//!         })
+//!     }
+//! }
+//! ```
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    embeddings::cache::{cache_key, unblock},
+    OneOrMany,
+};
+
+use super::{
+    cost::ModelPricing, message::AssistantContent, CompletionError, CompletionModel,
+    CompletionRequest, CompletionResponse, ContextTemplate, TokenUsage,
+};
+
+/// Storage of cached completions, keyed by strings made of the model name and a hash of the
+/// request (see [CachedCompletionModel]). Values are JSON strings.
+pub trait CompletionCacheBackend: Send + Sync {
+    /// Get the value stored under `key`, if any.
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<String>>;
+
+    /// Store `value` under `key`. Storage failures should not fail the completion request
+    /// (the completion is simply not cached).
+    fn insert<'a>(&'a self, key: &'a str, value: &'a str) -> BoxFuture<'a, ()>;
+}
+
+/// Backend storing the completions in memory. Clones share the same storage.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryCompletionCache {
+    entries: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl InMemoryCompletionCache {
+    /// Number of cached completions.
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CompletionCacheBackend for InMemoryCompletionCache {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<String>> {
+        let value = self.entries().get(key).cloned();
+        Box::pin(async move { value })
+    }
+
+    fn insert<'a>(&'a self, key: &'a str, value: &'a str) -> BoxFuture<'a, ()> {
+        self.entries().insert(key.to_string(), value.to_string());
+        Box::pin(async {})
+    }
+}
+
+/// Backend storing each completion as a JSON file in a directory.
+#[derive(Clone, Debug)]
+pub struct DiskCompletionCache {
+    dir: PathBuf,
+}
+
+impl DiskCompletionCache {
+    /// Create a backend storing the completions in `dir` (which is created if it does not exist).
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl CompletionCacheBackend for DiskCompletionCache {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<String>> {
+        let path = self.path(key);
+        Box::pin(unblock(move || std::fs::read_to_string(path).ok()))
+    }
+
+    fn insert<'a>(&'a self, key: &'a str, value: &'a str) -> BoxFuture<'a, ()> {
+        let (path, value) = (self.path(key), value.to_string());
+        Box::pin(async move {
+            if let Err(e) = unblock(move || std::fs::write(path, value)).await {
+                tracing::warn!(target: "rig", "Failed to cache completion {}: {}", key, e);
+            }
+        })
+    }
+}
+
+/// A cached completion.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    choice: OneOrMany<AssistantContent>,
+    /// When the completion was cached, in seconds since the Unix epoch
+    created_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Completion model wrapper caching the completions of the requests it sends.
+///
+/// The raw response of a completion is `None` when it is served from the cache, so cached
+/// completions have no token usage (and no cost).
+#[derive(Clone)]
+pub struct CachedCompletionModel<M: CompletionModel> {
+    model: M,
+    backend: Arc<dyn CompletionCacheBackend>,
+    ttl: Option<Duration>,
+    deterministic_only: bool,
+}
+
+impl<M: CompletionModel> CachedCompletionModel<M> {
+    /// Wrap `model` with an in-memory cache of its completions.
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            backend: Arc::new(InMemoryCompletionCache::default()),
+            ttl: None,
+            deterministic_only: false,
+        }
+    }
+
+    /// Store the completions in `backend` instead of in memory.
+    pub fn backend(mut self, backend: impl CompletionCacheBackend + 'static) -> Self {
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    /// Ignore cached completions older than `ttl` (they are replaced by new completions).
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Only cache the completions of deterministic requests (i.e.: with a temperature of 0).
+    pub fn deterministic_only(mut self, deterministic_only: bool) -> Self {
+        self.deterministic_only = deterministic_only;
+        self
+    }
+
+    fn is_cacheable(&self, request: &CompletionRequest) -> bool {
+        !self.deterministic_only || request.temperature == Some(0.0)
+    }
+
+    /// Cache key of a request: the model name (or the type of the model, if it has no name) and
+    /// a hash of the parts of the request which affect the completion (see [cache_key]). The
+    /// documents are keyed as rendered in the prompt by the context template of the request.
+    fn key(&self, request: &CompletionRequest) -> Result<String, CompletionError> {
+        let request = serde_json::to_string(&json!({
+            "preamble": request.preamble,
+            "chat_history": request.chat_history,
+            "prompt": request.prompt_with_context(),
+            "documents": request.documents,
+            "tools": request.tools,
+            "generation": request.generation_config(),
+            "additional_params": request.additional_params,
+        }))?;
+
+        Ok(cache_key(
+            self.model
+                .model_name()
+                .unwrap_or(std::any::type_name::<M>()),
+            &request,
+        ))
+    }
+
+    async fn lookup(&self, key: &str) -> Option<OneOrMany<AssistantContent>> {
+        let entry: CacheEntry = serde_json::from_str(&self.backend.get(key).await?).ok()?;
+
+        match self.ttl {
+            Some(ttl) if now().saturating_sub(entry.created_at) >= ttl.as_secs() => None,
+            _ => Some(entry.choice),
+        }
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for CachedCompletionModel<M> {
+    type Response = Option<M::Response>;
+
+    fn model_name(&self) -> Option<&str> {
+        self.model.model_name()
+    }
+
+    fn context_template(&self) -> ContextTemplate {
+        self.model.context_template()
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        self.model.token_usage(response.as_ref()?)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        self.model.raw_response_json(response.as_ref()?)
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        self.model.pricing()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        if !self.is_cacheable(&request) {
+            let response = self.model.completion(request).await?;
+            return Ok(CompletionResponse {
+                choice: response.choice,
+                raw_response: Some(response.raw_response),
+            });
+        }
+
+        let key = self.key(&request)?;
+        if let Some(choice) = self.lookup(&key).await {
+            tracing::debug!(target: "rig", "Completion cache hit: {}", key);
+            return Ok(CompletionResponse {
+                choice,
+                raw_response: None,
+            });
+        }
+
+        let response = self.model.completion(request).await?;
+
+        let entry = CacheEntry {
+            choice: response.choice.clone(),
+            created_at: now(),
+        };
+        match serde_json::to_string(&entry) {
+            Ok(value) => self.backend.insert(&key, &value).await,
+            Err(e) => tracing::warn!(target: "rig", "Failed to cache completion {}: {}", key, e),
+        }
+
+        Ok(CompletionResponse {
+            choice: response.choice,
+            raw_response: Some(response.raw_response),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Model {
+        completions: Arc<AtomicUsize>,
+    }
+
+    impl CompletionModel for Model {
+        type Response = usize;
+
+        fn model_name(&self) -> Option<&str> {
+            Some("model")
+        }
+
+        fn token_usage(&self, _response: &usize) -> Option<TokenUsage> {
+            Some(TokenUsage::default())
+        }
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<usize>, CompletionError> {
+            let completions = self.completions.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(format!("Hello {completions}"))),
+                raw_response: completions,
+            })
+        }
+    }
+
+    fn text(response: &CompletionResponse<Option<usize>>) -> String {
+        match response.choice.first() {
+            AssistantContent::Text(text) => text.text,
+            _ => panic!("Expected a text completion"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let model = Model::default();
+        let cache = InMemoryCompletionCache::default();
+        let cached_model = CachedCompletionModel::new(model.clone()).backend(cache.clone());
+
+        let response = cached_model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(response.raw_response, Some(1));
+        assert!(cached_model.token_usage(&response.raw_response).is_some());
+
+        // Identical requests are answered from the cache
+        let response = cached_model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(text(&response), "Hello 1");
+        assert_eq!(response.raw_response, None);
+        assert!(cached_model.token_usage(&response.raw_response).is_none());
+
+        // Requests with different parameters are not
+        let response = cached_model
+            .completion_request("Hi")
+            .temperature(0.5)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(text(&response), "Hello 2");
+        assert_eq!(model.completions.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_only() {
+        let model = Model::default();
+        let cached_model = CachedCompletionModel::new(model.clone()).deterministic_only(true);
+
+        for _ in 0..2 {
+            cached_model.completion_request("Hi").send().await.unwrap();
+            cached_model
+                .completion_request("Hi")
+                .temperature(0.0)
+                .send()
+                .await
+                .unwrap();
+        }
+        assert_eq!(model.completions.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_ttl() {
+        let model = Model::default();
+        let cached_model = CachedCompletionModel::new(model.clone()).ttl(Duration::ZERO);
+
+        cached_model.completion_request("Hi").send().await.unwrap();
+        let response = cached_model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(text(&response), "Hello 2");
+    }
+
+    #[tokio::test]
+    async fn test_context_template_key() {
+        use crate::completion::{ContextTemplate, Document};
+
+        let model = Model::default();
+        let cached_model = CachedCompletionModel::new(model.clone());
+        let document = Document {
+            id: "doc".to_string(),
+            text: "Some text".to_string(),
+            additional_props: HashMap::new(),
+        };
+        let request = |prefix: &'static str| {
+            cached_model
+                .completion_request("Hi")
+                .document(document.clone())
+                .context_template(ContextTemplate::formatter(
+                    move |documents: &[Document]| format!("{prefix}{}", documents[0].text),
+                ))
+                .send()
+        };
+
+        // Formatters rendering the documents alike share their completions, others do not
+        request("Context: ").await.unwrap();
+        let response = request("Context: ").await.unwrap();
+        assert_eq!(text(&response), "Hello 1");
+        let response = request("Sources: ").await.unwrap();
+        assert_eq!(text(&response), "Hello 2");
+    }
+
+    #[tokio::test]
+    async fn test_disk_backend() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let model = Model::default();
+
+        let cached_model = CachedCompletionModel::new(model.clone())
+            .backend(DiskCompletionCache::new(dir.path()).unwrap());
+        cached_model.completion_request("Hi").send().await.unwrap();
+
+        // A new cache (e.g.: after a restart) reuses the completions stored on disk
+        let cached_model = CachedCompletionModel::new(model.clone())
+            .backend(DiskCompletionCache::new(dir.path()).unwrap());
+        let response = cached_model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(text(&response), "Hello 1");
+        assert_eq!(model.completions.load(Ordering::SeqCst), 1);
+    }
+}
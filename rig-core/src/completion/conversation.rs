@@ -0,0 +1,313 @@
+//! This module provides the [ConversationBuffer] struct, which holds the history of a
+//! conversation with a [Chat] model or agent and keeps it within a token budget.
+//!
+//! When the history exceeds the budget, the oldest turns are dropped. Alternatively, older turns
+//! can be summarized with [ConversationBuffer::summarize], in which case the summary is kept at
+//! the beginning of the history.
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::conversation::ConversationBuffer, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//!
+//! let mut conversation = ConversationBuffer::new().max_tokens(4000);
+//!
+//! let response = conversation.chat(&agent, "My name is Alice.").await?;
+//! let response = conversation.chat(&agent, "What is my name?").await?;
+//!
+//! // Summarize all but the last 4 messages
+//! conversation.summarize(&agent, 4).await?;
+//! ```
+use super::{
+    template::{ChatTemplate, RoleFormat},
+    tokens::estimate_tokens,
+    Chat, Message, Prompt, PromptError,
+};
+use crate::message::UserContent;
+
+/// History of a conversation, optionally bounded by a number of tokens.
+#[derive(Clone, Debug)]
+pub struct ConversationBuffer {
+    messages: Vec<Message>,
+    summary: Option<String>,
+    max_tokens: Option<usize>,
+    token_counter: fn(&str) -> usize,
+}
+
+impl Default for ConversationBuffer {
+    fn default() -> Self {
+        Self {
+            messages: vec![],
+            summary: None,
+            max_tokens: None,
+            token_counter: estimate_tokens,
+        }
+    }
+}
+
+impl ConversationBuffer {
+    /// Create a new, empty and unbounded conversation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of tokens of the history (including the summary, if any).
+    /// When the history exceeds it, the oldest turns are dropped.
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self.truncate();
+        self
+    }
+
+    /// Set the function counting the tokens of a text (default: an approximation of
+    /// 4 characters per token). Use the tokenizer of the model for exact counts.
+    pub fn token_counter(mut self, token_counter: fn(&str) -> usize) -> Self {
+        self.token_counter = token_counter;
+        self.truncate();
+        self
+    }
+
+    /// Append a message to the conversation, dropping the oldest turns if the history exceeds
+    /// the maximum number of tokens.
+    pub fn push(&mut self, message: impl Into<Message>) {
+        self.messages.push(message.into());
+        self.truncate();
+    }
+
+    /// The messages of the conversation (not including the summary).
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// The summary of the older turns of the conversation, if they were summarized.
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    /// The chat history to send to the model: the summary (if any) followed by the messages.
+    pub fn history(&self) -> Vec<Message> {
+        self.summary
+            .as_ref()
+            .map(|summary| Message::user(summary_text(summary)))
+            .into_iter()
+            .chain(self.messages.iter().cloned())
+            .collect()
+    }
+
+    /// Number of tokens of the history (as counted by the buffer's token counter).
+    pub fn token_count(&self) -> usize {
+        self.summary
+            .as_ref()
+            .map(|summary| (self.token_counter)(&summary_text(summary)))
+            .unwrap_or_default()
+            + self
+                .messages
+                .iter()
+                .map(|message| self.message_tokens(message))
+                .sum::<usize>()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Remove all messages and the summary.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.summary = None;
+    }
+
+    /// Send a prompt with the conversation's history to `chat`, and append the prompt and the
+    /// response to the conversation.
+    pub async fn chat(
+        &mut self,
+        chat: &impl Chat,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        let response = chat.chat(prompt.clone(), self.history()).await?;
+
+        self.messages.push(prompt);
+        self.messages.push(Message::assistant(&response));
+        self.truncate();
+
+        Ok(response)
+    }
+
+    /// Replace all but (about) the last `keep_last` messages with a summary generated by
+    /// `summarizer` (which also summarizes the previous summary, if any). The summarized part
+    /// always ends at a turn boundary, so tool calls are not separated from their results.
+    pub async fn summarize(
+        &mut self,
+        summarizer: &impl Prompt,
+        keep_last: usize,
+    ) -> Result<(), PromptError> {
+        let split = self.turn_start(self.messages.len().saturating_sub(keep_last));
+        if split == 0 {
+            return Ok(());
+        }
+
+        let transcript = transcript_template()
+            .render(None, &self.messages[..split])
+            .trim_end_matches(&transcript_template().assistant.prefix)
+            .to_string();
+
+        let previous = self
+            .summary
+            .as_ref()
+            .map(|summary| format!("Summary of the earlier conversation:\n{summary}\n\n"))
+            .unwrap_or_default();
+
+        let summary = summarizer
+            .prompt(format!(
+                "Summarize the following conversation concisely, keeping all the facts, \
+                decisions and open questions needed to continue it.\n\n{previous}\
+                Conversation:\n{transcript}"
+            ))
+            .await?;
+
+        self.summary = Some(summary);
+        self.messages.drain(..split);
+        Ok(())
+    }
+
+    /// Drop the oldest turns until the history fits in the maximum number of tokens.
+    /// The last turn is always kept.
+    fn truncate(&mut self) {
+        let Some(max_tokens) = self.max_tokens else {
+            return;
+        };
+
+        while self.token_count() > max_tokens {
+            let next_turn = self.turn_start(1);
+            if next_turn >= self.messages.len() {
+                break;
+            }
+            self.messages.drain(..next_turn);
+        }
+    }
+
+    /// Index of the first message at or after `from` starting a turn (i.e.: a user message which
+    /// is not a tool result), or the number of messages if there is none.
+    fn turn_start(&self, from: usize) -> usize {
+        (from..self.messages.len())
+            .find(|i| match &self.messages[*i] {
+                Message::User { content } => !content
+                    .iter()
+                    .any(|content| matches!(content, UserContent::ToolResult(_))),
+                Message::Assistant { .. } => false,
+            })
+            .unwrap_or(self.messages.len())
+    }
+
+    fn message_tokens(&self, message: &Message) -> usize {
+        (self.token_counter)(&ChatTemplate::new().render(None, std::slice::from_ref(message)))
+    }
+}
+
+fn summary_text(summary: &str) -> String {
+    format!("Summary of the conversation so far:\n{summary}")
+}
+
+fn transcript_template() -> ChatTemplate {
+    ChatTemplate::new()
+        .user(RoleFormat::new("User: ", "\n"))
+        .assistant(RoleFormat::new("Assistant: ", "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chat model answering with the number of messages in the history,
+    /// and summarizing conversations with their number of turns
+    struct Counter;
+
+    impl Chat for Counter {
+        async fn chat(
+            &self,
+            _prompt: impl Into<Message> + Send,
+            chat_history: Vec<Message>,
+        ) -> Result<String, PromptError> {
+            Ok(chat_history.len().to_string())
+        }
+    }
+
+    impl Prompt for Counter {
+        async fn prompt(&self, prompt: impl Into<Message> + Send) -> Result<String, PromptError> {
+            match prompt.into() {
+                Message::User { content } => match content.first() {
+                    UserContent::Text(text) => Ok(format!(
+                        "{} turns",
+                        text.text
+                            .lines()
+                            .filter(|line| line.starts_with("User: "))
+                            .count()
+                    )),
+                    _ => unreachable!(),
+                },
+                Message::Assistant { .. } => unreachable!(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat() {
+        let mut conversation = ConversationBuffer::new();
+
+        assert_eq!(conversation.chat(&Counter, "Hi").await.unwrap(), "0");
+        assert_eq!(conversation.chat(&Counter, "Hi").await.unwrap(), "2");
+        assert_eq!(conversation.len(), 4);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut conversation = ConversationBuffer::new()
+            .token_counter(|text| text.split_whitespace().count())
+            .max_tokens(5);
+
+        conversation.push(Message::user("one two"));
+        conversation.push(Message::assistant("three"));
+        assert_eq!(conversation.token_count(), 3);
+
+        conversation.push(Message::user("four five"));
+        conversation.push(Message::assistant("six"));
+
+        // The first turn was dropped
+        assert_eq!(
+            conversation.messages(),
+            &[Message::user("four five"), Message::assistant("six")]
+        );
+
+        // The last turn is kept even if it exceeds the budget
+        conversation.push(Message::user("a b c d e f"));
+        assert_eq!(conversation.messages(), &[Message::user("a b c d e f")]);
+    }
+
+    #[tokio::test]
+    async fn test_summarize() {
+        let mut conversation = ConversationBuffer::new();
+        for text in ["Hi", "Hello", "How are you?", "Fine"] {
+            conversation.push(Message::user(text));
+            conversation.push(Message::assistant(text));
+        }
+
+        conversation.summarize(&Counter, 3).await.unwrap();
+
+        // The last full turn is kept, the 3 previous turns are summarized
+        assert_eq!(conversation.len(), 2);
+        assert_eq!(conversation.summary(), Some("3 turns"));
+        assert_eq!(
+            conversation.history()[0],
+            Message::user("Summary of the conversation so far:\n3 turns")
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! This module provides the [ModelPricing] struct, which holds the price of the tokens of a
+//! completion model, and the [CostTracker] struct, which accumulates the token usage and cost
+//! of completions over a session (e.g.: all completions of an [Agent](crate::agent::Agent),
+//! see [AgentBuilder::cost_tracker](crate::agent::AgentBuilder::cost_tracker)).
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::{cost::CostTracker, Prompt}, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//! let tracker = CostTracker::new();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .cost_tracker(tracker.clone())
+//!     .build();
+//!
+//! agent.prompt("Hello!").await?;
+//! agent.prompt("How are you?").await?;
+//!
+//! let summary = tracker.summary();
+//! println!("{} requests, {} tokens, ${:.4}", summary.requests, summary.usage.total_tokens, summary.cost);
+//! ```
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::TokenUsage;
+
+/// Price of the tokens of a completion model, in US dollars per million tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    /// Price of one million input tokens (prompt, chat history, context and tool definitions)
+    pub input_per_million: f64,
+    /// Price of one million output tokens
+    pub output_per_million: f64,
+    /// Price of one million input tokens read from the prompt cache
+    pub cache_read_per_million: f64,
+    /// Price of one million input tokens written to the prompt cache
+    pub cache_write_per_million: f64,
+}
+
+impl ModelPricing {
+    /// Pricing of a model whose cached input tokens cost as much as the other input tokens
+    /// (see [ModelPricing::with_cache]).
+    pub const fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+            cache_read_per_million: input_per_million,
+            cache_write_per_million: input_per_million,
+        }
+    }
+
+    /// Set the price of the input tokens read from and written to the prompt cache.
+    pub const fn with_cache(self, read_per_million: f64, write_per_million: f64) -> Self {
+        Self {
+            cache_read_per_million: read_per_million,
+            cache_write_per_million: write_per_million,
+            ..self
+        }
+    }
+
+    /// Cost in US dollars of a completion with the given token usage.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let uncached_input_tokens = usage
+            .input_tokens
+            .saturating_sub(usage.cache_read_input_tokens + usage.cache_creation_input_tokens);
+
+        (uncached_input_tokens as f64 * self.input_per_million
+            + usage.cache_read_input_tokens as f64 * self.cache_read_per_million
+            + usage.cache_creation_input_tokens as f64 * self.cache_write_per_million
+            + usage.output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+
+    /// Look up the pricing of `model` in a pricing table of (model name prefix, pricing)
+    /// entries. The first entry whose prefix matches the model name is used, so more
+    /// specific prefixes (e.g.: `gpt-4o-mini`) must come before less specific ones (e.g.: `gpt-4o`).
+    pub fn lookup(table: &[(&str, ModelPricing)], model: &str) -> Option<Self> {
+        table
+            .iter()
+            .find(|(prefix, _)| model.starts_with(prefix))
+            .map(|(_, pricing)| *pricing)
+    }
+}
+
+/// Token usage and cost accumulated by a [CostTracker].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostSummary {
+    /// Number of completions recorded
+    pub requests: usize,
+    /// Total token usage of the completions whose token usage was reported by the provider
+    pub usage: TokenUsage,
+    /// Total cost in US dollars of the completions whose token usage and pricing are known
+    pub cost: f64,
+    /// Number of completions whose cost is unknown (token usage not reported by the provider
+    /// or pricing of the model unknown), hence not included in `cost`
+    pub unpriced_requests: usize,
+}
+
+/// Accumulator of the token usage and cost of completions.
+///
+/// The tracker can be cloned and shared (e.g.: between several agents of a session); clones
+/// accumulate into the same [CostSummary].
+#[derive(Clone, Debug, Default)]
+pub struct CostTracker {
+    summary: Arc<Mutex<CostSummary>>,
+}
+
+impl CostTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completion with the given token usage, made with a model with the given
+    /// pricing. Returns the cost of the completion, if known.
+    pub fn record(&self, usage: Option<TokenUsage>, pricing: Option<ModelPricing>) -> Option<f64> {
+        let cost = usage
+            .zip(pricing)
+            .map(|(usage, pricing)| pricing.cost(&usage));
+
+        let mut summary = self.lock();
+        summary.requests += 1;
+        summary.usage = summary.usage + usage.unwrap_or_default();
+        match cost {
+            Some(cost) => summary.cost += cost,
+            None => summary.unpriced_requests += 1,
+        }
+
+        cost
+    }
+
+    /// Token usage and cost accumulated so far.
+    pub fn summary(&self) -> CostSummary {
+        *self.lock()
+    }
+
+    /// Total cost in US dollars of the completions recorded so far.
+    pub fn total_cost(&self) -> f64 {
+        self.lock().cost
+    }
+
+    /// Take the token usage and cost accumulated so far, resetting the tracker.
+    pub fn take(&self) -> CostSummary {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CostSummary> {
+        self.summary
+            .lock()
+            .expect("Cost tracker lock should not be poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: input_tokens + output_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pricing_lookup() {
+        let table = [
+            ("gpt-4o-mini", ModelPricing::new(0.15, 0.6)),
+            ("gpt-4o", ModelPricing::new(2.5, 10.0)),
+        ];
+
+        assert_eq!(
+            ModelPricing::lookup(&table, "gpt-4o-2024-08-06"),
+            Some(ModelPricing::new(2.5, 10.0))
+        );
+        assert_eq!(
+            ModelPricing::lookup(&table, "gpt-4o-mini"),
+            Some(ModelPricing::new(0.15, 0.6))
+        );
+        assert_eq!(ModelPricing::lookup(&table, "o1"), None);
+    }
+
+    #[test]
+    fn test_cache_pricing() {
+        let pricing = ModelPricing::new(3.0, 15.0).with_cache(0.3, 3.75);
+        let cached = TokenUsage {
+            cache_read_input_tokens: 100_000,
+            cache_creation_input_tokens: 100_000,
+            ..usage(1_000_000, 0)
+        };
+
+        // 800k uncached, 100k read from and 100k written to the cache
+        assert!((pricing.cost(&cached) - (2.4 + 0.03 + 0.375)).abs() < 1e-9);
+        assert_eq!(
+            ModelPricing::new(3.0, 15.0).cost(&cached),
+            ModelPricing::new(3.0, 15.0).cost(&usage(1_000_000, 0))
+        );
+    }
+
+    #[test]
+    fn test_cost_tracker() {
+        let tracker = CostTracker::new();
+        let pricing = ModelPricing::new(2.0, 10.0);
+
+        assert_eq!(
+            tracker
+                .clone()
+                .record(Some(usage(1000, 100)), Some(pricing)),
+            Some(0.003)
+        );
+        assert_eq!(tracker.record(Some(usage(500, 50)), None), None);
+        assert_eq!(tracker.record(None, Some(pricing)), None);
+
+        assert_eq!(
+            tracker.take(),
+            CostSummary {
+                requests: 3,
+                usage: usage(1500, 150),
+                cost: 0.003,
+                unpriced_requests: 2,
+            }
+        );
+        assert_eq!(tracker.summary(), CostSummary::default());
+    }
+}
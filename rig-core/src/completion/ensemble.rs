@@ -0,0 +1,404 @@
+//! This module provides the [EnsembleModel] struct, a completion model that queries several
+//! completion models concurrently and selects one of their responses.
+//!
+//! The response is selected by a [Selector]. The following selectors are provided:
+//! - [First]: Select the response of the first model (all candidates are still available
+//!   in the raw response).
+//! - [MajorityVote]: Select the response given by the models with the highest total weight.
+//! - [Judge]: Ask a judge (e.g.: an [Agent](crate::agent::Agent)) to select the best response.
+//!
+//! Since [EnsembleModel] implements [CompletionModel], it can be used anywhere a completion
+//! model is expected (e.g.: with [AgentBuilder](crate::agent::AgentBuilder)).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::{ensemble::{EnsembleModel, Judge}, Prompt},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let judge = openai.agent(openai::GPT_4O)
+//!     .preamble("You are an impartial judge.")
+//!     .build();
+//!
+//! let ensemble = EnsembleModel::new(Judge::new(judge))
+//!     .model(openai.completion_model(openai::GPT_4O))
+//!     .model(openai.completion_model(openai::GPT_4O_MINI));
+//!
+//! let agent = AgentBuilder::new(ensemble)
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//!
+//! let response = agent.prompt("What is the capital of France?")
+//!     .await
+//!     .expect("Failed to prompt the agent");
+//! ```
+use std::{collections::HashMap, sync::Arc};
+
+use futures::{future, Future};
+
+use crate::{
+    completion::{
+        CompletionError, CompletionModel, CompletionRequest, CompletionResponse, ContextTemplate,
+        Prompt, TokenUsage,
+    },
+    message::AssistantContent,
+    OneOrMany,
+};
+
+/// A candidate response of one of the models of an [EnsembleModel].
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// Weight of the model that generated the candidate
+    pub weight: f64,
+    /// The candidate completion choice
+    pub choice: OneOrMany<AssistantContent>,
+}
+
+impl Candidate {
+    /// Text representation of the candidate (tool calls are represented by their JSON).
+    pub fn text(&self) -> String {
+        self.choice
+            .iter()
+            .map(|content| match content {
+                AssistantContent::Text(text) => text.text.clone(),
+                AssistantContent::ToolCall(tool_call) => {
+                    serde_json::to_string(tool_call).unwrap_or_default()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Trait defining how the response of an [EnsembleModel] is selected among the candidates.
+pub trait Selector: Send + Sync {
+    /// Select a candidate for the given request. Returns the index of the selected candidate.
+    fn select(
+        &self,
+        request: &CompletionRequest,
+        candidates: &[Candidate],
+    ) -> impl Future<Output = Result<usize, CompletionError>> + Send;
+}
+
+/// Selector that always selects the first candidate.
+/// All candidates remain available in the raw [EnsembleResponse].
+#[derive(Clone, Debug, Default)]
+pub struct First;
+
+impl Selector for First {
+    async fn select(
+        &self,
+        _request: &CompletionRequest,
+        _candidates: &[Candidate],
+    ) -> Result<usize, CompletionError> {
+        Ok(0)
+    }
+}
+
+/// Selector that selects the candidate whose text was given by the models with the
+/// highest total weight. Ties are broken in favor of the earliest candidate.
+#[derive(Clone, Debug, Default)]
+pub struct MajorityVote;
+
+impl Selector for MajorityVote {
+    async fn select(
+        &self,
+        _request: &CompletionRequest,
+        candidates: &[Candidate],
+    ) -> Result<usize, CompletionError> {
+        let mut votes: HashMap<String, (usize, f64)> = HashMap::new();
+        candidates.iter().enumerate().for_each(|(i, candidate)| {
+            votes
+                .entry(candidate.text().trim().to_string())
+                .or_insert((i, 0.0))
+                .1 += candidate.weight;
+        });
+
+        Ok(votes
+            .into_values()
+            .max_by(|(i_a, votes_a), (i_b, votes_b)| {
+                votes_a.total_cmp(votes_b).then_with(|| i_b.cmp(i_a))
+            })
+            .map(|(i, _)| i)
+            .unwrap_or_default())
+    }
+}
+
+/// Selector that asks a judge (e.g.: an [Agent](crate::agent::Agent)) to select the best candidate.
+/// The judge is prompted with the request's prompt and the candidates, and must answer with
+/// the number of the best candidate.
+pub struct Judge<P: Prompt> {
+    judge: P,
+}
+
+impl<P: Prompt> Judge<P> {
+    pub fn new(judge: P) -> Self {
+        Self { judge }
+    }
+}
+
+impl<P: Prompt> Selector for Judge<P> {
+    async fn select(
+        &self,
+        request: &CompletionRequest,
+        candidates: &[Candidate],
+    ) -> Result<usize, CompletionError> {
+        let prompt = format!(
+            "Select the best response to the following prompt.\n\n<prompt>\n{}\n</prompt>\n\n{}\n\nAnswer with the number of the best response only.",
+            request.prompt.rag_text().unwrap_or_default(),
+            candidates
+                .iter()
+                .enumerate()
+                .map(|(i, candidate)| format!(
+                    "<response number=\"{i}\">\n{}\n</response>",
+                    candidate.text()
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let answer = self
+            .judge
+            .prompt(prompt)
+            .await
+            .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+
+        answer
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|s| !s.is_empty())
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|i| *i < candidates.len())
+            .ok_or_else(|| {
+                CompletionError::ResponseError(format!(
+                    "Judge did not select a valid response: {answer}"
+                ))
+            })
+    }
+}
+
+/// Raw response of an [EnsembleModel], containing the responses of all models.
+#[derive(Debug)]
+pub struct EnsembleResponse<T> {
+    /// The responses of the models, in the order the models were added to the ensemble
+    pub responses: Vec<CompletionResponse<T>>,
+    /// Index of the selected response
+    pub selected: usize,
+}
+
+impl<T> EnsembleResponse<T> {
+    /// Aggregate a value (e.g.: token usage) over the raw responses of all models.
+    ///
+    /// # Example
+    /// ```rust
+    /// let total_tokens = response.raw_response.aggregate(|raw| {
+    ///     raw.usage.as_ref().map(|usage| usage.total_tokens).unwrap_or_default()
+    /// });
+    /// ```
+    pub fn aggregate<U: std::iter::Sum>(&self, f: impl Fn(&T) -> U) -> U {
+        self.responses
+            .iter()
+            .map(|response| f(&response.raw_response))
+            .sum()
+    }
+}
+
+/// Completion model that queries several completion models concurrently and selects
+/// one of their responses using a [Selector].
+pub struct EnsembleModel<M: CompletionModel, S: Selector> {
+    /// The models of the ensemble with their weight
+    models: Vec<(M, f64)>,
+    selector: Arc<S>,
+}
+
+impl<M: CompletionModel, S: Selector> Clone for EnsembleModel<M, S> {
+    fn clone(&self) -> Self {
+        Self {
+            models: self.models.clone(),
+            selector: self.selector.clone(),
+        }
+    }
+}
+
+impl<M: CompletionModel, S: Selector> EnsembleModel<M, S> {
+    /// Create a new ensemble without models, using `selector` to select the response.
+    pub fn new(selector: S) -> Self {
+        Self {
+            models: vec![],
+            selector: Arc::new(selector),
+        }
+    }
+
+    /// Add a model with a weight of 1.0 to the ensemble.
+    pub fn model(self, model: M) -> Self {
+        self.weighted_model(model, 1.0)
+    }
+
+    /// Add a model with the given weight to the ensemble.
+    pub fn weighted_model(mut self, model: M, weight: f64) -> Self {
+        self.models.push((model, weight));
+        self
+    }
+}
+
+impl<M: CompletionModel, S: Selector> CompletionModel for EnsembleModel<M, S> {
+    type Response = EnsembleResponse<M::Response>;
+
+    /// The template of the first model of the ensemble.
+    fn context_template(&self) -> ContextTemplate {
+        self.models
+            .first()
+            .map(|(model, _)| model.context_template())
+            .unwrap_or_default()
+    }
+
+    /// The total token usage of all models of the ensemble (the token usage of the judge of
+    /// a [Judge] selector is not included).
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        self.models
+            .iter()
+            .zip(&response.responses)
+            .map(|((model, _), response)| model.token_usage(&response.raw_response))
+            .sum()
+    }
+
+    /// The raw responses of all models of the ensemble, as a JSON array.
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        self.models
+            .iter()
+            .zip(&response.responses)
+            .map(|((model, _), response)| model.raw_response_json(&response.raw_response))
+            .collect::<Option<Vec<_>>>()
+            .map(serde_json::Value::Array)
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        if self.models.is_empty() {
+            return Err(CompletionError::ProviderError(
+                "Ensemble does not contain any model".into(),
+            ));
+        }
+
+        let responses = future::try_join_all(
+            self.models
+                .iter()
+                .map(|(model, _)| model.completion(request.clone())),
+        )
+        .await?;
+
+        let candidates = responses
+            .iter()
+            .zip(self.models.iter())
+            .map(|(response, (_, weight))| Candidate {
+                weight: *weight,
+                choice: response.choice.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let selected = self.selector.select(&request, &candidates).await?;
+
+        let choice = candidates
+            .into_iter()
+            .nth(selected)
+            .ok_or_else(|| {
+                CompletionError::ResponseError(format!("Selected response {selected} not found"))
+            })?
+            .choice;
+
+        Ok(CompletionResponse {
+            choice,
+            raw_response: EnsembleResponse {
+                responses,
+                selected,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct Model {
+        answer: &'static str,
+        tokens: u64,
+    }
+
+    impl CompletionModel for Model {
+        type Response = u64;
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<u64>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(self.answer)),
+                raw_response: self.tokens,
+            })
+        }
+    }
+
+    fn model(answer: &'static str, tokens: u64) -> Model {
+        Model { answer, tokens }
+    }
+
+    fn text(choice: &OneOrMany<AssistantContent>) -> String {
+        match choice.first() {
+            AssistantContent::Text(text) => text.text,
+            _ => panic!("Expected text content"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first() {
+        let ensemble = EnsembleModel::new(First)
+            .model(model("Paris", 10))
+            .model(model("Lyon", 20));
+
+        let response = ensemble.completion_request("prompt").send().await.unwrap();
+
+        assert_eq!(text(&response.choice), "Paris");
+        assert_eq!(response.raw_response.selected, 0);
+        assert_eq!(response.raw_response.responses.len(), 2);
+        assert_eq!(response.raw_response.aggregate(|tokens| *tokens), 30);
+    }
+
+    #[tokio::test]
+    async fn test_majority_vote() {
+        let ensemble = EnsembleModel::new(MajorityVote)
+            .model(model("Lyon", 1))
+            .model(model("Paris", 1))
+            .model(model("Paris ", 1));
+
+        let response = ensemble.completion_request("prompt").send().await.unwrap();
+
+        assert_eq!(text(&response.choice), "Paris");
+        assert_eq!(response.raw_response.selected, 1);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_majority_vote() {
+        let ensemble = EnsembleModel::new(MajorityVote)
+            .weighted_model(model("Lyon", 1), 3.0)
+            .model(model("Paris", 1))
+            .model(model("Paris", 1));
+
+        let response = ensemble.completion_request("prompt").send().await.unwrap();
+
+        assert_eq!(text(&response.choice), "Lyon");
+    }
+
+    #[tokio::test]
+    async fn test_empty_ensemble() {
+        let ensemble = EnsembleModel::<Model, _>::new(First);
+
+        assert!(ensemble.completion_request("prompt").send().await.is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! This module provides the [FallbackModel] struct, a completion model wrapper that sends
+//! completion requests to a chain of models, falling back to the next model of the chain when
+//! a model fails with a transient error (e.g.: a provider outage or rate limit).
+//!
+//! Errors which would fail with any model (e.g.: an invalid request) are returned immediately.
+//! When all models of the chain fail, the error of the last model is returned.
+//!
+//! Each model of the chain can itself retry failed requests before falling back to the next
+//! one (see [RetryModel](crate::completion::retry::RetryModel)).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::{retry::RetryConfig, CompletionModel},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! // Retry gpt-4o twice, then fall back to gpt-4o-mini
+//! let model = openai
+//!     .completion_model(openai::GPT_4O)
+//!     .with_retry(RetryConfig::default().max_retries(2))
+//!     .with_fallback(
+//!         openai
+//!             .completion_model(openai::GPT_4O_MINI)
+//!             .with_retry(RetryConfig::default().max_retries(2)),
+//!     );
+//!
+//! let agent = AgentBuilder::new(model)
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//! ```
+use super::{
+    cost::ModelPricing, retry::is_retryable, CompletionError, CompletionModel, CompletionRequest,
+    CompletionResponse, ContextTemplate, TokenUsage,
+};
+
+/// Completion model wrapper falling back to other models of the same type when a completion
+/// request fails with a transient error.
+#[derive(Clone)]
+pub struct FallbackModel<M: CompletionModel> {
+    models: Vec<M>,
+}
+
+impl<M: CompletionModel> FallbackModel<M> {
+    /// Create a fallback chain starting with the `primary` model.
+    pub fn new(primary: M) -> Self {
+        Self {
+            models: vec![primary],
+        }
+    }
+
+    /// Add a model at the end of the fallback chain.
+    pub fn fallback(mut self, model: M) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    fn primary(&self) -> &M {
+        self.models
+            .first()
+            .expect("A fallback chain has at least one model")
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for FallbackModel<M> {
+    type Response = M::Response;
+
+    /// The name of the primary model.
+    fn model_name(&self) -> Option<&str> {
+        self.primary().model_name()
+    }
+
+    /// The template of the primary model.
+    fn context_template(&self) -> ContextTemplate {
+        self.primary().context_template()
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        self.primary().token_usage(response)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        self.primary().raw_response_json(response)
+    }
+
+    /// The pricing of the models of the chain, if they all have the same pricing (since the
+    /// model which answered a request is not known from its response).
+    fn pricing(&self) -> Option<ModelPricing> {
+        let pricing = self.primary().pricing()?;
+        self.models
+            .iter()
+            .all(|model| model.pricing() == Some(pricing))
+            .then_some(pricing)
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let (last, models) = self
+            .models
+            .split_last()
+            .expect("A fallback chain has at least one model");
+
+        for (i, model) in models.iter().enumerate() {
+            match model.completion(request.clone()).await {
+                Err(error) if is_retryable(&error) => {
+                    tracing::warn!(target: "rig",
+                        "Completion request failed, falling back to model {} of the chain: {}",
+                        i + 1,
+                        error
+                    );
+                }
+                result => return result,
+            }
+        }
+
+        last.completion(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::{message::AssistantContent, OneOrMany};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct Model {
+        name: &'static str,
+        error: Option<fn() -> CompletionError>,
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl Model {
+        fn new(name: &'static str, error: Option<fn() -> CompletionError>) -> Self {
+            Self {
+                name,
+                error,
+                attempts: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl CompletionModel for Model {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            match self.error {
+                Some(error) => Err(error()),
+                None => Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text(self.name)),
+                    raw_response: (),
+                }),
+            }
+        }
+    }
+
+    fn overloaded() -> CompletionError {
+        CompletionError::ProviderError("Overloaded".into())
+    }
+
+    fn invalid() -> CompletionError {
+        CompletionError::ResponseError("Invalid response".into())
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let primary = Model::new("primary", Some(overloaded));
+        let secondary = Model::new("secondary", Some(overloaded));
+        let tertiary = Model::new("tertiary", None);
+
+        let model = FallbackModel::new(primary.clone())
+            .fallback(secondary.clone())
+            .fallback(tertiary.clone());
+
+        let response = model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(
+            response.choice,
+            OneOrMany::one(AssistantContent::text("tertiary"))
+        );
+        assert_eq!(primary.attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(secondary.attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_fallback_on_non_retryable_error() {
+        let secondary = Model::new("secondary", None);
+        let model = Model::new("primary", Some(invalid)).with_fallback(secondary.clone());
+
+        let result = model.completion_request("Hi").send().await;
+        assert!(matches!(result, Err(CompletionError::ResponseError(_))));
+        assert_eq!(secondary.attempts.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_all_models_fail() {
+        let model = Model::new("primary", Some(overloaded))
+            .with_fallback(Model::new("secondary", Some(invalid)));
+
+        let result = model.completion_request("Hi").send().await;
+        assert!(matches!(result, Err(CompletionError::ResponseError(_))));
+    }
+}
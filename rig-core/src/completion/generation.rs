@@ -0,0 +1,172 @@
+//! This module provides the [GenerationConfig] struct, which holds the parameters controlling
+//! how a completion model generates its completions: temperature, nucleus sampling (top_p),
+//! maximum number of tokens, stop sequences, seed, frequency and presence penalties, and format
+//! of the answers (see [ResponseFormat]).
+//!
+//! The parameters are provider-agnostic: each provider maps them to its own request format
+//! (e.g.: `stop` is sent as `stop_sequences` to Anthropic and as `stopSequences` to Gemini,
+//! `max_tokens` as `max_completion_tokens` to OpenAI).
+//! Parameters which are not set are not sent, and the provider's defaults apply. Parameters
+//! which a provider does not support are ignored (e.g.: Anthropic has no `seed`).
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::GenerationConfig, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .generation_config(
+//!         GenerationConfig::default()
+//!             .temperature(0.2)
+//!             .top_p(0.9)
+//!             .stop("END")
+//!             .seed(42),
+//!     )
+//!     .build();
+//! ```
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::ResponseFormat;
+
+/// Parameters controlling the generation of completions (see the [module](self) documentation).
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct GenerationConfig {
+    /// Sampling temperature
+    pub temperature: Option<f64>,
+    /// Nucleus sampling: only the tokens within the `top_p` probability mass are considered
+    pub top_p: Option<f64>,
+    /// Maximum number of tokens of the completion
+    pub max_tokens: Option<u64>,
+    /// Sequences at which the model stops generating
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Seed of the sampling, for (best effort) deterministic completions
+    pub seed: Option<u64>,
+    /// Penalty of tokens proportional to their frequency in the text so far
+    pub frequency_penalty: Option<f64>,
+    /// Penalty of tokens which already appear in the text so far
+    pub presence_penalty: Option<f64>,
+    /// Format of the answers (e.g.: JSON matching a schema)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+impl GenerationConfig {
+    /// Set the sampling temperature
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the nucleus sampling probability mass
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set the maximum number of tokens of the completion
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Add a stop sequence
+    pub fn stop(mut self, stop: impl Into<String>) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    /// Set the seed of the sampling
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the frequency penalty
+    pub fn frequency_penalty(mut self, frequency_penalty: f64) -> Self {
+        self.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    /// Set the presence penalty
+    pub fn presence_penalty(mut self, presence_penalty: f64) -> Self {
+        self.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// Set the format of the answers
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
+    }
+
+    /// The parameters which are set, in the format of OpenAI's chat completions API (also used
+    /// by OpenAI-compatible providers).
+    pub(crate) fn openai_params(&self) -> Value {
+        let mut params = Map::new();
+        insert_opt(&mut params, "temperature", self.temperature);
+        insert_opt(&mut params, "top_p", self.top_p);
+        insert_opt(&mut params, "max_tokens", self.max_tokens);
+        if !self.stop.is_empty() {
+            params.insert("stop".to_string(), self.stop.clone().into());
+        }
+        insert_opt(&mut params, "seed", self.seed);
+        insert_opt(&mut params, "frequency_penalty", self.frequency_penalty);
+        insert_opt(&mut params, "presence_penalty", self.presence_penalty);
+        insert_opt(
+            &mut params,
+            "response_format",
+            self.response_format
+                .as_ref()
+                .map(ResponseFormat::openai_param),
+        );
+        Value::Object(params)
+    }
+}
+
+/// Insert `value` in `params` under `key`, if it is set.
+pub(crate) fn insert_opt(
+    params: &mut Map<String, Value>,
+    key: &str,
+    value: Option<impl Into<Value>>,
+) {
+    if let Some(value) = value {
+        params.insert(key.to_string(), value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_openai_params() {
+        assert_eq!(GenerationConfig::default().openai_params(), json!({}));
+
+        let config = GenerationConfig::default()
+            .temperature(0.5)
+            .max_tokens(100)
+            .stop("END")
+            .stop("STOP")
+            .seed(42)
+            .presence_penalty(0.1)
+            .response_format(ResponseFormat::JsonObject);
+
+        assert_eq!(
+            config.openai_params(),
+            json!({
+                "temperature": 0.5,
+                "max_tokens": 100,
+                "stop": ["END", "STOP"],
+                "seed": 42,
+                "presence_penalty": 0.1,
+                "response_format": {"type": "json_object"},
+            })
+        );
+    }
+}
@@ -223,6 +223,30 @@ impl Message {
         }
     }
 
+    /// Helper constructor to make creating user messages with images easier
+    /// (e.g.: to ask a vision model about images).
+    pub fn user_with_images(
+        text: impl Into<String>,
+        images: impl IntoIterator<Item = Image>,
+    ) -> Self {
+        Message::User {
+            content: OneOrMany::many(
+                std::iter::once(UserContent::text(text)).chain(images.into_iter().map(Into::into)),
+            )
+            .expect("There is at least one content"),
+        }
+    }
+
+    /// Whether the message contains images (which are not included in its [rag_text](Message::rag_text)).
+    pub(crate) fn has_images(&self) -> bool {
+        match self {
+            Message::User { content } => content
+                .iter()
+                .any(|content| matches!(content, UserContent::Image(_))),
+            _ => false,
+        }
+    }
+
     /// Helper constructor to make creating assistant messages easier.
     pub fn assistant(text: impl Into<String>) -> Self {
         Message::Assistant {
@@ -329,6 +353,95 @@ impl ToolResultContent {
             detail,
         })
     }
+
+    /// Helper constructor to convert the output of a tool call into tool result content.
+    /// Outputs that are base64 encoded image data URIs (e.g.: `data:image/png;base64,...`),
+    ///  either raw or serialized as a JSON string, are converted to image content so that
+    ///  vision models can see them. Any other output is converted to text content.
+    pub fn from_tool_output(output: impl Into<String>) -> Self {
+        let output = output.into();
+
+        let data_uri = serde_json::from_str::<String>(&output).unwrap_or_else(|_| output.clone());
+
+        match parse_image_data_uri(&data_uri) {
+            Some((media_type, data)) => {
+                ToolResultContent::image(data, Some(ContentFormat::Base64), Some(media_type), None)
+            }
+            None => ToolResultContent::text(output),
+        }
+    }
+
+    /// Convert the content to text for the providers whose tool results can only be text.
+    /// Images are replaced by a placeholder telling the model that the tool returned an image
+    ///  it cannot see, instead of failing the request.
+    pub fn into_text(self) -> String {
+        match self {
+            ToolResultContent::Text(text) => text.text,
+            ToolResultContent::Image(Image { media_type, .. }) => match media_type {
+                Some(media_type) => format!(
+                    "[The tool returned an image ({}) which cannot be shown to this model]",
+                    media_type.to_mime_type()
+                ),
+                None => "[The tool returned an image which cannot be shown to this model]".into(),
+            },
+        }
+    }
+}
+
+impl Image {
+    /// Helper constructor to make creating image content from the URL of an image easier.
+    /// Base64 encoded image data URIs (e.g.: `data:image/png;base64,...`) are converted
+    ///  to base64 image content.
+    pub fn url(url: impl Into<String>) -> Self {
+        let url = url.into();
+
+        match parse_image_data_uri(&url) {
+            Some((media_type, data)) => Image::base64(data, media_type),
+            None => Image {
+                data: url,
+                format: Some(ContentFormat::String),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Helper constructor to make creating image content from base64 encoded data easier.
+    pub fn base64(data: impl Into<String>, media_type: ImageMediaType) -> Self {
+        Image {
+            data: data.into(),
+            format: Some(ContentFormat::Base64),
+            media_type: Some(media_type),
+            detail: None,
+        }
+    }
+
+    /// Set the level of detail with which the model should process the image
+    ///  (supported by OpenAI).
+    pub fn detail(mut self, detail: ImageDetail) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// The image as a URL: the URL of the image, or a base64 encoded data URI for base64
+    ///  image content with a media type.
+    pub fn to_url(&self) -> String {
+        match &self.media_type {
+            Some(media_type)
+                if self.format != Some(ContentFormat::String)
+                    && !self.data.starts_with("data:") =>
+            {
+                format!("data:{};base64,{}", media_type.to_mime_type(), self.data)
+            }
+            _ => self.data.clone(),
+        }
+    }
+}
+
+/// Parse a base64 encoded image data URI into its media type and data.
+fn parse_image_data_uri(uri: &str) -> Option<(ImageMediaType, &str)> {
+    let (mime_type, data) = uri.strip_prefix("data:")?.split_once(";base64,")?;
+
+    Some((ImageMediaType::from_mime_type(mime_type)?, data))
 }
 
 /// Trait for converting between MIME types and media types.
@@ -513,6 +626,12 @@ impl From<Image> for Message {
     }
 }
 
+impl From<Image> for UserContent {
+    fn from(image: Image) -> Self {
+        UserContent::Image(image)
+    }
+}
+
 impl From<Audio> for Message {
     fn from(audio: Audio) -> Self {
         Message::User {
@@ -563,3 +682,71 @@ impl From<MessageError> for CompletionError {
         CompletionError::RequestError(error.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_url() {
+        let image = Image::url("https://example.com/image.png").detail(ImageDetail::High);
+        assert_eq!(image.format, Some(ContentFormat::String));
+        assert_eq!(image.detail, Some(ImageDetail::High));
+        assert_eq!(image.to_url(), "https://example.com/image.png");
+
+        // Data URIs are converted to base64 content, and back
+        let image = Image::url("data:image/png;base64,iVBORw0KGgo=");
+        assert_eq!(image, Image::base64("iVBORw0KGgo=", ImageMediaType::PNG));
+        assert_eq!(image.to_url(), "data:image/png;base64,iVBORw0KGgo=");
+    }
+
+    #[test]
+    fn test_tool_result_content_from_image_output() {
+        let content = ToolResultContent::from_tool_output("data:image/png;base64,iVBORw0KGgo=");
+
+        assert_eq!(
+            content,
+            ToolResultContent::image(
+                "iVBORw0KGgo=",
+                Some(ContentFormat::Base64),
+                Some(ImageMediaType::PNG),
+                None,
+            )
+        );
+
+        // Tools returning a `String` have their output serialized as a JSON string
+        let content =
+            ToolResultContent::from_tool_output("\"data:image/jpeg;base64,/9j/4AAQSkZJRg==\"");
+
+        assert_eq!(
+            content,
+            ToolResultContent::image(
+                "/9j/4AAQSkZJRg==",
+                Some(ContentFormat::Base64),
+                Some(ImageMediaType::JPEG),
+                None,
+            )
+        );
+    }
+
+    #[test]
+    fn test_tool_result_content_into_text() {
+        assert_eq!(ToolResultContent::text("42").into_text(), "42");
+        assert_eq!(
+            ToolResultContent::from_tool_output("data:image/png;base64,iVBORw0KGgo=").into_text(),
+            "[The tool returned an image (image/png) which cannot be shown to this model]"
+        );
+    }
+
+    #[test]
+    fn test_tool_result_content_from_text_output() {
+        assert_eq!(
+            ToolResultContent::from_tool_output("42"),
+            ToolResultContent::text("42")
+        );
+        assert_eq!(
+            ToolResultContent::from_tool_output("\"data:text/plain;base64,aGVsbG8=\""),
+            ToolResultContent::text("\"data:text/plain;base64,aGVsbG8=\"")
+        );
+    }
+}
@@ -1,5 +1,20 @@
+pub mod batch;
+pub mod cache;
+pub mod conversation;
+pub mod cost;
+pub mod ensemble;
+pub mod fallback;
+pub mod generation;
 pub mod message;
+pub mod provider;
 pub mod request;
+pub mod response_format;
+pub mod retry;
+pub mod semantic_cache;
+pub mod template;
+pub mod tokens;
 
+pub use generation::GenerationConfig;
 pub use message::{AssistantContent, Message, MessageError};
 pub use request::*;
+pub use response_format::ResponseFormat;
@@ -0,0 +1,230 @@
+//! This module provides the [CompletionProvider] trait, which simplifies adding support for
+//! a new completion model provider.
+//!
+//! A provider implementing [CompletionProvider] only has to define:
+//! - How a provider-neutral [CompletionRequest] (messages, parameters, tools) is translated
+//!   into the provider's request ([CompletionProvider::create_request]),
+//! - How the request is sent to the provider ([CompletionProvider::send_request]),
+//! - How the provider's response is translated into a provider-neutral [CompletionResponse]
+//!   ([CompletionProvider::parse_response]).
+//!
+//! [CompletionModel] is then implemented for the provider automatically.
+//!
+//! The OpenAI provider ([openai::CompletionModel](crate::providers::openai::CompletionModel))
+//! is the reference implementation.
+//!
+//! # Example
+//! ```rust
+//! use rig::completion::{
+//!     provider::{CompletionProvider, RequestOptions},
+//!     CompletionError, CompletionRequest, CompletionResponse,
+//! };
+//!
+//! #[derive(Clone)]
+//! struct MyModel {
+//!     client: reqwest::Client,
+//! }
+//!
+//! impl CompletionProvider for MyModel {
+//!     type Request = serde_json::Value;
+//!     type Response = MyResponse;
+//!
+//!     fn create_request(
+//!         &self,
+//!         request: CompletionRequest,
+//!     ) -> Result<Self::Request, CompletionError> {
+//!         // Translate the request into the provider's format
+//!     }
+//!
+//!     async fn send_request(
+//!         &self,
+//!         request: Self::Request,
+//!         options: RequestOptions,
+//!     ) -> Result<Self::Response, CompletionError> {
+//!         // Send the request to the provider
+//!     }
+//!
+//!     fn parse_response(
+//!         &self,
+//!         response: Self::Response,
+//!     ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+//!         // Translate the provider's response
+//!     }
+//! }
+//! ```
+use futures::Future;
+
+use crate::rate_limit::{acquire_completion, RateLimiter};
+
+use super::{
+    cost::ModelPricing, CompletionError, CompletionModel, CompletionRequest, CompletionResponse,
+    ContextTemplate, TokenUsage,
+};
+
+/// Provider-neutral options of a completion request that are not part of the request body
+/// (e.g.: HTTP headers).
+#[derive(Clone, Debug, Default)]
+pub struct RequestOptions {
+    /// Key identifying the logical request, used by providers that support it
+    /// to deduplicate retries of the same request
+    pub idempotency_key: Option<String>,
+}
+
+impl From<&CompletionRequest> for RequestOptions {
+    fn from(request: &CompletionRequest) -> Self {
+        Self {
+            idempotency_key: request.idempotency_key.clone(),
+        }
+    }
+}
+
+/// Trait defining a completion model provider in terms of request/response translation
+/// and the call to the provider. Any type implementing this trait implements [CompletionModel].
+pub trait CompletionProvider: Clone + Send + Sync {
+    /// The provider-specific request type.
+    type Request: Send;
+    /// The provider-specific (raw) response type.
+    type Response: Send + Sync;
+
+    /// Translate a provider-neutral completion request into the provider's request.
+    fn create_request(&self, request: CompletionRequest) -> Result<Self::Request, CompletionError>;
+
+    /// Send a request to the provider and return its raw response.
+    fn send_request(
+        &self,
+        request: Self::Request,
+        options: RequestOptions,
+    ) -> impl Future<Output = Result<Self::Response, CompletionError>> + Send;
+
+    /// Translate the provider's raw response into a provider-neutral completion response.
+    fn parse_response(
+        &self,
+        response: Self::Response,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError>;
+
+    /// The name of the provider's model, if known.
+    fn model_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// The template used by default to render context documents for this provider's models.
+    fn context_template(&self) -> ContextTemplate {
+        ContextTemplate::Xml
+    }
+
+    /// The token usage reported in a raw response of the provider, if any.
+    fn token_usage(&self, _response: &Self::Response) -> Option<TokenUsage> {
+        None
+    }
+
+    /// A raw response of the provider as JSON, if it can be serialized.
+    fn raw_response_json(&self, _response: &Self::Response) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// The price of the tokens of the provider's model, if known.
+    fn pricing(&self) -> Option<ModelPricing> {
+        None
+    }
+
+    /// The rate limiter of the provider's client, if any (see [rate_limit](crate::rate_limit)).
+    fn rate_limiter(&self) -> Option<&RateLimiter> {
+        None
+    }
+}
+
+impl<P: CompletionProvider> CompletionModel for P {
+    type Response = P::Response;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        acquire_completion(self.rate_limiter(), &request).await?;
+
+        let options = RequestOptions::from(&request);
+        let request = self.create_request(request)?;
+        let response = self.send_request(request, options).await?;
+        self.parse_response(response)
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        CompletionProvider::model_name(self)
+    }
+
+    fn context_template(&self) -> ContextTemplate {
+        CompletionProvider::context_template(self)
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        CompletionProvider::token_usage(self, response)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        CompletionProvider::raw_response_json(self, response)
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        CompletionProvider::pricing(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{message::AssistantContent, OneOrMany};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct EchoProvider;
+
+    impl CompletionProvider for EchoProvider {
+        type Request = (String, Option<f64>);
+        type Response = String;
+
+        fn create_request(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<Self::Request, CompletionError> {
+            Ok((
+                request.prompt.rag_text().unwrap_or_default(),
+                request.temperature,
+            ))
+        }
+
+        async fn send_request(
+            &self,
+            (prompt, temperature): Self::Request,
+            options: RequestOptions,
+        ) -> Result<Self::Response, CompletionError> {
+            Ok(format!(
+                "{prompt} {temperature:?} {:?}",
+                options.idempotency_key
+            ))
+        }
+
+        fn parse_response(
+            &self,
+            response: Self::Response,
+        ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(&response)),
+                raw_response: response,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_completion() {
+        let response = EchoProvider
+            .completion_request("Hello")
+            .temperature(0.5)
+            .idempotency_key("key")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.raw_response, "Hello Some(0.5) Some(\"key\")");
+    }
+}
@@ -62,20 +62,38 @@
 //!
 //! For more information on how to use the completion functionality, refer to the documentation of
 //! the individual traits, structs, and enums defined in this module.
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::Instrument;
+use web_time::Instant;
 
 use crate::streaming::{StreamingCompletionModel, StreamingResult};
 use crate::OneOrMany;
 use crate::{
+    cancel::Cancelled,
+    error::ApiError,
     json_utils,
     message::{Message, UserContent},
-    tool::ToolSetError,
+    moderation::{GuardrailViolation, ModerationError},
+    prompt::PromptTemplate,
+    rate_limit::{RateLimitedModel, RateLimiter},
+    session::SessionError,
+    telemetry,
+    tool::{ToolError, ToolSetError},
 };
 
-use super::message::AssistantContent;
+use super::{
+    batch::BatchConfig,
+    cache::CachedCompletionModel,
+    cost::ModelPricing,
+    fallback::FallbackModel,
+    generation::GenerationConfig,
+    message::AssistantContent,
+    response_format::ResponseFormat,
+    retry::{RetryConfig, RetryModel},
+};
 
 // Errors
 #[derive(Debug, Error)]
@@ -99,6 +117,29 @@ pub enum CompletionError {
     /// Error returned by the completion model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// The arguments of a tool call of the response are invalid, e.g.: incomplete because the
+    /// response was cut off by `max_tokens` (see [ToolError::IncompleteArguments])
+    #[error("ToolCallError: tool call {name}: {error}")]
+    ToolCallError {
+        name: String,
+        #[source]
+        error: ToolError,
+    },
+
+    /// Error returned by the completion model provider, with its status and code (see
+    /// [rig::Error](crate::Error) to classify it)
+    #[error("ApiError: {0}")]
+    ApiError(#[from] ApiError),
+
+    /// The completion request did not complete in time (see [RetryConfig::request_timeout](crate::completion::retry::RetryConfig::request_timeout))
+    #[error("TimeoutError: request timed out after {0:?}")]
+    TimeoutError(std::time::Duration),
+
+    /// The request was shed by a [RateLimiter](crate::rate_limit::RateLimiter), and could be
+    /// sent after the given delay
+    #[error("RateLimitError: rate limit exceeded, retry after {0:?}")]
+    RateLimitError(std::time::Duration),
 }
 
 #[derive(Debug, Error)]
@@ -108,9 +149,48 @@ pub enum PromptError {
 
     #[error("ToolCallError: {0}")]
     ToolError(#[from] ToolSetError),
+
+    /// The model was still calling tools after the maximum number of turns. Contains the chat
+    /// history of the run so far (including the prompt, the tool calls and their results), ending
+    /// with the tool calls of the model that were not executed.
+    #[error("MaxTurnsError: no final answer after {max_turns} turns")]
+    MaxTurnsError {
+        max_turns: usize,
+        chat_history: Vec<Message>,
+    },
+
+    /// The run was stopped by a hook of the agent (see [AgentHook](crate::hook::AgentHook))
+    #[error("HookRejection: {0}")]
+    HookRejection(String),
+
+    /// The prompt or the answer was flagged by a moderation model guarding the agent (see
+    /// [AgentBuilder::with_input_guard](crate::agent::AgentBuilder::with_input_guard))
+    #[error("GuardrailViolation: {0}")]
+    GuardrailViolation(#[from] GuardrailViolation),
+
+    #[error("ModerationError: {0}")]
+    ModerationError(#[from] ModerationError),
+
+    /// The answer could not be deserialized into the requested type (see
+    /// [Agent::prompt_typed](crate::agent::Agent::prompt_typed)). Contains the answer.
+    #[error("StructuredOutputError: {source} in answer {text:?}")]
+    StructuredOutputError {
+        text: String,
+        source: serde_json::Error,
+    },
+
+    /// The chat history of a session could not be loaded or saved (see
+    /// [Agent::chat_with_session](crate::agent::Agent::chat_with_session))
+    #[error("SessionError: {0}")]
+    SessionError(#[from] SessionError),
+
+    /// The run was aborted by its cancellation token (see
+    /// [Agent::cancellable](crate::agent::Agent::cancellable))
+    #[error("Cancelled: {0}")]
+    Cancelled(#[from] Cancelled),
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Document {
     pub id: String,
     pub text: String,
@@ -140,6 +220,154 @@ impl std::fmt::Display for Document {
     }
 }
 
+/// Format used to render the context documents of a completion request into the prompt.
+///
+/// Model families follow different conventions: e.g. Anthropic models work best with XML tags
+/// while OpenAI models work best with markdown. Each [CompletionModel] selects a default
+/// template (see [CompletionModel::context_template]), which can be overridden per request.
+#[derive(Clone, Default)]
+pub enum ContextTemplate {
+    /// Documents wrapped in XML tags (e.g.: `<file id: doc1>...</file>`)
+    #[default]
+    Xml,
+    /// Documents as markdown sections
+    Markdown,
+    /// Documents rendered by a [PromptTemplate], with a `documents` variable listing the
+    /// documents (each with an `id`, a `text` and a `metadata` object). If the template fails
+    /// to render, the documents are rendered with [ContextTemplate::Xml] instead.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::{completion::ContextTemplate, prompt::PromptTemplate};
+    ///
+    /// let template = ContextTemplate::Custom(PromptTemplate::new(
+    ///     "{{#each documents}}[{{@index}}] {{text}} (source: {{metadata.url}})\n{{/each}}",
+    /// )?);
+    /// ```
+    Custom(PromptTemplate),
+    /// Documents rendered by a [ContextFormatter] (e.g.: a closure), for full control of the
+    /// rendering (e.g.: citation markers, source URLs or truncation of long documents).
+    /// See [ContextTemplate::formatter].
+    Formatter(Arc<dyn ContextFormatter>),
+}
+
+impl std::fmt::Debug for ContextTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContextTemplate::Xml => write!(f, "Xml"),
+            ContextTemplate::Markdown => write!(f, "Markdown"),
+            ContextTemplate::Custom(template) => f.debug_tuple("Custom").field(template).finish(),
+            ContextTemplate::Formatter(formatter) => {
+                write!(f, "Formatter({:p})", Arc::as_ptr(formatter))
+            }
+        }
+    }
+}
+
+impl PartialEq for ContextTemplate {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ContextTemplate::Xml, ContextTemplate::Xml) => true,
+            (ContextTemplate::Markdown, ContextTemplate::Markdown) => true,
+            (ContextTemplate::Custom(a), ContextTemplate::Custom(b)) => a == b,
+            (ContextTemplate::Formatter(a), ContextTemplate::Formatter(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl ContextTemplate {
+    /// Render the documents with `formatter` (see [ContextTemplate::Formatter]).
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::completion::ContextTemplate;
+    ///
+    /// // Numbered citation markers, with the source URL of the documents
+    /// let template = ContextTemplate::formatter(|documents: &[Document]| {
+    ///     documents
+    ///         .iter()
+    ///         .enumerate()
+    ///         .map(|(i, doc)| match doc.additional_props.get("url") {
+    ///             Some(url) => format!("[{}] {} ({})\n", i + 1, doc.text, url),
+    ///             None => format!("[{}] {}\n", i + 1, doc.text),
+    ///         })
+    ///         .collect::<String>()
+    /// });
+    /// ```
+    pub fn formatter(formatter: impl ContextFormatter + 'static) -> Self {
+        ContextTemplate::Formatter(Arc::new(formatter))
+    }
+
+    /// Render context documents using the template.
+    pub fn render(&self, documents: &[Document]) -> String {
+        match self {
+            ContextTemplate::Xml => format!(
+                "<attachments>\n{}</attachments>",
+                documents
+                    .iter()
+                    .map(|doc| doc.to_string())
+                    .collect::<Vec<_>>()
+                    .join("")
+            ),
+            ContextTemplate::Markdown => format!(
+                "# Attachments\n\n{}",
+                documents
+                    .iter()
+                    .map(|doc| {
+                        let mut sorted_props = doc.additional_props.iter().collect::<Vec<_>>();
+                        sorted_props.sort_by(|a, b| a.0.cmp(b.0));
+                        let metadata = sorted_props
+                            .iter()
+                            .map(|(k, v)| format!("- {k}: {v}\n"))
+                            .collect::<String>();
+
+                        match metadata.is_empty() {
+                            true => format!("## {}\n\n{}\n", doc.id, doc.text),
+                            false => format!("## {}\n\n{}\n{}\n", doc.id, metadata, doc.text),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            ContextTemplate::Custom(template) => {
+                let variables = serde_json::json!({
+                    "documents": documents
+                        .iter()
+                        .map(|doc| serde_json::json!({
+                            "id": doc.id,
+                            "text": doc.text,
+                            "metadata": doc.additional_props,
+                        }))
+                        .collect::<Vec<_>>(),
+                });
+
+                template.render(&variables).unwrap_or_else(|e| {
+                    tracing::warn!(target: "rig", "Failed to render the context documents: {}", e);
+                    ContextTemplate::Xml.render(documents)
+                })
+            }
+            ContextTemplate::Formatter(formatter) => formatter.format(documents),
+        }
+    }
+}
+
+/// Renders the context documents of a completion request into the prompt
+/// (see [ContextTemplate::Formatter]). Implemented by closures taking the documents.
+pub trait ContextFormatter: Send + Sync {
+    /// Render the documents, in order of relevance when they are retrieved from a vector store.
+    fn format(&self, documents: &[Document]) -> String;
+}
+
+impl<F> ContextFormatter for F
+where
+    F: Fn(&[Document]) -> String + Send + Sync,
+{
+    fn format(&self, documents: &[Document]) -> String {
+        self(documents)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ToolDefinition {
     pub name: String,
@@ -214,6 +442,45 @@ pub struct CompletionResponse<T> {
     pub raw_response: T,
 }
 
+/// Token usage of a completion, as reported by the completion model provider.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Number of tokens of the request (prompt, chat history, context and tool definitions)
+    pub input_tokens: u64,
+    /// Number of tokens generated by the model
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    /// Number of input tokens read from the prompt cache of the provider, included in
+    /// `input_tokens`
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+    /// Number of input tokens written to the prompt cache of the provider, included in
+    /// `input_tokens`
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+}
+
+impl std::ops::Add for TokenUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            input_tokens: self.input_tokens + other.input_tokens,
+            output_tokens: self.output_tokens + other.output_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+            cache_read_input_tokens: self.cache_read_input_tokens + other.cache_read_input_tokens,
+            cache_creation_input_tokens: self.cache_creation_input_tokens
+                + other.cache_creation_input_tokens,
+        }
+    }
+}
+
+impl std::iter::Sum for TokenUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |acc, usage| acc + usage)
+    }
+}
+
 /// Trait defining a completion model that can be used to generate completion responses.
 /// This trait is meant to be implemented by the user to define a custom completion model,
 /// either from a third party provider (e.g.: OpenAI) or a local model.
@@ -228,13 +495,96 @@ pub trait CompletionModel: Clone + Send + Sync {
     ) -> impl std::future::Future<Output = Result<CompletionResponse<Self::Response>, CompletionError>>
            + Send;
 
+    /// The name of the model (e.g.: `gpt-4o`), if known. Used to annotate the telemetry
+    /// of completion requests (see [telemetry](crate::telemetry)).
+    fn model_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// The template used by default to render context documents for this model.
+    fn context_template(&self) -> ContextTemplate {
+        ContextTemplate::Xml
+    }
+
+    /// The token usage reported in a raw response of this model, if the provider reports it.
+    fn token_usage(&self, _response: &Self::Response) -> Option<TokenUsage> {
+        None
+    }
+
+    /// A raw response of this model as JSON, if it can be serialized (e.g.: to record the
+    /// payloads of the provider in [traces](crate::trace)).
+    fn raw_response_json(&self, _response: &Self::Response) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// The price of the tokens of this model, if known.
+    fn pricing(&self) -> Option<ModelPricing> {
+        None
+    }
+
+    /// The cost in US dollars of a completion of this model, if its token usage and the
+    /// pricing of the model are known.
+    fn cost(&self, response: &Self::Response) -> Option<f64> {
+        self.token_usage(response)
+            .zip(self.pricing())
+            .map(|(usage, pricing)| pricing.cost(&usage))
+    }
+
     /// Generates a completion request builder for the given `prompt`.
     fn completion_request(&self, prompt: impl Into<Message>) -> CompletionRequestBuilder<Self> {
         CompletionRequestBuilder::new(self.clone(), prompt)
     }
+
+    /// Send `requests` with at most `config.concurrency` requests in flight, within the
+    /// limits of the rate limiter of `config` if any (see [batch](super::batch)). The results
+    /// are returned in the order of the requests.
+    fn batch(
+        &self,
+        requests: Vec<CompletionRequest>,
+        config: BatchConfig,
+    ) -> impl std::future::Future<
+        Output = Vec<Result<CompletionResponse<Self::Response>, CompletionError>>,
+    > + Send {
+        super::batch::batch(self, requests, config)
+    }
+
+    /// Wrap the model to retry failed completion requests according to `config`
+    /// (see [RetryModel]).
+    fn with_retry(self, config: RetryConfig) -> RetryModel<Self> {
+        RetryModel::new(self, config)
+    }
+
+    /// Wrap the model to cache the completions of identical requests in memory
+    /// (see [CachedCompletionModel]).
+    fn with_cache(self) -> CachedCompletionModel<Self> {
+        CachedCompletionModel::new(self)
+    }
+
+    /// Wrap the model to fall back to `fallback` when a completion request fails with a
+    /// transient error (see [FallbackModel]).
+    fn with_fallback(self, fallback: Self) -> FallbackModel<Self> {
+        FallbackModel::new(self).fallback(fallback)
+    }
+
+    /// Wrap the model to send its completion requests within the limits of `limiter`
+    /// (see [RateLimitedModel]).
+    fn with_rate_limit(self, limiter: RateLimiter) -> RateLimitedModel<Self> {
+        RateLimitedModel::new(self, limiter)
+    }
+}
+
+/// Trait for provider clients creating completion models by name, so the model of an agent can
+/// be chosen at runtime (e.g.: from an [AgentConfig](crate::agent::AgentConfig)).
+pub trait CompletionClient: Send + Sync {
+    /// The type of the completion models of the client.
+    type Model: CompletionModel;
+
+    /// Create the completion model with the given name (e.g.: `gpt-4o`).
+    fn completion_model(&self, model: &str) -> Self::Model;
 }
 
 /// Struct representing a general completion request that can be sent to a completion model provider.
+#[derive(Clone)]
 pub struct CompletionRequest {
     /// The prompt to be sent to the completion model provider
     pub prompt: Message,
@@ -250,8 +600,31 @@ pub struct CompletionRequest {
     pub temperature: Option<f64>,
     /// The max tokens to be sent to the completion model provider
     pub max_tokens: Option<u64>,
+    /// The nucleus sampling probability mass to be sent to the completion model provider
+    pub top_p: Option<f64>,
+    /// The stop sequences to be sent to the completion model provider
+    pub stop: Vec<String>,
+    /// The sampling seed to be sent to the completion model provider
+    pub seed: Option<u64>,
+    /// The frequency penalty to be sent to the completion model provider
+    pub frequency_penalty: Option<f64>,
+    /// The presence penalty to be sent to the completion model provider
+    pub presence_penalty: Option<f64>,
+    /// The format of the answer to be sent to the completion model provider
+    pub response_format: Option<ResponseFormat>,
     /// Additional provider-specific parameters to be sent to the completion model provider
     pub additional_params: Option<serde_json::Value>,
+    /// Key identifying the logical request, used by providers that support it (e.g.: OpenAI)
+    /// to deduplicate retries of the same request
+    pub idempotency_key: Option<String>,
+    /// Whether the provider should store the completion, sent by providers that support it
+    /// (e.g.: OpenAI's dashboard)
+    pub store: Option<bool>,
+    /// Metadata attached to the stored completion, sent by providers that support it
+    /// (e.g.: OpenAI)
+    pub metadata: Option<HashMap<String, String>>,
+    /// The template used to render the documents into the prompt
+    pub context_template: ContextTemplate,
 }
 
 impl CompletionRequest {
@@ -259,13 +632,7 @@ impl CompletionRequest {
         let mut new_prompt = self.prompt.clone();
         if let Message::User { ref mut content } = new_prompt {
             if !self.documents.is_empty() {
-                let attachments = self
-                    .documents
-                    .iter()
-                    .map(|doc| doc.to_string())
-                    .collect::<Vec<_>>()
-                    .join("");
-                let formatted_content = format!("<attachments>\n{}</attachments>", attachments);
+                let formatted_content = self.context_template.render(&self.documents);
                 let mut new_content = vec![UserContent::text(formatted_content)];
                 new_content.extend(content.clone());
                 *content = OneOrMany::many(new_content).expect("This has more than 1 item");
@@ -273,6 +640,20 @@ impl CompletionRequest {
         }
         new_prompt
     }
+
+    /// The generation parameters of the request (temperature, top_p, max tokens, etc.).
+    pub fn generation_config(&self) -> GenerationConfig {
+        GenerationConfig {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+            stop: self.stop.clone(),
+            seed: self.seed,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            response_format: self.response_format.clone(),
+        }
+    }
 }
 
 /// Builder struct for constructing a completion request.
@@ -326,9 +707,12 @@ pub struct CompletionRequestBuilder<M: CompletionModel> {
     chat_history: Vec<Message>,
     documents: Vec<Document>,
     tools: Vec<ToolDefinition>,
-    temperature: Option<f64>,
-    max_tokens: Option<u64>,
+    generation: GenerationConfig,
     additional_params: Option<serde_json::Value>,
+    idempotency_key: Option<String>,
+    store: Option<bool>,
+    metadata: Option<HashMap<String, String>>,
+    context_template: Option<ContextTemplate>,
 }
 
 impl<M: CompletionModel> CompletionRequestBuilder<M> {
@@ -340,9 +724,12 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
             chat_history: Vec::new(),
             documents: Vec::new(),
             tools: Vec::new(),
-            temperature: None,
-            max_tokens: None,
+            generation: GenerationConfig::default(),
             additional_params: None,
+            idempotency_key: None,
+            store: None,
+            metadata: None,
+            context_template: None,
         }
     }
 
@@ -420,48 +807,177 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 
     /// Sets the temperature for the completion request.
     pub fn temperature(mut self, temperature: f64) -> Self {
-        self.temperature = Some(temperature);
+        self.generation.temperature = Some(temperature);
         self
     }
 
     /// Sets the temperature for the completion request.
     pub fn temperature_opt(mut self, temperature: Option<f64>) -> Self {
-        self.temperature = temperature;
+        self.generation.temperature = temperature;
         self
     }
 
     /// Sets the max tokens for the completion request.
     /// Note: This is required if using Anthropic
     pub fn max_tokens(mut self, max_tokens: u64) -> Self {
-        self.max_tokens = Some(max_tokens);
+        self.generation.max_tokens = Some(max_tokens);
         self
     }
 
     /// Sets the max tokens for the completion request.
     /// Note: This is required if using Anthropic
     pub fn max_tokens_opt(mut self, max_tokens: Option<u64>) -> Self {
-        self.max_tokens = max_tokens;
+        self.generation.max_tokens = max_tokens;
+        self
+    }
+
+    /// Sets the nucleus sampling probability mass for the completion request.
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.generation.top_p = Some(top_p);
+        self
+    }
+
+    /// Adds a stop sequence to the completion request.
+    pub fn stop(mut self, stop: impl Into<String>) -> Self {
+        self.generation.stop.push(stop.into());
+        self
+    }
+
+    /// Sets the sampling seed for the completion request.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.generation.seed = Some(seed);
+        self
+    }
+
+    /// Sets the frequency penalty for the completion request.
+    pub fn frequency_penalty(mut self, frequency_penalty: f64) -> Self {
+        self.generation.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    /// Sets the presence penalty for the completion request.
+    pub fn presence_penalty(mut self, presence_penalty: f64) -> Self {
+        self.generation.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// Sets the format of the answer (e.g.: JSON matching a schema, see [ResponseFormat]).
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.generation.response_format = Some(response_format);
+        self
+    }
+
+    /// Sets all the generation parameters (temperature, top_p, max tokens, etc.) of the
+    /// completion request, replacing the ones previously set.
+    pub fn generation_config(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Sets the idempotency key for the completion request.
+    /// Providers that support it (e.g.: OpenAI) deduplicate requests sent with the same key,
+    /// so retrying a request with the same key will not be billed twice.
+    /// Use a unique key per logical request and reuse it when retrying that request.
+    /// [RetryModel] generates a key for the requests sent without one.
+    pub fn idempotency_key(mut self, idempotency_key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(idempotency_key.into());
+        self
+    }
+
+    /// Sets the idempotency key for the completion request.
+    pub fn idempotency_key_opt(mut self, idempotency_key: Option<String>) -> Self {
+        self.idempotency_key = idempotency_key;
+        self
+    }
+
+    /// Sets whether the provider should store the completion (e.g.: to make it available in
+    /// OpenAI's dashboard). Ignored by the providers that do not support it.
+    pub fn store(mut self, store: bool) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Sets whether the provider should store the completion, if any.
+    pub fn store_opt(mut self, store: Option<bool>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Sets the metadata attached to the stored completion (e.g.: to filter the completions
+    /// in OpenAI's dashboard). Ignored by the providers that do not support it.
+    pub fn metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Sets the metadata attached to the stored completion, if any.
+    pub fn metadata_opt(mut self, metadata: Option<HashMap<String, String>>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets the template used to render the documents into the prompt, overriding the
+    /// default template of the model (see [CompletionModel::context_template]).
+    pub fn context_template(mut self, context_template: ContextTemplate) -> Self {
+        self.context_template = Some(context_template);
+        self
+    }
+
+    /// Sets the template used to render the documents into the prompt, if any.
+    pub fn context_template_opt(mut self, context_template: Option<ContextTemplate>) -> Self {
+        self.context_template = context_template;
         self
     }
 
     /// Builds the completion request.
     pub fn build(self) -> CompletionRequest {
+        let context_template = self
+            .context_template
+            .unwrap_or_else(|| self.model.context_template());
+
         CompletionRequest {
             prompt: self.prompt,
             preamble: self.preamble,
             chat_history: self.chat_history,
             documents: self.documents,
             tools: self.tools,
-            temperature: self.temperature,
-            max_tokens: self.max_tokens,
+            temperature: self.generation.temperature,
+            max_tokens: self.generation.max_tokens,
+            top_p: self.generation.top_p,
+            stop: self.generation.stop,
+            seed: self.generation.seed,
+            frequency_penalty: self.generation.frequency_penalty,
+            presence_penalty: self.generation.presence_penalty,
+            response_format: self.generation.response_format,
             additional_params: self.additional_params,
+            idempotency_key: self.idempotency_key,
+            store: self.store,
+            metadata: self.metadata,
+            context_template,
         }
     }
 
     /// Sends the completion request to the completion model provider and returns the completion response.
+    /// The request is instrumented with a `chat` span (see [telemetry](crate::telemetry)).
     pub async fn send(self) -> Result<CompletionResponse<M::Response>, CompletionError> {
         let model = self.model.clone();
-        model.completion(self.build()).await
+        let span = telemetry::chat_span(
+            model.model_name(),
+            self.generation.temperature,
+            self.generation.max_tokens,
+        );
+
+        let start = Instant::now();
+        let result = model
+            .completion(self.build())
+            .instrument(span.clone())
+            .await;
+
+        telemetry::record_result(&span, start, &result);
+        if let Ok(response) = &result {
+            telemetry::record_usage(&span, model.token_usage(&response.raw_response));
+        }
+        result
     }
 }
 
@@ -534,7 +1050,17 @@ mod tests {
             tools: Vec::new(),
             temperature: None,
             max_tokens: None,
+            top_p: None,
+            stop: Vec::new(),
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            response_format: None,
             additional_params: None,
+            idempotency_key: None,
+            store: None,
+            metadata: None,
+            context_template: ContextTemplate::Xml,
         };
 
         let expected = Message::User {
@@ -554,4 +1080,65 @@ mod tests {
 
         assert_eq!(request.prompt_with_context(), expected);
     }
+
+    #[test]
+    fn test_markdown_context_template() {
+        let docs = vec![
+            Document {
+                id: "doc1".to_string(),
+                text: "Document 1 text.".to_string(),
+                additional_props: HashMap::new(),
+            },
+            Document {
+                id: "doc2".to_string(),
+                text: "Document 2 text.".to_string(),
+                additional_props: HashMap::from([("source".to_string(), "wiki".to_string())]),
+            },
+        ];
+
+        assert_eq!(
+            ContextTemplate::Markdown.render(&docs),
+            concat!(
+                "# Attachments\n\n",
+                "## doc1\n\nDocument 1 text.\n",
+                "\n",
+                "## doc2\n\n- source: wiki\n\nDocument 2 text.\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_custom_context_template() {
+        let docs = vec![
+            Document {
+                id: "doc1".to_string(),
+                text: "Document 1 text.".to_string(),
+                additional_props: HashMap::new(),
+            },
+            Document {
+                id: "doc2".to_string(),
+                text: "Document 2 text.".to_string(),
+                additional_props: HashMap::from([("source".to_string(), "wiki".to_string())]),
+            },
+        ];
+
+        let template = ContextTemplate::Custom(
+            PromptTemplate::new(concat!(
+                "Sources:\n",
+                "{{#each documents}}\n",
+                "[{{@index}}] {{text}}{{#if metadata.source}} ({{metadata.source}}){{/if}}\n",
+                "{{/each}}",
+            ))
+            .unwrap(),
+        );
+
+        assert_eq!(
+            template.render(&docs),
+            "Sources:\n[0] Document 1 text.\n[1] Document 2 text. (wiki)\n"
+        );
+
+        // Templates failing to render fall back to XML
+        let template = ContextTemplate::Custom(PromptTemplate::new("{{> missing}}").unwrap());
+        assert_eq!(template.render(&docs), ContextTemplate::Xml.render(&docs));
+    }
 }
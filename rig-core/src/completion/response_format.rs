@@ -0,0 +1,228 @@
+//! This module provides the [ResponseFormat] enum, which constrains the format of the answers of
+//! a completion model: free text (the default), any JSON object (JSON mode), or JSON matching a
+//! schema (structured outputs, e.g.: OpenAI's `json_schema` mode).
+//!
+//! JSON schemas are usually derived from a Rust type with [ResponseFormat::json_schema], and the
+//! answers deserialized into that type with [parse_json]. Agents do both with
+//! [AgentBuilder::structured_output](crate::agent::AgentBuilder::structured_output) and
+//! [Agent::prompt_typed](crate::agent::Agent::prompt_typed).
+//!
+//! The format is sent as `response_format` to OpenAI and OpenAI-compatible providers, as a
+//! response MIME type and schema to Gemini, and as `format` to Ollama. Providers without JSON
+//! modes ignore it (the preamble should then ask for JSON).
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::ResponseFormat, providers::openai};
+//!
+//! #[derive(serde::Deserialize, schemars::JsonSchema)]
+//! struct Person {
+//!     name: String,
+//!     age: Option<u8>,
+//! }
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("Extract the person described by the user.")
+//!     .structured_output::<Person>()
+//!     .build();
+//!
+//! let person: Person = agent.prompt_typed("John is 42 years old").await?;
+//! ```
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Format of the answers of a completion model (see the [module](self) documentation).
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Free text
+    #[default]
+    Text,
+    /// Any JSON object
+    JsonObject,
+    /// JSON matching `schema`
+    JsonSchema {
+        /// Name of the schema (letters, digits, `_` and `-` only)
+        name: String,
+        /// JSON schema of the answers
+        schema: Value,
+        /// Whether the provider must follow the schema exactly (OpenAI's structured outputs),
+        /// which requires every property to be required and no additional properties
+        strict: bool,
+    },
+}
+
+impl ResponseFormat {
+    /// Strict JSON schema format derived from the type `T`, named after it. The schema is made
+    /// compatible with strict mode: properties of optional fields are required but nullable,
+    /// and additional properties are rejected.
+    pub fn json_schema<T: JsonSchema>() -> Self {
+        let mut schema = json!(SchemaSettings::draft07()
+            .with(|settings| settings.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<T>());
+        let name = schema_name(&T::schema_name());
+
+        if let Value::Object(schema) = &mut schema {
+            schema.remove("$schema");
+            schema.remove("title");
+        }
+        make_strict(&mut schema);
+
+        Self::JsonSchema {
+            name,
+            schema,
+            strict: true,
+        }
+    }
+
+    /// The format in the format of OpenAI's chat completions API (also used by
+    /// OpenAI-compatible providers).
+    pub(crate) fn openai_param(&self) -> Value {
+        match self {
+            Self::Text => json!({"type": "text"}),
+            Self::JsonObject => json!({"type": "json_object"}),
+            Self::JsonSchema {
+                name,
+                schema,
+                strict,
+            } => json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": name,
+                    "schema": schema,
+                    "strict": strict,
+                },
+            }),
+        }
+    }
+}
+
+/// Deserialize a JSON answer into `T`. Markdown code fences around the JSON (which some models
+/// add without a JSON mode) are ignored.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, serde_json::Error> {
+    let text = text.trim();
+    let json = text
+        .strip_prefix("```")
+        .and_then(|text| text.strip_suffix("```"))
+        .map(|fenced| fenced.trim_start_matches("json").trim())
+        .unwrap_or(text);
+
+    serde_json::from_str(json)
+}
+
+/// Name of a schema with the characters OpenAI rejects replaced by `_`, truncated to 64
+/// characters.
+fn schema_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect()
+}
+
+/// Formats of strings supported by OpenAI's strict mode (others, such as the `uint8` format of
+/// integers generated by schemars, are rejected)
+const STRICT_FORMATS: [&str; 9] = [
+    "date-time",
+    "time",
+    "date",
+    "duration",
+    "email",
+    "hostname",
+    "ipv4",
+    "ipv6",
+    "uuid",
+];
+
+/// Make a JSON schema compatible with strict mode: every property of the objects is required
+/// and additional properties are rejected, recursively.
+fn make_strict(schema: &mut Value) {
+    match schema {
+        Value::Object(schema) => {
+            if let Some(Value::Object(properties)) = schema.get("properties") {
+                let required = properties.keys().cloned().collect::<Vec<_>>();
+                schema.insert("required".to_string(), json!(required));
+                schema.insert("additionalProperties".to_string(), json!(false));
+            }
+            if matches!(schema.get("format"), Some(Value::String(format)) if !STRICT_FORMATS.contains(&format.as_str()))
+            {
+                schema.remove("format");
+            }
+            schema.values_mut().for_each(make_strict);
+        }
+        Value::Array(schemas) => schemas.iter_mut().for_each(make_strict),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Person {
+        name: String,
+        age: Option<u8>,
+        address: Address,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Address {
+        city: String,
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let ResponseFormat::JsonSchema {
+            name,
+            schema,
+            strict,
+        } = ResponseFormat::json_schema::<Person>()
+        else {
+            panic!("expected a JSON schema format");
+        };
+
+        assert_eq!(name, "Person");
+        assert!(strict);
+        assert_eq!(schema.get("$schema"), None);
+        assert_eq!(schema["required"], json!(["address", "age", "name"]));
+        assert_eq!(schema["additionalProperties"], json!(false));
+        assert_eq!(
+            schema["properties"]["age"]["type"],
+            json!(["integer", "null"])
+        );
+        assert_eq!(schema["properties"]["age"].get("format"), None);
+        assert_eq!(
+            schema["properties"]["address"]["additionalProperties"],
+            json!(false)
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let person = Person {
+            name: "John".to_string(),
+            age: None,
+            address: Address {
+                city: "Paris".to_string(),
+            },
+        };
+        let json = r#"{"name": "John", "age": null, "address": {"city": "Paris"}}"#;
+
+        assert_eq!(parse_json::<Person>(json).unwrap(), person);
+        assert_eq!(
+            parse_json::<Person>(&format!("```json\n{json}\n```")).unwrap(),
+            person
+        );
+        assert!(parse_json::<Person>(r#"{"name": "John"}"#).is_err());
+    }
+}
@@ -0,0 +1,503 @@
+//! This module provides the [RetryModel] struct, a completion model wrapper that retries
+//! failed completion requests according to a [RetryConfig].
+//!
+//! Only transient errors are retried: connection errors, timeouts (including HTTP 408), rate
+//! limits (HTTP 429) and server errors (HTTP 5xx). Each attempt can be bounded by a timeout (see
+//! [RetryConfig::request_timeout]). When the provider tells when to retry (e.g.: with a
+//! `Retry-After` header), the retry waits at least that long.
+//!
+//! Retries are limited both by a maximum number of retries and, optionally, by a total time
+//! budget (see [RetryConfig::total_timeout] and [RetryConfig::deadline]). Retries stop as soon
+//! as either limit is reached, and the last error is returned.
+//!
+//! All the attempts of a request are sent with the same idempotency key, so that providers
+//! supporting it can deduplicate them: the key set on the request (see
+//! [CompletionRequestBuilder::idempotency_key](crate::completion::CompletionRequestBuilder::idempotency_key)),
+//! or a random key generated for the request otherwise.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::retry::{RetryConfig, RetryModel},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let model = RetryModel::new(
+//!     openai.completion_model(openai::GPT_4O),
+//!     RetryConfig::default()
+//!         .max_retries(10)
+//!         .request_timeout(Duration::from_secs(10))
+//!         .total_timeout(Duration::from_secs(30)),
+//! );
+//!
+//! // Equivalently
+//! let model = openai
+//!     .completion_model(openai::GPT_4O)
+//!     .with_retry(RetryConfig::default().max_retries(10));
+//!
+//! let agent = AgentBuilder::new(model)
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//! ```
+use std::{future::Future, time::Duration};
+
+use futures::future::{self, Either};
+
+use crate::{
+    completion::{
+        cost::ModelPricing, CompletionError, CompletionModel, CompletionRequest,
+        CompletionResponse, ContextTemplate, TokenUsage,
+    },
+    time::{self, Instant},
+};
+
+/// Configuration of the retries of a [RetryModel].
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// Maximum number of retries (not including the initial attempt)
+    pub max_retries: usize,
+    /// Delay before the first retry. The delay is doubled after each retry.
+    pub initial_backoff: Duration,
+    /// Maximum delay between two retries
+    pub max_backoff: Duration,
+    /// Total time budget for the operation (including all retries), starting when the
+    /// operation starts
+    pub total_timeout: Option<Duration>,
+    /// Point in time after which no more retries are attempted
+    pub deadline: Option<Instant>,
+    /// Time budget of each attempt
+    pub request_timeout: Option<Duration>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            total_timeout: None,
+            deadline: None,
+            request_timeout: None,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Set the maximum number of retries
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the delay before the first retry
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the maximum delay between two retries
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the total time budget of the operation. No retry is attempted once the budget
+    /// is exhausted, regardless of the number of retries left.
+    pub fn total_timeout(mut self, total_timeout: Duration) -> Self {
+        self.total_timeout = Some(total_timeout);
+        self
+    }
+
+    /// Set the point in time after which no retry is attempted, regardless of the number
+    /// of retries left.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the time budget of each attempt. Attempts taking longer fail with
+    /// [CompletionError::TimeoutError] (and are retried if retries are left).
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// Compute the deadline of an operation starting at `start`, if any.
+    pub(crate) fn deadline_from(&self, start: Instant) -> Option<Instant> {
+        let timeout_deadline = self.total_timeout.map(|timeout| start + timeout);
+
+        match (timeout_deadline, self.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Whether a completion error is worth retrying (i.e.: transient network and provider errors,
+/// see [Error::is_retryable](crate::Error::is_retryable)).
+pub(crate) fn is_retryable(error: &CompletionError) -> bool {
+    crate::error::completion_error_retry(error).0
+}
+
+/// Await `completion`, failing with [CompletionError::TimeoutError] if it takes longer
+/// than `timeout`.
+async fn with_timeout<T>(
+    completion: impl Future<Output = Result<T, CompletionError>>,
+    timeout: Option<Duration>,
+) -> Result<T, CompletionError> {
+    let Some(timeout) = timeout else {
+        return completion.await;
+    };
+
+    let completion = std::pin::pin!(completion);
+    match future::select(completion, std::pin::pin!(time::sleep(timeout))).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(CompletionError::TimeoutError(timeout)),
+    }
+}
+
+/// Completion model wrapper that retries failed completion requests.
+#[derive(Clone)]
+pub struct RetryModel<M: CompletionModel> {
+    model: M,
+    config: RetryConfig,
+}
+
+impl<M: CompletionModel> RetryModel<M> {
+    pub fn new(model: M, config: RetryConfig) -> Self {
+        Self { model, config }
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for RetryModel<M> {
+    type Response = M::Response;
+
+    fn model_name(&self) -> Option<&str> {
+        self.model.model_name()
+    }
+
+    fn context_template(&self) -> ContextTemplate {
+        self.model.context_template()
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        self.model.token_usage(response)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        self.model.raw_response_json(response)
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        self.model.pricing()
+    }
+
+    async fn completion(
+        &self,
+        mut request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let deadline = self.config.deadline_from(Instant::now());
+        request
+            .idempotency_key
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+        let mut backoff = self.config.initial_backoff;
+        let mut retries = 0;
+
+        loop {
+            let completion = self.model.completion(request.clone());
+            let error = match with_timeout(completion, self.config.request_timeout).await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+
+            let (retryable, retry_after) = crate::error::completion_error_retry(&error);
+            if !retryable || retries >= self.config.max_retries {
+                return Err(error);
+            }
+
+            // Wait at least as long as asked by the provider, but not past the deadline
+            let delay = retry_after.map_or(backoff, |retry_after| retry_after.max(backoff));
+            if deadline
+                .is_some_and(|deadline| deadline.saturating_duration_since(Instant::now()) <= delay)
+            {
+                return Err(error);
+            }
+
+            tracing::warn!(target: "rig",
+                "Completion request failed, retrying in {:?} ({}/{}): {}",
+                delay,
+                retries + 1,
+                self.config.max_retries,
+                error
+            );
+
+            time::sleep(delay).await;
+
+            retries += 1;
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::{error::ApiError, message::AssistantContent, OneOrMany};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct Model {
+        failures: usize,
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl Model {
+        fn new(failures: usize) -> Self {
+            Self {
+                failures,
+                attempts: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl CompletionModel for Model {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(CompletionError::ProviderError("Overloaded".into()))
+            } else {
+                Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text("Hello")),
+                    raw_response: (),
+                })
+            }
+        }
+    }
+
+    fn config() -> RetryConfig {
+        RetryConfig::default()
+            .initial_backoff(Duration::from_millis(10))
+            .max_backoff(Duration::from_millis(10))
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let model = Model::new(2);
+        let retry_model = RetryModel::new(model.clone(), config().max_retries(3));
+
+        assert!(retry_model.completion_request("Hi").send().await.is_ok());
+        assert_eq!(model.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_max_retries() {
+        let model = Model::new(10);
+        let retry_model = RetryModel::new(model.clone(), config().max_retries(3));
+
+        assert!(retry_model.completion_request("Hi").send().await.is_err());
+        assert_eq!(model.attempts.load(Ordering::SeqCst), 4);
+    }
+
+    // The clock is paused and advanced by the delays of the retries, so the timings are exact
+    #[tokio::test(start_paused = true)]
+    async fn test_total_timeout() {
+        let model = Model::new(10);
+        let retry_model = RetryModel::new(
+            model.clone(),
+            config()
+                .max_retries(100)
+                .total_timeout(Duration::from_millis(35)),
+        );
+
+        let start = Instant::now();
+        assert!(retry_model.completion_request("Hi").send().await.is_err());
+
+        // Attempts at 0, 10, 20 and 30ms: a retry at 40ms would end past the deadline
+        assert_eq!(model.attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after() {
+        #[derive(Clone)]
+        struct RateLimitedModel(Arc<AtomicUsize>);
+
+        impl CompletionModel for RateLimitedModel {
+            type Response = ();
+
+            async fn completion(
+                &self,
+                _request: CompletionRequest,
+            ) -> Result<CompletionResponse<()>, CompletionError> {
+                if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(CompletionError::ApiError(ApiError {
+                        retry_after: Some(Duration::from_millis(100)),
+                        ..ApiError::new(Some(429), "Too Many Requests")
+                    }));
+                }
+                Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text("Hello")),
+                    raw_response: (),
+                })
+            }
+        }
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let retry_model = RetryModel::new(RateLimitedModel(attempts.clone()), config());
+
+        let start = Instant::now();
+        assert!(retry_model.completion_request("Hi").send().await.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        // The retry waited for the delay of the provider rather than the 10ms backoff
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error() {
+        #[derive(Clone)]
+        struct FailingModel(Arc<AtomicUsize>);
+
+        impl CompletionModel for FailingModel {
+            type Response = ();
+
+            async fn completion(
+                &self,
+                _request: CompletionRequest,
+            ) -> Result<CompletionResponse<()>, CompletionError> {
+                match self.0.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(CompletionError::ResponseError("Invalid response".into())),
+                    1 => Err(ApiError::new(Some(400), "Invalid request").into()),
+                    _ => Err(CompletionError::ProviderError("Unknown error".into())),
+                }
+            }
+        }
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+
+        // Invalid responses, rejected requests and unclassified provider errors are not retried
+        for attempt in 1..=3 {
+            let retry_model = RetryModel::new(FailingModel(attempts.clone()), config());
+
+            assert!(retry_model.completion_request("Hi").send().await.is_err());
+            assert_eq!(attempts.load(Ordering::SeqCst), attempt);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        /// Model hanging on the first attempt
+        #[derive(Clone)]
+        struct SlowModel(Arc<AtomicUsize>);
+
+        impl CompletionModel for SlowModel {
+            type Response = ();
+
+            async fn completion(
+                &self,
+                _request: CompletionRequest,
+            ) -> Result<CompletionResponse<()>, CompletionError> {
+                if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text("Hello")),
+                    raw_response: (),
+                })
+            }
+        }
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let model = SlowModel(attempts.clone());
+
+        let result = model
+            .clone()
+            .with_retry(
+                config()
+                    .max_retries(0)
+                    .request_timeout(Duration::from_millis(10)),
+            )
+            .completion_request("Hi")
+            .send()
+            .await;
+        assert!(matches!(result, Err(CompletionError::TimeoutError(_))));
+
+        attempts.store(0, Ordering::SeqCst);
+        let result = model
+            .with_retry(
+                config()
+                    .max_retries(1)
+                    .request_timeout(Duration::from_millis(10)),
+            )
+            .completion_request("Hi")
+            .send()
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key() {
+        /// Model failing on the first attempt, recording the idempotency keys of the requests
+        #[derive(Clone, Default)]
+        struct KeyModel(Arc<std::sync::Mutex<Vec<Option<String>>>>);
+
+        impl CompletionModel for KeyModel {
+            type Response = ();
+
+            async fn completion(
+                &self,
+                request: CompletionRequest,
+            ) -> Result<CompletionResponse<()>, CompletionError> {
+                let mut keys = self.0.lock().unwrap();
+                keys.push(request.idempotency_key);
+                if keys.len() % 2 == 1 {
+                    return Err(CompletionError::ProviderError("Overloaded".into()));
+                }
+                Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text("Hello")),
+                    raw_response: (),
+                })
+            }
+        }
+
+        let model = KeyModel::default();
+        let retry_model = RetryModel::new(model.clone(), config());
+
+        // A key is generated for each logical request and reused across its retries
+        retry_model.completion_request("Hi").send().await.unwrap();
+        retry_model.completion_request("Hi").send().await.unwrap();
+        {
+            let keys = model.0.lock().unwrap();
+            assert!(keys.iter().all(Option::is_some));
+            assert_eq!(keys[0], keys[1]);
+            assert_eq!(keys[2], keys[3]);
+            assert_ne!(keys[0], keys[2]);
+        }
+
+        // The key set on the request is kept
+        model.0.lock().unwrap().clear();
+        retry_model
+            .completion_request("Hi")
+            .idempotency_key("key")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            *model.0.lock().unwrap(),
+            vec![Some("key".to_string()), Some("key".to_string())]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! This module provides the [SemanticCache] struct, a cache of completions keyed by the
+//! meaning of the prompt rather than its exact text.
+//!
+//! Prompts are embedded and stored with their response in an in-memory vector store. When a new
+//! prompt is similar enough to a cached prompt (i.e.: the cosine similarity of their embeddings
+//! is at least the cache's threshold), the cached response is returned instead of calling the model.
+//!
+//! The cache is usually used through [Agent::with_semantic_cache](crate::agent::Agent::with_semantic_cache).
+//! Cache hits and misses are logged with `tracing` (target `rig`).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     completion::Prompt,
+//!     providers::openai,
+//!     vector_store::in_memory_store::InMemoryVectorStore,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let cache_index = InMemoryVectorStore::default()
+//!     .index(openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL));
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .build()
+//!     .with_semantic_cache(cache_index, 0.95);
+//!
+//! // The second prompt is answered from the cache
+//! let response = agent.prompt("What is the capital of France?").await?;
+//! let response = agent.prompt("What's the capital city of France?").await?;
+//! ```
+use std::sync::RwLock;
+
+use futures::future::BoxFuture;
+
+use crate::{
+    embeddings::{Embedding, EmbeddingModel},
+    vector_store::{
+        in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore},
+        VectorStoreError,
+    },
+    OneOrMany,
+};
+
+/// Result of a [SemanticCache] lookup.
+#[derive(Debug)]
+pub enum CacheLookup {
+    /// A similar prompt was found in the cache
+    Hit {
+        /// The cached response
+        response: String,
+        /// Similarity between the prompt and the cached prompt
+        similarity: f64,
+    },
+    /// No similar prompt was found. Contains the embedding of the prompt, to be used to
+    /// insert the response in the cache (see [SemanticCache::insert]).
+    Miss { embedding: Embedding },
+}
+
+/// Cache of completion responses, looked up by prompt similarity.
+pub struct SemanticCache<M: EmbeddingModel> {
+    model: M,
+    store: RwLock<InMemoryVectorStore<String>>,
+    threshold: f64,
+}
+
+impl<M: EmbeddingModel> SemanticCache<M> {
+    /// Create a new cache from an index (whose store may already contain cached responses)
+    /// and a similarity threshold (between -1.0 and 1.0) above which a cached response is returned.
+    pub fn new(index: InMemoryVectorIndex<M, String>, threshold: f64) -> Self {
+        let (model, store) = index.into_parts();
+
+        Self {
+            model,
+            store: RwLock::new(store),
+            threshold,
+        }
+    }
+
+    /// Look up a prompt in the cache.
+    pub async fn lookup(&self, prompt: &str) -> Result<CacheLookup, VectorStoreError> {
+        let embedding = self.model.embed_text(prompt).await?;
+
+        let nearest = self
+            .store
+            .read()
+            .expect("Semantic cache lock should not be poisoned")
+            .top_n_by_embedding::<String>(&embedding, 1)?;
+
+        match nearest.into_iter().next() {
+            Some((similarity, id, response)) if similarity >= self.threshold => {
+                tracing::info!(target: "rig",
+                    "Semantic cache hit (similarity {:.3} with cached prompt {:?})",
+                    similarity,
+                    id
+                );
+                Ok(CacheLookup::Hit {
+                    response,
+                    similarity,
+                })
+            }
+            _ => {
+                tracing::info!(target: "rig", "Semantic cache miss");
+                Ok(CacheLookup::Miss { embedding })
+            }
+        }
+    }
+
+    /// Insert the response to a prompt in the cache, given the embedding of the prompt
+    /// (as returned by [SemanticCache::lookup]).
+    pub fn insert(&self, embedding: Embedding, response: String) {
+        if let Err(e) = self
+            .store
+            .write()
+            .expect("Semantic cache lock should not be poisoned")
+            .add_documents_with_ids([(
+                embedding.document.clone(),
+                response,
+                OneOrMany::one(embedding),
+            )])
+        {
+            tracing::warn!(target: "rig", "Semantic cache insertion failed: {}", e);
+        }
+    }
+
+    /// Number of cached responses.
+    pub fn len(&self) -> usize {
+        self.store
+            .read()
+            .expect("Semantic cache lock should not be poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Object-safe version of [SemanticCache], used to store caches of any embedding model.
+pub(crate) trait SemanticCacheDyn: Send + Sync {
+    fn lookup<'a>(
+        &'a self,
+        prompt: &'a str,
+    ) -> BoxFuture<'a, Result<CacheLookup, VectorStoreError>>;
+
+    fn insert(&self, embedding: Embedding, response: String);
+}
+
+impl<M: EmbeddingModel> SemanticCacheDyn for SemanticCache<M> {
+    fn lookup<'a>(
+        &'a self,
+        prompt: &'a str,
+    ) -> BoxFuture<'a, Result<CacheLookup, VectorStoreError>> {
+        Box::pin(self.lookup(prompt))
+    }
+
+    fn insert(&self, embedding: Embedding, response: String) {
+        self.insert(embedding, response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embeddings::EmbeddingError;
+
+    use super::*;
+
+    /// Embeds texts by their first word, so that prompts starting with the same word are identical.
+    #[derive(Clone)]
+    struct Model;
+
+    impl EmbeddingModel for Model {
+        const MAX_DOCUMENTS: usize = 5;
+
+        fn ndims(&self) -> usize {
+            2
+        }
+
+        async fn embed_texts(
+            &self,
+            texts: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            Ok(texts
+                .into_iter()
+                .map(|text| Embedding {
+                    vec: match text.split_whitespace().next() {
+                        Some("Hello") => vec![1.0, 0.0],
+                        _ => vec![0.0, 1.0],
+                    },
+                    document: text,
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_semantic_cache() {
+        let cache = SemanticCache::new(InMemoryVectorStore::default().index(Model), 0.9);
+
+        let CacheLookup::Miss { embedding } = cache.lookup("Hello world").await.unwrap() else {
+            panic!("Expected cache miss");
+        };
+        cache.insert(embedding, "Hi!".to_string());
+        assert_eq!(cache.len(), 1);
+
+        match cache.lookup("Hello there").await.unwrap() {
+            CacheLookup::Hit { response, .. } => assert_eq!(response, "Hi!"),
+            CacheLookup::Miss { .. } => panic!("Expected cache hit"),
+        }
+
+        assert!(matches!(
+            cache.lookup("Goodbye").await.unwrap(),
+            CacheLookup::Miss { .. }
+        ));
+    }
+}
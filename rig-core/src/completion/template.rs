@@ -0,0 +1,229 @@
+//! This module provides the [ChatTemplate] struct, which renders a conversation into a single
+//! prompt string with role markers. This is needed to use chat conversations with text completion
+//! (i.e.: non-chat) endpoints of instruct-tuned models.
+//!
+//! Templates for common formats are provided: [ChatTemplate::chatml], [ChatTemplate::llama3]
+//! and [ChatTemplate::alpaca]. Custom templates can be defined by setting the prefix and suffix
+//! of each role.
+//!
+//! # Example
+//! ```rust
+//! use rig::completion::{template::{ChatTemplate, RoleFormat}, Message};
+//!
+//! let template = ChatTemplate::new()
+//!     .user(RoleFormat::new("<|user|>\n", "\n"))
+//!     .assistant(RoleFormat::new("<|assistant|>\n", "\n"));
+//!
+//! let prompt = template.render(None, &[Message::user("Hello!")]);
+//!
+//! assert_eq!(prompt, "<|user|>\nHello!\n<|assistant|>\n");
+//! ```
+use crate::{
+    completion::CompletionRequest,
+    message::{AssistantContent, Message, UserContent},
+};
+
+/// Prefix and suffix surrounding the messages of a role.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoleFormat {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl RoleFormat {
+    pub fn new(prefix: impl Into<String>, suffix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+        }
+    }
+
+    fn wrap(&self, text: &str) -> String {
+        format!("{}{}{}", self.prefix, text, self.suffix)
+    }
+}
+
+/// Template rendering a conversation into a single prompt string.
+///
+/// The rendered prompt is the template's `bos` string, followed by the system prompt (if any)
+/// and the messages, each wrapped in the format of their role, and finally the assistant prefix
+/// so that the model generates the assistant's response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChatTemplate {
+    /// String at the beginning of the prompt (e.g.: a beginning of sequence token)
+    pub bos: String,
+    pub system: RoleFormat,
+    pub user: RoleFormat,
+    pub assistant: RoleFormat,
+}
+
+impl ChatTemplate {
+    /// Create a new template without any role markers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ChatML format (e.g.: Qwen, Yi, OpenHermes models).
+    pub fn chatml() -> Self {
+        Self {
+            bos: String::new(),
+            system: RoleFormat::new("<|im_start|>system\n", "<|im_end|>\n"),
+            user: RoleFormat::new("<|im_start|>user\n", "<|im_end|>\n"),
+            assistant: RoleFormat::new("<|im_start|>assistant\n", "<|im_end|>\n"),
+        }
+    }
+
+    /// Llama 3 instruct format.
+    pub fn llama3() -> Self {
+        Self {
+            bos: "<|begin_of_text|>".to_string(),
+            system: RoleFormat::new(
+                "<|start_header_id|>system<|end_header_id|>\n\n",
+                "<|eot_id|>",
+            ),
+            user: RoleFormat::new("<|start_header_id|>user<|end_header_id|>\n\n", "<|eot_id|>"),
+            assistant: RoleFormat::new(
+                "<|start_header_id|>assistant<|end_header_id|>\n\n",
+                "<|eot_id|>",
+            ),
+        }
+    }
+
+    /// Alpaca instruction format.
+    pub fn alpaca() -> Self {
+        Self {
+            bos: String::new(),
+            system: RoleFormat::new("", "\n\n"),
+            user: RoleFormat::new("### Instruction:\n", "\n\n"),
+            assistant: RoleFormat::new("### Response:\n", "\n\n"),
+        }
+    }
+
+    /// Set the string at the beginning of the prompt.
+    pub fn bos(mut self, bos: impl Into<String>) -> Self {
+        self.bos = bos.into();
+        self
+    }
+
+    /// Set the format of the system prompt.
+    pub fn system(mut self, format: RoleFormat) -> Self {
+        self.system = format;
+        self
+    }
+
+    /// Set the format of the user messages.
+    pub fn user(mut self, format: RoleFormat) -> Self {
+        self.user = format;
+        self
+    }
+
+    /// Set the format of the assistant messages.
+    pub fn assistant(mut self, format: RoleFormat) -> Self {
+        self.assistant = format;
+        self
+    }
+
+    /// Render a system prompt and messages into a prompt string, ending with the assistant prefix.
+    /// Only the textual content of the messages is rendered (tool calls are rendered as JSON,
+    /// images and audio are skipped).
+    pub fn render(&self, system: Option<&str>, messages: &[Message]) -> String {
+        let mut prompt = self.bos.clone();
+
+        if let Some(system) = system {
+            prompt.push_str(&self.system.wrap(system));
+        }
+
+        for message in messages {
+            let (format, text) = match message {
+                Message::User { content } => (
+                    &self.user,
+                    content.iter().filter_map(user_text).collect::<Vec<_>>(),
+                ),
+                Message::Assistant { content } => (
+                    &self.assistant,
+                    content.iter().map(assistant_text).collect::<Vec<_>>(),
+                ),
+            };
+            prompt.push_str(&format.wrap(&text.join("\n")));
+        }
+
+        prompt.push_str(&self.assistant.prefix);
+        prompt
+    }
+
+    /// Render a completion request (preamble, chat history and prompt with its context documents)
+    /// into a prompt string.
+    pub fn render_request(&self, request: &CompletionRequest) -> String {
+        let mut messages = request.chat_history.clone();
+        messages.push(request.prompt_with_context());
+
+        self.render(request.preamble.as_deref(), &messages)
+    }
+}
+
+fn user_text(content: &UserContent) -> Option<String> {
+    match content {
+        UserContent::Text(text) => Some(text.text.clone()),
+        UserContent::Document(document) => Some(document.data.clone()),
+        UserContent::ToolResult(tool_result) => Some(
+            tool_result
+                .content
+                .iter()
+                .map(|content| content.clone().into_text())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        UserContent::Image(_) | UserContent::Audio(_) => None,
+    }
+}
+
+fn assistant_text(content: &AssistantContent) -> String {
+    match content {
+        AssistantContent::Text(text) => text.text.clone(),
+        AssistantContent::ToolCall(tool_call) => {
+            serde_json::to_string(&tool_call.function).unwrap_or_default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::user("Hi"),
+            Message::assistant("Hello!"),
+            Message::user("How are you?"),
+        ]
+    }
+
+    #[test]
+    fn test_chatml() {
+        assert_eq!(
+            ChatTemplate::chatml().render(Some("Be nice."), &messages()),
+            "<|im_start|>system\nBe nice.<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\nHello!<|im_end|>\n\
+             <|im_start|>user\nHow are you?<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn test_llama3() {
+        assert_eq!(
+            ChatTemplate::llama3().render(None, &[Message::user("Hi")]),
+            "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+    }
+
+    #[test]
+    fn test_alpaca() {
+        assert_eq!(
+            ChatTemplate::alpaca().render(Some("Be nice."), &[Message::user("Hi")]),
+            "Be nice.\n\n### Instruction:\nHi\n\n### Response:\n"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! This module provides the [TokenCounter] trait, used to count the number of tokens of the
+//! texts sent to a model (e.g.: to fit the dynamic context of an [Agent](crate::agent::Agent)
+//! in the context window of its model, see [AgentBuilder::context_window](crate::agent::AgentBuilder::context_window)).
+//!
+//! By default, the number of tokens is approximated from the number of characters of the text
+//! ([EstimatedTokenCounter]). With the `tiktoken` feature, [TiktokenCounter] counts the exact
+//! number of tokens of texts sent to OpenAI models.
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::tokens::TiktokenCounter, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a dictionary assistant.")
+//!     .dynamic_context(10, index)
+//!     .context_window(128_000)
+//!     .token_counter(TiktokenCounter::for_model(openai::GPT_4O)?)
+//!     .build();
+//! ```
+
+/// Counts the number of tokens of a text, as tokenized by a model.
+pub trait TokenCounter: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+impl<F> TokenCounter for F
+where
+    F: Fn(&str) -> usize + Send + Sync,
+{
+    fn count_tokens(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// [TokenCounter] approximating the number of tokens of a text from its number of characters
+/// (about 4 characters per token for English). Used when no tokenizer is available for a model.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EstimatedTokenCounter;
+
+impl TokenCounter for EstimatedTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+}
+
+/// Approximate number of characters per token for English, used to estimate the number of
+/// tokens of texts when no tokenizer is available.
+pub const APPROX_CHARS_PER_TOKEN: usize = 4;
+
+/// Approximation of the number of tokens of a text (about 4 characters per token for English).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(APPROX_CHARS_PER_TOKEN)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenCounterError {
+    /// No tokenizer is known for the model
+    #[error("No tokenizer found for model {0}")]
+    UnknownModel(String),
+}
+
+/// [TokenCounter] counting the exact number of tokens of texts sent to an OpenAI model,
+/// using [tiktoken](https://github.com/openai/tiktoken).
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenCounter {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenCounter {
+    /// Create a token counter using the tokenizer of the OpenAI model `model` (e.g.: `gpt-4o`).
+    pub fn for_model(model: &str) -> Result<Self, TokenCounterError> {
+        let bpe = tiktoken_rs::get_bpe_from_model(model)
+            .map_err(|_| TokenCounterError::UnknownModel(model.to_string()))?;
+        Ok(Self { bpe })
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl TokenCounter for TiktokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimated_token_counter() {
+        assert_eq!(EstimatedTokenCounter.count_tokens(""), 0);
+        assert_eq!(EstimatedTokenCounter.count_tokens("Hello"), 2);
+        assert_eq!(EstimatedTokenCounter.count_tokens("Hello world!"), 3);
+    }
+
+    #[test]
+    fn test_closure_token_counter() {
+        let counter = |text: &str| text.split_whitespace().count();
+        assert_eq!(counter.count_tokens("Hello big world"), 3);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_tiktoken_counter() {
+        let counter = TiktokenCounter::for_model("gpt-4o").unwrap();
+        assert_eq!(counter.count_tokens("Hello world!"), 3);
+        assert!(TiktokenCounter::for_model("not-a-model").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! This module provides the [ContextCompressor] trait, implemented by compressors of the
+//! documents retrieved for a prompt: they filter out the irrelevant documents, or shorten the
+//! relevant ones, before the documents are inserted in the request of an agent.
+//!
+//! Retrieved chunks of long documents mostly contain text irrelevant to the prompt, which is
+//! paid for in every request of the agent. A compressor can reduce the documents retrieved from
+//! the dynamic context with a cheap model (see
+//! [AgentBuilder::context_compression](crate::agent::AgentBuilder::context_compression)),
+//! cutting the token costs of the (more expensive) model of the agent.
+//!
+//! The [LlmCompressor] prompts a completion model to filter or summarize each document. Users
+//! can supply their own compressors (e.g.: extractive models) by implementing
+//! [ContextCompressor].
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     compression::{CompressionMode, LlmCompressor},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .dynamic_context(10, index)
+//!     .context_compression(
+//!         LlmCompressor::new(openai.completion_model(openai::GPT_4O_MINI), CompressionMode::Summarize)
+//!             .min_length(500),
+//!     )
+//!     .build();
+//! ```
+use futures::{future::BoxFuture, stream, StreamExt, TryStreamExt};
+
+use crate::{
+    completion::{CompletionError, CompletionModel, Document},
+    message::AssistantContent,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    /// Error of the completion model used to compress the documents
+    #[error("CompletionError: {0}")]
+    CompletionError(#[from] CompletionError),
+
+    /// Error of a custom compressor
+    #[error("CompressorError: {0}")]
+    CompressorError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+/// Trait for compressors of the documents retrieved for a prompt.
+pub trait ContextCompressor: Send + Sync {
+    /// Compress the `documents` retrieved for `query`, returning the documents to insert in
+    /// the request (in order of relevance).
+    fn compress(
+        &self,
+        query: &str,
+        documents: Vec<Document>,
+    ) -> impl std::future::Future<Output = Result<Vec<Document>, CompressionError>> + Send;
+}
+
+/// Dyn-compatible version of [ContextCompressor], used to store compressors of different types
+/// (e.g.: in an [Agent](crate::agent::Agent)).
+pub trait ContextCompressorDyn: Send + Sync {
+    fn compress<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<Document>,
+    ) -> BoxFuture<'a, Result<Vec<Document>, CompressionError>>;
+}
+
+impl<C: ContextCompressor> ContextCompressorDyn for C {
+    fn compress<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<Document>,
+    ) -> BoxFuture<'a, Result<Vec<Document>, CompressionError>> {
+        Box::pin(ContextCompressor::compress(self, query, documents))
+    }
+}
+
+/// How an [LlmCompressor] compresses the documents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompressionMode {
+    /// Drop the documents irrelevant to the query, keeping the others as is
+    Filter,
+    /// Replace the documents by a summary of their content relevant to the query, dropping the
+    /// irrelevant ones
+    Summarize,
+}
+
+/// Answer of the model to documents without relevant content in [CompressionMode::Summarize]
+const NO_OUTPUT: &str = "NO_OUTPUT";
+
+/// Compressor prompting a completion model (typically a cheap one) with each document.
+///
+/// #### Default Values
+/// - `concurrency`: 4
+/// - `min_length`: 0
+#[derive(Clone)]
+pub struct LlmCompressor<M: CompletionModel> {
+    model: M,
+    mode: CompressionMode,
+    concurrency: usize,
+    min_length: usize,
+}
+
+impl<M: CompletionModel> LlmCompressor<M> {
+    pub fn new(model: M, mode: CompressionMode) -> Self {
+        Self {
+            model,
+            mode,
+            concurrency: 4,
+            min_length: 0,
+        }
+    }
+
+    /// Set the maximum number of documents compressed concurrently (at least 1).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Only compress the documents of at least `min_length` characters: shorter documents are
+    /// kept as is, as compressing them would cost more than it saves.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Compress a document, returning `None` if it is irrelevant to `query`.
+    async fn compress_document(
+        &self,
+        query: &str,
+        mut document: Document,
+    ) -> Result<Option<Document>, CompletionError> {
+        if document.text.chars().count() < self.min_length {
+            return Ok(Some(document));
+        }
+
+        let prompt = match self.mode {
+            CompressionMode::Filter => format!(
+                "Does the document below contain information relevant to the question? \
+                 Answer with YES or NO only.\n\n\
+                 Question: {query}\n\nDocument:\n{}",
+                document.text
+            ),
+            CompressionMode::Summarize => format!(
+                "Summarize the information of the document below which is relevant to the \
+                 question, as concisely as possible and without answering the question. If \
+                 the document contains no relevant information, answer with {NO_OUTPUT} only.\
+                 \n\nQuestion: {query}\n\nDocument:\n{}",
+                document.text
+            ),
+        };
+
+        let response = self.model.completion_request(prompt).send().await?;
+        let answer = response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                AssistantContent::ToolCall(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let answer = answer.trim();
+
+        Ok(match self.mode {
+            CompressionMode::Filter => answer
+                .to_ascii_lowercase()
+                .starts_with("yes")
+                .then_some(document),
+            CompressionMode::Summarize if answer.is_empty() || answer == NO_OUTPUT => None,
+            CompressionMode::Summarize => {
+                document.text = answer.to_string();
+                Some(document)
+            }
+        })
+    }
+}
+
+impl<M: CompletionModel> ContextCompressor for LlmCompressor<M> {
+    async fn compress(
+        &self,
+        query: &str,
+        documents: Vec<Document>,
+    ) -> Result<Vec<Document>, CompressionError> {
+        let count = documents.len();
+        let compressed = stream::iter(documents)
+            .map(|document| self.compress_document(query, document))
+            .buffered(self.concurrency)
+            .try_filter_map(|document| async move { Ok(document) })
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        tracing::debug!(target: "rig",
+            "Compressed {} retrieved documents into {}",
+            count,
+            compressed.len()
+        );
+        Ok(compressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{CompressionMode, ContextCompressor, LlmCompressor};
+    use crate::{completion::Document, providers::mock::MockCompletionModel};
+
+    fn document(id: &str, text: &str) -> Document {
+        Document {
+            id: id.to_string(),
+            text: text.to_string(),
+            additional_props: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_compressor() {
+        let documents = vec![
+            document("a", "Paris is the capital of France. It has many museums."),
+            document("b", "Bananas are rich in potassium."),
+            document("c", "Short."),
+        ];
+
+        let model = MockCompletionModel::new().text("Yes").text("No");
+        let filtered = LlmCompressor::new(model.clone(), CompressionMode::Filter)
+            .concurrency(1)
+            .min_length(10)
+            .compress("What is the capital of France?", documents.clone())
+            .await
+            .unwrap();
+        assert_eq!(filtered, vec![documents[0].clone(), documents[2].clone()]);
+        // Short documents are not sent to the model
+        assert_eq!(model.requests().len(), 2);
+
+        let model = MockCompletionModel::new()
+            .text("Paris is the capital of France.")
+            .text("NO_OUTPUT")
+            .text("Short.");
+        let summarized = LlmCompressor::new(model, CompressionMode::Summarize)
+            .compress("What is the capital of France?", documents.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            summarized,
+            vec![
+                document("a", "Paris is the capital of France."),
+                documents[2].clone()
+            ]
+        );
+
+        // Summaries mentioning the token are kept
+        let model = MockCompletionModel::new()
+            .text("The tool prints NO_OUTPUT on empty input.")
+            .text(" NO_OUTPUT\n");
+        let summarized = LlmCompressor::new(model, CompressionMode::Summarize)
+            .compress("What does the tool print?", documents[..2].to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            summarized,
+            vec![document("a", "The tool prints NO_OUTPUT on empty input.")]
+        );
+    }
+}
@@ -2,15 +2,21 @@
 //! and batch generates the embeddings for each object when built.
 //! Only types that implement the [Embed] trait can be added to the [EmbeddingsBuilder].
 
-use std::{cmp::max, collections::HashMap};
+use std::{cmp::max, collections::HashMap, sync::Arc};
 
-use futures::{stream, StreamExt};
+use futures::{future, stream, StreamExt};
+use tracing::Instrument;
+use web_time::Instant;
 
 use crate::{
+    completion::{
+        retry::RetryConfig,
+        tokens::{estimate_tokens, APPROX_CHARS_PER_TOKEN},
+    },
     embeddings::{
         embed::TextEmbedder, Embed, EmbedError, Embedding, EmbeddingError, EmbeddingModel,
     },
-    OneOrMany,
+    telemetry, time, OneOrMany,
 };
 
 /// Builder for creating embeddings from one or more documents of type `T`.
@@ -50,6 +56,9 @@ use crate::{
 pub struct EmbeddingsBuilder<M: EmbeddingModel, T: Embed> {
     model: M,
     documents: Vec<(T, Vec<String>)>,
+    truncation: Option<(TruncationPolicy, usize)>,
+    id_f: Option<fn(&T) -> String>,
+    batching: BatchOptions,
 }
 
 impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
@@ -58,15 +67,62 @@ impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
         Self {
             model,
             documents: vec![],
+            truncation: None,
+            id_f: None,
+            batching: BatchOptions::default(),
         }
     }
 
+    /// Set the maximum number of batches (of at most [EmbeddingModel::MAX_DOCUMENTS] texts)
+    /// embedded concurrently. By default, up to `1024 / MAX_DOCUMENTS` batches are embedded
+    /// concurrently.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.batching.concurrency = Some(max(1, concurrency));
+        self
+    }
+
+    /// Retry the batches failing with a transient error (network errors, timeouts, rate limits
+    /// and server errors, i.e.: HTTP 408, 429 and 5xx) with exponential backoff, according to
+    /// `config`. Other errors (e.g.: an invalid request or API key) are returned immediately.
+    /// By default, failed batches are not retried and the first error is returned.
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.batching.retry = Some(config);
+        self
+    }
+
+    /// Call `on_progress` each time a batch of texts is embedded, e.g.: to display the progress
+    /// of large embedding jobs.
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(EmbeddingProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.batching.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    /// Set the policy applied to texts longer than `max_input_tokens` tokens (see [TruncationPolicy]).
+    /// By default, texts are sent as is and the behavior depends on the provider (some
+    /// truncate them silently, some return an error).
+    ///
+    /// Note: the number of tokens of a text is approximated from its number of characters
+    /// (about 4 characters per token).
+    pub fn truncation_policy(mut self, policy: TruncationPolicy, max_input_tokens: usize) -> Self {
+        self.truncation = Some((policy, max_input_tokens));
+        self
+    }
+
+    /// Identify the documents with the id generated by `f` in the errors of the builder (see
+    /// [InputTooLongError]). By default, documents are identified by their index.
+    pub fn document_id_f(mut self, f: fn(&T) -> String) -> Self {
+        self.id_f = Some(f);
+        self
+    }
+
     /// Add a document to be embedded to the builder. `document` must implement the [Embed] trait.
     pub fn document(mut self, document: T) -> Result<Self, EmbedError> {
-        let mut embedder = TextEmbedder::default();
-        document.embed(&mut embedder)?;
+        let texts = document_texts(&document)?;
 
-        self.documents.push((document, embedder.texts));
+        self.documents.push((document, texts));
 
         Ok(self)
     }
@@ -80,57 +136,63 @@ impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
 
         Ok(builder)
     }
+
+    /// Estimate the number of tokens and the cost of embedding all documents in the builder,
+    /// without making any request to the model provider.
+    ///
+    /// The cost is computed from [EmbeddingModel::price_per_million_tokens] and is `None` if
+    /// the price of the model is unknown.
+    ///
+    /// Note: the number of tokens of a text is approximated from its number of characters
+    /// (about 4 characters per token).
+    pub fn estimated_cost(&self) -> CostEstimate {
+        let tokens = self
+            .documents
+            .iter()
+            .flat_map(|(_, texts)| texts.iter())
+            .map(|text| {
+                let tokens = estimate_tokens(text);
+                match self.truncation {
+                    Some((TruncationPolicy::Truncate, max_input_tokens)) => {
+                        tokens.min(max_input_tokens)
+                    }
+                    _ => tokens,
+                }
+            })
+            .sum::<usize>();
+
+        CostEstimate {
+            tokens,
+            cost: self
+                .model
+                .price_per_million_tokens()
+                .map(|price| tokens as f64 * price / 1_000_000.0),
+        }
+    }
+}
+
+/// Estimate of the number of tokens and cost of an embedding job (see [EmbeddingsBuilder::estimated_cost]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CostEstimate {
+    /// Approximate total number of tokens to embed
+    pub tokens: usize,
+    /// Estimated cost in US dollars, if the price of the model is known
+    pub cost: Option<f64>,
 }
 
 impl<M: EmbeddingModel, T: Embed + Send> EmbeddingsBuilder<M, T> {
     /// Generate embeddings for all documents in the builder.
     /// Returns a vector of tuples, where the first element is the document and the second element is the embeddings (either one embedding or many).
     pub async fn build(self) -> Result<Vec<(T, OneOrMany<Embedding>)>, EmbeddingError> {
-        use stream::TryStreamExt;
-
-        // Store the documents and their texts in a HashMap for easy access.
-        let mut docs = HashMap::new();
-        let mut texts = Vec::new();
+        let (docs, texts): (Vec<_>, Vec<_>) = self.documents.into_iter().unzip();
+        let texts = truncate_texts(&docs, texts, self.truncation, self.id_f)?;
 
-        // Iterate over all documents in the builder and insert their docs and texts into the lookup stores.
-        for (i, (doc, doc_texts)) in self.documents.into_iter().enumerate() {
-            docs.insert(i, doc);
-            texts.push((i, doc_texts));
-        }
-
-        // Compute the embeddings.
-        let mut embeddings = stream::iter(texts.into_iter())
-            // Merge the texts of each document into a single list of texts.
-            .flat_map(|(i, texts)| stream::iter(texts.into_iter().map(move |text| (i, text))))
-            // Chunk them into batches. Each batch size is at most the embedding API limit per request.
-            .chunks(M::MAX_DOCUMENTS)
-            // Generate the embeddings for each batch.
-            .map(|text| async {
-                let (ids, docs): (Vec<_>, Vec<_>) = text.into_iter().unzip();
-
-                let embeddings = self.model.embed_texts(docs).await?;
-                Ok::<_, EmbeddingError>(ids.into_iter().zip(embeddings).collect::<Vec<_>>())
-            })
-            // Parallelize the embeddings generation over 10 concurrent requests
-            .buffer_unordered(max(1, 1024 / M::MAX_DOCUMENTS))
-            // Collect the embeddings into a HashMap.
-            .try_fold(
-                HashMap::new(),
-                |mut acc: HashMap<_, OneOrMany<Embedding>>, embeddings| async move {
-                    embeddings.into_iter().for_each(|(i, embedding)| {
-                        acc.entry(i)
-                            .and_modify(|embeddings| embeddings.push(embedding.clone()))
-                            .or_insert(OneOrMany::one(embedding.clone()));
-                    });
-
-                    Ok(acc)
-                },
-            )
-            .await?;
+        let mut embeddings = embed_documents(&self.model, texts, &self.batching).await?;
 
         // Merge the embeddings with their respective documents
         Ok(docs
             .into_iter()
+            .enumerate()
             .map(|(i, doc)| {
                 (
                     doc,
@@ -141,6 +203,431 @@ impl<M: EmbeddingModel, T: Embed + Send> EmbeddingsBuilder<M, T> {
     }
 }
 
+/// Texts to embed of a document. Fails if the document has none (e.g.: all its fields tagged
+/// with `#[embed]` are `None`), since a document without embeddings cannot be stored.
+fn document_texts<T: Embed>(document: &T) -> Result<Vec<String>, EmbedError> {
+    let mut embedder = TextEmbedder::default();
+    document.embed(&mut embedder)?;
+
+    if embedder.texts.is_empty() {
+        return Err(EmbedError::from(
+            Box::<dyn std::error::Error + Send + Sync>::from("Document has no text to embed"),
+        ));
+    }
+
+    Ok(embedder.texts.into_iter().map(|(_, text)| text).collect())
+}
+
+/// Progress of the generation of embeddings (see [EmbeddingsBuilder::on_progress]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmbeddingProgress {
+    /// Number of batches embedded so far
+    pub batches_done: usize,
+    /// Total number of batches to embed
+    pub batches_total: usize,
+    /// Number of texts embedded so far
+    pub texts_done: usize,
+    /// Total number of texts to embed
+    pub texts_total: usize,
+}
+
+/// Options of the batched generation of embeddings.
+#[derive(Clone, Default)]
+struct BatchOptions {
+    concurrency: Option<usize>,
+    retry: Option<RetryConfig>,
+    on_progress: Option<Arc<dyn Fn(EmbeddingProgress) + Send + Sync>>,
+}
+
+/// Policy applied by the [EmbeddingsBuilder] to texts exceeding the maximum number of input
+/// tokens of the embedding model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Fail with an [InputTooLongError] identifying the offending document.
+    Error,
+    /// Cut the text to the maximum number of input tokens.
+    Truncate,
+    /// Split the text in chunks of at most the maximum number of input tokens. Each chunk is
+    /// embedded separately, so the document gets one embedding per chunk.
+    Split,
+}
+
+/// Error returned when a text exceeds the maximum number of input tokens of the embedding model
+/// and the [TruncationPolicy::Error] policy is used.
+#[derive(Debug, thiserror::Error)]
+#[error("Document {id} has a text of about {tokens} tokens, exceeding the maximum of {max_tokens} tokens")]
+pub struct InputTooLongError {
+    /// Id of the offending document (see [EmbeddingsBuilder::document_id_f]), or its index in
+    /// the order the documents were added to the builder
+    pub id: String,
+    /// Approximate number of tokens of the offending text
+    pub tokens: usize,
+    /// Maximum number of input tokens
+    pub max_tokens: usize,
+}
+
+impl TruncationPolicy {
+    /// Apply the policy to the texts of a document, identified by `id` in errors.
+    fn apply(
+        &self,
+        id: impl FnOnce() -> String,
+        texts: Vec<String>,
+        max_tokens: usize,
+    ) -> Result<Vec<String>, InputTooLongError> {
+        let mut id = Some(id);
+        let max_chars = max(1, max_tokens * APPROX_CHARS_PER_TOKEN);
+
+        texts.into_iter().try_fold(vec![], |mut acc, text| {
+            let chars = text.chars().count();
+            if chars <= max_chars {
+                acc.push(text);
+                return Ok(acc);
+            }
+
+            match self {
+                TruncationPolicy::Error => {
+                    return Err(InputTooLongError {
+                        id: id.take().map(|id| id()).unwrap_or_default(),
+                        tokens: chars.div_ceil(APPROX_CHARS_PER_TOKEN),
+                        max_tokens,
+                    })
+                }
+                TruncationPolicy::Truncate => acc.push(text.chars().take(max_chars).collect()),
+                TruncationPolicy::Split => acc.extend(
+                    text.chars()
+                        .collect::<Vec<_>>()
+                        .chunks(max_chars)
+                        .map(|chunk| chunk.iter().collect::<String>()),
+                ),
+            }
+
+            Ok(acc)
+        })
+    }
+}
+
+/// Apply the truncation policy, if any, to the texts of each of the `documents`, identified by
+/// `id_f` (or their index) in errors.
+fn truncate_texts<T>(
+    documents: &[T],
+    texts: Vec<Vec<String>>,
+    truncation: Option<(TruncationPolicy, usize)>,
+    id_f: Option<fn(&T) -> String>,
+) -> Result<Vec<Vec<String>>, EmbeddingError> {
+    let Some((policy, max_input_tokens)) = truncation else {
+        return Ok(texts);
+    };
+
+    documents
+        .iter()
+        .zip(texts)
+        .enumerate()
+        .map(|(i, (document, texts))| {
+            let id = || id_f.map_or_else(|| i.to_string(), |id_f| id_f(document));
+            policy.apply(id, texts, max_input_tokens)
+        })
+        .collect::<Result<_, _>>()
+        .map_err(|e| EmbeddingError::DocumentError(Box::new(e)))
+}
+
+/// Builder for embedding the same documents with several embedding models at once.
+/// Each model is identified by a name, and the documents are embedded once per model
+/// (concurrently across models).
+/// Note: all models must be of the same type `M`. To mix providers, wrap them in an enum
+/// that implements [EmbeddingModel].
+///
+/// # Example
+/// ```rust
+/// use rig::{
+///     embeddings::MultiEmbeddingsBuilder,
+///     providers::openai::{Client, TEXT_EMBEDDING_3_SMALL, TEXT_EMBEDDING_ADA_002},
+/// };
+///
+/// let openai_client = Client::from_env();
+///
+/// let embeddings = MultiEmbeddingsBuilder::new([
+///         (TEXT_EMBEDDING_ADA_002, openai_client.embedding_model(TEXT_EMBEDDING_ADA_002)),
+///         (TEXT_EMBEDDING_3_SMALL, openai_client.embedding_model(TEXT_EMBEDDING_3_SMALL)),
+///     ])
+///     .documents(vec![
+///         "1. *flurbo* (noun): A green alien that lives on cold planets.".to_string(),
+///         "2. *flurbo* (noun): A fictional digital currency that originated in the animated series Rick and Morty.".to_string()
+///     ])?
+///     .build()
+///     .await?;
+///
+/// let ada_embeddings = &embeddings[TEXT_EMBEDDING_ADA_002];
+/// ```
+pub struct MultiEmbeddingsBuilder<M: EmbeddingModel, T: Embed> {
+    models: Vec<(String, M)>,
+    documents: Vec<(T, Vec<String>)>,
+    truncation: Option<(TruncationPolicy, usize)>,
+    id_f: Option<fn(&T) -> String>,
+}
+
+impl<M: EmbeddingModel, T: Embed> MultiEmbeddingsBuilder<M, T> {
+    /// Create a new multi-model embedding builder from a list of `(name, model)` pairs.
+    ///
+    /// # Panics
+    /// Panics if two models have the same name (see [MultiEmbeddingsBuilder::model]).
+    pub fn new(models: impl IntoIterator<Item = (impl ToString, M)>) -> Self {
+        let builder = Self {
+            models: vec![],
+            documents: vec![],
+            truncation: None,
+            id_f: None,
+        };
+
+        models
+            .into_iter()
+            .fold(builder, |builder, (name, model)| builder.model(name, model))
+    }
+
+    /// Add a model to the builder under the given name.
+    ///
+    /// # Panics
+    /// Panics if a model with the same name was already added: the embeddings are returned
+    /// in a map keyed by model name, so the embeddings of one of the two models would be lost.
+    pub fn model(mut self, name: impl ToString, model: M) -> Self {
+        let name = name.to_string();
+        if self.models.iter().any(|(existing, _)| *existing == name) {
+            panic!("MultiEmbeddingsBuilder: a model named `{name}` was already added");
+        }
+
+        self.models.push((name, model));
+        self
+    }
+
+    /// Set the policy applied to texts longer than `max_input_tokens` tokens, for all models
+    /// (see [EmbeddingsBuilder::truncation_policy]).
+    pub fn truncation_policy(mut self, policy: TruncationPolicy, max_input_tokens: usize) -> Self {
+        self.truncation = Some((policy, max_input_tokens));
+        self
+    }
+
+    /// Identify the documents with the id generated by `f` in the errors of the builder (see
+    /// [EmbeddingsBuilder::document_id_f]).
+    pub fn document_id_f(mut self, f: fn(&T) -> String) -> Self {
+        self.id_f = Some(f);
+        self
+    }
+
+    /// Add a document to be embedded to the builder. `document` must implement the [Embed] trait.
+    pub fn document(mut self, document: T) -> Result<Self, EmbedError> {
+        let texts = document_texts(&document)?;
+
+        self.documents.push((document, texts));
+
+        Ok(self)
+    }
+
+    /// Add multiple documents to be embedded to the builder. `documents` must be iterable
+    /// with items that implement the [Embed] trait.
+    pub fn documents(self, documents: impl IntoIterator<Item = T>) -> Result<Self, EmbedError> {
+        let builder = documents
+            .into_iter()
+            .try_fold(self, |builder, doc| builder.document(doc))?;
+
+        Ok(builder)
+    }
+}
+
+impl<M: EmbeddingModel, T: Embed + Clone + Send> MultiEmbeddingsBuilder<M, T> {
+    /// Generate embeddings for all documents in the builder with every model.
+    /// Returns a map from model name to a vector of tuples, where the first element is the
+    /// document and the second element is the embeddings generated by that model.
+    /// Documents appear in the same order for every model.
+    pub async fn build(
+        self,
+    ) -> Result<HashMap<String, Vec<(T, OneOrMany<Embedding>)>>, EmbeddingError> {
+        let (docs, texts): (Vec<_>, Vec<_>) = self.documents.into_iter().unzip();
+        let texts = truncate_texts(&docs, texts, self.truncation, self.id_f)?;
+
+        let results = future::try_join_all(self.models.iter().map(|(name, model)| {
+            let texts = texts.clone();
+            async move {
+                embed_documents(model, texts, &BatchOptions::default())
+                    .await
+                    .map(|embeddings| (name.clone(), embeddings))
+            }
+        }))
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|(name, mut embeddings)| {
+                let documents = docs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, doc)| {
+                        (
+                            doc.clone(),
+                            embeddings.remove(&i).expect("Document should be present"),
+                        )
+                    })
+                    .collect();
+                (name, documents)
+            })
+            .collect())
+    }
+}
+
+/// Embed the texts of each document with `model`, batching them according to the model's
+/// [EmbeddingModel::MAX_DOCUMENTS] limit.
+/// Returns a map from the document's index to its embeddings (in the order of its texts).
+async fn embed_documents<M: EmbeddingModel>(
+    model: &M,
+    texts: Vec<Vec<String>>,
+    options: &BatchOptions,
+) -> Result<HashMap<usize, OneOrMany<Embedding>>, EmbeddingError> {
+    use stream::TryStreamExt;
+
+    // Merge the texts of each document into a single list of texts.
+    let texts = texts
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, texts)| texts.into_iter().map(move |text| (i, text)))
+        .collect::<Vec<_>>();
+
+    // Chunk them into batches. Each batch size is at most the embedding API limit per request.
+    let batches = texts
+        .chunks(M::MAX_DOCUMENTS)
+        .map(|batch| batch.to_vec())
+        .collect::<Vec<_>>();
+
+    let progress = EmbeddingProgress {
+        batches_done: 0,
+        batches_total: batches.len(),
+        texts_done: 0,
+        texts_total: texts.len(),
+    };
+
+    stream::iter(batches)
+        // Generate the embeddings for each batch.
+        .map(|batch| async {
+            let (ids, docs): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+
+            let embeddings = embed_batch(model, docs, options.retry.as_ref()).await?;
+            Ok::<_, EmbeddingError>(ids.into_iter().zip(embeddings).collect::<Vec<_>>())
+        })
+        // Parallelize the embeddings generation (keeping the order of the batches, so the
+        // embeddings of a document are in the order of its texts)
+        .buffered(
+            options
+                .concurrency
+                .unwrap_or(max(1, 1024 / M::MAX_DOCUMENTS)),
+        )
+        // Collect the embeddings into a HashMap.
+        .try_fold(
+            (HashMap::new(), progress),
+            |(mut acc, mut progress): (HashMap<_, OneOrMany<Embedding>>, _), embeddings| async move {
+                progress.batches_done += 1;
+                progress.texts_done += embeddings.len();
+                if let Some(on_progress) = &options.on_progress {
+                    on_progress(progress);
+                }
+
+                embeddings.into_iter().for_each(|(i, embedding)| {
+                    acc.entry(i)
+                        .and_modify(|embeddings| embeddings.push(embedding.clone()))
+                        .or_insert(OneOrMany::one(embedding.clone()));
+                });
+
+                Ok((acc, progress))
+            },
+        )
+        .await
+        .map(|(embeddings, _)| embeddings)
+}
+
+/// Embed a batch of texts, retrying on transient errors (see [crate::Error::is_retryable])
+/// according to `retry`.
+/// The batch (including its retries) is instrumented with an `embeddings` span.
+async fn embed_batch<M: EmbeddingModel>(
+    model: &M,
+    texts: Vec<String>,
+    retry: Option<&RetryConfig>,
+) -> Result<Vec<Embedding>, EmbeddingError> {
+    let span = telemetry::embeddings_span(model.model_name(), texts.len());
+
+    let start = Instant::now();
+    let result = embed_batch_with_retries(model, texts, retry)
+        .instrument(span.clone())
+        .await
+        .and_then(|embeddings| check_ndims(model, embeddings));
+
+    telemetry::record_result(&span, start, &result);
+    result
+}
+
+/// Check that the embeddings have the number of dimensions of the model (e.g.: the shortened
+/// size of OpenAI's `text-embedding-3` models), so that they fit the vector stores created for
+/// the model. Models reporting 0 dimensions (unknown) are not checked.
+fn check_ndims<M: EmbeddingModel>(
+    model: &M,
+    embeddings: Vec<Embedding>,
+) -> Result<Vec<Embedding>, EmbeddingError> {
+    let ndims = model.ndims();
+    match embeddings
+        .iter()
+        .find(|embedding| ndims > 0 && embedding.vec.len() != ndims)
+    {
+        Some(embedding) => Err(EmbeddingError::ResponseError(format!(
+            "Embedding of {} dimensions, expected {} dimensions",
+            embedding.vec.len(),
+            ndims
+        ))),
+        None => Ok(embeddings),
+    }
+}
+
+async fn embed_batch_with_retries<M: EmbeddingModel>(
+    model: &M,
+    texts: Vec<String>,
+    retry: Option<&RetryConfig>,
+) -> Result<Vec<Embedding>, EmbeddingError> {
+    let Some(retry) = retry else {
+        return model.embed_texts(texts).await;
+    };
+
+    let deadline = retry.deadline_from(time::Instant::now());
+    let mut backoff = retry.initial_backoff;
+    let mut retries = 0;
+
+    loop {
+        let error = match model.embed_texts(texts.clone()).await {
+            Ok(embeddings) => return Ok(embeddings),
+            Err(error) => error,
+        };
+
+        let (retryable, retry_after) = crate::error::embedding_error_retry(&error);
+        if !retryable || retries >= retry.max_retries {
+            return Err(error);
+        }
+
+        // Wait at least as long as asked by the provider, but not past the deadline
+        let delay = retry_after.map_or(backoff, |retry_after| retry_after.max(backoff));
+        if deadline.is_some_and(|deadline| {
+            deadline.saturating_duration_since(time::Instant::now()) <= delay
+        }) {
+            return Err(error);
+        }
+
+        tracing::warn!(target: "rig",
+            "Embedding request failed, retrying in {:?} ({}/{}): {}",
+            delay,
+            retries + 1,
+            retry.max_retries,
+            error
+        );
+
+        time::sleep(delay).await;
+
+        retries += 1;
+        backoff = (backoff * 2).min(retry.max_backoff);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -148,7 +635,16 @@ mod tests {
         Embed,
     };
 
-    use super::EmbeddingsBuilder;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use super::{EmbeddingsBuilder, MultiEmbeddingsBuilder, TruncationPolicy};
+    use crate::{completion::retry::RetryConfig, embeddings::EmbeddingError, error::ApiError};
 
     #[derive(Clone)]
     struct Model;
@@ -160,6 +656,10 @@ mod tests {
             10
         }
 
+        fn price_per_million_tokens(&self) -> Option<f64> {
+            Some(2.0)
+        }
+
         async fn embed_texts(
             &self,
             documents: impl IntoIterator<Item = String> + Send,
@@ -366,7 +866,7 @@ mod tests {
             .unwrap();
 
         result.sort_by(|(fake_definition_1, _), (fake_definition_2, _)| {
-            fake_definition_1.cmp(&fake_definition_2)
+            fake_definition_1.cmp(fake_definition_2)
         });
 
         assert_eq!(result.len(), 2);
@@ -384,4 +884,303 @@ mod tests {
             second_definition.1.rest()[0].document, "A fictional creature found in the distant, swampy marshlands of the planet Glibbo in the Andromeda galaxy.".to_string()
         )
     }
+
+    #[tokio::test]
+    async fn test_dimension_mismatch() {
+        let model = crate::embeddings::MatryoshkaEmbeddingModel::new(Model, 4);
+        let result = EmbeddingsBuilder::new(model)
+            .document("A green alien")
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(result[0].1.first().vec.len(), 4);
+
+        // Embeddings of 10 dimensions from a model claiming 4 dimensions
+        #[derive(Clone)]
+        struct WrongModel;
+
+        impl EmbeddingModel for WrongModel {
+            const MAX_DOCUMENTS: usize = 5;
+
+            fn ndims(&self) -> usize {
+                4
+            }
+
+            async fn embed_texts(
+                &self,
+                documents: impl IntoIterator<Item = String> + Send,
+            ) -> Result<Vec<Embedding>, EmbeddingError> {
+                Model.embed_texts(documents).await
+            }
+        }
+
+        let result = EmbeddingsBuilder::new(WrongModel)
+            .document("A green alien")
+            .unwrap()
+            .build()
+            .await;
+        assert!(matches!(result, Err(EmbeddingError::ResponseError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_build_multiple_models() {
+        let fake_definitions = definitions_multiple_text();
+
+        let result = MultiEmbeddingsBuilder::new([("model_a", Model), ("model_b", Model)])
+            .documents(fake_definitions)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 2);
+
+        for name in ["model_a", "model_b"] {
+            let embeddings = &result[name];
+            assert_eq!(embeddings.len(), 2);
+
+            assert_eq!(embeddings[0].0.id, "doc0");
+            assert_eq!(embeddings[0].1.len(), 2);
+            assert_eq!(
+                embeddings[0].1.first().document,
+                "A green alien that lives on cold planets.".to_string()
+            );
+
+            assert_eq!(embeddings[1].0.id, "doc1");
+            assert_eq!(embeddings[1].1.len(), 2);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "a model named `model_a` was already added")]
+    fn test_multiple_models_duplicate_name() {
+        let _ =
+            MultiEmbeddingsBuilder::<_, String>::new([("model_a", Model)]).model("model_a", Model);
+    }
+
+    #[test]
+    #[should_panic(expected = "a model named `model_b` was already added")]
+    fn test_multiple_models_duplicate_name_in_new() {
+        let _ = MultiEmbeddingsBuilder::<_, String>::new([
+            ("model_a", Model),
+            ("model_b", Model),
+            ("model_b", Model),
+        ]);
+    }
+
+    #[test]
+    fn test_document_without_text() {
+        assert!(EmbeddingsBuilder::new(Model)
+            .document(None::<String>)
+            .is_err());
+        assert!(EmbeddingsBuilder::new(Model)
+            .document(Some("text".to_string()))
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_truncation_policy_error() {
+        let result = EmbeddingsBuilder::new(Model)
+            .documents(vec!["short".to_string(), "a".repeat(20)])
+            .unwrap()
+            .truncation_policy(TruncationPolicy::Error, 4)
+            .build()
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "DocumentError: Document 1 has a text of about 5 tokens, exceeding the maximum of 4 tokens"
+        );
+
+        let result = EmbeddingsBuilder::new(Model)
+            .documents(vec!["short".to_string(), "a".repeat(20)])
+            .unwrap()
+            .truncation_policy(TruncationPolicy::Error, 4)
+            .document_id_f(|text| format!("doc-{}", &text[..1]))
+            .build()
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "DocumentError: Document doc-a has a text of about 5 tokens, exceeding the maximum of 4 tokens"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multi_truncation_policy() {
+        let result = MultiEmbeddingsBuilder::new([("model_a", Model), ("model_b", Model)])
+            .documents(vec!["short".to_string(), "a".repeat(20)])
+            .unwrap()
+            .truncation_policy(TruncationPolicy::Split, 4)
+            .build()
+            .await
+            .unwrap();
+
+        for name in ["model_a", "model_b"] {
+            assert_eq!(result[name][1].1.len(), 2);
+            assert_eq!(result[name][1].1.first().document, "a".repeat(16));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncation_policy_truncate() {
+        let result = EmbeddingsBuilder::new(Model)
+            .documents(vec!["short".to_string(), "a".repeat(20)])
+            .unwrap()
+            .truncation_policy(TruncationPolicy::Truncate, 4)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(result[0].1.first().document, "short");
+        assert_eq!(result[1].1.len(), 1);
+        assert_eq!(result[1].1.first().document, "a".repeat(16));
+    }
+
+    #[tokio::test]
+    async fn test_truncation_policy_split() {
+        let result = EmbeddingsBuilder::new(Model)
+            .documents(vec!["short".to_string(), "a".repeat(20)])
+            .unwrap()
+            .truncation_policy(TruncationPolicy::Split, 4)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(result[0].1.len(), 1);
+        assert_eq!(result[1].1.len(), 2);
+        assert_eq!(result[1].1.first().document, "a".repeat(16));
+        assert_eq!(result[1].1.rest()[0].document, "a".repeat(4));
+    }
+
+    #[test]
+    fn test_estimated_cost() {
+        let builder = EmbeddingsBuilder::new(Model)
+            .documents(vec!["short".to_string(), "a".repeat(20)])
+            .unwrap();
+
+        let estimate = builder.estimated_cost();
+        assert_eq!(estimate.tokens, 7);
+        assert_eq!(estimate.cost, Some(7.0 * 2.0 / 1_000_000.0));
+
+        let estimate = builder
+            .truncation_policy(TruncationPolicy::Truncate, 4)
+            .estimated_cost();
+        assert_eq!(estimate.tokens, 6);
+    }
+
+    /// Model failing with a rate limit error on every other request
+    #[derive(Clone, Default)]
+    struct RateLimitedModel {
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl EmbeddingModel for RateLimitedModel {
+        const MAX_DOCUMENTS: usize = 2;
+
+        fn ndims(&self) -> usize {
+            1
+        }
+
+        async fn embed_texts(
+            &self,
+            documents: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            if self
+                .requests
+                .fetch_add(1, Ordering::SeqCst)
+                .is_multiple_of(2)
+            {
+                return Err(EmbeddingError::ApiError(ApiError::new(
+                    Some(429),
+                    "Too Many Requests",
+                )));
+            }
+
+            Ok(documents
+                .into_iter()
+                .map(|doc| Embedding {
+                    vec: vec![doc.len() as f64],
+                    document: doc,
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_progress() {
+        let progress = Arc::new(Mutex::new(vec![]));
+        let model = RateLimitedModel::default();
+
+        let result = EmbeddingsBuilder::new(model.clone())
+            .documents(vec!["a".to_string(), "bb".to_string(), "ccc".to_string()])
+            .unwrap()
+            .concurrency(1)
+            .retry(RetryConfig::default().initial_backoff(Duration::from_millis(1)))
+            .on_progress({
+                let progress = progress.clone();
+                move |p| {
+                    progress
+                        .lock()
+                        .unwrap()
+                        .push((p.batches_done, p.texts_done))
+                }
+            })
+            .build()
+            .await
+            .unwrap();
+
+        // Each of the 2 batches was retried once
+        assert_eq!(model.requests.load(Ordering::SeqCst), 4);
+        assert_eq!(result[2].1.first().vec, vec![3.0]);
+        assert_eq!(*progress.lock().unwrap(), vec![(1, 2), (2, 3)]);
+
+        // Errors which are not transient are not retried
+        #[derive(Clone)]
+        struct FailingModel(u16, Arc<AtomicUsize>);
+
+        impl EmbeddingModel for FailingModel {
+            const MAX_DOCUMENTS: usize = 2;
+
+            fn ndims(&self) -> usize {
+                1
+            }
+
+            async fn embed_texts(
+                &self,
+                _documents: impl IntoIterator<Item = String> + Send,
+            ) -> Result<Vec<Embedding>, EmbeddingError> {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                Err(EmbeddingError::ApiError(ApiError::new(
+                    Some(self.0),
+                    "Error",
+                )))
+            }
+        }
+
+        for (status, attempts) in [(400, 1), (401, 1), (404, 1), (408, 3), (500, 3), (503, 3)] {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let result = EmbeddingsBuilder::new(FailingModel(status, requests.clone()))
+                .documents(vec!["a".to_string()])
+                .unwrap()
+                .retry(
+                    RetryConfig::default()
+                        .max_retries(2)
+                        .initial_backoff(Duration::from_millis(1)),
+                )
+                .build()
+                .await;
+            assert!(result.is_err());
+            assert_eq!(requests.load(Ordering::SeqCst), attempts, "status {status}");
+        }
+
+        // Without retries, the first error is returned
+        let result = EmbeddingsBuilder::new(RateLimitedModel::default())
+            .documents(vec!["a".to_string()])
+            .unwrap()
+            .build()
+            .await;
+        assert!(matches!(result, Err(EmbeddingError::ApiError(_))));
+    }
 }
@@ -0,0 +1,417 @@
+//! This module provides the [EmbeddingCache] struct, an embedding model wrapper which caches
+//! the embeddings of the texts it embeds, so identical texts are only embedded once.
+//!
+//! Embeddings are keyed by the name of the model and a SHA-256 hash of the model name and the
+//! text. They are kept in an
+//! in-memory LRU cache and, optionally, in a persistent [EmbeddingCacheBackend] (e.g.: a
+//! [DiskCacheBackend]) so they survive restarts (e.g.: when re-running an ingestion pipeline).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     embeddings::{cache::{DiskCacheBackend, EmbeddingCache}, EmbeddingsBuilder},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let model = EmbeddingCache::new(
+//!     openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL),
+//!     openai::TEXT_EMBEDDING_3_SMALL,
+//!     10_000,
+//! )
+//! .backend(DiskCacheBackend::new(".embeddings_cache")?);
+//!
+//! // Only the texts which were not embedded before are sent to the provider
+//! let embeddings = EmbeddingsBuilder::new(model)
+//!     .documents(documents)?
+//!     .build()
+//!     .await?;
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+
+use super::{Embedding, EmbeddingError, EmbeddingModel};
+
+/// Persistent storage of cached embeddings, keyed by strings made of the model name and a hash
+/// of the text (see [EmbeddingCache]).
+pub trait EmbeddingCacheBackend: Send + Sync {
+    /// Get the embedding vector stored under `key`, if any.
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<Vec<f64>>>;
+
+    /// Store the embedding vector under `key`. Storage failures should not fail the
+    /// embedding request (the embedding is simply not cached).
+    fn insert<'a>(&'a self, key: &'a str, vec: &'a [f64]) -> BoxFuture<'a, ()>;
+}
+
+/// Cache key of a text: the model name (made safe for file names, for readability) and the
+/// SHA-256 hash of the model name and the text, so texts of models whose names only differ by
+/// their unsafe characters have different keys. Also used to key cached completions
+/// (see [CachedCompletionModel](crate::completion::cache::CachedCompletionModel)).
+pub(crate) fn cache_key(model_name: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    // The length of the model name separates it from the text
+    hasher.update((model_name.len() as u64).to_le_bytes());
+    hasher.update(model_name);
+    hasher.update(text);
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let model_name = model_name.replace(
+        |c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '.',
+        "_",
+    );
+
+    format!("{model_name}-{hash}")
+}
+
+/// Run the blocking `f` (e.g.: file system I/O of disk caches) on its own thread, so it does
+/// not block the async runtime.
+pub(crate) async fn unblock<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    // No threads in the browser
+    #[cfg(target_arch = "wasm32")]
+    return f();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(f());
+        });
+        receiver.await.expect("Blocking task should not panic")
+    }
+}
+
+/// Backend storing each embedding as a JSON file in a directory.
+#[derive(Clone, Debug)]
+pub struct DiskCacheBackend {
+    dir: PathBuf,
+}
+
+impl DiskCacheBackend {
+    /// Create a backend storing the embeddings in `dir` (which is created if it does not exist).
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl EmbeddingCacheBackend for DiskCacheBackend {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<Vec<f64>>> {
+        let path = self.path(key);
+        Box::pin(unblock(move || {
+            let content = std::fs::read(path).ok()?;
+            serde_json::from_slice(&content).ok()
+        }))
+    }
+
+    fn insert<'a>(&'a self, key: &'a str, vec: &'a [f64]) -> BoxFuture<'a, ()> {
+        let path = self.path(key);
+        let content = serde_json::to_vec(vec);
+        Box::pin(async move {
+            let result = match content {
+                Ok(content) => unblock(move || std::fs::write(path, content)).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                tracing::warn!(target: "rig", "Failed to cache embedding {}: {}", key, e);
+            }
+        })
+    }
+}
+
+/// Embedding model wrapper caching the embeddings of the texts it embeds.
+#[derive(Clone)]
+pub struct EmbeddingCache<M: EmbeddingModel> {
+    model: M,
+    model_name: String,
+    memory: Arc<Mutex<LruCache>>,
+    backend: Option<Arc<dyn EmbeddingCacheBackend>>,
+}
+
+impl<M: EmbeddingModel> EmbeddingCache<M> {
+    /// Wrap `model` (identified by `model_name` in the cache keys) with an in-memory cache of
+    /// at most `capacity` embeddings.
+    pub fn new(model: M, model_name: &str, capacity: usize) -> Self {
+        Self {
+            model,
+            model_name: model_name.to_string(),
+            memory: Arc::new(Mutex::new(LruCache::new(capacity))),
+            backend: None,
+        }
+    }
+
+    /// Also store the embeddings in `backend`, which is looked up on in-memory cache misses.
+    pub fn backend(mut self, backend: impl EmbeddingCacheBackend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Number of embeddings in the in-memory cache.
+    pub fn len(&self) -> usize {
+        self.memory().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn key(&self, text: &str) -> String {
+        cache_key(&self.model_name, text)
+    }
+
+    async fn lookup(&self, key: &str) -> Option<Vec<f64>> {
+        if let Some(vec) = self.memory().get(key) {
+            return Some(vec);
+        }
+
+        let vec = self.backend.as_ref()?.get(key).await?;
+        self.memory().insert(key.to_string(), vec.clone());
+        Some(vec)
+    }
+
+    fn memory(&self) -> std::sync::MutexGuard<'_, LruCache> {
+        self.memory
+            .lock()
+            .expect("Embedding cache lock should not be poisoned")
+    }
+}
+
+impl<M: EmbeddingModel> EmbeddingModel for EmbeddingCache<M> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        self.model.model_name()
+    }
+
+    fn price_per_million_tokens(&self) -> Option<f64> {
+        self.model.price_per_million_tokens()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        let mut entries = vec![];
+        for text in texts {
+            let key = self.key(&text);
+            let vec = self.lookup(&key).await;
+            entries.push((text, key, vec));
+        }
+        let texts = entries;
+
+        // Embed the texts missing from the cache (once each, even if repeated)
+        let mut missing = texts
+            .iter()
+            .filter(|(_, _, vec)| vec.is_none())
+            .map(|(text, key, _)| (key.clone(), text.clone()))
+            .collect::<HashMap<_, _>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        missing.sort();
+
+        tracing::debug!(target: "rig",
+            "Embedding cache: {} hits, {} misses",
+            texts.iter().filter(|(_, _, vec)| vec.is_some()).count(),
+            missing.len()
+        );
+
+        let mut embedded = HashMap::new();
+        if !missing.is_empty() {
+            let (keys, missing_texts): (Vec<_>, Vec<_>) = missing.into_iter().unzip();
+            let embeddings = self.model.embed_texts(missing_texts).await?;
+
+            for (key, embedding) in keys.into_iter().zip(embeddings) {
+                if let Some(backend) = &self.backend {
+                    backend.insert(&key, &embedding.vec).await;
+                }
+                self.memory().insert(key.clone(), embedding.vec.clone());
+                embedded.insert(key, embedding.vec);
+            }
+        }
+
+        texts
+            .into_iter()
+            .map(|(text, key, vec)| {
+                let vec = vec.or_else(|| embedded.get(&key).cloned()).ok_or_else(|| {
+                    EmbeddingError::ResponseError(format!("Missing embedding for text: {text}"))
+                })?;
+                Ok(Embedding {
+                    document: text,
+                    vec,
+                })
+            })
+            .collect()
+    }
+}
+
+/// In-memory cache evicting the least recently used entries beyond its capacity.
+struct LruCache {
+    capacity: usize,
+    /// Entries with the tick of their last use
+    entries: HashMap<String, (Vec<f64>, u64)>,
+    /// Keys of the entries, by tick of last use
+    usage: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&mut self, key: &str) -> Option<Vec<f64>> {
+        self.tick += 1;
+        let (vec, last_used) = self.entries.get_mut(key)?;
+        let key = self
+            .usage
+            .remove(last_used)
+            .expect("Entry should be in usage");
+        *last_used = self.tick;
+        self.usage.insert(self.tick, key);
+        Some(vec.clone())
+    }
+
+    fn insert(&mut self, key: String, vec: Vec<f64>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (vec, self.tick)) {
+            self.usage.remove(&last_used);
+        }
+        self.usage.insert(self.tick, key);
+
+        while self.entries.len() > self.capacity {
+            let (_, key) = self.usage.pop_first().expect("Usage should not be empty");
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Model embedding texts as their length, counting the texts it embeds
+    #[derive(Clone, Default)]
+    struct Model {
+        embedded: Arc<AtomicUsize>,
+    }
+
+    impl EmbeddingModel for Model {
+        const MAX_DOCUMENTS: usize = 10;
+
+        fn ndims(&self) -> usize {
+            1
+        }
+
+        async fn embed_texts(
+            &self,
+            texts: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            Ok(texts
+                .into_iter()
+                .map(|text| {
+                    self.embedded.fetch_add(1, Ordering::SeqCst);
+                    Embedding {
+                        vec: vec![text.len() as f64],
+                        document: text,
+                    }
+                })
+                .collect())
+        }
+    }
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let model = Model::default();
+        let cache = EmbeddingCache::new(model.clone(), "model", 2);
+
+        let embeddings = cache.embed_texts(texts(&["a", "bb", "a"])).await.unwrap();
+        assert_eq!(
+            embeddings.iter().map(|e| e.vec[0]).collect::<Vec<_>>(),
+            vec![1.0, 2.0, 1.0]
+        );
+        assert_eq!(embeddings[2].document, "a");
+        assert_eq!(model.embedded.load(Ordering::SeqCst), 2);
+
+        // "a" is cached, "ccc" evicts the least recently used entry ("bb")
+        cache.embed_texts(texts(&["a", "ccc"])).await.unwrap();
+        assert_eq!(model.embedded.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 2);
+
+        cache.embed_texts(texts(&["bb"])).await.unwrap();
+        assert_eq!(model.embedded.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_disk_backend() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let model = Model::default();
+
+        let cache = EmbeddingCache::new(model.clone(), "model", 10)
+            .backend(DiskCacheBackend::new(dir.path()).unwrap());
+        cache.embed_texts(texts(&["a", "bb"])).await.unwrap();
+
+        // A new cache (e.g.: after a restart) reuses the embeddings stored on disk
+        let cache = EmbeddingCache::new(model.clone(), "model", 10)
+            .backend(DiskCacheBackend::new(dir.path()).unwrap());
+        let embeddings = cache.embed_texts(texts(&["bb", "a"])).await.unwrap();
+        assert_eq!(embeddings[0].vec, vec![2.0]);
+        assert_eq!(model.embedded.load(Ordering::SeqCst), 2);
+
+        // Embeddings of other models are not reused
+        let cache = EmbeddingCache::new(model.clone(), "other-model", 10)
+            .backend(DiskCacheBackend::new(dir.path()).unwrap());
+        cache.embed_texts(texts(&["a"])).await.unwrap();
+        assert_eq!(model.embedded.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let key = cache_key("model", "text");
+        assert_eq!(key.len(), "model-".len() + 64);
+        assert_ne!(key, cache_key("model", "text "));
+        // Model names made the same by their sanitization still have different keys
+        assert_ne!(
+            cache_key("org/model", "text"),
+            cache_key("org_model", "text")
+        );
+        assert!(cache_key("org/model", "text").starts_with("org_model-"));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::embeddings::Embedding;
+
 pub trait VectorDistance {
     /// Get dot product of two embedding vectors
     fn dot_product(&self, other: &Self) -> f64;
@@ -19,27 +21,109 @@ pub trait VectorDistance {
     fn chebyshev_distance(&self, other: &Self) -> f64;
 }
 
-#[cfg(not(feature = "rayon"))]
-impl VectorDistance for crate::embeddings::Embedding {
+/// Metric by which embedding vectors are compared in vector searches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// Cosine similarity
+    #[default]
+    Cosine,
+    /// Cosine similarity of vectors normalized to unit length (e.g.: OpenAI embeddings), computed
+    /// as their dot product, skipping the norms
+    NormalizedCosine,
+    /// Dot product
+    DotProduct,
+    /// Euclidean (L2) distance `d`, as the similarity `1 / (1 + d)`
+    Euclidean,
+}
+
+impl DistanceMetric {
+    /// Similarity of two embedding vectors under this metric: higher is more similar, so the
+    /// Euclidean distance `d` is converted to the similarity `1 / (1 + d)`, in (0, 1].
+    pub fn similarity(self, a: &Embedding, b: &Embedding) -> f64 {
+        match self {
+            Self::Cosine => a.cosine_similarity(b, false),
+            Self::NormalizedCosine => a.cosine_similarity(b, true),
+            Self::DotProduct => a.dot_product(b),
+            Self::Euclidean => 1.0 / (1.0 + a.euclidean_distance(b)),
+        }
+    }
+}
+
+/// Number of independent accumulators of the loops over the dimensions of the vectors.
+/// Accumulating lane by lane (instead of into a single sum, whose order of additions must be
+/// preserved) lets the compiler vectorize the loops with SIMD instructions.
+const LANES: usize = 8;
+
+/// Sum of `f(x, y)` over the dimensions of `a` and `b`, accumulated lane by lane.
+#[inline]
+fn lanewise_sum(a: &[f64], b: &[f64], f: impl Fn(f64, f64) -> f64) -> f64 {
+    let len = a.len().min(b.len());
+    let (a, b) = (a[..len].chunks_exact(LANES), b[..len].chunks_exact(LANES));
+    let remainder = a
+        .remainder()
+        .iter()
+        .zip(b.remainder())
+        .map(|(x, y)| f(*x, *y))
+        .sum::<f64>();
+
+    let mut sums = [0.0; LANES];
+    for (a, b) in a.zip(b) {
+        for ((sum, x), y) in sums.iter_mut().zip(a).zip(b) {
+            *sum += f(*x, *y);
+        }
+    }
+    sums.iter().sum::<f64>() + remainder
+}
+
+/// Dot product of `a` and `b` and squared norms of `a` and `b`, in a single pass accumulated lane
+/// by lane.
+#[inline]
+fn dot_and_norms(a: &[f64], b: &[f64]) -> (f64, f64, f64) {
+    let len = a.len().min(b.len());
+    let (chunks_a, chunks_b) = (a[..len].chunks_exact(LANES), b[..len].chunks_exact(LANES));
+    let (remainder_a, remainder_b) = (chunks_a.remainder(), chunks_b.remainder());
+
+    let mut dots = [0.0; LANES];
+    let mut norms_a = [0.0; LANES];
+    let mut norms_b = [0.0; LANES];
+    for (a, b) in chunks_a.zip(chunks_b) {
+        for i in 0..LANES {
+            dots[i] += a[i] * b[i];
+            norms_a[i] += a[i] * a[i];
+            norms_b[i] += b[i] * b[i];
+        }
+    }
+
+    let (mut dot, mut norm_a, mut norm_b) = (
+        dots.iter().sum::<f64>(),
+        norms_a.iter().sum::<f64>(),
+        norms_b.iter().sum::<f64>(),
+    );
+    for (x, y) in remainder_a.iter().zip(remainder_b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    // The dimensions of the longest vector beyond the length of the other one only count in its
+    // norm
+    norm_a += a[len..].iter().map(|x| x * x).sum::<f64>();
+    norm_b += b[len..].iter().map(|y| y * y).sum::<f64>();
+
+    (dot, norm_a, norm_b)
+}
+
+impl VectorDistance for Embedding {
     fn dot_product(&self, other: &Self) -> f64 {
-        self.vec
-            .iter()
-            .zip(other.vec.iter())
-            .map(|(x, y)| x * y)
-            .sum()
+        lanewise_sum(&self.vec, &other.vec, |x, y| x * y)
     }
 
     fn cosine_similarity(&self, other: &Self, normalized: bool) -> f64 {
-        let dot_product = self.dot_product(other);
-
         if normalized {
-            dot_product
-        } else {
-            let magnitude1: f64 = self.vec.iter().map(|x| x.powi(2)).sum::<f64>().sqrt();
-            let magnitude2: f64 = other.vec.iter().map(|x| x.powi(2)).sum::<f64>().sqrt();
-
-            dot_product / (magnitude1 * magnitude2)
+            return self.dot_product(other);
         }
+
+        let (dot_product, norm1, norm2) = dot_and_norms(&self.vec, &other.vec);
+        dot_product / (norm1.sqrt() * norm2.sqrt())
     }
 
     fn angular_distance(&self, other: &Self, normalized: bool) -> f64 {
@@ -48,20 +132,11 @@ impl VectorDistance for crate::embeddings::Embedding {
     }
 
     fn euclidean_distance(&self, other: &Self) -> f64 {
-        self.vec
-            .iter()
-            .zip(other.vec.iter())
-            .map(|(x, y)| (x - y).powi(2))
-            .sum::<f64>()
-            .sqrt()
+        lanewise_sum(&self.vec, &other.vec, |x, y| (x - y).powi(2)).sqrt()
     }
 
     fn manhattan_distance(&self, other: &Self) -> f64 {
-        self.vec
-            .iter()
-            .zip(other.vec.iter())
-            .map(|(x, y)| (x - y).abs())
-            .sum()
+        lanewise_sum(&self.vec, &other.vec, |x, y| (x - y).abs())
     }
 
     fn chebyshev_distance(&self, other: &Self) -> f64 {
@@ -73,68 +148,9 @@ impl VectorDistance for crate::embeddings::Embedding {
     }
 }
 
-#[cfg(feature = "rayon")]
-mod rayon {
-    use crate::embeddings::{distance::VectorDistance, Embedding};
-    use rayon::prelude::*;
-
-    impl VectorDistance for Embedding {
-        fn dot_product(&self, other: &Self) -> f64 {
-            self.vec
-                .par_iter()
-                .zip(other.vec.par_iter())
-                .map(|(x, y)| x * y)
-                .sum()
-        }
-
-        fn cosine_similarity(&self, other: &Self, normalized: bool) -> f64 {
-            let dot_product = self.dot_product(other);
-
-            if normalized {
-                dot_product
-            } else {
-                let magnitude1: f64 = self.vec.par_iter().map(|x| x.powi(2)).sum::<f64>().sqrt();
-                let magnitude2: f64 = other.vec.par_iter().map(|x| x.powi(2)).sum::<f64>().sqrt();
-
-                dot_product / (magnitude1 * magnitude2)
-            }
-        }
-
-        fn angular_distance(&self, other: &Self, normalized: bool) -> f64 {
-            let cosine_sim = self.cosine_similarity(other, normalized);
-            cosine_sim.acos() / std::f64::consts::PI
-        }
-
-        fn euclidean_distance(&self, other: &Self) -> f64 {
-            self.vec
-                .par_iter()
-                .zip(other.vec.par_iter())
-                .map(|(x, y)| (x - y).powi(2))
-                .sum::<f64>()
-                .sqrt()
-        }
-
-        fn manhattan_distance(&self, other: &Self) -> f64 {
-            self.vec
-                .par_iter()
-                .zip(other.vec.par_iter())
-                .map(|(x, y)| (x - y).abs())
-                .sum()
-        }
-
-        fn chebyshev_distance(&self, other: &Self) -> f64 {
-            self.vec
-                .iter()
-                .zip(other.vec.iter())
-                .map(|(x, y)| (x - y).abs())
-                .fold(0.0, f64::max)
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use super::VectorDistance;
+    use super::{DistanceMetric, VectorDistance};
     use crate::embeddings::Embedding;
 
     fn embeddings() -> (Embedding, Embedding) {
@@ -198,4 +214,61 @@ mod tests {
 
         assert_eq!(embedding_1.chebyshev_distance(&embedding_2), 4.0)
     }
+
+    #[test]
+    fn test_distance_metrics() {
+        let (embedding_1, embedding_2) = embeddings();
+
+        assert_eq!(
+            DistanceMetric::Cosine.similarity(&embedding_1, &embedding_2),
+            0.9875414397573881
+        );
+        assert_eq!(
+            DistanceMetric::NormalizedCosine.similarity(&embedding_1, &embedding_2),
+            32.0
+        );
+        assert_eq!(
+            DistanceMetric::DotProduct.similarity(&embedding_1, &embedding_2),
+            32.0
+        );
+
+        // Distance of 5
+        assert_eq!(
+            DistanceMetric::Euclidean.similarity(&embedding_1, &embedding_2),
+            1.0 / 6.0
+        );
+    }
+
+    #[test]
+    fn test_lanewise_distances() {
+        // Lengths which are not a multiple of the number of lanes
+        let embedding = |len: usize, f: fn(f64) -> f64| Embedding {
+            document: "test".to_string(),
+            vec: (0..len).map(|i| f(i as f64)).collect(),
+        };
+        let embedding_1 = embedding(1539, |x| (x * 0.1).sin());
+        let embedding_2 = embedding(1539, |x| (x * 0.07).cos());
+
+        let dot = (0..1539)
+            .map(|i| embedding_1.vec[i] * embedding_2.vec[i])
+            .sum::<f64>();
+        let norm = |e: &Embedding| e.vec.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+        assert!((embedding_1.dot_product(&embedding_2) - dot).abs() < 1e-9);
+        assert!(
+            (embedding_1.cosine_similarity(&embedding_2, false)
+                - dot / (norm(&embedding_1) * norm(&embedding_2)))
+            .abs()
+                < 1e-12
+        );
+        assert!(
+            (embedding_1.euclidean_distance(&embedding_2)
+                - (0..1539)
+                    .map(|i| (embedding_1.vec[i] - embedding_2.vec[i]).powi(2))
+                    .sum::<f64>()
+                    .sqrt())
+            .abs()
+                < 1e-9
+        );
+    }
 }
@@ -64,23 +64,55 @@ pub trait Embed {
     fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError>;
 }
 
+/// A text to embed, with the name of the field of the document it comes from (if any).
+pub(crate) type FieldText = (Option<String>, String);
+
 /// Accumulates string values that need to be embedded.
 /// Used by the [Embed] trait.
+///
+/// Texts can be tagged with the name of the field of the document they come from (see
+/// [TextEmbedder::embed_field]), so that searches can weigh the fields of structured documents
+/// differently (e.g.: a match on the title counting more than a match on the body).
 #[derive(Default)]
 pub struct TextEmbedder {
-    pub(crate) texts: Vec<String>,
+    /// Texts to embed, with the name of their field
+    pub(crate) texts: Vec<FieldText>,
+    field: Option<String>,
 }
 
 impl TextEmbedder {
     /// Adds input `text` string to the list of texts in the [TextEmbedder] that need to be embedded.
     pub fn embed(&mut self, text: String) {
-        self.texts.push(text);
+        self.texts.push((self.field.clone(), text));
+    }
+
+    /// Adds the texts of `value` to the texts that need to be embedded, as texts of the field
+    /// `field` of the document (see [InMemoryVectorStore::field_weights](crate::vector_store::in_memory_store::InMemoryVectorStore::field_weights)).
+    pub fn embed_field(&mut self, field: &str, value: &impl Embed) -> Result<(), EmbedError> {
+        self.in_field(field, |embedder| value.embed(embedder))
+    }
+
+    /// Runs `embed`, tagging the texts it adds with the field `field` (e.g.: for custom embedding
+    /// functions of a field).
+    pub fn in_field<R>(&mut self, field: &str, embed: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = self.field.replace(field.to_string());
+        let result = embed(self);
+        self.field = previous;
+        result
     }
 }
 
 /// Utility function that returns a vector of strings that need to be embedded for a
 /// given object that implements the [Embed] trait.
 pub fn to_texts(item: impl Embed) -> Result<Vec<String>, EmbedError> {
+    let mut embedder = TextEmbedder::default();
+    item.embed(&mut embedder)?;
+    Ok(embedder.texts.into_iter().map(|(_, text)| text).collect())
+}
+
+/// Texts that need to be embedded for `item`, with the name of their field (see
+/// [TextEmbedder::embed_field]).
+pub(crate) fn to_field_texts(item: &impl Embed) -> Result<Vec<FieldText>, EmbedError> {
     let mut embedder = TextEmbedder::default();
     item.embed(&mut embedder)?;
     Ok(embedder.texts)
@@ -188,3 +220,13 @@ impl<T: Embed> Embed for Vec<T> {
         Ok(())
     }
 }
+
+/// Embeds the value if it is `Some`, nothing otherwise.
+impl<T: Embed> Embed for Option<T> {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        match self {
+            Some(item) => item.embed(embedder),
+            None => Ok(()),
+        }
+    }
+}
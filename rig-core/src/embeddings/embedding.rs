@@ -6,7 +6,12 @@
 //! Finally, the module defines the [EmbeddingError] enum, which represents various errors that
 //! can occur during embedding generation or processing.
 
+use web_time::Instant;
+
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use crate::telemetry;
 
 #[derive(Debug, thiserror::Error)]
 pub enum EmbeddingError {
@@ -29,6 +34,16 @@ pub enum EmbeddingError {
     /// Error returned by the embedding model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Error returned by the embedding model provider, with its status and code (see
+    /// [rig::Error](crate::Error) to classify it)
+    #[error("ApiError: {0}")]
+    ApiError(#[from] crate::error::ApiError),
+
+    /// The request was shed by a [RateLimiter](crate::rate_limit::RateLimiter), and could be
+    /// sent after the given delay
+    #[error("RateLimitError: rate limit exceeded, retry after {0:?}")]
+    RateLimitError(std::time::Duration),
 }
 
 /// Trait for embedding models that can generate embeddings for documents.
@@ -39,6 +54,18 @@ pub trait EmbeddingModel: Clone + Sync + Send {
     /// The number of dimensions in the embedding vector.
     fn ndims(&self) -> usize;
 
+    /// The name of the model (e.g.: `text-embedding-3-small`), if known. Used to annotate the
+    /// telemetry of embedding requests (see [telemetry](crate::telemetry)).
+    fn model_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// The price (in US dollars) of embedding one million tokens with the model, if known.
+    /// Used to estimate the cost of embedding jobs (see [EmbeddingsBuilder::estimated_cost](crate::embeddings::EmbeddingsBuilder::estimated_cost)).
+    fn price_per_million_tokens(&self) -> Option<f64> {
+        None
+    }
+
     /// Embed multiple text documents in a single request
     fn embed_texts(
         &self,
@@ -50,13 +77,18 @@ pub trait EmbeddingModel: Clone + Sync + Send {
         &self,
         text: &str,
     ) -> impl std::future::Future<Output = Result<Embedding, EmbeddingError>> + Send {
-        async {
-            Ok(self
-                .embed_texts(vec![text.to_string()])
-                .await?
+        let span = telemetry::embeddings_span(self.model_name(), 1);
+        let text = text.to_string();
+        async move {
+            let start = Instant::now();
+            let result = self.embed_texts(vec![text]).await;
+            telemetry::record_result(&tracing::Span::current(), start, &result);
+
+            Ok(result?
                 .pop()
                 .expect("There should be at least one embedding"))
         }
+        .instrument(span)
     }
 }
 
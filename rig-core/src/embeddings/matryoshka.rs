@@ -0,0 +1,108 @@
+//! This module provides the [MatryoshkaEmbeddingModel] struct, an embedding model wrapper which
+//! shortens the embeddings of models trained with Matryoshka representation learning (e.g.:
+//! `nomic-embed-text-v1.5`, `mxbai-embed-large-v1`), whose leading dimensions carry most of the
+//! meaning. Shorter embeddings make vector stores smaller and searches faster, at a small cost
+//! in retrieval quality.
+//!
+//! The embeddings are truncated to the first `ndims` dimensions and normalized to unit length.
+//! Providers which shorten the embeddings themselves should be preferred when available (e.g.:
+//! OpenAI's `text-embedding-3` models, see
+//! [EmbeddingModel::dimensions](crate::providers::openai::EmbeddingModel::dimensions)).
+//!
+//! # Example
+//! ```rust
+//! use rig::{embeddings::matryoshka::MatryoshkaEmbeddingModel, providers::ollama};
+//!
+//! let ollama = ollama::Client::new();
+//!
+//! // 768 dimensions shortened to 256
+//! let model = MatryoshkaEmbeddingModel::new(ollama.embedding_model("nomic-embed-text"), 256);
+//! ```
+use super::{Embedding, EmbeddingError, EmbeddingModel};
+
+/// Embedding model whose embeddings are truncated to `ndims` dimensions and normalized.
+#[derive(Clone)]
+pub struct MatryoshkaEmbeddingModel<M: EmbeddingModel> {
+    model: M,
+    ndims: usize,
+}
+
+impl<M: EmbeddingModel> MatryoshkaEmbeddingModel<M> {
+    /// Shorten the embeddings of `model` to `ndims` dimensions, which should not exceed the
+    /// number of dimensions of the model.
+    pub fn new(model: M, ndims: usize) -> Self {
+        Self { model, ndims }
+    }
+
+    /// Truncate `vec` to the number of dimensions of the model and normalize it.
+    fn truncate(&self, mut vec: Vec<f64>) -> Result<Vec<f64>, EmbeddingError> {
+        if vec.len() < self.ndims {
+            return Err(EmbeddingError::ResponseError(format!(
+                "Embedding of {} dimensions cannot be shortened to {} dimensions",
+                vec.len(),
+                self.ndims
+            )));
+        }
+
+        vec.truncate(self.ndims);
+        let norm = vec.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            vec.iter_mut().for_each(|x| *x /= norm);
+        }
+        Ok(vec)
+    }
+}
+
+impl<M: EmbeddingModel> EmbeddingModel for MatryoshkaEmbeddingModel<M> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        self.model.model_name()
+    }
+
+    fn price_per_million_tokens(&self) -> Option<f64> {
+        self.model.price_per_million_tokens()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.model
+            .embed_texts(texts)
+            .await?
+            .into_iter()
+            .map(|embedding| {
+                Ok(Embedding {
+                    vec: self.truncate(embedding.vec)?,
+                    document: embedding.document,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MatryoshkaEmbeddingModel;
+    use crate::{embeddings::EmbeddingModel, providers::mock::MockEmbeddingModel};
+
+    #[tokio::test]
+    async fn test_matryoshka_embedding_model() {
+        let model = MatryoshkaEmbeddingModel::new(
+            MockEmbeddingModel::new(4).embedding("hello", vec![3.0, 4.0, 5.0, 6.0]),
+            2,
+        );
+
+        assert_eq!(model.ndims(), 2);
+        assert_eq!(model.embed_text("hello").await.unwrap().vec, vec![0.6, 0.8]);
+        assert!(MatryoshkaEmbeddingModel::new(MockEmbeddingModel::new(4), 8)
+            .embed_text("hello")
+            .await
+            .is_err());
+    }
+}
@@ -4,12 +4,17 @@
 //! and document similarity.
 
 pub mod builder;
+pub mod cache;
 pub mod embed;
 pub mod embedding;
+pub mod matryoshka;
 pub mod tool;
 
 pub mod distance;
-pub use builder::EmbeddingsBuilder;
+pub use builder::{
+    CostEstimate, EmbeddingProgress, EmbeddingsBuilder, MultiEmbeddingsBuilder, TruncationPolicy,
+};
 pub use embed::{to_texts, Embed, EmbedError, TextEmbedder};
 pub use embedding::{Embedding, EmbeddingError, EmbeddingModel};
+pub use matryoshka::MatryoshkaEmbeddingModel;
 pub use tool::ToolSchema;
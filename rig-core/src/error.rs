@@ -0,0 +1,676 @@
+//! This module provides [Error], a structured view of the errors of Rig, so applications can
+//! branch on failure modes (e.g.: shorten the prompt when it exceeds the context length of the
+//! model, ask for a new API key when it is rejected) instead of parsing error strings.
+//!
+//! Every error of Rig (e.g.: [CompletionError], [PromptError], [EmbeddingError]) converts into
+//! an [Error], which exposes:
+//! - The [ErrorKind] of the failure,
+//! - The error reported by the provider ([ApiError]): its HTTP status, its code (e.g.:
+//!   `context_length_exceeded`) and its message,
+//! - Whether the failed operation is worth retrying ([Error::is_retryable]).
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::Prompt, error::ErrorKind};
+//!
+//! match agent.prompt(question).await.map_err(rig::Error::from) {
+//!     Ok(answer) => println!("{answer}"),
+//!     Err(error) if error.kind() == ErrorKind::ContextLength => {
+//!         // Retry with a shorter chat history
+//!     }
+//!     Err(error) if error.is_retryable() => {
+//!         // Retry later, e.g.: after `error.retry_after()`
+//!     }
+//!     Err(error) => eprintln!("{:?}: {}", error.kind(), error),
+//! }
+//! ```
+use std::{fmt, time::Duration};
+
+use serde_json::Value;
+
+use crate::{
+    completion::{CompletionError, PromptError},
+    embeddings::EmbeddingError,
+    moderation::ModerationError,
+};
+
+/// The failure mode of an [Error].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The credentials were rejected, or lack the permissions of the request (e.g.: HTTP 401)
+    Auth,
+    /// The rate limits or the quota of the account were exceeded (e.g.: HTTP 429)
+    RateLimit,
+    /// The request exceeds the context length of the model
+    ContextLength,
+    /// The request or the answer was blocked by a content filter (of the provider, or of a
+    /// [guardrail](crate::agent::AgentBuilder::with_input_guard))
+    ContentFilter,
+    /// The provider could not be reached (e.g.: connection error)
+    Network,
+    /// The request did not complete in time
+    Timeout,
+    /// The request was rejected by the provider as invalid (e.g.: HTTP 400), or could not be built
+    InvalidRequest,
+    /// The response of the provider could not be parsed
+    InvalidResponse,
+    /// The provider failed to process the request (e.g.: HTTP 5xx, overloaded)
+    Server,
+    /// An error reported by the provider which could not be classified
+    Provider,
+    /// A tool call failed
+    Tool,
+    /// The operation was cancelled (see [CancellationToken](crate::cancel::CancellationToken))
+    Cancelled,
+    /// Any other error (e.g.: a session store error)
+    Other,
+}
+
+impl ErrorKind {
+    /// The kind of a failed request with HTTP status `status`.
+    fn from_status(status: u16) -> Option<Self> {
+        match status {
+            401 | 403 => Some(ErrorKind::Auth),
+            408 => Some(ErrorKind::Timeout),
+            413 => Some(ErrorKind::ContextLength),
+            429 => Some(ErrorKind::RateLimit),
+            400..=499 => Some(ErrorKind::InvalidRequest),
+            500..=599 => Some(ErrorKind::Server),
+            _ => None,
+        }
+    }
+}
+
+/// An error reported by a provider, with its HTTP status (if known), its code (e.g.:
+/// `context_length_exceeded`, `rate_limit_error`, `RESOURCE_EXHAUSTED`) and its message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiError {
+    pub status: Option<u16>,
+    /// The error code of the provider, or its error type if it has no code
+    pub code: Option<String>,
+    pub message: String,
+    /// The delay after which the request can be retried, if the provider sent one
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.status, &self.code) {
+            (Some(status), Some(code)) => write!(f, "{status} {code}: {}", self.message),
+            (Some(status), None) => write!(f, "{status}: {}", self.message),
+            (None, Some(code)) => write!(f, "{code}: {}", self.message),
+            (None, None) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// Parse the error `body` returned by a provider. The error formats of the main providers
+    /// (e.g.: `{"error": {"code": .., "message": ..}}`) are recognized, other bodies are used as
+    /// the message.
+    pub fn new(status: Option<u16>, body: &str) -> Self {
+        let mut error = Self {
+            status,
+            code: None,
+            message: body.to_string(),
+            retry_after: None,
+        };
+
+        let Ok(value) = serde_json::from_str::<Value>(body) else {
+            return error;
+        };
+        let details = match value.get("error") {
+            Some(Value::String(message)) => {
+                error.message = message.clone();
+                return error;
+            }
+            Some(details @ Value::Object(_)) => details,
+            _ => &value,
+        };
+
+        // e.g.: OpenAI `code` (falling back on `type`), Anthropic `type`, Gemini `status`
+        error.code = ["code", "type", "status"]
+            .iter()
+            .find_map(|field| details.get(field).and_then(Value::as_str))
+            .map(str::to_string);
+        if let Some(message) = details.get("message").and_then(Value::as_str) {
+            error.message = message.to_string();
+        }
+        error
+    }
+
+    /// Read the error of a failed `response` of a provider (status, body and `Retry-After`
+    /// header).
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let now = web_time::SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, now));
+
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(err) => err.to_string(),
+        };
+        Self {
+            retry_after,
+            ..Self::new(Some(status), &body)
+        }
+    }
+
+    /// The kind of the error, from its code, then from its status. The message is only used
+    /// to classify the errors reported without code nor status (e.g.: error strings in the
+    /// body of a stream).
+    pub fn kind(&self) -> ErrorKind {
+        if let Some(kind) = self.code.as_deref().and_then(kind_from_text) {
+            return kind;
+        }
+        if let Some(status) = self.status {
+            return ErrorKind::from_status(status).unwrap_or(ErrorKind::Provider);
+        }
+        if self.code.is_none() {
+            if let Some(kind) = kind_from_text(&self.message) {
+                return kind;
+            }
+        }
+        ErrorKind::Provider
+    }
+}
+
+/// Patterns identifying the kinds of errors in their codes (e.g.: `context_length_exceeded`)
+/// or messages (e.g.: `Rate limit reached`), in order of precedence.
+const KIND_PATTERNS: &[(ErrorKind, &[&str])] = &[
+    (
+        ErrorKind::ContextLength,
+        &[
+            "context_length",
+            "context length",
+            "context window",
+            "maximum context",
+            "prompt is too long",
+            "too many tokens",
+        ],
+    ),
+    (
+        ErrorKind::ContentFilter,
+        &[
+            "content_filter",
+            "content filter",
+            "content_policy",
+            "content policy",
+            "content management",
+            "safety",
+        ],
+    ),
+    (
+        ErrorKind::RateLimit,
+        &["rate_limit", "rate limit", "resource_exhausted", "quota"],
+    ),
+    (
+        ErrorKind::Auth,
+        &[
+            "invalid_api_key",
+            "invalid api key",
+            "authentication",
+            "unauthenticated",
+            "permission",
+        ],
+    ),
+    (
+        ErrorKind::Server,
+        &[
+            "overloaded",
+            "server_error",
+            "server error",
+            "unavailable",
+            "internal",
+        ],
+    ),
+    (
+        ErrorKind::InvalidRequest,
+        &["invalid_request", "invalid_argument"],
+    ),
+];
+
+/// The kind of an error whose code or message is `text`, if it matches one of the
+/// [KIND_PATTERNS].
+fn kind_from_text(text: &str) -> Option<ErrorKind> {
+    let text = text.to_lowercase();
+    KIND_PATTERNS
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|pattern| text.contains(pattern)))
+        .map(|(kind, _)| *kind)
+}
+
+/// Parse the value of a `Retry-After` header: a number of seconds, or an HTTP date (e.g.:
+/// `Wed, 21 Oct 2015 07:28:00 GMT`), `now` being the time elapsed since the Unix epoch.
+/// Dates in the past give a zero delay.
+fn parse_retry_after(value: &str, now: Duration) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value)
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(date.saturating_sub(now))
+}
+
+/// A structured Rig error: the [ErrorKind] of the failure, the [ApiError] reported by the
+/// provider (if any), and the original error (see [std::error::Error::source]).
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    api_error: Option<ApiError>,
+    retry_after: Option<Duration>,
+    retryable: bool,
+    source: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl Error {
+    fn new(kind: ErrorKind, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self {
+            kind,
+            api_error: None,
+            retry_after: None,
+            retryable: is_retryable(kind, None),
+            source: Box::new(source),
+        }
+    }
+
+    fn from_api_error(
+        api_error: ApiError,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            kind: api_error.kind(),
+            retry_after: api_error.retry_after,
+            retryable: is_retryable(api_error.kind(), Some(&api_error)),
+            api_error: Some(api_error),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The error reported by the provider, if the failure was reported by the provider.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        self.api_error.as_ref()
+    }
+
+    /// The HTTP status of the failed request, if known.
+    pub fn status(&self) -> Option<u16> {
+        self.api_error.as_ref().and_then(|error| error.status)
+    }
+
+    /// The error code of the provider (e.g.: `context_length_exceeded`), if any.
+    pub fn code(&self) -> Option<&str> {
+        self.api_error
+            .as_ref()
+            .and_then(|error| error.code.as_deref())
+    }
+
+    /// The delay after which the operation can be retried, if known (e.g.: from the
+    /// `Retry-After` header of the provider, or from a [RateLimiter](crate::rate_limit::RateLimiter)).
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Whether the failed operation is worth retrying: request timeouts (HTTP 408), rate limits
+    /// (HTTP 429, except exhausted quotas), server errors (HTTP 5xx) and network errors.
+    /// Provider errors which could not be classified are not retried, nor are the requests shed
+    /// by a [RateLimiter](crate::rate_limit::RateLimiter), which already waited as long as its
+    /// configuration allows.
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
+
+/// The kind of a failed HTTP request (without response, or with an unexpected status).
+fn http_error_kind(error: &reqwest::Error) -> ErrorKind {
+    match error.status() {
+        Some(status) => ErrorKind::from_status(status.as_u16()).unwrap_or(ErrorKind::Provider),
+        None if error.is_timeout() => ErrorKind::Timeout,
+        None if error.is_decode() => ErrorKind::InvalidResponse,
+        None => ErrorKind::Network,
+    }
+}
+
+/// The kind, the provider error and the retry delay of a completion error.
+fn completion_error_details(
+    error: &CompletionError,
+) -> (ErrorKind, Option<ApiError>, Option<Duration>) {
+    match error {
+        CompletionError::HttpError(error) => (http_error_kind(error), None, None),
+        CompletionError::JsonError(_)
+        | CompletionError::ResponseError(_)
+        | CompletionError::ToolCallError { .. } => (ErrorKind::InvalidResponse, None, None),
+        CompletionError::RequestError(_) => (ErrorKind::InvalidRequest, None, None),
+        CompletionError::ProviderError(body) => {
+            let api_error = ApiError::new(None, body);
+            (api_error.kind(), Some(api_error), None)
+        }
+        CompletionError::ApiError(api_error) => (
+            api_error.kind(),
+            Some(api_error.clone()),
+            api_error.retry_after,
+        ),
+        CompletionError::TimeoutError(_) => (ErrorKind::Timeout, None, None),
+        CompletionError::RateLimitError(retry_after) => {
+            (ErrorKind::RateLimit, None, Some(*retry_after))
+        }
+    }
+}
+
+/// The kind, the provider error and the retry delay of an embedding error.
+fn embedding_error_details(
+    error: &EmbeddingError,
+) -> (ErrorKind, Option<ApiError>, Option<Duration>) {
+    match error {
+        EmbeddingError::HttpError(error) => (http_error_kind(error), None, None),
+        EmbeddingError::JsonError(_) | EmbeddingError::ResponseError(_) => {
+            (ErrorKind::InvalidResponse, None, None)
+        }
+        EmbeddingError::DocumentError(_) => (ErrorKind::InvalidRequest, None, None),
+        EmbeddingError::ProviderError(body) => {
+            let api_error = ApiError::new(None, body);
+            (api_error.kind(), Some(api_error), None)
+        }
+        EmbeddingError::ApiError(api_error) => (
+            api_error.kind(),
+            Some(api_error.clone()),
+            api_error.retry_after,
+        ),
+        EmbeddingError::RateLimitError(retry_after) => {
+            (ErrorKind::RateLimit, None, Some(*retry_after))
+        }
+    }
+}
+
+/// Whether a completion error is worth retrying (see [Error::is_retryable]), and the delay
+/// after which it can be retried, if known.
+pub(crate) fn completion_error_retry(error: &CompletionError) -> (bool, Option<Duration>) {
+    let (kind, api_error, retry_after) = completion_error_details(error);
+    let shed = matches!(error, CompletionError::RateLimitError(_));
+    (!shed && is_retryable(kind, api_error.as_ref()), retry_after)
+}
+
+/// Whether an embedding error is worth retrying (see [Error::is_retryable]), and the delay
+/// after which it can be retried, if known.
+pub(crate) fn embedding_error_retry(error: &EmbeddingError) -> (bool, Option<Duration>) {
+    let (kind, api_error, retry_after) = embedding_error_details(error);
+    let shed = matches!(error, EmbeddingError::RateLimitError(_));
+    (!shed && is_retryable(kind, api_error.as_ref()), retry_after)
+}
+
+fn is_retryable(kind: ErrorKind, api_error: Option<&ApiError>) -> bool {
+    if api_error.and_then(|error| error.code.as_deref()) == Some("insufficient_quota") {
+        return false;
+    }
+    match api_error.and_then(|error| error.status) {
+        Some(status) => matches!(status, 408 | 429 | 500..=599),
+        // Errors reported without status (e.g.: in the body of a response, or of a stream) are
+        // only retried when their code or message identifies them as transient
+        None => matches!(
+            kind,
+            ErrorKind::RateLimit | ErrorKind::Server | ErrorKind::Network | ErrorKind::Timeout
+        ),
+    }
+}
+
+impl From<CompletionError> for Error {
+    fn from(error: CompletionError) -> Self {
+        let (kind, api_error, retry_after) = completion_error_details(&error);
+        Self {
+            kind,
+            api_error,
+            retry_after,
+            retryable: completion_error_retry(&error).0,
+            source: Box::new(error),
+        }
+    }
+}
+
+impl From<PromptError> for Error {
+    fn from(error: PromptError) -> Self {
+        match error {
+            PromptError::CompletionError(error) => error.into(),
+            PromptError::ModerationError(error) => error.into(),
+            PromptError::ToolError(_) => Error::new(ErrorKind::Tool, error),
+            PromptError::GuardrailViolation(_) => Error::new(ErrorKind::ContentFilter, error),
+            PromptError::StructuredOutputError { .. } => {
+                Error::new(ErrorKind::InvalidResponse, error)
+            }
+            PromptError::Cancelled(_) => Error::new(ErrorKind::Cancelled, error),
+            PromptError::MaxTurnsError { .. }
+            | PromptError::HookRejection(_)
+            | PromptError::SessionError(_) => Error::new(ErrorKind::Other, error),
+        }
+    }
+}
+
+impl From<EmbeddingError> for Error {
+    fn from(error: EmbeddingError) -> Self {
+        let (kind, api_error, retry_after) = embedding_error_details(&error);
+        Self {
+            kind,
+            api_error,
+            retry_after,
+            retryable: embedding_error_retry(&error).0,
+            source: Box::new(error),
+        }
+    }
+}
+
+impl From<ModerationError> for Error {
+    fn from(error: ModerationError) -> Self {
+        match &error {
+            ModerationError::HttpError(http_error) => {
+                let kind = http_error_kind(http_error);
+                Error::new(kind, error)
+            }
+            ModerationError::JsonError(_) | ModerationError::ResponseError(_) => {
+                Error::new(ErrorKind::InvalidResponse, error)
+            }
+            ModerationError::ProviderError(body) => {
+                let api_error = ApiError::new(None, body);
+                Error::from_api_error(api_error, error)
+            }
+            ModerationError::ApiError(api_error) => {
+                let api_error = api_error.clone();
+                Error::from_api_error(api_error, error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_retry_after, ApiError, Error, ErrorKind};
+    use crate::completion::{CompletionError, PromptError};
+
+    #[test]
+    fn test_parse_api_errors() {
+        // OpenAI
+        let error = ApiError::new(
+            Some(400),
+            r#"{"error": {"message": "This model's maximum context length is 128000 tokens.", "type": "invalid_request_error", "param": "messages", "code": "context_length_exceeded"}}"#,
+        );
+        assert_eq!(error.code.as_deref(), Some("context_length_exceeded"));
+        assert_eq!(error.kind(), ErrorKind::ContextLength);
+
+        // Anthropic
+        let error = ApiError::new(
+            Some(529),
+            r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#,
+        );
+        assert_eq!(error.code.as_deref(), Some("overloaded_error"));
+        assert_eq!(error.message, "Overloaded");
+        assert_eq!(error.kind(), ErrorKind::Server);
+
+        // Gemini
+        let error = ApiError::new(
+            None,
+            r#"{"error": {"code": 429, "message": "Resource has been exhausted.", "status": "RESOURCE_EXHAUSTED"}}"#,
+        );
+        assert_eq!(error.code.as_deref(), Some("RESOURCE_EXHAUSTED"));
+        assert_eq!(error.kind(), ErrorKind::RateLimit);
+
+        let error = ApiError::new(Some(401), "Unauthorized");
+        assert_eq!(error.code, None);
+        assert_eq!(error.message, "Unauthorized");
+        assert_eq!(error.kind(), ErrorKind::Auth);
+
+        assert_eq!(ApiError::new(None, "Oops").kind(), ErrorKind::Provider);
+    }
+
+    #[test]
+    fn test_api_error_kind_precedence() {
+        // The code is used first
+        let error = ApiError::new(
+            Some(400),
+            r#"{"error": {"message": "Rate limit of the content filter reached", "code": "context_length_exceeded"}}"#,
+        );
+        assert_eq!(error.kind(), ErrorKind::ContextLength);
+
+        // then the status, even if the message matches another kind
+        let error = ApiError::new(
+            Some(400),
+            r#"{"error": {"type": "invalid_request_error", "message": "prompt is too long: 210000 tokens > 200000 maximum"}}"#,
+        );
+        assert_eq!(error.kind(), ErrorKind::InvalidRequest);
+        assert_eq!(
+            ApiError::new(Some(500), "Rate limit exceeded").kind(),
+            ErrorKind::Server
+        );
+        assert_eq!(
+            ApiError::new(
+                Some(429),
+                r#"{"error": {"code": "slow_down", "message": "Slow down"}}"#
+            )
+            .kind(),
+            ErrorKind::RateLimit
+        );
+        assert_eq!(
+            ApiError::new(Some(302), "Found").kind(),
+            ErrorKind::Provider
+        );
+
+        // The message is only used without code nor status
+        assert_eq!(
+            ApiError::new(None, "Rate limit exceeded").kind(),
+            ErrorKind::RateLimit
+        );
+        assert_eq!(
+            ApiError::new(None, "This model's maximum context length is 8192 tokens").kind(),
+            ErrorKind::ContextLength
+        );
+        assert_eq!(
+            ApiError::new(
+                None,
+                r#"{"error": {"code": "unknown", "message": "Server overloaded"}}"#
+            )
+            .kind(),
+            ErrorKind::Provider
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        // Wed, 21 Oct 2015 07:28:00 GMT
+        let date = Duration::from_secs(1_445_412_480);
+
+        assert_eq!(
+            parse_retry_after("120", date),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(parse_retry_after(" 0 ", date), Some(Duration::ZERO));
+        assert_eq!(
+            parse_retry_after(
+                "Wed, 21 Oct 2015 07:28:00 GMT",
+                date - Duration::from_secs(90)
+            ),
+            Some(Duration::from_secs(90))
+        );
+        // Obsolete RFC 850 format
+        assert_eq!(
+            parse_retry_after(
+                "Wednesday, 21-Oct-15 07:28:00 GMT",
+                date - Duration::from_secs(5)
+            ),
+            Some(Duration::from_secs(5))
+        );
+        // Dates in the past
+        assert_eq!(
+            parse_retry_after(
+                "Wed, 21 Oct 2015 07:28:00 GMT",
+                date + Duration::from_secs(1)
+            ),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", date), None);
+        assert_eq!(parse_retry_after("-1", date), None);
+    }
+
+    #[test]
+    fn test_error_from_completion_error() {
+        let error = Error::from(CompletionError::ApiError(ApiError {
+            retry_after: Some(Duration::from_secs(20)),
+            ..ApiError::new(
+                Some(429),
+                r#"{"error": {"message": "Rate limit reached", "type": "requests", "code": "rate_limit_exceeded"}}"#,
+            )
+        }));
+        assert_eq!(error.kind(), ErrorKind::RateLimit);
+        assert_eq!(error.status(), Some(429));
+        assert_eq!(error.code(), Some("rate_limit_exceeded"));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(20)));
+        assert!(error.is_retryable());
+
+        let error = Error::from(PromptError::CompletionError(CompletionError::ProviderError(
+            r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}}"#.to_string(),
+        )));
+        assert_eq!(error.kind(), ErrorKind::RateLimit);
+        assert!(!error.is_retryable());
+
+        // Provider errors without status which could not be classified are not retried
+        let error = Error::from(CompletionError::ProviderError("Oops".to_string()));
+        assert_eq!(error.kind(), ErrorKind::Provider);
+        assert!(!error.is_retryable());
+
+        let error = Error::from(CompletionError::ResponseError("No choices".to_string()));
+        assert_eq!(error.kind(), ErrorKind::InvalidResponse);
+        assert!(!error.is_retryable());
+        assert_eq!(error.to_string(), "ResponseError: No choices");
+
+        // Requests shed by a rate limiter already waited as long as allowed
+        let error = Error::from(CompletionError::RateLimitError(Duration::from_secs(1)));
+        assert_eq!(error.kind(), ErrorKind::RateLimit);
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(1)));
+        assert!(!error.is_retryable());
+    }
+}
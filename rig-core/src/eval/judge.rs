@@ -0,0 +1,204 @@
+//! LLM-as-judge scorers: a completion model rates an answer against a criterion (e.g.: its
+//! faithfulness to the retrieved context), with a score between 0 and 1 and a justification.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{EvalError, EvalSample, Score, Scorer};
+use crate::{
+    completion::CompletionModel,
+    extractor::{Extractor, ExtractorBuilder},
+};
+
+const FAITHFULNESS: &str = "\
+    Rate the faithfulness of the answer to the context: 1 if every claim of the answer is \
+    supported by the context, 0 if none is. Claims which are not in the context lower the score, \
+    even if they are true.";
+
+const ANSWER_RELEVANCE: &str = "\
+    Rate the relevance of the answer to the question: 1 if it directly and completely answers \
+    the question, 0 if it is unrelated to it. Incomplete, evasive or off-topic answers lower \
+    the score.";
+
+const ANSWER_CORRECTNESS: &str = "\
+    Rate the correctness of the answer against the reference answer: 1 if it states the same \
+    facts, 0 if it contradicts or misses all of them.";
+
+/// Rating of an answer by the judge model
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+struct Judgement {
+    /// Score between 0 (worst) and 1 (best)
+    score: f64,
+    /// Short justification of the score
+    reason: String,
+}
+
+/// Scorer asking a completion model to rate the answers against a criterion.
+pub struct LlmJudge<M: CompletionModel> {
+    name: String,
+    extractor: Extractor<M, Judgement>,
+    /// Whether only the cases with a reference answer are scored
+    requires_reference: bool,
+}
+
+impl<M: CompletionModel> LlmJudge<M> {
+    /// Create a judge named `name` rating the answers against `criterion` (e.g.: "Rate the
+    /// politeness of the answer: ..."). The judge is shown the question, the retrieved context,
+    /// the reference answer (if any) and the answer.
+    pub fn new(model: M, name: &str, criterion: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            extractor: ExtractorBuilder::new(model)
+                .preamble(&format!(
+                    "You are evaluating the answer of an AI assistant.\n{criterion}"
+                ))
+                .retries(1)
+                .build(),
+            requires_reference: false,
+        }
+    }
+
+    /// Judge of the faithfulness of the answers to the retrieved context (i.e.: the absence of
+    /// hallucinations), named `faithfulness`.
+    pub fn faithfulness(model: M) -> Self {
+        Self::new(model, "faithfulness", FAITHFULNESS)
+    }
+
+    /// Judge of the relevance of the answers to the questions, named `answer_relevance`.
+    pub fn answer_relevance(model: M) -> Self {
+        Self::new(model, "answer_relevance", ANSWER_RELEVANCE)
+    }
+
+    /// Judge of the correctness of the answers against the reference answers of the cases,
+    /// named `answer_correctness`. Cases without a reference answer are not scored.
+    pub fn answer_correctness(model: M) -> Self {
+        Self {
+            requires_reference: true,
+            ..Self::new(model, "answer_correctness", ANSWER_CORRECTNESS)
+        }
+    }
+}
+
+/// Text shown to the judge model for `sample`.
+fn judge_prompt(sample: &EvalSample) -> String {
+    let context = sample
+        .sources
+        .iter()
+        .map(|source| {
+            format!(
+                "<document id=\"{}\">\n{}\n</document>",
+                source.id, source.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut prompt = format!(
+        "<question>\n{}\n</question>\n<context>\n{}\n</context>\n",
+        sample.case.question, context
+    );
+    if let Some(reference) = &sample.case.reference_answer {
+        prompt.push_str(&format!(
+            "<reference_answer>\n{reference}\n</reference_answer>\n"
+        ));
+    }
+    prompt.push_str(&format!("<answer>\n{}\n</answer>", sample.answer));
+    prompt
+}
+
+impl<M: CompletionModel> Scorer for LlmJudge<M> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn score(&self, sample: &EvalSample) -> Result<Option<Score>, EvalError> {
+        if self.requires_reference && sample.case.reference_answer.is_none() {
+            return Ok(None);
+        }
+
+        let judgement = self.extractor.extract(&judge_prompt(sample)).await?;
+        Ok(Some(Score {
+            value: judgement.score.clamp(0.0, 1.0),
+            reason: Some(judgement.reason),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        agent::SourceRef,
+        completion::{CompletionError, CompletionRequest, CompletionResponse},
+        eval::EvalCase,
+        message::AssistantContent,
+        OneOrMany,
+    };
+
+    /// Judge model rating every answer 1.5, with the prompt it was shown as reason
+    #[derive(Clone)]
+    struct Model;
+
+    impl CompletionModel for Model {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::tool_call(
+                    "call_1",
+                    "submit",
+                    json!({
+                        "score": 1.5,
+                        "reason": request.prompt.rag_text().unwrap_or_default(),
+                    }),
+                )),
+                raw_response: (),
+            })
+        }
+    }
+
+    fn sample(reference_answer: Option<&str>) -> EvalSample {
+        EvalSample {
+            case: EvalCase {
+                question: "What is a flurbo?".to_string(),
+                reference_answer: reference_answer.map(str::to_string),
+                ..Default::default()
+            },
+            answer: "A green alien.".to_string(),
+            sources: vec![SourceRef {
+                id: "doc0".to_string(),
+                text: "A flurbo is a green alien".to_string(),
+                metadata: Default::default(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_judge() {
+        let score = LlmJudge::faithfulness(Model)
+            .score(&sample(None))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(score.value, 1.0);
+        assert_eq!(
+            score.reason.unwrap(),
+            "<question>\nWhat is a flurbo?\n</question>\n\
+            <context>\n<document id=\"doc0\">\nA flurbo is a green alien\n</document>\n</context>\n\
+            <answer>\nA green alien.\n</answer>"
+        );
+
+        let judge = LlmJudge::answer_correctness(Model);
+        assert!(judge.score(&sample(None)).await.unwrap().is_none());
+        assert!(judge
+            .score(&sample(Some("A green alien")))
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
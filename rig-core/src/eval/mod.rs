@@ -0,0 +1,548 @@
+//! This module provides an evaluation harness measuring the quality of agents (e.g.: RAG
+//! agents) over a dataset of questions, to compare prompts, models and embedding models
+//! systematically.
+//!
+//! - A [Dataset] holds the [EvalCase]s: questions with, optionally, a reference answer and the
+//!   ids of the documents relevant to them (labeled chunks).
+//! - [Scorer]s rate the answers of the agent, e.g.: the LLM-as-judge scorers of [judge]
+//!   (faithfulness, answer relevance and answer correctness).
+//! - The [EvalRunner] prompts the agent with each question, computes the [retrieval] metrics
+//!   (hit rate and MRR) of the documents retrieved by the agent against the labeled documents,
+//!   scores the answers and produces an [EvalReport].
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     eval::{judge::LlmJudge, Dataset, EvalRunner},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let judge_model = openai.completion_model(openai::GPT_4O);
+//!
+//! // One JSON case per line, e.g.:
+//! // {"id": "q1", "question": "What is a flurbo?", "relevant_ids": ["doc0"]}
+//! let dataset = Dataset::from_jsonl(&std::fs::read_to_string("dataset.jsonl")?)?;
+//!
+//! let report = EvalRunner::new()
+//!     .scorer(LlmJudge::faithfulness(judge_model.clone()))
+//!     .scorer(LlmJudge::answer_relevance(judge_model))
+//!     .concurrency(4)
+//!     .run(&rag_agent, &dataset)
+//!     .await;
+//!
+//! println!("{report}");
+//! println!("Hit rate: {:?}", report.hit_rate());
+//! ```
+use std::collections::BTreeMap;
+
+use futures::{future::BoxFuture, stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::{Agent, SourceRef},
+    completion::CompletionModel,
+    extractor::ExtractionError,
+};
+
+pub mod judge;
+pub mod retrieval;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EvalError {
+    /// Error parsing a dataset
+    #[error("DatasetError: line {line}: {source}")]
+    DatasetError {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    /// Error of an LLM-as-judge scorer
+    #[error("JudgeError: {0}")]
+    JudgeError(#[from] ExtractionError),
+
+    /// Error of a custom scorer
+    #[error("ScorerError: {0}")]
+    ScorerError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+/// A question of a dataset.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+    /// Id of the case (its position in the dataset if not set)
+    #[serde(default)]
+    pub id: String,
+    /// Question asked to the agent
+    pub question: String,
+    /// Expected answer, if known
+    #[serde(default)]
+    pub reference_answer: Option<String>,
+    /// Ids of the documents relevant to the question, if labeled
+    #[serde(default)]
+    pub relevant_ids: Vec<String>,
+}
+
+/// A set of [EvalCase]s.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dataset {
+    pub cases: Vec<EvalCase>,
+}
+
+impl Dataset {
+    /// Create a dataset from cases. Cases without id are identified by their position.
+    pub fn new(cases: impl IntoIterator<Item = EvalCase>) -> Self {
+        Self {
+            cases: cases
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut case)| {
+                    if case.id.is_empty() {
+                        case.id = i.to_string();
+                    }
+                    case
+                })
+                .collect(),
+        }
+    }
+
+    /// Parse a dataset from JSON lines, one [EvalCase] per line. Empty lines are ignored.
+    pub fn from_jsonl(jsonl: &str) -> Result<Self, EvalError> {
+        let cases = jsonl
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).map_err(|source| EvalError::DatasetError {
+                    line: i + 1,
+                    source,
+                })
+            })
+            .collect::<Result<Vec<EvalCase>, _>>()?;
+        Ok(Self::new(cases))
+    }
+
+    pub fn len(&self) -> usize {
+        self.cases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cases.is_empty()
+    }
+}
+
+/// An answer of the agent to a case, to be scored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalSample {
+    pub case: EvalCase,
+    /// Answer of the agent
+    pub answer: String,
+    /// Documents retrieved by the agent, from most to least relevant
+    pub sources: Vec<SourceRef>,
+}
+
+/// Score of an answer, between 0 (worst) and 1 (best).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub value: f64,
+    /// Justification of the score, if any
+    pub reason: Option<String>,
+}
+
+impl From<f64> for Score {
+    fn from(value: f64) -> Self {
+        Self {
+            value,
+            reason: None,
+        }
+    }
+}
+
+/// Trait for scorers rating the answers of an agent.
+pub trait Scorer: Send + Sync {
+    /// Name of the scorer, under which its scores are reported (e.g.: `faithfulness`)
+    fn name(&self) -> &str;
+
+    /// Score `sample`, or return `None` if the scorer does not apply to it (e.g.: a
+    /// correctness scorer for a case without reference answer).
+    fn score(
+        &self,
+        sample: &EvalSample,
+    ) -> impl std::future::Future<Output = Result<Option<Score>, EvalError>> + Send;
+}
+
+/// Dyn-compatible version of [Scorer], used to store scorers of different types
+/// (e.g.: in an [EvalRunner]).
+pub trait ScorerDyn: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn score<'a>(
+        &'a self,
+        sample: &'a EvalSample,
+    ) -> BoxFuture<'a, Result<Option<Score>, EvalError>>;
+}
+
+impl<S: Scorer> ScorerDyn for S {
+    fn name(&self) -> &str {
+        Scorer::name(self)
+    }
+
+    fn score<'a>(
+        &'a self,
+        sample: &'a EvalSample,
+    ) -> BoxFuture<'a, Result<Option<Score>, EvalError>> {
+        Box::pin(Scorer::score(self, sample))
+    }
+}
+
+/// Result of the evaluation of a case.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub case_id: String,
+    /// Answer of the agent (`None` if prompting the agent failed)
+    pub answer: Option<String>,
+    /// Ids of the documents retrieved by the agent, from most to least relevant
+    pub retrieved_ids: Vec<String>,
+    /// Whether a relevant document was retrieved (`None` if the case has no labeled documents)
+    pub hit: Option<bool>,
+    /// Reciprocal rank of the first relevant document retrieved (`None` if the case has no
+    /// labeled documents)
+    pub reciprocal_rank: Option<f64>,
+    /// Scores of the answer, by scorer name
+    pub scores: BTreeMap<String, Score>,
+    /// Errors of the agent or of the scorers
+    pub errors: Vec<String>,
+}
+
+/// Results of an evaluation, with aggregate metrics.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub results: Vec<CaseResult>,
+}
+
+/// Mean of `values`, or `None` if there are none.
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+impl EvalReport {
+    /// Fraction of the labeled cases for which a relevant document was retrieved.
+    pub fn hit_rate(&self) -> Option<f64> {
+        mean(
+            self.results
+                .iter()
+                .filter_map(|result| result.hit)
+                .map(|hit| if hit { 1.0 } else { 0.0 }),
+        )
+    }
+
+    /// Mean reciprocal rank of the first relevant document retrieved, over the labeled cases.
+    pub fn mrr(&self) -> Option<f64> {
+        mean(
+            self.results
+                .iter()
+                .filter_map(|result| result.reciprocal_rank),
+        )
+    }
+
+    /// Mean score of the scorer `name`, over the scored cases.
+    pub fn mean_score(&self, name: &str) -> Option<f64> {
+        mean(
+            self.results
+                .iter()
+                .filter_map(|result| result.scores.get(name))
+                .map(|score| score.value),
+        )
+    }
+
+    /// Names of the scorers which scored at least one case.
+    pub fn scorer_names(&self) -> Vec<&str> {
+        let mut names = self
+            .results
+            .iter()
+            .flat_map(|result| result.scores.keys().map(String::as_str))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Number of cases with at least one error.
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| !result.errors.is_empty())
+            .count()
+    }
+}
+
+impl std::fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let metric = |value: Option<f64>| value.map_or("N/A".to_string(), |v| format!("{v:.3}"));
+
+        writeln!(f, "cases: {}", self.results.len())?;
+        writeln!(f, "failures: {}", self.failures())?;
+        writeln!(f, "hit_rate: {}", metric(self.hit_rate()))?;
+        writeln!(f, "mrr: {}", metric(self.mrr()))?;
+        for name in self.scorer_names() {
+            writeln!(f, "{}: {}", name, metric(self.mean_score(name)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runner of evaluations: prompts an agent with the questions of a dataset and scores its
+/// answers (see the [module](self) documentation).
+pub struct EvalRunner {
+    scorers: Vec<Box<dyn ScorerDyn>>,
+    concurrency: usize,
+}
+
+impl Default for EvalRunner {
+    fn default() -> Self {
+        Self {
+            scorers: vec![],
+            concurrency: 1,
+        }
+    }
+}
+
+impl EvalRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a scorer of the answers
+    pub fn scorer(mut self, scorer: impl Scorer + 'static) -> Self {
+        self.scorers.push(Box::new(scorer));
+        self
+    }
+
+    /// Set the number of cases evaluated concurrently (default: 1)
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Evaluate `agent` on `dataset`. Errors of the agent or of the scorers on a case are
+    /// recorded in the result of the case, and do not stop the evaluation.
+    pub async fn run<M: CompletionModel>(&self, agent: &Agent<M>, dataset: &Dataset) -> EvalReport {
+        let results = stream::iter(&dataset.cases)
+            .map(|case| self.run_case(agent, case))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        EvalReport { results }
+    }
+
+    async fn run_case<M: CompletionModel>(&self, agent: &Agent<M>, case: &EvalCase) -> CaseResult {
+        let mut result = CaseResult {
+            case_id: case.id.clone(),
+            ..Default::default()
+        };
+
+        let response = match agent.prompt_with_sources(case.question.as_str()).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!(target: "rig", "Evaluation case {} failed: {}", case.id, e);
+                result.errors.push(e.to_string());
+                return result;
+            }
+        };
+
+        result.retrieved_ids = response
+            .sources
+            .iter()
+            .map(|source| source.id.clone())
+            .collect();
+        if !case.relevant_ids.is_empty() {
+            result.hit = Some(retrieval::hit(
+                &result.retrieved_ids,
+                &case.relevant_ids,
+                result.retrieved_ids.len(),
+            ));
+            result.reciprocal_rank = Some(retrieval::reciprocal_rank(
+                &result.retrieved_ids,
+                &case.relevant_ids,
+            ));
+        }
+
+        let sample = EvalSample {
+            case: case.clone(),
+            answer: response.text,
+            sources: response.sources,
+        };
+        for scorer in &self.scorers {
+            match scorer.score(&sample).await {
+                Ok(Some(score)) => {
+                    result.scores.insert(scorer.name().to_string(), score);
+                }
+                Ok(None) => {}
+                Err(e) => result.errors.push(format!("{}: {}", scorer.name(), e)),
+            }
+        }
+
+        result.answer = Some(sample.answer);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::AgentBuilder,
+        completion::{CompletionError, CompletionRequest, CompletionResponse},
+        message::AssistantContent,
+        vector_store::{VectorStoreError, VectorStoreIndex},
+        OneOrMany,
+    };
+
+    /// Model answering with the ids of the documents of the request
+    #[derive(Clone)]
+    struct Model;
+
+    impl CompletionModel for Model {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let ids = request
+                .documents
+                .iter()
+                .map(|document| document.id.as_str())
+                .collect::<Vec<_>>();
+
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(ids.join(","))),
+                raw_response: (),
+            })
+        }
+    }
+
+    /// Index returning the documents `doc0` and `doc1`, in this order
+    struct Index;
+
+    impl VectorStoreIndex for Index {
+        async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+            &self,
+            _query: &str,
+            n: usize,
+        ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+            (0..n.min(2))
+                .map(|i| {
+                    Ok((
+                        1.0,
+                        format!("doc{i}"),
+                        serde_json::from_value(serde_json::json!(format!("text {i}")))?,
+                    ))
+                })
+                .collect()
+        }
+
+        async fn top_n_ids(
+            &self,
+            _query: &str,
+            n: usize,
+        ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+            Ok((0..n.min(2)).map(|i| (1.0, format!("doc{i}"))).collect())
+        }
+    }
+
+    /// Scorer scoring 1 the answers mentioning `doc1`, 0 the others
+    struct MentionsDoc1;
+
+    impl Scorer for MentionsDoc1 {
+        fn name(&self) -> &str {
+            "mentions_doc1"
+        }
+
+        async fn score(&self, sample: &EvalSample) -> Result<Option<Score>, EvalError> {
+            Ok(Some(
+                if sample.answer.contains("doc1") {
+                    1.0
+                } else {
+                    0.0
+                }
+                .into(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_dataset_from_jsonl() {
+        let dataset = Dataset::from_jsonl(
+            r#"{"id": "q1", "question": "What is a flurbo?", "relevant_ids": ["doc0"]}
+
+            {"question": "What is a glarb-glarb?", "reference_answer": "A tool"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.cases[0].relevant_ids, vec!["doc0"]);
+        assert_eq!(dataset.cases[1].id, "1");
+        assert_eq!(dataset.cases[1].reference_answer.as_deref(), Some("A tool"));
+
+        assert!(matches!(
+            Dataset::from_jsonl("{\"question\": 1}"),
+            Err(EvalError::DatasetError { line: 1, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_eval_runner() {
+        let dataset = Dataset::new([
+            EvalCase {
+                question: "first".to_string(),
+                relevant_ids: vec!["doc1".to_string()],
+                ..Default::default()
+            },
+            EvalCase {
+                question: "second".to_string(),
+                relevant_ids: vec!["doc2".to_string()],
+                ..Default::default()
+            },
+            EvalCase {
+                question: "unlabeled".to_string(),
+                ..Default::default()
+            },
+        ]);
+
+        let report = EvalRunner::new()
+            .scorer(MentionsDoc1)
+            .concurrency(2)
+            .run(
+                &AgentBuilder::new(Model).dynamic_context(1, Index).build(),
+                &dataset,
+            )
+            .await;
+
+        assert_eq!(report.results.len(), 3);
+        assert_eq!(report.results[0].retrieved_ids, vec!["doc0"]);
+        assert_eq!(report.results[0].hit, Some(false));
+        assert_eq!(report.results[2].hit, None);
+        assert_eq!(report.hit_rate(), Some(0.0));
+        assert_eq!(report.mrr(), Some(0.0));
+        assert_eq!(report.mean_score("mentions_doc1"), Some(0.0));
+
+        let report = EvalRunner::new()
+            .scorer(MentionsDoc1)
+            .run(
+                &AgentBuilder::new(Model).dynamic_context(2, Index).build(),
+                &dataset,
+            )
+            .await;
+
+        assert_eq!(report.hit_rate(), Some(0.5));
+        assert_eq!(report.mrr(), Some(0.25));
+        assert_eq!(report.mean_score("mentions_doc1"), Some(1.0));
+        assert_eq!(report.failures(), 0);
+        assert_eq!(
+            report.to_string(),
+            "cases: 3\nfailures: 0\nhit_rate: 0.500\nmrr: 0.250\nmentions_doc1: 1.000\n"
+        );
+    }
+}
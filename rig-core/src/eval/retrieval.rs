@@ -0,0 +1,60 @@
+//! Retrieval metrics, comparing the ids of the documents retrieved for a question (from most to
+//! least relevant) to the ids of the documents labeled as relevant to it.
+
+/// Whether at least one relevant document is among the first `k` retrieved documents.
+pub fn hit(retrieved: &[String], relevant: &[String], k: usize) -> bool {
+    retrieved
+        .iter()
+        .take(k)
+        .any(|id| relevant.iter().any(|relevant| relevant == id))
+}
+
+/// Inverse of the rank of the first relevant document among the retrieved documents, or 0 if no
+/// relevant document was retrieved.
+pub fn reciprocal_rank(retrieved: &[String], relevant: &[String]) -> f64 {
+    retrieved
+        .iter()
+        .position(|id| relevant.iter().any(|relevant| relevant == id))
+        .map_or(0.0, |rank| 1.0 / (rank + 1) as f64)
+}
+
+/// Fraction of the relevant documents which are among the first `k` retrieved documents
+/// (1 if no document is relevant).
+pub fn recall_at_k(retrieved: &[String], relevant: &[String], k: usize) -> f64 {
+    if relevant.is_empty() {
+        return 1.0;
+    }
+
+    let found = relevant
+        .iter()
+        .filter(|relevant| retrieved.iter().take(k).any(|id| id == *relevant))
+        .count();
+    found as f64 / relevant.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_retrieval_metrics() {
+        let retrieved = ids(&["a", "b", "c"]);
+
+        assert!(hit(&retrieved, &ids(&["c"]), 3));
+        assert!(!hit(&retrieved, &ids(&["c"]), 2));
+        assert!(!hit(&retrieved, &ids(&["d"]), 3));
+
+        assert_eq!(reciprocal_rank(&retrieved, &ids(&["b", "c"])), 0.5);
+        assert_eq!(reciprocal_rank(&retrieved, &ids(&["d"])), 0.0);
+
+        assert_eq!(
+            recall_at_k(&retrieved, &ids(&["a", "c", "d", "e"]), 2),
+            0.25
+        );
+        assert_eq!(recall_at_k(&retrieved, &[], 2), 1.0);
+    }
+}
@@ -18,8 +18,9 @@
 //!    profession: Option<String>,
 //! }
 //!
-//! // Create the extractor
+//! // Create the extractor, retrying up to 2 times if the model's output cannot be parsed
 //! let extractor = openai.extractor::<Person>(openai::GPT_4O)
+//!     .retries(2)
 //!     .build();
 //!
 //! // Extract structured data from text
@@ -52,9 +53,23 @@ pub enum ExtractionError {
     PromptError(#[from] PromptError),
 }
 
+impl ExtractionError {
+    /// Whether the error is due to the model's output (in which case asking the model again
+    /// may succeed), as opposed to e.g. a connection or provider error.
+    fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ExtractionError::NoData
+                | ExtractionError::DeserializationError(_)
+                | ExtractionError::PromptError(PromptError::ToolError(_))
+        )
+    }
+}
+
 /// Extractor for structured data from text
 pub struct Extractor<M: CompletionModel, T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync> {
     agent: Agent<M>,
+    retries: usize,
     _t: PhantomData<T>,
 }
 
@@ -62,7 +77,31 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync, M: CompletionModel>
 where
     M: Sync,
 {
+    /// Extract structured data from text. If the model's output cannot be parsed, the model is
+    /// asked again (up to the number of retries of the extractor) with the parsing error.
     pub async fn extract(&self, text: &str) -> Result<T, ExtractionError> {
+        let mut prompt = text.to_string();
+        let mut attempt = 0;
+
+        loop {
+            match self.extract_once(&prompt).await {
+                Err(e) if e.is_retryable() && attempt < self.retries => {
+                    attempt += 1;
+                    tracing::warn!(target: "rig",
+                        "Extraction attempt {attempt} failed, retrying: {e}"
+                    );
+                    prompt = format!(
+                        "{text}\n\n\
+                        Your previous attempt to extract the data failed with the error: {e}\n\
+                        Call the `submit` function with data matching its parameters."
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn extract_once(&self, text: &str) -> Result<T, ExtractionError> {
         let summary = self.agent.prompt(text).await?;
 
         if summary.is_empty() {
@@ -79,6 +118,7 @@ pub struct ExtractorBuilder<
     M: CompletionModel,
 > {
     agent_builder: AgentBuilder<M>,
+    retries: usize,
     _t: PhantomData<T>,
 }
 
@@ -95,6 +135,7 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
                     Be sure to fill out every field and ALWAYS CALL THE `submit` function, event with default values!!!.
                 ")
                 .tool(SubmitTool::<T> {_t: PhantomData}),
+            retries: 0,
             _t: PhantomData,
         }
     }
@@ -113,10 +154,18 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
         self
     }
 
+    /// Set the number of times the model is asked again when its output cannot be parsed
+    /// (default: 0)
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
     /// Build the Extractor
     pub fn build(self) -> Extractor<M, T> {
         Extractor {
             agent: self.agent_builder.build(),
+            retries: self.retries,
             _t: PhantomData,
         }
     }
@@ -150,3 +199,68 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync> Tool for
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::{
+        completion::{CompletionError, CompletionRequest, CompletionResponse},
+        message::AssistantContent,
+        OneOrMany,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq)]
+    struct Person {
+        name: String,
+    }
+
+    /// Model answering with text the first time, and calling the `submit` tool afterwards
+    #[derive(Clone, Default)]
+    struct Model {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl CompletionModel for Model {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let choice = match self.calls.fetch_add(1, Ordering::SeqCst) {
+                0 => AssistantContent::text("The person is John."),
+                _ => AssistantContent::tool_call("call_1", "submit", json!({"name": "John"})),
+            };
+
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(choice),
+                raw_response: (),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let extractor = ExtractorBuilder::<Person, _>::new(Model::default()).build();
+        assert!(matches!(
+            extractor.extract("John").await,
+            Err(ExtractionError::DeserializationError(_))
+        ));
+
+        let extractor = ExtractorBuilder::<Person, _>::new(Model::default())
+            .retries(1)
+            .build();
+        assert_eq!(
+            extractor.extract("John").await.unwrap(),
+            Person {
+                name: "John".to_string()
+            }
+        );
+    }
+}
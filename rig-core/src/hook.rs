@@ -0,0 +1,336 @@
+//! This module provides the [AgentHook] trait, which allows running callbacks at the steps of
+//! an agent run (see [AgentBuilder::with_hook](crate::agent::AgentBuilder::with_hook)): before a
+//! prompt is sent, after each completion of the model, before each tool call and when the run
+//! fails.
+//!
+//! Hooks can observe the run (e.g.: logging), modify it (e.g.: redacting personal data from
+//! prompts or completions) or stop it (e.g.: answering from a cache, rejecting prompts violating
+//! a guardrail), without changes to the agent loop.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     completion::{Message, Prompt},
+//!     hook::{AgentHook, HookAction},
+//!     providers::openai,
+//! };
+//!
+//! /// Reject prompts mentioning passwords
+//! struct PasswordGuardrail;
+//!
+//! impl AgentHook for PasswordGuardrail {
+//!     fn before_prompt(&self, prompt: &mut Message, _chat_history: &[Message]) -> HookAction {
+//!         match prompt.rag_text() {
+//!             Some(text) if text.to_lowercase().contains("password") => {
+//!                 HookAction::Reject("Prompts must not contain passwords".to_string())
+//!             }
+//!             _ => HookAction::Continue,
+//!         }
+//!     }
+//! }
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .with_hook(PasswordGuardrail)
+//!     .build();
+//!
+//! // Fails with a `PromptError::HookRejection` error
+//! let response = agent.prompt("My password is hunter2, is it secure?").await;
+//! ```
+use crate::{
+    completion::{PromptError, TokenUsage},
+    message::{AssistantContent, Message, ToolCall},
+    OneOrMany,
+};
+
+/// Outcome of an [AgentHook] callback.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HookAction {
+    /// Continue the run
+    Continue,
+    /// Stop the run and answer with the given response (e.g.: a cached response). When
+    /// returned by [AgentHook::on_tool_call], the response is used as the output of the tool
+    /// instead, and the tool is not called.
+    Respond(String),
+    /// Stop the run with a [PromptError::HookRejection] error with the given reason
+    /// (e.g.: a guardrail violation)
+    Reject(String),
+}
+
+/// Callbacks run at the steps of an agent run. All callbacks do nothing by default.
+///
+/// When an agent has several hooks, they are run in the order in which they were added,
+/// and the first callback which does not return [HookAction::Continue] stops the others.
+pub trait AgentHook: Send + Sync {
+    /// Called when a prompt is sent to the agent, with the prompt and its chat history.
+    /// The prompt can be modified (e.g.: to redact personal data) before it is processed.
+    fn before_prompt(&self, _prompt: &mut Message, _chat_history: &[Message]) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Called after each completion of the model, with the completion and its token usage
+    /// (if reported by the provider). The completion can be modified (e.g.: to redact personal
+    /// data) before it is processed.
+    fn after_completion(
+        &self,
+        _choice: &mut OneOrMany<AssistantContent>,
+        _usage: Option<TokenUsage>,
+    ) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Called before each tool call requested by the model.
+    fn on_tool_call(&self, _tool_call: &ToolCall) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Called when the run fails, including when a hook rejects it.
+    fn on_error(&self, _error: &PromptError) {}
+}
+
+/// Run a callback of each hook in order, until one does not continue. Returns the response
+/// to answer with, if a hook responded.
+pub(crate) fn run_hooks(
+    hooks: &[Box<dyn AgentHook>],
+    mut callback: impl FnMut(&dyn AgentHook) -> HookAction,
+) -> Result<Option<String>, PromptError> {
+    for hook in hooks {
+        match callback(hook.as_ref()) {
+            HookAction::Continue => (),
+            HookAction::Respond(response) => return Ok(Some(response)),
+            HookAction::Reject(reason) => return Err(PromptError::HookRejection(reason)),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        agent::AgentBuilder, completion::Prompt, message::UserContent,
+        providers::mock::MockCompletionModel,
+    };
+
+    type Events = Arc<Mutex<Vec<String>>>;
+
+    /// Hook recording its callbacks in `events`, and answering the prompts and tool calls
+    /// with `action`
+    struct NamedHook {
+        name: &'static str,
+        events: Events,
+        action: HookAction,
+    }
+
+    impl NamedHook {
+        fn new(name: &'static str, events: &Events, action: HookAction) -> Self {
+            Self {
+                name,
+                events: events.clone(),
+                action,
+            }
+        }
+
+        fn record(&self, event: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{} {event}", self.name));
+        }
+    }
+
+    impl AgentHook for NamedHook {
+        fn before_prompt(&self, prompt: &mut Message, _chat_history: &[Message]) -> HookAction {
+            self.record(&format!("prompt {}", prompt.rag_text().unwrap_or_default()));
+            self.action.clone()
+        }
+
+        fn after_completion(
+            &self,
+            _choice: &mut OneOrMany<AssistantContent>,
+            _usage: Option<TokenUsage>,
+        ) -> HookAction {
+            self.record("completion");
+            HookAction::Continue
+        }
+
+        fn on_tool_call(&self, tool_call: &ToolCall) -> HookAction {
+            self.record(&format!("tool {}", tool_call.function.name));
+            self.action.clone()
+        }
+
+        fn on_error(&self, _error: &PromptError) {
+            self.record("error");
+        }
+    }
+
+    /// Hook replacing the digits of the prompts with `*`
+    struct Redact;
+
+    impl AgentHook for Redact {
+        fn before_prompt(&self, prompt: &mut Message, _chat_history: &[Message]) -> HookAction {
+            if let Message::User { content } = prompt {
+                for content in content.iter_mut() {
+                    if let UserContent::Text(text) = content {
+                        text.text = text.text.replace(|c: char| c.is_ascii_digit(), "*");
+                    }
+                }
+            }
+            HookAction::Continue
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks_order() {
+        let events = Events::default();
+        let model = MockCompletionModel::new().text("Hello!");
+        let agent = AgentBuilder::new(model.clone())
+            .with_hook(NamedHook::new("first", &events, HookAction::Continue))
+            .with_hook(Redact)
+            .with_hook(NamedHook::new("second", &events, HookAction::Continue))
+            .build();
+
+        assert_eq!(agent.prompt("My PIN is 1234").await.unwrap(), "Hello!");
+
+        // Hooks run in the order in which they were added, and see the modifications of the
+        // previous hooks
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "first prompt My PIN is 1234",
+                "second prompt My PIN is ****",
+                "first completion",
+                "second completion",
+            ]
+        );
+        assert_eq!(
+            model.requests()[0].prompt.rag_text().unwrap(),
+            "My PIN is ****"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_respond_short_circuits() {
+        let events = Events::default();
+        let model = MockCompletionModel::new().text("Hello!");
+        let agent = AgentBuilder::new(model.clone())
+            .with_hook(NamedHook::new(
+                "cache",
+                &events,
+                HookAction::Respond("Cached".into()),
+            ))
+            .with_hook(NamedHook::new("second", &events, HookAction::Continue))
+            .build();
+
+        assert_eq!(agent.prompt("Hi").await.unwrap(), "Cached");
+
+        // Neither the following hooks nor the model are called
+        assert_eq!(*events.lock().unwrap(), vec!["cache prompt Hi"]);
+        assert!(model.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hook_reject_short_circuits() {
+        let events = Events::default();
+        let model = MockCompletionModel::new().text("Hello!");
+        let agent = AgentBuilder::new(model.clone())
+            .with_hook(NamedHook::new(
+                "guardrail",
+                &events,
+                HookAction::Reject("Forbidden".into()),
+            ))
+            .with_hook(NamedHook::new("second", &events, HookAction::Continue))
+            .build();
+
+        assert!(matches!(
+            agent.prompt("Hi").await,
+            Err(PromptError::HookRejection(reason)) if reason == "Forbidden"
+        ));
+
+        // All the hooks are notified of the error
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["guardrail prompt Hi", "guardrail error", "second error"]
+        );
+        assert!(model.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hook_responds_to_tool_call() {
+        let events = Events::default();
+        let model = MockCompletionModel::new()
+            .tool_call("add", json!({"x": 1, "y": 2}))
+            .text("1 + 2 = 3");
+
+        /// Hook answering the tool calls, without a tool
+        struct ToolCache;
+
+        impl AgentHook for ToolCache {
+            fn on_tool_call(&self, _tool_call: &ToolCall) -> HookAction {
+                HookAction::Respond("3".into())
+            }
+        }
+
+        let agent = AgentBuilder::new(model.clone())
+            .with_hook(ToolCache)
+            .with_hook(NamedHook::new("second", &events, HookAction::Continue))
+            .build();
+
+        assert_eq!(
+            agent.multi_turn(2).prompt("1 + 2?").await.unwrap(),
+            "1 + 2 = 3"
+        );
+
+        // The run continues with the response of the hook as the output of the tool
+        assert!(!events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|event| event.starts_with("second tool")));
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(matches!(
+            &requests[1].prompt,
+            Message::User { content } if matches!(
+                content.first(),
+                UserContent::ToolResult(result) if result.id == "call_0"
+            )
+        ));
+    }
+
+    #[test]
+    fn test_run_hooks() {
+        let events = Events::default();
+        let hooks: Vec<Box<dyn AgentHook>> = vec![
+            Box::new(NamedHook::new("a", &events, HookAction::Continue)),
+            Box::new(NamedHook::new(
+                "b",
+                &events,
+                HookAction::Respond("b".into()),
+            )),
+            Box::new(NamedHook::new("c", &events, HookAction::Reject("c".into()))),
+        ];
+
+        let mut prompt = Message::user("Hi");
+        assert_eq!(
+            run_hooks(&hooks, |hook| hook.before_prompt(&mut prompt, &[])).unwrap(),
+            Some("b".to_string())
+        );
+        assert_eq!(*events.lock().unwrap(), vec!["a prompt Hi", "b prompt Hi"]);
+
+        assert_eq!(
+            run_hooks(&hooks[..1], |_| HookAction::Continue).unwrap(),
+            None
+        );
+        assert!(matches!(
+            run_hooks(&hooks[2..], |hook| hook.before_prompt(&mut prompt, &[])),
+            Err(PromptError::HookRejection(reason)) if reason == "c"
+        ));
+    }
+}
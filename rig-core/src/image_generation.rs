@@ -1,3 +1,36 @@
+//! This module provides functionality for working with image generation models.
+//! It provides traits and structs for generating image generation requests,
+//! handling their responses, and defining image generation models.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     image_generation::{ImageGenerationModel, ImageResponseFormat},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let dalle = openai.image_generation_model(openai::DALL_E_3);
+//!
+//! // Generate an image and get its bytes
+//! let response = dalle
+//!     .image_generation_request()
+//!     .prompt("A castle sitting upon a large mountain, overlooking the water.")
+//!     .width(1024)
+//!     .height(1024)
+//!     .send()
+//!     .await?;
+//! std::fs::write("castle.png", response.image)?;
+//!
+//! // Or get its URL (e.g.: to show it in a web page)
+//! let response = dalle
+//!     .image_generation_request()
+//!     .prompt("A castle sitting upon a large mountain, overlooking the water.")
+//!     .response_format(ImageResponseFormat::Url)
+//!     .send()
+//!     .await?;
+//! println!("{}", response.url.unwrap());
+//! ```
 use serde_json::Value;
 use thiserror::Error;
 
@@ -11,22 +44,22 @@ pub enum ImageGenerationError {
     #[error("JsonError: {0}")]
     JsonError(#[from] serde_json::Error),
 
-    /// Error building the transcription request
+    /// Error building the image generation request
     #[error("RequestError: {0}")]
     RequestError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
 
-    /// Error parsing the transcription response
+    /// Error parsing the image generation response
     #[error("ResponseError: {0}")]
     ResponseError(String),
 
-    /// Error returned by the transcription model provider
+    /// Error returned by the image generation model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
 }
 pub trait ImageGeneration<M: ImageGenerationModel> {
-    /// Generates a transcription request builder for the given `file`.
+    /// Generates an image generation request builder for the given `prompt` and `size`.
     /// This function is meant to be called by the user to further customize the
-    /// request at transcription time before sending it.
+    /// request at generation time before sending it.
     ///
     /// ❗IMPORTANT: The type that implements this trait might have already
     /// populated fields in the builder (the exact fields depend on the type).
@@ -41,9 +74,24 @@ pub trait ImageGeneration<M: ImageGenerationModel> {
     > + Send;
 }
 
+/// How the generated image is returned (see [ImageGenerationResponse]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageResponseFormat {
+    /// The bytes of the image
+    #[default]
+    Bytes,
+    /// A URL of the image (supported by OpenAI's DALL·E models). Providers which do not
+    /// support URLs return the bytes of the image instead.
+    Url,
+}
+
 #[derive(Debug)]
 pub struct ImageGenerationResponse<T> {
+    /// The bytes of the image (empty if the image is returned as a URL)
     pub image: Vec<u8>,
+    /// The URL of the image, if requested with [ImageResponseFormat::Url] and supported
+    /// by the provider
+    pub url: Option<String>,
     pub response: T,
 }
 
@@ -66,6 +114,7 @@ pub struct ImageGenerationRequest {
     pub prompt: String,
     pub width: u32,
     pub height: u32,
+    pub response_format: ImageResponseFormat,
     pub additional_params: Option<Value>,
 }
 
@@ -74,6 +123,7 @@ pub struct ImageGenerationRequestBuilder<M: ImageGenerationModel> {
     prompt: String,
     width: u32,
     height: u32,
+    response_format: ImageResponseFormat,
     additional_params: Option<Value>,
 }
 
@@ -84,6 +134,7 @@ impl<M: ImageGenerationModel> ImageGenerationRequestBuilder<M> {
             prompt: "".to_string(),
             height: 256,
             width: 256,
+            response_format: ImageResponseFormat::default(),
             additional_params: None,
         }
     }
@@ -106,6 +157,12 @@ impl<M: ImageGenerationModel> ImageGenerationRequestBuilder<M> {
         self
     }
 
+    /// How the generated image is returned (bytes by default)
+    pub fn response_format(mut self, response_format: ImageResponseFormat) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
     /// Adds additional parameters to the image generation request.
     pub fn additional_params(mut self, params: Value) -> Self {
         self.additional_params = Some(params);
@@ -117,6 +174,7 @@ impl<M: ImageGenerationModel> ImageGenerationRequestBuilder<M> {
             prompt: self.prompt,
             width: self.width,
             height: self.height,
+            response_format: self.response_format,
             additional_params: self.additional_params,
         }
     }
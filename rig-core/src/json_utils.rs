@@ -44,7 +44,15 @@ pub mod stringified_json {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        serde_json::from_str(&s).map_err(serde::de::Error::custom)
+        serde_json::from_str(&s).map_err(|e| {
+            if e.is_eof() {
+                serde::de::Error::custom(format!(
+                    "incomplete JSON (the response may have been truncated, e.g. by max_tokens): {s}"
+                ))
+            } else {
+                serde::de::Error::custom(e)
+            }
+        })
     }
 }
 
@@ -188,4 +196,11 @@ mod tests {
         };
         assert_eq!(dummy, expected);
     }
+
+    #[test]
+    fn test_stringified_json_deserialize_incomplete() {
+        let json_str = r#"{"data":"{\"key\": \"val"}"#;
+        let error = serde_json::from_str::<Dummy>(json_str).unwrap_err();
+        assert!(error.to_string().contains("incomplete JSON"));
+    }
 }
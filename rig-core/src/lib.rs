@@ -83,25 +83,42 @@
 pub mod agent;
 #[cfg(feature = "audio")]
 pub mod audio_generation;
+pub mod cancel;
 pub mod cli_chatbot;
 pub mod completion;
+pub mod compression;
 pub mod embeddings;
+pub mod error;
+pub mod eval;
 pub mod extractor;
+pub mod hook;
 #[cfg(feature = "image")]
 pub mod image_generation;
+pub mod injection;
 pub(crate) mod json_utils;
 pub mod loaders;
+pub mod memory;
+pub mod moderation;
 pub mod one_or_many;
 pub mod pipeline;
+pub mod prompt;
 pub mod providers;
+pub mod rate_limit;
+pub mod rerank;
+pub mod router;
+pub mod session;
 pub mod streaming;
+pub mod telemetry;
+pub(crate) mod time;
 pub mod tool;
+pub mod trace;
 pub mod transcription;
 pub mod vector_store;
 
 // Re-export commonly used types and traits
 pub use completion::message;
 pub use embeddings::Embed;
+pub use error::Error;
 pub use one_or_many::{EmptyListError, OneOrMany};
 
 #[cfg(feature = "derive")]
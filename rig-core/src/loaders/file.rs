@@ -1,8 +1,11 @@
-use std::{fs, path::PathBuf};
+use std::{fs, path::PathBuf, time::UNIX_EPOCH};
 
 use glob::glob;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
 #[derive(Error, Debug)]
 pub enum FileLoaderError {
     #[error("Invalid glob pattern: {0}")]
@@ -24,6 +27,36 @@ pub enum FileLoaderError {
 pub(crate) trait Readable {
     fn read(self) -> Result<String, FileLoaderError>;
     fn read_with_path(self) -> Result<(PathBuf, String), FileLoaderError>;
+    fn read_document(self) -> Result<FileDocument, FileLoaderError>;
+}
+
+/// Contents of a file along with metadata about its source, ready to be embedded with
+/// [EmbeddingsBuilder::documents](crate::embeddings::EmbeddingsBuilder::documents)
+/// (only the content is embedded).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDocument {
+    /// Path of the file
+    pub path: PathBuf,
+    pub content: String,
+    /// Size of the file, in bytes
+    pub size_bytes: u64,
+    /// Last modification time of the file, in seconds since the Unix epoch (if available)
+    pub modified: Option<u64>,
+}
+
+impl FileDocument {
+    /// Id of the document (its path), e.g.: to split it with a
+    /// [TextSplitter](crate::loaders::splitter::TextSplitter).
+    pub fn id(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+impl Embed for FileDocument {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.content.clone());
+        Ok(())
+    }
 }
 
 impl<'a> FileLoader<'a, PathBuf> {
@@ -37,6 +70,11 @@ impl<'a> FileLoader<'a, PathBuf> {
             iterator: Box::new(self.iterator.map(|res| res.read_with_path())),
         }
     }
+    pub fn read_documents(self) -> FileLoader<'a, Result<FileDocument, FileLoaderError>> {
+        FileLoader {
+            iterator: Box::new(self.iterator.map(|res| res.read_document())),
+        }
+    }
 }
 
 impl Readable for PathBuf {
@@ -47,6 +85,20 @@ impl Readable for PathBuf {
         let contents = fs::read_to_string(&self);
         Ok((self, contents?))
     }
+    fn read_document(self) -> Result<FileDocument, FileLoaderError> {
+        let content = fs::read_to_string(&self)?;
+        let metadata = fs::metadata(&self)?;
+        Ok(FileDocument {
+            path: self,
+            content,
+            size_bytes: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        })
+    }
 }
 impl<T: Readable> Readable for Result<T, FileLoaderError> {
     fn read(self) -> Result<String, FileLoaderError> {
@@ -55,6 +107,9 @@ impl<T: Readable> Readable for Result<T, FileLoaderError> {
     fn read_with_path(self) -> Result<(PathBuf, String), FileLoaderError> {
         self.map(|t| t.read_with_path())?
     }
+    fn read_document(self) -> Result<FileDocument, FileLoaderError> {
+        self.map(|t| t.read_document())?
+    }
 }
 
 // ================================================================
@@ -140,6 +195,27 @@ impl<'a> FileLoader<'a, Result<PathBuf, FileLoaderError>> {
             iterator: Box::new(self.iterator.map(|res| res.read_with_path())),
         }
     }
+    /// Reads the contents of the files within the iterator returned by [FileLoader::with_glob] or
+    ///  [FileLoader::with_dir] as [FileDocument]s, with the path and metadata of the files.
+    ///
+    /// # Example
+    /// Embed the markdown files in directory "docs" and its subdirectories.
+    ///
+    /// ```rust
+    /// let documents = FileLoader::with_glob("docs/**/*.md")?
+    ///     .read_documents()
+    ///     .ignore_errors();
+    ///
+    /// let embeddings = EmbeddingsBuilder::new(model)
+    ///     .documents(documents)?
+    ///     .build()
+    ///     .await?;
+    /// ```
+    pub fn read_documents(self) -> FileLoader<'a, Result<FileDocument, FileLoaderError>> {
+        FileLoader {
+            iterator: Box::new(self.iterator.map(|res| res.read_document())),
+        }
+    }
 }
 
 impl<'a, T: 'a> FileLoader<'a, Result<T, FileLoaderError>> {
@@ -173,7 +249,7 @@ impl FileLoader<'_, Result<PathBuf, FileLoaderError>> {
     /// ```
     pub fn with_glob(
         pattern: &str,
-    ) -> Result<FileLoader<Result<PathBuf, FileLoaderError>>, FileLoaderError> {
+    ) -> Result<FileLoader<'_, Result<PathBuf, FileLoaderError>>, FileLoaderError> {
         let paths = glob(pattern)?;
         Ok(FileLoader {
             iterator: Box::new(
@@ -194,7 +270,7 @@ impl FileLoader<'_, Result<PathBuf, FileLoaderError>> {
     /// ```
     pub fn with_dir(
         directory: &str,
-    ) -> Result<FileLoader<Result<PathBuf, FileLoaderError>>, FileLoaderError> {
+    ) -> Result<FileLoader<'_, Result<PathBuf, FileLoaderError>>, FileLoaderError> {
         Ok(FileLoader {
             iterator: Box::new(fs::read_dir(directory)?.filter_map(|entry| {
                 let path = entry.ok()?.path();
@@ -270,4 +346,27 @@ mod tests {
         assert!(!actual.is_empty());
         assert!(expected == actual)
     }
+
+    #[test]
+    fn test_read_documents() {
+        let temp = assert_fs::TempDir::new().expect("Failed to create temp dir");
+        temp.child("docs/foo.md")
+            .write_str("# Foo")
+            .expect("Failed to write to foo");
+
+        let glob = temp.path().to_string_lossy().to_string() + "/**/*.md";
+
+        let documents = FileLoader::with_glob(&glob)
+            .unwrap()
+            .read_documents()
+            .ignore_errors()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].content, "# Foo");
+        assert_eq!(documents[0].size_bytes, 5);
+        assert!(documents[0].id().ends_with("foo.md"));
+        assert!(documents[0].modified.is_some());
+    }
 }
@@ -15,10 +15,14 @@
 //! and keeping track of the chapter numbers along with their contents.
 //!
 //! Note: The [EpubFileLoader] requires the `epub` feature to be enabled in the `Cargo.toml` file.
+//!
+//! The [splitter] module provides text splitters to split the loaded documents in chunks before
+//! embedding them.
 
 pub mod file;
+pub mod splitter;
 
-pub use file::FileLoader;
+pub use file::{FileDocument, FileLoader};
 
 #[cfg(feature = "pdf")]
 pub mod pdf;
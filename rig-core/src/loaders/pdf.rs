@@ -1,10 +1,10 @@
-use std::{fs, path::PathBuf};
+use std::{fs, path::PathBuf, time::UNIX_EPOCH};
 
 use glob::glob;
 use lopdf::{Document, Error as LopdfError};
 use thiserror::Error;
 
-use super::file::FileLoaderError;
+use super::file::{FileDocument, FileLoaderError};
 
 #[derive(Error, Debug)]
 pub enum PdfLoaderError {
@@ -208,6 +208,51 @@ impl<'a> PdfFileLoader<'a, Result<PathBuf, PdfLoaderError>> {
             })),
         }
     }
+
+    /// Directly reads the contents of the pdfs within the iterator returned by
+    ///  [PdfFileLoader::with_glob] or [PdfFileLoader::with_dir] as [FileDocument]s, with the
+    ///  path and metadata of the files.
+    ///
+    /// # Example
+    /// Embed the pdfs in directory "tests/data/*.pdf".
+    ///
+    /// ```rust
+    /// let documents = PdfFileLoader::with_glob("tests/data/*.pdf")?
+    ///     .read_documents()
+    ///     .ignore_errors();
+    ///
+    /// let embeddings = EmbeddingsBuilder::new(model)
+    ///     .documents(documents)?
+    ///     .build()
+    ///     .await?;
+    /// ```
+    pub fn read_documents(self) -> PdfFileLoader<'a, Result<FileDocument, PdfLoaderError>> {
+        PdfFileLoader {
+            iterator: Box::new(self.iterator.map(|res| {
+                let (path, doc) = res.load_with_path()?;
+                let content = doc
+                    .page_iter()
+                    .enumerate()
+                    .map(|(page_no, _)| {
+                        doc.extract_text(&[page_no as u32 + 1])
+                            .map_err(PdfLoaderError::PdfError)
+                    })
+                    .collect::<Result<String, PdfLoaderError>>()?;
+                let metadata = fs::metadata(&path).map_err(FileLoaderError::IoError)?;
+
+                Ok(FileDocument {
+                    path,
+                    content,
+                    size_bytes: metadata.len(),
+                    modified: metadata
+                        .modified()
+                        .ok()
+                        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                        .map(|duration| duration.as_secs()),
+                })
+            })),
+        }
+    }
 }
 
 impl<'a> PdfFileLoader<'a, Document> {
@@ -453,4 +498,21 @@ mod tests {
         assert!(!actual.is_empty());
         assert!(expected == actual)
     }
+
+    #[test]
+    fn test_pdf_read_documents() {
+        let mut documents = PdfFileLoader::with_glob("tests/data/*.pdf")
+            .unwrap()
+            .read_documents()
+            .ignore_errors()
+            .into_iter()
+            .collect::<Vec<_>>();
+        documents.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].path, PathBuf::from("tests/data/dummy.pdf"));
+        assert_eq!(documents[0].content, "Test\nPDF\nDocument\n");
+        assert_eq!(documents[1].content, "Page\n1\nPage\n2\nPage\n3\n");
+        assert!(documents[1].size_bytes > 0);
+    }
 }
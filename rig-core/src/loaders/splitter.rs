@@ -0,0 +1,344 @@
+//! This module provides text splitters, which split documents in chunks small enough to be
+//! embedded (and to be relevant as RAG context), before adding them to an
+//! [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder).
+//!
+//! The [RecursiveSplitter] splits texts on a list of separators, from the coarsest (e.g.:
+//! paragraphs) to the finest (e.g.: characters), until the chunks fit in the chunk size.
+//! Consecutive chunks overlap, so that the context of a sentence is not lost at chunk
+//! boundaries. Chunk sizes are measured in characters, in tokens (see [RecursiveSplitter::tokens])
+//! or with any length function, and markdown documents can be split on their headings first
+//! (see [RecursiveSplitter::markdown]).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     embeddings::EmbeddingsBuilder,
+//!     loaders::{splitter::{RecursiveSplitter, TextSplitter}, FileLoader},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let documents = FileLoader::with_glob("docs/*.md")?
+//!     .read_with_path()
+//!     .ignore_errors()
+//!     .map(|(path, content)| (path.to_string_lossy().to_string(), content));
+//!
+//! // Chunks of at most 500 tokens, overlapping by 50 tokens
+//! let chunks = RecursiveSplitter::markdown(500, 50)?.split_documents(documents);
+//!
+//! let embeddings = EmbeddingsBuilder::new(openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL))
+//!     .documents(chunks)?
+//!     .build()
+//!     .await?;
+//! ```
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SplitterError {
+    /// The overlap between consecutive chunks is not smaller than the chunk size
+    #[error("SplitterError: chunk overlap ({chunk_overlap}) must be smaller than the chunk size ({chunk_size})")]
+    InvalidOverlap {
+        chunk_size: usize,
+        chunk_overlap: usize,
+    },
+}
+
+/// A chunk of a document, produced by a [TextSplitter].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Id of the chunk, of the form `"{parent_id}#{index}"`
+    pub id: String,
+    /// Id of the document the chunk is part of
+    pub parent_id: String,
+    /// Index of the chunk in the document
+    pub index: usize,
+    pub text: String,
+}
+
+impl Embed for Chunk {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text.clone());
+        Ok(())
+    }
+}
+
+/// Splitter of texts in chunks.
+pub trait TextSplitter {
+    /// Split the text in chunks.
+    fn split_text(&self, text: &str) -> Vec<String>;
+
+    /// Split the document `parent_id` in chunks.
+    fn split(&self, parent_id: &str, text: &str) -> Vec<Chunk> {
+        self.split_text(text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| Chunk {
+                id: format!("{parent_id}#{index}"),
+                parent_id: parent_id.to_string(),
+                index,
+                text,
+            })
+            .collect()
+    }
+
+    /// Split the `(id, text)` documents in chunks.
+    fn split_documents(
+        &self,
+        documents: impl IntoIterator<Item = (impl ToString, impl AsRef<str>)>,
+    ) -> Vec<Chunk> {
+        documents
+            .into_iter()
+            .flat_map(|(id, text)| self.split(&id.to_string(), text.as_ref()))
+            .collect()
+    }
+}
+
+/// Splitter splitting texts recursively on a list of separators (see the [module](self)
+/// documentation). Separators are kept at the beginning of the pieces they separate.
+#[derive(Clone, Debug)]
+pub struct RecursiveSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+    separators: Vec<String>,
+    length: fn(&str) -> usize,
+}
+
+impl RecursiveSplitter {
+    /// Create a splitter producing chunks of at most `chunk_size` characters, with
+    /// `chunk_overlap` characters of overlap between consecutive chunks.
+    /// Texts are split on paragraphs, then lines, then words, then characters.
+    ///
+    /// Fails if `chunk_overlap` is not smaller than `chunk_size`.
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Result<Self, SplitterError> {
+        if chunk_overlap >= chunk_size {
+            return Err(SplitterError::InvalidOverlap {
+                chunk_size,
+                chunk_overlap,
+            });
+        }
+
+        Ok(Self {
+            chunk_size,
+            chunk_overlap,
+            separators: ["\n\n", "\n", " ", ""].map(String::from).to_vec(),
+            length: |text| text.chars().count(),
+        })
+    }
+
+    /// Same as [RecursiveSplitter::new], but with sizes in tokens (approximated as 4 characters
+    /// per token). Use [RecursiveSplitter::length_function] for exact token counts.
+    pub fn tokens(chunk_tokens: usize, overlap_tokens: usize) -> Result<Self, SplitterError> {
+        Ok(Self::new(chunk_tokens, overlap_tokens)?
+            .length_function(crate::completion::tokens::estimate_tokens))
+    }
+
+    /// Same as [RecursiveSplitter::tokens], but markdown documents are split on their
+    /// headings (from the highest level to the lowest) and code blocks first.
+    pub fn markdown(chunk_tokens: usize, overlap_tokens: usize) -> Result<Self, SplitterError> {
+        Ok(Self::tokens(chunk_tokens, overlap_tokens)?.separators([
+            "\n# ",
+            "\n## ",
+            "\n### ",
+            "\n#### ",
+            "\n##### ",
+            "\n###### ",
+            "\n```",
+            "\n\n",
+            "\n",
+            " ",
+            "",
+        ]))
+    }
+
+    /// Set the separators, from the coarsest to the finest. Pieces still larger than the chunk
+    /// size after splitting on the last separator are kept as is, unless the last separator is
+    /// the empty string (which splits on characters).
+    pub fn separators(mut self, separators: impl IntoIterator<Item = impl ToString>) -> Self {
+        self.separators = separators.into_iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Set the function measuring the length of texts (e.g.: the token count of the tokenizer
+    /// of the embedding model).
+    pub fn length_function(mut self, length: fn(&str) -> usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    fn split_recursive(&self, text: &str, separators: &[String]) -> Vec<String> {
+        // Use the first separator found in the text
+        let position = separators
+            .iter()
+            .position(|separator| separator.is_empty() || text.contains(separator.as_str()))
+            .unwrap_or(separators.len());
+
+        let (pieces, finer_separators) = match separators.get(position) {
+            Some(separator) if separator.is_empty() => (
+                text.chars().map(String::from).collect::<Vec<_>>(),
+                &separators[position + 1..],
+            ),
+            Some(separator) => (
+                split_keeping_separator(text, separator),
+                &separators[position + 1..],
+            ),
+            None => (vec![text.to_string()], &[][..]),
+        };
+
+        let mut chunks = vec![];
+        let mut mergeable = vec![];
+
+        for piece in pieces {
+            if (self.length)(&piece) <= self.chunk_size {
+                mergeable.push(piece);
+                continue;
+            }
+
+            chunks.extend(self.merge(std::mem::take(&mut mergeable)));
+            if finer_separators.is_empty() {
+                chunks.push(piece);
+            } else {
+                chunks.extend(self.split_recursive(&piece, finer_separators));
+            }
+        }
+        chunks.extend(self.merge(mergeable));
+
+        chunks
+    }
+
+    /// Merge consecutive pieces in chunks of at most the chunk size, overlapping by at most
+    /// the chunk overlap.
+    fn merge(&self, pieces: Vec<String>) -> Vec<String> {
+        let mut chunks = vec![];
+        let mut current = VecDeque::new();
+        let mut current_length = 0;
+
+        for piece in pieces {
+            let length = (self.length)(&piece);
+
+            if current_length + length > self.chunk_size && !current.is_empty() {
+                chunks.push(current.iter().map(String::as_str).collect::<String>());
+
+                // Keep the end of the chunk as overlap with the next one
+                while current_length > self.chunk_overlap
+                    || (current_length + length > self.chunk_size && current_length > 0)
+                {
+                    let Some(removed) = current.pop_front() else {
+                        break;
+                    };
+                    current_length -= (self.length)(&removed);
+                }
+            }
+
+            current_length += length;
+            current.push_back(piece);
+        }
+
+        if !current.is_empty() {
+            chunks.push(current.iter().map(String::as_str).collect::<String>());
+        }
+
+        chunks
+    }
+}
+
+impl TextSplitter for RecursiveSplitter {
+    fn split_text(&self, text: &str) -> Vec<String> {
+        self.split_recursive(text, &self.separators)
+            .into_iter()
+            .map(|chunk| chunk.trim().to_string())
+            .filter(|chunk| !chunk.is_empty())
+            .collect()
+    }
+}
+
+/// Split `text` on `separator`, keeping the separator at the beginning of each piece
+/// (except the first).
+fn split_keeping_separator(text: &str, separator: &str) -> Vec<String> {
+    let mut pieces = text.split(separator);
+    pieces
+        .next()
+        .map(String::from)
+        .into_iter()
+        .chain(pieces.map(|piece| format!("{separator}{piece}")))
+        .filter(|piece| !piece.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recursive_splitter() {
+        let splitter = RecursiveSplitter::new(20, 8).unwrap();
+
+        let chunks = splitter.split_text("one two three four five six seven eight");
+        assert_eq!(
+            chunks,
+            vec!["one two three four", "four five six seven", "seven eight"]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 20));
+
+        // Paragraphs are kept together when they fit
+        let chunks = splitter.split_text("Short paragraph.\n\nAnother one.");
+        assert_eq!(chunks, vec!["Short paragraph.", "Another one."]);
+
+        // Words longer than the chunk size are split on characters
+        let chunks = RecursiveSplitter::new(4, 0)
+            .unwrap()
+            .split_text("abcdefghij");
+        assert_eq!(chunks, vec!["abcd", "efgh", "ij"]);
+
+        assert!(matches!(
+            RecursiveSplitter::new(10, 10),
+            Err(SplitterError::InvalidOverlap {
+                chunk_size: 10,
+                chunk_overlap: 10
+            })
+        ));
+        assert!(RecursiveSplitter::tokens(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_markdown_splitter() {
+        let text = "# Title\nIntro.\n## Install\nRun the installer.\n## Usage\nCall the function.";
+        let chunks = RecursiveSplitter::markdown(8, 0).unwrap().split_text(text);
+
+        assert_eq!(
+            chunks,
+            vec![
+                "# Title\nIntro.",
+                "## Install\nRun the installer.",
+                "## Usage\nCall the function."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_documents() {
+        let chunks = RecursiveSplitter::new(10, 0)
+            .unwrap()
+            .split_documents([("a", "first doc"), ("b", "second document")]);
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (
+                    chunk.id.as_str(),
+                    chunk.parent_id.as_str(),
+                    chunk.text.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a#0", "a", "first doc"),
+                ("b#0", "b", "second"),
+                ("b#1", "b", "document")
+            ]
+        );
+    }
+}
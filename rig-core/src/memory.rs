@@ -0,0 +1,635 @@
+//! This module provides the [Memory] trait, implemented by long-term memories of the
+//! conversations of an agent, [VectorMemory], a semantic memory backed by a vector store, and
+//! [SummarizingMemory], a sliding window of the last exchanges with a rolling summary of the
+//! older ones.
+//!
+//! An agent with a memory (see [AgentBuilder::memory](crate::agent::AgentBuilder::memory))
+//! remembers each exchange (the prompt and the final answer) of its runs, and recalls the past
+//! exchanges relevant to each new prompt into the context of the request, so that it can refer to
+//! them even when they are no longer in the chat history.
+//!
+//! [VectorMemory] embeds the exchanges and stores them in any [VectorStore]. Past exchanges are
+//! ranked by the similarity of their embedding to the prompt, optionally weighted by their
+//! recency (see [VectorMemory::recency_weight]).
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{
+//!     completion::Prompt,
+//!     memory::VectorMemory,
+//!     providers::openai,
+//!     vector_store::in_memory_store::InMemoryVectorStore,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let embedding_model = openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+//!
+//! let memory = VectorMemory::new(
+//!     embedding_model.clone(),
+//!     InMemoryVectorStore::default().index(embedding_model),
+//! )
+//! .sample(3)
+//! .recency_weight(0.3)
+//! .recency_half_life(Duration::from_secs(7 * 24 * 3600));
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .memory(memory)
+//!     .build();
+//!
+//! agent.prompt("My favorite color is green.").await?;
+//!
+//! // Later, in another conversation
+//! let response = agent.prompt("What is my favorite color?").await?;
+//! ```
+//!
+//! [SummarizingMemory] keeps long conversations within the context window of the model: the
+//! last exchanges are recalled verbatim, and the older ones are folded by a (typically cheap)
+//! model into a summary, which is appended to the preamble of the agent.
+//! ```rust
+//! use rig::{completion::Prompt, memory::SummarizingMemory, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//! let summarizer = openai.agent(openai::GPT_4O_MINI).build();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .memory(SummarizingMemory::new(summarizer).keep_last(6))
+//!     .build();
+//! ```
+use std::{collections::HashMap, time::Duration};
+
+use futures::{future::BoxFuture, lock::Mutex};
+use serde::{Deserialize, Serialize};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    completion::{Document, Prompt},
+    embeddings::{Embedding, EmbeddingModel},
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex},
+    OneOrMany,
+};
+
+/// Trait for long-term memories of the conversations of an agent.
+pub trait Memory: Send + Sync {
+    /// Store an exchange: a prompt and the answer of the agent.
+    fn remember(
+        &self,
+        prompt: &str,
+        answer: &str,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+
+    /// Retrieve the past exchanges relevant to `query`, from most to least relevant, as
+    /// documents to insert in the context of a request.
+    fn recall(
+        &self,
+        query: &str,
+    ) -> impl std::future::Future<Output = Result<Vec<Document>, VectorStoreError>> + Send;
+
+    /// Text appended to the preamble (system message) of the requests of the agent, if any
+    /// (e.g.: a summary of the conversation). None by default.
+    fn preamble(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Option<String>, VectorStoreError>> + Send {
+        async { Ok(None) }
+    }
+}
+
+/// Dyn-compatible version of [Memory], used to store memories of different types
+/// (e.g.: in an [Agent](crate::agent::Agent)).
+pub trait MemoryDyn: Send + Sync {
+    fn remember<'a>(
+        &'a self,
+        prompt: &'a str,
+        answer: &'a str,
+    ) -> BoxFuture<'a, Result<(), VectorStoreError>>;
+
+    fn recall<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Document>, VectorStoreError>>;
+
+    fn preamble(&self) -> BoxFuture<'_, Result<Option<String>, VectorStoreError>>;
+}
+
+impl<T: Memory> MemoryDyn for T {
+    fn remember<'a>(
+        &'a self,
+        prompt: &'a str,
+        answer: &'a str,
+    ) -> BoxFuture<'a, Result<(), VectorStoreError>> {
+        Box::pin(Memory::remember(self, prompt, answer))
+    }
+
+    fn recall<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Document>, VectorStoreError>> {
+        Box::pin(Memory::recall(self, query))
+    }
+
+    fn preamble(&self) -> BoxFuture<'_, Result<Option<String>, VectorStoreError>> {
+        Box::pin(Memory::preamble(self))
+    }
+}
+
+/// An exchange stored in a [VectorMemory].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRecord {
+    /// Prompt of the exchange
+    pub prompt: String,
+    /// Answer of the agent
+    pub answer: String,
+    /// Time of the exchange, in seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl MemoryRecord {
+    /// Text of the exchange, which is embedded and inserted in the context of requests.
+    pub fn text(&self) -> String {
+        format!("User: {}\nAssistant: {}", self.prompt, self.answer)
+    }
+}
+
+/// Semantic memory storing the exchanges of an agent in a vector store.
+///
+/// `store` is any vector store whose documents can be built from the id, [MemoryRecord] and
+/// embedding of an exchange (e.g.: an
+/// [InMemoryVectorIndex](crate::vector_store::in_memory_store::InMemoryVectorIndex) of
+/// [MemoryRecord]s). `model` embeds the exchanges and must be the embedding model of the store.
+pub struct VectorMemory<M: EmbeddingModel, S> {
+    model: M,
+    store: Mutex<S>,
+    /// Number of exchanges recalled
+    sample: usize,
+    /// Number of candidates fetched from the store before weighting (at least `sample`)
+    candidates: usize,
+    /// Weight of the similarity of an exchange to the query in its score
+    score_weight: f64,
+    /// Weight of the recency of an exchange in its score
+    recency_weight: f64,
+    /// Age at which the recency of an exchange is halved
+    recency_half_life: Duration,
+    /// Minimum score of the recalled exchanges
+    min_score: Option<f64>,
+}
+
+impl<M, S> VectorMemory<M, S>
+where
+    M: EmbeddingModel,
+    S: VectorStore + VectorStoreIndex,
+    S::Document: From<(String, MemoryRecord, OneOrMany<Embedding>)>,
+{
+    /// Create a memory storing the exchanges in `store`, embedded with `model`. By default,
+    /// the 3 exchanges most similar to the query are recalled, regardless of their age.
+    pub fn new(model: M, store: S) -> Self {
+        Self {
+            model,
+            store: Mutex::new(store),
+            sample: 3,
+            candidates: 10,
+            score_weight: 1.0,
+            recency_weight: 0.0,
+            recency_half_life: Duration::from_secs(24 * 3600),
+            min_score: None,
+        }
+    }
+
+    /// Set the number of exchanges recalled (default: 3)
+    pub fn sample(mut self, sample: usize) -> Self {
+        self.sample = sample;
+        self
+    }
+
+    /// Set the number of candidates fetched from the store, which are then ranked by their
+    /// weighted score (default: 10). More candidates give recent exchanges more chances to be
+    /// recalled.
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    /// Set the weight of the similarity of an exchange to the query in its score (default: 1)
+    pub fn score_weight(mut self, weight: f64) -> Self {
+        self.score_weight = weight;
+        self
+    }
+
+    /// Set the weight of the recency of an exchange in its score (default: 0, i.e.: the age of
+    /// exchanges is ignored). The recency of an exchange is 1 when it just happened, and is
+    /// halved every [recency half-life](Self::recency_half_life).
+    pub fn recency_weight(mut self, weight: f64) -> Self {
+        self.recency_weight = weight;
+        self
+    }
+
+    /// Set the age at which the recency of an exchange is halved (default: 1 day)
+    pub fn recency_half_life(mut self, half_life: Duration) -> Self {
+        self.recency_half_life = half_life;
+        self
+    }
+
+    /// Only recall the exchanges whose weighted score is at least `min_score`
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Access the underlying vector store (e.g.: to delete the exchanges of a user).
+    pub async fn store(&self) -> futures::lock::MutexGuard<'_, S> {
+        self.store.lock().await
+    }
+
+    /// Weighted score of an exchange of similarity `score` to the query, at time `now`.
+    fn weighted_score(&self, score: f64, record: &MemoryRecord, now: u64) -> f64 {
+        let age = now.saturating_sub(record.timestamp) as f64;
+        let half_life = self.recency_half_life.as_secs_f64().max(1.0);
+        let recency = 0.5_f64.powf(age / half_life);
+        self.score_weight * score + self.recency_weight * recency
+    }
+}
+
+/// Current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+impl<M, S> Memory for VectorMemory<M, S>
+where
+    M: EmbeddingModel,
+    S: VectorStore + VectorStoreIndex,
+    S::Document: From<(String, MemoryRecord, OneOrMany<Embedding>)>,
+{
+    async fn remember(&self, prompt: &str, answer: &str) -> Result<(), VectorStoreError> {
+        let record = MemoryRecord {
+            prompt: prompt.to_string(),
+            answer: answer.to_string(),
+            timestamp: now(),
+        };
+        let embedding = self.model.embed_text(&record.text()).await?;
+
+        // Unique across processes sharing the store, unlike a counter
+        let id = uuid::Uuid::new_v4().to_string();
+        self.store
+            .lock()
+            .await
+            .insert_documents(vec![(id, record, OneOrMany::one(embedding)).into()], true)
+            .await
+    }
+
+    async fn recall(&self, query: &str) -> Result<Vec<Document>, VectorStoreError> {
+        let candidates = self
+            .store
+            .lock()
+            .await
+            .top_n::<MemoryRecord>(query, self.candidates.max(self.sample))
+            .await?;
+
+        let now = now();
+        let mut exchanges = candidates
+            .into_iter()
+            .map(|(score, id, record)| (self.weighted_score(score, &record, now), id, record))
+            .filter(|(score, _, _)| self.min_score.is_none_or(|min_score| *score >= min_score))
+            .collect::<Vec<_>>();
+        exchanges.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+
+        Ok(exchanges
+            .into_iter()
+            .take(self.sample)
+            .map(|(_, id, record)| Document {
+                id,
+                text: record.text(),
+                additional_props: HashMap::from([(
+                    "timestamp".to_string(),
+                    record.timestamp.to_string(),
+                )]),
+            })
+            .collect())
+    }
+}
+
+/// The exchanges of a [SummarizingMemory]: the summary of the older exchanges and the last ones.
+#[derive(Default)]
+struct SummarizedExchanges {
+    summary: Option<String>,
+    exchanges: Vec<MemoryRecord>,
+    /// Number of exchanges folded into the summary, to build unique ids
+    summarized: usize,
+    /// Whether the oldest exchanges are being summarized (without holding the lock)
+    summarizing: bool,
+}
+
+/// Memory of the last exchanges of an agent, verbatim, with a rolling summary of the older ones.
+///
+/// The last [keep_last](SummarizingMemory::keep_last) exchanges are recalled (from most to least
+/// recent) whatever the query. When an exchange is remembered beyond them, the oldest exchanges
+/// are folded by `summarizer` (e.g.: an agent using a cheap model) into the summary of the
+/// conversation, which is appended to the preamble of the requests.
+///
+/// #### Default Values
+/// - `keep_last`: 4
+/// - `summarize_every`: 4
+pub struct SummarizingMemory<P: Prompt> {
+    summarizer: P,
+    keep_last: usize,
+    summarize_every: usize,
+    state: Mutex<SummarizedExchanges>,
+}
+
+impl<P: Prompt> SummarizingMemory<P> {
+    pub fn new(summarizer: P) -> Self {
+        Self {
+            summarizer,
+            keep_last: 4,
+            summarize_every: 4,
+            state: Mutex::new(SummarizedExchanges::default()),
+        }
+    }
+
+    /// Set the number of last exchanges kept verbatim.
+    pub fn keep_last(mut self, keep_last: usize) -> Self {
+        self.keep_last = keep_last;
+        self
+    }
+
+    /// Only summarize once `summarize_every` exchanges exceed the last ones kept verbatim (at
+    /// least 1), to summarize in fewer (but larger) requests.
+    pub fn summarize_every(mut self, summarize_every: usize) -> Self {
+        self.summarize_every = summarize_every.max(1);
+        self
+    }
+
+    /// Start from the summary of an earlier conversation (e.g.: restored from a session).
+    pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+        self.state.get_mut().summary = Some(summary.into());
+        self
+    }
+
+    /// The summary of the exchanges older than the last ones, if any.
+    pub async fn summary(&self) -> Option<String> {
+        self.state.lock().await.summary.clone()
+    }
+
+    /// The last exchanges, kept verbatim, from oldest to most recent.
+    pub async fn exchanges(&self) -> Vec<MemoryRecord> {
+        self.state.lock().await.exchanges.clone()
+    }
+}
+
+impl<P: Prompt> Memory for SummarizingMemory<P> {
+    /// The oldest exchanges are summarized without holding the lock of the memory, so the
+    /// memory can be recalled (and other exchanges remembered) meanwhile. Only one summary is
+    /// written at a time: the exchanges remembered meanwhile are summarized by the next one.
+    async fn remember(&self, prompt: &str, answer: &str) -> Result<(), VectorStoreError> {
+        let (split, previous, transcript) = {
+            let mut state = self.state.lock().await;
+            state.exchanges.push(MemoryRecord {
+                prompt: prompt.to_string(),
+                answer: answer.to_string(),
+                timestamp: now(),
+            });
+
+            if state.summarizing || state.exchanges.len() < self.keep_last + self.summarize_every {
+                return Ok(());
+            }
+
+            let split = state.exchanges.len() - self.keep_last;
+            let transcript = state.exchanges[..split]
+                .iter()
+                .map(MemoryRecord::text)
+                .collect::<Vec<_>>()
+                .join("\n");
+            let previous = state
+                .summary
+                .as_ref()
+                .map(|summary| format!("Summary of the earlier conversation:\n{summary}\n\n"))
+                .unwrap_or_default();
+            state.summarizing = true;
+            (split, previous, transcript)
+        };
+
+        let summary = self
+            .summarizer
+            .prompt(format!(
+                "Summarize the following conversation concisely, keeping all the facts, \
+                decisions and open questions needed to continue it.\n\n{previous}\
+                Conversation:\n{transcript}"
+            ))
+            .await;
+
+        let mut state = self.state.lock().await;
+        state.summarizing = false;
+        let summary = summary.map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        tracing::debug!(target: "rig", "Summarized {} exchanges of the conversation", split);
+        // Exchanges remembered during the summary were appended after the summarized ones
+        state.summary = Some(summary);
+        state.exchanges.drain(..split);
+        state.summarized += split;
+        Ok(())
+    }
+
+    async fn recall(&self, _query: &str) -> Result<Vec<Document>, VectorStoreError> {
+        let state = self.state.lock().await;
+        Ok(state
+            .exchanges
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, record)| Document {
+                id: format!("exchange-{}", state.summarized + i),
+                text: record.text(),
+                additional_props: HashMap::from([(
+                    "timestamp".to_string(),
+                    record.timestamp.to_string(),
+                )]),
+            })
+            .collect())
+    }
+
+    async fn preamble(&self) -> Result<Option<String>, VectorStoreError> {
+        Ok(self
+            .state
+            .lock()
+            .await
+            .summary
+            .as_ref()
+            .map(|summary| format!("Summary of the conversation so far:\n{summary}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Duration, Memory, MemoryRecord, SummarizingMemory, VectorMemory};
+    use crate::{
+        agent::AgentBuilder,
+        embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+        providers::mock::MockCompletionModel,
+        vector_store::in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore},
+    };
+
+    /// Model embedding texts by whether they mention colors or animals
+    #[derive(Clone)]
+    struct Model;
+
+    impl EmbeddingModel for Model {
+        const MAX_DOCUMENTS: usize = 5;
+
+        fn ndims(&self) -> usize {
+            2
+        }
+
+        async fn embed_texts(
+            &self,
+            documents: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            Ok(documents
+                .into_iter()
+                .map(|document| Embedding {
+                    vec: vec![
+                        if document.contains("color") { 1.0 } else { 0.0 },
+                        if document.contains("cat") { 1.0 } else { 0.1 },
+                    ],
+                    document,
+                })
+                .collect())
+        }
+    }
+
+    fn memory() -> VectorMemory<Model, InMemoryVectorIndex<Model, MemoryRecord>> {
+        VectorMemory::new(Model, InMemoryVectorStore::default().index(Model))
+    }
+
+    #[tokio::test]
+    async fn test_recall() {
+        let memory = memory().sample(1);
+        memory.remember("I have a cat", "Nice!").await.unwrap();
+        memory
+            .remember("My favorite color is green", "Noted.")
+            .await
+            .unwrap();
+
+        let documents = memory.recall("What is my favorite color?").await.unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(
+            documents[0].text,
+            "User: My favorite color is green\nAssistant: Noted."
+        );
+        assert!(uuid::Uuid::parse_str(&documents[0].id).is_ok());
+        assert_eq!(memory.store().await.len(), 2);
+    }
+
+    #[test]
+    fn test_recency_weight() {
+        let memory = memory()
+            .recency_weight(1.0)
+            .recency_half_life(Duration::from_secs(100));
+        let record = |timestamp| MemoryRecord {
+            prompt: "".to_string(),
+            answer: "".to_string(),
+            timestamp,
+        };
+
+        assert_eq!(memory.weighted_score(0.5, &record(1000), 1000), 1.5);
+        assert_eq!(memory.weighted_score(0.5, &record(900), 1000), 1.0);
+        // A less similar but more recent exchange ranks first
+        assert!(
+            memory.weighted_score(0.6, &record(1000), 1000)
+                > memory.weighted_score(0.9, &record(700), 1000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_summarizing_memory() {
+        let summarizer = MockCompletionModel::new()
+            .text("The user has a cat.")
+            .text("The user has a cat named Felix.");
+        let memory = SummarizingMemory::new(AgentBuilder::new(summarizer.clone()).build())
+            .keep_last(2)
+            .summarize_every(1);
+
+        memory.remember("I have a cat", "Nice!").await.unwrap();
+        memory.remember("Hello", "Hi!").await.unwrap();
+        assert_eq!(memory.preamble().await.unwrap(), None);
+
+        memory
+            .remember("Its name is Felix", "Noted.")
+            .await
+            .unwrap();
+        memory.remember("What time is it?", "Noon.").await.unwrap();
+
+        // The last 2 exchanges are recalled verbatim, from most to least recent
+        let documents = memory.recall("anything").await.unwrap();
+        assert_eq!(
+            documents
+                .iter()
+                .map(|document| (document.id.as_str(), document.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("exchange-3", "User: What time is it?\nAssistant: Noon."),
+                ("exchange-2", "User: Its name is Felix\nAssistant: Noted."),
+            ]
+        );
+        assert_eq!(
+            memory.preamble().await.unwrap().as_deref(),
+            Some("Summary of the conversation so far:\nThe user has a cat named Felix.")
+        );
+
+        // The previous summary is folded into the next one
+        let requests = summarizer.requests();
+        assert_eq!(requests.len(), 2);
+        let prompt = requests[1].prompt.rag_text().unwrap();
+        assert!(prompt.contains("Summary of the earlier conversation:\nThe user has a cat."));
+        assert!(prompt.contains("User: Hello\nAssistant: Hi!"));
+        assert!(!prompt.contains("I have a cat"));
+    }
+
+    /// Summarizer answering once notified
+    struct GatedSummarizer(tokio::sync::Notify);
+
+    impl crate::completion::Prompt for GatedSummarizer {
+        async fn prompt(
+            &self,
+            _prompt: impl Into<crate::message::Message> + Send,
+        ) -> Result<String, crate::completion::PromptError> {
+            self.0.notified().await;
+            Ok("summary".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarizing_memory_unlocked() {
+        let memory = SummarizingMemory::new(GatedSummarizer(tokio::sync::Notify::new()))
+            .keep_last(1)
+            .summarize_every(1);
+        memory.remember("first", "1").await.unwrap();
+
+        let summarize = memory.remember("second", "2");
+        let meanwhile = async {
+            // The memory is recalled and remembers exchanges while the summary is written
+            assert_eq!(memory.recall("anything").await.unwrap().len(), 2);
+            memory.remember("third", "3").await.unwrap();
+            memory.summarizer.0.notify_one();
+        };
+        let (summarized, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+            futures::join!(summarize, meanwhile)
+        })
+        .await
+        .expect("the memory should not be locked during the summary");
+        summarized.unwrap();
+
+        assert_eq!(memory.summary().await.as_deref(), Some("summary"));
+        let exchanges = memory.exchanges().await;
+        assert_eq!(
+            exchanges
+                .iter()
+                .map(|record| record.prompt.as_str())
+                .collect::<Vec<_>>(),
+            vec!["second", "third"]
+        );
+    }
+}
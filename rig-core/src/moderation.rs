@@ -0,0 +1,298 @@
+//! This module provides the [ModerationModel] trait, implemented by moderation models which
+//! classify texts as harmful or not (e.g.: OpenAI's moderation models, see
+//! [openai::ModerationModel](crate::providers::openai::ModerationModel)).
+//!
+//! Moderation models can guard the inputs and outputs of an agent (see
+//! [AgentBuilder::with_input_guard](crate::agent::AgentBuilder::with_input_guard) and
+//! [AgentBuilder::with_output_guard](crate::agent::AgentBuilder::with_output_guard)): flagged
+//! prompts are blocked (or rewritten) before they reach the model, and flagged answers before
+//! they reach the user. The agent fails with [PromptError::GuardrailViolation] when a flagged
+//! text is blocked. The guards apply to every way of prompting the agent, streams included: the
+//! text of a streamed answer is withheld until it is checked.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     completion::{Prompt, PromptError},
+//!     moderation::GuardPolicy,
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let moderation = openai.moderation_model(openai::OMNI_MODERATION_LATEST);
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .with_input_guard(moderation.clone(), GuardPolicy::Block)
+//!     .with_output_guard(moderation, GuardPolicy::Rewrite("I can't help with that.".to_string()))
+//!     .build();
+//!
+//! match agent.prompt("How do I hurt someone?").await {
+//!     Err(PromptError::GuardrailViolation(violation)) => {
+//!         println!("Blocked: {:?}", violation.result.categories)
+//!     }
+//!     response => println!("{:?}", response),
+//! }
+//! ```
+use std::collections::HashMap;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::completion::PromptError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModerationError {
+    /// Http error (e.g.: connection error, timeout, etc.)
+    #[error("HttpError: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    /// Json error (e.g.: serialization, deserialization)
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// Error parsing the moderation response
+    #[error("ResponseError: {0}")]
+    ResponseError(String),
+
+    /// Error returned by the moderation model provider
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
+
+    /// Error returned by the moderation model provider, with its status and code (see
+    /// [rig::Error](crate::Error) to classify it)
+    #[error("ApiError: {0}")]
+    ApiError(#[from] crate::error::ApiError),
+}
+
+/// Classification of a text by a moderation model.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModerationResult {
+    /// Whether the text is harmful
+    pub flagged: bool,
+    /// Categories in which the text is harmful (e.g.: `violence`), in the provider's naming
+    pub categories: Vec<String>,
+    /// Score of the text in each category, between 0 and 1 (higher is more harmful)
+    pub category_scores: HashMap<String, f64>,
+}
+
+/// Trait for moderation models that classify texts as harmful or not.
+pub trait ModerationModel: Send + Sync {
+    /// Classify `text`.
+    fn moderate(
+        &self,
+        text: &str,
+    ) -> impl std::future::Future<Output = Result<ModerationResult, ModerationError>> + Send;
+}
+
+/// Dyn-compatible version of [ModerationModel], used to store moderation models of different
+/// types (e.g.: in an [Agent](crate::agent::Agent)).
+pub trait ModerationModelDyn: Send + Sync {
+    fn moderate<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<ModerationResult, ModerationError>>;
+}
+
+impl<M: ModerationModel> ModerationModelDyn for M {
+    fn moderate<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<ModerationResult, ModerationError>> {
+        Box::pin(ModerationModel::moderate(self, text))
+    }
+}
+
+/// What an agent does with a text flagged by the moderation model guarding it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GuardPolicy {
+    /// Stop the run with a [GuardrailViolation] error
+    Block,
+    /// Replace the text with the given text (e.g.: a refusal message) and continue
+    Rewrite(String),
+}
+
+/// Which text of an agent run was flagged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GuardStage {
+    /// The prompt sent to the agent
+    Input,
+    /// The answer of the agent
+    Output,
+}
+
+/// A text of an agent run flagged by a moderation model guarding the agent with
+/// [GuardPolicy::Block].
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[error("{stage:?} flagged by moderation (categories: {})", result.categories.join(", "))]
+pub struct GuardrailViolation {
+    pub stage: GuardStage,
+    pub result: ModerationResult,
+}
+
+/// A moderation model guarding the inputs or outputs of an agent.
+pub(crate) struct Guard {
+    pub(crate) model: Box<dyn ModerationModelDyn>,
+    pub(crate) policy: GuardPolicy,
+}
+
+impl Guard {
+    pub(crate) fn new(model: impl ModerationModel + 'static, policy: GuardPolicy) -> Self {
+        Self {
+            model: Box::new(model),
+            policy,
+        }
+    }
+
+    /// Moderate `text`, returning the text to continue with (`text` itself, or its replacement
+    /// if it is flagged and rewritten).
+    pub(crate) async fn check(
+        &self,
+        stage: GuardStage,
+        text: String,
+    ) -> Result<String, PromptError> {
+        let result = self.model.moderate(&text).await?;
+        if !result.flagged {
+            return Ok(text);
+        }
+
+        tracing::info!(target: "rig",
+            "{:?} flagged by moderation (categories: {})",
+            stage,
+            result.categories.join(", ")
+        );
+        match &self.policy {
+            GuardPolicy::Block => Err(GuardrailViolation { stage, result }.into()),
+            GuardPolicy::Rewrite(replacement) => Ok(replacement.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{agent::AgentBuilder, completion::Prompt, providers::mock::MockCompletionModel};
+
+    /// Moderation model flagging the texts containing "attack", and failing on the texts
+    /// containing "unavailable"
+    struct KeywordModeration;
+
+    impl ModerationModel for KeywordModeration {
+        async fn moderate(&self, text: &str) -> Result<ModerationResult, ModerationError> {
+            if text.contains("unavailable") {
+                return Err(ModerationError::ProviderError(
+                    "Moderation unavailable".to_string(),
+                ));
+            }
+
+            let flagged = text.contains("attack");
+            Ok(ModerationResult {
+                flagged,
+                categories: if flagged {
+                    vec!["violence".to_string()]
+                } else {
+                    vec![]
+                },
+                category_scores: HashMap::from([(
+                    "violence".to_string(),
+                    if flagged { 0.9 } else { 0.1 },
+                )]),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_guard_check() {
+        let guard = Guard::new(KeywordModeration, GuardPolicy::Block);
+        assert_eq!(
+            guard
+                .check(GuardStage::Input, "hello".to_string())
+                .await
+                .unwrap(),
+            "hello"
+        );
+        match guard
+            .check(GuardStage::Output, "attack plan".to_string())
+            .await
+        {
+            Err(PromptError::GuardrailViolation(violation)) => {
+                assert_eq!(violation.stage, GuardStage::Output);
+                assert_eq!(violation.result.categories, vec!["violence"]);
+                assert_eq!(
+                    violation.to_string(),
+                    "Output flagged by moderation (categories: violence)"
+                );
+            }
+            response => panic!("unexpected response: {:?}", response),
+        }
+
+        let guard = Guard::new(KeywordModeration, GuardPolicy::Rewrite("Sorry".to_string()));
+        assert_eq!(
+            guard
+                .check(GuardStage::Input, "attack plan".to_string())
+                .await
+                .unwrap(),
+            "Sorry"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_guards() {
+        let model = MockCompletionModel::new()
+            .text("Hello!")
+            .text("Here is an attack plan");
+        let agent = AgentBuilder::new(model.clone())
+            .with_input_guard(KeywordModeration, GuardPolicy::Block)
+            .with_output_guard(KeywordModeration, GuardPolicy::Block)
+            .build();
+
+        // Allowed prompt and answer
+        assert_eq!(agent.prompt("Hi").await.unwrap(), "Hello!");
+
+        // Blocked prompt: the model is not called
+        assert!(matches!(
+            agent.prompt("How do I attack?").await,
+            Err(PromptError::GuardrailViolation(GuardrailViolation {
+                stage: GuardStage::Input,
+                ..
+            }))
+        ));
+        assert_eq!(model.requests().len(), 1);
+
+        // Blocked answer
+        assert!(matches!(
+            agent.prompt("Any plans?").await,
+            Err(PromptError::GuardrailViolation(GuardrailViolation {
+                stage: GuardStage::Output,
+                ..
+            }))
+        ));
+        assert_eq!(model.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_agent_guard_error() {
+        let model = MockCompletionModel::new().text("The service is unavailable");
+        let agent = AgentBuilder::new(model.clone())
+            .with_input_guard(KeywordModeration, GuardPolicy::Block)
+            .with_output_guard(KeywordModeration, GuardPolicy::Rewrite("Sorry".to_string()))
+            .build();
+
+        // Texts which cannot be moderated are neither sent to the model nor answered
+        assert!(matches!(
+            agent.prompt("Is moderation unavailable?").await,
+            Err(PromptError::ModerationError(
+                ModerationError::ProviderError(_)
+            ))
+        ));
+        assert!(model.requests().is_empty());
+
+        assert!(matches!(
+            agent.prompt("Status?").await,
+            Err(PromptError::ModerationError(
+                ModerationError::ProviderError(_)
+            ))
+        ));
+        assert_eq!(model.requests().len(), 1);
+    }
+}
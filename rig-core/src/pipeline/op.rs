@@ -167,6 +167,35 @@ pub trait Op: Send + Sync {
     {
         Sequential::new(self, Prompt::new(prompt))
     }
+
+    /// Chain an extract operation to the current chain. The extract operation expects the
+    /// current chain to output a string. The extract operation will use the given `extractor`
+    /// to extract structured data of type `Output` from the string and return it.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::pipeline::{self, Op};
+    ///
+    /// let extractor = openai_client.extractor::<Sentiment>("gpt-4").build();
+    ///
+    /// let chain = pipeline::new()
+    ///     .map(|text| format!("Analyze the sentiment of the following text: {text}!"))
+    ///     .extract(extractor);
+    ///
+    /// let result: Sentiment = chain.call("I love ice cream!".to_string()).await?;
+    /// ```
+    fn extract<M, Output>(
+        self,
+        extractor: Extractor<M, Output>,
+    ) -> Sequential<Self, Extract<M, Self::Output, Output>>
+    where
+        M: completion::CompletionModel,
+        Output: schemars::JsonSchema + for<'a> serde::Deserialize<'a> + Send + Sync,
+        Self::Output: Into<String>,
+        Self: Sized,
+    {
+        Sequential::new(self, Extract::new(extractor))
+    }
 }
 
 impl<T: Op> Op for &T {
@@ -208,9 +237,9 @@ where
     }
 }
 
-use crate::{completion, vector_store};
+use crate::{completion, extractor::Extractor, vector_store};
 
-use super::agent_ops::{Lookup, Prompt};
+use super::agent_ops::{Extract, Lookup, Prompt};
 
 // ================================================================
 // Core Op implementations
@@ -327,6 +356,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{completion::Message, extractor::ExtractorBuilder};
 
     #[tokio::test]
     async fn test_sequential_constructor() {
@@ -350,6 +380,33 @@ mod tests {
         assert_eq!(result, 12);
     }
 
+    #[tokio::test]
+    async fn test_extract_chain() {
+        #[derive(Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema, PartialEq)]
+        struct Sentiment {
+            score: f64,
+        }
+
+        let model = crate::providers::mock::MockCompletionModel::new()
+            .tool_call("submit", serde_json::json!({ "score": 0.9 }))
+            .text("I don't know");
+        let extractor = ExtractorBuilder::<Sentiment, _>::new(model.clone()).build();
+
+        let pipeline =
+            map(|text: &str| format!("Analyze the sentiment of: {text}")).extract(extractor);
+
+        let result = pipeline.call("I love ice cream!").await;
+        assert_eq!(result.unwrap(), Sentiment { score: 0.9 });
+        // The extractor is given the output of the previous op
+        assert_eq!(
+            model.requests()[0].prompt,
+            Message::user("Analyze the sentiment of: I love ice cream!")
+        );
+
+        // The extraction error is the output of the pipeline
+        assert!(pipeline.call("Meh").await.is_err());
+    }
+
     // #[tokio::test]
     // async fn test_flatten() {
     //     let op = Parallel::new(
@@ -0,0 +1,565 @@
+//! This module provides the [PromptTemplate] struct, a template engine to build prompts from
+//! named variables: preambles, few-shot examples, and the rendering of the context documents
+//! of an agent (see [ContextTemplate::Custom](crate::completion::ContextTemplate::Custom)).
+//!
+//! The syntax is a subset of Handlebars:
+//! - `{{name}}` inserts the variable `name`. Nested fields are accessed with dots
+//!   (e.g.: `{{user.name}}`). Strings are inserted as is, other values as JSON, and missing or
+//!   null values as an empty string (missing variables fail the rendering in strict mode, see
+//!   [PromptTemplate::strict]).
+//! - `{{#if name}}...{{else}}...{{/if}}` renders its first block if `name` is truthy (i.e.: set,
+//!   and not `false`, `null`, `0`, an empty string or an empty list), and the optional `else`
+//!   block otherwise.
+//! - `{{#each name}}...{{/each}}` renders its block for each item of the list `name`
+//!   (e.g.: few-shot examples). In the block, the fields of the item are accessed directly,
+//!   `{{this}}` is the item itself and `{{@index}}` its index.
+//! - `{{> name}}` renders the partial `name` (see [PromptTemplate::partial]) with the current
+//!   variables.
+//! - `{{! comment }}` is a comment, which is not rendered.
+//!
+//! Block tags alone on their line do not leave an empty line in the output.
+//!
+//! # Example
+//! ```rust
+//! use rig::{prompt::PromptTemplate, providers::openai};
+//! use serde_json::json;
+//!
+//! let template = PromptTemplate::new(
+//!     "You are a {{role}}.\n\
+//!      {{#if examples}}\n\
+//!      Answer like in the following examples:\n\
+//!      {{#each examples}}\n\
+//!      {{> example}}\n\
+//!      {{/each}}\n\
+//!      {{/if}}",
+//! )?
+//! .partial("example", PromptTemplate::new("Q: {{question}}\nA: {{answer}}")?);
+//!
+//! let preamble = template.render(&json!({
+//!     "role": "geography teacher",
+//!     "examples": [{"question": "Capital of France?", "answer": "Paris"}],
+//! }))?;
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble(&preamble)
+//!     .build();
+//! ```
+use std::{borrow::Cow, collections::HashMap, str::FromStr};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Maximum nesting of partials, to stop partials rendering themselves
+const MAX_PARTIAL_DEPTH: usize = 32;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    /// The template is malformed (e.g.: unclosed tag or block)
+    #[error("ParseError: {0}")]
+    ParseError(String),
+
+    /// A variable of the template is missing (in strict mode)
+    #[error("MissingVariableError: `{0}`")]
+    MissingVariableError(String),
+
+    /// A `{{#each}}` block iterates over a variable which is not a list
+    #[error("NotAListError: `{0}`")]
+    NotAListError(String),
+
+    /// A partial of the template is not registered
+    #[error("UnknownPartialError: `{0}`")]
+    UnknownPartialError(String),
+
+    /// Partials are nested too deep (e.g.: a partial renders itself)
+    #[error("PartialDepthError: partials are nested more than {MAX_PARTIAL_DEPTH} levels deep")]
+    PartialDepthError,
+
+    /// The variables could not be serialized
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// A parsed prompt template (see the [module](self) documentation for the syntax).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PromptTemplate {
+    nodes: Vec<Node>,
+    partials: HashMap<String, PromptTemplate>,
+    strict: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Variable(String),
+    If {
+        path: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        path: String,
+        body: Vec<Node>,
+    },
+    Partial(String),
+}
+
+impl PromptTemplate {
+    /// Parse a template.
+    pub fn new(source: &str) -> Result<Self, TemplateError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let (nodes, _) = parser.parse_block(None)?;
+
+        Ok(Self {
+            nodes,
+            partials: HashMap::new(),
+            strict: false,
+        })
+    }
+
+    /// Register a partial, rendered by `{{> name}}` tags. Partials are rendered with the
+    /// partials and the strictness of the template rendering them.
+    pub fn partial(mut self, name: impl Into<String>, partial: PromptTemplate) -> Self {
+        self.partials.insert(name.into(), partial);
+        self
+    }
+
+    /// Fail the rendering with [TemplateError::MissingVariableError] when a variable is missing,
+    /// instead of rendering it as an empty string (default: false).
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Render the template with the given variables (e.g.: a [serde_json::Value] object or a
+    /// struct implementing [Serialize]).
+    pub fn render(&self, variables: &impl Serialize) -> Result<String, TemplateError> {
+        let variables = serde_json::to_value(variables)?;
+        let mut scopes = vec![Scope {
+            value: &variables,
+            index: None,
+        }];
+
+        let mut output = String::new();
+        self.render_nodes(&self.nodes, &mut scopes, 0, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_nodes<'a>(
+        &'a self,
+        nodes: &'a [Node],
+        scopes: &mut Vec<Scope<'a>>,
+        depth: usize,
+        output: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Variable(path) => match lookup(path, scopes) {
+                    Some(value) => output.push_str(&to_text(&value)),
+                    None if self.strict => {
+                        return Err(TemplateError::MissingVariableError(path.clone()))
+                    }
+                    None => (),
+                },
+                Node::If {
+                    path,
+                    then,
+                    otherwise,
+                } => {
+                    let block = match lookup(path, scopes) {
+                        Some(value) if is_truthy(&value) => then,
+                        _ => otherwise,
+                    };
+                    self.render_nodes(block, scopes, depth, output)?;
+                }
+                Node::Each { path, body } => {
+                    let items = match lookup(path, scopes) {
+                        Some(Cow::Borrowed(Value::Array(items))) => items,
+                        Some(Cow::Borrowed(Value::Null)) => continue,
+                        Some(_) => return Err(TemplateError::NotAListError(path.clone())),
+                        None if self.strict => {
+                            return Err(TemplateError::MissingVariableError(path.clone()))
+                        }
+                        None => continue,
+                    };
+
+                    for (index, item) in items.iter().enumerate() {
+                        scopes.push(Scope {
+                            value: item,
+                            index: Some(index),
+                        });
+                        let result = self.render_nodes(body, scopes, depth, output);
+                        scopes.pop();
+                        result?;
+                    }
+                }
+                Node::Partial(name) => {
+                    if depth >= MAX_PARTIAL_DEPTH {
+                        return Err(TemplateError::PartialDepthError);
+                    }
+                    let partial = self
+                        .partials
+                        .get(name)
+                        .ok_or_else(|| TemplateError::UnknownPartialError(name.clone()))?;
+                    self.render_nodes(&partial.nodes, scopes, depth + 1, output)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PromptTemplate {
+    type Err = TemplateError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::new(source)
+    }
+}
+
+/// Variables in scope: the rendering variables, or an item of an `{{#each}}` block.
+struct Scope<'a> {
+    value: &'a Value,
+    /// Index of the item, in an `{{#each}}` block
+    index: Option<usize>,
+}
+
+/// Look up a variable by its (dotted) path, from the innermost to the outermost scope.
+fn lookup<'a>(path: &str, scopes: &[Scope<'a>]) -> Option<Cow<'a, Value>> {
+    let innermost = scopes.last()?;
+    if path == "@index" {
+        return innermost.index.map(|index| Cow::Owned(index.into()));
+    }
+
+    let mut segments = path.split('.');
+    let first = segments.next()?;
+    let mut value = match first {
+        "this" => innermost.value,
+        _ => scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.value.get(first))?,
+    };
+
+    for segment in segments {
+        value = match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => value.get(segment)?,
+        };
+    }
+    Some(Cow::Borrowed(value))
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(string) => !string.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        _ => value.to_string(),
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Text(String),
+    /// Content of a `{{...}}` tag, trimmed
+    Tag(String),
+}
+
+fn is_standalone_tag(tag: &str) -> bool {
+    tag.starts_with(['#', '/', '>', '!']) || tag == "else"
+}
+
+/// Split a template into text and tags, removing the lines of the block tags which are alone
+/// on their line.
+fn tokenize(source: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = vec![];
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or_else(|| {
+            TemplateError::ParseError(format!(
+                "Unclosed tag at byte {}",
+                source.len() - rest.len() + start
+            ))
+        })? + start;
+
+        tokens.push(Token::Text(rest[..start].to_string()));
+        tokens.push(Token::Tag(rest[start + 2..end].trim().to_string()));
+        rest = &rest[end + 2..];
+    }
+    tokens.push(Token::Text(rest.to_string()));
+
+    // Tags alternate with texts, starting and ending with a text. Each text is trimmed to
+    // `start..end`, if the tags around it are standalone.
+    let mut bounds = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Text(text) => (0, text.len()),
+            Token::Tag(_) => (0, 0),
+        })
+        .collect::<Vec<_>>();
+
+    for i in (1..tokens.len()).step_by(2) {
+        let (Token::Text(before), Token::Tag(tag), Token::Text(after)) =
+            (&tokens[i - 1], &tokens[i], &tokens[i + 1])
+        else {
+            unreachable!("Tags are surrounded by texts");
+        };
+        if !is_standalone_tag(tag) {
+            continue;
+        }
+
+        let line_start = match before.rfind('\n') {
+            Some(newline) => newline + 1,
+            None if i == 1 => 0,
+            None => continue,
+        };
+        let line_end = match after.find('\n') {
+            Some(newline) => newline + 1,
+            None if i + 2 == tokens.len() => after.len(),
+            None => continue,
+        };
+
+        if before[line_start..].trim().is_empty() && after[..line_end].trim().is_empty() {
+            bounds[i - 1].1 = line_start;
+            bounds[i + 1].0 = line_end;
+        }
+    }
+
+    Ok(tokens
+        .into_iter()
+        .zip(bounds)
+        .map(|(token, (start, end))| match token {
+            Token::Text(text) => Token::Text(text[start.min(end)..end].to_string()),
+            tag => tag,
+        })
+        .collect())
+}
+
+/// How a block of nodes ends
+enum BlockEnd {
+    /// End of the template
+    Eof,
+    /// `{{else}}` tag
+    Else,
+    /// Closing tag of the block
+    Close,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Parse the nodes of the block `block` (e.g.: `if`), or of the whole template.
+    fn parse_block(&mut self, block: Option<&str>) -> Result<(Vec<Node>, BlockEnd), TemplateError> {
+        let mut nodes = vec![];
+
+        while let Some(token) = self.tokens.get(self.pos).cloned() {
+            self.pos += 1;
+
+            let tag = match token {
+                Token::Text(text) if text.is_empty() => continue,
+                Token::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Token::Tag(tag) => tag,
+            };
+
+            if let Some(open) = tag.strip_prefix('#') {
+                let (name, path) = open.split_once(char::is_whitespace).ok_or_else(|| {
+                    TemplateError::ParseError(format!("Missing variable in `{{{{{tag}}}}}`"))
+                })?;
+                let path = parse_path(path)?;
+
+                match name {
+                    "if" => {
+                        let (then, end) = self.parse_block(Some("if"))?;
+                        let otherwise = match end {
+                            BlockEnd::Else => match self.parse_block(Some("if"))? {
+                                (otherwise, BlockEnd::Close) => otherwise,
+                                _ => {
+                                    return Err(TemplateError::ParseError(
+                                        "Several `{{else}}` in an `{{#if}}` block".to_string(),
+                                    ))
+                                }
+                            },
+                            _ => vec![],
+                        };
+                        nodes.push(Node::If {
+                            path,
+                            then,
+                            otherwise,
+                        });
+                    }
+                    "each" => match self.parse_block(Some("each"))? {
+                        (body, BlockEnd::Close) => nodes.push(Node::Each { path, body }),
+                        _ => {
+                            return Err(TemplateError::ParseError(
+                                "`{{else}}` is not supported in `{{#each}}` blocks".to_string(),
+                            ))
+                        }
+                    },
+                    _ => return Err(TemplateError::ParseError(format!("Unknown block `{name}`"))),
+                }
+            } else if let Some(close) = tag.strip_prefix('/') {
+                return match block {
+                    Some(block) if block == close.trim() => Ok((nodes, BlockEnd::Close)),
+                    _ => Err(TemplateError::ParseError(format!(
+                        "Unexpected closing tag `{{{{{tag}}}}}`"
+                    ))),
+                };
+            } else if tag == "else" {
+                return match block {
+                    Some(_) => Ok((nodes, BlockEnd::Else)),
+                    None => Err(TemplateError::ParseError(
+                        "`{{else}}` outside of a block".to_string(),
+                    )),
+                };
+            } else if let Some(name) = tag.strip_prefix('>') {
+                nodes.push(Node::Partial(parse_path(name)?));
+            } else if !tag.starts_with('!') {
+                nodes.push(Node::Variable(parse_path(&tag)?));
+            }
+        }
+
+        match block {
+            Some(block) => Err(TemplateError::ParseError(format!(
+                "Unclosed block `{{{{#{block}}}}}`"
+            ))),
+            None => Ok((nodes, BlockEnd::Eof)),
+        }
+    }
+}
+
+fn parse_path(path: &str) -> Result<String, TemplateError> {
+    let path = path.trim();
+    if path.is_empty() || path.contains(char::is_whitespace) {
+        return Err(TemplateError::ParseError(format!(
+            "Invalid variable `{path}`"
+        )));
+    }
+    Ok(path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_variables() {
+        let template =
+            PromptTemplate::new("Hello {{ name }}, you are {{user.age}} ({{missing}})").unwrap();
+
+        assert_eq!(
+            template
+                .render(&json!({"name": "Alice", "user": {"age": 30}}))
+                .unwrap(),
+            "Hello Alice, you are 30 ()"
+        );
+
+        assert!(matches!(
+            template.strict(true).render(&json!({"name": "Alice", "user": {"age": 30}})),
+            Err(TemplateError::MissingVariableError(path)) if path == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let template =
+            PromptTemplate::new("{{#if formal}}Dear {{name}}{{else}}Hi {{name}}{{/if}}!").unwrap();
+
+        assert_eq!(
+            template
+                .render(&json!({"formal": true, "name": "Bob"}))
+                .unwrap(),
+            "Dear Bob!"
+        );
+        assert_eq!(template.render(&json!({"name": "Bob"})).unwrap(), "Hi Bob!");
+    }
+
+    #[test]
+    fn test_examples_and_partials() {
+        let template = PromptTemplate::new(concat!(
+            "Examples:\n",
+            "{{#each examples}}\n",
+            "{{@index}}. {{> example}}\n",
+            "{{/each}}\n",
+            "{{#if tags}}\n",
+            "Tags: {{#each tags}}{{this}} {{/each}}\n",
+            "{{/if}}\n",
+            "End",
+        ))
+        .unwrap()
+        .partial(
+            "example",
+            PromptTemplate::new("{{question}} -> {{answer}} ({{topic}})").unwrap(),
+        );
+
+        let variables = json!({
+            "topic": "math",
+            "examples": [
+                {"question": "1 + 1", "answer": 2},
+                {"question": "2 + 2", "answer": 4},
+            ],
+            "tags": [],
+        });
+
+        assert_eq!(
+            template.render(&variables).unwrap(),
+            "Examples:\n0. 1 + 1 -> 2 (math)\n1. 2 + 2 -> 4 (math)\nEnd"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for source in [
+            "{{name",
+            "{{#if a}}",
+            "{{/if}}",
+            "{{#if a}}{{/each}}",
+            "{{#loop a}}{{/loop}}",
+            "{{else}}",
+            "{{}}",
+        ] {
+            assert!(
+                matches!(
+                    PromptTemplate::new(source),
+                    Err(TemplateError::ParseError(_))
+                ),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_recursive_partial() {
+        let template = PromptTemplate::new("{{> loop}}")
+            .unwrap()
+            .partial("loop", PromptTemplate::new("{{> loop}}").unwrap());
+
+        assert!(matches!(
+            template.render(&json!({})),
+            Err(TemplateError::PartialDepthError)
+        ));
+    }
+}
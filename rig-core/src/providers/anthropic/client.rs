@@ -1,6 +1,9 @@
 //! Anthropic client api implementation
 
-use crate::{agent::AgentBuilder, extractor::ExtractorBuilder};
+use std::time::Duration;
+
+use crate::providers::http_client::HttpClient;
+use crate::{agent::AgentBuilder, extractor::ExtractorBuilder, providers::timeouts::Timeouts};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,6 +21,8 @@ pub struct ClientBuilder<'a> {
     base_url: &'a str,
     anthropic_version: &'a str,
     anthropic_betas: Option<Vec<&'a str>>,
+    timeouts: Timeouts,
+    http_client: Option<reqwest::Client>,
 }
 
 /// Create a new anthropic client using the builder
@@ -39,6 +44,8 @@ impl<'a> ClientBuilder<'a> {
             base_url: ANTHROPIC_API_BASE_URL,
             anthropic_version: ANTHROPIC_VERSION_LATEST,
             anthropic_betas: None,
+            timeouts: Timeouts::default(),
+            http_client: None,
         }
     }
 
@@ -62,20 +69,50 @@ impl<'a> ClientBuilder<'a> {
         self
     }
 
+    /// Set the time allowed to establish a connection (default: [DEFAULT_CONNECT_TIMEOUT](crate::providers::timeouts::DEFAULT_CONNECT_TIMEOUT)).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Set the time allowed between two reads of a response (default: none).
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /// Set both timeouts (`None` disables a timeout).
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The timeouts of the builder do not apply to `http_client`.
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
     pub fn build(self) -> Client {
-        Client::new(
+        let client = Client::new_with_timeouts(
             self.api_key,
             self.base_url,
             self.anthropic_betas,
             self.anthropic_version,
-        )
+            self.timeouts,
+        );
+        match self.http_client {
+            Some(http_client) => client.with_http_client(http_client),
+            None => client,
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 impl Client {
@@ -87,10 +124,20 @@ impl Client {
     ///   - This should really never happen.
     /// - If the reqwest client cannot be built (if the TLS backend cannot be initialized).
     pub fn new(api_key: &str, base_url: &str, betas: Option<Vec<&str>>, version: &str) -> Self {
+        Self::new_with_timeouts(api_key, base_url, betas, version, Timeouts::default())
+    }
+
+    fn new_with_timeouts(
+        api_key: &str,
+        base_url: &str,
+        betas: Option<Vec<&str>>,
+        version: &str,
+        timeouts: Timeouts,
+    ) -> Self {
         Self {
             base_url: base_url.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
+            http_client: HttpClient::with_timeouts(
+                {
                     let mut headers = reqwest::header::HeaderMap::new();
                     headers.insert("x-api-key", api_key.parse().expect("API key should parse"));
                     headers.insert(
@@ -107,9 +154,9 @@ impl Client {
                         );
                     }
                     headers
-                })
-                .build()
-                .expect("Anthropic reqwest client should build"),
+                },
+                timeouts,
+            ),
         }
     }
 
@@ -120,6 +167,33 @@ impl Client {
         ClientBuilder::new(&api_key).build()
     }
 
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
         self.http_client.post(url)
@@ -154,3 +228,11 @@ impl Client {
         ExtractorBuilder::new(self.completion_model(model))
     }
 }
+
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
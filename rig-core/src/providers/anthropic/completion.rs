@@ -2,8 +2,9 @@
 
 use std::{convert::Infallible, str::FromStr};
 
+use crate::error::ApiError;
 use crate::{
-    completion::{self, CompletionError},
+    completion::{self, cost::ModelPricing, CompletionError, TokenUsage},
     json_utils,
     message::{self, MessageError},
     one_or_many::string_or_one_or_many,
@@ -36,11 +37,41 @@ pub const CLAUDE_3_SONNET: &str = "claude-3-sonnet-20240229";
 /// `claude-3-haiku-20240307` completion model
 pub const CLAUDE_3_HAIKU: &str = "claude-3-haiku-20240307";
 
+/// Pricing of Anthropic completion models (in US dollars per million tokens), by model name
+/// prefix. More specific prefixes come first (see [ModelPricing::lookup]).
+/// Tokens read from the prompt cache cost 10% of the input price, tokens written to it 125%.
+pub const PRICING: &[(&str, ModelPricing)] = &[
+    (
+        "claude-3-7-sonnet",
+        ModelPricing::new(3.0, 15.0).with_cache(0.3, 3.75),
+    ),
+    (
+        "claude-3-5-sonnet",
+        ModelPricing::new(3.0, 15.0).with_cache(0.3, 3.75),
+    ),
+    (
+        "claude-3-5-haiku",
+        ModelPricing::new(0.8, 4.0).with_cache(0.08, 1.0),
+    ),
+    (
+        "claude-3-opus",
+        ModelPricing::new(15.0, 75.0).with_cache(1.5, 18.75),
+    ),
+    (
+        "claude-3-sonnet",
+        ModelPricing::new(3.0, 15.0).with_cache(0.3, 3.75),
+    ),
+    (
+        "claude-3-haiku",
+        ModelPricing::new(0.25, 1.25).with_cache(0.03, 0.3),
+    ),
+];
+
 pub const ANTHROPIC_VERSION_2023_01_01: &str = "2023-01-01";
 pub const ANTHROPIC_VERSION_2023_06_01: &str = "2023-06-01";
 pub const ANTHROPIC_VERSION_LATEST: &str = ANTHROPIC_VERSION_2023_06_01;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CompletionResponse {
     pub content: Vec<Content>,
     pub id: String,
@@ -51,7 +82,7 @@ pub struct CompletionResponse {
     pub usage: Usage,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Usage {
     pub input_tokens: u64,
     pub cache_read_input_tokens: Option<u64>,
@@ -59,6 +90,25 @@ pub struct Usage {
     pub output_tokens: u64,
 }
 
+/// Input tokens include the tokens read from and written to the prompt cache, which Anthropic
+/// reports separately.
+impl From<&Usage> for TokenUsage {
+    fn from(usage: &Usage) -> Self {
+        let cache_read_input_tokens = usage.cache_read_input_tokens.unwrap_or(0);
+        let cache_creation_input_tokens = usage.cache_creation_input_tokens.unwrap_or(0);
+        let input_tokens =
+            usage.input_tokens + cache_read_input_tokens + cache_creation_input_tokens;
+
+        TokenUsage {
+            input_tokens,
+            output_tokens: usage.output_tokens,
+            total_tokens: input_tokens + usage.output_tokens,
+            cache_read_input_tokens,
+            cache_creation_input_tokens,
+        }
+    }
+}
+
 impl std::fmt::Display for Usage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -178,7 +228,7 @@ impl FromStr for Content {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ToolResultContent {
     Text { text: String },
-    Image(ImageSource),
+    Image { source: ImageSource },
 }
 
 impl FromStr for ToolResultContent {
@@ -189,11 +239,17 @@ impl FromStr for ToolResultContent {
     }
 }
 
+/// Source of an image: base64 encoded data or the URL of the image.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
-pub struct ImageSource {
-    pub data: String,
-    pub media_type: ImageFormat,
-    pub r#type: SourceType,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Base64 {
+        data: String,
+        media_type: ImageFormat,
+    },
+    Url {
+        url: String,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -280,6 +336,36 @@ impl TryFrom<message::ImageMediaType> for ImageFormat {
     }
 }
 
+impl TryFrom<message::Image> for ImageSource {
+    type Error = MessageError;
+
+    fn try_from(image: message::Image) -> Result<Self, Self::Error> {
+        match image.format {
+            Some(message::ContentFormat::String) => Ok(ImageSource::Url { url: image.data }),
+            _ => Ok(ImageSource::Base64 {
+                data: image.data,
+                media_type: image
+                    .media_type
+                    .ok_or(MessageError::ConversionError(
+                        "Image media type is required".to_owned(),
+                    ))?
+                    .try_into()?,
+            }),
+        }
+    }
+}
+
+impl From<ImageSource> for message::Image {
+    fn from(source: ImageSource) -> Self {
+        match source {
+            ImageSource::Base64 { data, media_type } => {
+                message::Image::base64(data, media_type.into())
+            }
+            ImageSource::Url { url } => message::Image::url(url),
+        }
+    }
+}
+
 impl From<ImageFormat> for message::ImageMediaType {
     fn from(format: ImageFormat) -> Self {
         match format {
@@ -325,47 +411,17 @@ impl TryFrom<message::Message> for Message {
                                     Ok(ToolResultContent::Text { text })
                                 }
                                 message::ToolResultContent::Image(image) => {
-                                    let media_type =
-                                        image.media_type.ok_or(MessageError::ConversionError(
-                                            "Image media type is required".to_owned(),
-                                        ))?;
-                                    let format =
-                                        image.format.ok_or(MessageError::ConversionError(
-                                            "Image format is required".to_owned(),
-                                        ))?;
-                                    Ok(ToolResultContent::Image(ImageSource {
-                                        data: image.data,
-                                        media_type: media_type.try_into()?,
-                                        r#type: format.try_into()?,
-                                    }))
+                                    Ok(ToolResultContent::Image {
+                                        source: image.try_into()?,
+                                    })
                                 }
                             })?,
                             is_error: None,
                         })
                     }
-                    message::UserContent::Image(message::Image {
-                        data,
-                        format,
-                        media_type,
-                        ..
-                    }) => {
-                        let source = ImageSource {
-                            data,
-                            media_type: match media_type {
-                                Some(media_type) => media_type.try_into()?,
-                                None => {
-                                    return Err(MessageError::ConversionError(
-                                        "Image media type is required".to_owned(),
-                                    ))
-                                }
-                            },
-                            r#type: match format {
-                                Some(format) => format.try_into()?,
-                                None => SourceType::BASE64,
-                            },
-                        };
-                        Ok(Content::Image { source })
-                    }
+                    message::UserContent::Image(image) => Ok(Content::Image {
+                        source: image.try_into()?,
+                    }),
                     message::UserContent::Document(message::Document { data, format, .. }) => {
                         let source = DocumentSource {
                             data,
@@ -414,16 +470,7 @@ impl From<ToolResultContent> for message::ToolResultContent {
     fn from(content: ToolResultContent) -> Self {
         match content {
             ToolResultContent::Text { text } => message::ToolResultContent::text(text),
-            ToolResultContent::Image(ImageSource {
-                data,
-                media_type: format,
-                r#type,
-            }) => message::ToolResultContent::image(
-                data,
-                Some(r#type.into()),
-                Some(format.into()),
-                None,
-            ),
+            ToolResultContent::Image { source } => message::ToolResultContent::Image(source.into()),
         }
     }
 }
@@ -445,12 +492,7 @@ impl TryFrom<Message> for message::Message {
                             tool_use_id,
                             content.map(|content| content.into()),
                         ),
-                        Content::Image { source } => message::UserContent::Image(message::Image {
-                            data: source.data,
-                            format: Some(message::ContentFormat::Base64),
-                            media_type: Some(source.media_type.into()),
-                            detail: None,
-                        }),
+                        Content::Image { source } => message::UserContent::Image(source.into()),
                         Content::Document { source } => message::UserContent::document(
                             source.data,
                             Some(message::ContentFormat::Base64),
@@ -502,7 +544,9 @@ impl CompletionModel {
 ///
 /// Dev Note: This is really bad design, I'm not sure why they did it like this..
 fn calculate_max_tokens(model: &str) -> Option<u64> {
-    if model.starts_with("claude-3-5-sonnet") || model.starts_with("claude-3-5-haiku") {
+    if model.starts_with("claude-3-7-sonnet") {
+        Some(64000)
+    } else if model.starts_with("claude-3-5-sonnet") || model.starts_with("claude-3-5-haiku") {
         Some(8192)
     } else if model.starts_with("claude-3-opus")
         || model.starts_with("claude-3-sonnet")
@@ -514,11 +558,6 @@ fn calculate_max_tokens(model: &str) -> Option<u64> {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Metadata {
-    user_id: Option<String>,
-}
-
 #[derive(Default, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ToolChoice {
@@ -533,11 +572,18 @@ pub enum ToolChoice {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: completion::CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         // Note: Ideally we'd introduce provider-specific Request models to handle the
         // specific requirements of each provider. For now, we just manually check while
         // building the request as a raw JSON document.
@@ -574,13 +620,27 @@ impl completion::CompletionModel for CompletionModel {
             "model": self.model,
             "messages": messages,
             "max_tokens": max_tokens,
-            "system": completion_request.preamble.unwrap_or("".to_string()),
         });
 
+        if let Some(preamble) = completion_request.preamble {
+            json_utils::merge_inplace(&mut request, json!({ "system": preamble }));
+        }
+
         if let Some(temperature) = completion_request.temperature {
             json_utils::merge_inplace(&mut request, json!({ "temperature": temperature }));
         }
 
+        if let Some(top_p) = completion_request.top_p {
+            json_utils::merge_inplace(&mut request, json!({ "top_p": top_p }));
+        }
+
+        if !completion_request.stop.is_empty() {
+            json_utils::merge_inplace(
+                &mut request,
+                json!({ "stop_sequences": completion_request.stop }),
+            );
+        }
+
         if !completion_request.tools.is_empty() {
             json_utils::merge_inplace(
                 &mut request,
@@ -624,9 +684,21 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        Some((&response.usage).into())
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        serde_json::to_value(response).ok()
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        ModelPricing::lookup(PRICING, &self.model)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -719,94 +791,84 @@ mod tests {
             })
         };
 
-        match assistant_message {
-            Message { role, content } => {
-                assert_eq!(role, Role::Assistant);
-                assert_eq!(
-                    content.first(),
-                    Content::Text {
-                        text: "\n\nHello there, how may I assist you today?".to_owned()
-                    }
-                );
+        let Message { role, content } = assistant_message;
+        assert_eq!(role, Role::Assistant);
+        assert_eq!(
+            content.first(),
+            Content::Text {
+                text: "\n\nHello there, how may I assist you today?".to_owned()
             }
-        }
+        );
 
-        match assistant_message2 {
-            Message { role, content } => {
-                assert_eq!(role, Role::Assistant);
-                assert_eq!(content.len(), 2);
+        let Message { role, content } = assistant_message2;
+        assert_eq!(role, Role::Assistant);
+        assert_eq!(content.len(), 2);
 
-                let mut iter = content.into_iter();
+        let mut iter = content.into_iter();
 
-                match iter.next().unwrap() {
-                    Content::Text { text } => {
-                        assert_eq!(text, "\n\nHello there, how may I assist you today?");
-                    }
-                    _ => panic!("Expected text content"),
-                }
-
-                match iter.next().unwrap() {
-                    Content::ToolUse { id, name, input } => {
-                        assert_eq!(id, "toolu_01A09q90qw90lq917835lq9");
-                        assert_eq!(name, "get_weather");
-                        assert_eq!(input, json!({"location": "San Francisco, CA"}));
-                    }
-                    _ => panic!("Expected tool use content"),
-                }
+        match iter.next().unwrap() {
+            Content::Text { text } => {
+                assert_eq!(text, "\n\nHello there, how may I assist you today?");
+            }
+            _ => panic!("Expected text content"),
+        }
 
-                assert_eq!(iter.next(), None);
+        match iter.next().unwrap() {
+            Content::ToolUse { id, name, input } => {
+                assert_eq!(id, "toolu_01A09q90qw90lq917835lq9");
+                assert_eq!(name, "get_weather");
+                assert_eq!(input, json!({"location": "San Francisco, CA"}));
             }
+            _ => panic!("Expected tool use content"),
         }
 
-        match user_message {
-            Message { role, content } => {
-                assert_eq!(role, Role::User);
-                assert_eq!(content.len(), 3);
+        assert_eq!(iter.next(), None);
 
-                let mut iter = content.into_iter();
+        let Message { role, content } = user_message;
+        assert_eq!(role, Role::User);
+        assert_eq!(content.len(), 3);
 
-                match iter.next().unwrap() {
-                    Content::Image { source } => {
-                        assert_eq!(
-                            source,
-                            ImageSource {
-                                data: "/9j/4AAQSkZJRg...".to_owned(),
-                                media_type: ImageFormat::JPEG,
-                                r#type: SourceType::BASE64,
-                            }
-                        );
-                    }
-                    _ => panic!("Expected image content"),
-                }
+        let mut iter = content.into_iter();
 
-                match iter.next().unwrap() {
-                    Content::Text { text } => {
-                        assert_eq!(text, "What is in this image?");
+        match iter.next().unwrap() {
+            Content::Image { source } => {
+                assert_eq!(
+                    source,
+                    ImageSource::Base64 {
+                        data: "/9j/4AAQSkZJRg...".to_owned(),
+                        media_type: ImageFormat::JPEG,
                     }
-                    _ => panic!("Expected text content"),
-                }
+                );
+            }
+            _ => panic!("Expected image content"),
+        }
 
-                match iter.next().unwrap() {
-                    Content::ToolResult {
-                        tool_use_id,
-                        content,
-                        is_error,
-                    } => {
-                        assert_eq!(tool_use_id, "toolu_01A09q90qw90lq917835lq9");
-                        assert_eq!(
-                            content.first(),
-                            ToolResultContent::Text {
-                                text: "15 degrees".to_owned()
-                            }
-                        );
-                        assert_eq!(is_error, None);
-                    }
-                    _ => panic!("Expected tool result content"),
-                }
+        match iter.next().unwrap() {
+            Content::Text { text } => {
+                assert_eq!(text, "What is in this image?");
+            }
+            _ => panic!("Expected text content"),
+        }
 
-                assert_eq!(iter.next(), None);
+        match iter.next().unwrap() {
+            Content::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+            } => {
+                assert_eq!(tool_use_id, "toolu_01A09q90qw90lq917835lq9");
+                assert_eq!(
+                    content.first(),
+                    ToolResultContent::Text {
+                        text: "15 degrees".to_owned()
+                    }
+                );
+                assert_eq!(is_error, None);
             }
+            _ => panic!("Expected tool result content"),
         }
+
+        assert_eq!(iter.next(), None);
     }
 
     #[test]
@@ -953,4 +1015,44 @@ mod tests {
         assert_eq!(assistant_message, original_assistant_message);
         assert_eq!(tool_message, original_tool_message);
     }
+
+    #[test]
+    fn test_image_sources() {
+        let message: Message = message::Message::user_with_images(
+            "What is in these images?",
+            [
+                message::Image::url("https://example.com/image.jpg"),
+                message::Image::base64("/9j/4AAQSkZJRg...", message::ImageMediaType::JPEG),
+            ],
+        )
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&message.content).unwrap(),
+            json!([
+                {"type": "text", "text": "What is in these images?"},
+                {"type": "image", "source": {"type": "url", "url": "https://example.com/image.jpg"}},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/jpeg", "data": "/9j/4AAQSkZJRg..."}},
+            ])
+        );
+
+        let tool_result = ToolResultContent::Image {
+            source: ImageSource::Url {
+                url: "https://example.com/image.jpg".to_string(),
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&tool_result).unwrap(),
+            json!({"type": "image", "source": {"type": "url", "url": "https://example.com/image.jpg"}})
+        );
+    }
+
+    #[test]
+    fn test_default_max_tokens() {
+        assert_eq!(calculate_max_tokens(CLAUDE_3_7_SONNET), Some(64000));
+        assert_eq!(calculate_max_tokens(CLAUDE_3_5_HAIKU), Some(8192));
+        assert_eq!(calculate_max_tokens(CLAUDE_3_HAIKU), Some(4096));
+        assert_eq!(calculate_max_tokens("claude-2.1"), None);
+    }
 }
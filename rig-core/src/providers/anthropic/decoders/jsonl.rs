@@ -1,5 +1,5 @@
 //! JSONL is currently not used, it might be used when Anthropic batches beta feature is used.
-use crate::providers::anthropic::decoders::line::LineDecoder;
+use crate::providers::sse::line::LineDecoder;
 use futures::{Stream, StreamExt};
 use serde::de::DeserializeOwned;
 use serde::de::Error;
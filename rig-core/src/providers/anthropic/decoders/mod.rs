@@ -6,5 +6,6 @@
  * https://github.com/anthropics/anthropic-sdk-typescript/blob/main/LICENSE
  */
 pub mod jsonl;
-pub mod line;
-pub mod sse;
+
+// The SSE and line decoders are shared by the providers, see [crate::providers::sse]
+pub use crate::providers::sse::{self, line};
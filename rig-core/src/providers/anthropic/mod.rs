@@ -4,7 +4,7 @@
 //! ```
 //! use rig::providers::anthropic;
 //!
-//! let client = anthropic::Anthropic::new("YOUR_API_KEY");
+//! let client = anthropic::Client::new("YOUR_API_KEY");
 //!
 //! let sonnet = client.completion_model(anthropic::CLAUDE_3_5_SONNET);
 //! ```
@@ -17,5 +17,6 @@ pub mod streaming;
 pub use client::{Client, ClientBuilder};
 pub use completion::{
     ANTHROPIC_VERSION_2023_01_01, ANTHROPIC_VERSION_2023_06_01, ANTHROPIC_VERSION_LATEST,
-    CLAUDE_3_5_SONNET, CLAUDE_3_7_SONNET, CLAUDE_3_HAIKU, CLAUDE_3_OPUS, CLAUDE_3_SONNET,
+    CLAUDE_3_5_HAIKU, CLAUDE_3_5_SONNET, CLAUDE_3_7_SONNET, CLAUDE_3_HAIKU, CLAUDE_3_OPUS,
+    CLAUDE_3_SONNET,
 };
@@ -4,11 +4,13 @@ use serde::Deserialize;
 use serde_json::json;
 
 use super::completion::{CompletionModel, Content, Message, ToolChoice, ToolDefinition, Usage};
-use super::decoders::sse::from_response as sse_from_response;
 use crate::completion::{CompletionError, CompletionRequest};
+use crate::error::ApiError;
 use crate::json_utils::merge_inplace;
 use crate::message::MessageError;
+use crate::providers::sse::from_response as sse_from_response;
 use crate::streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult};
+use crate::tool::ToolError;
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -33,6 +35,10 @@ pub enum StreamingEvent {
     },
     MessageStop,
     Ping,
+    /// Error occurring after the start of the stream (e.g.: `overloaded_error`)
+    Error {
+        error: serde_json::Value,
+    },
     #[serde(other)]
     Unknown,
 }
@@ -80,6 +86,9 @@ impl StreamingCompletionModel for CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let max_tokens = if let Some(tokens) = completion_request.max_tokens {
             tokens
         } else if let Some(tokens) = self.default_max_tokens {
@@ -111,14 +120,28 @@ impl StreamingCompletionModel for CompletionModel {
             "model": self.model,
             "messages": messages,
             "max_tokens": max_tokens,
-            "system": completion_request.preamble.unwrap_or("".to_string()),
             "stream": true,
         });
 
+        if let Some(preamble) = completion_request.preamble {
+            merge_inplace(&mut request, json!({ "system": preamble }));
+        }
+
         if let Some(temperature) = completion_request.temperature {
             merge_inplace(&mut request, json!({ "temperature": temperature }));
         }
 
+        if let Some(top_p) = completion_request.top_p {
+            merge_inplace(&mut request, json!({ "top_p": top_p }));
+        }
+
+        if !completion_request.stop.is_empty() {
+            merge_inplace(
+                &mut request,
+                json!({ "stop_sequences": completion_request.stop }),
+            );
+        }
+
         if !completion_request.tools.is_empty() {
             merge_inplace(
                 &mut request,
@@ -149,7 +172,7 @@ impl StreamingCompletionModel for CompletionModel {
             .await?;
 
         if !response.status().is_success() {
-            return Err(CompletionError::ProviderError(response.text().await?));
+            return Err(ApiError::from_response(response).await.into());
         }
 
         // Use our SSE decoder to directly handle Server-Sent Events format
@@ -157,6 +180,7 @@ impl StreamingCompletionModel for CompletionModel {
 
         Ok(Box::pin(stream! {
             let mut current_tool_call: Option<ToolCallState> = None;
+            let mut usage: Option<Usage> = None;
             let mut sse_stream = Box::pin(sse_stream);
 
             while let Some(sse_result) = sse_stream.next().await {
@@ -165,21 +189,19 @@ impl StreamingCompletionModel for CompletionModel {
                         // Parse the SSE data as a StreamingEvent
                         match serde_json::from_str::<StreamingEvent>(&sse.data) {
                             Ok(event) => {
-                                if let Some(result) = handle_event(&event, &mut current_tool_call) {
+                                if let Some(result) = handle_event(&event, &mut current_tool_call, &mut usage) {
                                     yield result;
                                 }
                             },
                             Err(e) => {
                                 if !sse.data.trim().is_empty() {
-                                    yield Err(CompletionError::ResponseError(
-                                        format!("Failed to parse JSON: {} (Data: {})", e, sse.data)
-                                    ));
+                                    yield Err(CompletionError::JsonError(e));
                                 }
                             }
                         }
                     },
                     Err(e) => {
-                        yield Err(CompletionError::ResponseError(format!("SSE Error: {}", e)));
+                        yield Err(CompletionError::from(e));
                         break;
                     }
                 }
@@ -191,8 +213,27 @@ impl StreamingCompletionModel for CompletionModel {
 fn handle_event(
     event: &StreamingEvent,
     current_tool_call: &mut Option<ToolCallState>,
+    usage: &mut Option<Usage>,
 ) -> Option<Result<StreamingChoice, CompletionError>> {
     match event {
+        // The input tokens are reported at the start of the message, the output tokens at its end
+        StreamingEvent::MessageStart { message } => {
+            *usage = Some(message.usage.clone());
+            None
+        }
+        StreamingEvent::MessageDelta {
+            usage: partial_usage,
+            ..
+        } => {
+            let usage = usage.get_or_insert_with(|| Usage {
+                input_tokens: partial_usage.input_tokens.unwrap_or(0) as u64,
+                cache_read_input_tokens: None,
+                cache_creation_input_tokens: None,
+                output_tokens: 0,
+            });
+            usage.output_tokens = partial_usage.output_tokens as u64;
+            Some(Ok(StreamingChoice::Usage((&*usage).into())))
+        }
         StreamingEvent::ContentBlockDelta { delta, .. } => match delta {
             ContentDelta::TextDelta { text } => {
                 if current_tool_call.is_none() {
@@ -232,17 +273,97 @@ fn handle_event(
                         tool_call.id,
                         json_value,
                     ))),
-                    Err(e) => Some(Err(CompletionError::from(e))),
+                    Err(e) => Some(Err(CompletionError::ToolCallError {
+                        error: ToolError::from_args_error(&tool_call.input_json, e),
+                        name: tool_call.name,
+                    })),
                 }
             } else {
                 None
             }
         }
         // Ignore other event types or handle as needed
-        StreamingEvent::MessageStart { .. }
-        | StreamingEvent::MessageDelta { .. }
-        | StreamingEvent::MessageStop
-        | StreamingEvent::Ping
-        | StreamingEvent::Unknown => None,
+        StreamingEvent::Error { error } => Some(Err(ApiError::new(
+            None,
+            &serde_json::json!({ "error": error }).to_string(),
+        )
+        .into())),
+        StreamingEvent::MessageStop | StreamingEvent::Ping | StreamingEvent::Unknown => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::TokenUsage;
+
+    #[test]
+    fn test_streamed_usage() {
+        let events = [
+            r#"{"type": "message_start", "message": {"id": "msg_1", "role": "assistant", "content": [], "model": "claude-3-5-sonnet-latest", "stop_reason": null, "stop_sequence": null, "usage": {"input_tokens": 10, "cache_read_input_tokens": 90, "cache_creation_input_tokens": 0, "output_tokens": 1}}}"#,
+            r#"{"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hi"}}"#,
+            r#"{"type": "message_delta", "delta": {"stop_reason": "end_turn", "stop_sequence": null}, "usage": {"output_tokens": 5}}"#,
+        ];
+
+        let mut current_tool_call = None;
+        let mut usage = None;
+        let chunks = events
+            .iter()
+            .filter_map(|event| {
+                handle_event(
+                    &serde_json::from_str(event).unwrap(),
+                    &mut current_tool_call,
+                    &mut usage,
+                )
+            })
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert!(matches!(&chunks[0], StreamingChoice::Message(text) if text == "Hi"));
+        assert!(matches!(
+            chunks[1],
+            StreamingChoice::Usage(TokenUsage {
+                input_tokens: 100,
+                output_tokens: 5,
+                total_tokens: 105,
+                cache_read_input_tokens: 90,
+                cache_creation_input_tokens: 0,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_streamed_errors() {
+        let mut current_tool_call = None;
+        let mut usage = None;
+        let mut handle = |event: &str| {
+            handle_event(
+                &serde_json::from_str(event).unwrap(),
+                &mut current_tool_call,
+                &mut usage,
+            )
+        };
+
+        handle(
+            r#"{"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "add", "input": {}}}"#,
+        );
+        handle(
+            r#"{"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "{\"x\": 1"}}"#,
+        );
+        assert!(matches!(
+            handle(r#"{"type": "content_block_stop", "index": 0}"#),
+            Some(Err(CompletionError::ToolCallError {
+                name,
+                error: ToolError::IncompleteArguments(args),
+            })) if name == "add" && args == r#"{"x": 1"#
+        ));
+
+        assert!(matches!(
+            handle(
+                r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#
+            ),
+            Some(Err(CompletionError::ApiError(error)))
+                if error.code.as_deref() == Some("overloaded_error") && error.message == "Overloaded"
+        ));
     }
 }
@@ -4,14 +4,16 @@
 //! ```
 //! use rig::providers::azure;
 //!
-//! let client = azure::Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+//! let client = azure::Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
 //!
 //! let gpt4o = client.completion_model(azure::GPT_4O);
 //! ```
 
 use super::openai::{send_compatible_streaming_request, TranscriptionResponse};
 
+use crate::error::ApiError;
 use crate::json_utils::merge;
+use crate::providers::http_client::HttpClient;
 use crate::streaming::{StreamingCompletionModel, StreamingResult};
 use crate::{
     agent::AgentBuilder,
@@ -35,7 +37,7 @@ use serde_json::json;
 pub struct Client {
     api_version: String,
     azure_endpoint: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 #[derive(Clone)]
@@ -77,10 +79,7 @@ impl Client {
         Self {
             api_version: api_version.to_string(),
             azure_endpoint: azure_endpoint.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers(headers)
-                .build()
-                .expect("Azure OpenAI reqwest client should build"),
+            http_client: HttpClient::new(headers),
         }
     }
 
@@ -130,51 +129,70 @@ impl Client {
         Self::new(auth, &api_version, &azure_endpoint)
     }
 
-    fn post_embedding(&self, deployment_id: &str) -> reqwest::RequestBuilder {
-        let url = format!(
-            "{}/openai/deployments/{}/embeddings?api-version={}",
-            self.azure_endpoint, deployment_id, self.api_version
+    /// URL of an endpoint of a deployment, e.g.:
+    /// `{azure_endpoint}/openai/deployments/{deployment_id}/chat/completions?api-version={api_version}`
+    fn deployment_url(&self, deployment_id: &str, path: &str) -> String {
+        format!(
+            "{}/openai/deployments/{}/{}?api-version={}",
+            self.azure_endpoint.trim_end_matches('/'),
+            deployment_id,
+            path,
+            self.api_version
         )
-        .replace("//", "/");
-        self.http_client.post(url)
+    }
+
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: crate::providers::timeouts::Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
+    fn post_embedding(&self, deployment_id: &str) -> reqwest::RequestBuilder {
+        self.http_client
+            .post(self.deployment_url(deployment_id, "embeddings"))
     }
 
     fn post_chat_completion(&self, deployment_id: &str) -> reqwest::RequestBuilder {
-        let url = format!(
-            "{}/openai/deployments/{}/chat/completions?api-version={}",
-            self.azure_endpoint, deployment_id, self.api_version
-        )
-        .replace("//", "/");
-        self.http_client.post(url)
+        self.http_client
+            .post(self.deployment_url(deployment_id, "chat/completions"))
     }
 
     fn post_transcription(&self, deployment_id: &str) -> reqwest::RequestBuilder {
-        let url = format!(
-            "{}/openai/deployments/{}/audio/translations?api-version={}",
-            self.azure_endpoint, deployment_id, self.api_version
-        )
-        .replace("//", "/");
-        self.http_client.post(url)
+        self.http_client
+            .post(self.deployment_url(deployment_id, "audio/translations"))
     }
 
     #[cfg(feature = "image")]
     fn post_image_generation(&self, deployment_id: &str) -> reqwest::RequestBuilder {
-        let url = format!(
-            "{}/openai/deployments/{}/images/generations?api-version={}",
-            self.azure_endpoint, deployment_id, self.api_version
-        )
-        .replace("//", "/");
-        self.http_client.post(url)
+        self.http_client
+            .post(self.deployment_url(deployment_id, "images/generations"))
     }
 
     #[cfg(feature = "audio")]
     fn post_audio_generation(&self, deployment_id: &str) -> reqwest::RequestBuilder {
-        let url = format!(
-            "{}/openai/deployments/{}/audio/speech?api-version={}",
-            self.azure_endpoint, deployment_id, self.api_version
-        )
-        .replace("//", "/");
-        self.http_client.post(url)
+        self.http_client
+            .post(self.deployment_url(deployment_id, "audio/speech"))
     }
 
     /// Create an embedding model with the given name.
@@ -186,7 +204,7 @@ impl Client {
     /// use rig::providers::azure::{Client, self};
     ///
     /// // Initialize the Azure OpenAI client
-    /// let azure = Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+    /// let azure = Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
     ///
     /// let embedding_model = azure.embedding_model(azure::TEXT_EMBEDDING_3_LARGE);
     /// ```
@@ -206,7 +224,7 @@ impl Client {
     /// use rig::providers::azure::{Client, self};
     ///
     /// // Initialize the Azure OpenAI client
-    /// let azure = Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+    /// let azure = Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
     ///
     /// let embedding_model = azure.embedding_model("model-unknown-to-rig", 3072);
     /// ```
@@ -221,7 +239,7 @@ impl Client {
     /// use rig::providers::azure::{Client, self};
     ///
     /// // Initialize the Azure OpenAI client
-    /// let azure = Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+    /// let azure = Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
     ///
     /// let embeddings = azure.embeddings(azure::TEXT_EMBEDDING_3_LARGE)
     ///     .simple_document("doc0", "Hello, world!")
@@ -241,7 +259,7 @@ impl Client {
     /// use rig::providers::azure::{Client, self};
     ///
     /// // Initialize the Azure OpenAI client
-    /// let azure = Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+    /// let azure = Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
     ///
     /// let gpt4 = azure.completion_model(azure::GPT_4);
     /// ```
@@ -256,7 +274,7 @@ impl Client {
     /// use rig::providers::azure::{Client, self};
     ///
     /// // Initialize the Azure OpenAI client
-    /// let azure = Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+    /// let azure = Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
     ///
     /// let whisper = azure.transcription_model("model-unknown-to-rig");
     /// ```
@@ -271,7 +289,7 @@ impl Client {
     /// use rig::providers::azure::{Client, self};
     ///
     /// // Initialize the Azure OpenAI client
-    /// let azure = Client::new("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
+    /// let azure = Client::from_api_key("YOUR_API_KEY", "YOUR_API_VERSION", "YOUR_ENDPOINT");
     ///
     /// let agent = azure.agent(azure::GPT_4)
     ///    .preamble("You are comedian AI with a mission to make people laugh.")
@@ -291,6 +309,14 @@ impl Client {
     }
 }
 
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -364,11 +390,17 @@ pub struct EmbeddingModel {
     client: Client,
     pub model: String,
     ndims: usize,
+    /// Number of dimensions the embeddings are shortened to by Azure OpenAI, if any
+    dimensions: Option<usize>,
 }
 
 impl embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 1024;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     fn ndims(&self) -> usize {
         self.ndims
     }
@@ -379,13 +411,19 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         documents: impl IntoIterator<Item = String>,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
+        crate::rate_limit::acquire_embedding(self.client.rate_limiter(), &documents).await?;
+
+        let mut request = json!({
+            "input": documents,
+        });
+        if let Some(dimensions) = self.dimensions {
+            request["dimensions"] = json!(dimensions);
+        }
 
         let response = self
             .client
             .post_embedding(&self.model)
-            .json(&json!({
-                "input": documents,
-            }))
+            .json(&request)
             .send()
             .await?;
 
@@ -406,7 +444,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                     Ok(response
                         .data
                         .into_iter()
-                        .zip(documents.into_iter())
+                        .zip(documents)
                         .map(|(embedding, document)| embeddings::Embedding {
                             document,
                             vec: embedding.embedding,
@@ -416,7 +454,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                 ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -427,8 +465,22 @@ impl EmbeddingModel {
             client,
             model: model.to_string(),
             ndims,
+            dimensions: None,
         }
     }
+
+    /// Shorten the embeddings to `ndims` dimensions (only supported by the `text-embedding-3`
+    /// models).
+    ///
+    /// # Example
+    /// ```rust
+    /// let model = azure.embedding_model(azure::TEXT_EMBEDDING_3_LARGE).dimensions(256);
+    /// ```
+    pub fn dimensions(mut self, ndims: usize) -> Self {
+        self.ndims = ndims;
+        self.dimensions = Some(ndims);
+        self
+    }
 }
 
 // ================================================================
@@ -480,6 +532,8 @@ impl CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<serde_json::Value, CompletionError> {
+        let generation = completion_request.generation_config();
+
         // Add preamble to chat history (if available)
         let mut full_history: Vec<openai::Message> = match &completion_request.preamble {
             Some(preamble) => vec![openai::Message::system(preamble)],
@@ -518,6 +572,7 @@ impl CompletionModel {
                 "tool_choice": "auto",
             })
         };
+        let request = json_utils::merge(request, generation.openai_params());
 
         let request = if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
@@ -532,11 +587,22 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = openai::CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn context_template(&self) -> completion::ContextTemplate {
+        completion::ContextTemplate::Markdown
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request = self.create_completion_request(completion_request)?;
 
         let response = self
@@ -561,7 +627,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -571,6 +637,8 @@ impl completion::CompletionModel for CompletionModel {
 // -----------------------------------------------------
 impl StreamingCompletionModel for CompletionModel {
     async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &request).await?;
+
         let mut request = self.create_completion_request(request)?;
 
         request = merge(request, json!({"stream": true}));
@@ -657,7 +725,7 @@ impl transcription::TranscriptionModel for TranscriptionModel {
                 )),
             }
         } else {
-            Err(TranscriptionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -757,7 +825,7 @@ mod audio_generation {
 
             let response = self
                 .client
-                .post_audio_generation("/audio/speech")
+                .post_audio_generation(&self.model)
                 .json(&request)
                 .send()
                 .await?;
@@ -787,6 +855,17 @@ mod azure_tests {
     use crate::completion::CompletionModel;
     use crate::embeddings::EmbeddingModel;
 
+    #[test]
+    fn test_deployment_url() {
+        let client =
+            Client::from_api_key("key", "2024-10-21", "https://resource.openai.azure.com/");
+
+        assert_eq!(
+            client.deployment_url("gpt-4o", "chat/completions"),
+            "https://resource.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-10-21"
+        );
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_azure_embedding() {
@@ -817,8 +896,18 @@ mod azure_tests {
                 documents: vec![],
                 max_tokens: Some(100),
                 temperature: Some(0.0),
+                top_p: None,
+                stop: vec![],
+                seed: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                response_format: None,
                 tools: vec![],
                 additional_params: None,
+                idempotency_key: None,
+                store: None,
+                metadata: None,
+                context_template: Default::default(),
             })
             .await
             .unwrap();
@@ -0,0 +1,789 @@
+//! Record and replay of the HTTP traffic of providers ("cassettes"), to make the integration
+//! tests of agents, extractors and embeddings deterministic and free.
+//!
+//! A [Cassette] is a local HTTP server standing in for the API of a provider: provider clients
+//! are pointed at it with their `from_url` constructor (or the `base_url` method of their
+//! `ClientBuilder`). In [CassetteMode::Record], the requests are forwarded to the real API and
+//! the interactions (requests and responses) are saved to the cassette file. In
+//! [CassetteMode::Replay], the recorded responses are served from the file, without network
+//! access nor API keys (e.g.: in CI).
+//!
+//! Requests are matched on their method, path and body (JSON bodies are compared as JSON
+//! values, so the order of their keys does not matter), in order of recording: a request sent
+//! twice is answered with the two recorded responses in turn. A request without recorded
+//! interaction is answered with a `500` error, failing the test. Multipart bodies (e.g.: file
+//! uploads), whose boundaries are random, are matched on their method and path only.
+//!
+//! The headers of the requests (e.g.: `Authorization`) are not recorded, and the values of the
+//! query parameters carrying credentials (e.g.: the `key` parameter of Gemini, see
+//! [CREDENTIAL_PARAMS]) are redacted, so cassettes can be committed to the repository. These
+//! parameters are ignored when matching requests, so that cassettes are replayed with any key.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     completion::Prompt,
+//!     providers::{cassette::{Cassette, CassetteMode}, openai},
+//! };
+//!
+//! #[tokio::test]
+//! async fn test_agent() {
+//!     // Run once with `RIG_CASSETTE=record` (and an API key) to record the cassette
+//!     let cassette = Cassette::start(
+//!         "tests/cassettes/agent.json",
+//!         "https://api.openai.com/v1",
+//!         CassetteMode::from_env(),
+//!     )
+//!     .await
+//!     .unwrap();
+//!
+//!     let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
+//!     let openai = openai::Client::from_url(&api_key, &cassette.url());
+//!     let agent = openai.agent(openai::GPT_4O).build();
+//!
+//!     assert!(agent.prompt("What is the capital of France?").await.unwrap().contains("Paris"));
+//! }
+//! ```
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// Environment variable setting the mode of the cassettes (see [CassetteMode::from_env])
+pub const CASSETTE_MODE_ENV: &str = "RIG_CASSETTE";
+
+/// Query parameters carrying credentials (compared case-insensitively), whose values are
+/// redacted from the recorded paths
+pub const CREDENTIAL_PARAMS: [&str; 6] = [
+    "key",
+    "api_key",
+    "apikey",
+    "api-key",
+    "access_token",
+    "token",
+];
+
+/// Value replacing the credentials in the recorded paths
+const REDACTED: &str = "REDACTED";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CassetteError {
+    /// Error reading or writing the cassette file, or of the local server
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// Error parsing the cassette file
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Whether a [Cassette] records or replays the traffic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Forward the requests to the real API, and save the interactions to the cassette file
+    /// (replacing its previous interactions)
+    Record,
+    /// Answer the requests with the interactions of the cassette file
+    Replay,
+    /// Replay the cassette file if it exists, record it otherwise
+    Auto,
+}
+
+impl CassetteMode {
+    /// The mode set in the `RIG_CASSETTE` environment variable (`record`, `replay` or `auto`),
+    /// [CassetteMode::Replay] by default so that tests never reach the real API unless asked to.
+    pub fn from_env() -> Self {
+        match std::env::var(CASSETTE_MODE_ENV).as_deref() {
+            Ok("record") => CassetteMode::Record,
+            Ok("auto") => CassetteMode::Auto,
+            _ => CassetteMode::Replay,
+        }
+    }
+}
+
+/// Recorded HTTP request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path of the request, relative to the base URL of the API (e.g.: `/chat/completions`)
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub body: RecordedBody,
+}
+
+/// Recorded HTTP response.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// Headers of the response, without the hop-by-hop headers (e.g.: `Transfer-Encoding`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+    pub body: RecordedBody,
+}
+
+/// Body of a recorded request or response: text if it is valid UTF-8 (e.g.: JSON or
+/// server-sent events), base64 otherwise (e.g.: audio or images).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedBody {
+    Text(String),
+    Base64(String),
+}
+
+impl RecordedBody {
+    fn new(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => RecordedBody::Text(text.to_string()),
+            Err(_) => RecordedBody::Base64(BASE64_STANDARD.encode(bytes)),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            RecordedBody::Text(text) => text.as_bytes().to_vec(),
+            RecordedBody::Base64(data) => BASE64_STANDARD.decode(data).unwrap_or_default(),
+        }
+    }
+
+    /// Whether two bodies are equal, as JSON values if both are JSON.
+    fn matches(&self, other: &RecordedBody) -> bool {
+        match (self, other) {
+            (RecordedBody::Text(a), RecordedBody::Text(b)) => {
+                match (
+                    serde_json::from_str::<serde_json::Value>(a),
+                    serde_json::from_str::<serde_json::Value>(b),
+                ) {
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => a == b,
+                }
+            }
+            _ => self == other,
+        }
+    }
+}
+
+/// Recorded request and its response.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+impl RecordedRequest {
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        let multipart = self
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("multipart/"));
+
+        self.method == other.method
+            && redact_path(&self.path, None) == redact_path(&other.path, None)
+            && (multipart || self.body.matches(&other.body))
+    }
+}
+
+/// `path` with the values of its [CREDENTIAL_PARAMS] query parameters replaced with
+/// `replacement`, or with these parameters removed if `replacement` is `None`.
+fn redact_path(path: &str, replacement: Option<&str>) -> String {
+    let Some((path, query)) = path.split_once('?') else {
+        return path.to_string();
+    };
+
+    let params = query
+        .split('&')
+        .filter_map(|param| {
+            let name = param.split_once('=').map_or(param, |(name, _)| name);
+            if !CREDENTIAL_PARAMS.contains(&name.to_lowercase().as_str()) {
+                Some(param.to_string())
+            } else {
+                replacement.map(|replacement| format!("{}={}", name, replacement))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if params.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, params.join("&"))
+    }
+}
+
+/// Content of a cassette file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// State of a cassette, shared with its server.
+struct State {
+    path: PathBuf,
+    /// Base URL of the real API, to which the requests are forwarded when recording
+    upstream: String,
+    recording: bool,
+    interactions: Vec<Interaction>,
+    /// Whether each interaction was replayed
+    replayed: Vec<bool>,
+    http_client: reqwest::Client,
+}
+
+/// Local HTTP server recording or replaying the traffic of provider clients (see the
+/// [module](self) documentation). The server stops when the cassette is dropped.
+pub struct Cassette {
+    url: String,
+    state: Arc<Mutex<State>>,
+    server: JoinHandle<()>,
+}
+
+impl Cassette {
+    /// Start a cassette server for the API at `upstream` (e.g.: `https://api.openai.com/v1`),
+    /// recording or replaying the cassette file at `path` according to `mode`.
+    pub async fn start(
+        path: impl AsRef<Path>,
+        upstream: &str,
+        mode: CassetteMode,
+    ) -> Result<Self, CassetteError> {
+        let path = path.as_ref().to_path_buf();
+        let recording = match mode {
+            CassetteMode::Record => true,
+            CassetteMode::Replay => false,
+            CassetteMode::Auto => !path.exists(),
+        };
+        let interactions = if recording {
+            vec![]
+        } else {
+            serde_json::from_slice::<CassetteFile>(&std::fs::read(&path)?)?.interactions
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State {
+            path,
+            upstream: upstream.trim_end_matches('/').to_string(),
+            recording,
+            replayed: vec![false; interactions.len()],
+            interactions,
+            http_client: reqwest::Client::new(),
+        }));
+
+        let server = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, state).await {
+                            tracing::warn!(target: "rig", "Cassette server error: {}", e);
+                        }
+                    });
+                }
+            }
+        });
+
+        Ok(Self { url, state, server })
+    }
+
+    /// Base URL of the cassette server, to create provider clients with.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Whether the cassette records the traffic (as opposed to replaying it).
+    pub fn is_recording(&self) -> bool {
+        self.lock().recording
+    }
+
+    /// Interactions of the cassette: the recorded ones when recording, the ones of the
+    /// cassette file otherwise.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.lock().interactions.clone()
+    }
+
+    /// Number of interactions of the cassette file not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.lock()
+            .replayed
+            .iter()
+            .filter(|replayed| !**replayed)
+            .count()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("cassette lock poisoned")
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Headers of a response which are not recorded: hop-by-hop headers, and headers describing
+/// the encoding of the body, which is recorded decoded
+const SKIPPED_HEADERS: [&str; 6] = [
+    "connection",
+    "content-encoding",
+    "content-length",
+    "keep-alive",
+    "set-cookie",
+    "transfer-encoding",
+];
+
+/// Answer a request of `stream`, closing the connection afterwards.
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) -> Result<(), CassetteError> {
+    let Some((request, headers)) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+
+    let (recording, upstream, http_client) = {
+        let state = state.lock().expect("cassette lock poisoned");
+        (
+            state.recording,
+            state.upstream.clone(),
+            state.http_client.clone(),
+        )
+    };
+
+    let response = if recording {
+        let response = forward(&http_client, &upstream, &request, headers).await;
+        let mut state = state.lock().expect("cassette lock poisoned");
+        // The request is forwarded with its credentials, but recorded without them
+        state.interactions.push(Interaction {
+            request: RecordedRequest {
+                path: redact_path(&request.path, Some(REDACTED)),
+                ..request
+            },
+            response: response.clone(),
+        });
+        state.replayed.push(true);
+        // The file is saved after each interaction, so that it is complete whenever the test ends
+        let file = serde_json::to_vec_pretty(&CassetteFile {
+            interactions: state.interactions.clone(),
+        })?;
+        if let Some(parent) = state.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&state.path, file)?;
+        response
+    } else {
+        let mut state = state.lock().expect("cassette lock poisoned");
+        let State {
+            interactions,
+            replayed,
+            ..
+        } = &mut *state;
+        match interactions
+            .iter()
+            .zip(replayed.iter_mut())
+            .find(|(interaction, replayed)| !**replayed && interaction.request.matches(&request))
+        {
+            Some((interaction, replayed)) => {
+                *replayed = true;
+                interaction.response.clone()
+            }
+            None => {
+                let path = redact_path(&request.path, Some(REDACTED));
+                tracing::error!(target: "rig",
+                    "No recorded interaction for {} {} in cassette {}",
+                    request.method,
+                    path,
+                    state.path.display()
+                );
+                RecordedResponse {
+                    status: 500,
+                    headers: vec![],
+                    body: RecordedBody::Text(format!(
+                        "No recorded interaction for {} {}",
+                        request.method, path
+                    )),
+                }
+            }
+        }
+    };
+
+    write_response(&mut stream, &response).await
+}
+
+/// Send `request` to the real API, returning its response (or a `502` error if it fails).
+async fn forward(
+    http_client: &reqwest::Client,
+    upstream: &str,
+    request: &RecordedRequest,
+    headers: Vec<(String, String)>,
+) -> RecordedResponse {
+    let result = async {
+        let method =
+            reqwest::Method::from_bytes(request.method.as_bytes()).map_err(|e| e.to_string())?;
+        let mut builder = http_client.request(method, format!("{}{}", upstream, request.path));
+        for (name, value) in headers {
+            if !matches!(
+                name.as_str(),
+                "host" | "content-length" | "connection" | "transfer-encoding" | "accept-encoding"
+            ) {
+                builder = builder.header(name, value);
+            }
+        }
+        let response = builder
+            .body(request.body.bytes())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+
+        Ok::<_, String>(RecordedResponse {
+            status,
+            headers,
+            body: RecordedBody::new(&body),
+        })
+    }
+    .await;
+
+    result.unwrap_or_else(|e| RecordedResponse {
+        status: 502,
+        headers: vec![],
+        body: RecordedBody::Text(format!("Cassette failed to reach {}: {}", upstream, e)),
+    })
+}
+
+/// Read an HTTP/1.1 request from `stream`, returning it along with its headers (lowercased
+/// names), or `None` if the connection was closed before a request was sent.
+async fn read_request(
+    stream: &mut TcpStream,
+) -> Result<Option<(RecordedRequest, Vec<(String, String)>)>, CassetteError> {
+    let mut buffer = vec![];
+    let header_end = loop {
+        if let Some(position) = find(&buffer, b"\r\n\r\n") {
+            break position;
+        }
+        let mut chunk = [0; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_lowercase(), value.trim().to_string()))
+        })
+        .collect::<Vec<_>>();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    let mut body = buffer.split_off(header_end + 4);
+    if header("transfer-encoding").is_some_and(|encoding| encoding.contains("chunked")) {
+        while find(&body, b"0\r\n\r\n").is_none() {
+            let mut chunk = [0; 4096];
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..read]);
+        }
+        body = decode_chunked(&body);
+    } else {
+        let length = header("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap_or(0);
+        while body.len() < length {
+            let mut chunk = [0; 4096];
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    Ok(Some((
+        RecordedRequest {
+            method,
+            path,
+            content_type: header("content-type"),
+            body: RecordedBody::new(&body),
+        },
+        headers,
+    )))
+}
+
+/// Write `response` to `stream` as an HTTP/1.1 response, closing the connection.
+async fn write_response(
+    stream: &mut TcpStream,
+    response: &RecordedResponse,
+) -> Result<(), CassetteError> {
+    let body = response.body.bytes();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reqwest::StatusCode::from_u16(response.status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("Unknown")
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "content-length: {}\r\nconnection: close\r\n\r\n",
+        body.len()
+    ));
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Decode a body sent with the chunked transfer encoding.
+fn decode_chunked(mut body: &[u8]) -> Vec<u8> {
+    let mut decoded = vec![];
+    while let Some(line_end) = find(body, b"\r\n") {
+        let size = std::str::from_utf8(&body[..line_end])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.split(';').next()?.trim(), 16).ok())
+            .unwrap_or(0);
+        let start = line_end + 2;
+        if size == 0 || body.len() < start + size {
+            break;
+        }
+        decoded.extend_from_slice(&body[start..start + size]);
+        body = &body[(start + size + 2).min(body.len())..];
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::{
+        decode_chunked, read_request, redact_path, write_response, Cassette, CassetteMode,
+        RecordedBody, RecordedResponse,
+    };
+    use crate::{
+        completion::Prompt,
+        embeddings::EmbeddingModel,
+        providers::{
+            gemini,
+            openai::{self, TEXT_EMBEDDING_3_SMALL},
+        },
+    };
+
+    fn completion_response(text: &str) -> serde_json::Value {
+        json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": text},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}
+        })
+    }
+
+    /// Local server answering every request with `response`, standing in for the real API.
+    /// Returns its URL and the number of requests it received.
+    async fn upstream(response: RecordedResponse) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    if let Ok(Some((request, headers))) = read_request(&mut stream).await {
+                        assert_eq!(request.path, "/chat/completions");
+                        assert!(headers.contains(&(
+                            "authorization".to_string(),
+                            "Bearer secret-key".to_string()
+                        )));
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        write_response(&mut stream, &response).await.unwrap();
+                    }
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("cassettes").join("agent.json");
+
+        let (upstream, requests) = upstream(RecordedResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: RecordedBody::Text(completion_response("Hi there!").to_string()),
+        })
+        .await;
+
+        let cassette = Cassette::start(&path, &upstream, CassetteMode::Auto)
+            .await
+            .unwrap();
+        assert!(cassette.is_recording());
+        let agent = openai::Client::from_url("secret-key", &cassette.url())
+            .agent(openai::GPT_4O)
+            .build();
+        assert_eq!(agent.prompt("Hello").await.unwrap(), "Hi there!");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        drop(cassette);
+
+        // The API key is not recorded
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("secret-key"));
+
+        // Replayed without the real API
+        let cassette = Cassette::start(&path, "http://unused", CassetteMode::Auto)
+            .await
+            .unwrap();
+        assert!(!cassette.is_recording());
+        let agent = openai::Client::from_url("", &cassette.url())
+            .agent(openai::GPT_4O)
+            .build();
+        assert_eq!(agent.prompt("Hello").await.unwrap(), "Hi there!");
+        assert_eq!(cassette.remaining(), 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Requests without recorded interaction fail
+        assert!(agent.prompt("Hello").await.is_err());
+        assert!(openai::Client::from_url("", &cassette.url())
+            .embedding_model(TEXT_EMBEDDING_3_SMALL)
+            .embed_text("Hello")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_credential_params_redacted() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("gemini.json");
+
+        // Gemini sends its API key in the `key` query parameter
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                if let Ok(Some((request, _))) = read_request(&mut stream).await {
+                    // Forwarded with the real key
+                    assert!(request.path.ends_with("?key=gemini-secret-key"));
+                    let response = RecordedResponse {
+                        status: 200,
+                        headers: vec![("content-type".to_string(), "application/json".to_string())],
+                        body: RecordedBody::Text(
+                            json!({
+                                "candidates": [{
+                                    "content": {"parts": [{"text": "Hi there!"}], "role": "model"},
+                                    "finishReason": "STOP"
+                                }],
+                                "usageMetadata": {
+                                    "promptTokenCount": 1,
+                                    "candidatesTokenCount": 2,
+                                    "totalTokenCount": 3
+                                }
+                            })
+                            .to_string(),
+                        ),
+                    };
+                    write_response(&mut stream, &response).await.unwrap();
+                }
+            }
+        });
+
+        let cassette = Cassette::start(&path, &upstream, CassetteMode::Record)
+            .await
+            .unwrap();
+        let agent = gemini::Client::from_url("gemini-secret-key", &cassette.url())
+            .agent(gemini::completion::GEMINI_1_5_FLASH)
+            .build();
+        assert_eq!(agent.prompt("Hello").await.unwrap(), "Hi there!");
+        drop(cassette);
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("gemini-secret-key"));
+        assert!(file.contains("key=REDACTED"));
+
+        // Replayed with another key
+        let cassette = Cassette::start(&path, "http://unused", CassetteMode::Replay)
+            .await
+            .unwrap();
+        let agent = gemini::Client::from_url("other-key", &cassette.url())
+            .agent(gemini::completion::GEMINI_1_5_FLASH)
+            .build();
+        assert_eq!(agent.prompt("Hello").await.unwrap(), "Hi there!");
+        assert_eq!(cassette.remaining(), 0);
+    }
+
+    #[test]
+    fn test_redact_path() {
+        assert_eq!(
+            redact_path(
+                "/models/gemini:generateContent?key=secret",
+                Some("REDACTED")
+            ),
+            "/models/gemini:generateContent?key=REDACTED"
+        );
+        assert_eq!(
+            redact_path("/search?q=rust&API_KEY=secret&page=2", None),
+            "/search?q=rust&page=2"
+        );
+        assert_eq!(redact_path("/embed?key=secret", None), "/embed");
+        assert_eq!(redact_path("/chat/completions", None), "/chat/completions");
+    }
+
+    #[test]
+    fn test_body_matching() {
+        let a = RecordedBody::Text(r#"{"a": 1, "b": [1, 2]}"#.to_string());
+        let b = RecordedBody::Text(r#"{"b":[1,2],"a":1}"#.to_string());
+        assert!(a.matches(&b));
+        assert!(!a.matches(&RecordedBody::Text(r#"{"a": 2, "b": [1, 2]}"#.to_string())));
+
+        assert_eq!(
+            decode_chunked(b"5\r\nHello\r\n7\r\n, world\r\n0\r\n\r\n"),
+            b"Hello, world"
+        );
+    }
+}
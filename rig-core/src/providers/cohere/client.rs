@@ -2,10 +2,11 @@ use crate::{
     agent::AgentBuilder, embeddings::EmbeddingsBuilder, extractor::ExtractorBuilder, Embed,
 };
 
+use crate::providers::http_client::HttpClient;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::{CompletionModel, EmbeddingModel};
+use super::{CompletionModel, EmbeddingModel, RerankModel};
 
 #[derive(Debug, Deserialize)]
 pub struct ApiErrorResponse {
@@ -27,7 +28,7 @@ const COHERE_API_BASE_URL: &str = "https://api.cohere.ai";
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 impl Client {
@@ -38,19 +39,16 @@ impl Client {
     pub fn from_url(api_key: &str, base_url: &str) -> Self {
         Self {
             base_url: base_url.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
-                    headers.insert(
-                        "Authorization",
-                        format!("Bearer {}", api_key)
-                            .parse()
-                            .expect("Bearer token should parse"),
-                    );
-                    headers
-                })
-                .build()
-                .expect("Cohere reqwest client should build"),
+            http_client: HttpClient::new({
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert(
+                    "Authorization",
+                    format!("Bearer {}", api_key)
+                        .parse()
+                        .expect("Bearer token should parse"),
+                );
+                headers
+            }),
         }
     }
 
@@ -61,6 +59,33 @@ impl Client {
         Self::new(&api_key)
     }
 
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: crate::providers::timeouts::Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
         self.http_client.post(url)
@@ -99,6 +124,11 @@ impl Client {
         EmbeddingsBuilder::new(self.embedding_model(model, input_type))
     }
 
+    /// Create a rerank model with the given name (see [crate::rerank::Reranker]).
+    pub fn rerank_model(&self, model: &str) -> RerankModel {
+        RerankModel::new(self.clone(), model)
+    }
+
     pub fn completion_model(&self, model: &str) -> CompletionModel {
         CompletionModel::new(self.clone(), model)
     }
@@ -114,3 +144,11 @@ impl Client {
         ExtractorBuilder::new(self.completion_model(model))
     }
 }
+
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
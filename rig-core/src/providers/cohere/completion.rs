@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 
+use crate::error::ApiError;
 use crate::{
-    completion::{self, CompletionError},
+    completion::{self, generation::insert_opt, CompletionError, TokenUsage},
     json_utils, message, OneOrMany,
 };
 
 use super::client::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Map, Value};
 
 #[derive(Debug, Deserialize)]
 pub struct CompletionResponse {
@@ -289,15 +290,10 @@ impl TryFrom<message::Message> for Vec<Message> {
                     message::UserContent::ToolResult(message::ToolResult { id, content }) => {
                         Ok(Message::Tool {
                             tool_call_id: id,
-                            content: content.try_map(|content| match content {
-                                message::ToolResultContent::Text(text) => {
-                                    Ok(ToolResultContent::Text { text: text.text })
-                                }
-                                _ => Err(message::MessageError::ConversionError(
-                                    "Only text tool result content is supported by Cohere"
-                                        .to_owned(),
-                                )),
-                            })?,
+                            // Images are replaced by a placeholder
+                            content: content.map(|content| ToolResultContent::Text {
+                                text: content.into_text(),
+                            }),
                         })
                     }
                     _ => Err(message::MessageError::ConversionError(
@@ -419,7 +415,7 @@ impl TryFrom<Message> for message::Message {
 
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(crate) client: Client,
     pub model: String,
 }
 
@@ -430,17 +426,13 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         let prompt = completion_request.prompt_with_context();
+        let generation = completion_request.generation_config();
 
         let mut messages: Vec<message::Message> =
             if let Some(preamble) = completion_request.preamble {
@@ -468,24 +460,55 @@ impl completion::CompletionModel for CompletionModel {
             "tools": completion_request.tools.into_iter().map(Tool::from).collect::<Vec<_>>(),
         });
 
+        let mut params = Map::new();
+        insert_opt(&mut params, "p", generation.top_p);
+        insert_opt(&mut params, "max_tokens", generation.max_tokens);
+        if !generation.stop.is_empty() {
+            params.insert("stop_sequences".to_string(), generation.stop.into());
+        }
+        insert_opt(&mut params, "seed", generation.seed);
+        insert_opt(
+            &mut params,
+            "frequency_penalty",
+            generation.frequency_penalty,
+        );
+        insert_opt(&mut params, "presence_penalty", generation.presence_penalty);
+        let request = json_utils::merge(request, Value::Object(params));
+
+        let request = if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        };
+
         tracing::debug!(
             "Cohere request: {}",
             serde_json::to_string_pretty(&request)?
         );
 
-        let response = self
-            .client
-            .post("/v2/chat")
-            .json(
-                &if let Some(ref params) = completion_request.additional_params {
-                    json_utils::merge(request.clone(), params.clone())
-                } else {
-                    request.clone()
-                },
-            )
-            .send()
+        Ok(request)
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
             .await?;
 
+        let request = self.create_completion_request(completion_request)?;
+
+        let response = self.client.post("/v2/chat").json(&request).send().await?;
+
         if response.status().is_success() {
             let text_response = response.text().await?;
             tracing::debug!("Cohere response text: {}", text_response);
@@ -495,9 +518,22 @@ impl completion::CompletionModel for CompletionModel {
                 json_response.try_into()?;
             Ok(completion)
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        let tokens = response.usage.as_ref()?.tokens.as_ref()?;
+        let input_tokens = tokens.input_tokens.unwrap_or(0.0) as u64;
+        let output_tokens = tokens.output_tokens.unwrap_or(0.0) as u64;
+
+        Some(TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: input_tokens + output_tokens,
+            ..Default::default()
+        })
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -1,6 +1,7 @@
 use super::{client::ApiResponse, Client};
 
 use crate::embeddings::{self, EmbeddingError};
+use crate::error::ApiError;
 
 use serde::Deserialize;
 use serde_json::json;
@@ -16,7 +17,7 @@ pub struct EmbeddingResponse {
     pub meta: Option<Meta>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct Meta {
     pub api_version: ApiVersion,
     pub billed_units: BilledUnits,
@@ -24,7 +25,7 @@ pub struct Meta {
     pub warnings: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct ApiVersion {
     pub version: String,
     #[serde(default)]
@@ -66,6 +67,10 @@ pub struct EmbeddingModel {
 impl embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 96;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     fn ndims(&self) -> usize {
         self.ndims
     }
@@ -76,6 +81,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         documents: impl IntoIterator<Item = String>,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
+        crate::rate_limit::acquire_embedding(self.client.rate_limiter(), &documents).await?;
 
         let response = self
             .client
@@ -115,7 +121,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                     Ok(response
                         .embeddings
                         .into_iter()
-                        .zip(documents.into_iter())
+                        .zip(documents)
                         .map(|(embedding, document)| embeddings::Embedding {
                             document,
                             vec: embedding,
@@ -125,7 +131,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                 ApiResponse::Err(error) => Err(EmbeddingError::ProviderError(error.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -7,16 +7,21 @@
 //! let client = cohere::Client::new("YOUR_API_KEY");
 //!
 //! let command_r = client.completion_model(cohere::COMMAND_R);
+//!
+//! let reranker = client.rerank_model(cohere::RERANK_V3_5);
 //! ```
 
 pub mod client;
 pub mod completion;
 pub mod embeddings;
+pub mod rerank;
+pub mod streaming;
 
 pub use client::Client;
 pub use client::{ApiErrorResponse, ApiResponse};
 pub use completion::CompletionModel;
 pub use embeddings::EmbeddingModel;
+pub use rerank::RerankModel;
 
 // ================================================================
 // Cohere Completion Models
@@ -53,3 +58,14 @@ pub const EMBED_ENGLISH_V2: &str = "embed-english-v2.0";
 pub const EMBED_ENGLISH_LIGHT_V2: &str = "embed-english-light-v2.0";
 /// `embed-multilingual-v2.0` embedding model
 pub const EMBED_MULTILINGUAL_V2: &str = "embed-multilingual-v2.0";
+
+// ================================================================
+// Cohere Rerank Models
+// ================================================================
+
+/// `rerank-v3.5` rerank model
+pub const RERANK_V3_5: &str = "rerank-v3.5";
+/// `rerank-english-v3.0` rerank model
+pub const RERANK_ENGLISH_V3: &str = "rerank-english-v3.0";
+/// `rerank-multilingual-v3.0` rerank model
+pub const RERANK_MULTILINGUAL_V3: &str = "rerank-multilingual-v3.0";
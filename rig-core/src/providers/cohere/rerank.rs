@@ -0,0 +1,129 @@
+use super::{client::ApiResponse, embeddings::Meta, Client};
+
+use crate::error::ApiError;
+use crate::rerank::{self, RerankError, RerankResult};
+
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize)]
+pub struct RerankResponse {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub results: Vec<RerankResult>,
+    #[serde(default)]
+    pub meta: Option<Meta>,
+}
+
+/// Cohere reranking model (see [Rerank API](https://docs.cohere.com/reference/rerank)).
+#[derive(Clone)]
+pub struct RerankModel {
+    client: Client,
+    pub model: String,
+}
+
+impl RerankModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+}
+
+impl rerank::Reranker for RerankModel {
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> Result<Vec<RerankResult>, RerankError> {
+        if documents.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let response = self
+            .client
+            .post("/v2/rerank")
+            .json(&json!({
+                "model": self.model,
+                "query": query,
+                "documents": documents,
+                "top_n": top_n.min(documents.len()),
+            }))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<RerankResponse>>().await? {
+                ApiResponse::Ok(response) => {
+                    if let Some(meta) = &response.meta {
+                        tracing::info!(target: "rig",
+                            "Cohere rerank billed units: {}",
+                            meta.billed_units,
+                        );
+                    }
+
+                    if let Some(result) = response
+                        .results
+                        .iter()
+                        .find(|result| result.index >= documents.len())
+                    {
+                        return Err(RerankError::ResponseError(format!(
+                            "Invalid document index {} ({} documents)",
+                            result.index,
+                            documents.len()
+                        )));
+                    }
+
+                    Ok(response.results)
+                }
+                ApiResponse::Err(error) => Err(RerankError::ProviderError(error.message)),
+            }
+        } else {
+            Err(ApiError::from_response(response).await.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_rerank_response() {
+        let response: ApiResponse<RerankResponse> = serde_json::from_str(
+            r#"{
+                "id": "07734bd2-2473-4f07-94e1-0d9f0e6843cf",
+                "results": [
+                    {"index": 3, "relevance_score": 0.999071},
+                    {"index": 4, "relevance_score": 0.7867867}
+                ],
+                "meta": {
+                    "api_version": {"version": "2"},
+                    "billed_units": {"search_units": 1}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let ApiResponse::Ok(response) = response else {
+            panic!("Expected a successful response");
+        };
+        assert_eq!(
+            response.results,
+            vec![
+                RerankResult {
+                    index: 3,
+                    relevance_score: 0.999071
+                },
+                RerankResult {
+                    index: 4,
+                    relevance_score: 0.7867867
+                },
+            ]
+        );
+        assert_eq!(response.meta.unwrap().billed_units.search_units, 1);
+    }
+}
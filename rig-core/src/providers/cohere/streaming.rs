@@ -0,0 +1,242 @@
+use async_stream::stream;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::completion::CompletionModel;
+use crate::completion::{CompletionError, CompletionRequest};
+use crate::error::ApiError;
+use crate::json_utils::merge;
+use crate::providers::sse::from_response as sse_from_response;
+use crate::streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult};
+use crate::tool::ToolError;
+
+/// Event of a streamed Cohere chat response (see <https://docs.cohere.com/v2/docs/streaming>)
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum StreamingEvent {
+    ContentDelta {
+        delta: Delta,
+    },
+    ToolCallStart {
+        index: usize,
+        delta: Delta,
+    },
+    ToolCallDelta {
+        index: usize,
+        delta: Delta,
+    },
+    ToolCallEnd {
+        index: usize,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Delta {
+    #[serde(default)]
+    pub message: DeltaMessage,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeltaMessage {
+    pub content: Option<ContentDelta>,
+    pub tool_calls: Option<ToolCallDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentDelta {
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToolCallDelta {
+    pub id: Option<String>,
+    pub function: Option<FunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+#[derive(Default)]
+struct ToolCallState {
+    name: String,
+    id: String,
+    arguments: String,
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
+        let request = merge(
+            self.create_completion_request(completion_request)?,
+            json!({"stream": true}),
+        );
+
+        let response = self.client.post("/v2/chat").json(&request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(response).await.into());
+        }
+
+        let sse_stream = sse_from_response(response);
+
+        Ok(Box::pin(stream! {
+            let mut current_tool_call: Option<ToolCallState> = None;
+            let mut sse_stream = Box::pin(sse_stream);
+
+            while let Some(sse_result) = sse_stream.next().await {
+                match sse_result {
+                    Ok(sse) => match serde_json::from_str::<StreamingEvent>(&sse.data) {
+                        Ok(event) => {
+                            if let Some(result) = handle_event(event, &mut current_tool_call) {
+                                yield result;
+                            }
+                        }
+                        Err(e) => {
+                            if !sse.data.trim().is_empty() {
+                                yield Err(CompletionError::JsonError(e));
+                            }
+                        }
+                    },
+                    Err(e) => {
+                        yield Err(CompletionError::from(e));
+                        break;
+                    }
+                }
+            }
+        }))
+    }
+}
+
+fn handle_event(
+    event: StreamingEvent,
+    current_tool_call: &mut Option<ToolCallState>,
+) -> Option<Result<StreamingChoice, CompletionError>> {
+    match event {
+        StreamingEvent::ContentDelta { delta } => delta
+            .message
+            .content
+            .and_then(|content| content.text)
+            .map(|text| Ok(StreamingChoice::Message(text))),
+        StreamingEvent::ToolCallStart { index, delta } => {
+            let tool_call = delta.message.tool_calls?;
+            let function = tool_call.function?;
+            let state = current_tool_call.insert(ToolCallState {
+                name: function.name.unwrap_or_default(),
+                id: tool_call.id.unwrap_or_default(),
+                arguments: function.arguments.unwrap_or_default(),
+            });
+
+            (!state.arguments.is_empty()).then(|| {
+                Ok(StreamingChoice::ToolCallDelta(
+                    index,
+                    state.name.clone(),
+                    state.arguments.clone(),
+                ))
+            })
+        }
+        StreamingEvent::ToolCallDelta { index, delta } => {
+            let state = current_tool_call.as_mut()?;
+            let fragment = delta.message.tool_calls?.function?.arguments?;
+            state.arguments.push_str(&fragment);
+
+            Some(Ok(StreamingChoice::ToolCallDelta(
+                index,
+                state.name.clone(),
+                fragment,
+            )))
+        }
+        StreamingEvent::ToolCallEnd { .. } => {
+            let tool_call = current_tool_call.take()?;
+            let arguments = if tool_call.arguments.is_empty() {
+                "{}"
+            } else {
+                &tool_call.arguments
+            };
+
+            Some(match serde_json::from_str(arguments) {
+                Ok(arguments) => Ok(StreamingChoice::ToolCall(
+                    tool_call.name,
+                    tool_call.id,
+                    arguments,
+                )),
+                Err(e) => Err(CompletionError::ToolCallError {
+                    error: ToolError::from_args_error(&tool_call.arguments, e),
+                    name: tool_call.name,
+                }),
+            })
+        }
+        StreamingEvent::Unknown => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(data: &str, state: &mut Option<ToolCallState>) -> Option<StreamingChoice> {
+        let event = serde_json::from_str(data).unwrap();
+        handle_event(event, state).map(|result| result.unwrap())
+    }
+
+    #[test]
+    fn test_handle_events() {
+        let mut state = None;
+
+        assert!(handle(
+            r#"{"type":"message-start","id":"1","delta":{"message":{"role":"assistant"}}}"#,
+            &mut state
+        )
+        .is_none());
+        assert!(matches!(
+            handle(r#"{"type":"content-delta","index":0,"delta":{"message":{"content":{"text":"Hi"}}}}"#, &mut state),
+            Some(StreamingChoice::Message(text)) if text == "Hi"
+        ));
+
+        handle(
+            r#"{"type":"tool-call-start","index":0,"delta":{"message":{"tool_calls":{"id":"add_1","type":"function","function":{"name":"add","arguments":""}}}}}"#,
+            &mut state,
+        );
+        assert!(matches!(
+            handle(r#"{"type":"tool-call-delta","index":0,"delta":{"message":{"tool_calls":{"function":{"arguments":"{\"x\": 1"}}}}}"#, &mut state),
+            Some(StreamingChoice::ToolCallDelta(0, name, fragment)) if name == "add" && fragment == r#"{"x": 1"#
+        ));
+        handle(
+            r#"{"type":"tool-call-delta","index":0,"delta":{"message":{"tool_calls":{"function":{"arguments":"}"}}}}}"#,
+            &mut state,
+        );
+        assert!(matches!(
+            handle(r#"{"type":"tool-call-end","index":0}"#, &mut state),
+            Some(StreamingChoice::ToolCall(name, id, args))
+                if name == "add" && id == "add_1" && args == json!({"x": 1})
+        ));
+    }
+
+    #[test]
+    fn test_handle_incomplete_tool_call() {
+        let mut state = None;
+
+        handle(
+            r#"{"type":"tool-call-start","index":0,"delta":{"message":{"tool_calls":{"id":"add_1","type":"function","function":{"name":"add","arguments":"{\"x\": 1"}}}}}"#,
+            &mut state,
+        );
+        let event = serde_json::from_str(r#"{"type":"tool-call-end","index":0}"#).unwrap();
+        assert!(matches!(
+            handle_event(event, &mut state),
+            Some(Err(CompletionError::ToolCallError {
+                name,
+                error: ToolError::IncompleteArguments(args),
+            })) if name == "add" && args == r#"{"x": 1"#
+        ));
+    }
+}
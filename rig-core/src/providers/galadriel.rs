@@ -11,7 +11,9 @@
 //! let gpt4o = client.completion_model(galadriel::GPT_4O);
 //! ```
 use super::openai;
+use crate::error::ApiError;
 use crate::json_utils::merge;
+use crate::providers::http_client::HttpClient;
 use crate::providers::openai::send_compatible_streaming_request;
 use crate::streaming::{StreamingCompletionModel, StreamingResult};
 use crate::{
@@ -32,7 +34,7 @@ const GALADRIEL_API_BASE_URL: &str = "https://api.galadriel.com/v1/verified";
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 impl Client {
@@ -53,27 +55,24 @@ impl Client {
     ) -> Self {
         Self {
             base_url: base_url.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
+            http_client: HttpClient::new({
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert(
+                    "Authorization",
+                    format!("Bearer {}", api_key)
+                        .parse()
+                        .expect("Bearer token should parse"),
+                );
+                if let Some(key) = fine_tune_api_key {
                     headers.insert(
-                        "Authorization",
-                        format!("Bearer {}", api_key)
+                        "Fine-Tune-Authorization",
+                        format!("Bearer {}", key)
                             .parse()
                             .expect("Bearer token should parse"),
                     );
-                    if let Some(key) = fine_tune_api_key {
-                        headers.insert(
-                            "Fine-Tune-Authorization",
-                            format!("Bearer {}", key)
-                                .parse()
-                                .expect("Bearer token should parse"),
-                        );
-                    }
-                    headers
-                })
-                .build()
-                .expect("Galadriel reqwest client should build"),
+                }
+                headers
+            }),
         }
     }
 
@@ -85,6 +84,33 @@ impl Client {
         let fine_tune_api_key = std::env::var("GALADRIEL_FINE_TUNE_API_KEY").ok();
         Self::new(&api_key, fine_tune_api_key.as_deref())
     }
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: crate::providers::timeouts::Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
         self.http_client.post(url)
@@ -132,6 +158,14 @@ impl Client {
     }
 }
 
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -303,8 +337,7 @@ impl TryFrom<Message> for message::Message {
                         .chain(
                             message
                                 .content
-                                .map(|content| message::AssistantContent::text(&content))
-                                .into_iter(),
+                                .map(|content| message::AssistantContent::text(&content)),
                         ),
                 )
                 .map_err(|_| {
@@ -398,6 +431,8 @@ impl CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<Value, CompletionError> {
+        let generation = completion_request.generation_config();
+
         // Add preamble to chat history (if available)
         let mut full_history: Vec<Message> = match &completion_request.preamble {
             Some(preamble) => vec![Message {
@@ -437,6 +472,7 @@ impl CompletionModel {
                 "tool_choice": "auto",
             })
         };
+        let request = json_utils::merge(request, generation.openai_params());
 
         let request = if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
@@ -460,11 +496,18 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request = self.create_completion_request(completion_request)?;
 
         let response = self
@@ -489,13 +532,15 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
 
 impl StreamingCompletionModel for CompletionModel {
     async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &request).await?;
+
         let mut request = self.create_completion_request(request)?;
 
         request = merge(request, json!({"stream": true}));
@@ -1,3 +1,4 @@
+use crate::providers::http_client::HttpClient;
 use crate::{
     agent::AgentBuilder,
     embeddings::{self},
@@ -20,7 +21,7 @@ const GEMINI_API_BASE_URL: &str = "https://generativelanguage.googleapis.com";
 pub struct Client {
     base_url: String,
     api_key: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 impl Client {
@@ -31,17 +32,14 @@ impl Client {
         Self {
             base_url: base_url.to_string(),
             api_key: api_key.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
-                    headers.insert(
-                        reqwest::header::CONTENT_TYPE,
-                        "application/json".parse().unwrap(),
-                    );
-                    headers
-                })
-                .build()
-                .expect("Gemini reqwest client should build"),
+            http_client: HttpClient::new({
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert(
+                    reqwest::header::CONTENT_TYPE,
+                    "application/json".parse().unwrap(),
+                );
+                headers
+            }),
         }
     }
 
@@ -52,6 +50,33 @@ impl Client {
         Self::new(&api_key)
     }
 
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: crate::providers::timeouts::Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}?key={}", self.base_url, path, self.api_key).replace("//", "/");
 
@@ -164,6 +189,14 @@ impl Client {
     }
 }
 
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ApiErrorResponse {
     pub message: String,
@@ -14,6 +14,16 @@ pub const GEMINI_1_5_PRO_8B: &str = "gemini-1.5-pro-8b";
 /// `gemini-1.0-pro` completion model
 pub const GEMINI_1_0_PRO: &str = "gemini-1.0-pro";
 
+/// Pricing of Gemini completion models (in US dollars per million tokens, for prompts of up to
+/// 128k tokens), by model name prefix. More specific prefixes come first (see [ModelPricing::lookup]).
+pub const PRICING: &[(&str, ModelPricing)] = &[
+    (GEMINI_2_0_FLASH, ModelPricing::new(0.1, 0.4)),
+    ("gemini-1.5-flash-8b", ModelPricing::new(0.0375, 0.15)),
+    (GEMINI_1_5_FLASH, ModelPricing::new(0.075, 0.3)),
+    (GEMINI_1_5_PRO, ModelPricing::new(1.25, 5.0)),
+    (GEMINI_1_0_PRO, ModelPricing::new(0.5, 1.5)),
+];
+
 use gemini_api_types::{
     Content, FunctionDeclaration, GenerateContentRequest, GenerateContentResponse,
     GenerationConfig, Part, Role, Tool,
@@ -21,8 +31,11 @@ use gemini_api_types::{
 use serde_json::{Map, Value};
 use std::convert::TryFrom;
 
+use crate::error::ApiError;
 use crate::{
-    completion::{self, CompletionError, CompletionRequest},
+    completion::{
+        self, cost::ModelPricing, CompletionError, CompletionRequest, ResponseFormat, TokenUsage,
+    },
     OneOrMany,
 };
 
@@ -52,11 +65,22 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = GenerateContentResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn context_template(&self) -> completion::ContextTemplate {
+        completion::ContextTemplate::Markdown
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request = create_request_body(completion_request)?;
 
         tracing::debug!(
@@ -87,9 +111,25 @@ impl completion::CompletionModel for CompletionModel {
 
             Ok(completion::CompletionResponse::try_from(response))
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from(
+                ApiError::from_response(response).await,
+            ))
         }?
     }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        response.usage_metadata.as_ref().map(|usage| TokenUsage {
+            input_tokens: usage.prompt_token_count as u64,
+            output_tokens: usage.candidates_token_count as u64,
+            total_tokens: usage.total_token_count as u64,
+            cache_read_input_tokens: usage.cached_content_token_count.unwrap_or(0) as u64,
+            cache_creation_input_tokens: 0,
+        })
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        ModelPricing::lookup(PRICING, &self.model)
+    }
 }
 
 pub(crate) fn create_request_body(
@@ -113,6 +153,37 @@ pub(crate) fn create_request_body(
         generation_config.max_output_tokens = Some(max_tokens);
     }
 
+    if let Some(top_p) = completion_request.top_p {
+        generation_config.top_p = Some(top_p);
+    }
+
+    if !completion_request.stop.is_empty() {
+        generation_config.stop_sequences = Some(completion_request.stop.clone());
+    }
+
+    if let Some(seed) = completion_request.seed {
+        generation_config.seed = Some(seed);
+    }
+
+    if let Some(frequency_penalty) = completion_request.frequency_penalty {
+        generation_config.frequency_penalty = Some(frequency_penalty);
+    }
+
+    if let Some(presence_penalty) = completion_request.presence_penalty {
+        generation_config.presence_penalty = Some(presence_penalty);
+    }
+
+    match completion_request.response_format.clone() {
+        Some(ResponseFormat::JsonObject) => {
+            generation_config.response_mime_type = Some("application/json".to_string());
+        }
+        Some(ResponseFormat::JsonSchema { schema, .. }) => {
+            generation_config.response_mime_type = Some("application/json".to_string());
+            generation_config.response_schema = Some(schema.try_into()?);
+        }
+        Some(ResponseFormat::Text) | None => (),
+    }
+
     let system_instruction = completion_request.preamble.clone().map(|preamble| Content {
         parts: OneOrMany::one(preamble.into()),
         role: Some(Role::Model),
@@ -416,10 +487,10 @@ pub mod gemini_api_types {
                 message::UserContent::ToolResult(message::ToolResult { id, content }) => {
                     let content = match content.first() {
                         message::ToolResultContent::Text(text) => text.text,
-                        message::ToolResultContent::Image(_) => {
-                            return Err(message::MessageError::ConversionError(
-                                "Tool result content must be text".to_string(),
-                            ))
+                        // Function responses can only be JSON, images are replaced by a
+                        //  placeholder
+                        image @ message::ToolResultContent::Image(_) => {
+                            serde_json::Value::String(image.into_text()).to_string()
                         }
                     };
                     Ok(Part::FunctionResponse(FunctionResponse {
@@ -793,6 +864,9 @@ pub mod gemini_api_types {
         /// the model to  repeating a common token until it hits the maxOutputTokens limit: "...the the the the the...".
         #[serde(skip_serializing_if = "Option::is_none")]
         pub frequency_penalty: Option<f64>,
+        /// Seed used in decoding. If not set, the request uses a randomly generated seed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub seed: Option<u64>,
         /// If true, export the logprobs results in response.
         #[serde(skip_serializing_if = "Option::is_none")]
         pub response_logprobs: Option<bool>,
@@ -815,6 +889,7 @@ pub mod gemini_api_types {
                 top_k: None,
                 presence_penalty: None,
                 frequency_penalty: None,
+                seed: None,
                 response_logprobs: None,
                 logprobs: None,
             }
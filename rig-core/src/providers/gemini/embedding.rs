@@ -6,6 +6,7 @@
 use serde_json::json;
 
 use crate::embeddings::{self, EmbeddingError};
+use crate::error::ApiError;
 
 use super::{client::ApiResponse, Client};
 
@@ -33,12 +34,16 @@ impl EmbeddingModel {
 impl embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 1024;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     fn ndims(&self) -> usize {
-        match self.model.as_str() {
+        self.ndims.unwrap_or(match self.model.as_str() {
             EMBEDDING_001 => 768,
-            EMBEDDING_004 => 1024,
+            EMBEDDING_004 => 768,
             _ => 0, // Default to 0 for unknown models
-        }
+        })
     }
 
     /// <https://ai.google.dev/api/embeddings#batch_embed_contents-SHELL>
@@ -48,6 +53,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents: Vec<String> = documents.into_iter().collect();
+        crate::rate_limit::acquire_embedding(self.client.rate_limiter(), &documents).await?;
 
         // Google batch embed requests. See docstrings for API ref link.
         let requests: Vec<_> = documents
@@ -67,19 +73,23 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
 
         let request_body = json!({ "requests": requests  });
 
-        println!("{}", serde_json::to_string_pretty(&request_body).unwrap());
+        tracing::debug!("Gemini embedding request: {}", request_body);
 
         let response = self
             .client
             .post(&format!("/v1beta/models/{}:batchEmbedContents", self.model))
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?
-            .json::<ApiResponse<gemini_api_types::EmbeddingResponse>>()
             .await?;
 
-        match response {
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(response).await.into());
+        }
+
+        match response
+            .json::<ApiResponse<gemini_api_types::EmbeddingResponse>>()
+            .await?
+        {
             ApiResponse::Ok(response) => {
                 let docs = documents
                     .into_iter()
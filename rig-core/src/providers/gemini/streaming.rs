@@ -4,6 +4,7 @@ use serde::Deserialize;
 
 use crate::{
     completion::{CompletionError, CompletionRequest},
+    error::ApiError,
     streaming::{self, StreamingCompletionModel, StreamingResult},
 };
 
@@ -22,6 +23,9 @@ impl StreamingCompletionModel for CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request = create_request_body(completion_request)?;
 
         let response = self
@@ -35,11 +39,7 @@ impl StreamingCompletionModel for CompletionModel {
             .await?;
 
         if !response.status().is_success() {
-            return Err(CompletionError::ProviderError(format!(
-                "{}: {}",
-                response.status(),
-                response.text().await?
-            )));
+            return Err(ApiError::from_response(response).await.into());
         }
 
         Ok(Box::pin(stream! {
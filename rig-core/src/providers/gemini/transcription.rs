@@ -5,6 +5,7 @@ use mime_guess;
 use serde_json::{Map, Value};
 
 use crate::{
+    error::ApiError,
     providers::gemini::completion::gemini_api_types::{
         Blob, Content, GenerateContentRequest, GenerationConfig, Part, Role,
     },
@@ -114,7 +115,9 @@ impl transcription::TranscriptionModel for TranscriptionModel {
 
             Ok(transcription::TranscriptionResponse::try_from(response))
         } else {
-            Err(TranscriptionError::ProviderError(response.text().await?))
+            Err(TranscriptionError::from(
+                ApiError::from_response(response).await,
+            ))
         }?
     }
 }
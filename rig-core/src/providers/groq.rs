@@ -1,95 +1,45 @@
 //! Groq API client and Rig integration
 //!
+//! Groq exposes an OpenAI-compatible API (see [openai_compat]).
+//!
 //! # Example
 //! ```
 //! use rig::providers::groq;
 //!
 //! let client = groq::Client::new("YOUR_API_KEY");
 //!
-//! let gpt4o = client.completion_model(groq::GPT_4O);
+//! let llama = client.completion_model(groq::LLAMA_3_3_70B_VERSATILE);
 //! ```
-use super::openai::{send_compatible_streaming_request, CompletionResponse, TranscriptionResponse};
-use crate::json_utils::merge;
-use crate::streaming::{StreamingCompletionModel, StreamingResult};
-use crate::{
-    agent::AgentBuilder,
-    completion::{self, CompletionError, CompletionRequest},
-    extractor::ExtractorBuilder,
-    json_utils,
-    message::{self, MessageError},
-    providers::openai::ToolDefinition,
-    transcription::{self, TranscriptionError},
-    OneOrMany,
-};
+use super::openai::{transcription::form_value, ApiResponse, TranscriptionResponse};
+use super::openai_compat::{self, OpenAICompatible};
+use crate::error::ApiError;
+use crate::transcription::{self, TranscriptionError};
 use reqwest::multipart::Part;
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::Value;
 
 // ================================================================
 // Main Groq Client
 // ================================================================
 const GROQ_API_BASE_URL: &str = "https://api.groq.com/openai/v1";
 
-#[derive(Clone)]
-pub struct Client {
-    base_url: String,
-    http_client: reqwest::Client,
-}
+/// Groq's OpenAI-compatible API.
+#[derive(Clone, Debug)]
+pub struct Groq;
 
-impl Client {
-    /// Create a new Groq client with the given API key.
-    pub fn new(api_key: &str) -> Self {
-        Self::from_url(api_key, GROQ_API_BASE_URL)
-    }
+impl OpenAICompatible for Groq {
+    const NAME: &'static str = "Groq";
+    const BASE_URL: &'static str = GROQ_API_BASE_URL;
+    const API_KEY_ENV: &'static str = "GROQ_API_KEY";
 
-    /// Create a new Groq client with the given API key and base API URL.
-    pub fn from_url(api_key: &str, base_url: &str) -> Self {
-        Self {
-            base_url: base_url.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
-                    headers.insert(
-                        "Authorization",
-                        format!("Bearer {}", api_key)
-                            .parse()
-                            .expect("Bearer token should parse"),
-                    );
-                    headers
-                })
-                .build()
-                .expect("Groq reqwest client should build"),
-        }
-    }
-
-    /// Create a new Groq client from the `GROQ_API_KEY` environment variable.
-    /// Panics if the environment variable is not set.
-    pub fn from_env() -> Self {
-        let api_key = std::env::var("GROQ_API_KEY").expect("GROQ_API_KEY not set");
-        Self::new(&api_key)
+    /// Groq does not support log probabilities nor logit biases
+    fn adapt_request(_model: &str, request: Value) -> Value {
+        openai_compat::remove_params(request, &["logprobs", "top_logprobs", "logit_bias"])
     }
+}
 
-    fn post(&self, path: &str) -> reqwest::RequestBuilder {
-        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
-        self.http_client.post(url)
-    }
-
-    /// Create a completion model with the given name.
-    ///
-    /// # Example
-    /// ```
-    /// use rig::providers::groq::{Client, self};
-    ///
-    /// // Initialize the Groq client
-    /// let groq = Client::new("your-groq-api-key");
-    ///
-    /// let gpt4 = groq.completion_model(groq::GPT_4);
-    /// ```
-    pub fn completion_model(&self, model: &str) -> CompletionModel {
-        CompletionModel::new(self.clone(), model)
-    }
+pub type Client = openai_compat::Client<Groq>;
 
+impl Client {
     /// Create a transcription model with the given name.
     ///
     /// # Example
@@ -99,135 +49,11 @@ impl Client {
     /// // Initialize the Groq client
     /// let groq = Client::new("your-groq-api-key");
     ///
-    /// let gpt4 = groq.transcription_model(groq::WHISPER_LARGE_V3);
+    /// let whisper = groq.transcription_model(groq::WHISPER_LARGE_V3);
     /// ```
     pub fn transcription_model(&self, model: &str) -> TranscriptionModel {
         TranscriptionModel::new(self.clone(), model)
     }
-
-    /// Create an agent builder with the given completion model.
-    ///
-    /// # Example
-    /// ```
-    /// use rig::providers::groq::{Client, self};
-    ///
-    /// // Initialize the Groq client
-    /// let groq = Client::new("your-groq-api-key");
-    ///
-    /// let agent = groq.agent(groq::GPT_4)
-    ///    .preamble("You are comedian AI with a mission to make people laugh.")
-    ///    .temperature(0.0)
-    ///    .build();
-    /// ```
-    pub fn agent(&self, model: &str) -> AgentBuilder<CompletionModel> {
-        AgentBuilder::new(self.completion_model(model))
-    }
-
-    /// Create an extractor builder with the given completion model.
-    pub fn extractor<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync>(
-        &self,
-        model: &str,
-    ) -> ExtractorBuilder<T, CompletionModel> {
-        ExtractorBuilder::new(self.completion_model(model))
-    }
-}
-
-#[derive(Debug, Deserialize)]
-struct ApiErrorResponse {
-    message: String,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-enum ApiResponse<T> {
-    Ok(T),
-    Err(ApiErrorResponse),
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Message {
-    pub role: String,
-    pub content: Option<String>,
-}
-
-impl TryFrom<Message> for message::Message {
-    type Error = message::MessageError;
-
-    fn try_from(message: Message) -> Result<Self, Self::Error> {
-        match message.role.as_str() {
-            "user" => Ok(Self::User {
-                content: OneOrMany::one(
-                    message
-                        .content
-                        .map(|content| message::UserContent::text(&content))
-                        .ok_or_else(|| {
-                            message::MessageError::ConversionError("Empty user message".to_string())
-                        })?,
-                ),
-            }),
-            "assistant" => Ok(Self::Assistant {
-                content: OneOrMany::one(
-                    message
-                        .content
-                        .map(|content| message::AssistantContent::text(&content))
-                        .ok_or_else(|| {
-                            message::MessageError::ConversionError(
-                                "Empty assistant message".to_string(),
-                            )
-                        })?,
-                ),
-            }),
-            _ => Err(message::MessageError::ConversionError(format!(
-                "Unknown role: {}",
-                message.role
-            ))),
-        }
-    }
-}
-
-impl TryFrom<message::Message> for Message {
-    type Error = message::MessageError;
-
-    fn try_from(message: message::Message) -> Result<Self, Self::Error> {
-        match message {
-            message::Message::User { content } => Ok(Self {
-                role: "user".to_string(),
-                content: content.iter().find_map(|c| match c {
-                    message::UserContent::Text(text) => Some(text.text.clone()),
-                    _ => None,
-                }),
-            }),
-            message::Message::Assistant { content } => {
-                let mut text_content: Option<String> = None;
-
-                for c in content.iter() {
-                    match c {
-                        message::AssistantContent::Text(text) => {
-                            text_content = Some(
-                                text_content
-                                    .map(|mut existing| {
-                                        existing.push('\n');
-                                        existing.push_str(&text.text);
-                                        existing
-                                    })
-                                    .unwrap_or_else(|| text.text.clone()),
-                            );
-                        }
-                        message::AssistantContent::ToolCall(_tool_call) => {
-                            return Err(MessageError::ConversionError(
-                                "Tool calls do not exist on this message".into(),
-                            ))
-                        }
-                    }
-                }
-
-                Ok(Self {
-                    role: "assistant".to_string(),
-                    content: text_content,
-                })
-            }
-        }
-    }
 }
 
 // ================================================================
@@ -249,6 +75,8 @@ pub const LLAMA_3_2_3B_PREVIEW: &str = "llama-3.2-3b-preview";
 pub const LLAMA_3_2_90B_VISION_PREVIEW: &str = "llama-3.2-90b-vision-preview";
 /// The `llama-3.2-70b-specdec` model. Used for chat completion.
 pub const LLAMA_3_2_70B_SPECDEC: &str = "llama-3.2-70b-specdec";
+/// The `llama-3.3-70b-versatile` model. Used for chat completion.
+pub const LLAMA_3_3_70B_VERSATILE: &str = "llama-3.3-70b-versatile";
 /// The `llama-3.2-70b-versatile` model. Used for chat completion.
 pub const LLAMA_3_2_70B_VERSATILE: &str = "llama-3.2-70b-versatile";
 /// The `llama-guard-3-8b` model. Used for chat completion.
@@ -260,119 +88,113 @@ pub const LLAMA_3_8B_8192: &str = "llama3-8b-8192";
 /// The `mixtral-8x7b-32768` model. Used for chat completion.
 pub const MIXTRAL_8X7B_32768: &str = "mixtral-8x7b-32768";
 
-#[derive(Clone)]
-pub struct CompletionModel {
-    client: Client,
-    /// Name of the model (e.g.: deepseek-r1-distill-llama-70b)
-    pub model: String,
-}
-
-impl CompletionModel {
-    pub fn new(client: Client, model: &str) -> Self {
-        Self {
-            client,
-            model: model.to_string(),
-        }
+pub type CompletionModel = openai_compat::CompletionModel<Groq>;
+
+// Former message type of the Groq provider, kept for compatibility
+#[allow(deprecated)]
+mod legacy {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        message::{self, MessageError},
+        OneOrMany,
+    };
+
+    #[deprecated(
+        since = "0.11.0",
+        note = "Groq completions are sent in the OpenAI format, see `providers::openai::Message`"
+    )]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Message {
+        pub role: String,
+        pub content: Option<String>,
     }
 
-    fn create_completion_request(
-        &self,
-        completion_request: CompletionRequest,
-    ) -> Result<Value, CompletionError> {
-        // Add preamble to chat history (if available)
-        let mut full_history: Vec<Message> = match &completion_request.preamble {
-            Some(preamble) => vec![Message {
-                role: "system".to_string(),
-                content: Some(preamble.to_string()),
-            }],
-            None => vec![],
-        };
-
-        // Convert prompt to user message
-        let prompt: Message = completion_request.prompt_with_context().try_into()?;
-
-        // Convert existing chat history
-        let chat_history: Vec<Message> = completion_request
-            .chat_history
-            .into_iter()
-            .map(|message| message.try_into())
-            .collect::<Result<Vec<Message>, _>>()?;
-
-        // Combine all messages into a single history
-        full_history.extend(chat_history);
-        full_history.push(prompt);
-
-        let request = if completion_request.tools.is_empty() {
-            json!({
-                "model": self.model,
-                "messages": full_history,
-                "temperature": completion_request.temperature,
-            })
-        } else {
-            json!({
-                "model": self.model,
-                "messages": full_history,
-                "temperature": completion_request.temperature,
-                "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
-            })
-        };
-
-        let request = if let Some(params) = completion_request.additional_params {
-            json_utils::merge(request, params)
-        } else {
-            request
-        };
-
-        Ok(request)
+    impl TryFrom<Message> for message::Message {
+        type Error = message::MessageError;
+
+        fn try_from(message: Message) -> Result<Self, Self::Error> {
+            match message.role.as_str() {
+                "user" => Ok(Self::User {
+                    content: OneOrMany::one(
+                        message
+                            .content
+                            .map(|content| message::UserContent::text(&content))
+                            .ok_or_else(|| {
+                                message::MessageError::ConversionError(
+                                    "Empty user message".to_string(),
+                                )
+                            })?,
+                    ),
+                }),
+                "assistant" => Ok(Self::Assistant {
+                    content: OneOrMany::one(
+                        message
+                            .content
+                            .map(|content| message::AssistantContent::text(&content))
+                            .ok_or_else(|| {
+                                message::MessageError::ConversionError(
+                                    "Empty assistant message".to_string(),
+                                )
+                            })?,
+                    ),
+                }),
+                _ => Err(message::MessageError::ConversionError(format!(
+                    "Unknown role: {}",
+                    message.role
+                ))),
+            }
+        }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
-        &self,
-        completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
-        let request = self.create_completion_request(completion_request)?;
 
-        let response = self
-            .client
-            .post("/chat/completions")
-            .json(&request)
-            .send()
-            .await?;
+    impl TryFrom<message::Message> for Message {
+        type Error = message::MessageError;
+
+        fn try_from(message: message::Message) -> Result<Self, Self::Error> {
+            match message {
+                message::Message::User { content } => Ok(Self {
+                    role: "user".to_string(),
+                    content: content.iter().find_map(|c| match c {
+                        message::UserContent::Text(text) => Some(text.text.clone()),
+                        _ => None,
+                    }),
+                }),
+                message::Message::Assistant { content } => {
+                    let mut text_content: Option<String> = None;
+
+                    for c in content.iter() {
+                        match c {
+                            message::AssistantContent::Text(text) => {
+                                text_content = Some(
+                                    text_content
+                                        .map(|mut existing| {
+                                            existing.push('\n');
+                                            existing.push_str(&text.text);
+                                            existing
+                                        })
+                                        .unwrap_or_else(|| text.text.clone()),
+                                );
+                            }
+                            message::AssistantContent::ToolCall(_tool_call) => {
+                                return Err(MessageError::ConversionError(
+                                    "Tool calls do not exist on this message".into(),
+                                ))
+                            }
+                        }
+                    }
 
-        if response.status().is_success() {
-            match response.json::<ApiResponse<CompletionResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    tracing::info!(target: "rig",
-                        "groq completion token usage: {:?}",
-                        response.usage.clone().map(|usage| format!("{usage}")).unwrap_or("N/A".to_string())
-                    );
-                    response.try_into()
+                    Ok(Self {
+                        role: "assistant".to_string(),
+                        content: text_content,
+                    })
                 }
-                ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
-        } else {
-            Err(CompletionError::ProviderError(response.text().await?))
         }
     }
 }
 
-impl StreamingCompletionModel for CompletionModel {
-    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
-        let mut request = self.create_completion_request(request)?;
-
-        request = merge(request, json!({"stream": true}));
-
-        let builder = self.client.post("/chat/completions").json(&request);
-
-        send_compatible_streaming_request(builder).await
-    }
-}
+#[allow(deprecated)]
+pub use legacy::Message;
 
 // ================================================================
 // Groq Transcription API
@@ -384,7 +206,7 @@ pub const DISTIL_WHISPER_LARGE_V3: &str = "distil-whisper-large-v3-en";
 #[derive(Clone)]
 pub struct TranscriptionModel {
     client: Client,
-    /// Name of the model (e.g.: gpt-3.5-turbo-1106)
+    /// Name of the model (e.g.: whisper-large-v3)
     pub model: String,
 }
 
@@ -428,9 +250,9 @@ impl transcription::TranscriptionModel for TranscriptionModel {
         if let Some(ref additional_params) = request.additional_params {
             for (key, value) in additional_params
                 .as_object()
-                .expect("Additional Parameters to OpenAI Transcription should be a map")
+                .expect("Additional Parameters to Groq Transcription should be a map")
             {
-                body = body.text(key.to_owned(), value.to_string());
+                body = body.text(key.to_owned(), form_value(value));
             }
         }
 
@@ -452,7 +274,29 @@ impl transcription::TranscriptionModel for TranscriptionModel {
                 )),
             }
         } else {
-            Err(TranscriptionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::completion::CompletionModel as _;
+
+    #[test]
+    fn test_unsupported_params() {
+        let model = Client::new("key").completion_model(LLAMA_3_3_70B_VERSATILE);
+        let request = model
+            .completion_request("Hello")
+            .additional_params(json!({"logprobs": true, "top_logprobs": 2, "seed": 42}))
+            .build();
+
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request.get("logprobs"), None);
+        assert_eq!(request.get("top_logprobs"), None);
+        assert_eq!(request["seed"], 42);
+    }
+}
@@ -0,0 +1,110 @@
+//! HTTP client of the providers.
+//!
+//! Provider clients send their requests with a [reqwest::Client], built with the default
+//! configuration (and the [Timeouts] set with the `ClientBuilder`, if any) unless a custom one
+//! is injected with
+//! the `with_http_client` method of the provider client (or the `http_client` method of its
+//! `ClientBuilder`). A custom client can configure proxies, root certificates, connection pools,
+//! default headers, etc.
+//!
+//! The headers required by the provider (e.g.: authentication) are sent with every request, so
+//! the injected client does not need to know about them.
+//!
+//! # Example
+//! ```rust
+//! use rig::providers::openai;
+//!
+//! let http_client = reqwest::Client::builder()
+//!     .proxy(reqwest::Proxy::https("http://proxy.corp.example:8080")?)
+//!     .add_root_certificate(reqwest::Certificate::from_pem(&std::fs::read("corp-ca.pem")?)?)
+//!     .build()?;
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key").with_http_client(http_client);
+//! ```
+use reqwest::{header::HeaderMap, IntoUrl, RequestBuilder};
+
+use super::timeouts::Timeouts;
+use crate::rate_limit::RateLimiter;
+
+/// [reqwest::Client] of a provider client, with the headers sent with every request and the
+/// rate limiter of the completions and embeddings of the client, if any.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpClient {
+    client: reqwest::Client,
+    headers: HeaderMap,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl HttpClient {
+    /// Create a client with the default configuration and [Timeouts], sending `headers` with
+    /// every request.
+    pub(crate) fn new(headers: HeaderMap) -> Self {
+        Self::with_timeouts(headers, Timeouts::default())
+    }
+
+    /// Create a client with the default configuration and the given timeouts, sending `headers`
+    /// with every request.
+    pub(crate) fn with_timeouts(headers: HeaderMap, timeouts: Timeouts) -> Self {
+        Self {
+            client: timeouts.build_client(),
+            headers,
+            rate_limiter: None,
+        }
+    }
+
+    /// Replace the underlying [reqwest::Client] with a client with the default configuration and
+    /// the given timeouts, keeping the headers.
+    pub(crate) fn apply_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.client = timeouts.build_client();
+        self
+    }
+
+    /// Replace the underlying [reqwest::Client], keeping the headers.
+    pub(crate) fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send the completions and embeddings of the client within the limits of `limiter`.
+    pub(crate) fn with_rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    pub(crate) fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.post(url).headers(self.headers.clone())
+    }
+
+    pub(crate) fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.get(url).headers(self.headers.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_sent_with_custom_client() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "key".parse().unwrap());
+
+        let client = HttpClient::new(headers).with_client(
+            reqwest::Client::builder()
+                .default_headers({
+                    let mut headers = HeaderMap::new();
+                    headers.insert("x-custom", "custom".parse().unwrap());
+                    headers
+                })
+                .build()
+                .unwrap(),
+        );
+
+        let request = client.post("https://example.com/v1").build().unwrap();
+        assert_eq!(request.headers()["x-api-key"], "key");
+    }
+}
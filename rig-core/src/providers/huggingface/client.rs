@@ -1,12 +1,19 @@
 use std::fmt::Display;
+use std::time::Duration;
 
 use super::completion::CompletionModel;
+use super::embedding::EmbeddingModel;
+use super::text_generation::TextGenerationModel;
 use crate::agent::AgentBuilder;
+use crate::completion::{template::ChatTemplate, CompletionError};
+use crate::embeddings::EmbeddingError;
 #[cfg(feature = "image")]
 use crate::image_generation::ImageGenerationError;
+use crate::providers::http_client::HttpClient;
 #[cfg(feature = "image")]
 use crate::providers::huggingface::image_generation::ImageGenerationModel;
 use crate::providers::huggingface::transcription::TranscriptionModel;
+use crate::providers::timeouts::Timeouts;
 use crate::transcription::TranscriptionError;
 
 // ================================================================
@@ -24,6 +31,9 @@ pub enum SubProvider {
     Hyperbolic,
     Nebius,
     Novita,
+    /// Self-hosted Text Generation Inference (TGI), Text Embeddings Inference (TEI) or vLLM
+    /// server, whose base URL is the URL of the server (see [Client::self_hosted])
+    SelfHosted,
     Custom(String),
 }
 
@@ -38,16 +48,45 @@ impl SubProvider {
         }
     }
 
+    /// Get the text generation endpoint for the SubProvider, to which prompts rendered with a
+    /// chat template are sent (see [TextGenerationModel]).
+    pub fn text_generation_endpoint(
+        &self,
+        model: &str,
+    ) -> Result<String, UnsupportedEndpointError> {
+        match self {
+            SubProvider::HFInference => Ok(format!("/{}", model)),
+            SubProvider::SelfHosted => Ok("/generate".to_string()),
+            _ => Err(UnsupportedEndpointError {
+                endpoint: "text generation",
+                sub_provider: self.clone(),
+            }),
+        }
+    }
+
+    /// Get the embedding endpoint for the SubProvider: the feature extraction pipeline of the
+    /// model for Huggingface Inference, the OpenAI-compatible endpoint of self-hosted servers.
+    pub fn embedding_endpoint(&self, model: &str) -> Result<String, UnsupportedEndpointError> {
+        match self {
+            SubProvider::HFInference => Ok(format!("/{}/pipeline/feature-extraction", model)),
+            SubProvider::SelfHosted => Ok("/v1/embeddings".to_string()),
+            _ => Err(UnsupportedEndpointError {
+                endpoint: "embedding",
+                sub_provider: self.clone(),
+            }),
+        }
+    }
+
     /// Get the transcription endpoint for the SubProvider
     /// Required because Huggingface Inference requires the model
     /// in the url and in the request body.
-    pub fn transcription_endpoint(&self, model: &str) -> Result<String, TranscriptionError> {
+    pub fn transcription_endpoint(&self, model: &str) -> Result<String, UnsupportedEndpointError> {
         match self {
             SubProvider::HFInference => Ok(format!("/{}", model)),
-            _ => Err(TranscriptionError::ProviderError(format!(
-                "transcription endpoint is not supported yet for {}",
-                self
-            ))),
+            _ => Err(UnsupportedEndpointError {
+                endpoint: "transcription",
+                sub_provider: self.clone(),
+            }),
         }
     }
 
@@ -55,13 +94,16 @@ impl SubProvider {
     /// Required because Huggingface Inference requires the model
     /// in the url and in the request body.
     #[cfg(feature = "image")]
-    pub fn image_generation_endpoint(&self, model: &str) -> Result<String, ImageGenerationError> {
+    pub fn image_generation_endpoint(
+        &self,
+        model: &str,
+    ) -> Result<String, UnsupportedEndpointError> {
         match self {
             SubProvider::HFInference => Ok(format!("/{}", model)),
-            _ => Err(ImageGenerationError::ProviderError(format!(
-                "image generation endpoint is not supported yet for {}",
-                self
-            ))),
+            _ => Err(UnsupportedEndpointError {
+                endpoint: "image generation",
+                sub_provider: self.clone(),
+            }),
         }
     }
 
@@ -73,6 +115,40 @@ impl SubProvider {
     }
 }
 
+/// Error of the endpoints not supported yet by a [SubProvider], returned before sending any
+/// request
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{endpoint} endpoint is not supported yet for {sub_provider}")]
+pub struct UnsupportedEndpointError {
+    pub endpoint: &'static str,
+    pub sub_provider: SubProvider,
+}
+
+impl From<UnsupportedEndpointError> for CompletionError {
+    fn from(error: UnsupportedEndpointError) -> Self {
+        CompletionError::RequestError(Box::new(error))
+    }
+}
+
+impl From<UnsupportedEndpointError> for EmbeddingError {
+    fn from(error: UnsupportedEndpointError) -> Self {
+        EmbeddingError::DocumentError(Box::new(error))
+    }
+}
+
+impl From<UnsupportedEndpointError> for TranscriptionError {
+    fn from(error: UnsupportedEndpointError) -> Self {
+        TranscriptionError::RequestError(Box::new(error))
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<UnsupportedEndpointError> for ImageGenerationError {
+    fn from(error: UnsupportedEndpointError) -> Self {
+        ImageGenerationError::RequestError(Box::new(error))
+    }
+}
+
 impl From<&str> for SubProvider {
     fn from(s: &str) -> Self {
         SubProvider::Custom(s.to_string())
@@ -95,6 +171,7 @@ impl Display for SubProvider {
             SubProvider::Hyperbolic => "hyperbolic".to_string(),
             SubProvider::Nebius => "nebius".to_string(),
             SubProvider::Novita => "novita".to_string(),
+            SubProvider::SelfHosted => String::new(),
             SubProvider::Custom(route) => route.clone(),
         };
 
@@ -106,6 +183,8 @@ pub struct ClientBuilder {
     api_key: String,
     base_url: String,
     sub_provider: SubProvider,
+    timeouts: Timeouts,
+    http_client: Option<reqwest::Client>,
 }
 
 impl ClientBuilder {
@@ -114,6 +193,8 @@ impl ClientBuilder {
             api_key: api_key.to_string(),
             base_url: HUGGINGFACE_API_BASE_URL.to_string(),
             sub_provider: SubProvider::default(),
+            timeouts: Timeouts::default(),
+            http_client: None,
         }
     }
 
@@ -127,19 +208,53 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the time allowed to establish a connection (default: [DEFAULT_CONNECT_TIMEOUT](crate::providers::timeouts::DEFAULT_CONNECT_TIMEOUT)).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Set the time allowed between two reads of a response (default: none).
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /// Set both timeouts (`None` disables a timeout).
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The timeouts of the builder do not apply to `http_client`.
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
     pub fn build(self) -> Client {
         let route = self.sub_provider.to_string();
 
         let base_url = format!("{}/{}", self.base_url, route).replace("//", "/");
 
-        Client::from_url(self.api_key.as_str(), base_url.as_str(), self.sub_provider)
+        let client = Client::from_url_with_timeouts(
+            self.api_key.as_str(),
+            base_url.as_str(),
+            self.sub_provider,
+            self.timeouts,
+        );
+        match self.http_client {
+            Some(http_client) => client.with_http_client(http_client),
+            None => client,
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
     pub(crate) sub_provider: SubProvider,
 }
 
@@ -153,15 +268,27 @@ impl Client {
 
     /// Create a new Client with the given API key and base API URL.
     pub fn from_url(api_key: &str, base_url: &str, sub_provider: SubProvider) -> Self {
-        let http_client = reqwest::Client::builder()
-            .default_headers({
+        Self::from_url_with_timeouts(api_key, base_url, sub_provider, Timeouts::default())
+    }
+
+    fn from_url_with_timeouts(
+        api_key: &str,
+        base_url: &str,
+        sub_provider: SubProvider,
+        timeouts: Timeouts,
+    ) -> Self {
+        let http_client = HttpClient::with_timeouts(
+            {
                 let mut headers = reqwest::header::HeaderMap::new();
-                headers.insert(
-                    "Authorization",
-                    format!("Bearer {api_key}")
-                        .parse()
-                        .expect("Failed to parse API key"),
-                );
+                // Self-hosted servers without an API key reject empty bearer tokens
+                if !api_key.is_empty() {
+                    headers.insert(
+                        "Authorization",
+                        format!("Bearer {api_key}")
+                            .parse()
+                            .expect("Failed to parse API key"),
+                    );
+                }
                 headers.insert(
                     "Content-Type",
                     "application/json"
@@ -169,9 +296,9 @@ impl Client {
                         .expect("Failed to parse Content-Type"),
                 );
                 headers
-            })
-            .build()
-            .expect("Failed to build HTTP client");
+            },
+            timeouts,
+        );
 
         Self {
             base_url: base_url.to_owned(),
@@ -179,6 +306,23 @@ impl Client {
             sub_provider,
         }
     }
+
+    /// Create a new client for a self-hosted TGI, TEI or vLLM server at `base_url`
+    /// (e.g.: `http://localhost:8080`), which does not require an API key. Servers requiring
+    /// one are used with a [ClientBuilder] with the [SubProvider::SelfHosted] sub provider.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::huggingface::Client;
+    ///
+    /// let client = Client::self_hosted("http://localhost:8080");
+    ///
+    /// let agent = client.agent("meta-llama/Meta-Llama-3.1-8B-Instruct").build();
+    /// ```
+    pub fn self_hosted(base_url: &str) -> Self {
+        Self::from_url("", base_url, SubProvider::SelfHosted)
+    }
+
     /// Create a new Huggingface client from the `HUGGINGFACE_API_KEY` environment variable.
     /// Panics if the environment variable is not set.
     pub fn from_env() -> Self {
@@ -186,6 +330,33 @@ impl Client {
         Self::new(&api_key)
     }
 
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
         self.http_client.post(url)
@@ -206,6 +377,41 @@ impl Client {
         CompletionModel::new(self.clone(), model)
     }
 
+    /// Create a new text generation model with the given name, sending the conversations
+    /// rendered with `template` to the text generation endpoint of the model. This is needed
+    /// for models without a chat template, which cannot be used with [Client::completion_model].
+    ///
+    /// # Example
+    /// ```
+    /// use rig::{completion::template::ChatTemplate, providers::huggingface::Client};
+    ///
+    /// let client = Client::self_hosted("http://localhost:8080");
+    ///
+    /// let model = client.text_generation_model("teknium/OpenHermes-2.5-Mistral-7B", ChatTemplate::chatml());
+    /// ```
+    pub fn text_generation_model(
+        &self,
+        model: &str,
+        template: ChatTemplate,
+    ) -> TextGenerationModel {
+        TextGenerationModel::new(self.clone(), model, template)
+    }
+
+    /// Create a new embedding model with the given name and number of dimensions
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::huggingface::{Client, self};
+    ///
+    /// // Initialize the Huggingface client
+    /// let client = Client::new("your-huggingface-api-key");
+    ///
+    /// let embedding_model = client.embedding_model(huggingface::ALL_MINILM_L6_V2, 384);
+    /// ```
+    pub fn embedding_model(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+
     /// Create a new transcription model with the given name
     ///
     /// # Example
@@ -256,3 +462,46 @@ impl Client {
         AgentBuilder::new(self.completion_model(model))
     }
 }
+
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_hosted_without_authorization() {
+        let client = Client::self_hosted("http://localhost:8080");
+        let request = client.post("/generate").build().unwrap();
+        assert!(!request.headers().contains_key("Authorization"));
+
+        let client = Client::from_url("key", "http://localhost:8080", SubProvider::SelfHosted);
+        let request = client.post("/generate").build().unwrap();
+        assert_eq!(request.headers()["Authorization"], "Bearer key");
+    }
+
+    #[test]
+    fn test_unsupported_endpoint() {
+        let error = SubProvider::Together
+            .embedding_endpoint("model")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "embedding endpoint is not supported yet for together"
+        );
+        assert!(matches!(
+            CompletionError::from(
+                SubProvider::Together
+                    .text_generation_endpoint("model")
+                    .unwrap_err()
+            ),
+            CompletionError::RequestError(_)
+        ));
+    }
+}
@@ -5,6 +5,7 @@ use serde_json::{json, Value};
 
 use crate::{
     completion::{self, CompletionError, CompletionRequest},
+    error::ApiError,
     json_utils,
     message::{self},
     one_or_many::string_or_one_or_many,
@@ -276,14 +277,9 @@ impl TryFrom<message::Message> for Vec<Message> {
                             }) => Ok::<_, message::MessageError>(Message::ToolResult {
                                 name: id,
                                 arguments: None,
-                                content: content.try_map(|content| match content {
-                                    message::ToolResultContent::Text(message::Text { text }) => {
-                                        Ok(text)
-                                    }
-                                    _ => Err(message::MessageError::ConversionError(
-                                        "Tool result content does not support non-text".into(),
-                                    )),
-                                })?,
+                                // Tool messages can only be text, images are replaced by a
+                                //  placeholder
+                                content: content.map(message::ToolResultContent::into_text),
                             }),
                             _ => unreachable!(),
                         })
@@ -535,6 +531,10 @@ impl CompletionModel {
                 "tool_choice": "auto",
             })
         };
+        let request = json_utils::merge(
+            request,
+            completion_request.generation_config().openai_params(),
+        );
         Ok(request)
     }
 }
@@ -542,11 +542,18 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request = self.create_request_body(&completion_request)?;
 
         let path = self.client.sub_provider.completion_endpoint(&self.model);
@@ -561,7 +568,7 @@ impl completion::CompletionModel for CompletionModel {
 
         if response.status().is_success() {
             let t = response.text().await?;
-            tracing::debug!(target: "rig", "Huggingface completion error: {}", t);
+            tracing::debug!(target: "rig", "Huggingface completion response: {}", t);
 
             match serde_json::from_str::<ApiResponse<CompletionResponse>>(&t)? {
                 ApiResponse::Ok(response) => {
@@ -571,14 +578,10 @@ impl completion::CompletionModel for CompletionModel {
                     );
                     response.try_into()
                 }
-                ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.to_string())),
+                ApiResponse::Err(err) => Err(ApiError::new(None, &err.to_string()).into()),
             }
         } else {
-            Err(CompletionError::ProviderError(format!(
-                "{}: {}",
-                response.status(),
-                response.text().await?
-            )))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -0,0 +1,147 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::client::{Client, SubProvider};
+use crate::embeddings::{self, EmbeddingError};
+use crate::error::ApiError;
+
+// ================================================================
+// Huggingface Embedding API
+// ================================================================
+
+/// `sentence-transformers/all-MiniLM-L6-v2` embedding model (384 dimensions)
+pub const ALL_MINILM_L6_V2: &str = "sentence-transformers/all-MiniLM-L6-v2";
+/// `BAAI/bge-base-en-v1.5` embedding model (768 dimensions)
+pub const BGE_BASE_EN_V1_5: &str = "BAAI/bge-base-en-v1.5";
+/// `intfloat/multilingual-e5-large` embedding model (1024 dimensions)
+pub const MULTILINGUAL_E5_LARGE: &str = "intfloat/multilingual-e5-large";
+
+/// Response of the OpenAI-compatible embedding endpoint of self-hosted servers
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f64>,
+}
+
+#[derive(Clone)]
+pub struct EmbeddingModel {
+    client: Client,
+    /// Name of the model (e.g.: sentence-transformers/all-MiniLM-L6-v2)
+    pub model: String,
+    ndims: usize,
+}
+
+impl EmbeddingModel {
+    pub fn new(client: Client, model: &str, ndims: usize) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+            ndims,
+        }
+    }
+
+    /// Parse the embeddings of a response of the embedding endpoint of the sub provider
+    fn parse_embeddings(&self, response: Value) -> Result<Vec<Vec<f64>>, EmbeddingError> {
+        match self.client.sub_provider {
+            // The feature extraction pipeline returns the embeddings as is
+            SubProvider::HFInference => Ok(serde_json::from_value(response)?),
+            _ => Ok(serde_json::from_value::<EmbeddingResponse>(response)?
+                .data
+                .into_iter()
+                .map(|data| data.embedding)
+                .collect()),
+        }
+    }
+}
+
+impl embeddings::EmbeddingModel for EmbeddingModel {
+    const MAX_DOCUMENTS: usize = 32;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts(
+        &self,
+        documents: impl IntoIterator<Item = String>,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        let documents = documents.into_iter().collect::<Vec<_>>();
+        crate::rate_limit::acquire_embedding(self.client.rate_limiter(), &documents).await?;
+
+        let request = match self.client.sub_provider {
+            SubProvider::HFInference => json!({ "inputs": documents }),
+            _ => json!({
+                "model": self.model,
+                "input": documents,
+            }),
+        };
+
+        let route = self.client.sub_provider.embedding_endpoint(&self.model)?;
+        let response = self.client.post(&route).json(&request).send().await?;
+
+        if response.status().is_success() {
+            let embeddings = self.parse_embeddings(response.json().await?)?;
+
+            if embeddings.len() != documents.len() {
+                return Err(EmbeddingError::ResponseError(
+                    "Response data length does not match input length".into(),
+                ));
+            }
+
+            Ok(embeddings
+                .into_iter()
+                .zip(documents)
+                .map(|(vec, document)| embeddings::Embedding { document, vec })
+                .collect())
+        } else {
+            Err(ApiError::from_response(response).await.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::EmbeddingModel;
+    use crate::providers::huggingface::Client;
+
+    #[test]
+    fn test_parse_embeddings() {
+        let model = EmbeddingModel::new(Client::new("key"), super::ALL_MINILM_L6_V2, 2);
+        assert_eq!(
+            model
+                .parse_embeddings(json!([[0.1, 0.2], [0.3, 0.4]]))
+                .unwrap(),
+            vec![vec![0.1, 0.2], vec![0.3, 0.4]]
+        );
+
+        let model = EmbeddingModel::new(
+            Client::self_hosted("http://localhost:8080"),
+            super::ALL_MINILM_L6_V2,
+            2,
+        );
+        assert_eq!(
+            model
+                .parse_embeddings(json!({
+                    "object": "list",
+                    "data": [
+                        {"object": "embedding", "index": 0, "embedding": [0.1, 0.2]},
+                        {"object": "embedding", "index": 1, "embedding": [0.3, 0.4]}
+                    ],
+                    "model": super::ALL_MINILM_L6_V2
+                }))
+                .unwrap(),
+            vec![vec![0.1, 0.2], vec![0.3, 0.4]]
+        );
+    }
+}
@@ -20,6 +20,7 @@ impl TryFrom<ImageGenerationResponse>
     fn try_from(value: ImageGenerationResponse) -> Result<Self, Self::Error> {
         Ok(image_generation::ImageGenerationResponse {
             image: value.data.clone(),
+            url: None,
             response: value,
         })
     }
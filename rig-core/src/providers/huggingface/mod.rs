@@ -9,20 +9,29 @@
 //!
 //! let completion_model = client.completion_model(completion::GEMMA_2);
 //! ```
+//!
+//! Open-weight models served with Text Generation Inference (TGI), Text Embeddings Inference
+//! (TEI) or vLLM are used with a client of the server (see [Client::self_hosted]). Models
+//! without a chat template are prompted through their text generation endpoint, with a
+//! [ChatTemplate](crate::completion::template::ChatTemplate) (see [TextGenerationModel]).
 
 pub mod client;
 pub mod completion;
+pub mod embedding;
 
 #[cfg(feature = "image")]
 pub mod image_generation;
 pub mod streaming;
+pub mod text_generation;
 pub mod transcription;
 
-pub use client::{Client, ClientBuilder, SubProvider};
+pub use client::{Client, ClientBuilder, SubProvider, UnsupportedEndpointError};
 pub use completion::{
     GEMMA_2, META_LLAMA_3_1, PHI_4, QWEN2_5, QWEN2_5_CODER, QWEN2_VL, QWEN_QVQ_PREVIEW,
     SMALLTHINKER_PREVIEW,
 };
+pub use embedding::{ALL_MINILM_L6_V2, BGE_BASE_EN_V1_5, MULTILINGUAL_E5_LARGE};
+pub use text_generation::TextGenerationModel;
 
 #[cfg(feature = "image")]
 pub use image_generation::{FLUX_1, KOLORS, STABLE_DIFFUSION_3};
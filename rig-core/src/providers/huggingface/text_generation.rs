@@ -0,0 +1,236 @@
+//! Text generation model, for open-weight models without a chat template (e.g.: base models or
+//! fine-tunes served with TGI). The conversation is rendered into a single prompt with a
+//! [ChatTemplate] and sent to the text generation endpoint of the model.
+//!
+//! # Example
+//! ```
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::{template::ChatTemplate, Prompt},
+//!     providers::huggingface::Client,
+//! };
+//!
+//! let client = Client::self_hosted("http://localhost:8080");
+//! let model =
+//!     client.text_generation_model("teknium/OpenHermes-2.5-Mistral-7B", ChatTemplate::chatml());
+//!
+//! let agent = AgentBuilder::new(model)
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//!
+//! let answer = agent.prompt("Hello!").await?;
+//! ```
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::client::Client;
+use crate::{
+    completion::{self, template::ChatTemplate, CompletionError, CompletionRequest},
+    error::ApiError,
+    json_utils,
+    message::AssistantContent,
+    OneOrMany,
+};
+
+/// Response of the text generation endpoint. Huggingface Inference returns a list of
+/// generations, TGI a single one.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ApiResponse {
+    Generations(Vec<TextGenerationResponse>),
+    Generation(TextGenerationResponse),
+    Err { error: Value },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TextGenerationResponse {
+    pub generated_text: String,
+    /// Details of the generation (e.g.: finish reason, number of generated tokens), if requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+#[derive(Clone)]
+pub struct TextGenerationModel {
+    client: Client,
+    /// Name of the model (e.g.: teknium/OpenHermes-2.5-Mistral-7B)
+    pub model: String,
+    /// Template rendering the conversations into prompts
+    pub template: ChatTemplate,
+}
+
+impl TextGenerationModel {
+    pub fn new(client: Client, model: &str, template: ChatTemplate) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+            template,
+        }
+    }
+
+    /// End of the turns of the assistant in the template (e.g.: `<|im_end|>`), at which the
+    /// generation stops.
+    fn end_of_turn(&self) -> Option<&str> {
+        Some(self.template.assistant.suffix.trim()).filter(|suffix| !suffix.is_empty())
+    }
+
+    pub(crate) fn create_request_body(
+        &self,
+        completion_request: &CompletionRequest,
+    ) -> Result<Value, CompletionError> {
+        if !completion_request.tools.is_empty() {
+            return Err(CompletionError::ProviderError(
+                "Huggingface text generation does not support tools".into(),
+            ));
+        }
+
+        let generation = completion_request.generation_config();
+        let mut stop = generation.stop;
+        if let Some(end_of_turn) = self.end_of_turn() {
+            stop.push(end_of_turn.to_string());
+        }
+
+        let mut parameters = Map::new();
+        parameters.insert("return_full_text".into(), json!(false));
+        let optional = [
+            ("temperature", generation.temperature.map(Value::from)),
+            ("top_p", generation.top_p.map(Value::from)),
+            ("max_new_tokens", generation.max_tokens.map(Value::from)),
+            ("seed", generation.seed.map(Value::from)),
+            (
+                "frequency_penalty",
+                generation.frequency_penalty.map(Value::from),
+            ),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                parameters.insert(key.into(), value);
+            }
+        }
+        if !stop.is_empty() {
+            parameters.insert("stop".into(), json!(stop));
+        }
+
+        let request = json!({
+            "inputs": self.template.render_request(completion_request),
+            "parameters": parameters,
+        });
+
+        Ok(match &completion_request.additional_params {
+            Some(params) => json_utils::merge(request, params.clone()),
+            None => request,
+        })
+    }
+
+    /// The answer of a generation: its text, without the end of turn marker generated by
+    /// the model.
+    fn answer(&self, generated_text: &str) -> String {
+        let text = generated_text.trim_end();
+        self.end_of_turn()
+            .and_then(|end_of_turn| text.strip_suffix(end_of_turn))
+            .unwrap_or(text)
+            .trim()
+            .to_string()
+    }
+}
+
+impl completion::CompletionModel for TextGenerationModel {
+    type Response = TextGenerationResponse;
+
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<TextGenerationResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
+        let request = self.create_request_body(&completion_request)?;
+
+        let path = self
+            .client
+            .sub_provider
+            .text_generation_endpoint(&self.model)?;
+
+        let response = self.client.post(&path).json(&request).send().await?;
+
+        if response.status().is_success() {
+            let response = match response.json::<ApiResponse>().await? {
+                ApiResponse::Generations(generations) => {
+                    generations.into_iter().next().ok_or_else(|| {
+                        CompletionError::ResponseError("Response contained no generation".into())
+                    })?
+                }
+                ApiResponse::Generation(generation) => generation,
+                ApiResponse::Err { error } => {
+                    return Err(ApiError::new(None, &json!({ "error": error }).to_string()).into())
+                }
+            };
+
+            Ok(completion::CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(
+                    self.answer(&response.generated_text),
+                )),
+                raw_response: response,
+            })
+        } else {
+            Err(ApiError::from_response(response).await.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::TextGenerationModel;
+    use crate::{
+        completion::{template::ChatTemplate, CompletionModel},
+        providers::huggingface::Client,
+    };
+
+    #[test]
+    fn test_text_generation_request() {
+        let model = TextGenerationModel::new(
+            Client::self_hosted("http://localhost:8080"),
+            "teknium/OpenHermes-2.5-Mistral-7B",
+            ChatTemplate::chatml(),
+        );
+
+        let request = model
+            .completion_request("Hello!")
+            .preamble("Be brief.".to_string())
+            .temperature(0.5)
+            .max_tokens(64)
+            .build();
+
+        assert_eq!(
+            model.create_request_body(&request).unwrap(),
+            json!({
+                "inputs": "<|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nHello!<|im_end|>\n<|im_start|>assistant\n",
+                "parameters": {
+                    "return_full_text": false,
+                    "temperature": 0.5,
+                    "max_new_tokens": 64,
+                    "stop": ["<|im_end|>"]
+                }
+            })
+        );
+
+        assert_eq!(model.answer(" Hi there!<|im_end|>\n"), "Hi there!");
+
+        let path = model
+            .client
+            .sub_provider
+            .text_generation_endpoint(&model.model)
+            .unwrap();
+        assert_eq!(
+            model.client.post(&path).build().unwrap().url().as_str(),
+            "http://localhost:8080/generate"
+        );
+    }
+}
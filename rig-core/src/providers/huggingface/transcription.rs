@@ -1,3 +1,4 @@
+use crate::error::ApiError;
 use crate::providers::huggingface::completion::ApiResponse;
 use crate::providers::huggingface::Client;
 use crate::transcription;
@@ -74,7 +75,7 @@ impl transcription::TranscriptionModel for TranscriptionModel {
                 ApiResponse::Err(err) => Err(TranscriptionError::ProviderError(err.to_string())),
             }
         } else {
-            Err(TranscriptionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -9,6 +9,7 @@
 //! ```
 use crate::json_utils::merge;
 use crate::providers::openai::send_compatible_streaming_request;
+use crate::providers::timeouts::Timeouts;
 use crate::streaming::{StreamingCompletionModel, StreamingResult};
 use crate::{
     agent::AgentBuilder,
@@ -113,6 +114,7 @@ pub struct Client {
     base_url: String,
     client: reqwest::Client,
     headers: HeaderMap,
+    rate_limiter: Option<crate::rate_limit::RateLimiter>,
 }
 
 impl Client {
@@ -136,10 +138,9 @@ impl Client {
 
         Ok(Self {
             base_url: MIRA_API_BASE_URL.to_string(),
-            client: reqwest::Client::builder()
-                .build()
-                .expect("Failed to build HTTP client"),
+            client: Timeouts::default().build_client(),
             headers,
+            rate_limiter: None,
         })
     }
 
@@ -160,6 +161,29 @@ impl Client {
         Ok(client)
     }
 
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.client = http_client;
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.client = timeouts.build_client();
+        self
+    }
+
+    /// Send the completions (streamed or not) of the models of the client within the limits of
+    /// `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
     /// List available models
     pub async fn list_models(&self) -> Result<Vec<String>, MiraError> {
         let url = format!("{}/v1/models", self.base_url);
@@ -209,6 +233,14 @@ impl Client {
     }
 }
 
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Clone)]
 pub struct CompletionModel {
     client: Client,
@@ -297,6 +329,11 @@ impl CompletionModel {
             "stream": false
         });
 
+        let request = match completion_request.additional_params {
+            Some(params) => merge(request, params),
+            None => request,
+        };
+
         Ok(request)
     }
 }
@@ -304,11 +341,21 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(
+            self.client.rate_limiter.as_ref(),
+            &completion_request,
+        )
+        .await?;
+
         if !completion_request.tools.is_empty() {
             tracing::warn!(target: "rig",
                 "Tool calls are not supported by the Mira provider. {} tools will be ignored.",
@@ -351,6 +398,12 @@ impl StreamingCompletionModel for CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(
+            self.client.rate_limiter.as_ref(),
+            &completion_request,
+        )
+        .await?;
+
         let mut request = self.create_completion_request(completion_request)?;
 
         request = merge(request, json!({"stream": true}));
@@ -616,13 +669,13 @@ mod tests {
         };
 
         // Convert to Mira format
-        let mira_value: serde_json::Value = original_message.clone().try_into().unwrap();
+        let mira_value: serde_json::Value = original_message.clone().into();
 
         // Convert back to our Message type
         let converted_message: Message = mira_value.try_into().unwrap();
 
         // Convert back to original format
-        let final_message: message::Message = converted_message.try_into().unwrap();
+        let final_message: message::Message = converted_message;
 
         assert_eq!(original_message, final_message);
     }
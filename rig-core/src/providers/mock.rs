@@ -0,0 +1,690 @@
+//! Mock provider, to test agents, pipelines and RAG systems deterministically, without network
+//! access nor API keys (e.g.: in CI).
+//!
+//! - [MockCompletionModel]: completion model answering with a script of responses (text answers,
+//!   tool calls or errors), and recording the requests it receives.
+//! - [MockEmbeddingModel]: embedding model returning fixed embeddings for known texts, and
+//!   deterministic embeddings (based on the words of the text) for the others.
+//! - [MockVectorStore]: vector store returning canned documents, and recording the queries it
+//!   receives.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     completion::Prompt,
+//!     providers::mock::{MockCompletionModel, MockVectorStore},
+//! };
+//! use serde_json::json;
+//!
+//! let model = MockCompletionModel::new()
+//!     .tool_call("add", json!({"x": 1, "y": 2}))
+//!     .text("1 + 2 = 3");
+//!
+//! let agent = rig::agent::AgentBuilder::new(model.clone())
+//!     .tool(Adder)
+//!     .dynamic_context(1, MockVectorStore::new().document("doc0", "Addition is commutative."))
+//!     .build();
+//!
+//! assert_eq!(agent.multi_turn(2).prompt("What is 1 + 2?").await?, "1 + 2 = 3");
+//! assert_eq!(model.requests().len(), 2);
+//! ```
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    completion::{self, CompletionError, CompletionRequest, CompletionResponse, TokenUsage},
+    embeddings::{self, EmbeddingError},
+    message::AssistantContent,
+    streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult},
+    vector_store::{
+        filter::Filter, VectorStore, VectorStoreError, VectorStoreIndex, VectorStoreStats,
+    },
+    OneOrMany,
+};
+
+// ================================================================
+// Mock Completion Model
+// ================================================================
+
+/// Scripted response of a [MockCompletionModel].
+#[derive(Clone, Debug)]
+enum ScriptedResponse {
+    Choice(OneOrMany<AssistantContent>),
+    Error(String),
+}
+
+/// Raw response of a [MockCompletionModel], reporting the (approximate) token usage of the
+/// completion: one token per word of the request and of the response.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MockResponse {
+    pub usage: TokenUsage,
+}
+
+/// Completion model answering with a script of responses, in order.
+///
+/// Clones of the model share the same script and the same recorded requests, so the model can
+/// be given to an agent and inspected afterwards.
+#[derive(Clone, Default)]
+pub struct MockCompletionModel {
+    script: Arc<Mutex<VecDeque<ScriptedResponse>>>,
+    fallback: Option<String>,
+    requests: Arc<Mutex<Vec<CompletionRequest>>>,
+    tool_calls: Arc<Mutex<usize>>,
+}
+
+impl MockCompletionModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a text answer to the script
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.response(OneOrMany::one(AssistantContent::text(text)))
+    }
+
+    /// Add a call of the tool `name` with `arguments` to the script. The ids of the tool calls
+    /// are `call_0`, `call_1`, etc.
+    pub fn tool_call(self, name: impl Into<String>, arguments: Value) -> Self {
+        let id = {
+            let mut tool_calls = self.tool_calls.lock().expect("mock lock poisoned");
+            *tool_calls += 1;
+            format!("call_{}", *tool_calls - 1)
+        };
+        self.response(OneOrMany::one(AssistantContent::tool_call(
+            id, name, arguments,
+        )))
+    }
+
+    /// Add a response (e.g.: several tool calls) to the script
+    pub fn response(self, choice: OneOrMany<AssistantContent>) -> Self {
+        self.script
+            .lock()
+            .expect("mock lock poisoned")
+            .push_back(ScriptedResponse::Choice(choice));
+        self
+    }
+
+    /// Add an error to the script: the corresponding request fails with a
+    /// [CompletionError::ProviderError] with the message `message`
+    pub fn error(self, message: impl Into<String>) -> Self {
+        self.script
+            .lock()
+            .expect("mock lock poisoned")
+            .push_back(ScriptedResponse::Error(message.into()));
+        self
+    }
+
+    /// Set the text answer of the requests received once the script is exhausted.
+    /// By default, these requests fail with a [CompletionError::ProviderError].
+    pub fn fallback_text(mut self, text: impl Into<String>) -> Self {
+        self.fallback = Some(text.into());
+        self
+    }
+
+    /// Requests received by the model, in order
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.requests.lock().expect("mock lock poisoned").clone()
+    }
+
+    /// Number of scripted responses not yet returned
+    pub fn remaining(&self) -> usize {
+        self.script.lock().expect("mock lock poisoned").len()
+    }
+
+    /// Record `request` and pop the next response of the script
+    fn next_choice(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<(CompletionRequest, OneOrMany<AssistantContent>), CompletionError> {
+        self.requests
+            .lock()
+            .expect("mock lock poisoned")
+            .push(request.clone());
+
+        let scripted = self.script.lock().expect("mock lock poisoned").pop_front();
+        match (scripted, &self.fallback) {
+            (Some(ScriptedResponse::Choice(choice)), _) => Ok((request, choice)),
+            (Some(ScriptedResponse::Error(message)), _) => {
+                Err(CompletionError::ProviderError(message))
+            }
+            (None, Some(fallback)) => Ok((
+                request,
+                OneOrMany::one(AssistantContent::text(fallback.clone())),
+            )),
+            (None, None) => Err(CompletionError::ProviderError(
+                "Mock completion model script exhausted".to_string(),
+            )),
+        }
+    }
+}
+
+fn word_count(text: &str) -> u64 {
+    text.split_whitespace().count() as u64
+}
+
+impl completion::CompletionModel for MockCompletionModel {
+    type Response = MockResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<MockResponse>, CompletionError> {
+        let (request, choice) = self.next_choice(request)?;
+        let usage = usage(&request, &choice);
+
+        Ok(CompletionResponse {
+            choice,
+            raw_response: MockResponse { usage },
+        })
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        Some("mock")
+    }
+
+    fn token_usage(&self, response: &MockResponse) -> Option<TokenUsage> {
+        Some(response.usage)
+    }
+
+    fn raw_response_json(&self, response: &MockResponse) -> Option<Value> {
+        serde_json::to_value(response).ok()
+    }
+}
+
+/// The mock model is its own client: all the models it creates share its script.
+impl completion::CompletionClient for MockCompletionModel {
+    type Model = Self;
+
+    fn completion_model(&self, _model: &str) -> Self {
+        self.clone()
+    }
+}
+
+impl StreamingCompletionModel for MockCompletionModel {
+    /// Stream the next response of the script: each text is streamed word by word, and each tool
+    /// call as a single chunk, followed by the token usage of the response.
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        let (request, choice) = self.next_choice(request)?;
+        let usage = usage(&request, &choice);
+
+        let chunks = choice
+            .into_iter()
+            .flat_map(|content| match content {
+                AssistantContent::Text(text) => text
+                    .text
+                    .split_inclusive(' ')
+                    .map(|word| Ok(StreamingChoice::Message(word.to_string())))
+                    .collect::<Vec<_>>(),
+                AssistantContent::ToolCall(tool_call) => vec![Ok(StreamingChoice::ToolCall(
+                    tool_call.function.name,
+                    tool_call.id,
+                    tool_call.function.arguments,
+                ))],
+            })
+            .chain([Ok(StreamingChoice::Usage(usage))])
+            .collect::<Vec<_>>();
+
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+}
+
+/// Token usage of a response of the mock model: one token per word of the request (prompt,
+/// context and preamble) and of the response.
+fn usage(request: &CompletionRequest, choice: &OneOrMany<AssistantContent>) -> TokenUsage {
+    let input_tokens = word_count(&request.prompt_with_context().rag_text().unwrap_or_default())
+        + request
+            .preamble
+            .as_deref()
+            .map(word_count)
+            .unwrap_or_default();
+    let output_tokens = choice
+        .iter()
+        .map(|content| match content {
+            AssistantContent::Text(text) => word_count(&text.text),
+            AssistantContent::ToolCall(tool_call) => {
+                word_count(&tool_call.function.arguments.to_string())
+            }
+        })
+        .sum();
+
+    TokenUsage {
+        input_tokens,
+        output_tokens,
+        total_tokens: input_tokens + output_tokens,
+        ..Default::default()
+    }
+}
+
+// ================================================================
+// Mock Embedding Model
+// ================================================================
+
+/// Embedding model returning fixed embeddings for known texts, and deterministic embeddings for
+/// the others: the normalized sum of one pseudo-random vector per (lowercased) word, so texts
+/// sharing words are similar.
+#[derive(Clone, Debug)]
+pub struct MockEmbeddingModel {
+    ndims: usize,
+    embeddings: HashMap<String, Vec<f64>>,
+    requests: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl MockEmbeddingModel {
+    /// Create a model returning embeddings with `ndims` dimensions
+    pub fn new(ndims: usize) -> Self {
+        Self {
+            ndims,
+            embeddings: HashMap::new(),
+            requests: Default::default(),
+        }
+    }
+
+    /// Set the embedding of `text`. Its number of dimensions should be the number of dimensions
+    /// of the model.
+    pub fn embedding(mut self, text: impl Into<String>, vec: Vec<f64>) -> Self {
+        self.embeddings.insert(text.into(), vec);
+        self
+    }
+
+    /// Texts embedded by the model, one entry per request
+    pub fn requests(&self) -> Vec<Vec<String>> {
+        self.requests.lock().expect("mock lock poisoned").clone()
+    }
+
+    fn embed(&self, text: &str) -> Vec<f64> {
+        if let Some(vec) = self.embeddings.get(text) {
+            return vec.clone();
+        }
+
+        let mut vec = vec![0.0; self.ndims];
+        for word in text.split_whitespace() {
+            let mut state = fnv1a(word.to_lowercase().as_bytes());
+            for value in vec.iter_mut() {
+                // xorshift64, seeded by the hash of the word
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                *value += (state % 2001) as f64 / 1000.0 - 1.0;
+            }
+        }
+
+        let norm = vec.iter().map(|value| value * value).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            vec.iter_mut().for_each(|value| *value /= norm);
+        }
+        vec
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across platforms and Rust versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    }) | 1
+}
+
+impl embeddings::EmbeddingModel for MockEmbeddingModel {
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        Some("mock")
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        self.requests
+            .lock()
+            .expect("mock lock poisoned")
+            .push(texts.clone());
+
+        Ok(texts
+            .into_iter()
+            .map(|text| embeddings::Embedding {
+                vec: self.embed(&text),
+                document: text,
+            })
+            .collect())
+    }
+}
+
+// ================================================================
+// Mock Vector Store
+// ================================================================
+
+/// Vector store returning its documents by decreasing score (and in insertion order for equal
+/// scores), whatever the query.
+///
+/// Clones of the store share the same recorded queries.
+#[derive(Clone, Debug, Default)]
+pub struct MockVectorStore {
+    documents: Vec<(f64, String, Value)>,
+    queries: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a document with a score of 1
+    pub fn document(self, id: impl Into<String>, document: impl Serialize) -> Self {
+        self.scored_document(1.0, id, document)
+    }
+
+    /// Add a document with the given score
+    pub fn scored_document(
+        mut self,
+        score: f64,
+        id: impl Into<String>,
+        document: impl Serialize,
+    ) -> Self {
+        let document = serde_json::to_value(document).expect("document should serialize");
+        self.documents.push((score, id.into(), document));
+        self
+    }
+
+    /// Queries received by the store, in order
+    pub fn queries(&self) -> Vec<String> {
+        self.queries.lock().expect("mock lock poisoned").clone()
+    }
+
+    /// Record `query` and return the `n` documents with the highest scores among the documents
+    /// matching `filter`
+    fn search(&self, query: &str, n: usize, filter: Option<&Filter>) -> Vec<&(f64, String, Value)> {
+        self.queries
+            .lock()
+            .expect("mock lock poisoned")
+            .push(query.to_string());
+
+        let mut documents = self
+            .documents
+            .iter()
+            .filter(|(_, _, document)| filter.is_none_or(|filter| filter.matches(document)))
+            .collect::<Vec<_>>();
+        documents.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+        documents.truncate(n);
+        documents
+    }
+}
+
+fn deserialize<T: for<'a> Deserialize<'a>>(
+    (score, id, document): &(f64, String, Value),
+) -> Result<(f64, String, T), VectorStoreError> {
+    let document = serde_json::from_value(document.clone()).map_err(|source| {
+        VectorStoreError::DeserializationError {
+            id: id.clone(),
+            source,
+        }
+    })?;
+    Ok((*score, id.clone(), document))
+}
+
+impl VectorStoreIndex for MockVectorStore {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, None)
+            .into_iter()
+            .map(deserialize)
+            .collect()
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        Ok(self
+            .search(query, n, None)
+            .into_iter()
+            .map(|(score, id, _)| (*score, id.clone()))
+            .collect())
+    }
+
+    async fn top_n_filtered<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter))
+            .into_iter()
+            .map(deserialize)
+            .collect()
+    }
+
+    async fn top_n_ids_filtered(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        Ok(self
+            .search(query, n, Some(&filter))
+            .into_iter()
+            .map(|(score, id, _)| (*score, id.clone()))
+            .collect())
+    }
+
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        Ok(VectorStoreStats {
+            document_count: Some(self.documents.len()),
+            index_type: Some("MOCK".to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+impl VectorStore for MockVectorStore {
+    /// Documents are inserted with a score of 1
+    type Document = (String, Value);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<(String, Value)>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        if !upsert {
+            if let Some((id, _)) = documents
+                .iter()
+                .find(|(id, _)| self.documents.iter().any(|(_, other, _)| other == id))
+            {
+                return Err(VectorStoreError::DuplicateIdError(id.clone()));
+            }
+        }
+
+        for (id, document) in documents {
+            match self.documents.iter_mut().find(|(_, other, _)| *other == id) {
+                Some(existing) => existing.2 = document,
+                None => self.documents.push((1.0, id, document)),
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_document(
+        &mut self,
+        (id, document): (String, Value),
+    ) -> Result<(), VectorStoreError> {
+        match self.documents.iter_mut().find(|(_, other, _)| *other == id) {
+            Some(existing) => {
+                existing.2 = document;
+                Ok(())
+            }
+            None => Err(VectorStoreError::MissingIdError(id)),
+        }
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        self.documents.retain(|(_, id, _)| !ids.contains(id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        agent::AgentBuilder,
+        completion::{CompletionModel, Prompt, ToolDefinition},
+        embeddings::{distance::VectorDistance, EmbeddingModel},
+        tool::Tool,
+    };
+
+    #[derive(Deserialize)]
+    struct AddArgs {
+        x: i32,
+        y: i32,
+    }
+
+    struct Adder;
+
+    impl Tool for Adder {
+        const NAME: &'static str = "add";
+
+        type Error = std::convert::Infallible;
+        type Args = AddArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: json!({}),
+            }
+        }
+
+        async fn call(&self, args: AddArgs) -> Result<i32, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_agent() {
+        let model = MockCompletionModel::new()
+            .tool_call("add", json!({"x": 1, "y": 2}))
+            .text("1 + 2 = 3")
+            .error("overloaded");
+        let store = MockVectorStore::new()
+            .scored_document(0.5, "doc1", "Addition is associative.")
+            .document("doc0", "Addition is commutative.");
+
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder)
+            .dynamic_context(1, store.clone())
+            .build();
+
+        assert_eq!(
+            agent.multi_turn(2).prompt("What is 1 + 2?").await.unwrap(),
+            "1 + 2 = 3"
+        );
+
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].documents.len(), 1);
+        assert_eq!(requests[0].documents[0].id, "doc0");
+        assert_eq!(store.queries()[0], "What is 1 + 2?");
+        assert_eq!(model.remaining(), 1);
+
+        assert!(agent.prompt("Again").await.is_err());
+        assert!(agent.prompt("Again").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_completion_model() {
+        let model = MockCompletionModel::new()
+            .text("Hello there")
+            .fallback_text("Bye");
+
+        let response = model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(response.raw_response.usage.output_tokens, 2);
+        assert_eq!(
+            model
+                .token_usage(&response.raw_response)
+                .unwrap()
+                .input_tokens,
+            1
+        );
+
+        let mut stream = model
+            .stream(model.completion_request("Hi").build())
+            .await
+            .unwrap();
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            if let StreamingChoice::Message(chunk) = chunk.unwrap() {
+                text.push_str(&chunk);
+            }
+        }
+        assert_eq!(text, "Bye");
+    }
+
+    #[tokio::test]
+    async fn test_mock_embedding_model() {
+        let model = MockEmbeddingModel::new(16).embedding("fixed", vec![1.0; 16]);
+
+        let embeddings = model
+            .embed_texts(vec![
+                "fixed".to_string(),
+                "the cat sat".to_string(),
+                "The cat ran".to_string(),
+                "stock prices fell".to_string(),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(embeddings[0].vec, vec![1.0; 16]);
+        assert_eq!(
+            embeddings[1],
+            model.embed_text("the cat sat").await.unwrap()
+        );
+        assert!(
+            embeddings[1].cosine_similarity(&embeddings[2], true)
+                > embeddings[1].cosine_similarity(&embeddings[3], true)
+        );
+        assert_eq!(model.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_mock_vector_store() {
+        let mut store = MockVectorStore::new()
+            .document("doc0", json!({"lang": "en"}))
+            .document("doc1", json!({"lang": "fr"}));
+
+        assert!(matches!(
+            store
+                .insert_documents(vec![("doc0".to_string(), json!({}))], false)
+                .await,
+            Err(VectorStoreError::DuplicateIdError(_))
+        ));
+        store
+            .insert_documents(vec![("doc2".to_string(), json!({"lang": "fr"}))], false)
+            .await
+            .unwrap();
+        store.delete_documents(&["doc1".to_string()]).await.unwrap();
+
+        let results = store
+            .top_n_ids_filtered("query", 5, Filter::eq("lang", "fr"))
+            .await
+            .unwrap();
+        assert_eq!(results, vec![(1.0, "doc2".to_string())]);
+        assert_eq!(store.stats().await.unwrap().document_count, Some(2));
+    }
+}
@@ -11,6 +11,16 @@
 //! - DeepSeek
 //! - Azure OpenAI
 //! - Mira
+//! - Groq
+//!
+//! The [mock] module provides a mock completion model, embedding model and vector store to test
+//! agents deterministically, without network access nor API keys.
+//!
+//! xAI, DeepSeek and Groq share the OpenAI-compatible client of the [openai_compat] module,
+//! which can also be used to integrate other providers with an OpenAI-compatible API.
+//!
+//! The [sse] module decodes the Server-Sent Events of the providers streaming their completions
+//! as SSE (e.g.: Anthropic, Cohere).
 //!
 //! Each provider has its own module, which contains a `Client` implementation that can
 //! be used to initialize completion and embedding models and execute requests to those models.
@@ -47,18 +57,25 @@
 //! be used with the Cohere provider client.
 pub mod anthropic;
 pub mod azure;
+#[cfg(feature = "cassette")]
+pub mod cassette;
 pub mod cohere;
 pub mod deepseek;
 pub mod galadriel;
 pub mod gemini;
 pub mod groq;
+pub(crate) mod http_client;
 pub mod huggingface;
 pub mod hyperbolic;
 pub mod mira;
+pub mod mock;
 pub mod moonshot;
 pub mod ollama;
 pub mod openai;
+pub mod openai_compat;
 pub mod openrouter;
 pub mod perplexity;
+pub mod sse;
+pub mod timeouts;
 pub mod together;
 pub mod xai;
@@ -37,12 +37,19 @@
 //! // Also create an agent and extractor if needed
 //! let agent = client.agent("llama3.2");
 //! let extractor = client.extractor::<serde_json::Value>("llama3.2");
+//!
+//! // List the models available on the Ollama server
+//! let models = client.list_models().await.unwrap();
 //! ```
+use crate::error::ApiError;
 use crate::json_utils::merge_inplace;
+use crate::providers::http_client::HttpClient;
 use crate::streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult};
 use crate::{
     agent::AgentBuilder,
-    completion::{self, CompletionError, CompletionRequest},
+    completion::{
+        self, generation::insert_opt, CompletionError, CompletionRequest, ResponseFormat,
+    },
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
     json_utils, message,
@@ -54,7 +61,7 @@ use futures::StreamExt;
 use reqwest;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 use std::convert::Infallible;
 use std::{convert::TryFrom, str::FromStr};
 // ---------- Main Client ----------
@@ -64,7 +71,7 @@ const OLLAMA_API_BASE_URL: &str = "http://localhost:11434";
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 impl Default for Client {
@@ -77,18 +84,70 @@ impl Client {
     pub fn new() -> Self {
         Self::from_url(OLLAMA_API_BASE_URL)
     }
+    /// Create a new Ollama client from the `OLLAMA_HOST` environment variable (the variable
+    /// used by the Ollama CLI), defaulting to `http://localhost:11434` if it is not set.
+    pub fn from_env() -> Self {
+        match std::env::var("OLLAMA_HOST") {
+            Ok(host) if host.starts_with("http://") || host.starts_with("https://") => {
+                Self::from_url(&host)
+            }
+            Ok(host) => Self::from_url(&format!("http://{host}")),
+            Err(_) => Self::new(),
+        }
+    }
     pub fn from_url(base_url: &str) -> Self {
         Self {
             base_url: base_url.to_owned(),
-            http_client: reqwest::Client::builder()
-                .build()
-                .expect("Ollama reqwest client should build"),
+            http_client: HttpClient::new(reqwest::header::HeaderMap::new()),
         }
     }
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates).
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: crate::providers::timeouts::Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path);
         self.http_client.post(url)
     }
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path);
+        self.http_client.get(url)
+    }
+    /// List the models available locally on the Ollama server
+    pub async fn list_models(&self) -> Result<Vec<LocalModel>, OllamaError> {
+        let response = self.get("api/tags").send().await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<ListModelsResponse>>().await? {
+                ApiResponse::Ok(response) => Ok(response.models),
+                ApiResponse::Err(err) => Err(OllamaError::ProviderError(err.message)),
+            }
+        } else {
+            Err(OllamaError::ProviderError(response.text().await?))
+        }
+    }
     pub fn embedding_model(&self, model: &str) -> EmbeddingModel {
         EmbeddingModel::new(self.clone(), model, 0)
     }
@@ -112,8 +171,27 @@ impl Client {
     }
 }
 
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 // ---------- API Error and Response Structures ----------
 
+#[derive(Debug, thiserror::Error)]
+pub enum OllamaError {
+    /// Error returned by the Ollama server
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
+
+    /// Http error (e.g.: connection error, timeout, etc.)
+    #[error("HttpError: {0}")]
+    HttpError(#[from] reqwest::Error),
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -126,6 +204,37 @@ enum ApiResponse<T> {
     Err(ApiErrorResponse),
 }
 
+// ---------- Model Listing API ----------
+
+#[derive(Debug, Deserialize)]
+struct ListModelsResponse {
+    models: Vec<LocalModel>,
+}
+
+/// A model available locally on the Ollama server (see [Client::list_models])
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocalModel {
+    /// Name of the model (e.g.: `llama3.2:latest`), to be used to create completion and embedding models
+    pub name: String,
+    pub modified_at: String,
+    /// Size of the model in bytes
+    pub size: u64,
+    pub digest: String,
+    #[serde(default)]
+    pub details: Option<LocalModelDetails>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocalModelDetails {
+    pub format: Option<String>,
+    pub family: Option<String>,
+    pub families: Option<Vec<String>>,
+    /// Number of parameters of the model (e.g.: `3.2B`)
+    pub parameter_size: Option<String>,
+    /// Quantization of the model (e.g.: `Q4_K_M`)
+    pub quantization_level: Option<String>,
+}
+
 // ---------- Embedding API ----------
 
 pub const ALL_MINILM: &str = "all-minilm";
@@ -179,6 +288,10 @@ impl EmbeddingModel {
 
 impl embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 1024;
+
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
     fn ndims(&self) -> usize {
         self.ndims
     }
@@ -188,22 +301,14 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         documents: impl IntoIterator<Item = String>,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let docs: Vec<String> = documents.into_iter().collect();
+        crate::rate_limit::acquire_embedding(self.client.rate_limiter(), &docs).await?;
         let payload = json!({
             "model": self.model,
             "input": docs,
         });
-        let response = self
-            .client
-            .post("api/embed")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
+        let response = self.client.post("api/embed").json(&payload).send().await?;
         if response.status().is_success() {
-            let api_resp: EmbeddingResponse = response
-                .json()
-                .await
-                .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
+            let api_resp: EmbeddingResponse = response.json().await?;
             if api_resp.embeddings.len() != docs.len() {
                 return Err(EmbeddingError::ResponseError(
                     "Number of returned embeddings does not match input".into(),
@@ -212,11 +317,11 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
             Ok(api_resp
                 .embeddings
                 .into_iter()
-                .zip(docs.into_iter())
+                .zip(docs)
                 .map(|(vec, document)| embeddings::Embedding { document, vec })
                 .collect())
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -327,13 +432,31 @@ impl CompletionModel {
     ) -> Result<Value, CompletionError> {
         // Convert internal prompt into a provider Message
         let prompt: Message = completion_request.prompt_with_context().try_into()?;
+        let mut options = Map::new();
+        options.insert(
+            "temperature".to_string(),
+            completion_request.temperature.into(),
+        );
+        insert_opt(&mut options, "top_p", completion_request.top_p);
+        insert_opt(&mut options, "num_predict", completion_request.max_tokens);
+        if !completion_request.stop.is_empty() {
+            options.insert("stop".to_string(), completion_request.stop.clone().into());
+        }
+        insert_opt(&mut options, "seed", completion_request.seed);
+        insert_opt(
+            &mut options,
+            "frequency_penalty",
+            completion_request.frequency_penalty,
+        );
+        insert_opt(
+            &mut options,
+            "presence_penalty",
+            completion_request.presence_penalty,
+        );
         let options = if let Some(extra) = completion_request.additional_params {
-            json_utils::merge(
-                json!({ "temperature": completion_request.temperature }),
-                extra,
-            )
+            json_utils::merge(Value::Object(options), extra)
         } else {
-            json!({ "temperature": completion_request.temperature })
+            Value::Object(options)
         };
 
         // Chat mode: assemble full conversation history including preamble and chat history
@@ -352,6 +475,11 @@ impl CompletionModel {
             "options": options,
             "stream": false,
         });
+        match completion_request.response_format {
+            Some(ResponseFormat::JsonObject) => request_payload["format"] = json!("json"),
+            Some(ResponseFormat::JsonSchema { schema, .. }) => request_payload["format"] = schema,
+            Some(ResponseFormat::Text) | None => (),
+        }
         if !completion_request.tools.is_empty() {
             request_payload["tools"] = json!(completion_request
                 .tools
@@ -371,11 +499,22 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        serde_json::to_value(response).ok()
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request_payload = self.create_completion_request(completion_request)?;
 
         let response = self
@@ -383,30 +522,23 @@ impl completion::CompletionModel for CompletionModel {
             .post("api/chat")
             .json(&request_payload)
             .send()
-            .await
-            .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+            .await?;
         if response.status().is_success() {
-            let text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+            let text = response.text().await?;
             tracing::debug!(target: "rig", "Ollama chat response: {}", text);
-            let chat_resp: CompletionResponse = serde_json::from_str(&text)
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+            let chat_resp: CompletionResponse = serde_json::from_str(&text)?;
             let conv: completion::CompletionResponse<CompletionResponse> = chat_resp.try_into()?;
             Ok(conv)
         } else {
-            let err_text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
-            Err(CompletionError::ProviderError(err_text))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
 
 impl StreamingCompletionModel for CompletionModel {
     async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &request).await?;
+
         let mut request_payload = self.create_completion_request(request)?;
         merge_inplace(&mut request_payload, json!({"stream": true}));
 
@@ -415,15 +547,10 @@ impl StreamingCompletionModel for CompletionModel {
             .post("api/chat")
             .json(&request_payload)
             .send()
-            .await
-            .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+            .await?;
 
         if !response.status().is_success() {
-            let err_text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
-            return Err(CompletionError::ProviderError(err_text));
+            return Err(ApiError::from_response(response).await.into());
         }
 
         Ok(Box::pin(stream! {
@@ -880,4 +1007,42 @@ mod tests {
         let params = &ollama_tool.function.parameters;
         assert_eq!(params["properties"]["location"]["type"], "string");
     }
+
+    #[test]
+    fn test_list_models_response() {
+        let response = json!({
+            "models": [
+                {
+                    "name": "llama3.2:latest",
+                    "model": "llama3.2:latest",
+                    "modified_at": "2024-10-01T12:00:00.000000000+02:00",
+                    "size": 2019393189u64,
+                    "digest": "a80c4f17acd55265feec403c7aef86be0c25983ab279d83f3bcd3abbcb5b8b72",
+                    "details": {
+                        "parent_model": "",
+                        "format": "gguf",
+                        "family": "llama",
+                        "families": ["llama"],
+                        "parameter_size": "3.2B",
+                        "quantization_level": "Q4_K_M"
+                    }
+                }
+            ]
+        });
+
+        let response: ApiResponse<ListModelsResponse> = serde_json::from_value(response).unwrap();
+        let ApiResponse::Ok(response) = response else {
+            panic!("Expected a list of models");
+        };
+
+        assert_eq!(response.models.len(), 1);
+        assert_eq!(response.models[0].name, "llama3.2:latest");
+        assert_eq!(
+            response.models[0]
+                .details
+                .as_ref()
+                .and_then(|details| details.parameter_size.as_deref()),
+            Some("3.2B")
+        );
+    }
 }
@@ -1,12 +1,14 @@
 use crate::audio_generation::{
     self, AudioGenerationError, AudioGenerationRequest, AudioGenerationResponse,
 };
+use crate::json_utils;
 use crate::providers::openai::Client;
 use bytes::Bytes;
 use serde_json::json;
 
 pub const TTS_1: &str = "tts-1";
 pub const TTS_1_HD: &str = "tts-1-hd";
+pub const GPT_4O_MINI_TTS: &str = "gpt-4o-mini-tts";
 
 #[derive(Clone)]
 pub struct AudioGenerationModel {
@@ -23,6 +25,24 @@ impl AudioGenerationModel {
     }
 }
 
+impl AudioGenerationModel {
+    /// Body of a speech request. Additional parameters (e.g.: `response_format`, or
+    /// `instructions` for `gpt-4o-mini-tts`) are merged into it.
+    fn request_body(&self, request: AudioGenerationRequest) -> serde_json::Value {
+        let body = json!({
+            "model": self.model,
+            "input": request.text,
+            "voice": request.voice,
+            "speed": request.speed,
+        });
+
+        match request.additional_params {
+            Some(params) => json_utils::merge(body, params),
+            None => body,
+        }
+    }
+}
+
 impl audio_generation::AudioGenerationModel for AudioGenerationModel {
     type Response = Bytes;
 
@@ -30,17 +50,10 @@ impl audio_generation::AudioGenerationModel for AudioGenerationModel {
         &self,
         request: AudioGenerationRequest,
     ) -> Result<AudioGenerationResponse<Self::Response>, AudioGenerationError> {
-        let request = json!({
-            "model": self.model,
-            "input": request.text,
-            "voice": request.voice,
-            "speed": request.speed,
-        });
-
         let response = self
             .client
             .post("/audio/speech")
-            .json(&request)
+            .json(&self.request_body(request))
             .send()
             .await?;
 
@@ -60,3 +73,34 @@ impl audio_generation::AudioGenerationModel for AudioGenerationModel {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_body() {
+        let model = AudioGenerationModel::new(Client::new("key"), GPT_4O_MINI_TTS);
+        let request = AudioGenerationRequest {
+            text: "Hello".to_string(),
+            voice: "alloy".to_string(),
+            speed: 1.0,
+            additional_params: Some(json!({
+                "response_format": "wav",
+                "instructions": "Speak cheerfully",
+            })),
+        };
+
+        assert_eq!(
+            model.request_body(request),
+            json!({
+                "model": "gpt-4o-mini-tts",
+                "input": "Hello",
+                "voice": "alloy",
+                "speed": 1.0,
+                "response_format": "wav",
+                "instructions": "Speak cheerfully",
+            })
+        );
+    }
+}
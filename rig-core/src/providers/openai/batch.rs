@@ -0,0 +1,369 @@
+//! OpenAI Batch API: completion requests processed asynchronously (within 24 hours) at half
+//! the price of regular requests, for offline workloads (e.g.: bulk document summarization).
+//!
+//! The requests are uploaded as a JSONL file, processed by OpenAI, and their responses are
+//! downloaded once the batch is done. [CompletionModel::run_batch] does all of it, returning the
+//! results in the order of the requests like [batch](crate::completion::CompletionModel::batch).
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{completion::CompletionModel as _, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//! let model = openai.completion_model(openai::GPT_4O_MINI);
+//!
+//! let requests = documents
+//!     .iter()
+//!     .map(|document| {
+//!         model
+//!             .completion_request(format!("Summarize this document:\n{document}"))
+//!             .build()
+//!     })
+//!     .collect();
+//!
+//! // Or submit the batch, store its id, and retrieve it later with `retrieve_batch`
+//! let summaries = model.run_batch(requests, Duration::from_secs(60)).await?;
+//! ```
+use std::time::Duration;
+
+use reqwest::multipart::Part;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{ApiResponse, CompletionModel, CompletionResponse};
+use crate::completion::{self, CompletionError, CompletionRequest};
+use crate::error::ApiError;
+
+/// Endpoint of the requests of the batches
+const BATCH_ENDPOINT: &str = "/v1/chat/completions";
+
+/// Time within which OpenAI processes a batch
+pub const BATCH_COMPLETION_WINDOW: &str = "24h";
+
+/// Status of a batch job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// The input file is being validated
+    Validating,
+    /// The input file failed validation
+    Failed,
+    InProgress,
+    /// The results are being prepared
+    Finalizing,
+    Completed,
+    /// The batch was not completed within its completion window. The requests which were
+    /// completed are in the output file
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+impl BatchStatus {
+    /// Whether the batch is done (its status will not change anymore).
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            BatchStatus::Failed
+                | BatchStatus::Completed
+                | BatchStatus::Expired
+                | BatchStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BatchRequestCounts {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchError {
+    pub code: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BatchErrors {
+    #[serde(default)]
+    pub data: Vec<BatchError>,
+}
+
+/// A batch job, as returned by the Batch API.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchJob {
+    pub id: String,
+    pub status: BatchStatus,
+    pub input_file_id: String,
+    /// File of the responses of the successful requests
+    pub output_file_id: Option<String>,
+    /// File of the responses of the failed requests
+    pub error_file_id: Option<String>,
+    #[serde(default)]
+    pub request_counts: BatchRequestCounts,
+    /// Errors of the validation of the input file
+    pub errors: Option<BatchErrors>,
+}
+
+/// Results of the requests of a batch, in the order of the requests
+pub type BatchResults =
+    Vec<Result<completion::CompletionResponse<CompletionResponse>, CompletionError>>;
+
+#[derive(Debug, Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+/// A line of the output (or error) file of a batch.
+#[derive(Debug, Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    response: Option<BatchOutputResponse>,
+    error: Option<BatchError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: Value,
+}
+
+/// Id of the `index`-th request of a batch
+fn custom_id(index: usize) -> String {
+    format!("request-{index}")
+}
+
+async fn send<T: DeserializeOwned>(builder: reqwest::RequestBuilder) -> Result<T, CompletionError> {
+    let response = builder.send().await?;
+
+    if response.status().is_success() {
+        match response.json::<ApiResponse<T>>().await? {
+            ApiResponse::Ok(response) => Ok(response),
+            ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
+        }
+    } else {
+        Err(ApiError::from_response(response).await.into())
+    }
+}
+
+impl CompletionModel {
+    /// The JSONL input file of a batch of `requests`.
+    fn batch_input(&self, requests: Vec<CompletionRequest>) -> Result<String, CompletionError> {
+        requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, request)| {
+                let line = json!({
+                    "custom_id": custom_id(index),
+                    "method": "POST",
+                    "url": BATCH_ENDPOINT,
+                    "body": self.create_completion_request(request)?,
+                });
+                Ok(format!("{line}\n"))
+            })
+            .collect()
+    }
+
+    /// Upload `requests` and create a batch job processing them. The job can then be polled
+    /// with [Self::retrieve_batch] (e.g.: from another process, using the id of the job).
+    pub async fn submit_batch(
+        &self,
+        requests: Vec<CompletionRequest>,
+    ) -> Result<BatchJob, CompletionError> {
+        let input = self.batch_input(requests)?;
+
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", "batch")
+            .part(
+                "file",
+                Part::bytes(input.into_bytes()).file_name("batch.jsonl"),
+            );
+        let file: FileObject = send(self.client.post("/files").multipart(form)).await?;
+
+        let job: BatchJob = send(self.client.post("/batches").json(&json!({
+            "input_file_id": file.id,
+            "endpoint": BATCH_ENDPOINT,
+            "completion_window": BATCH_COMPLETION_WINDOW,
+        })))
+        .await?;
+
+        tracing::info!(target: "rig",
+            "OpenAI batch {} created ({} requests)",
+            job.id,
+            job.request_counts.total
+        );
+        Ok(job)
+    }
+
+    /// Retrieve the batch job with id `batch_id`.
+    pub async fn retrieve_batch(&self, batch_id: &str) -> Result<BatchJob, CompletionError> {
+        send(self.client.get(&format!("/batches/{batch_id}"))).await
+    }
+
+    /// Cancel the batch job with id `batch_id`. The requests already completed are kept in
+    /// its output file.
+    pub async fn cancel_batch(&self, batch_id: &str) -> Result<BatchJob, CompletionError> {
+        send(self.client.post(&format!("/batches/{batch_id}/cancel"))).await
+    }
+
+    /// The results of a batch job which is done, in the order of its requests. Requests which
+    /// failed, or were not processed (e.g.: the batch expired), have an error result.
+    pub async fn batch_results(&self, job: &BatchJob) -> Result<BatchResults, CompletionError> {
+        if job.status == BatchStatus::Failed {
+            let errors = job
+                .errors
+                .iter()
+                .flat_map(|errors| errors.data.iter())
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>();
+            return Err(CompletionError::ProviderError(format!(
+                "Batch {} failed: {}",
+                job.id,
+                errors.join("; ")
+            )));
+        }
+
+        let mut results = (0..job.request_counts.total)
+            .map(|_| {
+                Err(CompletionError::ProviderError(format!(
+                    "Request not processed by batch {} ({:?})",
+                    job.id, job.status
+                )))
+            })
+            .collect::<Vec<_>>();
+
+        for file_id in job.output_file_id.iter().chain(job.error_file_id.iter()) {
+            let response = self
+                .client
+                .get(&format!("/files/{file_id}/content"))
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(ApiError::from_response(response).await.into());
+            }
+            parse_batch_output(&response.text().await?, &mut results)?;
+        }
+
+        Ok(results)
+    }
+
+    /// Submit a batch of `requests`, poll it every `poll_interval` until it is done, and return
+    /// its results in the order of the requests (see [Self::batch_results]).
+    pub async fn run_batch(
+        &self,
+        requests: Vec<CompletionRequest>,
+        poll_interval: Duration,
+    ) -> Result<BatchResults, CompletionError> {
+        let mut job = self.submit_batch(requests).await?;
+        while !job.status.is_done() {
+            futures_timer::Delay::new(poll_interval).await;
+            job = self.retrieve_batch(&job.id).await?;
+            tracing::debug!(target: "rig",
+                "OpenAI batch {}: {:?} ({}/{} completed, {} failed)",
+                job.id,
+                job.status,
+                job.request_counts.completed,
+                job.request_counts.total,
+                job.request_counts.failed
+            );
+        }
+
+        self.batch_results(&job).await
+    }
+}
+
+/// Parse the lines of an output (or error) file of a batch into `results`, indexed by the
+/// custom ids of the requests.
+fn parse_batch_output(output: &str, results: &mut BatchResults) -> Result<(), CompletionError> {
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let line: BatchOutputLine = serde_json::from_str(line)?;
+        let Some(index) = line
+            .custom_id
+            .strip_prefix("request-")
+            .and_then(|index| index.parse::<usize>().ok())
+        else {
+            tracing::warn!(target: "rig", "Unknown batch request id {}", line.custom_id);
+            continue;
+        };
+
+        let result = match (line.response, line.error) {
+            (Some(response), _) if (200..300).contains(&response.status_code) => {
+                serde_json::from_value::<CompletionResponse>(response.body)
+                    .map_err(CompletionError::from)
+                    .and_then(|response| response.try_into())
+            }
+            (Some(response), _) => {
+                Err(ApiError::new(Some(response.status_code), &response.body.to_string()).into())
+            }
+            (None, Some(error)) => Err(CompletionError::ProviderError(error.message)),
+            (None, None) => Err(CompletionError::ResponseError(
+                "Batch output contained neither a response nor an error".to_string(),
+            )),
+        };
+
+        if index >= results.len() {
+            results.resize_with(index + 1, || {
+                Err(CompletionError::ResponseError(
+                    "Request missing from the batch output".to_string(),
+                ))
+            });
+        }
+        results[index] = result;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        completion::CompletionModel as _,
+        message::AssistantContent,
+        providers::openai::{Client, GPT_4O_MINI},
+    };
+
+    #[test]
+    fn test_batch_input() {
+        let model = Client::new("key").completion_model(GPT_4O_MINI);
+        let requests = vec![
+            model.completion_request("Hello").build(),
+            model.completion_request("World").build(),
+        ];
+
+        let input = model.batch_input(requests).unwrap();
+        let lines = input
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["custom_id"], "request-1");
+        assert_eq!(lines[1]["url"], "/v1/chat/completions");
+        assert_eq!(lines[1]["body"]["model"], GPT_4O_MINI);
+    }
+
+    #[test]
+    fn test_parse_batch_output() {
+        let output = r#"{"id": "batch_req_2", "custom_id": "request-2", "response": {"status_code": 200, "request_id": "req_2", "body": {"id": "chatcmpl-2", "object": "chat.completion", "created": 1711652795, "model": "gpt-4o-mini", "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello"}, "finish_reason": "stop"}], "usage": {"prompt_tokens": 22, "completion_tokens": 2, "total_tokens": 24}}}, "error": null}
+{"id": "batch_req_0", "custom_id": "request-0", "response": {"status_code": 400, "request_id": "req_0", "body": {"error": {"message": "Invalid model", "type": "invalid_request_error"}}}, "error": null}
+"#;
+        let mut results = (0..3)
+            .map(|_| Err(CompletionError::ProviderError("Not processed".to_string())))
+            .collect::<Vec<_>>();
+
+        parse_batch_output(output, &mut results).unwrap();
+
+        assert!(
+            matches!(&results[0], Err(CompletionError::ApiError(error)) if error.message == "Invalid model" && error.status == Some(400))
+        );
+        assert!(
+            matches!(&results[1], Err(CompletionError::ProviderError(message)) if message == "Not processed")
+        );
+        match &results[2].as_ref().unwrap().choice.first() {
+            AssistantContent::Text(text) => assert_eq!(text.text, "Hello"),
+            _ => panic!("Expected text"),
+        }
+    }
+}
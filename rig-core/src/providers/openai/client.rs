@@ -1,29 +1,106 @@
 #[cfg(feature = "audio")]
 use super::audio_generation::AudioGenerationModel;
-use super::completion::CompletionModel;
+use super::completion::{CompletionModel, Message};
 use super::embedding::{
     EmbeddingModel, TEXT_EMBEDDING_3_LARGE, TEXT_EMBEDDING_3_SMALL, TEXT_EMBEDDING_ADA_002,
 };
 
 #[cfg(feature = "image")]
 use super::image_generation::ImageGenerationModel;
+use super::moderation::ModerationModel;
 use super::transcription::TranscriptionModel;
 use crate::agent::AgentBuilder;
+use crate::completion::TokenUsage;
 use crate::embeddings::EmbeddingsBuilder;
 use crate::extractor::ExtractorBuilder;
+use crate::providers::http_client::HttpClient;
+use crate::providers::timeouts::Timeouts;
 
 use crate::Embed;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 // ================================================================
 // Main OpenAI Client
 // ================================================================
 const OPENAI_API_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Builder of an OpenAI client, to configure the base URL, the timeouts and the HTTP client of the
+/// client.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use rig::providers::openai::ClientBuilder;
+///
+/// // Short connect timeout, generous read timeout for streaming completions
+/// let openai = ClientBuilder::new("your-open-ai-api-key")
+///     .connect_timeout(Duration::from_secs(5))
+///     .read_timeout(Duration::from_secs(600))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct ClientBuilder<'a> {
+    api_key: &'a str,
+    base_url: &'a str,
+    timeouts: Timeouts,
+    http_client: Option<reqwest::Client>,
+}
+
+impl<'a> ClientBuilder<'a> {
+    pub fn new(api_key: &'a str) -> Self {
+        Self {
+            api_key,
+            base_url: OPENAI_API_BASE_URL,
+            timeouts: Timeouts::default(),
+            http_client: None,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: &'a str) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Set the time allowed to establish a connection (default: [DEFAULT_CONNECT_TIMEOUT](crate::providers::timeouts::DEFAULT_CONNECT_TIMEOUT)).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Set the time allowed between two reads of a response (default: none).
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /// Set both timeouts (`None` disables a timeout).
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The timeouts of the builder do not apply to `http_client`.
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    pub fn build(self) -> Client {
+        let client = Client::from_url_with_timeouts(self.api_key, self.base_url, self.timeouts);
+        match self.http_client {
+            Some(http_client) => client.with_http_client(http_client),
+            None => client,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Client {
     base_url: String,
-    http_client: reqwest::Client,
+    http_client: HttpClient,
 }
 
 impl Client {
@@ -34,10 +111,14 @@ impl Client {
 
     /// Create a new OpenAI client with the given API key and base API URL.
     pub fn from_url(api_key: &str, base_url: &str) -> Self {
+        Self::from_url_with_timeouts(api_key, base_url, Timeouts::default())
+    }
+
+    fn from_url_with_timeouts(api_key: &str, base_url: &str, timeouts: Timeouts) -> Self {
         Self {
             base_url: base_url.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
+            http_client: HttpClient::with_timeouts(
+                {
                     let mut headers = reqwest::header::HeaderMap::new();
                     headers.insert(
                         "Authorization",
@@ -46,9 +127,9 @@ impl Client {
                             .expect("Bearer token should parse"),
                     );
                     headers
-                })
-                .build()
-                .expect("OpenAI reqwest client should build"),
+                },
+                timeouts,
+            ),
         }
     }
 
@@ -59,11 +140,67 @@ impl Client {
         Self::new(&api_key)
     }
 
+    /// Create a client for Azure OpenAI (see [azure::Client](crate::providers::azure::Client)),
+    /// which serves OpenAI models from deployments of an Azure resource. Models are then
+    /// created from deployment names instead of model names.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::openai;
+    ///
+    /// let azure = openai::Client::azure(
+    ///     "YOUR_API_KEY",
+    ///     "2024-10-21",
+    ///     "https://your-resource-name.openai.azure.com",
+    /// );
+    ///
+    /// let gpt4o = azure.completion_model("your-gpt-4o-deployment");
+    /// ```
+    pub fn azure(
+        api_key: &str,
+        api_version: &str,
+        azure_endpoint: &str,
+    ) -> crate::providers::azure::Client {
+        crate::providers::azure::Client::from_api_key(api_key, api_version, azure_endpoint)
+    }
+
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
     pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
         self.http_client.post(url)
     }
 
+    pub(crate) fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
+        self.http_client.get(url)
+    }
+
     /// Create an embedding model with the given name.
     /// Note: default embedding dimension of 0 will be used if model is not known.
     /// If this is the case, it's better to use function `embedding_model_with_ndims`
@@ -171,12 +308,27 @@ impl Client {
     /// // Initialize the OpenAI client
     /// let openai = Client::new("your-open-ai-api-key");
     ///
-    /// let gpt4 = openai.transcription_model(openai::WHISPER_1);
+    /// let whisper = openai.transcription_model(openai::WHISPER_1);
     /// ```
     pub fn transcription_model(&self, model: &str) -> TranscriptionModel {
         TranscriptionModel::new(self.clone(), model)
     }
 
+    /// Create a moderation model with the given name (see [crate::moderation]).
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::openai::{Client, self};
+    ///
+    /// // Initialize the OpenAI client
+    /// let openai = Client::new("your-open-ai-api-key");
+    ///
+    /// let moderation = openai.moderation_model(openai::OMNI_MODERATION_LATEST);
+    /// ```
+    pub fn moderation_model(&self, model: &str) -> ModerationModel {
+        ModerationModel::new(self.clone(), model)
+    }
+
     /// Create an image generation model with the given name.
     ///
     /// # Example
@@ -193,7 +345,7 @@ impl Client {
         ImageGenerationModel::new(self.clone(), model)
     }
 
-    /// Create an image generation model with the given name.
+    /// Create an audio generation (text-to-speech) model with the given name.
     ///
     /// # Example
     /// ```
@@ -202,20 +354,117 @@ impl Client {
     /// // Initialize the OpenAI client
     /// let openai = Client::new("your-open-ai-api-key");
     ///
-    /// let gpt4 = openai.audio_generation_model(openai::TTS_1);
+    /// let tts = openai.audio_generation_model(openai::TTS_1);
     /// ```
     #[cfg(feature = "audio")]
     pub fn audio_generation_model(&self, model: &str) -> AudioGenerationModel {
         AudioGenerationModel::new(self.clone(), model)
     }
+
+    /// Count the number of prompt tokens of a list of chat messages sent to `model`, including
+    /// the per-message and per-conversation overhead added by OpenAI's chat format
+    /// (see [OpenAI's cookbook](https://cookbook.openai.com/examples/how_to_count_tokens_with_tiktoken)).
+    ///
+    /// Note: the count is computed locally (OpenAI does not provide a token counting endpoint)
+    /// and the number of tokens of the texts is approximated from their number of characters
+    /// (about 4 characters per token). Use [Client::count_tokens_with] to count the tokens of
+    /// the texts with an exact tokenizer (e.g.: tiktoken).
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::openai::{Client, Message, self};
+    ///
+    /// let openai = Client::new("your-open-ai-api-key");
+    ///
+    /// let tokens = openai.count_tokens(openai::GPT_4O, &[
+    ///     Message::system("You are a helpful assistant."),
+    ///     Message::system("Answer in French."),
+    /// ]);
+    /// ```
+    pub fn count_tokens(&self, model: &str, messages: &[Message]) -> usize {
+        self.count_tokens_with(model, messages, crate::completion::tokens::estimate_tokens)
+    }
+
+    /// Same as [Client::count_tokens] but the number of tokens of each text is computed by
+    /// `tokenizer`.
+    pub fn count_tokens_with(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tokenizer: impl Fn(&str) -> usize,
+    ) -> usize {
+        // Overhead documented by OpenAI: every message is wrapped in
+        // `<|start|>{role/name}\n{content}<|end|>\n` and every reply is primed with
+        // `<|start|>assistant<|message|>`
+        let (tokens_per_message, tokens_per_name) = match model {
+            "gpt-3.5-turbo-0301" => (4, -1),
+            _ => (3, 1),
+        };
+        const TOKENS_PER_REPLY: i64 = 3;
+
+        let tokens = messages
+            .iter()
+            .map(|message| {
+                let value = serde_json::to_value(message).unwrap_or_default();
+                tokens_per_message + count_value_tokens(&value, tokens_per_name, &tokenizer)
+            })
+            .sum::<i64>();
+
+        (tokens + TOKENS_PER_REPLY).max(0) as usize
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl crate::completion::CompletionClient for Client {
+    type Model = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+/// Count the tokens of the string values of a serialized message, adding `tokens_per_name`
+/// for each `name` field. Content type tags (e.g.: `"type": "text"`) are not sent as text
+/// to the model and are skipped, as are images and audio (which are not billed by text tokens).
+fn count_value_tokens(
+    value: &serde_json::Value,
+    tokens_per_name: i64,
+    tokenizer: &impl Fn(&str) -> usize,
+) -> i64 {
+    match value {
+        serde_json::Value::String(text) => tokenizer(text) as i64,
+        serde_json::Value::Array(values) => values
+            .iter()
+            .map(|value| count_value_tokens(value, tokens_per_name, tokenizer))
+            .sum(),
+        serde_json::Value::Object(map) => map
+            .iter()
+            .filter(|(key, _)| !matches!(key.as_str(), "type" | "image_url" | "input_audio"))
+            .map(|(key, value)| {
+                let name_tokens = if key == "name" { tokens_per_name } else { 0 };
+                name_tokens + count_value_tokens(value, tokens_per_name, tokenizer)
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Usage {
     pub prompt_tokens: usize,
     pub total_tokens: usize,
 }
 
+impl From<&Usage> for TokenUsage {
+    fn from(usage: &Usage) -> Self {
+        TokenUsage {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: (usage.total_tokens - usage.prompt_tokens) as u64,
+            total_tokens: usage.total_tokens as u64,
+            ..Default::default()
+        }
+    }
+}
+
 impl std::fmt::Display for Usage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -241,11 +490,36 @@ pub(crate) enum ApiResponse<T> {
 mod tests {
     use crate::message::ImageDetail;
     use crate::providers::openai::{
-        AssistantContent, Function, ImageUrl, Message, ToolCall, ToolType, UserContent,
+        AssistantContent, Function, ImageUrl, Message, ToolCall, ToolType, UserContent, GPT_4O,
     };
     use crate::{message, OneOrMany};
     use serde_path_to_error::deserialize;
 
+    #[test]
+    fn test_count_tokens() {
+        let client = super::Client::new("key");
+        let messages = vec![
+            Message::system("Be nice."),
+            Message::User {
+                content: OneOrMany::one(UserContent::Text {
+                    text: "Hello there".to_string(),
+                }),
+                name: Some("bob".to_string()),
+            },
+        ];
+        let tokenizer = |text: &str| text.split_whitespace().count();
+
+        // system: 3 + "system" + "Be nice." = 6
+        // user: 3 + "user" + "Hello there" + "bob" + 1 (name) = 8
+        // reply: 3
+        assert_eq!(client.count_tokens_with(GPT_4O, &messages, tokenizer), 17);
+        assert_eq!(
+            client.count_tokens_with("gpt-3.5-turbo-0301", &messages, tokenizer),
+            // 1 more token per message, 1 token less per name
+            17 + 2 - 2
+        );
+    }
+
     #[test]
     fn test_deserialize_message() {
         let assistant_message_json = r#"
@@ -483,6 +757,39 @@ mod tests {
         assert_eq!(original_assistant_message, assistant_message);
     }
 
+    #[test]
+    fn test_image_conversion() {
+        let message = message::Message::user_with_images(
+            "What is in these images?",
+            [
+                message::Image::url("https://example.com/image.jpg").detail(ImageDetail::Low),
+                message::Image::base64("iVBORw0KGgo=", message::ImageMediaType::PNG),
+            ],
+        );
+
+        let converted: Vec<Message> = message.try_into().unwrap();
+        let Message::User { content, .. } = converted[0].clone() else {
+            panic!("Expected user message");
+        };
+
+        let urls = content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::Image { image_url } => {
+                    Some((image_url.url.as_str(), image_url.detail.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                ("https://example.com/image.jpg", ImageDetail::Low),
+                ("data:image/png;base64,iVBORw0KGgo=", ImageDetail::Auto),
+            ]
+        );
+    }
+
     #[test]
     fn test_message_from_message_conversion() {
         let user_message = Message::User {
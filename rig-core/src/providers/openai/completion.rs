@@ -3,12 +3,17 @@
 // ================================================================
 
 use super::{ApiErrorResponse, ApiResponse, Client, Usage};
-use crate::completion::{CompletionError, CompletionRequest};
+use crate::completion::provider::{CompletionProvider, RequestOptions};
+use crate::completion::{
+    cost::ModelPricing, CompletionError, CompletionRequest, ContextTemplate, TokenUsage,
+};
+use crate::error::ApiError;
 use crate::message::{AudioMediaType, ImageDetail};
 use crate::one_or_many::string_or_one_or_many;
+use crate::providers::openai_compat;
 use crate::{completion, json_utils, message, OneOrMany};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::Value;
 use std::convert::Infallible;
 use std::str::FromStr;
 
@@ -69,11 +74,38 @@ pub const GPT_35_TURBO_1106: &str = "gpt-3.5-turbo-1106";
 /// `gpt-3.5-turbo-instruct` completion model
 pub const GPT_35_TURBO_INSTRUCT: &str = "gpt-3.5-turbo-instruct";
 
-#[derive(Debug, Deserialize)]
+/// Pricing of OpenAI completion models (in US dollars per million tokens), by model name prefix.
+/// More specific prefixes come first (see [ModelPricing::lookup]).
+pub const PRICING: &[(&str, ModelPricing)] = &[
+    (O3_MINI, ModelPricing::new(1.1, 4.4)),
+    (O1_PREVIEW, ModelPricing::new(15.0, 60.0)),
+    (O1_MINI, ModelPricing::new(1.1, 4.4)),
+    (O1, ModelPricing::new(15.0, 60.0)),
+    (GPT_4_5_PREVIEW, ModelPricing::new(75.0, 150.0)),
+    (GPT_4O_MINI, ModelPricing::new(0.15, 0.6)),
+    (GPT_4O_2024_05_13, ModelPricing::new(5.0, 15.0)),
+    (GPT_4O, ModelPricing::new(2.5, 10.0)),
+    (GPT_4_TURBO, ModelPricing::new(10.0, 30.0)),
+    (GPT_4_0125_PREVIEW, ModelPricing::new(10.0, 30.0)),
+    (GPT_4_1106_PREVIEW, ModelPricing::new(10.0, 30.0)),
+    (GPT_4_VISION_PREVIEW, ModelPricing::new(10.0, 30.0)),
+    (GPT_4_1106_VISION_PREVIEW, ModelPricing::new(10.0, 30.0)),
+    (GPT_4_32K, ModelPricing::new(60.0, 120.0)),
+    (GPT_4, ModelPricing::new(30.0, 60.0)),
+    (GPT_35_TURBO_INSTRUCT, ModelPricing::new(1.5, 2.0)),
+    (GPT_35_TURBO, ModelPricing::new(0.5, 1.5)),
+];
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CompletionResponse {
+    // Some OpenAI-compatible providers omit these fields
+    #[serde(default)]
     pub id: String,
+    #[serde(default)]
     pub object: String,
+    #[serde(default)]
     pub created: u64,
+    #[serde(default)]
     pub model: String,
     pub system_fingerprint: Option<String>,
     pub choices: Vec<Choice>,
@@ -345,14 +377,9 @@ impl TryFrom<message::Message> for Vec<Message> {
                                 content,
                             }) => Ok::<_, message::MessageError>(Message::ToolResult {
                                 tool_call_id: id,
-                                content: content.try_map(|content| match content {
-                                    message::ToolResultContent::Text(message::Text { text }) => {
-                                        Ok(text.into())
-                                    }
-                                    _ => Err(message::MessageError::ConversionError(
-                                        "Tool result content does not support non-text".into(),
-                                    )),
-                                })?,
+                                // Tool messages can only be text, images are replaced by a
+                                //  placeholder
+                                content: content.map(|content| content.into_text().into()),
                             }),
                             _ => unreachable!(),
                         })
@@ -367,12 +394,10 @@ impl TryFrom<message::Message> for Vec<Message> {
                             message::UserContent::Text(message::Text { text }) => {
                                 UserContent::Text { text }
                             }
-                            message::UserContent::Image(message::Image {
-                                data, detail, ..
-                            }) => UserContent::Image {
+                            message::UserContent::Image(image) => UserContent::Image {
                                 image_url: ImageUrl {
-                                    url: data,
-                                    detail: detail.unwrap_or_default(),
+                                    url: image.to_url(),
+                                    detail: image.detail.unwrap_or_default(),
                                 },
                             },
                             message::UserContent::Document(message::Document { data, .. }) => {
@@ -520,11 +545,8 @@ impl From<UserContent> for message::UserContent {
     fn from(content: UserContent) -> Self {
         match content {
             UserContent::Text { text } => message::UserContent::text(text),
-            UserContent::Image { image_url } => message::UserContent::image(
-                image_url.url,
-                Some(message::ContentFormat::default()),
-                None,
-                Some(image_url.detail),
+            UserContent::Image { image_url } => message::UserContent::Image(
+                message::Image::url(image_url.url).detail(image_url.detail),
             ),
             UserContent::Audio { input_audio } => message::UserContent::audio(
                 input_audio.data,
@@ -605,83 +627,58 @@ impl CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<Value, CompletionError> {
-        // Add preamble to chat history (if available)
-        let mut full_history: Vec<Message> = match &completion_request.preamble {
-            Some(preamble) => vec![Message::system(preamble)],
-            None => vec![],
-        };
-
-        // Convert prompt to user message
-        let prompt: Vec<Message> = completion_request.prompt_with_context().try_into()?;
-
-        // Convert existing chat history
-        let chat_history: Vec<Message> = completion_request
-            .chat_history
-            .into_iter()
-            .map(|message| message.try_into())
-            .collect::<Result<Vec<Vec<Message>>, _>>()?
-            .into_iter()
-            .flatten()
-            .collect();
-
-        // Combine all messages into a single history
-        full_history.extend(chat_history);
-        full_history.extend(prompt);
-
-        let request = if completion_request.tools.is_empty() {
-            json!({
-                "model": self.model,
-                "messages": full_history,
-
-            })
-        } else {
-            json!({
-                "model": self.model,
-                "messages": full_history,
-                "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
-            })
-        };
-
-        // only include temperature if it exists
-        // because some models don't support temperature
-        let request = if let Some(temperature) = completion_request.temperature {
-            json_utils::merge(
-                request,
-                json!({
-                    "temperature": temperature,
-                }),
-            )
-        } else {
-            request
-        };
-
-        let request = if let Some(params) = completion_request.additional_params {
-            json_utils::merge(request, params)
-        } else {
-            request
-        };
+        let store = completion_request.store;
+        let metadata = completion_request.metadata.clone();
+        let mut request = openai_compat::completion_request_body(&self.model, completion_request)?;
+
+        // OpenAI deprecated `max_tokens` in favor of `max_completion_tokens`, the only one
+        // accepted by its reasoning models (e.g.: o1, o3-mini)
+        if let Some(params) = request.as_object_mut() {
+            if let Some(max_tokens) = params.remove("max_tokens") {
+                params.entry("max_completion_tokens").or_insert(max_tokens);
+            }
+            if let Some(store) = store {
+                params.insert("store".into(), store.into());
+            }
+            if let Some(metadata) = metadata {
+                params.insert("metadata".into(), serde_json::json!(metadata));
+            }
+        }
 
         Ok(request)
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
+/// Header used by OpenAI to deduplicate retries of the same request
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+impl CompletionProvider for CompletionModel {
+    type Request = Value;
     type Response = CompletionResponse;
 
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn create_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
-        let request = self.create_completion_request(completion_request)?;
+    ) -> Result<Self::Request, CompletionError> {
+        self.create_completion_request(completion_request)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn send_request(
+        &self,
+        request: Self::Request,
+        options: RequestOptions,
+    ) -> Result<Self::Response, CompletionError> {
+        let mut builder = self.client.post("/chat/completions").json(&request);
+        if let Some(idempotency_key) = options.idempotency_key {
+            builder = builder.header(IDEMPOTENCY_KEY_HEADER, idempotency_key);
+        }
 
-        let response = self
-            .client
-            .post("/chat/completions")
-            .json(&request)
-            .send()
-            .await?;
+        let response = builder.send().await?;
 
         if response.status().is_success() {
             let t = response.text().await?;
@@ -693,12 +690,101 @@ impl completion::CompletionModel for CompletionModel {
                         "OpenAI completion token usage: {:?}",
                         response.usage.clone().map(|usage| format!("{usage}")).unwrap_or("N/A".to_string())
                     );
-                    response.try_into()
+                    Ok(response)
                 }
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
+
+    fn parse_response(
+        &self,
+        response: Self::Response,
+    ) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+        response.try_into()
+    }
+
+    fn context_template(&self) -> ContextTemplate {
+        ContextTemplate::Markdown
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        openai_compat::token_usage(response)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<Value> {
+        serde_json::to_value(response).ok()
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        ModelPricing::lookup(PRICING, &self.model)
+    }
+
+    fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.client.rate_limiter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::completion::CompletionModel as _;
+
+    #[test]
+    fn test_max_completion_tokens() {
+        let model = CompletionModel::new(Client::new("key"), O3_MINI);
+        let request = model.completion_request("Hello").max_tokens(100).build();
+
+        let body = model.create_completion_request(request).unwrap();
+        assert_eq!(body["max_completion_tokens"], json!(100));
+        assert!(body.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn test_store_and_metadata() {
+        let model = CompletionModel::new(Client::new("key"), GPT_4O);
+        let request = model
+            .completion_request("Hello")
+            .store(true)
+            .metadata([("user".to_string(), "alice".to_string())].into())
+            .build();
+
+        let body = model.create_completion_request(request).unwrap();
+        assert_eq!(body["store"], json!(true));
+        assert_eq!(body["metadata"], json!({"user": "alice"}));
+
+        let request = model.completion_request("Hello").build();
+        let body = model.create_completion_request(request).unwrap();
+        assert!(body.get("store").is_none());
+        assert!(body.get("metadata").is_none());
+    }
+
+    #[test]
+    fn test_image_tool_result_placeholder() {
+        let message = message::Message::User {
+            content: OneOrMany::one(message::UserContent::tool_result(
+                "call_1",
+                OneOrMany::one(message::ToolResultContent::from_tool_output(
+                    "data:image/png;base64,iVBORw0KGgo=",
+                )),
+            )),
+        };
+
+        let messages: Vec<Message> = message.try_into().unwrap();
+        assert_eq!(
+            serde_json::to_value(&messages).unwrap(),
+            json!([{
+                "role": "tool",
+                "tool_call_id": "call_1",
+                "content": [{
+                    "type": "text",
+                    "text": "[The tool returned an image (image/png) which cannot be shown to this model]"
+                }]
+            }])
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use super::{ApiErrorResponse, ApiResponse, Client, Usage};
 use crate::embeddings;
 use crate::embeddings::EmbeddingError;
+use crate::error::ApiError;
 use serde::Deserialize;
 use serde_json::json;
 
@@ -14,6 +15,13 @@ pub const TEXT_EMBEDDING_3_SMALL: &str = "text-embedding-3-small";
 /// `text-embedding-ada-002` embedding model
 pub const TEXT_EMBEDDING_ADA_002: &str = "text-embedding-ada-002";
 
+/// Price (in US dollars per million tokens) of the OpenAI embedding models.
+const EMBEDDING_PRICES: &[(&str, f64)] = &[
+    (TEXT_EMBEDDING_3_LARGE, 0.13),
+    (TEXT_EMBEDDING_3_SMALL, 0.02),
+    (TEXT_EMBEDDING_ADA_002, 0.10),
+];
+
 #[derive(Debug, Deserialize)]
 pub struct EmbeddingResponse {
     pub object: String,
@@ -49,29 +57,48 @@ pub struct EmbeddingModel {
     client: Client,
     pub model: String,
     ndims: usize,
+    /// Number of dimensions the embeddings are shortened to by OpenAI, if any
+    dimensions: Option<usize>,
 }
 
 impl embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 1024;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     fn ndims(&self) -> usize {
         self.ndims
     }
 
+    fn price_per_million_tokens(&self) -> Option<f64> {
+        EMBEDDING_PRICES
+            .iter()
+            .find(|(model, _)| *model == self.model)
+            .map(|(_, price)| *price)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
         documents: impl IntoIterator<Item = String>,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
+        crate::rate_limit::acquire_embedding(self.client.rate_limiter(), &documents).await?;
+
+        let mut request = json!({
+            "model": self.model,
+            "input": documents,
+        });
+        if let Some(dimensions) = self.dimensions {
+            request["dimensions"] = json!(dimensions);
+        }
 
         let response = self
             .client
             .post("/embeddings")
-            .json(&json!({
-                "model": self.model,
-                "input": documents,
-            }))
+            .json(&request)
             .send()
             .await?;
 
@@ -92,7 +119,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                     Ok(response
                         .data
                         .into_iter()
-                        .zip(documents.into_iter())
+                        .zip(documents)
                         .map(|(embedding, document)| embeddings::Embedding {
                             document,
                             vec: embedding.embedding,
@@ -102,7 +129,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                 ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
@@ -113,6 +140,20 @@ impl EmbeddingModel {
             client,
             model: model.to_string(),
             ndims,
+            dimensions: None,
         }
     }
+
+    /// Shorten the embeddings to `ndims` dimensions (only supported by the `text-embedding-3`
+    /// models, which are trained so that shortened embeddings keep their meaning).
+    ///
+    /// # Example
+    /// ```rust
+    /// let model = openai.embedding_model(openai::TEXT_EMBEDDING_3_LARGE).dimensions(256);
+    /// ```
+    pub fn dimensions(mut self, ndims: usize) -> Self {
+        self.ndims = ndims;
+        self.dimensions = Some(ndims);
+        self
+    }
 }
@@ -1,5 +1,6 @@
 use crate::image_generation;
-use crate::image_generation::{ImageGenerationError, ImageGenerationRequest};
+use crate::image_generation::{ImageGenerationError, ImageGenerationRequest, ImageResponseFormat};
+use crate::json_utils;
 use crate::providers::openai::{ApiResponse, Client};
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
@@ -11,10 +12,17 @@ use serde_json::json;
 // ================================================================
 pub const DALL_E_2: &str = "dall-e-2";
 pub const DALL_E_3: &str = "dall-e-3";
+pub const GPT_IMAGE_1: &str = "gpt-image-1";
 
 #[derive(Debug, Deserialize)]
 pub struct ImageGenerationData {
-    pub b64_json: String,
+    #[serde(default)]
+    pub b64_json: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Prompt used to generate the image, if it was revised by the model (DALL·E 3)
+    #[serde(default)]
+    pub revised_prompt: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,14 +37,27 @@ impl TryFrom<ImageGenerationResponse>
     type Error = ImageGenerationError;
 
     fn try_from(value: ImageGenerationResponse) -> Result<Self, Self::Error> {
-        let b64_json = value.data[0].b64_json.clone();
+        let data = value.data.first().ok_or_else(|| {
+            ImageGenerationError::ResponseError("Response contained no image".to_owned())
+        })?;
 
-        let bytes = BASE64_STANDARD
-            .decode(&b64_json)
-            .expect("Failed to decode b64");
+        let image = match &data.b64_json {
+            Some(b64_json) => BASE64_STANDARD.decode(b64_json).map_err(|e| {
+                ImageGenerationError::ResponseError(format!("Invalid base64 image: {e}"))
+            })?,
+            None => vec![],
+        };
+        let url = data.url.clone();
+
+        if image.is_empty() && url.is_none() {
+            return Err(ImageGenerationError::ResponseError(
+                "Response contained neither image data nor URL".to_owned(),
+            ));
+        }
 
         Ok(image_generation::ImageGenerationResponse {
-            image: bytes,
+            image,
+            url,
             response: value,
         })
     }
@@ -58,6 +79,31 @@ impl ImageGenerationModel {
     }
 }
 
+impl ImageGenerationModel {
+    /// Body of an image generation request. Additional parameters (e.g.: `quality`, `style`
+    /// or `background`) are merged into it.
+    fn request_body(&self, request: ImageGenerationRequest) -> serde_json::Value {
+        let mut body = json!({
+            "model": self.model,
+            "prompt": request.prompt,
+            "size": format!("{}x{}", request.width, request.height),
+        });
+
+        // gpt-image models always return base64 encoded images and reject `response_format`
+        if !self.model.starts_with("gpt-image") {
+            body["response_format"] = match request.response_format {
+                ImageResponseFormat::Bytes => json!("b64_json"),
+                ImageResponseFormat::Url => json!("url"),
+            };
+        }
+
+        match request.additional_params {
+            Some(params) => json_utils::merge(body, params),
+            None => body,
+        }
+    }
+}
+
 impl image_generation::ImageGenerationModel for ImageGenerationModel {
     type Response = ImageGenerationResponse;
 
@@ -66,17 +112,10 @@ impl image_generation::ImageGenerationModel for ImageGenerationModel {
         generation_request: ImageGenerationRequest,
     ) -> Result<image_generation::ImageGenerationResponse<Self::Response>, ImageGenerationError>
     {
-        let request = json!({
-            "model": self.model,
-            "prompt": generation_request.prompt,
-            "size": format!("{}x{}", generation_request.width, generation_request.height),
-            "response_format": "b64_json"
-        });
-
         let response = self
             .client
             .post("/images/generations")
-            .json(&request)
+            .json(&self.request_body(generation_request))
             .send()
             .await?;
 
@@ -96,3 +135,66 @@ impl image_generation::ImageGenerationModel for ImageGenerationModel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(response_format: ImageResponseFormat) -> ImageGenerationRequest {
+        ImageGenerationRequest {
+            prompt: "A castle".to_string(),
+            width: 1024,
+            height: 1024,
+            response_format,
+            additional_params: Some(json!({"quality": "hd"})),
+        }
+    }
+
+    #[test]
+    fn test_request_body() {
+        let model = ImageGenerationModel::new(Client::new("key"), DALL_E_3);
+        assert_eq!(
+            model.request_body(request(ImageResponseFormat::Url)),
+            json!({
+                "model": "dall-e-3",
+                "prompt": "A castle",
+                "size": "1024x1024",
+                "response_format": "url",
+                "quality": "hd",
+            })
+        );
+
+        let model = ImageGenerationModel::new(Client::new("key"), GPT_IMAGE_1);
+        let body = model.request_body(request(ImageResponseFormat::Bytes));
+        assert_eq!(body.get("response_format"), None);
+    }
+
+    #[test]
+    fn test_response() {
+        let response: ImageGenerationResponse = serde_json::from_str(
+            r#"{"created": 1713833628, "data": [{"b64_json": "iVBORw0KGgo="}]}"#,
+        )
+        .unwrap();
+        let response: image_generation::ImageGenerationResponse<_> = response.try_into().unwrap();
+        assert_eq!(response.image, b"\x89PNG\r\n\x1a\n");
+        assert_eq!(response.url, None);
+
+        let response: ImageGenerationResponse = serde_json::from_str(
+            r#"{
+                "created": 1713833628,
+                "data": [{"url": "https://example.com/image.png", "revised_prompt": "A castle on a mountain"}]
+            }"#,
+        )
+        .unwrap();
+        let response: image_generation::ImageGenerationResponse<_> = response.try_into().unwrap();
+        assert!(response.image.is_empty());
+        assert_eq!(
+            response.url.as_deref(),
+            Some("https://example.com/image.png")
+        );
+
+        let response: ImageGenerationResponse =
+            serde_json::from_str(r#"{"created": 1713833628, "data": []}"#).unwrap();
+        assert!(image_generation::ImageGenerationResponse::try_from(response).is_err());
+    }
+}
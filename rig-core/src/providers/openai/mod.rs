@@ -8,6 +8,7 @@
 //!
 //! let gpt4o = client.completion_model(openai::GPT_4O);
 //! ```
+pub mod batch;
 pub mod client;
 pub mod completion;
 pub mod embedding;
@@ -16,17 +17,20 @@ pub mod embedding;
 pub mod audio_generation;
 #[cfg(feature = "image")]
 pub mod image_generation;
+pub mod moderation;
 pub mod streaming;
 pub mod transcription;
 
+pub use batch::*;
 pub use client::*;
 pub use completion::*;
 pub use embedding::*;
 
 #[cfg(feature = "audio")]
-pub use audio_generation::{TTS_1, TTS_1_HD};
+pub use audio_generation::{GPT_4O_MINI_TTS, TTS_1, TTS_1_HD};
 
 #[cfg(feature = "image")]
 pub use image_generation::*;
+pub use moderation::*;
 pub use streaming::*;
 pub use transcription::*;
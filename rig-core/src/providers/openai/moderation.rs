@@ -0,0 +1,128 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::ApiError;
+use crate::moderation::{self, ModerationError, ModerationResult};
+use crate::providers::openai::{ApiResponse, Client};
+
+// ================================================================
+// OpenAI Moderation API
+// ================================================================
+pub const OMNI_MODERATION_LATEST: &str = "omni-moderation-latest";
+pub const TEXT_MODERATION_LATEST: &str = "text-moderation-latest";
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationResponse {
+    pub id: String,
+    pub model: String,
+    pub results: Vec<ModerationCategories>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationCategories {
+    pub flagged: bool,
+    pub categories: BTreeMap<String, bool>,
+    pub category_scores: HashMap<String, f64>,
+}
+
+impl From<ModerationCategories> for ModerationResult {
+    fn from(result: ModerationCategories) -> Self {
+        ModerationResult {
+            flagged: result.flagged,
+            categories: result
+                .categories
+                .into_iter()
+                .filter_map(|(category, flagged)| flagged.then_some(category))
+                .collect(),
+            category_scores: result.category_scores,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ModerationModel {
+    client: Client,
+    /// Name of the model (e.g.: omni-moderation-latest)
+    pub model: String,
+}
+
+impl ModerationModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+}
+
+impl moderation::ModerationModel for ModerationModel {
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn moderate(&self, text: &str) -> Result<ModerationResult, ModerationError> {
+        let response = self
+            .client
+            .post("/moderations")
+            .json(&json!({
+                "model": self.model,
+                "input": text,
+            }))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<ModerationResponse>>().await? {
+                ApiResponse::Ok(response) => response
+                    .results
+                    .into_iter()
+                    .next()
+                    .map(ModerationResult::from)
+                    .ok_or_else(|| {
+                        ModerationError::ResponseError("Response contained no result".to_string())
+                    }),
+                ApiResponse::Err(err) => Err(ModerationError::ProviderError(err.message)),
+            }
+        } else {
+            Err(ApiError::from_response(response).await.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_moderation_response() {
+        let response: ModerationResponse = serde_json::from_str(
+            r#"{
+                "id": "modr-970d409ef3bef3b70c73d8232df86e7d",
+                "model": "omni-moderation-latest",
+                "results": [
+                    {
+                        "flagged": true,
+                        "categories": {
+                            "harassment": false,
+                            "violence": true,
+                            "violence/graphic": true
+                        },
+                        "category_scores": {
+                            "harassment": 0.0012,
+                            "violence": 0.8599,
+                            "violence/graphic": 0.4123
+                        },
+                        "category_applied_input_types": {
+                            "violence": ["text"]
+                        }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let result: ModerationResult = response.results.into_iter().next().unwrap().into();
+        assert!(result.flagged);
+        assert_eq!(result.categories, vec!["violence", "violence/graphic"]);
+        assert_eq!(result.category_scores["violence"], 0.8599);
+    }
+}
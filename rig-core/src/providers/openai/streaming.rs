@@ -1,15 +1,19 @@
-use super::completion::CompletionModel;
-use crate::completion::{CompletionError, CompletionRequest};
+use super::client::Usage;
+use super::completion::{CompletionModel, IDEMPOTENCY_KEY_HEADER};
+use crate::completion::{CompletionError, CompletionRequest, TokenUsage};
+use crate::error::ApiError;
 use crate::json_utils;
 use crate::json_utils::merge;
+use crate::providers::sse::from_response as sse_from_response;
 use crate::streaming;
 use crate::streaming::{StreamingCompletionModel, StreamingResult};
+use crate::tool::ToolError;
 use async_stream::stream;
 use futures::StreamExt;
 use reqwest::RequestBuilder;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 // ================================================================
 // OpenAI Completion Streaming API
@@ -43,7 +47,15 @@ struct StreamingChoice {
 
 #[derive(Deserialize)]
 struct StreamingCompletionResponse {
+    #[serde(default)]
     choices: Vec<StreamingChoice>,
+    /// Sent in the last chunk, whose `choices` are empty, when requested with
+    /// `stream_options.include_usage`
+    #[serde(default)]
+    usage: Option<Usage>,
+    /// Error occurring after the start of the stream
+    #[serde(default)]
+    error: Option<serde_json::Value>,
 }
 
 impl StreamingCompletionModel for CompletionModel {
@@ -51,10 +63,20 @@ impl StreamingCompletionModel for CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
+        let idempotency_key = completion_request.idempotency_key.clone();
         let mut request = self.create_completion_request(completion_request)?;
-        request = merge(request, json!({"stream": true}));
+        request = merge(
+            request,
+            json!({"stream": true, "stream_options": {"include_usage": true}}),
+        );
 
-        let builder = self.client.post("/chat/completions").json(&request);
+        let mut builder = self.client.post("/chat/completions").json(&request);
+        if let Some(idempotency_key) = idempotency_key {
+            builder = builder.header(IDEMPOTENCY_KEY_HEADER, idempotency_key);
+        }
         send_compatible_streaming_request(builder).await
     }
 }
@@ -65,120 +87,198 @@ pub async fn send_compatible_streaming_request(
     let response = request_builder.send().await?;
 
     if !response.status().is_success() {
-        return Err(CompletionError::ProviderError(format!(
-            "{}: {}",
-            response.status(),
-            response.text().await?
-        )));
+        return Err(ApiError::from_response(response).await.into());
     }
 
     // Handle OpenAI Compatible SSE chunks
+    let sse_stream = sse_from_response(response);
+
     Ok(Box::pin(stream! {
-        let mut stream = response.bytes_stream();
+        let mut sse_stream = Box::pin(sse_stream);
 
-        let mut partial_data = None;
-        let mut calls: HashMap<usize, (String, String)> = HashMap::new();
+        let mut calls: BTreeMap<usize, (String, String)> = BTreeMap::new();
+        let mut usage = None;
 
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = match chunk_result {
-                Ok(c) => c,
+        while let Some(sse_result) = sse_stream.next().await {
+            let sse = match sse_result {
+                Ok(sse) => sse,
                 Err(e) => {
                     yield Err(CompletionError::from(e));
                     break;
                 }
             };
 
-            let text = match String::from_utf8(chunk.to_vec()) {
-                Ok(t) => t,
+            if sse.data.trim().is_empty() || sse.data.trim() == "[DONE]" {
+                continue;
+            }
+
+            let data = match serde_json::from_str::<StreamingCompletionResponse>(&sse.data) {
+                Ok(data) => data,
                 Err(e) => {
-                    yield Err(CompletionError::ResponseError(e.to_string()));
-                    break;
+                    yield Err(CompletionError::JsonError(e));
+                    continue;
                 }
             };
 
+            if let Some(error) = data.error {
+                yield Err(ApiError::new(None, &json!({ "error": error }).to_string()).into());
+                break;
+            }
 
-            for line in text.lines() {
-                let mut line = line.to_string();
-
+            if let Some(data_usage) = &data.usage {
+                usage = Some(TokenUsage::from(data_usage));
+            }
 
+            let Some(choice) = data.choices.first() else {
+                continue;
+            };
 
-                // If there was a remaining part, concat with current line
-                if partial_data.is_some() {
-                    line = format!("{}{}", partial_data.unwrap(), line);
-                    partial_data = None;
-                }
-                // Otherwise full data line
-                else {
-                    let Some(data) = line.strip_prefix("data: ") else {
-                        continue;
-                    };
-
-                    // Partial data, split somewhere in the middle
-                    if !line.ends_with("}") {
-                        partial_data = Some(data.to_string());
-                    } else {
-                        line = data.to_string();
-                    }
-                }
+            let delta = &choice.delta;
 
-                let data = serde_json::from_str::<StreamingCompletionResponse>(&line);
+            for tool_call in &delta.tool_calls {
+                let function = &tool_call.function;
 
-                let Ok(data) = data else {
-                    continue;
+                // The first fragment of a tool call contains its name, the following ones
+                // only contain the next part of the arguments
+                let name = match &function.name {
+                    Some(name) => {
+                        calls.insert(tool_call.index, (name.clone(), function.arguments.clone()));
+                        name.clone()
+                    }
+                    None => {
+                        let Some((name, arguments)) = calls.get_mut(&tool_call.index) else {
+                            continue;
+                        };
+                        arguments.push_str(&function.arguments);
+                        name.clone()
+                    }
                 };
 
-                let choice = data.choices.first().expect("Should have at least one choice");
-
-                let delta = &choice.delta;
-
-                if !delta.tool_calls.is_empty() {
-                    for tool_call in &delta.tool_calls {
-                        let function = tool_call.function.clone();
-
-                        // Start of tool call
-                        // name: Some(String)
-                        // arguments: None
-                        if function.name.is_some() && function.arguments.is_empty() {
-                            calls.insert(tool_call.index, (function.name.clone().unwrap(), "".to_string()));
-                        }
-                        // Part of tool call
-                        // name: None
-                        // arguments: Some(String)
-                        else if function.name.is_none() && !function.arguments.is_empty() {
-                            let Some((name, arguments)) = calls.get(&tool_call.index) else {
-                                continue;
-                            };
-
-                            let new_arguments = &tool_call.function.arguments;
-                            let arguments = format!("{}{}", arguments, new_arguments);
-
-                            calls.insert(tool_call.index, (name.clone(), arguments));
-                        }
-                        // Entire tool call
-                        else {
-                            let name = function.name.unwrap();
-                            let arguments = function.arguments;
-                            let Ok(arguments) = serde_json::from_str(&arguments) else {
-                                continue;
-                            };
-
-                            yield Ok(streaming::StreamingChoice::ToolCall(name, "".to_string(), arguments))
-                        }
-                    }
+                if !function.arguments.is_empty() {
+                    yield Ok(streaming::StreamingChoice::ToolCallDelta(tool_call.index, name, function.arguments.clone()))
                 }
+            }
 
-                if let Some(content) = &choice.delta.content {
-                    yield Ok(streaming::StreamingChoice::Message(content.clone()))
-                }
+            if let Some(content) = &choice.delta.content {
+                yield Ok(streaming::StreamingChoice::Message(content.clone()))
             }
         }
 
         for (_, (name, arguments)) in calls {
-            let Ok(arguments) = serde_json::from_str(&arguments) else {
-                continue;
-            };
+            match serde_json::from_str(&arguments) {
+                Ok(arguments) => {
+                    yield Ok(streaming::StreamingChoice::ToolCall(name, "".to_string(), arguments))
+                }
+                // e.g.: the stream ended in the middle of the arguments (max_tokens was reached)
+                Err(e) => {
+                    yield Err(CompletionError::ToolCallError {
+                        error: ToolError::from_args_error(&arguments, e),
+                        name,
+                    })
+                }
+            }
+        }
 
-            yield Ok(streaming::StreamingChoice::ToolCall(name, "".to_string(), arguments))
+        if let Some(usage) = usage {
+            yield Ok(streaming::StreamingChoice::Usage(usage))
         }
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use super::*;
+
+    /// Local server answering a single request with the SSE `events`. Returns its URL.
+    async fn sse_server(events: &[serde_json::Value]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let mut response =
+            "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n"
+                .to_string();
+        for event in events {
+            response.push_str(&format!("data: {event}\n\n"));
+        }
+        response.push_str("data: [DONE]\n\n");
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // The request is not needed: read it only so that the connection is not reset
+            let mut request = [0; 4096];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut request).await;
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+        url
+    }
+
+    fn fragment(index: usize, name: Option<&str>, arguments: &str) -> serde_json::Value {
+        let mut function = json!({ "arguments": arguments });
+        if let Some(name) = name {
+            function["name"] = name.into();
+        }
+        json!({
+            "choices": [{
+                "delta": { "tool_calls": [{ "index": index, "function": function }] }
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_fragments() {
+        // The fragments of two parallel tool calls, interleaved
+        let url = sse_server(&[
+            fragment(0, Some("add"), ""),
+            fragment(1, Some("search"), r#"{"query""#),
+            fragment(0, None, r#"{"x": 1,"#),
+            fragment(1, None, r#": "rust"}"#),
+            fragment(0, None, r#" "y": 2}"#),
+        ])
+        .await;
+
+        let chunks = send_compatible_streaming_request(reqwest::Client::new().post(url))
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        let deltas = chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                streaming::StreamingChoice::ToolCallDelta(index, name, arguments) => {
+                    Some((*index, name.as_str(), arguments.as_str()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deltas,
+            vec![
+                (1, "search", r#"{"query""#),
+                (0, "add", r#"{"x": 1,"#),
+                (1, "search", r#": "rust"}"#),
+                (0, "add", r#" "y": 2}"#),
+            ]
+        );
+
+        // The arguments of each tool call are reassembled from its own fragments
+        let tool_calls = chunks
+            .into_iter()
+            .filter_map(|chunk| match chunk {
+                streaming::StreamingChoice::ToolCall(name, _, arguments) => Some((name, arguments)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tool_calls,
+            vec![
+                ("add".to_string(), json!({ "x": 1, "y": 2 })),
+                ("search".to_string(), json!({ "query": "rust" })),
+            ]
+        );
+    }
+}
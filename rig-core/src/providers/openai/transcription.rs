@@ -1,3 +1,4 @@
+use crate::error::ApiError;
 use crate::providers::openai::{ApiResponse, Client};
 use crate::transcription;
 use crate::transcription::TranscriptionError;
@@ -8,6 +9,8 @@ use serde::Deserialize;
 // OpenAI Transcription API
 // ================================================================
 pub const WHISPER_1: &str = "whisper-1";
+pub const GPT_4O_TRANSCRIBE: &str = "gpt-4o-transcribe";
+pub const GPT_4O_MINI_TRANSCRIBE: &str = "gpt-4o-mini-transcribe";
 
 #[derive(Debug, Deserialize)]
 pub struct TranscriptionResponse {
@@ -43,6 +46,15 @@ impl TranscriptionModel {
     }
 }
 
+/// Value of a multipart form field: strings are sent as is (not JSON encoded), other values
+/// as JSON (e.g.: `timestamp_granularities`).
+pub(crate) fn form_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
 impl transcription::TranscriptionModel for TranscriptionModel {
     type Response = TranscriptionResponse;
 
@@ -77,7 +89,7 @@ impl transcription::TranscriptionModel for TranscriptionModel {
                 .as_object()
                 .expect("Additional Parameters to OpenAI Transcription should be a map")
             {
-                body = body.text(key.to_owned(), value.to_string());
+                body = body.text(key.to_owned(), form_value(value));
             }
         }
 
@@ -99,7 +111,21 @@ impl transcription::TranscriptionModel for TranscriptionModel {
                 )),
             }
         } else {
-            Err(TranscriptionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_form_value() {
+        assert_eq!(form_value(&json!("verbose_json")), "verbose_json");
+        assert_eq!(form_value(&json!(0.5)), "0.5");
+        assert_eq!(form_value(&json!(["word"])), r#"["word"]"#);
+    }
+}
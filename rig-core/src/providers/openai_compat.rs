@@ -0,0 +1,504 @@
+//! This module provides a client and a completion model for providers exposing an
+//! OpenAI-compatible chat completions API (e.g.: [xai](super::xai), [deepseek](super::deepseek)
+//! and [groq](super::groq)).
+//!
+//! The OpenAI wire format (messages, tools, responses and streaming) is shared with the
+//! [openai](super::openai) provider. A provider only has to implement [OpenAICompatible] to
+//! define its endpoint and its quirks (e.g.: parameters it does not support), and gets a
+//! [Client] and a [CompletionModel] implementing both
+//! [CompletionModel](crate::completion::CompletionModel) and [StreamingCompletionModel].
+//!
+//! # Example
+//! ```rust
+//! use rig::providers::openai_compat::{self, OpenAICompatible};
+//!
+//! #[derive(Clone)]
+//! struct Fireworks;
+//!
+//! impl OpenAICompatible for Fireworks {
+//!     const NAME: &'static str = "Fireworks";
+//!     const BASE_URL: &'static str = "https://api.fireworks.ai/inference/v1";
+//!     const API_KEY_ENV: &'static str = "FIREWORKS_API_KEY";
+//! }
+//!
+//! let fireworks = openai_compat::Client::<Fireworks>::from_env();
+//!
+//! let agent = fireworks
+//!     .agent("accounts/fireworks/models/llama-v3p1-70b-instruct")
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//! ```
+use std::marker::PhantomData;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::openai::{
+    send_compatible_streaming_request, ApiResponse, CompletionResponse, Message, ToolDefinition,
+};
+use crate::error::ApiError;
+use crate::providers::http_client::HttpClient;
+use crate::{
+    agent::AgentBuilder,
+    completion::{
+        self,
+        cost::ModelPricing,
+        provider::{CompletionProvider, RequestOptions},
+        CompletionError, CompletionRequest, TokenUsage,
+    },
+    extractor::ExtractorBuilder,
+    json_utils,
+    streaming::{StreamingCompletionModel, StreamingResult},
+};
+
+/// Endpoint and quirks of a provider with an OpenAI-compatible API.
+pub trait OpenAICompatible: Clone + Send + Sync + 'static {
+    /// Name of the provider, used in logs (e.g.: `Groq`)
+    const NAME: &'static str;
+    /// Default base URL of the API (e.g.: `https://api.groq.com/openai/v1`)
+    const BASE_URL: &'static str;
+    /// Environment variable containing the API key (see [Client::from_env])
+    const API_KEY_ENV: &'static str;
+    /// Path of the chat completions endpoint, relative to the base URL
+    const COMPLETIONS_PATH: &'static str = "/chat/completions";
+    /// Whether the text-only contents of the messages are sent as strings rather than arrays of
+    /// content parts, which most OpenAI-compatible endpoints do not accept (default: true)
+    const STRING_CONTENT: bool = true;
+
+    /// Adapt the body of a chat completion request for `model` to the quirks of the provider
+    /// (e.g.: remove the parameters it does not support). Returns the request as is by default.
+    fn adapt_request(_model: &str, request: Value) -> Value {
+        request
+    }
+
+    /// The price of the tokens of `model`, if known.
+    fn pricing(_model: &str) -> Option<ModelPricing> {
+        None
+    }
+}
+
+/// Client of a provider with an OpenAI-compatible API.
+#[derive(Clone)]
+pub struct Client<P: OpenAICompatible> {
+    /// Base URL of the API (e.g.: `https://api.groq.com/openai/v1`)
+    pub base_url: String,
+    http_client: HttpClient,
+    provider: PhantomData<P>,
+}
+
+impl<P: OpenAICompatible> Client<P> {
+    /// Create a new client with the given API key.
+    pub fn new(api_key: &str) -> Self {
+        Self::from_url(api_key, P::BASE_URL)
+    }
+
+    /// Create a new client with the given API key and base API URL.
+    pub fn from_url(api_key: &str, base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            http_client: HttpClient::new({
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert(
+                    "Authorization",
+                    format!("Bearer {}", api_key)
+                        .parse()
+                        .expect("Bearer token should parse"),
+                );
+                headers
+            }),
+            provider: PhantomData,
+        }
+    }
+
+    /// Create a new client from the API key in the provider's environment variable
+    /// (see [OpenAICompatible::API_KEY_ENV]).
+    /// Panics if the environment variable is not set.
+    pub fn from_env() -> Self {
+        let api_key =
+            std::env::var(P::API_KEY_ENV).unwrap_or_else(|_| panic!("{} not set", P::API_KEY_ENV));
+        Self::new(&api_key)
+    }
+
+    /// Send the requests of the client with `http_client` (e.g.: a client configured with a proxy
+    /// or custom root certificates). The authentication headers of the client are still sent
+    /// with every request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = self.http_client.with_client(http_client);
+        self
+    }
+
+    /// Apply `timeouts` (see [timeouts](crate::providers::timeouts)) to the requests of the
+    /// client. This replaces the client set with `with_http_client`, if any: configure the
+    /// timeouts of that client instead.
+    pub fn with_timeouts(mut self, timeouts: crate::providers::timeouts::Timeouts) -> Self {
+        self.http_client = self.http_client.apply_timeouts(timeouts);
+        self
+    }
+
+    /// Send the completions (streamed or not) and the embeddings of the models of the client
+    /// within the limits of `limiter` (see [rate_limit](crate::rate_limit)).
+    pub fn with_rate_limit(mut self, limiter: crate::rate_limit::RateLimiter) -> Self {
+        self.http_client = self.http_client.with_rate_limit(limiter);
+        self
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.http_client.rate_limiter()
+    }
+
+    pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
+        self.http_client.post(url)
+    }
+
+    /// Create a completion model with the given name.
+    pub fn completion_model(&self, model: &str) -> CompletionModel<P> {
+        CompletionModel::new(self.clone(), model)
+    }
+
+    /// Create an agent builder with the given completion model.
+    pub fn agent(&self, model: &str) -> AgentBuilder<CompletionModel<P>> {
+        AgentBuilder::new(self.completion_model(model))
+    }
+
+    /// Create an extractor builder with the given completion model.
+    pub fn extractor<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync>(
+        &self,
+        model: &str,
+    ) -> ExtractorBuilder<T, CompletionModel<P>> {
+        ExtractorBuilder::new(self.completion_model(model))
+    }
+}
+
+impl<P: OpenAICompatible> crate::completion::CompletionClient for Client<P> {
+    type Model = CompletionModel<P>;
+
+    fn completion_model(&self, model: &str) -> CompletionModel<P> {
+        self.completion_model(model)
+    }
+}
+
+/// Completion model of a provider with an OpenAI-compatible API.
+#[derive(Clone)]
+pub struct CompletionModel<P: OpenAICompatible> {
+    pub(crate) client: Client<P>,
+    /// Name of the model (e.g.: llama-3.3-70b-versatile)
+    pub model: String,
+}
+
+impl<P: OpenAICompatible> CompletionModel<P> {
+    pub fn new(client: Client<P>, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+
+    pub(crate) fn create_completion_request(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<Value, CompletionError> {
+        let mut request = completion_request_body(&self.model, completion_request)?;
+        if P::STRING_CONTENT {
+            string_content(&mut request);
+        }
+        Ok(P::adapt_request(&self.model, request))
+    }
+}
+
+impl<P: OpenAICompatible> CompletionProvider for CompletionModel<P> {
+    type Request = Value;
+    type Response = CompletionResponse;
+
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn create_request(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<Self::Request, CompletionError> {
+        self.create_completion_request(completion_request)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn send_request(
+        &self,
+        request: Self::Request,
+        _options: RequestOptions,
+    ) -> Result<Self::Response, CompletionError> {
+        let response = self
+            .client
+            .post(P::COMPLETIONS_PATH)
+            .json(&request)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<CompletionResponse>>().await? {
+                ApiResponse::Ok(response) => {
+                    tracing::info!(target: "rig",
+                        "{} completion token usage: {:?}",
+                        P::NAME,
+                        response.usage.clone().map(|usage| format!("{usage}")).unwrap_or("N/A".to_string())
+                    );
+                    Ok(response)
+                }
+                ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
+            }
+        } else {
+            Err(ApiError::from_response(response).await.into())
+        }
+    }
+
+    fn parse_response(
+        &self,
+        response: Self::Response,
+    ) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+        response.try_into()
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        token_usage(response)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<Value> {
+        serde_json::to_value(response).ok()
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        P::pricing(&self.model)
+    }
+
+    fn rate_limiter(&self) -> Option<&crate::rate_limit::RateLimiter> {
+        self.client.rate_limiter()
+    }
+}
+
+impl<P: OpenAICompatible> StreamingCompletionModel for CompletionModel<P> {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &request).await?;
+
+        let request = json_utils::merge(
+            self.create_completion_request(request)?,
+            json!({"stream": true}),
+        );
+
+        let builder = self.client.post(P::COMPLETIONS_PATH).json(&request);
+
+        send_compatible_streaming_request(builder).await
+    }
+}
+
+/// Body of an OpenAI chat completion request for `model`.
+pub(crate) fn completion_request_body(
+    model: &str,
+    completion_request: CompletionRequest,
+) -> Result<Value, CompletionError> {
+    let generation = completion_request.generation_config();
+
+    // Add preamble to chat history (if available). Some providers (e.g.: xAI) reject
+    // empty system messages.
+    let mut full_history: Vec<Message> = match &completion_request.preamble {
+        Some(preamble) if !preamble.is_empty() => vec![Message::system(preamble)],
+        _ => vec![],
+    };
+
+    // Convert prompt to user message
+    let prompt: Vec<Message> = completion_request.prompt_with_context().try_into()?;
+
+    // Convert existing chat history
+    let chat_history: Vec<Message> = completion_request
+        .chat_history
+        .into_iter()
+        .map(|message| message.try_into())
+        .collect::<Result<Vec<Vec<Message>>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    // Combine all messages into a single history
+    full_history.extend(chat_history);
+    full_history.extend(prompt);
+
+    let request = if completion_request.tools.is_empty() {
+        json!({
+            "model": model,
+            "messages": full_history,
+        })
+    } else {
+        json!({
+            "model": model,
+            "messages": full_history,
+            "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
+            "tool_choice": "auto",
+        })
+    };
+
+    // only include the generation parameters which are set
+    // because some models don't support them (e.g.: temperature)
+    let request = json_utils::merge(request, generation.openai_params());
+
+    let request = if let Some(params) = completion_request.additional_params {
+        json_utils::merge(request, params)
+    } else {
+        request
+    };
+
+    Ok(request)
+}
+
+/// Send the text-only contents of the messages of a request body as strings, without their empty
+/// text parts (e.g.: next to the tool calls of assistant messages). Contents with other parts
+/// (e.g.: images) are kept as arrays.
+fn string_content(request: &mut Value) {
+    let Some(messages) = request.get_mut("messages").and_then(Value::as_array_mut) else {
+        return;
+    };
+
+    for message in messages {
+        let Some(message) = message.as_object_mut() else {
+            continue;
+        };
+        let Some(parts) = message.get_mut("content").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        parts.retain(|part| part["type"] != "text" || part["text"] != "");
+
+        let texts = parts
+            .iter()
+            .map(|part| match part["type"].as_str() {
+                Some("text") => part["text"].as_str(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|texts| texts.join("\n"));
+        match texts {
+            // Assistant messages with tool calls may have no content
+            Some(text) if text.is_empty() && message.contains_key("tool_calls") => {
+                message.remove("content");
+            }
+            Some(text) => {
+                message.insert("content".to_string(), text.into());
+            }
+            None => {}
+        }
+    }
+}
+
+/// Token usage reported in an OpenAI chat completion response.
+pub(crate) fn token_usage(response: &CompletionResponse) -> Option<TokenUsage> {
+    response.usage.as_ref().map(TokenUsage::from)
+}
+
+/// Remove `params` from a request body, for providers which reject them (see
+/// [OpenAICompatible::adapt_request]).
+pub fn remove_params(mut request: Value, params: &[&str]) -> Value {
+    if let Some(request) = request.as_object_mut() {
+        for param in params {
+            if request.remove(*param).is_some() {
+                tracing::debug!(target: "rig", "Removed unsupported parameter `{}`", param);
+            }
+        }
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::CompletionModel as _;
+
+    #[derive(Clone)]
+    struct Provider;
+
+    impl OpenAICompatible for Provider {
+        const NAME: &'static str = "Provider";
+        const BASE_URL: &'static str = "https://api.example.com/v1";
+        const API_KEY_ENV: &'static str = "PROVIDER_API_KEY";
+
+        fn adapt_request(_model: &str, request: Value) -> Value {
+            remove_params(request, &["logprobs"])
+        }
+    }
+
+    #[test]
+    fn test_create_completion_request() {
+        let model = Client::<Provider>::new("key").completion_model("model");
+
+        let request = model
+            .completion_request("Hello")
+            .preamble("".to_string())
+            .temperature(0.0)
+            .stop("END")
+            .additional_params(json!({"logprobs": true, "seed": 42}))
+            .build();
+
+        assert_eq!(
+            model.create_completion_request(request).unwrap(),
+            json!({
+                "model": "model",
+                "messages": [{"role": "user", "content": "Hello"}],
+                "temperature": 0.0,
+                "stop": ["END"],
+                "seed": 42,
+            })
+        );
+    }
+
+    #[test]
+    fn test_store_not_sent() {
+        // Only OpenAI supports storing completions
+        let model = Client::<Provider>::new("key").completion_model("model");
+        let request = model
+            .completion_request("Hello")
+            .store(true)
+            .metadata([("user".to_string(), "alice".to_string())].into())
+            .build();
+
+        let body = model.create_completion_request(request).unwrap();
+        assert!(body.get("store").is_none());
+        assert!(body.get("metadata").is_none());
+    }
+
+    #[test]
+    fn test_string_content() {
+        let model = Client::<Provider>::new("key").completion_model("model");
+
+        let tool_call = crate::message::ToolCall {
+            id: "call_1".to_string(),
+            function: crate::message::ToolFunction {
+                name: "add".to_string(),
+                arguments: json!({"x": 1, "y": 2}),
+            },
+        };
+        let request = model
+            .completion_request(crate::message::Message::User {
+                content: crate::OneOrMany::one(crate::message::UserContent::tool_result(
+                    "call_1",
+                    crate::OneOrMany::one(crate::message::ToolResultContent::text("3")),
+                )),
+            })
+            .preamble("Be concise.".to_string())
+            .messages(vec![
+                crate::message::Message::user("What is 1 + 2?"),
+                crate::message::Message::Assistant {
+                    content: crate::OneOrMany::many(vec![
+                        crate::message::AssistantContent::text(""),
+                        crate::message::AssistantContent::ToolCall(tool_call),
+                    ])
+                    .unwrap(),
+                },
+            ])
+            .build();
+
+        let request = model.create_completion_request(request).unwrap();
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["content"], "Be concise.");
+        assert_eq!(messages[1]["content"], "What is 1 + 2?");
+        assert_eq!(messages[2].get("content"), None);
+        assert_eq!(messages[2]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(messages[3]["content"], "3");
+    }
+}
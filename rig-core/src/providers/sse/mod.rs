@@ -1,4 +1,12 @@
-use super::line::{self, LineDecoder};
+//! Decoder of the Server-Sent Events (SSE) of the streaming responses of the providers.
+//!
+//! Port of the decoders of the
+//! [Anthropic TypeScript SDK](https://github.com/anthropics/anthropic-sdk-typescript/tree/main)
+//! (MIT license), shared by the providers streaming their responses as SSE.
+pub mod line;
+
+use self::line::LineDecoder;
+use crate::completion::CompletionError;
 use futures::{Stream, StreamExt};
 use std::fmt::Debug;
 use thiserror::Error;
@@ -15,6 +23,26 @@ pub enum SSEDecoderError {
     IoError(#[from] std::io::Error),
 }
 
+impl From<SSEDecoderError> for CompletionError {
+    fn from(error: SSEDecoderError) -> Self {
+        match error {
+            // Transport errors of the underlying response are surfaced as such, so that they can
+            // be classified (e.g.: retried) like the errors of non-streamed requests
+            SSEDecoderError::IoError(e) => {
+                let message = e.to_string();
+                match e.into_inner() {
+                    Some(inner) => match inner.downcast::<reqwest::Error>() {
+                        Ok(e) => CompletionError::HttpError(*e),
+                        Err(inner) => CompletionError::RequestError(inner),
+                    },
+                    None => CompletionError::ResponseError(message),
+                }
+            }
+            e => CompletionError::ResponseError(e.to_string()),
+        }
+    }
+}
+
 /// Server-Sent Event with event name, data, and raw lines
 #[derive(Debug, Clone)]
 pub struct ServerSentEvent {
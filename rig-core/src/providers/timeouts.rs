@@ -0,0 +1,159 @@
+//! Timeouts of the HTTP clients of the providers.
+//!
+//! Two timeouts are applied to the underlying [reqwest::Client]:
+//! - The connect timeout limits the time spent establishing a connection to the provider.
+//! - The read timeout limits the time spent waiting for the next chunk of a response (i.e.: an
+//!   idle timeout). It is reset every time data is received, so long streaming completions are
+//!   not interrupted as long as the provider keeps sending data.
+//!
+//! Provider clients use [Timeouts::default] (a connect timeout of [DEFAULT_CONNECT_TIMEOUT] and
+//! no read timeout) unless configured otherwise with their `with_timeouts` method (or the
+//! `ClientBuilder` of the provider, if it has one). Streaming completions need a generous read
+//! timeout since providers may take a while to send the first token (e.g.: reasoning models).
+//! A timeout set on an individual request (e.g.: with [reqwest::RequestBuilder::timeout])
+//! limits the total duration of that request and applies in addition to these timeouts.
+//!
+//! # Example
+//! ```rust
+//! use rig::providers::{openai, timeouts::Timeouts};
+//! use std::time::Duration;
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key").with_timeouts(Timeouts {
+//!     connect: Some(Duration::from_secs(5)),
+//!     read: Some(Duration::from_secs(120)),
+//! });
+//! ```
+use std::time::Duration;
+
+/// Default time allowed to establish a connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connect and read timeouts of a provider client. `None` means no timeout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub read: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Some(DEFAULT_CONNECT_TIMEOUT),
+            read: None,
+        }
+    }
+}
+
+impl Timeouts {
+    /// Apply the timeouts to a [reqwest::ClientBuilder].
+    /// Note: timeouts are not supported by the WASM HTTP client and are ignored on WASM targets.
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut builder = builder;
+            if let Some(connect) = self.connect {
+                builder = builder.connect_timeout(connect);
+            }
+            if let Some(read) = self.read {
+                builder = builder.read_timeout(read);
+            }
+            builder
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        builder
+    }
+
+    /// Build a [reqwest::Client] with the default configuration and the timeouts.
+    pub(crate) fn build_client(&self) -> reqwest::Client {
+        self.apply(reqwest::Client::builder())
+            .build()
+            .expect("reqwest client should build")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use super::{Timeouts, DEFAULT_CONNECT_TIMEOUT};
+    use crate::{
+        completion::{CompletionError, Prompt, PromptError},
+        providers::{cohere, openai},
+    };
+
+    /// Local server accepting connections but never answering. Returns its URL.
+    async fn silent_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+        url
+    }
+
+    fn read_timeout(millis: u64) -> Timeouts {
+        Timeouts {
+            read: Some(Duration::from_millis(millis)),
+            ..Default::default()
+        }
+    }
+
+    fn is_timeout(error: &PromptError) -> bool {
+        matches!(
+            error,
+            PromptError::CompletionError(CompletionError::HttpError(e)) if e.is_timeout()
+        )
+    }
+
+    #[test]
+    fn test_default_timeouts() {
+        // No read timeout by default: long streaming completions are never interrupted
+        assert_eq!(
+            Timeouts::default(),
+            Timeouts {
+                connect: Some(DEFAULT_CONNECT_TIMEOUT),
+                read: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        let url = silent_server().await;
+
+        let response = read_timeout(50)
+            .build_client()
+            .get(&url)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(response.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn test_client_read_timeouts() {
+        let url = silent_server().await;
+
+        // With the builder of the provider
+        let agent = openai::ClientBuilder::new("key")
+            .base_url(&url)
+            .read_timeout(Duration::from_millis(50))
+            .build()
+            .agent(openai::GPT_4O)
+            .build();
+        assert!(is_timeout(&agent.prompt("Hi").await.unwrap_err()));
+
+        // With the client of a provider without builder
+        let agent = cohere::Client::from_url("key", &url)
+            .with_timeouts(read_timeout(50))
+            .agent(cohere::COMMAND_R)
+            .build();
+        assert!(is_timeout(&agent.prompt("Hi").await.unwrap_err()));
+    }
+}
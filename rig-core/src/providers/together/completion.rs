@@ -3,6 +3,7 @@
 //! From [Together AI Reference](https://docs.together.ai/docs/chat-overview)
 // ================================================================
 
+use crate::error::ApiError;
 use crate::{
     completion::{self, CompletionError},
     json_utils,
@@ -144,6 +145,8 @@ impl CompletionModel {
         &self,
         completion_request: completion::CompletionRequest,
     ) -> Result<serde_json::Value, CompletionError> {
+        let generation = completion_request.generation_config();
+
         let mut full_history: Vec<openai::Message> = match &completion_request.preamble {
             Some(preamble) => vec![openai::Message::system(preamble)],
             None => vec![],
@@ -174,6 +177,7 @@ impl CompletionModel {
                 "tool_choice": "auto",
             })
         };
+        request = json_utils::merge(request, generation.openai_params());
         request = if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
         } else {
@@ -186,11 +190,18 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = openai::CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
         completion_request: completion::CompletionRequest,
     ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+        crate::rate_limit::acquire_completion(self.client.rate_limiter(), &completion_request)
+            .await?;
+
         let request = self.create_completion_request(completion_request)?;
 
         let response = self
@@ -215,7 +226,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(err) => Err(CompletionError::ProviderError(err.error)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(ApiError::from_response(response).await.into())
         }
     }
 }
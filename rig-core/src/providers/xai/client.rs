@@ -1,66 +1,44 @@
 use crate::{
-    agent::AgentBuilder,
     embeddings::{self},
-    extractor::ExtractorBuilder,
+    providers::openai_compat::{self, OpenAICompatible},
     Embed,
 };
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
 
-use super::{completion::CompletionModel, embedding::EmbeddingModel, EMBEDDING_V1};
+use super::{embedding::EmbeddingModel, EMBEDDING_V1};
 
 // ================================================================
 // xAI Client
 // ================================================================
-const XAI_BASE_URL: &str = "https://api.x.ai";
+const XAI_BASE_URL: &str = "https://api.x.ai/v1";
 
-#[derive(Clone)]
-pub struct Client {
-    base_url: String,
-    http_client: reqwest::Client,
-}
-
-impl Client {
-    pub fn new(api_key: &str) -> Self {
-        Self::from_url(api_key, XAI_BASE_URL)
-    }
-    fn from_url(api_key: &str, base_url: &str) -> Self {
-        Self {
-            base_url: base_url.to_string(),
-            http_client: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
-                    headers.insert(
-                        reqwest::header::CONTENT_TYPE,
-                        "application/json".parse().unwrap(),
-                    );
-                    headers.insert(
-                        "Authorization",
-                        format!("Bearer {}", api_key)
-                            .parse()
-                            .expect("Bearer token should parse"),
-                    );
-                    headers
-                })
-                .build()
-                .expect("xAI reqwest client should build"),
-        }
-    }
-
-    /// Create a new xAI client from the `XAI_API_KEY` environment variable.
-    /// Panics if the environment variable is not set.
-    pub fn from_env() -> Self {
-        let api_key = std::env::var("XAI_API_KEY").expect("XAI_API_KEY not set");
-        Self::new(&api_key)
-    }
+/// xAI's OpenAI-compatible API.
+#[derive(Clone, Debug)]
+pub struct Xai;
 
-    pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
-        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
+impl OpenAICompatible for Xai {
+    const NAME: &'static str = "xAI";
+    const BASE_URL: &'static str = XAI_BASE_URL;
+    const API_KEY_ENV: &'static str = "XAI_API_KEY";
+}
 
-        tracing::debug!("POST {}", url);
-        self.http_client.post(url)
-    }
+/// xAI client. Completion models, agents and extractors are provided by
+/// [openai_compat::Client].
+///
+/// # Example
+/// ```
+/// use rig::providers::xai::{Client, self};
+///
+/// // Initialize the xAI client
+/// let xai = Client::new("your-xai-api-key");
+///
+/// let agent = xai.agent(xai::completion::GROK_BETA)
+///    .preamble("You are comedian AI with a mission to make people laugh.")
+///    .temperature(0.0)
+///    .build();
+/// ```
+pub type Client = openai_compat::Client<Xai>;
 
+impl Client {
     /// Create an embedding model with the given name.
     /// Note: default embedding dimension of 0 will be used if model is not known.
     /// If this is the case, it's better to use function `embedding_model_with_ndims`
@@ -120,36 +98,6 @@ impl Client {
     ) -> embeddings::EmbeddingsBuilder<EmbeddingModel, D> {
         embeddings::EmbeddingsBuilder::new(self.embedding_model(model))
     }
-
-    /// Create a completion model with the given name.
-    pub fn completion_model(&self, model: &str) -> CompletionModel {
-        CompletionModel::new(self.clone(), model)
-    }
-
-    /// Create an agent builder with the given completion model.
-    /// # Example
-    /// ```
-    /// use rig::providers::xai::{Client, self};
-    ///
-    /// // Initialize the xAI client
-    /// let xai = Client::new("your-xai-api-key");
-    ///
-    /// let agent = xai.agent(xai::completion::GROK_BETA)
-    ///    .preamble("You are comedian AI with a mission to make people laugh.")
-    ///    .temperature(0.0)
-    ///    .build();
-    /// ```
-    pub fn agent(&self, model: &str) -> AgentBuilder<CompletionModel> {
-        AgentBuilder::new(self.completion_model(model))
-    }
-
-    /// Create an extractor builder with the given completion model.
-    pub fn extractor<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync>(
-        &self,
-        model: &str,
-    ) -> ExtractorBuilder<T, CompletionModel> {
-        ExtractorBuilder::new(self.completion_model(model))
-    }
 }
 
 pub mod xai_api_types {
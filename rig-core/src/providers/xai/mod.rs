@@ -1,19 +1,26 @@
 //! xAi API client and Rig integration
 //!
+//! xAI exposes an OpenAI-compatible API (see [openai_compat](crate::providers::openai_compat)).
+//!
 //! # Example
 //! ```
 //! use rig::providers::xai;
 //!
 //! let client = xai::Client::new("YOUR_API_KEY");
 //!
-//! let groq_embedding_model = client.embedding_model(xai::v1);
+//! let grok = client.completion_model(xai::GROK_3);
+//! let embedding_model = client.embedding_model(xai::EMBEDDING_V1);
 //! ```
 
 pub mod client;
 pub mod completion;
 pub mod embedding;
+#[deprecated(
+    since = "0.11.0",
+    note = "xAI streaming is implemented by `providers::openai_compat`"
+)]
 pub mod streaming;
 
-pub use client::Client;
-pub use completion::GROK_BETA;
+pub use client::{Client, Xai};
+pub use completion::{CompletionModel, GROK_2, GROK_2_VISION, GROK_3, GROK_3_MINI, GROK_BETA};
 pub use embedding::EMBEDDING_V1;
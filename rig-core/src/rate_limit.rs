@@ -0,0 +1,566 @@
+//! This module provides the [RateLimiter] struct, a token-bucket rate limiter of the requests
+//! (and of the tokens) sent to a provider, and the [RateLimitedModel] and
+//! [RateLimitedEmbeddingModel] wrappers, which apply a rate limiter to the completion and
+//! embedding requests of a model.
+//!
+//! Providers limit the number of requests and of tokens per minute of an account, across all
+//! its models. A rate limiter is cheap to clone and clones share the same limits, so the models
+//! of a provider client should be wrapped with clones of the same limiter (e.g.: an ingestion
+//! job embedding documents while agents answer prompts), or the limiter can be set on the
+//! provider client itself with its `with_rate_limit` method, to apply it to the completions
+//! (streamed or not) and the embeddings of all the models of the client.
+//!
+//! Requests exceeding the limits wait until they fit in them (see [RateLimitConfig::max_wait]),
+//! or are shed with a [CompletionError::RateLimitError] (or [EmbeddingError::RateLimitError]),
+//! which is not retried (see [Error::is_retryable](crate::Error::is_retryable)): the request
+//! already waited as long as allowed. The tokens of a request are estimated before it is sent
+//! (see [estimate_tokens](crate::completion::tokens::estimate_tokens)) and, for the completions
+//! of a [RateLimitedModel], corrected with the token usage reported by the provider, if any.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::CompletionModel,
+//!     embeddings::EmbeddingsBuilder,
+//!     providers::openai,
+//!     rate_limit::{RateLimitConfig, RateLimitedEmbeddingModel, RateLimiter},
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! // Limits of the OpenAI account, shared by all its models
+//! let limiter = RateLimiter::new(
+//!     RateLimitConfig::default()
+//!         .requests_per_minute(500)
+//!         .tokens_per_minute(200_000),
+//! )?;
+//!
+//! let agent = AgentBuilder::new(
+//!     openai
+//!         .completion_model(openai::GPT_4O)
+//!         .with_rate_limit(limiter.clone()),
+//! )
+//! .build();
+//!
+//! let embeddings = EmbeddingsBuilder::new(RateLimitedEmbeddingModel::new(
+//!     openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL),
+//!     limiter,
+//! ))
+//! .documents(documents)?
+//! .build()
+//! .await?;
+//!
+//! // Or: the same limits, applied to all the models of the client
+//! let openai = openai::Client::from_env().with_rate_limit(limiter);
+//! ```
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use web_time::Instant;
+
+use crate::{
+    completion::{
+        cost::ModelPricing, template::ChatTemplate, tokens::estimate_tokens, CompletionError,
+        CompletionModel, CompletionRequest, CompletionResponse, ContextTemplate, TokenUsage,
+    },
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+    streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RateLimitConfigError {
+    /// A limit of 0 per minute, which no request would ever fit in
+    #[error("RateLimitConfigError: the limit of {0} per minute must be positive")]
+    ZeroLimit(&'static str),
+}
+
+/// Configuration of the limits of a [RateLimiter]. By default, there is no limit.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    /// Maximum number of requests per minute
+    pub requests_per_minute: Option<u32>,
+    /// Maximum number of tokens per minute (input and output tokens of completions, input
+    /// tokens of embeddings)
+    pub tokens_per_minute: Option<u32>,
+    /// Maximum time a request waits for the limits before being shed (`None` to wait as long
+    /// as needed)
+    pub max_wait: Option<Duration>,
+}
+
+impl RateLimitConfig {
+    /// Set the maximum number of requests per minute
+    pub fn requests_per_minute(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = Some(requests_per_minute);
+        self
+    }
+
+    /// Set the maximum number of tokens per minute
+    pub fn tokens_per_minute(mut self, tokens_per_minute: u32) -> Self {
+        self.tokens_per_minute = Some(tokens_per_minute);
+        self
+    }
+
+    /// Set the maximum time a request waits for the limits. Requests which would have to wait
+    /// longer are shed immediately.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Shed the requests exceeding the limits instead of waiting (same as a maximum wait of 0)
+    pub fn shed(self) -> Self {
+        self.max_wait(Duration::ZERO)
+    }
+}
+
+/// Token bucket holding up to a minute of its rate, refilled continuously.
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    /// Available amount, negative when more was consumed than available (e.g.: when the token
+    /// usage of a request exceeded its estimate)
+    available: f64,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            capacity: per_minute as f64,
+            available: per_minute as f64,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available =
+            (self.available + elapsed.as_secs_f64() * self.capacity / 60.0).min(self.capacity);
+    }
+
+    /// Time until `amount` is available. Amounts larger than the capacity only wait for a
+    /// full bucket.
+    fn wait_time(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60.0 / self.capacity)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    last_refill: Instant,
+}
+
+impl Buckets {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+        self.requests
+            .iter_mut()
+            .chain(self.tokens.iter_mut())
+            .for_each(|bucket| bucket.refill(elapsed));
+    }
+}
+
+/// Token-bucket rate limiter of the requests and tokens sent to a provider (see the
+/// [module](self) documentation).
+///
+/// The limiter is cheap to clone and clones share the same limits.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+    max_wait: Option<Duration>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter with the limits of `config`. Fails if a limit is 0.
+    pub fn new(config: RateLimitConfig) -> Result<Self, RateLimitConfigError> {
+        if config.requests_per_minute == Some(0) {
+            return Err(RateLimitConfigError::ZeroLimit("requests"));
+        }
+        if config.tokens_per_minute == Some(0) {
+            return Err(RateLimitConfigError::ZeroLimit("tokens"));
+        }
+
+        Ok(Self {
+            buckets: Arc::new(Mutex::new(Buckets {
+                requests: config.requests_per_minute.map(Bucket::new),
+                tokens: config.tokens_per_minute.map(Bucket::new),
+                last_refill: Instant::now(),
+            })),
+            max_wait: config.max_wait,
+        })
+    }
+
+    /// Take a request of `tokens` tokens from the limits if it fits in them. Otherwise, return
+    /// the time until it fits.
+    pub fn try_acquire(&self, tokens: u64) -> Result<(), Duration> {
+        let mut buckets = self.lock();
+        buckets.refill();
+
+        let wait = [
+            buckets
+                .requests
+                .as_ref()
+                .map(|bucket| bucket.wait_time(1.0)),
+            buckets
+                .tokens
+                .as_ref()
+                .map(|bucket| bucket.wait_time(tokens as f64)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or_default();
+
+        if wait > Duration::ZERO {
+            return Err(wait);
+        }
+        if let Some(bucket) = &mut buckets.requests {
+            bucket.available -= 1.0;
+        }
+        if let Some(bucket) = &mut buckets.tokens {
+            bucket.available -= tokens as f64;
+        }
+        Ok(())
+    }
+
+    /// Take a request of `tokens` tokens from the limits, waiting until it fits in them. Fails
+    /// with the time until it fits if that exceeds the maximum wait of the limiter.
+    pub async fn acquire(&self, tokens: u64) -> Result<(), Duration> {
+        let deadline = self.max_wait.map(|max_wait| Instant::now() + max_wait);
+
+        loop {
+            let wait = match self.try_acquire(tokens) {
+                Ok(()) => return Ok(()),
+                Err(wait) => wait,
+            };
+
+            if deadline.is_some_and(|deadline| Instant::now() + wait > deadline) {
+                return Err(wait);
+            }
+
+            tracing::debug!(target: "rig", "Rate limit reached, waiting {:?}", wait);
+            futures_timer::Delay::new(wait).await;
+        }
+    }
+
+    /// Correct the tokens taken by a request whose estimate was `estimated` tokens, once its
+    /// actual usage of `actual` tokens is known.
+    pub fn record_usage(&self, estimated: u64, actual: u64) {
+        if let Some(bucket) = &mut self.lock().tokens {
+            bucket.available += estimated as f64 - actual as f64;
+            bucket.available = bucket.available.min(bucket.capacity);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        self.buckets
+            .lock()
+            .expect("Rate limiter lock should not be poisoned")
+    }
+}
+
+/// Estimated number of tokens of a completion request: its input tokens and, if set, its
+/// maximum number of output tokens.
+fn estimate_request_tokens(request: &CompletionRequest) -> u64 {
+    estimate_tokens(&ChatTemplate::new().render_request(request)) as u64
+        + request.max_tokens.unwrap_or(0)
+}
+
+/// Wait for a completion request to fit in the limits of `limiter` (the limiter of a provider
+/// client, if any), and return its estimated number of tokens.
+pub(crate) async fn acquire_completion(
+    limiter: Option<&RateLimiter>,
+    request: &CompletionRequest,
+) -> Result<u64, CompletionError> {
+    let estimated = estimate_request_tokens(request);
+    if let Some(limiter) = limiter {
+        limiter
+            .acquire(estimated)
+            .await
+            .map_err(CompletionError::RateLimitError)?;
+    }
+    Ok(estimated)
+}
+
+/// Wait for an embedding request of `texts` to fit in the limits of `limiter` (the limiter of a
+/// provider client, if any).
+pub(crate) async fn acquire_embedding(
+    limiter: Option<&RateLimiter>,
+    texts: &[String],
+) -> Result<(), EmbeddingError> {
+    if let Some(limiter) = limiter {
+        let tokens = texts.iter().map(|text| estimate_tokens(text) as u64).sum();
+        limiter
+            .acquire(tokens)
+            .await
+            .map_err(EmbeddingError::RateLimitError)?;
+    }
+    Ok(())
+}
+
+/// Completion model wrapper that sends its completion requests within the limits of a
+/// [RateLimiter].
+#[derive(Clone)]
+pub struct RateLimitedModel<M: CompletionModel> {
+    model: M,
+    limiter: RateLimiter,
+}
+
+impl<M: CompletionModel> RateLimitedModel<M> {
+    pub fn new(model: M, limiter: RateLimiter) -> Self {
+        Self { model, limiter }
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for RateLimitedModel<M> {
+    type Response = M::Response;
+
+    fn model_name(&self) -> Option<&str> {
+        self.model.model_name()
+    }
+
+    fn context_template(&self) -> ContextTemplate {
+        self.model.context_template()
+    }
+
+    fn token_usage(&self, response: &Self::Response) -> Option<TokenUsage> {
+        self.model.token_usage(response)
+    }
+
+    fn raw_response_json(&self, response: &Self::Response) -> Option<serde_json::Value> {
+        self.model.raw_response_json(response)
+    }
+
+    fn pricing(&self) -> Option<ModelPricing> {
+        self.model.pricing()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let estimated = acquire_completion(Some(&self.limiter), &request).await?;
+
+        let response = self.model.completion(request).await?;
+        if let Some(usage) = self.model.token_usage(&response.raw_response) {
+            self.limiter.record_usage(estimated, usage.total_tokens);
+        }
+        Ok(response)
+    }
+}
+
+/// Streamed completions are sent within the limits too. Their estimate is corrected with the
+/// token usage reported at the end of the stream, by the providers reporting it.
+impl<M: StreamingCompletionModel> StreamingCompletionModel for RateLimitedModel<M> {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        let mut recorded = acquire_completion(Some(&self.limiter), &request).await?;
+
+        let limiter = self.limiter.clone();
+        let stream = self.model.stream(request).await?;
+        Ok(Box::pin(stream.inspect(move |chunk| {
+            if let Ok(StreamingChoice::Usage(usage)) = chunk {
+                limiter.record_usage(recorded, usage.total_tokens);
+                recorded = usage.total_tokens;
+            }
+        })))
+    }
+}
+
+/// Embedding model wrapper that sends its embedding requests within the limits of a
+/// [RateLimiter]. The tokens of the requests are estimated from the texts embedded.
+#[derive(Clone)]
+pub struct RateLimitedEmbeddingModel<M: EmbeddingModel> {
+    model: M,
+    limiter: RateLimiter,
+}
+
+impl<M: EmbeddingModel> RateLimitedEmbeddingModel<M> {
+    pub fn new(model: M, limiter: RateLimiter) -> Self {
+        Self { model, limiter }
+    }
+}
+
+impl<M: EmbeddingModel> EmbeddingModel for RateLimitedEmbeddingModel<M> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        self.model.model_name()
+    }
+
+    fn price_per_million_tokens(&self) -> Option<f64> {
+        self.model.price_per_million_tokens()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        acquire_embedding(Some(&self.limiter), &texts).await?;
+
+        self.model.embed_texts(texts).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        completion::Prompt,
+        providers::mock::{MockCompletionModel, MockEmbeddingModel},
+    };
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(
+            RateLimitConfig::default()
+                .requests_per_minute(2)
+                .tokens_per_minute(600),
+        )
+        .unwrap();
+
+        assert!(limiter.try_acquire(100).is_ok());
+        // The tokens limit is reached first
+        let wait = limiter.try_acquire(600).unwrap_err();
+        assert!(wait > Duration::from_secs(9) && wait <= Duration::from_secs(10));
+
+        assert!(limiter.try_acquire(100).is_ok());
+        // Then the requests limit
+        let wait = limiter.try_acquire(1).unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+
+        // Unused estimated tokens are given back, but not the requests
+        limiter.record_usage(100, 0);
+        assert!(limiter.try_acquire(1).is_err());
+    }
+
+    #[test]
+    fn test_zero_limits() {
+        assert!(matches!(
+            RateLimiter::new(RateLimitConfig::default().requests_per_minute(0)),
+            Err(RateLimitConfigError::ZeroLimit("requests"))
+        ));
+        assert!(matches!(
+            RateLimiter::new(RateLimitConfig::default().tokens_per_minute(0)),
+            Err(RateLimitConfigError::ZeroLimit("tokens"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_models() {
+        let limiter =
+            RateLimiter::new(RateLimitConfig::default().requests_per_minute(2).shed()).unwrap();
+
+        let model = MockCompletionModel::new()
+            .fallback_text("ok")
+            .with_rate_limit(limiter.clone());
+        let agent = crate::agent::AgentBuilder::new(model).build();
+        let embedding_model = RateLimitedEmbeddingModel::new(MockEmbeddingModel::new(4), limiter);
+
+        assert_eq!(agent.prompt("Hello").await.unwrap(), "ok");
+        embedding_model.embed_text("Hello").await.unwrap();
+
+        // The limit is shared by the two models
+        let error = agent.prompt("Hello").await.unwrap_err();
+        assert!(matches!(
+            error,
+            crate::completion::PromptError::CompletionError(CompletionError::RateLimitError(_))
+        ));
+        assert!(matches!(
+            embedding_model.embed_text("Hello").await,
+            Err(EmbeddingError::RateLimitError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_streaming() {
+        let limiter =
+            RateLimiter::new(RateLimitConfig::default().requests_per_minute(1).shed()).unwrap();
+        let model = MockCompletionModel::new()
+            .fallback_text("ok")
+            .with_rate_limit(limiter);
+
+        let request = model.completion_request("Hello").build();
+        // The text and its token usage
+        let chunks = model.stream(request.clone()).await.unwrap();
+        assert_eq!(chunks.count().await, 2);
+
+        assert!(matches!(
+            model.stream(request).await,
+            Err(CompletionError::RateLimitError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_rate_limit() {
+        use crate::providers::openai;
+
+        let limiter =
+            RateLimiter::new(RateLimitConfig::default().requests_per_minute(1).shed()).unwrap();
+        let client = openai::Client::new("key").with_rate_limit(limiter.clone());
+        // Exhaust the limit, so the requests of the models of the client are shed before
+        // being sent
+        limiter.try_acquire(0).unwrap();
+
+        let model = client.completion_model(openai::GPT_4O);
+        let request = model.completion_request("Hello").build();
+        assert!(matches!(
+            model.completion(request.clone()).await,
+            Err(CompletionError::RateLimitError(_))
+        ));
+        assert!(matches!(
+            model.stream(request).await,
+            Err(CompletionError::RateLimitError(_))
+        ));
+        assert!(matches!(
+            client
+                .embedding_model(openai::TEXT_EMBEDDING_3_SMALL)
+                .embed_text("Hello")
+                .await,
+            Err(EmbeddingError::RateLimitError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_queued_requests() {
+        // One request per 100ms
+        let limiter = RateLimiter::new(
+            RateLimitConfig::default()
+                .requests_per_minute(600)
+                .max_wait(Duration::from_secs(1)),
+        )
+        .unwrap();
+        let wait = loop {
+            if let Err(wait) = limiter.try_acquire(0) {
+                break wait;
+            }
+        };
+
+        let start = Instant::now();
+        limiter.acquire(0).await.unwrap();
+        assert!(start.elapsed() >= wait / 2);
+
+        let limiter = RateLimiter::new(
+            RateLimitConfig::default()
+                .requests_per_minute(600)
+                .max_wait(Duration::from_millis(1)),
+        )
+        .unwrap();
+        while limiter.try_acquire(0).is_ok() {}
+        assert!(limiter.acquire(0).await.is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! This module provides the [Reranker] trait, implemented by reranking models which order a
+//! list of documents by their relevance to a query (e.g.: Cohere's rerank models, see
+//! [cohere::RerankModel](crate::providers::cohere::RerankModel)).
+//!
+//! Rerankers are typically used in retrieval pipelines to rerank the documents retrieved from a
+//! vector store (which are only ranked by the similarity of their embeddings) before sending the
+//! most relevant ones to the model.
+//!
+//! # Example
+//! ```rust
+//! use rig::{providers::cohere, rerank::Reranker, vector_store::VectorStoreIndex};
+//!
+//! let cohere = cohere::Client::from_env();
+//! let reranker = cohere.rerank_model(cohere::RERANK_V3_5);
+//!
+//! // Fetch 20 candidates from the vector store and keep the 5 most relevant ones
+//! let query = "What does \"glarb-glarb\" mean?";
+//! let candidates = index.top_n::<String>(query, 20).await?;
+//!
+//! let results = reranker
+//!     .rerank(query, candidates.iter().map(|(_, _, doc)| doc.clone()).collect(), 5)
+//!     .await?;
+//!
+//! for result in results {
+//!     println!("{}: {}", result.relevance_score, candidates[result.index].2);
+//! }
+//! ```
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RerankError {
+    /// Http error (e.g.: connection error, timeout, etc.)
+    #[error("HttpError: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    /// Json error (e.g.: serialization, deserialization)
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// Error parsing the rerank response
+    #[error("ResponseError: {0}")]
+    ResponseError(String),
+
+    /// Error returned by the reranking model provider
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
+
+    /// Error returned by the reranking model provider, with its status and code (see
+    /// [rig::Error](crate::Error) to classify it)
+    #[error("ApiError: {0}")]
+    ApiError(#[from] crate::error::ApiError),
+}
+
+/// Relevance of a document to the query of a rerank request.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RerankResult {
+    /// Index of the document in the documents of the request
+    pub index: usize,
+    /// Relevance score of the document (higher is more relevant)
+    pub relevance_score: f64,
+}
+
+/// Trait for reranking models that order documents by relevance to a query.
+pub trait Reranker: Send + Sync {
+    /// Rerank `documents` by relevance to `query` and return the `top_n` most relevant ones,
+    /// from most to least relevant.
+    fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<RerankResult>, RerankError>> + Send;
+}
+
+/// Dyn-compatible version of [Reranker], used to store rerankers of different types
+/// (e.g.: in an [Agent](crate::agent::Agent), see [AgentBuilder::dynamic_context_reranked](crate::agent::AgentBuilder::dynamic_context_reranked)).
+pub trait RerankerDyn: Send + Sync {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> BoxFuture<'a, Result<Vec<RerankResult>, RerankError>>;
+}
+
+impl<R: Reranker> RerankerDyn for R {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> BoxFuture<'a, Result<Vec<RerankResult>, RerankError>> {
+        Box::pin(Reranker::rerank(self, query, documents, top_n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::AgentBuilder,
+        completion::{CompletionError, Prompt, PromptError},
+        providers::mock::{MockCompletionModel, MockVectorStore},
+    };
+
+    fn words(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect()
+    }
+
+    /// Reranker scoring the documents by the number of words of the query they contain. It
+    /// returns all the documents (and an invalid index), whatever `top_n`, to check that
+    /// agents only keep the valid results they asked for.
+    struct KeywordReranker;
+
+    impl Reranker for KeywordReranker {
+        async fn rerank(
+            &self,
+            query: &str,
+            documents: Vec<String>,
+            _top_n: usize,
+        ) -> Result<Vec<RerankResult>, RerankError> {
+            let query = words(query);
+            let mut results = documents
+                .iter()
+                .enumerate()
+                .map(|(index, document)| RerankResult {
+                    index,
+                    relevance_score: words(document)
+                        .iter()
+                        .filter(|word| query.contains(word))
+                        .count() as f64,
+                })
+                .collect::<Vec<_>>();
+            results.insert(
+                0,
+                RerankResult {
+                    index: documents.len(),
+                    relevance_score: f64::MAX,
+                },
+            );
+            results.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+            Ok(results)
+        }
+    }
+
+    struct FailingReranker;
+
+    impl Reranker for FailingReranker {
+        async fn rerank(
+            &self,
+            _query: &str,
+            _documents: Vec<String>,
+            _top_n: usize,
+        ) -> Result<Vec<RerankResult>, RerankError> {
+            Err(RerankError::ProviderError("Rate limited".to_string()))
+        }
+    }
+
+    fn store() -> MockVectorStore {
+        MockVectorStore::new()
+            .scored_document(0.9, "doc0", "Cats are pets")
+            .scored_document(0.8, "doc1", "Flurbos are green")
+            .scored_document(0.7, "doc2", "A flurbo is a green alien")
+            .scored_document(0.6, "doc3", "Stock prices fell")
+            .scored_document(
+                0.5,
+                "doc4",
+                "What is a green flurbo alien? A green flurbo alien",
+            )
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_context_reranked() {
+        let model = MockCompletionModel::new().text("A green alien");
+        let agent = AgentBuilder::new(model.clone())
+            .dynamic_context_reranked(2, 4, store(), KeywordReranker)
+            .build();
+
+        assert_eq!(
+            agent.prompt("What is a green flurbo alien?").await.unwrap(),
+            "A green alien"
+        );
+
+        // The 4 candidates are reordered by the reranker and truncated to 2 documents: doc4 is
+        // not a candidate, and the invalid index is ignored
+        let ids = model.requests()[0]
+            .documents
+            .iter()
+            .map(|document| document.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["doc2", "doc1"]);
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_context_reranked_error() {
+        let model = MockCompletionModel::new().text("A green alien");
+        let agent = AgentBuilder::new(model.clone())
+            .dynamic_context_reranked(2, 4, store(), FailingReranker)
+            .build();
+
+        assert!(matches!(
+            agent.prompt("What is a flurbo?").await,
+            Err(PromptError::CompletionError(CompletionError::RequestError(
+                _
+            )))
+        ));
+        assert!(model.requests().is_empty());
+    }
+}
@@ -0,0 +1,527 @@
+//! This module provides the [Router], which dispatches prompts to one of several agents: a
+//! [RouteClassifier] picks the route of each prompt from the descriptions of the routes, and
+//! the prompt is sent to the agent of that route.
+//!
+//! Two classifiers are provided:
+//! - [LlmClassifier]: prompts a completion model (typically a cheap one) with the descriptions
+//!   of the routes and the prompt, and asks for the name of the route.
+//! - [EmbeddingClassifier]: picks the route whose description is the most similar to the prompt
+//!   (cosine similarity of their embeddings), without any completion request.
+//!
+//! Prompts matching no route are sent to the fallback agent of the router, if any (see
+//! [RouterBuilder::fallback]), or fail with [RouterError::NoRoute].
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     providers::openai,
+//!     router::{LlmClassifier, RouterBuilder},
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let billing_agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You answer questions about invoices and payments.")
+//!     .build();
+//! let support_agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You help users troubleshoot the product.")
+//!     .build();
+//!
+//! let router = RouterBuilder::new(LlmClassifier::new(openai.completion_model(openai::GPT_4O_MINI)))
+//!     .route("billing", "Questions about invoices, payments and refunds", billing_agent)
+//!     .route("support", "Technical issues and bug reports", support_agent.clone())
+//!     .fallback("support", support_agent)
+//!     .build();
+//!
+//! let response = router.prompt("Why was I charged twice this month?").await?;
+//! assert_eq!(response.route, "billing");
+//! println!("{}", response.text);
+//! ```
+use std::{collections::HashMap, sync::RwLock};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    completion::{Chat, CompletionError, CompletionModel, Message, PromptError},
+    embeddings::{distance::VectorDistance, Embedding, EmbeddingError, EmbeddingModel},
+    message::AssistantContent,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouterError {
+    /// Error of the completion model classifying the prompt
+    #[error("CompletionError: {0}")]
+    CompletionError(#[from] CompletionError),
+
+    /// Error of the embedding model classifying the prompt
+    #[error("EmbeddingError: {0}")]
+    EmbeddingError(#[from] EmbeddingError),
+
+    /// Error of the agent of the route
+    #[error("PromptError: {0}")]
+    PromptError(#[from] PromptError),
+
+    /// The prompt matches no route and the router has no fallback
+    #[error("NoRouteError: no route matches the prompt")]
+    NoRoute,
+}
+
+/// A route of a [Router]: its name and the description of the prompts it handles, from which
+/// the classifiers pick the route of a prompt.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    pub name: String,
+    pub description: String,
+}
+
+/// Trait for classifiers picking the route of a prompt.
+pub trait RouteClassifier: Send + Sync {
+    /// Pick the route of `prompt` among `routes`, returning its index, or `None` if the prompt
+    /// matches no route.
+    fn classify(
+        &self,
+        prompt: &str,
+        routes: &[Route],
+    ) -> impl std::future::Future<Output = Result<Option<usize>, RouterError>> + Send;
+}
+
+/// Dyn-compatible version of [Chat], used to store agents of different types (e.g.: the agents
+/// of the routes of a [Router]).
+pub trait ChatDyn: Send + Sync {
+    fn chat(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>>;
+}
+
+impl<C: Chat> ChatDyn for C {
+    fn chat(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(Chat::chat(self, prompt, chat_history))
+    }
+}
+
+/// Answer of a [Router], with the route taken.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoutedResponse {
+    /// Name of the route the prompt was sent to (the name of the fallback route if the prompt
+    /// matched no route)
+    pub route: String,
+    /// Answer of the agent of the route
+    pub text: String,
+}
+
+/// Router dispatching prompts to the agents of its routes (see the [module](self) documentation).
+pub struct Router<C: RouteClassifier> {
+    classifier: C,
+    routes: Vec<Route>,
+    agents: Vec<Box<dyn ChatDyn>>,
+    fallback: Option<(String, Box<dyn ChatDyn>)>,
+}
+
+impl<C: RouteClassifier> Router<C> {
+    /// The routes of the router
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Name of the route of `prompt` (the name of the fallback route if the prompt matches no
+    /// route), without sending the prompt to its agent.
+    pub async fn route(&self, prompt: &str) -> Result<String, RouterError> {
+        let (route, _) = self.select(prompt).await?;
+        Ok(route.to_string())
+    }
+
+    /// Send `prompt` to the agent of its route.
+    pub async fn prompt(&self, prompt: &str) -> Result<RoutedResponse, RouterError> {
+        self.chat(prompt, vec![]).await
+    }
+
+    /// Send `prompt`, with the chat history `chat_history`, to the agent of its route. The route
+    /// is picked from the prompt only.
+    pub async fn chat(
+        &self,
+        prompt: &str,
+        chat_history: Vec<Message>,
+    ) -> Result<RoutedResponse, RouterError> {
+        let (route, agent) = self.select(prompt).await?;
+        let text = agent.chat(prompt.into(), chat_history).await?;
+
+        Ok(RoutedResponse {
+            route: route.to_string(),
+            text,
+        })
+    }
+
+    /// Name and agent of the route of `prompt`.
+    async fn select(&self, prompt: &str) -> Result<(&str, &dyn ChatDyn), RouterError> {
+        let selected = self
+            .classifier
+            .classify(prompt, &self.routes)
+            .await?
+            .and_then(|index| Some((self.routes.get(index)?, self.agents.get(index)?)));
+
+        match (selected, &self.fallback) {
+            (Some((route, agent)), _) => {
+                tracing::info!(target: "rig", "Routing prompt to {}", route.name);
+                Ok((&route.name, agent.as_ref()))
+            }
+            (None, Some((name, agent))) => {
+                tracing::info!(target: "rig",
+                    "No route matches the prompt, routing it to the fallback {}",
+                    name
+                );
+                Ok((name, agent.as_ref()))
+            }
+            (None, None) => Err(RouterError::NoRoute),
+        }
+    }
+}
+
+/// Builder of [Router].
+pub struct RouterBuilder<C: RouteClassifier> {
+    classifier: C,
+    routes: Vec<Route>,
+    agents: Vec<Box<dyn ChatDyn>>,
+    fallback: Option<(String, Box<dyn ChatDyn>)>,
+}
+
+impl<C: RouteClassifier> RouterBuilder<C> {
+    pub fn new(classifier: C) -> Self {
+        Self {
+            classifier,
+            routes: vec![],
+            agents: vec![],
+            fallback: None,
+        }
+    }
+
+    /// Add a route named `name`, sending the prompts matching `description` to `agent`.
+    pub fn route(mut self, name: &str, description: &str, agent: impl Chat + 'static) -> Self {
+        self.routes.push(Route {
+            name: name.to_string(),
+            description: description.to_string(),
+        });
+        self.agents.push(Box::new(agent));
+        self
+    }
+
+    /// Send the prompts matching no route to `agent`, reporting them as routed to `name`.
+    /// By default, such prompts fail with [RouterError::NoRoute].
+    pub fn fallback(mut self, name: &str, agent: impl Chat + 'static) -> Self {
+        self.fallback = Some((name.to_string(), Box::new(agent)));
+        self
+    }
+
+    pub fn build(self) -> Router<C> {
+        Router {
+            classifier: self.classifier,
+            routes: self.routes,
+            agents: self.agents,
+            fallback: self.fallback,
+        }
+    }
+}
+
+/// Answer of the model to prompts matching no route
+const NO_ROUTE: &str = "NONE";
+
+/// Classifier prompting a completion model (typically a cheap one) with the descriptions of the
+/// routes, asking for the name of the route of the prompt.
+#[derive(Clone)]
+pub struct LlmClassifier<M: CompletionModel> {
+    model: M,
+}
+
+impl<M: CompletionModel> LlmClassifier<M> {
+    pub fn new(model: M) -> Self {
+        Self { model }
+    }
+}
+
+impl<M: CompletionModel> RouteClassifier for LlmClassifier<M> {
+    async fn classify(&self, prompt: &str, routes: &[Route]) -> Result<Option<usize>, RouterError> {
+        let descriptions = routes
+            .iter()
+            .map(|route| format!("- {}: {}", route.name, route.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let request = format!(
+            "Classify the request below into one of the following routes:\n{descriptions}\n\n\
+             Answer with the name of the route only, or {NO_ROUTE} if the request matches none \
+             of them.\n\nRequest: {prompt}"
+        );
+
+        let response = self
+            .model
+            .completion_request(request)
+            .temperature(0.0)
+            .send()
+            .await?;
+        let answer = response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                AssistantContent::ToolCall(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let answer = answer
+            .trim()
+            .trim_matches(|c: char| c == '`' || c == '"' || c == '\'' || c == '.');
+
+        if let Some(index) = routes
+            .iter()
+            .position(|route| route.name.eq_ignore_ascii_case(answer))
+        {
+            return Ok(Some(index));
+        }
+
+        // Models sometimes wrap the name of the route in a sentence: pick the longest route
+        // mentioned (e.g.: "tech support" rather than "tech"), unless other routes are mentioned
+        // too
+        let mentioned = routes
+            .iter()
+            .enumerate()
+            .filter(|(_, route)| mentions(answer, &route.name))
+            .collect::<Vec<_>>();
+        let Some((index, longest)) = mentioned.iter().max_by_key(|(_, route)| route.name.len())
+        else {
+            return Ok(None);
+        };
+        if mentioned
+            .iter()
+            .all(|(_, route)| mentions(&longest.name, &route.name))
+        {
+            Ok(Some(*index))
+        } else {
+            tracing::warn!(target: "rig", "Ambiguous route classification: {answer}");
+            Ok(None)
+        }
+    }
+}
+
+/// Whether `text` mentions `name` as whole words, ignoring case (e.g.: "support" is mentioned
+/// by "the route is support", not by "supportive").
+fn mentions(text: &str, name: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let text = text.to_lowercase();
+    let name = name.to_lowercase();
+
+    !name.is_empty()
+        && text.match_indices(&name).any(|(start, _)| {
+            !text[..start].chars().next_back().is_some_and(is_word)
+                && !text[start + name.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(is_word)
+        })
+}
+
+/// Classifier picking the route whose description is the most similar to the prompt (cosine
+/// similarity of their embeddings). The embeddings of the descriptions are computed once, on the
+/// first classification.
+///
+/// #### Default Values
+/// - `threshold`: none (prompts always match their most similar route)
+pub struct EmbeddingClassifier<M: EmbeddingModel> {
+    model: M,
+    threshold: Option<f64>,
+    /// Embeddings of the descriptions of the routes, by description
+    descriptions: RwLock<HashMap<String, Embedding>>,
+}
+
+impl<M: EmbeddingModel> EmbeddingClassifier<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            threshold: None,
+            descriptions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Set the similarity (between -1.0 and 1.0) below which prompts match no route.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Embed the descriptions of `routes` which were not embedded yet.
+    async fn embed_descriptions(&self, routes: &[Route]) -> Result<(), EmbeddingError> {
+        let missing = {
+            let descriptions = self.descriptions.read().expect("Lock poisoned");
+            routes
+                .iter()
+                .map(|route| route.description.clone())
+                .filter(|description| !descriptions.contains_key(description))
+                .collect::<Vec<_>>()
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let embeddings = self.model.embed_texts(missing.clone()).await?;
+        self.descriptions
+            .write()
+            .expect("Lock poisoned")
+            .extend(missing.into_iter().zip(embeddings));
+        Ok(())
+    }
+}
+
+impl<M: EmbeddingModel> RouteClassifier for EmbeddingClassifier<M> {
+    async fn classify(&self, prompt: &str, routes: &[Route]) -> Result<Option<usize>, RouterError> {
+        self.embed_descriptions(routes).await?;
+        let prompt = self.model.embed_text(prompt).await?;
+
+        let descriptions = self.descriptions.read().expect("Lock poisoned");
+        let best = routes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, route)| {
+                let description = descriptions.get(&route.description)?;
+                Some((index, prompt.cosine_similarity(description, false)))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        Ok(best
+            .filter(|(_, similarity)| {
+                self.threshold
+                    .is_none_or(|threshold| *similarity >= threshold)
+            })
+            .map(|(index, _)| index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EmbeddingClassifier, LlmClassifier, Route, RouteClassifier, RouterBuilder, RouterError,
+    };
+    use crate::{
+        agent::AgentBuilder,
+        providers::mock::{MockCompletionModel, MockEmbeddingModel},
+    };
+
+    #[tokio::test]
+    async fn test_llm_router() {
+        let classifier = MockCompletionModel::new()
+            .text("billing")
+            .text("The route is `support`.")
+            .text("NONE");
+        let billing = MockCompletionModel::new().fallback_text("Refunded.");
+        let support = MockCompletionModel::new().fallback_text("Restart it.");
+
+        let router = RouterBuilder::new(LlmClassifier::new(classifier.clone()))
+            .route(
+                "billing",
+                "Questions about invoices and payments",
+                AgentBuilder::new(billing.clone()).build(),
+            )
+            .route(
+                "support",
+                "Technical issues",
+                AgentBuilder::new(support.clone()).build(),
+            )
+            .build();
+
+        let response = router.prompt("I was charged twice").await.unwrap();
+        assert_eq!(
+            (response.route.as_str(), response.text.as_str()),
+            ("billing", "Refunded.")
+        );
+        let response = router.prompt("The app crashes").await.unwrap();
+        assert_eq!(
+            (response.route.as_str(), response.text.as_str()),
+            ("support", "Restart it.")
+        );
+        assert!(matches!(
+            router.prompt("Tell me a joke").await,
+            Err(RouterError::NoRoute)
+        ));
+
+        assert_eq!(billing.requests().len(), 1);
+        assert_eq!(support.requests().len(), 1);
+        // The classifier is prompted with the descriptions of the routes
+        assert!(format!("{:?}", classifier.requests()[0].prompt)
+            .contains("billing: Questions about invoices and payments"));
+    }
+
+    #[tokio::test]
+    async fn test_llm_classifier_mentions() {
+        let routes = ["tech", "tech support", "billing"]
+            .into_iter()
+            .map(|name| Route {
+                name: name.to_string(),
+                description: String::new(),
+            })
+            .collect::<Vec<_>>();
+        let classify = |answer: &str| {
+            let classifier = LlmClassifier::new(MockCompletionModel::new().text(answer));
+            let routes = &routes;
+            async move { classifier.classify("prompt", routes).await.unwrap() }
+        };
+
+        // The longest route mentioned wins
+        assert_eq!(classify("The route is tech support.").await, Some(1));
+        assert_eq!(classify("Tech").await, Some(0));
+        // Routes are only matched as whole words
+        assert_eq!(classify("Billings").await, None);
+        assert_eq!(classify("technical").await, None);
+        // Several routes mentioned are ambiguous
+        assert_eq!(classify("Either billing or tech support").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_router() {
+        let model = MockEmbeddingModel::new(2)
+            .embedding("Invoices and payments", vec![1.0, 0.0])
+            .embedding("Technical issues", vec![0.0, 1.0])
+            .embedding("I was charged twice", vec![0.9, 0.1])
+            .embedding("Tell me a joke", vec![-1.0, 0.0]);
+
+        let router = RouterBuilder::new(EmbeddingClassifier::new(model.clone()).threshold(0.5))
+            .route(
+                "billing",
+                "Invoices and payments",
+                AgentBuilder::new(MockCompletionModel::new().fallback_text("Refunded.")).build(),
+            )
+            .route(
+                "support",
+                "Technical issues",
+                AgentBuilder::new(MockCompletionModel::new()).build(),
+            )
+            .fallback(
+                "small_talk",
+                AgentBuilder::new(MockCompletionModel::new().fallback_text("Knock knock.")).build(),
+            )
+            .build();
+
+        let response = router.prompt("I was charged twice").await.unwrap();
+        assert_eq!(
+            (response.route.as_str(), response.text.as_str()),
+            ("billing", "Refunded.")
+        );
+        assert_eq!(router.route("Tell me a joke").await.unwrap(), "small_talk");
+
+        // The descriptions are embedded once
+        assert_eq!(
+            model.requests(),
+            vec![
+                vec![
+                    "Invoices and payments".to_string(),
+                    "Technical issues".to_string()
+                ],
+                vec!["I was charged twice".to_string()],
+                vec!["Tell me a joke".to_string()],
+            ]
+        );
+    }
+}
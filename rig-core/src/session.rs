@@ -0,0 +1,140 @@
+//! This module provides the [SessionStore] trait, implemented by durable stores of the chat
+//! histories of conversations identified by a session id, and [InMemorySessionStore].
+//!
+//! With a session store, a web backend only needs the session id of a request to continue its
+//! conversation with [Agent::chat_with_session](crate::agent::Agent::chat_with_session), which
+//! loads the chat history of the session, prompts the agent and appends the exchange to it.
+//!
+//! Stores backed by databases are provided by companion crates (e.g.: `rig-redis`'s
+//! `RedisSessionStore` and `rig-postgres`'s `PostgresSessionStore`).
+//!
+//! # Example
+//! ```rust
+//! use rig::{providers::openai, session::{InMemorySessionStore, SessionStore}};
+//!
+//! let openai = openai::Client::from_env();
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//!
+//! let store = InMemorySessionStore::new();
+//!
+//! agent.chat_with_session(&store, "user-42", "My name is Alice.").await?;
+//! let response = agent.chat_with_session(&store, "user-42", "What is my name?").await?;
+//!
+//! // Only keep the last 20 messages of the session
+//! store.trim("user-42", 20).await?;
+//! ```
+use std::collections::HashMap;
+
+use futures::lock::Mutex;
+
+use crate::completion::Message;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    /// Json error (e.g.: serialization, deserialization, etc.)
+    #[error("Json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Datastore error: {0}")]
+    DatastoreError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+/// Trait for stores of the chat histories of sessions.
+pub trait SessionStore: Send + Sync {
+    /// Get the messages of the session `session_id`, from oldest to newest (none if the session
+    /// does not exist).
+    fn get(
+        &self,
+        session_id: &str,
+    ) -> impl std::future::Future<Output = Result<Vec<Message>, SessionError>> + Send;
+
+    /// Append `messages` to the session `session_id`, creating it if needed.
+    fn append(
+        &self,
+        session_id: &str,
+        messages: Vec<Message>,
+    ) -> impl std::future::Future<Output = Result<(), SessionError>> + Send;
+
+    /// Only keep the last `max_messages` messages of the session `session_id`.
+    fn trim(
+        &self,
+        session_id: &str,
+        max_messages: usize,
+    ) -> impl std::future::Future<Output = Result<(), SessionError>> + Send;
+}
+
+/// Session store keeping the chat histories in memory (e.g.: for tests, or single-process
+/// applications whose conversations do not need to survive restarts).
+#[derive(Debug, Default)]
+pub struct InMemorySessionStore {
+    sessions: Mutex<HashMap<String, Vec<Message>>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for InMemorySessionStore {
+    async fn get(&self, session_id: &str) -> Result<Vec<Message>, SessionError> {
+        Ok(self
+            .sessions
+            .lock()
+            .await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn append(&self, session_id: &str, messages: Vec<Message>) -> Result<(), SessionError> {
+        self.sessions
+            .lock()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .extend(messages);
+        Ok(())
+    }
+
+    async fn trim(&self, session_id: &str, max_messages: usize) -> Result<(), SessionError> {
+        if let Some(messages) = self.sessions.lock().await.get_mut(session_id) {
+            let excess = messages.len().saturating_sub(max_messages);
+            messages.drain(..excess);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemorySessionStore, SessionStore};
+    use crate::completion::Message;
+
+    #[tokio::test]
+    async fn test_in_memory_session_store() {
+        let store = InMemorySessionStore::new();
+        store
+            .append("a", vec![Message::user("Hello"), Message::assistant("Hi!")])
+            .await
+            .unwrap();
+        store
+            .append("a", vec![Message::user("How are you?")])
+            .await
+            .unwrap();
+
+        assert_eq!(store.get("a").await.unwrap().len(), 3);
+        assert!(store.get("b").await.unwrap().is_empty());
+
+        store.trim("a", 2).await.unwrap();
+        assert_eq!(
+            store.get("a").await.unwrap(),
+            vec![Message::assistant("Hi!"), Message::user("How are you?")]
+        );
+
+        store.trim("a", 5).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap().len(), 2);
+    }
+}
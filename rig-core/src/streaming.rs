@@ -8,10 +8,13 @@
 //! - [StreamingCompletion]: Defines a low-level streaming LLM completion interface
 //! - [StreamingCompletionModel]: Defines a streaming completion model interface
 //!
+//! Agents also stream the events of their loop, tool calls included, as [AgentEvent]s
+//! (see [Agent::stream_events]).
 
 use crate::agent::Agent;
 use crate::completion::{
     CompletionError, CompletionModel, CompletionRequest, CompletionRequestBuilder, Message,
+    PromptError, TokenUsage,
 };
 use futures::{Stream, StreamExt};
 use std::boxed::Box;
@@ -27,6 +30,18 @@ pub enum StreamingChoice {
 
     /// A tool call response chunk
     ToolCall(String, String, serde_json::Value),
+
+    /// A fragment of the arguments of a tool call, as emitted by the model:
+    /// (tool call index, tool name, arguments fragment).
+    /// Only emitted by providers streaming tool call arguments incrementally (e.g.: OpenAI).
+    /// The complete tool call is still yielded as a [StreamingChoice::ToolCall] once all
+    /// fragments have been received.
+    ToolCallDelta(usize, String, String),
+
+    /// The token usage of the completion, emitted at the end of the stream by providers
+    /// reporting it (e.g.: OpenAI, Anthropic). When emitted more than once, the last usage
+    /// is the usage of the whole completion.
+    Usage(TokenUsage),
 }
 
 impl Display for StreamingChoice {
@@ -36,6 +51,12 @@ impl Display for StreamingChoice {
             StreamingChoice::ToolCall(name, id, params) => {
                 write!(f, "Tool call: {} {} {:?}", name, id, params)
             }
+            StreamingChoice::ToolCallDelta(_, _, arguments) => write!(f, "{}", arguments),
+            StreamingChoice::Usage(usage) => write!(
+                f,
+                "Usage: {} input tokens, {} output tokens",
+                usage.input_tokens, usage.output_tokens
+            ),
         }
     }
 }
@@ -47,6 +68,40 @@ pub type StreamingResult =
 #[cfg(target_arch = "wasm32")]
 pub type StreamingResult = Pin<Box<dyn Stream<Item = Result<StreamingChoice, CompletionError>>>>;
 
+/// Event of the loop of an agent, streamed by [Agent::stream_events] so that chat UIs can render
+/// the answer and the intermediate tool activity as they happen.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentEvent {
+    /// A text chunk from the model
+    TextDelta(String),
+
+    /// The model called a tool, which is about to be run
+    ToolCallStarted {
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+
+    /// The output of a tool call, sent back to the model
+    ToolResult {
+        id: String,
+        name: String,
+        result: String,
+    },
+
+    /// The final answer of the agent: the text of its last turn, which did not call any tool.
+    /// Always the last event of the stream.
+    FinalResponse(String),
+}
+
+/// Stream of the events of the loop of an agent (see [Agent::stream_events]).
+#[cfg(not(target_arch = "wasm32"))]
+pub type AgentEventStream<'a> =
+    Pin<Box<dyn Stream<Item = Result<AgentEvent, PromptError>> + Send + 'a>>;
+
+#[cfg(target_arch = "wasm32")]
+pub type AgentEventStream<'a> = Pin<Box<dyn Stream<Item = Result<AgentEvent, PromptError>> + 'a>>;
+
 /// Trait for high-level streaming prompt interface
 pub trait StreamingPrompt: Send + Sync {
     /// Stream a simple prompt to the model
@@ -79,6 +134,14 @@ pub trait StreamingCompletion<M: StreamingCompletionModel> {
 /// Trait defining a streaming completion model
 pub trait StreamingCompletionModel: CompletionModel {
     /// Stream a completion response for the given request
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> impl Future<Output = Result<StreamingResult, CompletionError>> + Send;
+
+    /// Stream a completion response for the given request
+    #[cfg(target_arch = "wasm32")]
     fn stream(
         &self,
         request: CompletionRequest,
@@ -105,6 +168,7 @@ pub async fn stream_to_stdout<M: StreamingCompletionModel>(
                     .map_err(|e| std::io::Error::other(e.to_string()))?;
                 println!("\nResult: {}", res);
             }
+            Ok(StreamingChoice::ToolCallDelta(..) | StreamingChoice::Usage(_)) => {}
             Err(e) => {
                 eprintln!("Error: {}", e);
                 break;
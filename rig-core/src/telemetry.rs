@@ -0,0 +1,336 @@
+//! This module defines the `tracing` spans emitted by rig, so that agents can be observed in
+//! production (e.g.: exported to an OpenTelemetry collector).
+//!
+//! The following spans are emitted (all with the `rig` target):
+//! - `chat`: a completion request (see [CompletionRequestBuilder::send](crate::completion::CompletionRequestBuilder::send)),
+//!   with the model name, request parameters, token usage and latency.
+//! - `embeddings`: an embedding request (see [EmbeddingModel::embed_text](crate::embeddings::EmbeddingModel::embed_text)
+//!   and [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder)), with the model name, the
+//!   number of texts embedded and latency.
+//! - `vector_search`: a vector search for the dynamic context or dynamic tools of an
+//!   [Agent](crate::agent::Agent), with the number of results requested and returned, their
+//!   scores and latency.
+//! - `execute_tool`: a tool call made by an [Agent](crate::agent::Agent), with the tool name,
+//!   the tool call id and latency.
+//!
+//! The fields of the spans follow the [OpenTelemetry semantic conventions for generative AI](https://opentelemetry.io/docs/specs/semconv/gen-ai/)
+//! (see [attributes]) where they exist, and are prefixed with `rig.` otherwise. The spans also
+//! carry the `otel.name`, `otel.kind`, `otel.status_code` and `otel.status_message` fields, which
+//! `tracing-opentelemetry` uses to name the exported spans and set their status, so that the
+//! spans can be exported to any OTLP-compatible backend without further mapping. Other
+//! subscribers simply record these fields as is.
+//!
+//! # Example
+//! ```rust
+//! use opentelemetry::trace::TracerProvider;
+//! use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+//!
+//! let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+//!     .with_batch_exporter(opentelemetry_otlp::SpanExporter::builder().with_tonic().build()?)
+//!     .build();
+//!
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::filter::Targets::new().with_target("rig", tracing::Level::INFO))
+//!     .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("my-agent")))
+//!     .init();
+//!
+//! // Completions, embeddings, vector searches and tool calls of the agent are now exported
+//! let response = agent.prompt("What is 2 + 3?").await?;
+//! ```
+use std::{fmt::Display, future::Future};
+
+use tracing::{field::Empty, Instrument, Span};
+use web_time::Instant;
+
+use crate::completion::TokenUsage;
+
+/// Names of the span fields, following the OpenTelemetry semantic conventions for generative AI.
+pub mod attributes {
+    /// Name of the operation (`chat`, `embeddings` or `execute_tool`)
+    pub const GEN_AI_OPERATION_NAME: &str = "gen_ai.operation.name";
+    /// Name of the model of the request
+    pub const GEN_AI_REQUEST_MODEL: &str = "gen_ai.request.model";
+    /// Temperature of a completion request
+    pub const GEN_AI_REQUEST_TEMPERATURE: &str = "gen_ai.request.temperature";
+    /// Maximum number of tokens of a completion request
+    pub const GEN_AI_REQUEST_MAX_TOKENS: &str = "gen_ai.request.max_tokens";
+    /// Number of input tokens of a completion, as reported by the provider
+    pub const GEN_AI_USAGE_INPUT_TOKENS: &str = "gen_ai.usage.input_tokens";
+    /// Number of output tokens of a completion, as reported by the provider
+    pub const GEN_AI_USAGE_OUTPUT_TOKENS: &str = "gen_ai.usage.output_tokens";
+    /// Name of the tool called
+    pub const GEN_AI_TOOL_NAME: &str = "gen_ai.tool.name";
+    /// Id of the tool call
+    pub const GEN_AI_TOOL_CALL_ID: &str = "gen_ai.tool.call.id";
+    /// Number of texts embedded by an embedding request
+    pub const RIG_EMBEDDINGS_COUNT: &str = "rig.embeddings.count";
+    /// Number of results requested from a vector search
+    pub const RIG_VECTOR_SEARCH_TOP_N: &str = "rig.vector_search.top_n";
+    /// Number of results returned by a vector search
+    pub const RIG_VECTOR_SEARCH_RESULTS: &str = "rig.vector_search.results";
+    /// Scores of the results of a vector search, from most to least relevant
+    pub const RIG_VECTOR_SEARCH_SCORES: &str = "rig.vector_search.scores";
+    /// Duration of the operation in milliseconds
+    pub const RIG_LATENCY_MS: &str = "rig.latency_ms";
+}
+
+/// Span of a completion request.
+pub(crate) fn chat_span(
+    model: Option<&str>,
+    temperature: Option<f64>,
+    max_tokens: Option<u64>,
+) -> Span {
+    let model = model.unwrap_or_default();
+    tracing::info_span!(
+        target: "rig",
+        "chat",
+        otel.name = format!("chat {model}").trim_end(),
+        otel.kind = "client",
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        gen_ai.operation.name = "chat",
+        gen_ai.request.model = model,
+        gen_ai.request.temperature = temperature,
+        gen_ai.request.max_tokens = max_tokens,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        rig.latency_ms = Empty,
+    )
+}
+
+/// Span of an embedding request of `count` texts.
+pub(crate) fn embeddings_span(model: Option<&str>, count: usize) -> Span {
+    let model = model.unwrap_or_default();
+    tracing::info_span!(
+        target: "rig",
+        "embeddings",
+        otel.name = format!("embeddings {model}").trim_end(),
+        otel.kind = "client",
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        gen_ai.operation.name = "embeddings",
+        gen_ai.request.model = model,
+        rig.embeddings.count = count,
+        rig.latency_ms = Empty,
+    )
+}
+
+/// Span of a vector search of the top `top_n` results.
+fn vector_search_span(top_n: usize) -> Span {
+    tracing::info_span!(
+        target: "rig",
+        "vector_search",
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        rig.vector_search.top_n = top_n,
+        rig.vector_search.results = Empty,
+        rig.vector_search.scores = Empty,
+        rig.latency_ms = Empty,
+    )
+}
+
+/// Span of a tool call.
+pub(crate) fn tool_span(name: &str, call_id: &str) -> Span {
+    tracing::info_span!(
+        target: "rig",
+        "execute_tool",
+        otel.name = format!("execute_tool {name}"),
+        otel.kind = "internal",
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+        gen_ai.operation.name = "execute_tool",
+        gen_ai.tool.name = name,
+        gen_ai.tool.call.id = call_id,
+        rig.latency_ms = Empty,
+    )
+}
+
+/// Run a vector search (returning results from which `score` extracts the score), instrumented
+/// with a `vector_search` span.
+pub(crate) async fn vector_search<R, E: Display>(
+    top_n: usize,
+    search: impl Future<Output = Result<Vec<R>, E>>,
+    score: impl Fn(&R) -> f64,
+) -> Result<Vec<R>, E> {
+    let span = vector_search_span(top_n);
+
+    let start = Instant::now();
+    let result = search.instrument(span.clone()).await;
+
+    record_result(&span, start, &result);
+    if let Ok(results) = &result {
+        record_scores(&span, &results.iter().map(score).collect::<Vec<_>>());
+    }
+    result
+}
+
+/// Record the token usage of a completion on its span.
+pub(crate) fn record_usage(span: &Span, usage: Option<TokenUsage>) {
+    if let Some(usage) = usage {
+        span.record(attributes::GEN_AI_USAGE_INPUT_TOKENS, usage.input_tokens);
+        span.record(attributes::GEN_AI_USAGE_OUTPUT_TOKENS, usage.output_tokens);
+    }
+}
+
+/// Record the scores of the results of a vector search on its span.
+fn record_scores(span: &Span, scores: &[f64]) {
+    span.record(attributes::RIG_VECTOR_SEARCH_RESULTS, scores.len());
+    span.record(
+        attributes::RIG_VECTOR_SEARCH_SCORES,
+        tracing::field::debug(scores),
+    );
+}
+
+/// Record the latency of an operation started at `start` and its outcome on its span.
+pub(crate) fn record_result<T, E: Display>(span: &Span, start: Instant, result: &Result<T, E>) {
+    span.record(
+        attributes::RIG_LATENCY_MS,
+        start.elapsed().as_millis() as u64,
+    );
+    match result {
+        Ok(_) => span.record("otel.status_code", "OK"),
+        Err(error) => span
+            .record("otel.status_code", "ERROR")
+            .record("otel.status_message", error.to_string()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+    use super::*;
+    use crate::{
+        completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
+        message::AssistantContent,
+        OneOrMany,
+    };
+
+    type Spans = Arc<Mutex<HashMap<u64, (&'static str, HashMap<String, String>)>>>;
+
+    /// Layer recording the name and fields of all spans.
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: Spans,
+    }
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.spans
+                .lock()
+                .unwrap()
+                .insert(id.into_u64(), (attrs.metadata().name(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            if let Some((_, fields)) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+                values.record(&mut FieldVisitor(fields));
+            }
+        }
+    }
+
+    impl SpanRecorder {
+        fn span(&self, name: &str) -> HashMap<String, String> {
+            self.spans
+                .lock()
+                .unwrap()
+                .values()
+                .find(|(span_name, _)| *span_name == name)
+                .map(|(_, fields)| fields.clone())
+                .unwrap_or_else(|| panic!("No {name} span"))
+        }
+    }
+
+    #[derive(Clone)]
+    struct Model;
+
+    impl CompletionModel for Model {
+        type Response = ();
+
+        fn model_name(&self) -> Option<&str> {
+            Some("mock-model")
+        }
+
+        fn token_usage(&self, _response: &()) -> Option<TokenUsage> {
+            Some(TokenUsage {
+                input_tokens: 12,
+                output_tokens: 3,
+                total_tokens: 15,
+                ..Default::default()
+            })
+        }
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text("Hello!")),
+                raw_response: (),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spans() {
+        let recorder = SpanRecorder::default();
+        let _guard = tracing_subscriber::registry()
+            .with(recorder.clone())
+            .set_default();
+
+        Model
+            .completion_request("Hi")
+            .temperature(0.5)
+            .send()
+            .await
+            .unwrap();
+
+        let chat = recorder.span("chat");
+        assert_eq!(chat["otel.name"], "chat mock-model");
+        assert_eq!(chat["otel.status_code"], "OK");
+        assert_eq!(chat["gen_ai.request.model"], "mock-model");
+        assert_eq!(chat["gen_ai.request.temperature"], "0.5");
+        assert_eq!(chat["gen_ai.usage.input_tokens"], "12");
+        assert_eq!(chat["gen_ai.usage.output_tokens"], "3");
+        assert!(chat.contains_key("rig.latency_ms"));
+
+        let results = vector_search(
+            3,
+            async { Ok::<_, CompletionError>(vec![(0.9, "a"), (0.7, "b")]) },
+            |(score, _)| *score,
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let search = recorder.span("vector_search");
+        assert_eq!(search["rig.vector_search.top_n"], "3");
+        assert_eq!(search["rig.vector_search.results"], "2");
+        assert_eq!(search["rig.vector_search.scores"], "[0.9, 0.7]");
+    }
+}
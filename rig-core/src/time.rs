@@ -0,0 +1,19 @@
+//! Clock and timer of the retries (see [RetryModel](crate::completion::retry::RetryModel)).
+//!
+//! Builds use [web_time] and [futures_timer], which also work in WebAssembly. The unit tests of
+//! the crate use tokio's clock instead, so that they can pause it (with
+//! `#[tokio::test(start_paused = true)]`) and check the delays exactly.
+use std::time::Duration;
+
+#[cfg(test)]
+pub(crate) use tokio::time::Instant;
+#[cfg(not(test))]
+pub(crate) use web_time::Instant;
+
+/// Wait for `duration`.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(test))]
+    futures_timer::Delay::new(duration).await;
+    #[cfg(test)]
+    tokio::time::sleep(duration).await;
+}
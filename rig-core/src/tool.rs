@@ -1,514 +0,0 @@
-//! Module defining tool related structs and traits.
-//!
-//! The [Tool] trait defines a simple interface for creating tools that can be used
-//! by [Agents](crate::agent::Agent).
-//!
-//! The [ToolEmbedding] trait extends the [Tool] trait to allow for tools that can be
-//! stored in a vector store and RAGged.
-//!
-//! The [ToolSet] struct is a collection of tools that can be used by an [Agent](crate::agent::Agent)
-//! and optionally RAGged.
-
-use std::{collections::HashMap, pin::Pin};
-
-use futures::Future;
-use serde::{Deserialize, Serialize};
-
-use crate::{
-    completion::{self, ToolDefinition},
-    embeddings::{embed::EmbedError, tool::ToolSchema},
-};
-
-#[derive(Debug, thiserror::Error)]
-pub enum ToolError {
-    /// Error returned by the tool
-    #[error("ToolCallError: {0}")]
-    ToolCallError(#[from] Box<dyn std::error::Error + Send + Sync>),
-
-    #[error("JsonError: {0}")]
-    JsonError(#[from] serde_json::Error),
-}
-
-/// Trait that represents a simple LLM tool
-///
-/// # Example
-/// ```
-/// use rig::{
-///     completion::ToolDefinition,
-///     tool::{ToolSet, Tool},
-/// };
-///
-/// #[derive(serde::Deserialize)]
-/// struct AddArgs {
-///     x: i32,
-///     y: i32,
-/// }
-///
-/// #[derive(Debug, thiserror::Error)]
-/// #[error("Math error")]
-/// struct MathError;
-///
-/// #[derive(serde::Deserialize, serde::Serialize)]
-/// struct Adder;
-///
-/// impl Tool for Adder {
-///     const NAME: &'static str = "add";
-///
-///     type Error = MathError;
-///     type Args = AddArgs;
-///     type Output = i32;
-///
-///     async fn definition(&self, _prompt: String) -> ToolDefinition {
-///         ToolDefinition {
-///             name: "add".to_string(),
-///             description: "Add x and y together".to_string(),
-///             parameters: serde_json::json!({
-///                 "type": "object",
-///                 "properties": {
-///                     "x": {
-///                         "type": "number",
-///                         "description": "The first number to add"
-///                     },
-///                     "y": {
-///                         "type": "number",
-///                         "description": "The second number to add"
-///                     }
-///                 }
-///             })
-///         }
-///     }
-///
-///     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-///         let result = args.x + args.y;
-///         Ok(result)
-///     }
-/// }
-/// ```
-pub trait Tool: Sized + Send + Sync {
-    /// The name of the tool. This name should be unique.
-    const NAME: &'static str;
-
-    /// The error type of the tool.
-    type Error: std::error::Error + Send + Sync + 'static;
-    /// The arguments type of the tool.
-    type Args: for<'a> Deserialize<'a> + Send + Sync;
-    /// The output type of the tool.
-    type Output: Serialize;
-
-    /// A method returning the name of the tool.
-    fn name(&self) -> String {
-        Self::NAME.to_string()
-    }
-
-    /// A method returning the tool definition. The user prompt can be used to
-    /// tailor the definition to the specific use case.
-    fn definition(&self, _prompt: String) -> impl Future<Output = ToolDefinition> + Send + Sync;
-
-    /// The tool execution method.
-    /// Both the arguments and return value are a String since these values are meant to
-    /// be the output and input of LLM models (respectively)
-    fn call(
-        &self,
-        args: Self::Args,
-    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send + Sync;
-}
-
-/// Trait that represents an LLM tool that can be stored in a vector store and RAGged
-pub trait ToolEmbedding: Tool {
-    type InitError: std::error::Error + Send + Sync + 'static;
-
-    /// Type of the tool' context. This context will be saved and loaded from the
-    /// vector store when ragging the tool.
-    /// This context can be used to store the tool's static configuration and local
-    /// context.
-    type Context: for<'a> Deserialize<'a> + Serialize;
-
-    /// Type of the tool's state. This state will be passed to the tool when initializing it.
-    /// This state can be used to pass runtime arguments to the tool such as clients,
-    /// API keys and other configuration.
-    type State: Send;
-
-    /// A method returning the documents that will be used as embeddings for the tool.
-    /// This allows for a tool to be retrieved from multiple embedding "directions".
-    /// If the tool will not be RAGged, this method should return an empty vector.
-    fn embedding_docs(&self) -> Vec<String>;
-
-    /// A method returning the context of the tool.
-    fn context(&self) -> Self::Context;
-
-    /// A method to initialize the tool from the context, and a state.
-    fn init(state: Self::State, context: Self::Context) -> Result<Self, Self::InitError>;
-}
-
-/// Wrapper trait to allow for dynamic dispatch of simple tools
-pub trait ToolDyn: Send + Sync {
-    fn name(&self) -> String;
-
-    fn definition(
-        &self,
-        prompt: String,
-    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>>;
-
-    fn call(
-        &self,
-        args: String,
-    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>>;
-}
-
-impl<T: Tool> ToolDyn for T {
-    fn name(&self) -> String {
-        self.name()
-    }
-
-    fn definition(
-        &self,
-        prompt: String,
-    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>> {
-        Box::pin(<Self as Tool>::definition(self, prompt))
-    }
-
-    fn call(
-        &self,
-        args: String,
-    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>> {
-        Box::pin(async move {
-            match serde_json::from_str(&args) {
-                Ok(args) => <Self as Tool>::call(self, args)
-                    .await
-                    .map_err(|e| ToolError::ToolCallError(Box::new(e)))
-                    .and_then(|output| {
-                        serde_json::to_string(&output).map_err(ToolError::JsonError)
-                    }),
-                Err(e) => Err(ToolError::JsonError(e)),
-            }
-        })
-    }
-}
-
-#[cfg(feature = "mcp")]
-pub struct McpTool<T: mcp_core::transport::Transport> {
-    definition: mcp_core::types::Tool,
-    client: mcp_core::client::Client<T>,
-}
-
-#[cfg(feature = "mcp")]
-impl<T> McpTool<T>
-where
-    T: mcp_core::transport::Transport,
-{
-    pub fn from_mcp_server(
-        definition: mcp_core::types::Tool,
-        client: mcp_core::client::Client<T>,
-    ) -> Self {
-        Self { definition, client }
-    }
-}
-
-#[cfg(feature = "mcp")]
-impl From<&mcp_core::types::Tool> for ToolDefinition {
-    fn from(val: &mcp_core::types::Tool) -> Self {
-        Self {
-            name: val.name.to_owned(),
-            description: val.description.to_owned().unwrap_or_default(),
-            parameters: val.input_schema.to_owned(),
-        }
-    }
-}
-
-#[cfg(feature = "mcp")]
-impl From<mcp_core::types::Tool> for ToolDefinition {
-    fn from(val: mcp_core::types::Tool) -> Self {
-        Self {
-            name: val.name,
-            description: val.description.unwrap_or_default(),
-            parameters: val.input_schema,
-        }
-    }
-}
-
-#[cfg(feature = "mcp")]
-#[derive(Debug, thiserror::Error)]
-#[error("MCP tool error: {0}")]
-pub struct McpToolError(String);
-
-#[cfg(feature = "mcp")]
-impl From<McpToolError> for ToolError {
-    fn from(e: McpToolError) -> Self {
-        ToolError::ToolCallError(Box::new(e))
-    }
-}
-
-#[cfg(feature = "mcp")]
-impl<T> ToolDyn for McpTool<T>
-where
-    T: mcp_core::transport::Transport,
-{
-    fn name(&self) -> String {
-        self.definition.name.clone()
-    }
-
-    fn definition(
-        &self,
-        _prompt: String,
-    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>> {
-        Box::pin(async move {
-            ToolDefinition {
-                name: self.definition.name.clone(),
-                description: match &self.definition.description {
-                    Some(desc) => desc.clone(),
-                    None => String::new(),
-                },
-                parameters: serde_json::to_value(&self.definition.input_schema).unwrap_or_default(),
-            }
-        })
-    }
-
-    fn call(
-        &self,
-        args: String,
-    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>> {
-        let name = self.definition.name.clone();
-        let args_clone = args.clone();
-        let args: serde_json::Value = serde_json::from_str(&args_clone).unwrap_or_default();
-        Box::pin(async move {
-            let result = self
-                .client
-                .call_tool(&name, Some(args))
-                .await
-                .map_err(|e| McpToolError(format!("Tool returned an error: {}", e)))?;
-
-            if result.is_error.unwrap_or(false) {
-                if let Some(error) = result.content.first() {
-                    match error {
-                        mcp_core::types::ToolResponseContent::Text { text } => {
-                            return Err(McpToolError(text.clone()).into());
-                        }
-                        _ => return Err(McpToolError("Unsuppported error type".to_string()).into()),
-                    }
-                } else {
-                    return Err(McpToolError("No error message returned".to_string()).into());
-                }
-            }
-
-            Ok(result
-                .content
-                .into_iter()
-                .map(|c| match c {
-                    mcp_core::types::ToolResponseContent::Text { text } => text,
-                    mcp_core::types::ToolResponseContent::Image { data, mime_type } => {
-                        format!("data:{};base64,{}", mime_type, data)
-                    }
-                    mcp_core::types::ToolResponseContent::Resource {
-                        resource: mcp_core::types::ResourceContents { uri, mime_type },
-                    } => {
-                        format!(
-                            "{}{}",
-                            mime_type
-                                .map(|m| format!("data:{};", m))
-                                .unwrap_or_default(),
-                            uri
-                        )
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join(""))
-        })
-    }
-}
-
-/// Wrapper trait to allow for dynamic dispatch of raggable tools
-pub trait ToolEmbeddingDyn: ToolDyn {
-    fn context(&self) -> serde_json::Result<serde_json::Value>;
-
-    fn embedding_docs(&self) -> Vec<String>;
-}
-
-impl<T: ToolEmbedding> ToolEmbeddingDyn for T {
-    fn context(&self) -> serde_json::Result<serde_json::Value> {
-        serde_json::to_value(self.context())
-    }
-
-    fn embedding_docs(&self) -> Vec<String> {
-        self.embedding_docs()
-    }
-}
-
-pub(crate) enum ToolType {
-    Simple(Box<dyn ToolDyn>),
-    Embedding(Box<dyn ToolEmbeddingDyn>),
-}
-
-impl ToolType {
-    pub fn name(&self) -> String {
-        match self {
-            ToolType::Simple(tool) => tool.name(),
-            ToolType::Embedding(tool) => tool.name(),
-        }
-    }
-
-    pub async fn definition(&self, prompt: String) -> ToolDefinition {
-        match self {
-            ToolType::Simple(tool) => tool.definition(prompt).await,
-            ToolType::Embedding(tool) => tool.definition(prompt).await,
-        }
-    }
-
-    pub async fn call(&self, args: String) -> Result<String, ToolError> {
-        match self {
-            ToolType::Simple(tool) => tool.call(args).await,
-            ToolType::Embedding(tool) => tool.call(args).await,
-        }
-    }
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum ToolSetError {
-    /// Error returned by the tool
-    #[error("ToolCallError: {0}")]
-    ToolCallError(#[from] ToolError),
-
-    #[error("ToolNotFoundError: {0}")]
-    ToolNotFoundError(String),
-
-    // TODO: Revisit this
-    #[error("JsonError: {0}")]
-    JsonError(#[from] serde_json::Error),
-}
-
-/// A struct that holds a set of tools
-#[derive(Default)]
-pub struct ToolSet {
-    pub(crate) tools: HashMap<String, ToolType>,
-}
-
-impl ToolSet {
-    /// Create a new ToolSet from a list of tools
-    pub fn from_tools(tools: Vec<impl ToolDyn + 'static>) -> Self {
-        let mut toolset = Self::default();
-        tools.into_iter().for_each(|tool| {
-            toolset.add_tool(tool);
-        });
-        toolset
-    }
-
-    /// Create a toolset builder
-    pub fn builder() -> ToolSetBuilder {
-        ToolSetBuilder::default()
-    }
-
-    /// Check if the toolset contains a tool with the given name
-    pub fn contains(&self, toolname: &str) -> bool {
-        self.tools.contains_key(toolname)
-    }
-
-    /// Add a tool to the toolset
-    pub fn add_tool(&mut self, tool: impl ToolDyn + 'static) {
-        self.tools
-            .insert(tool.name(), ToolType::Simple(Box::new(tool)));
-    }
-
-    /// Merge another toolset into this one
-    pub fn add_tools(&mut self, toolset: ToolSet) {
-        self.tools.extend(toolset.tools);
-    }
-
-    pub(crate) fn get(&self, toolname: &str) -> Option<&ToolType> {
-        self.tools.get(toolname)
-    }
-
-    /// Call a tool with the given name and arguments
-    pub async fn call(&self, toolname: &str, args: String) -> Result<String, ToolSetError> {
-        if let Some(tool) = self.tools.get(toolname) {
-            tracing::info!(target: "rig",
-                "Calling tool {toolname} with args:\n{}",
-                serde_json::to_string_pretty(&args).unwrap_or_else(|_| args.clone())
-            );
-            Ok(tool.call(args).await?)
-        } else {
-            Err(ToolSetError::ToolNotFoundError(toolname.to_string()))
-        }
-    }
-
-    /// Get the documents of all the tools in the toolset
-    pub async fn documents(&self) -> Result<Vec<completion::Document>, ToolSetError> {
-        let mut docs = Vec::new();
-        for tool in self.tools.values() {
-            match tool {
-                ToolType::Simple(tool) => {
-                    docs.push(completion::Document {
-                        id: tool.name(),
-                        text: format!(
-                            "\
-                            Tool: {}\n\
-                            Definition: \n\
-                            {}\
-                        ",
-                            tool.name(),
-                            serde_json::to_string_pretty(&tool.definition("".to_string()).await)?
-                        ),
-                        additional_props: HashMap::new(),
-                    });
-                }
-                ToolType::Embedding(tool) => {
-                    docs.push(completion::Document {
-                        id: tool.name(),
-                        text: format!(
-                            "\
-                            Tool: {}\n\
-                            Definition: \n\
-                            {}\
-                        ",
-                            tool.name(),
-                            serde_json::to_string_pretty(&tool.definition("".to_string()).await)?
-                        ),
-                        additional_props: HashMap::new(),
-                    });
-                }
-            }
-        }
-        Ok(docs)
-    }
-
-    /// Convert tools in self to objects of type ToolSchema.
-    /// This is necessary because when adding tools to the EmbeddingBuilder because all
-    /// documents added to the builder must all be of the same type.
-    pub fn schemas(&self) -> Result<Vec<ToolSchema>, EmbedError> {
-        self.tools
-            .values()
-            .filter_map(|tool_type| {
-                if let ToolType::Embedding(tool) = tool_type {
-                    Some(ToolSchema::try_from(&**tool))
-                } else {
-                    None
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()
-    }
-}
-
-#[derive(Default)]
-pub struct ToolSetBuilder {
-    tools: Vec<ToolType>,
-}
-
-impl ToolSetBuilder {
-    pub fn static_tool(mut self, tool: impl ToolDyn + 'static) -> Self {
-        self.tools.push(ToolType::Simple(Box::new(tool)));
-        self
-    }
-
-    pub fn dynamic_tool(mut self, tool: impl ToolEmbeddingDyn + 'static) -> Self {
-        self.tools.push(ToolType::Embedding(Box::new(tool)));
-        self
-    }
-
-    pub fn build(self) -> ToolSet {
-        ToolSet {
-            tools: self
-                .tools
-                .into_iter()
-                .map(|tool| (tool.name(), tool))
-                .collect(),
-        }
-    }
-}
@@ -0,0 +1,426 @@
+//! This module provides client support for the [Model Context Protocol](https://modelcontextprotocol.io)
+//! (MCP). The [McpClient] struct connects to an MCP server (over stdio or, with the `mcp-sse`
+//! feature, over SSE) and discovers its tools, which are exposed as [McpTool]s that can be
+//! added to an agent (see [AgentBuilder::mcp_tools](crate::agent::AgentBuilder::mcp_tools)).
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::Prompt, providers::openai, tool::mcp::McpClient};
+//!
+//! // Launch the MCP server as a child process and connect to it
+//! let mcp_client = McpClient::stdio("npx", &["-y", "@modelcontextprotocol/server-everything"]).await?;
+//! let tools = mcp_client.tools().await?;
+//!
+//! let openai = openai::Client::from_env();
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .mcp_tools(tools)
+//!     .build();
+//!
+//! let response = agent.prompt("Add 10 + 10").await?;
+//! ```
+use std::pin::Pin;
+
+use futures::Future;
+use mcp_core::{
+    client::{Client, ClientBuilder},
+    transport::{ClientStdioTransport, Transport},
+    types::{ClientCapabilities, Implementation, InitializeResponse},
+};
+
+use super::{ToolDyn, ToolError};
+use crate::completion::ToolDefinition;
+
+#[derive(Debug, thiserror::Error)]
+pub enum McpClientError {
+    /// Error opening the transport (e.g.: the server process could not be launched)
+    #[error("TransportError: {0}")]
+    TransportError(String),
+
+    /// Error initializing the MCP session (e.g.: unsupported protocol version)
+    #[error("InitializationError: {0}")]
+    InitializationError(String),
+
+    /// Error returned by the MCP server
+    #[error("ServerError: {0}")]
+    ServerError(String),
+}
+
+/// Client of an MCP server, connected and initialized.
+#[derive(Clone)]
+pub struct McpClient<T: Transport> {
+    client: Client<T>,
+    server: InitializeResponse,
+}
+
+impl McpClient<ClientStdioTransport> {
+    /// Launch an MCP server as a child process running `program` with `args`, and connect
+    /// to it over its standard input and output.
+    pub async fn stdio(program: &str, args: &[&str]) -> Result<Self, McpClientError> {
+        let transport = ClientStdioTransport::new(program, args)
+            .map_err(|e| McpClientError::TransportError(e.to_string()))?;
+        Self::connect(transport).await
+    }
+}
+
+#[cfg(feature = "mcp-sse")]
+impl McpClient<mcp_core::transport::ClientSseTransport> {
+    /// Connect to the MCP server listening for SSE connections at `url`
+    /// (e.g.: `http://127.0.0.1:3000/sse`).
+    pub async fn sse(url: &str) -> Result<Self, McpClientError> {
+        Self::connect(mcp_core::transport::ClientSseTransportBuilder::new(url.to_string()).build())
+            .await
+    }
+}
+
+impl<T: Transport + Clone> McpClient<T> {
+    /// Connect to an MCP server over `transport` and initialize the session.
+    pub async fn connect(transport: T) -> Result<Self, McpClientError> {
+        Self::from_client(ClientBuilder::new(transport).build()).await
+    }
+
+    /// Open and initialize an MCP session with an existing (not yet opened) `client`.
+    /// Useful to configure the client (e.g.: secure values) before connecting.
+    pub async fn from_client(client: Client<T>) -> Result<Self, McpClientError> {
+        client
+            .open()
+            .await
+            .map_err(|e| McpClientError::TransportError(e.to_string()))?;
+
+        let server = client
+            .initialize(
+                Implementation {
+                    name: "rig".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                ClientCapabilities::default(),
+            )
+            .await
+            .map_err(|e| McpClientError::InitializationError(e.to_string()))?;
+
+        tracing::info!(target: "rig",
+            "Connected to MCP server {} {}",
+            server.server_info.name,
+            server.server_info.version
+        );
+
+        Ok(Self { client, server })
+    }
+
+    /// Name, version and capabilities of the server, as returned on initialization.
+    pub fn server(&self) -> &InitializeResponse {
+        &self.server
+    }
+
+    /// The underlying MCP client.
+    pub fn client(&self) -> &Client<T> {
+        &self.client
+    }
+
+    /// Discover the tools of the server (all pages of the tool list).
+    pub async fn tools(&self) -> Result<Vec<McpTool<T>>, McpClientError> {
+        let mut tools = vec![];
+        let mut cursor = None;
+
+        loop {
+            let response = self
+                .client
+                .list_tools(cursor, None)
+                .await
+                .map_err(|e| McpClientError::ServerError(e.to_string()))?;
+
+            tools.extend(
+                response
+                    .tools
+                    .into_iter()
+                    .map(|tool| McpTool::from_mcp_server(tool, self.client.clone())),
+            );
+
+            match response.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        Ok(tools)
+    }
+}
+
+/// Tool of an MCP server, called through an MCP client.
+/// MCP tools are usually obtained from [McpClient::tools].
+#[derive(Clone)]
+pub struct McpTool<T: mcp_core::transport::Transport> {
+    definition: mcp_core::types::Tool,
+    client: mcp_core::client::Client<T>,
+}
+
+impl<T> McpTool<T>
+where
+    T: mcp_core::transport::Transport,
+{
+    pub fn from_mcp_server(
+        definition: mcp_core::types::Tool,
+        client: mcp_core::client::Client<T>,
+    ) -> Self {
+        Self { definition, client }
+    }
+}
+
+impl From<&mcp_core::types::Tool> for ToolDefinition {
+    fn from(val: &mcp_core::types::Tool) -> Self {
+        Self {
+            name: val.name.to_owned(),
+            description: val.description.to_owned().unwrap_or_default(),
+            parameters: val.input_schema.to_owned(),
+        }
+    }
+}
+
+impl From<mcp_core::types::Tool> for ToolDefinition {
+    fn from(val: mcp_core::types::Tool) -> Self {
+        Self {
+            name: val.name,
+            description: val.description.unwrap_or_default(),
+            parameters: val.input_schema,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("MCP tool error: {0}")]
+pub struct McpToolError(String);
+
+impl From<McpToolError> for ToolError {
+    fn from(e: McpToolError) -> Self {
+        ToolError::ToolCallError(Box::new(e))
+    }
+}
+
+impl<T> ToolDyn for McpTool<T>
+where
+    T: mcp_core::transport::Transport,
+{
+    fn name(&self) -> String {
+        self.definition.name.clone()
+    }
+
+    fn definition(
+        &self,
+        _prompt: String,
+    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>> {
+        Box::pin(async move {
+            ToolDefinition {
+                name: self.definition.name.clone(),
+                description: match &self.definition.description {
+                    Some(desc) => desc.clone(),
+                    None => String::new(),
+                },
+                parameters: serde_json::to_value(&self.definition.input_schema).unwrap_or_default(),
+            }
+        })
+    }
+
+    fn call(
+        &self,
+        args: String,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>> {
+        let name = self.definition.name.clone();
+        let args_clone = args.clone();
+        let args: serde_json::Value = serde_json::from_str(&args_clone).unwrap_or_default();
+        Box::pin(async move {
+            let result = self
+                .client
+                .call_tool(&name, Some(args))
+                .await
+                .map_err(|e| McpToolError(format!("Tool returned an error: {}", e)))?;
+
+            if result.is_error.unwrap_or(false) {
+                if let Some(error) = result.content.first() {
+                    match error {
+                        mcp_core::types::ToolResponseContent::Text { text } => {
+                            return Err(McpToolError(text.clone()).into());
+                        }
+                        _ => return Err(McpToolError("Unsuppported error type".to_string()).into()),
+                    }
+                } else {
+                    return Err(McpToolError("No error message returned".to_string()).into());
+                }
+            }
+
+            Ok(result
+                .content
+                .into_iter()
+                .map(|c| match c {
+                    mcp_core::types::ToolResponseContent::Text { text } => text,
+                    mcp_core::types::ToolResponseContent::Image { data, mime_type } => {
+                        format!("data:{};base64,{}", mime_type, data)
+                    }
+                    mcp_core::types::ToolResponseContent::Resource {
+                        resource: mcp_core::types::ResourceContents { uri, mime_type },
+                    } => {
+                        format!(
+                            "{}{}",
+                            mime_type
+                                .map(|m| format!("data:{};", m))
+                                .unwrap_or_default(),
+                            uri
+                        )
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(""))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use mcp_core::{
+        protocol::RequestOptions,
+        transport::{JsonRpcError, JsonRpcResponse, Message, RequestId},
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::tool::ToolSet;
+
+    type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+    /// Transport answering requests like an MCP server with two tools, listed on two pages.
+    #[derive(Clone)]
+    struct MockTransport;
+
+    impl MockTransport {
+        fn result(method: &str, params: Option<Value>) -> Value {
+            let params = params.unwrap_or_default();
+            match method {
+                "initialize" => json!({
+                    "protocolVersion": mcp_core::types::LATEST_PROTOCOL_VERSION,
+                    "capabilities": {"tools": {"listChanged": false}},
+                    "serverInfo": {"name": "calculator", "version": "1.0"},
+                }),
+                "tools/list" if params["cursor"].is_null() => json!({
+                    "tools": [{
+                        "name": "add",
+                        "description": "Adds two numbers",
+                        "inputSchema": {"type": "object"},
+                    }],
+                    "nextCursor": "2",
+                }),
+                "tools/list" => json!({
+                    "tools": [{"name": "subtract", "inputSchema": {"type": "object"}}],
+                }),
+                "tools/call" => {
+                    let (a, b) = (
+                        params["arguments"]["a"].as_f64().unwrap(),
+                        params["arguments"]["b"].as_f64().unwrap(),
+                    );
+                    let result = match params["name"].as_str() {
+                        Some("add") => a + b,
+                        _ => a - b,
+                    };
+                    json!({"content": [{"type": "text", "text": result.to_string()}]})
+                }
+                _ => Value::Null,
+            }
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn open<'life0, 'async_trait>(&'life0 self) -> BoxFuture<'async_trait, ()>
+        where
+            'life0: 'async_trait,
+            Self: 'async_trait,
+        {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn close<'life0, 'async_trait>(&'life0 self) -> BoxFuture<'async_trait, ()>
+        where
+            'life0: 'async_trait,
+            Self: 'async_trait,
+        {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn poll_message<'life0, 'async_trait>(
+            &'life0 self,
+        ) -> BoxFuture<'async_trait, Option<Message>>
+        where
+            'life0: 'async_trait,
+            Self: 'async_trait,
+        {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn request(
+            &self,
+            method: &str,
+            params: Option<Value>,
+            _options: RequestOptions,
+        ) -> Pin<Box<dyn Future<Output = anyhow::Result<JsonRpcResponse>> + Send + Sync>> {
+            let result = Self::result(method, params);
+            Box::pin(async move {
+                Ok(JsonRpcResponse {
+                    id: 0,
+                    result: Some(result),
+                    error: None,
+                    jsonrpc: Default::default(),
+                })
+            })
+        }
+
+        fn send_notification<'life0, 'life1, 'async_trait>(
+            &'life0 self,
+            _method: &'life1 str,
+            _params: Option<Value>,
+        ) -> BoxFuture<'async_trait, ()>
+        where
+            'life0: 'async_trait,
+            'life1: 'async_trait,
+            Self: 'async_trait,
+        {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn send_response<'life0, 'async_trait>(
+            &'life0 self,
+            _id: RequestId,
+            _result: Option<Value>,
+            _error: Option<JsonRpcError>,
+        ) -> BoxFuture<'async_trait, ()>
+        where
+            'life0: 'async_trait,
+            Self: 'async_trait,
+        {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_tools() {
+        let client = McpClient::connect(MockTransport).await.unwrap();
+        assert_eq!(client.server().server_info.name, "calculator");
+
+        let tools = client.tools().await.unwrap();
+        assert_eq!(
+            tools.iter().map(|tool| tool.name()).collect::<Vec<_>>(),
+            vec!["add", "subtract"]
+        );
+        let definition = tools[0].definition(String::new()).await;
+        assert_eq!(definition.description, "Adds two numbers");
+        assert_eq!(definition.parameters, json!({"type": "object"}));
+
+        let mut toolset = ToolSet::default();
+        tools.into_iter().for_each(|tool| toolset.add_tool(tool));
+        assert_eq!(
+            toolset
+                .call("subtract", r#"{"a": 5, "b": 3}"#.to_string())
+                .await
+                .unwrap(),
+            "2"
+        );
+    }
+}
@@ -0,0 +1,678 @@
+//! Module defining tool related structs and traits.
+//!
+//! The [Tool] trait defines a simple interface for creating tools that can be used
+//! by [Agents](crate::agent::Agent).
+//!
+//! The [ToolEmbedding] trait extends the [Tool] trait to allow for tools that can be
+//! stored in a vector store and RAGged.
+//!
+//! The [ToolSet] struct is a collection of tools that can be used by an [Agent](crate::agent::Agent)
+//! and optionally RAGged.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    pin::Pin,
+};
+
+use futures::Future;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "mcp")]
+pub mod mcp;
+
+#[cfg(feature = "mcp")]
+pub use mcp::{McpTool, McpToolError};
+
+use crate::{
+    completion::{self, ToolDefinition},
+    embeddings::{
+        embed::EmbedError, tool::ToolSchema, EmbeddingError, EmbeddingModel, EmbeddingsBuilder,
+    },
+    vector_store::in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    /// Error returned by the tool
+    #[error("ToolCallError: {0}")]
+    ToolCallError(#[from] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// The arguments of the tool call are incomplete JSON, usually because the model's response
+    /// was cut off (e.g.: the `max_tokens` limit was reached). Contains the partial arguments.
+    #[error("IncompleteArguments: tool call arguments are incomplete (the response may have been truncated, e.g. by max_tokens): {0}")]
+    IncompleteArguments(String),
+}
+
+impl ToolError {
+    /// Create the error corresponding to a failure to parse the arguments `args` of a tool call.
+    pub fn from_args_error(args: &str, error: serde_json::Error) -> Self {
+        if error.is_eof() {
+            ToolError::IncompleteArguments(args.to_string())
+        } else {
+            ToolError::JsonError(error)
+        }
+    }
+}
+
+/// Trait that represents a simple LLM tool
+///
+/// # Example
+/// ```
+/// use rig::{
+///     completion::ToolDefinition,
+///     tool::{ToolSet, Tool},
+/// };
+///
+/// #[derive(serde::Deserialize)]
+/// struct AddArgs {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// #[derive(Debug, thiserror::Error)]
+/// #[error("Math error")]
+/// struct MathError;
+///
+/// #[derive(serde::Deserialize, serde::Serialize)]
+/// struct Adder;
+///
+/// impl Tool for Adder {
+///     const NAME: &'static str = "add";
+///
+///     type Error = MathError;
+///     type Args = AddArgs;
+///     type Output = i32;
+///
+///     async fn definition(&self, _prompt: String) -> ToolDefinition {
+///         ToolDefinition {
+///             name: "add".to_string(),
+///             description: "Add x and y together".to_string(),
+///             parameters: serde_json::json!({
+///                 "type": "object",
+///                 "properties": {
+///                     "x": {
+///                         "type": "number",
+///                         "description": "The first number to add"
+///                     },
+///                     "y": {
+///                         "type": "number",
+///                         "description": "The second number to add"
+///                     }
+///                 }
+///             })
+///         }
+///     }
+///
+///     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+///         let result = args.x + args.y;
+///         Ok(result)
+///     }
+/// }
+/// ```
+pub trait Tool: Sized + Send + Sync {
+    /// The name of the tool. This name should be unique.
+    const NAME: &'static str;
+
+    /// The error type of the tool.
+    type Error: std::error::Error + Send + Sync + 'static;
+    /// The arguments type of the tool.
+    type Args: for<'a> Deserialize<'a> + Send + Sync;
+    /// The output type of the tool.
+    /// To return an image (e.g.: a screenshot) to a vision model, output a base64 encoded
+    /// image data URI (e.g.: `data:image/png;base64,...`) and convert it to tool result
+    /// content using [ToolResultContent::from_tool_output](crate::message::ToolResultContent::from_tool_output).
+    /// Providers whose tool results can only be text (e.g.: OpenAI) receive a placeholder
+    /// instead of the image.
+    type Output: Serialize;
+
+    /// A method returning the name of the tool.
+    fn name(&self) -> String {
+        Self::NAME.to_string()
+    }
+
+    /// A method returning the tool definition. The user prompt can be used to
+    /// tailor the definition to the specific use case.
+    fn definition(&self, _prompt: String) -> impl Future<Output = ToolDefinition> + Send + Sync;
+
+    /// The tool execution method.
+    /// Both the arguments and return value are a String since these values are meant to
+    /// be the output and input of LLM models (respectively)
+    fn call(
+        &self,
+        args: Self::Args,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send + Sync;
+}
+
+/// Trait that represents an LLM tool that can be stored in a vector store and RAGged
+pub trait ToolEmbedding: Tool {
+    type InitError: std::error::Error + Send + Sync + 'static;
+
+    /// Type of the tool' context. This context will be saved and loaded from the
+    /// vector store when ragging the tool.
+    /// This context can be used to store the tool's static configuration and local
+    /// context.
+    type Context: for<'a> Deserialize<'a> + Serialize;
+
+    /// Type of the tool's state. This state will be passed to the tool when initializing it.
+    /// This state can be used to pass runtime arguments to the tool such as clients,
+    /// API keys and other configuration.
+    type State: Send;
+
+    /// A method returning the documents that will be used as embeddings for the tool.
+    /// This allows for a tool to be retrieved from multiple embedding "directions".
+    /// If the tool will not be RAGged, this method should return an empty vector.
+    fn embedding_docs(&self) -> Vec<String>;
+
+    /// A method returning the context of the tool.
+    fn context(&self) -> Self::Context;
+
+    /// A method to initialize the tool from the context, and a state.
+    fn init(state: Self::State, context: Self::Context) -> Result<Self, Self::InitError>;
+}
+
+/// Wrapper trait to allow for dynamic dispatch of simple tools
+pub trait ToolDyn: Send + Sync {
+    fn name(&self) -> String;
+
+    fn definition(
+        &self,
+        prompt: String,
+    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>>;
+
+    fn call(
+        &self,
+        args: String,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>>;
+}
+
+impl<T: Tool> ToolDyn for T {
+    fn name(&self) -> String {
+        self.name()
+    }
+
+    fn definition(
+        &self,
+        prompt: String,
+    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>> {
+        Box::pin(<Self as Tool>::definition(self, prompt))
+    }
+
+    fn call(
+        &self,
+        args: String,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>> {
+        Box::pin(async move {
+            match serde_json::from_str(&args) {
+                Ok(args) => <Self as Tool>::call(self, args)
+                    .await
+                    .map_err(|e| ToolError::ToolCallError(Box::new(e)))
+                    .and_then(|output| {
+                        serde_json::to_string(&output).map_err(ToolError::JsonError)
+                    }),
+                Err(e) => Err(ToolError::from_args_error(&args, e)),
+            }
+        })
+    }
+}
+
+/// Wrapper trait to allow for dynamic dispatch of raggable tools
+pub trait ToolEmbeddingDyn: ToolDyn {
+    fn context(&self) -> serde_json::Result<serde_json::Value>;
+
+    fn embedding_docs(&self) -> Vec<String>;
+}
+
+impl<T: ToolEmbedding> ToolEmbeddingDyn for T {
+    fn context(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self.context())
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        self.embedding_docs()
+    }
+}
+
+/// Separator of the namespace and the name of [namespaced](ToolSet::namespaced) tools, allowed
+/// in tool names by all providers.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+pub(crate) enum ToolType {
+    Simple(Box<dyn ToolDyn>),
+    Embedding(Box<dyn ToolEmbeddingDyn>),
+    /// Tool registered under a namespaced name (e.g.: `group__tool`)
+    Namespaced(String, Box<ToolType>),
+}
+
+impl ToolType {
+    pub fn name(&self) -> String {
+        match self {
+            ToolType::Simple(tool) => tool.name(),
+            ToolType::Embedding(tool) => tool.name(),
+            ToolType::Namespaced(name, _) => name.clone(),
+        }
+    }
+
+    pub fn definition(
+        &self,
+        prompt: String,
+    ) -> Pin<Box<dyn Future<Output = ToolDefinition> + Send + Sync + '_>> {
+        match self {
+            ToolType::Simple(tool) => tool.definition(prompt),
+            ToolType::Embedding(tool) => tool.definition(prompt),
+            ToolType::Namespaced(name, tool) => Box::pin(async move {
+                ToolDefinition {
+                    name: name.clone(),
+                    ..tool.definition(prompt).await
+                }
+            }),
+        }
+    }
+
+    pub fn call(
+        &self,
+        args: String,
+    ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + Sync + '_>> {
+        match self {
+            ToolType::Simple(tool) => tool.call(args),
+            ToolType::Embedding(tool) => tool.call(args),
+            ToolType::Namespaced(_, tool) => tool.call(args),
+        }
+    }
+
+    /// The schema of the tool, if it is a [ToolEmbedding].
+    fn schema(&self) -> Option<Result<ToolSchema, EmbedError>> {
+        match self {
+            ToolType::Simple(_) => None,
+            ToolType::Embedding(tool) => Some(ToolSchema::try_from(&**tool)),
+            ToolType::Namespaced(name, tool) => tool.schema().map(|schema| {
+                schema.map(|schema| ToolSchema {
+                    name: name.clone(),
+                    ..schema
+                })
+            }),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolSetError {
+    /// Error returned by the tool
+    #[error("ToolCallError: {0}")]
+    ToolCallError(#[from] ToolError),
+
+    #[error("ToolNotFoundError: {0}")]
+    ToolNotFoundError(String),
+
+    /// A tool with the same name is already registered
+    #[error("DuplicateToolError: {0}")]
+    DuplicateToolError(String),
+
+    /// The tool did not respond in time (see [AgentBuilder::tool_timeout](crate::agent::AgentBuilder::tool_timeout))
+    #[error("TimeoutError: tool `{name}` timed out after {timeout:?}")]
+    TimeoutError {
+        name: String,
+        timeout: std::time::Duration,
+    },
+
+    // TODO: Revisit this
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// A struct that holds a set of tools
+#[derive(Default)]
+pub struct ToolSet {
+    pub(crate) tools: HashMap<String, ToolType>,
+}
+
+impl ToolSet {
+    /// Create a new ToolSet from a list of tools
+    pub fn from_tools(tools: Vec<impl ToolDyn + 'static>) -> Self {
+        let mut toolset = Self::default();
+        tools.into_iter().for_each(|tool| {
+            toolset.add_tool(tool);
+        });
+        toolset
+    }
+
+    /// Create a toolset builder
+    pub fn builder() -> ToolSetBuilder {
+        ToolSetBuilder::default()
+    }
+
+    /// Check if the toolset contains a tool with the given name
+    pub fn contains(&self, toolname: &str) -> bool {
+        self.tools.contains_key(toolname)
+    }
+
+    /// Add a tool to the toolset
+    pub fn add_tool(&mut self, tool: impl ToolDyn + 'static) {
+        self.tools
+            .insert(tool.name(), ToolType::Simple(Box::new(tool)));
+    }
+
+    /// Add a tool to the toolset, failing if a tool with the same name is already registered
+    pub fn try_add_tool(&mut self, tool: impl ToolDyn + 'static) -> Result<(), ToolSetError> {
+        self.try_insert(ToolType::Simple(Box::new(tool)))
+    }
+
+    /// Merge another toolset into this one
+    pub fn add_tools(&mut self, toolset: ToolSet) {
+        self.tools.extend(toolset.tools);
+    }
+
+    /// Merge another toolset into this one, failing if any of its tools has the same name as a
+    /// tool of this toolset. On error, this toolset is left unchanged.
+    pub fn try_add_tools(&mut self, toolset: ToolSet) -> Result<(), ToolSetError> {
+        if let Some(name) = toolset.tools.keys().find(|name| self.contains(name)) {
+            return Err(ToolSetError::DuplicateToolError(name.clone()));
+        }
+        self.tools.extend(toolset.tools);
+        Ok(())
+    }
+
+    /// Move all tools of the toolset into the namespace `namespace`: a tool named `tool` is
+    /// renamed `namespace__tool` (in its definition too, so the model calls it by its new name).
+    ///
+    /// The separator is [NAMESPACE_SEPARATOR], since providers restrict the characters allowed
+    /// in tool names (e.g.: OpenAI only allows letters, digits, `_` and `-`), so `namespace`
+    /// should only contain these characters too.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut tools = ToolSet::default();
+    /// tools.try_add_tools(ToolSet::from_tools(vec![Search]).namespaced("web"))?;
+    /// tools.try_add_tools(ToolSet::from_tools(vec![Search]).namespaced("docs"))?;
+    ///
+    /// assert!(tools.contains("web__search") && tools.contains("docs__search"));
+    /// ```
+    pub fn namespaced(self, namespace: &str) -> Self {
+        Self {
+            tools: self
+                .tools
+                .into_iter()
+                .map(|(name, tool)| {
+                    let name = format!("{namespace}{NAMESPACE_SEPARATOR}{name}");
+                    (name.clone(), ToolType::Namespaced(name, Box::new(tool)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Names of the tools in the toolset
+    pub fn names(&self) -> Vec<String> {
+        self.tools.keys().cloned().collect()
+    }
+
+    /// Get the definitions of all the tools in the toolset, sorted by name.
+    /// These are the definitions sent to the model in a completion request.
+    pub async fn definitions(&self, prompt: &str) -> Vec<ToolDefinition> {
+        let mut tools = self.tools.iter().collect::<Vec<_>>();
+        tools.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut definitions = Vec::with_capacity(tools.len());
+        for (_, tool) in tools {
+            definitions.push(tool.definition(prompt.to_string()).await);
+        }
+        definitions
+    }
+
+    fn try_insert(&mut self, tool: ToolType) -> Result<(), ToolSetError> {
+        match self.tools.entry(tool.name()) {
+            Entry::Occupied(entry) => Err(ToolSetError::DuplicateToolError(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(tool);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, toolname: &str) -> Option<&ToolType> {
+        self.tools.get(toolname)
+    }
+
+    /// Call a tool with the given name and arguments
+    pub async fn call(&self, toolname: &str, args: String) -> Result<String, ToolSetError> {
+        if let Some(tool) = self.tools.get(toolname) {
+            tracing::info!(target: "rig",
+                "Calling tool {toolname} with args:\n{}",
+                serde_json::to_string_pretty(&args).unwrap_or_else(|_| args.clone())
+            );
+            Ok(tool.call(args).await?)
+        } else {
+            Err(ToolSetError::ToolNotFoundError(toolname.to_string()))
+        }
+    }
+
+    /// Get the documents of all the tools in the toolset
+    pub async fn documents(&self) -> Result<Vec<completion::Document>, ToolSetError> {
+        let mut docs = Vec::new();
+        for tool in self.tools.values() {
+            docs.push(completion::Document {
+                id: tool.name(),
+                text: format!(
+                    "\
+                    Tool: {}\n\
+                    Definition: \n\
+                    {}\
+                ",
+                    tool.name(),
+                    serde_json::to_string_pretty(&tool.definition("".to_string()).await)?
+                ),
+                additional_props: HashMap::new(),
+            });
+        }
+        Ok(docs)
+    }
+
+    /// Convert tools in self to objects of type ToolSchema.
+    /// This is necessary because when adding tools to the EmbeddingBuilder because all
+    /// documents added to the builder must all be of the same type.
+    pub fn schemas(&self) -> Result<Vec<ToolSchema>, EmbedError> {
+        self.tools
+            .values()
+            .filter_map(ToolType::schema)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Embed all the tools of the toolset in an in-memory vector index, to be used as a source of
+    /// dynamic tools (see [AgentBuilder::dynamic_tools](crate::agent::AgentBuilder::dynamic_tools)).
+    ///
+    /// [ToolEmbedding] tools are embedded using their embedding docs, while other tools are
+    /// embedded using the description of their definition.
+    ///
+    /// # Example
+    /// ```rust
+    /// let toolset = ToolSet::builder()
+    ///     .static_tool(Add)
+    ///     .dynamic_tool(Subtract)
+    ///     .build();
+    ///
+    /// let index = toolset.index(embedding_model).await?;
+    ///
+    /// let agent = openai.agent(openai::GPT_4O)
+    ///     .dynamic_tools(1, index, toolset)
+    ///     .build();
+    /// ```
+    pub async fn index<M: EmbeddingModel>(
+        &self,
+        model: M,
+    ) -> Result<InMemoryVectorIndex<M, ToolSchema>, EmbeddingError> {
+        let mut schemas = Vec::with_capacity(self.tools.len());
+        for tool in self.tools.values() {
+            let schema = match tool.schema() {
+                Some(schema) => schema.map_err(|e| EmbeddingError::DocumentError(e.into()))?,
+                None => {
+                    let definition = tool.definition("".to_string()).await;
+                    ToolSchema {
+                        name: tool.name(),
+                        context: serde_json::Value::Null,
+                        embedding_docs: vec![definition.description],
+                    }
+                }
+            };
+            schemas.push(schema);
+        }
+
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(schemas)
+            .map_err(|e| EmbeddingError::DocumentError(e.into()))?
+            .build()
+            .await?;
+
+        Ok(
+            InMemoryVectorStore::from_documents_with_id_f(embeddings, |tool| tool.name.clone())
+                .index(model),
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct ToolSetBuilder {
+    tools: Vec<ToolType>,
+}
+
+impl ToolSetBuilder {
+    pub fn static_tool(mut self, tool: impl ToolDyn + 'static) -> Self {
+        self.tools.push(ToolType::Simple(Box::new(tool)));
+        self
+    }
+
+    pub fn dynamic_tool(mut self, tool: impl ToolEmbeddingDyn + 'static) -> Self {
+        self.tools.push(ToolType::Embedding(Box::new(tool)));
+        self
+    }
+
+    pub fn build(self) -> ToolSet {
+        ToolSet {
+            tools: self
+                .tools
+                .into_iter()
+                .map(|tool| (tool.name(), tool))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct Adder;
+
+    #[derive(Deserialize)]
+    struct AddArgs {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Math error")]
+    struct MathError;
+
+    impl Tool for Adder {
+        const NAME: &'static str = "add";
+
+        type Error = MathError;
+        type Args = AddArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: "add".to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: serde_json::json!({}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_arguments() {
+        let result = ToolDyn::call(&Adder, r#"{"x": 1, "y"#.to_string()).await;
+        assert!(
+            matches!(result, Err(ToolError::IncompleteArguments(args)) if args == r#"{"x": 1, "y"#)
+        );
+
+        let result = ToolDyn::call(&Adder, r#"{"x": 1}"#.to_string()).await;
+        assert!(matches!(result, Err(ToolError::JsonError(_))));
+
+        let result = ToolDyn::call(&Adder, r#"{"x": 1, "y": 2}"#.to_string()).await;
+        assert_eq!(result.unwrap(), "3");
+    }
+
+    #[tokio::test]
+    async fn test_toolset_registration() {
+        let mut toolset = ToolSet::default();
+        toolset.try_add_tool(Adder).unwrap();
+        assert!(matches!(
+            toolset.try_add_tool(Adder),
+            Err(ToolSetError::DuplicateToolError(name)) if name == "add"
+        ));
+
+        toolset
+            .try_add_tools(ToolSet::from_tools(vec![Adder]).namespaced("math"))
+            .unwrap();
+        assert!(toolset.contains("add") && toolset.contains("math__add"));
+
+        let definitions = toolset.definitions("").await;
+        let names = definitions
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["add", "math__add"]);
+
+        let result = toolset
+            .call("math__add", r#"{"x": 1, "y": 2}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(result, "3");
+    }
+
+    #[tokio::test]
+    async fn test_index() {
+        use crate::{embeddings::Embedding, vector_store::VectorStoreIndex};
+
+        /// Embeds texts mentioning additions and other texts on different axes
+        #[derive(Clone)]
+        struct Model;
+
+        impl EmbeddingModel for Model {
+            const MAX_DOCUMENTS: usize = 5;
+
+            fn ndims(&self) -> usize {
+                2
+            }
+
+            async fn embed_texts(
+                &self,
+                texts: impl IntoIterator<Item = String> + Send,
+            ) -> Result<Vec<Embedding>, EmbeddingError> {
+                Ok(texts
+                    .into_iter()
+                    .map(|text| Embedding {
+                        vec: if text.to_lowercase().contains("add") {
+                            vec![1.0, 0.0]
+                        } else {
+                            vec![0.0, 1.0]
+                        },
+                        document: text,
+                    })
+                    .collect())
+            }
+        }
+
+        let toolset = ToolSet::from_tools(vec![Adder]);
+        let index = toolset.index(Model).await.unwrap();
+
+        let results = index.top_n_ids("Add 1 and 2", 1).await.unwrap();
+        assert_eq!(results[0].1, "add");
+    }
+}
@@ -0,0 +1,302 @@
+//! This module provides the [TraceRecorder] struct, which records a structured trace of the
+//! steps of an agent run: the prompts sent, the documents retrieved for them, the completions
+//! (with their token usage and the raw payloads of the provider), and the tool calls and their
+//! results.
+//!
+//! Unlike `tracing` logs, the trace is a [Trace] (a list of [TraceStep] values) which can be
+//! inspected programmatically, serialized (e.g.: to JSON), compared with another trace
+//! ([Trace::diff]) and replayed against a mock provider ([Trace::replay_model]), e.g.: to
+//! reproduce the run of an agent which misbehaved in production.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     completion::Prompt,
+//!     providers::openai,
+//!     trace::{Trace, TraceRecorder},
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let recorder = TraceRecorder::new();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .tool(Adder)
+//!     .trace(recorder.clone())
+//!     .build();
+//!
+//! let response = agent.prompt("What is 2 + 3?").await?;
+//!
+//! let json = recorder.to_json()?;
+//! println!("Total token usage: {:?}", recorder.token_usage());
+//!
+//! // Later: replay the run with the same tools, the model answering as it did
+//! let trace = Trace::from_json(&json)?;
+//! let replay = TraceRecorder::new();
+//! let agent = AgentBuilder::new(trace.replay_model())
+//!     .preamble("You are a helpful assistant.")
+//!     .tool(Adder)
+//!     .trace(replay.clone())
+//!     .build();
+//!
+//! agent.prompt("What is 2 + 3?").await?;
+//!
+//! if let Some(diff) = trace.diff(&replay.trace()) {
+//!     println!("Runs diverged at step {}: {:?}", diff.index, diff);
+//! }
+//! ```
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    completion::{AssistantContent, Document, Message, TokenUsage},
+    providers::mock::MockCompletionModel,
+    OneOrMany,
+};
+
+/// A step of an agent run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceStep {
+    /// A prompt sent to the agent, with the chat history it was sent with
+    Prompt {
+        prompt: Message,
+        chat_history: Vec<Message>,
+    },
+    /// The documents retrieved from the dynamic context of the agent for a completion
+    Retrieval { documents: Vec<Document> },
+    /// A completion returned by the model
+    Completion {
+        choice: OneOrMany<AssistantContent>,
+        /// Token usage of the completion, if reported by the provider
+        usage: Option<TokenUsage>,
+        /// Raw response of the provider, if it can be serialized (see
+        /// [CompletionModel::raw_response_json](crate::completion::CompletionModel::raw_response_json))
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        raw_response: Option<serde_json::Value>,
+    },
+    /// A response answered from the agent's semantic cache instead of the model
+    CacheHit { response: String },
+    /// A tool call requested by the model
+    ToolCall {
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    /// The result of a tool call: its output, or the error it failed with
+    ToolResult {
+        id: String,
+        name: String,
+        output: Option<String>,
+        error: Option<String>,
+    },
+}
+
+impl TraceStep {
+    /// The step without the fields which depend on the provider rather than on the behavior
+    /// of the agent (token usage and raw response of the completions).
+    fn comparable(&self) -> Self {
+        match self {
+            Self::Completion { choice, .. } => Self::Completion {
+                choice: choice.clone(),
+                usage: None,
+                raw_response: None,
+            },
+            step => step.clone(),
+        }
+    }
+}
+
+/// Trace of agent runs: the [TraceStep]s recorded, in order. Serialized as the list of its
+/// steps.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+}
+
+/// First difference between two traces (see [Trace::diff]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceDiff {
+    /// Index of the first step which differs
+    pub index: usize,
+    /// Step of the first trace, if it has that many steps
+    pub expected: Option<TraceStep>,
+    /// Step of the second trace, if it has that many steps
+    pub actual: Option<TraceStep>,
+}
+
+impl Trace {
+    pub fn new(steps: Vec<TraceStep>) -> Self {
+        Self { steps }
+    }
+
+    /// Deserialize a trace from JSON (e.g.: the output of [Trace::to_json]).
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Serialize the trace to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Total token usage of the completions of the trace (completions whose token usage was
+    /// not reported by the provider are not counted).
+    pub fn token_usage(&self) -> TokenUsage {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                TraceStep::Completion { usage, .. } => *usage,
+                _ => None,
+            })
+            .sum()
+    }
+
+    /// The prompts of the trace, with the chat histories they were sent with, in order.
+    pub fn prompts(&self) -> Vec<(&Message, &[Message])> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                TraceStep::Prompt {
+                    prompt,
+                    chat_history,
+                } => Some((prompt, chat_history.as_slice())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The first difference between this trace and `other`, if any. The token usage and the
+    /// raw responses of the completions are ignored, since they depend on the provider (e.g.:
+    /// they differ between a run and its replay).
+    pub fn diff(&self, other: &Trace) -> Option<TraceDiff> {
+        let len = self.steps.len().max(other.steps.len());
+        (0..len).find_map(|index| {
+            let expected = self.steps.get(index);
+            let actual = other.steps.get(index);
+            let same = matches!(
+                (expected, actual),
+                (Some(expected), Some(actual)) if expected.comparable() == actual.comparable()
+            );
+
+            (!same).then(|| TraceDiff {
+                index,
+                expected: expected.cloned(),
+                actual: actual.cloned(),
+            })
+        })
+    }
+
+    /// Mock completion model answering with the completions of the trace, in order. An agent
+    /// built with the same configuration and tools as the one which recorded the trace, and
+    /// prompted with the same prompts (see [Trace::prompts]), replays its run without calling
+    /// the provider.
+    pub fn replay_model(&self) -> MockCompletionModel {
+        self.steps
+            .iter()
+            .fold(MockCompletionModel::new(), |model, step| match step {
+                TraceStep::Completion { choice, .. } => model.response(choice.clone()),
+                _ => model,
+            })
+    }
+}
+
+/// Recorder of the [TraceStep]s of agent runs.
+///
+/// The recorder is cheap to clone and clones share the same trace, so a clone can be given
+/// to an agent (see [AgentBuilder::trace](crate::agent::AgentBuilder::trace)) while the
+/// original is used to read the trace.
+#[derive(Clone, Debug, Default)]
+pub struct TraceRecorder {
+    steps: Arc<Mutex<Vec<TraceStep>>>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a step to the trace.
+    pub fn record(&self, step: TraceStep) {
+        self.lock().push(step);
+    }
+
+    /// Copy of the steps recorded so far.
+    pub fn steps(&self) -> Vec<TraceStep> {
+        self.lock().clone()
+    }
+
+    /// The trace recorded so far.
+    pub fn trace(&self) -> Trace {
+        Trace::new(self.steps())
+    }
+
+    /// Take the steps recorded so far, leaving the trace empty.
+    pub fn take(&self) -> Vec<TraceStep> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Number of steps recorded so far.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total token usage of the completions recorded so far (completions whose token usage
+    /// was not reported by the provider are not counted).
+    pub fn token_usage(&self) -> TokenUsage {
+        self.trace().token_usage()
+    }
+
+    /// Serialize the steps recorded so far to pretty-printed JSON (see [Trace::from_json]).
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        self.trace().to_json()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<TraceStep>> {
+        self.steps
+            .lock()
+            .expect("Trace recorder lock should not be poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_recorder() {
+        let recorder = TraceRecorder::new();
+        let clone = recorder.clone();
+
+        clone.record(TraceStep::Prompt {
+            prompt: Message::user("Hello"),
+            chat_history: vec![],
+        });
+        clone.record(TraceStep::Completion {
+            choice: OneOrMany::one(AssistantContent::text("Hi!")),
+            usage: Some(TokenUsage {
+                input_tokens: 10,
+                output_tokens: 2,
+                total_tokens: 12,
+                ..Default::default()
+            }),
+            raw_response: None,
+        });
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.token_usage().total_tokens, 12);
+
+        let json: Vec<TraceStep> = serde_json::from_str(&recorder.to_json().unwrap()).unwrap();
+        assert_eq!(json, recorder.steps());
+
+        assert_eq!(recorder.take().len(), 2);
+        assert!(clone.is_empty());
+    }
+}
@@ -30,6 +30,11 @@ pub enum TranscriptionError {
     /// Error returned by the transcription model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Error returned by the transcription model provider, with its status and code (see
+    /// [rig::Error](crate::Error) to classify it)
+    #[error("ApiError: {0}")]
+    ApiError(#[from] crate::error::ApiError),
 }
 
 /// Trait defining a low-level LLM transcription interface
@@ -100,14 +105,15 @@ pub struct TranscriptionRequest {
 /// ```rust
 /// use rig::{
 ///     providers::openai::{Client, self},
-///     transcription::TranscriptionRequestBuilder,
+///     transcription::{TranscriptionModel, TranscriptionRequestBuilder},
 /// };
 ///
 /// let openai = Client::new("your-openai-api-key");
-/// let model = openai.transcription_model(openai::WHISPER_1).build();
+/// let model = openai.transcription_model(openai::WHISPER_1);
 ///
-/// // Create the completion request and execute it separately
-/// let request = TranscriptionRequestBuilder::new(model, "~/audio.mp3".to_string())
+/// // Create the transcription request and execute it separately
+/// let request = TranscriptionRequestBuilder::new(model.clone())
+///     .load_file("audio.mp3")
 ///     .temperature(0.5)
 ///     .build();
 ///
@@ -120,14 +126,15 @@ pub struct TranscriptionRequest {
 /// ```rust
 /// use rig::{
 ///     providers::openai::{Client, self},
-///     transcription::TranscriptionRequestBuilder,
+///     transcription::TranscriptionModel,
 /// };
 ///
 /// let openai = Client::new("your-openai-api-key");
-/// let model = openai.transcription_model(openai::WHISPER_1).build();
+/// let model = openai.transcription_model(openai::WHISPER_1);
 ///
-/// // Create the completion request and execute it directly
-/// let response = TranscriptionRequestBuilder::new(model, "~/audio.mp3".to_string())
+/// // Create the transcription request and execute it directly
+/// let response = model.transcription_request()
+///     .load_file("audio.mp3")
 ///     .temperature(0.5)
 ///     .send()
 ///     .await
@@ -0,0 +1,366 @@
+//! This module provides the [Filter] type, a provider-agnostic expression constraining the
+//! documents returned by vector searches (see [VectorStoreIndex::top_n_filtered]).
+//!
+//! Filters are evaluated against the fields of the documents: in memory with [Filter::matches],
+//! or lowered to the query language of the vector store (e.g.: [Filter::to_sql] for stores
+//! with SQL `WHERE` clauses such as LanceDB).
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::filter::Filter;
+//! use serde_json::json;
+//!
+//! // Documents of the tenant "acme" tagged "faq" or published after 2023
+//! let filter = Filter::eq("tenant", "acme")
+//!     .and(Filter::eq("tag", "faq").or(Filter::gt("year", 2023)));
+//!
+//! assert!(filter.matches(&json!({"tenant": "acme", "tag": "blog", "year": 2024})));
+//! assert_eq!(
+//!     filter.to_sql().unwrap(),
+//!     "(tenant = 'acme' AND (tag = 'faq' OR year > 2023))"
+//! );
+//! ```
+//!
+//!
+//! To scope all the searches on an index (e.g.: the dynamic context of an agent), wrap it in a
+//! [FilteredIndex]:
+//! ```rust,ignore
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .dynamic_context(2, FilteredIndex::new(index, Filter::eq("tenant", "acme")))
+//!     .build();
+//! ```
+//!
+//! [VectorStoreIndex::top_n_filtered]: super::VectorStoreIndex::top_n_filtered
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{VectorStoreError, VectorStoreIndex, VectorStoreStats};
+
+/// Filter on the fields of the documents of a vector store.
+/// Nested fields are referred to with dot-separated paths (e.g.: `"metadata.author"`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Eq(String, Value),
+    Ne(String, Value),
+    Gt(String, Value),
+    Gte(String, Value),
+    Lt(String, Value),
+    Lte(String, Value),
+    /// The field is equal to one of the values
+    In(String, Vec<Value>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(field: &str, value: impl Into<Value>) -> Self {
+        Self::Eq(field.to_string(), value.into())
+    }
+
+    pub fn ne(field: &str, value: impl Into<Value>) -> Self {
+        Self::Ne(field.to_string(), value.into())
+    }
+
+    pub fn gt(field: &str, value: impl Into<Value>) -> Self {
+        Self::Gt(field.to_string(), value.into())
+    }
+
+    pub fn gte(field: &str, value: impl Into<Value>) -> Self {
+        Self::Gte(field.to_string(), value.into())
+    }
+
+    pub fn lt(field: &str, value: impl Into<Value>) -> Self {
+        Self::Lt(field.to_string(), value.into())
+    }
+
+    pub fn lte(field: &str, value: impl Into<Value>) -> Self {
+        Self::Lte(field.to_string(), value.into())
+    }
+
+    pub fn one_of(field: &str, values: impl IntoIterator<Item = impl Into<Value>>) -> Self {
+        Self::In(
+            field.to_string(),
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    /// Combine the filter with `other`: documents must match both.
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Self::And(mut filters) => {
+                filters.push(other);
+                Self::And(filters)
+            }
+            filter => Self::And(vec![filter, other]),
+        }
+    }
+
+    /// Combine the filter with `other`: documents must match either.
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Self::Or(mut filters) => {
+                filters.push(other);
+                Self::Or(filters)
+            }
+            filter => Self::Or(vec![filter, other]),
+        }
+    }
+
+    /// Whether the (JSON serialized) document matches the filter.
+    /// Comparisons with a missing field, or a value of a different type, do not match.
+    pub fn matches(&self, document: &Value) -> bool {
+        let compare = |field: &str, value: &Value| {
+            field_value(document, field).and_then(|field_value| compare(field_value, value))
+        };
+
+        match self {
+            Self::Eq(field, value) => compare(field, value) == Some(Ordering::Equal),
+            Self::Ne(field, value) => {
+                matches!(
+                    compare(field, value),
+                    Some(Ordering::Less | Ordering::Greater)
+                )
+            }
+            Self::Gt(field, value) => compare(field, value) == Some(Ordering::Greater),
+            Self::Gte(field, value) => {
+                matches!(
+                    compare(field, value),
+                    Some(Ordering::Greater | Ordering::Equal)
+                )
+            }
+            Self::Lt(field, value) => compare(field, value) == Some(Ordering::Less),
+            Self::Lte(field, value) => {
+                matches!(
+                    compare(field, value),
+                    Some(Ordering::Less | Ordering::Equal)
+                )
+            }
+            Self::In(field, values) => values
+                .iter()
+                .any(|value| compare(field, value) == Some(Ordering::Equal)),
+            Self::And(filters) => filters.iter().all(|filter| filter.matches(document)),
+            Self::Or(filters) => filters.iter().any(|filter| filter.matches(document)),
+            Self::Not(filter) => !filter.matches(document),
+        }
+    }
+
+    /// Lower the filter to a SQL boolean expression, to be used in a `WHERE` clause.
+    /// Fails if a field is not a valid identifier or a value is not a scalar.
+    pub fn to_sql(&self) -> Result<String, VectorStoreError> {
+        let comparison = |field: &str, operator: &str, value: &Value| {
+            Ok(format!(
+                "{} {} {}",
+                sql_field(field)?,
+                operator,
+                sql_value(value)?
+            ))
+        };
+
+        let join = |filters: &[Filter], operator: &str, empty: &str| {
+            if filters.is_empty() {
+                return Ok(empty.to_string());
+            }
+            Ok(format!(
+                "({})",
+                filters
+                    .iter()
+                    .map(Filter::to_sql)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(&format!(" {operator} "))
+            ))
+        };
+
+        match self {
+            Self::Eq(field, Value::Null) => Ok(format!("{} IS NULL", sql_field(field)?)),
+            Self::Ne(field, Value::Null) => Ok(format!("{} IS NOT NULL", sql_field(field)?)),
+            Self::Eq(field, value) => comparison(field, "=", value),
+            Self::Ne(field, value) => comparison(field, "<>", value),
+            Self::Gt(field, value) => comparison(field, ">", value),
+            Self::Gte(field, value) => comparison(field, ">=", value),
+            Self::Lt(field, value) => comparison(field, "<", value),
+            Self::Lte(field, value) => comparison(field, "<=", value),
+            Self::In(_, values) if values.is_empty() => Ok("FALSE".to_string()),
+            Self::In(field, values) => Ok(format!(
+                "{} IN ({})",
+                sql_field(field)?,
+                values
+                    .iter()
+                    .map(sql_value)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(", ")
+            )),
+            Self::And(filters) => join(filters, "AND", "TRUE"),
+            Self::Or(filters) => join(filters, "OR", "FALSE"),
+            Self::Not(filter) => Ok(format!("NOT ({})", filter.to_sql()?)),
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Self::Output {
+        Self::Not(Box::new(self))
+    }
+}
+
+/// A [VectorStoreIndex] whose searches only return the documents matching a filter.
+/// The index being wrapped must support filters (see [VectorStoreIndex::top_n_filtered]).
+pub struct FilteredIndex<I: VectorStoreIndex> {
+    index: I,
+    filter: Filter,
+}
+
+impl<I: VectorStoreIndex> FilteredIndex<I> {
+    pub fn new(index: I, filter: Filter) -> Self {
+        Self { index, filter }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.index
+    }
+}
+
+impl<I: VectorStoreIndex> VectorStoreIndex for FilteredIndex<I> {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.index
+            .top_n_filtered(query, n, self.filter.clone())
+            .await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.index
+            .top_n_ids_filtered(query, n, self.filter.clone())
+            .await
+    }
+
+    async fn top_n_filtered<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.index
+            .top_n_filtered(query, n, self.filter.clone().and(filter))
+            .await
+    }
+
+    async fn top_n_ids_filtered(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.index
+            .top_n_ids_filtered(query, n, self.filter.clone().and(filter))
+            .await
+    }
+
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        self.index.stats().await
+    }
+}
+
+fn field_value<'a>(document: &'a Value, field: &str) -> Option<&'a Value> {
+    field
+        .split('.')
+        .try_fold(document, |value, key| value.as_object()?.get(key))
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (a, b) => (a == b).then_some(Ordering::Equal),
+    }
+}
+
+fn sql_field(field: &str) -> Result<&str, VectorStoreError> {
+    let is_identifier = |part: &str| {
+        part.chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+
+    if field.split('.').all(is_identifier) {
+        Ok(field)
+    } else {
+        Err(VectorStoreError::FilterError(format!(
+            "Invalid field name: {field}"
+        )))
+    }
+}
+
+fn sql_value(value: &Value) -> Result<String, VectorStoreError> {
+    match value {
+        Value::Null => Ok("NULL".to_string()),
+        Value::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::String(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+        value => Err(VectorStoreError::FilterError(format!(
+            "Unsupported value in SQL filter: {value}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let document = json!({
+            "tenant": "acme",
+            "year": 2024,
+            "metadata": {"author": "Alice"},
+        });
+
+        assert!(Filter::eq("tenant", "acme").matches(&document));
+        assert!(Filter::eq("year", 2024.0).matches(&document));
+        assert!(Filter::eq("metadata.author", "Alice").matches(&document));
+        assert!(Filter::gte("year", 2024)
+            .and(Filter::lt("year", 2025))
+            .matches(&document));
+        assert!(Filter::one_of("tenant", ["acme", "globex"]).matches(&document));
+        assert!((!Filter::eq("tenant", "globex")).matches(&document));
+
+        // Missing fields and mismatched types do not match
+        assert!(!Filter::eq("missing", "acme").matches(&document));
+        assert!(!Filter::ne("missing", "acme").matches(&document));
+        assert!(!Filter::gt("tenant", 1).matches(&document));
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let filter = Filter::eq("tenant", "o'brien")
+            .and(Filter::one_of("year", [2023, 2024]))
+            .and((!Filter::eq("metadata.author", Value::Null)).or(Filter::eq("draft", false)));
+
+        assert_eq!(
+            filter.to_sql().unwrap(),
+            "(tenant = 'o''brien' AND year IN (2023, 2024) AND \
+            (NOT (metadata.author IS NULL) OR draft = FALSE))"
+        );
+
+        assert!(Filter::eq("tenant; DROP TABLE docs", "acme")
+            .to_sql()
+            .is_err());
+        assert!(Filter::eq("tags", json!(["a"])).to_sql().is_err());
+    }
+}
@@ -0,0 +1,471 @@
+//! Hierarchical Navigable Small World (HNSW) graph, an approximate nearest neighbor index
+//! making vector searches on large local indexes (e.g.: more than 100k documents) sublinear
+//! (see [InMemoryVectorStore::hnsw]).
+//!
+//! The vectors are linked to their nearest neighbors in a hierarchy of graphs: searches descend
+//! from a sparse layer of long links to the dense bottom layer, exploring only a small fraction
+//! of the vectors. Results are approximate: higher values of [HnswConfig::ef_search] (and of
+//! [HnswConfig::ef_construction]) improve recall at the cost of latency.
+//!
+//! Removed documents stay in the graph (to keep it connected) until more than a quarter of its
+//! vectors are removed, when it is rebuilt without them.
+//!
+//! Requires the `hnsw` feature.
+//!
+//! Reference: Malkov & Yashunin, "Efficient and robust approximate nearest neighbor search using
+//! Hierarchical Navigable Small World graphs" (2016).
+//!
+//! [InMemoryVectorStore::hnsw]: super::in_memory_store::InMemoryVectorStore::hnsw
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use ordered_float::OrderedFloat;
+
+use crate::embeddings::{distance::DistanceMetric, Embedding};
+
+/// Share of removed nodes above which the graph is rebuilt without them
+const COMPACTION_RATIO: f64 = 0.25;
+
+/// Configuration of an HNSW index (see the [module](self) documentation).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HnswConfig {
+    /// Number of neighbors of each vector in the upper layers (twice as many in the bottom layer)
+    pub m: usize,
+    /// Number of candidates explored when inserting a vector
+    pub ef_construction: usize,
+    /// Number of candidates explored when searching (at least the number of requested documents)
+    pub ef_search: usize,
+    /// Seed of the random levels of the vectors, so the graph is deterministic
+    pub seed: u64,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            seed: 42,
+        }
+    }
+}
+
+impl HnswConfig {
+    /// Set the number of neighbors of each vector
+    pub fn m(mut self, m: usize) -> Self {
+        self.m = m.max(2);
+        self
+    }
+
+    /// Set the number of candidates explored when inserting a vector
+    pub fn ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction.max(1);
+        self
+    }
+
+    /// Set the number of candidates explored when searching
+    pub fn ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search.max(1);
+        self
+    }
+
+    /// Set the seed of the random levels of the vectors
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// A vector of the graph: the embedding `index` of the document `id`.
+#[derive(Clone, Debug)]
+struct Node {
+    id: String,
+    index: usize,
+    vec: Embedding,
+    /// Neighbors of the node, in each layer from the bottom one
+    neighbors: Vec<Vec<usize>>,
+    /// Removed nodes are still traversed (to keep the graph connected) but never returned
+    removed: bool,
+}
+
+/// HNSW graph over the embeddings of the documents of a vector store.
+#[derive(Clone, Debug)]
+pub(crate) struct Hnsw {
+    config: HnswConfig,
+    /// Metric by which the nearest neighbors are found
+    metric: DistanceMetric,
+    nodes: Vec<Node>,
+    /// Nodes of each document
+    by_id: HashMap<String, Vec<usize>>,
+    /// Node of the top layer from which searches start
+    entry_point: Option<usize>,
+    /// Number of removed nodes still in the graph
+    removed: usize,
+    rng: u64,
+}
+
+impl Hnsw {
+    pub(crate) fn new(config: HnswConfig, metric: DistanceMetric) -> Self {
+        Self {
+            config,
+            metric,
+            nodes: vec![],
+            by_id: HashMap::new(),
+            entry_point: None,
+            removed: 0,
+            rng: config.seed | 1,
+        }
+    }
+
+    pub(crate) fn config(&self) -> HnswConfig {
+        self.config
+    }
+
+    /// Insert the embeddings of the document `id`, replacing its previous ones (if any).
+    pub(crate) fn insert<'a>(&mut self, id: &str, embeddings: impl Iterator<Item = &'a Embedding>) {
+        self.remove(id);
+        for (index, embedding) in embeddings.enumerate() {
+            self.insert_node(id, index, embedding.clone());
+        }
+    }
+
+    /// Remove the embeddings of the document `id`. The graph is compacted once the share of
+    /// removed nodes exceeds [COMPACTION_RATIO].
+    pub(crate) fn remove(&mut self, id: &str) {
+        for node in self.by_id.remove(id).unwrap_or_default() {
+            self.nodes[node].removed = true;
+            self.removed += 1;
+        }
+
+        if self.removed as f64 > COMPACTION_RATIO * self.nodes.len() as f64 {
+            self.compact();
+        }
+    }
+
+    /// Rebuild the graph without its removed nodes.
+    fn compact(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.by_id.clear();
+        self.entry_point = None;
+        self.removed = 0;
+        for node in nodes.into_iter().filter(|node| !node.removed) {
+            self.insert_node(&node.id, node.index, node.vec);
+        }
+    }
+
+    /// Number of removed nodes still in the graph
+    #[cfg(test)]
+    pub(crate) fn removed_count(&self) -> usize {
+        self.removed
+    }
+
+    /// Approximate memory used by the graph (vectors and links), in bytes.
+    pub(crate) fn size_bytes(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| {
+                node.id.len()
+                    + node.vec.vec.len() * std::mem::size_of::<f64>()
+                    + node.neighbors.iter().map(Vec::len).sum::<usize>()
+                        * std::mem::size_of::<usize>()
+            })
+            .sum()
+    }
+
+    /// Approximate top `n` documents most similar to `query`, as (similarity, document id,
+    /// index of the most similar embedding of the document), from most to least similar.
+    pub(crate) fn search(&self, query: &Embedding, n: usize) -> Vec<(f64, &str, usize)> {
+        let Some(entry_point) = self.entry_point else {
+            return vec![];
+        };
+
+        let mut entry_point = entry_point;
+        for layer in (1..self.nodes[entry_point].neighbors.len()).rev() {
+            entry_point = self.greedy_search(query, entry_point, layer);
+        }
+
+        // Removed nodes and the other embeddings of the documents found are skipped, so the
+        // search is widened until it finds `n` documents (or explores the whole graph)
+        let mut ef = self.config.ef_search.max(n);
+        loop {
+            let mut found = HashSet::new();
+            let results = self
+                .search_layer(query, &[entry_point], ef, 0)
+                .into_iter()
+                .map(|(similarity, node)| (similarity, &self.nodes[node]))
+                .filter(|(_, node)| !node.removed && found.insert(node.id.as_str()))
+                .take(n)
+                .map(|(similarity, node)| (similarity, node.id.as_str(), node.index))
+                .collect::<Vec<_>>();
+
+            if results.len() == n || ef >= self.nodes.len() {
+                return results;
+            }
+            ef = ef.saturating_mul(2);
+        }
+    }
+
+    fn similarity(&self, query: &Embedding, node: usize) -> f64 {
+        self.metric.similarity(query, &self.nodes[node].vec)
+    }
+
+    /// Random level of a new node: the level `l` has a probability of `1 / m^l`.
+    fn random_level(&mut self) -> usize {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let uniform = (self.rng >> 11) as f64 / (1u64 << 53) as f64;
+        (-(1.0 - uniform).ln() / (self.config.m as f64).ln()).floor() as usize
+    }
+
+    /// Maximum number of neighbors of a node in `layer`
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    fn insert_node(&mut self, id: &str, index: usize, vec: Embedding) {
+        let level = self.random_level();
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            id: id.to_string(),
+            index,
+            vec,
+            neighbors: vec![vec![]; level + 1],
+            removed: false,
+        });
+        self.by_id.entry(id.to_string()).or_default().push(node);
+
+        let Some(mut entry_point) = self.entry_point else {
+            self.entry_point = Some(node);
+            return;
+        };
+
+        let query = self.nodes[node].vec.clone();
+        let top_level = self.nodes[entry_point].neighbors.len() - 1;
+
+        for layer in (level + 1..=top_level).rev() {
+            entry_point = self.greedy_search(&query, entry_point, layer);
+        }
+
+        let mut entry_points = vec![entry_point];
+        for layer in (0..=level.min(top_level)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry_points, self.config.ef_construction, layer);
+            entry_points = candidates.iter().map(|(_, node)| *node).collect();
+
+            let neighbors = candidates
+                .into_iter()
+                .take(self.max_neighbors(layer))
+                .map(|(_, node)| node)
+                .collect::<Vec<_>>();
+            for neighbor in &neighbors {
+                self.link(*neighbor, node, layer);
+            }
+            self.nodes[node].neighbors[layer] = neighbors;
+        }
+
+        if level > top_level {
+            self.entry_point = Some(node);
+        }
+    }
+
+    /// Link `from` to `to` in `layer`, keeping only the nearest neighbors of `from`.
+    fn link(&mut self, from: usize, to: usize, layer: usize) {
+        self.nodes[from].neighbors[layer].push(to);
+        if self.nodes[from].neighbors[layer].len() <= self.max_neighbors(layer) {
+            return;
+        }
+
+        let vec = &self.nodes[from].vec;
+        let mut neighbors = self.nodes[from].neighbors[layer]
+            .iter()
+            .map(|neighbor| (OrderedFloat(self.similarity(vec, *neighbor)), *neighbor))
+            .collect::<Vec<_>>();
+        neighbors.sort_by(|a, b| b.cmp(a));
+        neighbors.truncate(self.max_neighbors(layer));
+        self.nodes[from].neighbors[layer] = neighbors
+            .into_iter()
+            .map(|(_, neighbor)| neighbor)
+            .collect();
+    }
+
+    /// Follow the links of `layer` from `entry_point` to the node most similar to `query`.
+    fn greedy_search(&self, query: &Embedding, mut entry_point: usize, layer: usize) -> usize {
+        let mut best = self.similarity(query, entry_point);
+        loop {
+            let next = self.nodes[entry_point].neighbors[layer]
+                .iter()
+                .map(|neighbor| (self.similarity(query, *neighbor), *neighbor))
+                .filter(|(similarity, _)| *similarity > best)
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            match next {
+                Some((similarity, neighbor)) => {
+                    best = similarity;
+                    entry_point = neighbor;
+                }
+                None => return entry_point,
+            }
+        }
+    }
+
+    /// Best-first search of `layer` from `entry_points`, keeping the `ef` nodes most similar to
+    /// `query`. Returns (similarity, node), from most to least similar.
+    fn search_layer(
+        &self,
+        query: &Embedding,
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<(f64, usize)> {
+        let mut visited = entry_points.iter().copied().collect::<HashSet<_>>();
+        // Candidates to explore, most similar first
+        let mut candidates = BinaryHeap::new();
+        // Best nodes found, least similar first
+        let mut results = BinaryHeap::new();
+
+        for node in entry_points {
+            let similarity = OrderedFloat(self.similarity(query, *node));
+            candidates.push((similarity, *node));
+            results.push(Reverse((similarity, *node)));
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some((similarity, node)) = candidates.pop() {
+            let Some(Reverse((worst, _))) = results.peek() else {
+                break;
+            };
+            if similarity < *worst && results.len() >= ef {
+                break;
+            }
+
+            for neighbor in &self.nodes[node].neighbors[layer] {
+                if !visited.insert(*neighbor) {
+                    continue;
+                }
+
+                let similarity = OrderedFloat(self.similarity(query, *neighbor));
+                let worst = results.peek().map(|Reverse((worst, _))| *worst);
+                if results.len() < ef || worst.is_some_and(|worst| similarity > worst) {
+                    candidates.push((similarity, *neighbor));
+                    results.push(Reverse((similarity, *neighbor)));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((similarity, node))| (similarity.0, node))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(i: usize) -> Embedding {
+        // Pseudo-random unit vectors
+        let mut state = (i as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15);
+        let vec = (0..16)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % 2001) as f64 / 1000.0 - 1.0
+            })
+            .collect();
+        Embedding {
+            document: i.to_string(),
+            vec,
+        }
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let embeddings = (0..2000).map(embedding).collect::<Vec<_>>();
+        let mut hnsw = Hnsw::new(HnswConfig::default(), DistanceMetric::Cosine);
+        for (i, embedding) in embeddings.iter().enumerate() {
+            hnsw.insert(&format!("doc{i}"), std::iter::once(embedding));
+        }
+
+        let mut found = 0;
+        for query in (5000..5020).map(embedding) {
+            let mut exact = embeddings
+                .iter()
+                .enumerate()
+                .map(|(i, embedding)| {
+                    (
+                        OrderedFloat(DistanceMetric::Cosine.similarity(&query, embedding)),
+                        format!("doc{i}"),
+                    )
+                })
+                .collect::<Vec<_>>();
+            exact.sort_by(|a, b| b.cmp(a));
+
+            let results = hnsw.search(&query, 10);
+            assert_eq!(results.len(), 10);
+            found += exact[..10]
+                .iter()
+                .filter(|(_, id)| results.iter().any(|(_, result, _)| result == id))
+                .count();
+        }
+
+        // Recall@10 over 20 queries
+        assert!(
+            found as f64 / 200.0 > 0.95,
+            "recall: {}",
+            found as f64 / 200.0
+        );
+
+        hnsw.remove("doc0");
+        assert!(hnsw
+            .search(&embeddings[0], 10)
+            .iter()
+            .all(|(_, id, _)| *id != "doc0"));
+        assert_eq!(hnsw.removed_count(), 1);
+    }
+
+    #[test]
+    fn test_hnsw_removals() {
+        let mut hnsw = Hnsw::new(HnswConfig::default().ef_search(4), DistanceMetric::Cosine);
+        // Documents of 3 embeddings each
+        for i in 0..100 {
+            let embeddings = (3 * i..3 * i + 3).map(embedding).collect::<Vec<_>>();
+            hnsw.insert(&format!("doc{i}"), embeddings.iter());
+        }
+
+        // The search is widened to find `n` documents, skipping the removed ones and the other
+        // embeddings of the documents found
+        for i in 0..20 {
+            hnsw.remove(&format!("doc{i}"));
+        }
+        assert_eq!(hnsw.removed_count(), 60);
+        let results = hnsw.search(&embedding(0), 50);
+        assert_eq!(results.len(), 50);
+        let ids = results.iter().map(|(_, id, _)| *id).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 50);
+        assert!(ids.iter().all(|id| id[3..].parse::<usize>().unwrap() >= 20));
+
+        // The graph is rebuilt without the removed nodes once they are more than a quarter of
+        // its nodes (after removing 26 documents, 78 of 300 nodes)
+        for i in 20..30 {
+            hnsw.remove(&format!("doc{i}"));
+        }
+        assert_eq!(hnsw.nodes.len(), 300 - 78);
+        assert_eq!(hnsw.removed_count(), 12);
+        assert_eq!(hnsw.search(&embedding(0), 100).len(), 70);
+    }
+}
@@ -1,26 +1,136 @@
 //! In-memory implementation of a vector store.
 use std::{
     cmp::Reverse,
-    collections::{BinaryHeap, HashMap},
+    collections::{hash_map, BinaryHeap, HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
 use ordered_float::OrderedFloat;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use super::{VectorStoreError, VectorStoreIndex};
+#[cfg(feature = "hnsw")]
+use super::hnsw::{Hnsw, HnswConfig};
+use super::{
+    filter::Filter,
+    quantization::{Quantization, QuantizationConfig, QuantizedVector},
+    schema::{embed_documents, EmbeddingSchema},
+    VectorStore, VectorStoreCollections, VectorStoreError, VectorStoreIndex, VectorStoreStats,
+};
 use crate::{
-    embeddings::{distance::VectorDistance, Embedding, EmbeddingModel},
+    embeddings::{
+        distance::DistanceMetric,
+        embed::{to_field_texts, FieldText},
+        Embed, EmbedError, Embedding, EmbeddingModel,
+    },
     OneOrMany,
 };
 
 /// [InMemoryVectorStore] is a simple in-memory vector store that stores embeddings
 /// in-memory using a HashMap.
-#[derive(Clone, Default)]
+/// Search results are sorted by similarity, with ties broken by document id so that
+/// the results are deterministic.
+///
+/// Besides vector search, the store supports keyword search (BM25 scoring of the embedded text
+/// of the documents, see [InMemoryVectorStore::top_n_by_keywords]) and hybrid search, which fuses
+/// both rankings (see [InMemoryVectorIndex::hybrid]). Keyword search finds exact terms (e.g.:
+/// error codes, identifiers) that embeddings tend to miss.
+#[derive(Clone)]
 pub struct InMemoryVectorStore<D: Serialize> {
     /// The embeddings are stored in a HashMap.
     /// Hashmap key is the document id.
     /// Hashmap value is a tuple of the serializable document and its corresponding embeddings.
     embeddings: HashMap<String, (D, OneOrMany<Embedding>)>,
+    /// Quantized vectors of the documents, if the store is quantized
+    quantized: Option<QuantizedVectors>,
+    /// HNSW graph of the embeddings, if enabled
+    #[cfg(feature = "hnsw")]
+    hnsw: Option<Hnsw>,
+    /// Metric by which the query is compared to the embeddings
+    metric: DistanceMetric,
+    /// Model, dimensions and schema version of the embeddings, if known
+    embedding_schema: Option<EmbeddingSchema>,
+    /// Weights of the fields of the documents in vector searches, if enabled (see
+    /// [InMemoryVectorStore::field_weights])
+    field_weights: Option<FieldWeights<D>>,
+}
+
+// Not derived, as an empty store does not require a default document
+impl<D: Serialize> Default for InMemoryVectorStore<D> {
+    fn default() -> Self {
+        Self {
+            embeddings: HashMap::new(),
+            quantized: None,
+            #[cfg(feature = "hnsw")]
+            hnsw: None,
+            metric: DistanceMetric::default(),
+            embedding_schema: None,
+            field_weights: None,
+        }
+    }
+}
+
+/// Weights of the fields of the documents of an [InMemoryVectorStore], with the field of each
+/// embedding of the documents.
+#[derive(Clone)]
+struct FieldWeights<D> {
+    weights: HashMap<String, f64>,
+    /// Texts to embed of a document, with their fields (see [TextEmbedder::embed_field](crate::embeddings::TextEmbedder::embed_field))
+    field_texts: fn(&D) -> Result<Vec<FieldText>, EmbedError>,
+    /// Field of each embedding of each document, by document id
+    fields: HashMap<String, Vec<Option<String>>>,
+}
+
+impl<D> FieldWeights<D> {
+    /// Update the fields of the embeddings of the document `id` after it was inserted, replaced
+    /// or removed.
+    fn index_document(&mut self, id: &str, entry: Option<&(D, OneOrMany<Embedding>)>) {
+        let Some((document, embeddings)) = entry else {
+            self.fields.remove(id);
+            return;
+        };
+
+        let texts = (self.field_texts)(document).unwrap_or_default();
+        self.fields
+            .insert(id.to_string(), embedding_fields(&texts, embeddings));
+    }
+
+    /// Weight of the `index`-th embedding of the document `id`, if it is an embedding of a
+    /// weighted field.
+    fn weight(&self, id: &str, index: usize) -> Option<(&String, f64)> {
+        let field = self.fields.get(id)?.get(index)?.as_ref()?;
+        Some((field, *self.weights.get(field)?))
+    }
+}
+
+/// Field of each embedding of a document whose texts to embed are `texts`. The embeddings are in
+/// the order of the texts, each embedding a text or a part of it (see
+/// [TruncationPolicy](crate::embeddings::TruncationPolicy)), so they are matched to the texts in
+/// order.
+fn embedding_fields(texts: &[FieldText], embeddings: &OneOrMany<Embedding>) -> Vec<Option<String>> {
+    let mut current = 0;
+    embeddings
+        .iter()
+        .map(|embedding| {
+            let offset = texts[current.min(texts.len())..]
+                .iter()
+                .position(|(_, text)| text.contains(&embedding.document))?;
+            current += offset;
+            texts[current].0.clone()
+        })
+        .collect()
+}
+
+/// Quantized vectors of the documents of an [InMemoryVectorStore], by document id.
+#[derive(Clone)]
+struct QuantizedVectors {
+    config: QuantizationConfig,
+    /// Number of dimensions of the vectors (known even if the full-precision vectors were
+    /// discarded)
+    ndims: Option<usize>,
+    vectors: HashMap<String, Vec<QuantizedVector>>,
 }
 
 impl<D: Serialize + Eq> InMemoryVectorStore<D> {
@@ -36,7 +146,15 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
                 store.insert(format!("doc{i}"), (doc, embeddings));
             });
 
-        Self { embeddings: store }
+        Self {
+            embeddings: store,
+            quantized: None,
+            #[cfg(feature = "hnsw")]
+            hnsw: None,
+            metric: DistanceMetric::default(),
+            embedding_schema: None,
+            field_weights: None,
+        }
     }
 
     /// Create a new [InMemoryVectorStore] from documents and and their corresponding embeddings with ids.
@@ -48,7 +166,15 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
             store.insert(i.to_string(), (doc, embeddings));
         });
 
-        Self { embeddings: store }
+        Self {
+            embeddings: store,
+            quantized: None,
+            #[cfg(feature = "hnsw")]
+            hnsw: None,
+            metric: DistanceMetric::default(),
+            embedding_schema: None,
+            field_weights: None,
+        }
     }
 
     /// Create a new [InMemoryVectorStore] from documents and their corresponding embeddings.
@@ -62,23 +188,159 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
             store.insert(f(&doc), (doc, embeddings));
         });
 
-        Self { embeddings: store }
+        Self {
+            embeddings: store,
+            quantized: None,
+            #[cfg(feature = "hnsw")]
+            hnsw: None,
+            metric: DistanceMetric::default(),
+            embedding_schema: None,
+            field_weights: None,
+        }
     }
 
     /// Implement vector search on [InMemoryVectorStore].
     /// To be used by implementations of [VectorStoreIndex::top_n] and [VectorStoreIndex::top_n_ids] methods.
-    fn vector_search(&self, prompt_embedding: &Embedding, n: usize) -> EmbeddingRanking<D> {
+    fn vector_search(&self, prompt_embedding: &Embedding, n: usize) -> EmbeddingRanking<'_, D> {
+        self.filtered_vector_search(prompt_embedding, n, None)
+    }
+
+    /// Same as `vector_search`, but only among the documents matching `filter` (if any).
+    fn filtered_vector_search(
+        &self,
+        prompt_embedding: &Embedding,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> EmbeddingRanking<'_, D> {
+        let docs = match &self.quantized {
+            // Searches fusing the scores of the fields compare the query to all the embeddings
+            _ if self.field_weights.is_some() && !self.discarded_vectors() => {
+                self.exact_vector_search(self.embeddings.iter(), prompt_embedding, n, filter)
+            }
+            // The graph is used for unfiltered searches of a part of the documents only
+            #[cfg(feature = "hnsw")]
+            _ if self.hnsw.is_some() && filter.is_none() && n < self.embeddings.len() => {
+                self.hnsw_search(prompt_embedding, n)
+            }
+            Some(quantized) => self.quantized_vector_search(quantized, prompt_embedding, n, filter),
+            _ => self.exact_vector_search(self.embeddings.iter(), prompt_embedding, n, filter),
+        };
+
+        // Log selected tools with their similarities
+        tracing::info!(target: "rig",
+            "Selected documents: {}",
+            docs.iter()
+                .map(|Reverse(RankingItem(similarity, id, _, _))| {
+                    format!("{} ({})", id, similarity.0)
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        docs
+    }
+
+    /// Rank the documents among `entries` matching `filter` (if any) by the similarity of their
+    /// best embedding to the query (or the weighted score of their fields, if
+    /// [field weights](InMemoryVectorStore::field_weights) are set), and keep the top `n`.
+    fn exact_vector_search<'a>(
+        &self,
+        entries: impl Iterator<Item = (&'a String, &'a (D, OneOrMany<Embedding>))>,
+        prompt_embedding: &Embedding,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> EmbeddingRanking<'a, D> {
+        let entries = entries
+            .filter(|(_, (doc, _))| matches_filter(doc, filter))
+            .collect::<Vec<_>>();
+        let similarities = best_similarities(
+            &entries
+                .iter()
+                .map(|(id, (_, embeddings))| (*id, embeddings))
+                .collect::<Vec<_>>(),
+            prompt_embedding,
+            self.metric,
+            self.field_weights.as_ref(),
+        );
+
         // Sort documents by best embedding distance
         let mut docs = BinaryHeap::new();
 
-        for (id, (doc, embeddings)) in self.embeddings.iter() {
+        for ((id, (doc, embeddings)), best) in entries.into_iter().zip(similarities) {
             // Get the best context for the document given the prompt
-            if let Some((distance, embed_doc)) = embeddings
+            if let Some((distance, embed_doc)) =
+                best.and_then(|(distance, index)| Some((distance, embeddings.iter().nth(index)?)))
+            {
+                docs.push(Reverse(RankingItem(distance, id, doc, embed_doc)));
+            };
+
+            // If the heap size exceeds n, pop the least old element.
+            if docs.len() > n {
+                docs.pop();
+            }
+        }
+
+        docs
+    }
+
+    /// Approximate top `n` documents most similar to the query, found with the HNSW graph.
+    #[cfg(feature = "hnsw")]
+    fn hnsw_search(&self, prompt_embedding: &Embedding, n: usize) -> EmbeddingRanking<'_, D> {
+        let mut docs = BinaryHeap::new();
+        let Some(hnsw) = &self.hnsw else {
+            return docs;
+        };
+
+        for (similarity, id, index) in hnsw.search(prompt_embedding, n) {
+            let Some((id, (doc, embeddings))) = self.embeddings.get_key_value(id) else {
+                continue;
+            };
+            let Some(embedding) = embeddings.iter().nth(index) else {
+                continue;
+            };
+            docs.push(Reverse(RankingItem(
+                OrderedFloat(similarity),
+                id,
+                doc,
+                embedding,
+            )));
+        }
+
+        docs
+    }
+
+    /// Rank the documents matching `filter` (if any) by the similarity of their quantized
+    /// vectors to the query, under the metric of the store. If rescoring is enabled, the top candidates are then reranked by
+    /// their exact similarity under the metric of the store. Keeps the top `n`.
+    fn quantized_vector_search<'a>(
+        &'a self,
+        quantized: &QuantizedVectors,
+        prompt_embedding: &Embedding,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> EmbeddingRanking<'a, D> {
+        let query = QuantizedVector::new(quantized.config.quantization, &prompt_embedding.vec);
+        let candidates = quantized
+            .config
+            .rescore
+            .map_or(n, |oversampling| n.saturating_mul(oversampling));
+
+        let mut docs = BinaryHeap::new();
+        for (id, (doc, embeddings)) in self.embeddings.iter() {
+            if !matches_filter(doc, filter) {
+                continue;
+            }
+            let Some(vectors) = quantized.vectors.get(id) else {
+                continue;
+            };
+
+            if let Some((distance, embed_doc)) = vectors
                 .iter()
-                .map(|embedding| {
+                .zip(embeddings.iter())
+                .map(|(vector, embedding)| {
                     (
-                        OrderedFloat(embedding.cosine_similarity(prompt_embedding, false)),
-                        &embedding.document,
+                        OrderedFloat(vector.similarity(&query, self.metric)),
+                        embedding,
                     )
                 })
                 .max_by(|a, b| a.0.cmp(&b.0))
@@ -86,62 +348,243 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
                 docs.push(Reverse(RankingItem(distance, id, doc, embed_doc)));
             };
 
-            // If the heap size exceeds n, pop the least old element.
-            if docs.len() > n {
+            if docs.len() > candidates {
                 docs.pop();
             }
         }
 
-        // Log selected tools with their distances
-        tracing::info!(target: "rig",
-            "Selected documents: {}",
-            docs.iter()
-                .map(|Reverse(RankingItem(distance, id, _, _))| format!("{} ({})", id, distance))
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
+        if quantized.config.rescore.is_none() {
+            return docs;
+        }
 
-        docs
+        let candidates = docs
+            .into_iter()
+            .filter_map(|Reverse(RankingItem(_, id, _, _))| self.embeddings.get_key_value(id));
+        self.exact_vector_search(candidates, prompt_embedding, n, None)
+    }
+
+    /// Keyword search on [InMemoryVectorStore]: BM25 scores of the documents matching `filter`
+    /// (if any) which contain at least one term of `query`, from most to least relevant.
+    /// The text of a document is the text of its embeddings.
+    fn keyword_search(&self, query: &str, filter: Option<&Filter>) -> Vec<(f64, &String, &D)> {
+        let terms = tokenize(query).collect::<HashSet<_>>();
+        if terms.is_empty() {
+            return vec![];
+        }
+
+        // Term frequencies (of the query terms only) and length of each document
+        let docs = self
+            .embeddings
+            .iter()
+            .filter(|(_, (doc, _))| matches_filter(doc, filter))
+            .map(|(id, (doc, embeddings))| {
+                let mut frequencies = HashMap::new();
+                let mut length = 0;
+                for token in embeddings
+                    .iter()
+                    .flat_map(|embedding| tokenize(&embedding.document))
+                {
+                    length += 1;
+                    if terms.contains(&token) {
+                        *frequencies.entry(token).or_insert(0usize) += 1;
+                    }
+                }
+                (id, doc, frequencies, length)
+            })
+            .collect::<Vec<_>>();
+
+        let doc_count = docs.len() as f64;
+        let avg_length =
+            docs.iter().map(|(_, _, _, length)| *length).sum::<usize>() as f64 / doc_count.max(1.0);
+
+        let idf = terms
+            .iter()
+            .map(|term| {
+                let doc_frequency = docs
+                    .iter()
+                    .filter(|(_, _, frequencies, _)| frequencies.contains_key(term))
+                    .count() as f64;
+                (
+                    term,
+                    (1.0 + (doc_count - doc_frequency + 0.5) / (doc_frequency + 0.5)).ln(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut results = docs
+            .into_iter()
+            .filter(|(_, _, frequencies, _)| !frequencies.is_empty())
+            .map(|(id, doc, frequencies, length)| {
+                let length_norm = 1.0 - BM25_B + BM25_B * length as f64 / avg_length.max(1.0);
+                let score = frequencies
+                    .iter()
+                    .map(|(term, frequency)| {
+                        let frequency = *frequency as f64;
+                        idf[term] * frequency * (BM25_K1 + 1.0)
+                            / (frequency + BM25_K1 * length_norm)
+                    })
+                    .sum::<f64>();
+                (score, id, doc)
+            })
+            .collect::<Vec<_>>();
+
+        sort_ranking(&mut results);
+        results
+    }
+
+    /// Hybrid search on [InMemoryVectorStore]: the vector ranking and the keyword ranking of the
+    /// documents matching `filter` (if any) are fused according to `fusion`.
+    /// Returns the top `n` documents, from most to least relevant.
+    fn hybrid_search(
+        &self,
+        prompt_embedding: &Embedding,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+        fusion: FusionStrategy,
+    ) -> Vec<(f64, &String, &D)> {
+        let vector_ranking = self
+            .filtered_vector_search(prompt_embedding, self.embeddings.len(), filter)
+            .into_sorted_vec();
+        let keyword_ranking = self.keyword_search(query, filter);
+
+        let mut scores: HashMap<&String, (f64, &D)> = HashMap::new();
+        match fusion {
+            FusionStrategy::ReciprocalRank { k } => {
+                let ranks = vector_ranking
+                    .iter()
+                    .map(|Reverse(RankingItem(_, id, doc, _))| (*id, *doc))
+                    .enumerate()
+                    .chain(
+                        keyword_ranking
+                            .iter()
+                            .map(|(_, id, doc)| (*id, *doc))
+                            .enumerate(),
+                    );
+                for (rank, (id, doc)) in ranks {
+                    scores.entry(id).or_insert((0.0, doc)).0 += 1.0 / (k + rank as f64 + 1.0);
+                }
+            }
+            FusionStrategy::Weighted { vector_weight } => {
+                let max_keyword_score = keyword_ranking
+                    .first()
+                    .map(|(score, _, _)| *score)
+                    .unwrap_or(1.0);
+                for Reverse(RankingItem(similarity, id, doc, _)) in &vector_ranking {
+                    scores.insert(id, (vector_weight * similarity.0, doc));
+                }
+                for (score, id, doc) in &keyword_ranking {
+                    scores.entry(id).or_insert((0.0, doc)).0 +=
+                        (1.0 - vector_weight) * score / max_keyword_score;
+                }
+            }
+        }
+
+        let mut results = scores
+            .into_iter()
+            .map(|(id, (score, doc))| (score, id, doc))
+            .collect::<Vec<_>>();
+        sort_ranking(&mut results);
+        results.truncate(n);
+        results
     }
 
     /// Add documents and their corresponding embeddings to the store.
     /// Ids are automatically generated have will have the form `"doc{n}"` where `n`
     /// is the index of the document.
+    ///
+    /// Fails with [VectorStoreError::DimensionMismatch] if the embeddings do not all have the
+    /// number of dimensions of the embeddings of the store, in which case no document is added.
     pub fn add_documents(
         &mut self,
         documents: impl IntoIterator<Item = (D, OneOrMany<Embedding>)>,
-    ) {
+    ) -> Result<(), VectorStoreError> {
         let current_index = self.embeddings.len();
-        documents
-            .into_iter()
-            .enumerate()
-            .for_each(|(index, (doc, embeddings))| {
-                self.embeddings
-                    .insert(format!("doc{}", index + current_index), (doc, embeddings));
-            });
+        self.add_documents_with_ids(documents.into_iter().enumerate().map(
+            |(index, (doc, embeddings))| (format!("doc{}", index + current_index), doc, embeddings),
+        ))
     }
 
-    /// Add documents and their corresponding embeddings to the store with ids.
+    /// Add documents and their corresponding embeddings to the store with ids
+    /// (see [InMemoryVectorStore::add_documents]).
     pub fn add_documents_with_ids(
         &mut self,
         documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>)>,
-    ) {
-        documents.into_iter().for_each(|(id, doc, embeddings)| {
-            self.embeddings.insert(id.to_string(), (doc, embeddings));
-        });
+    ) -> Result<(), VectorStoreError> {
+        let documents = documents.into_iter().collect::<Vec<_>>();
+        self.check_ndims(
+            None,
+            documents
+                .iter()
+                .flat_map(|(_, _, embeddings)| embeddings.iter()),
+        )?;
+
+        for (id, doc, embeddings) in documents {
+            let id = id.to_string();
+            self.embeddings.insert(id.clone(), (doc, embeddings));
+            self.index_document(&id);
+        }
+        Ok(())
     }
 
     /// Add documents and their corresponding embeddings to the store.
-    /// Document ids are generated using the provided function.
+    /// Document ids are generated using the provided function
+    /// (see [InMemoryVectorStore::add_documents]).
     pub fn add_documents_with_id_f(
         &mut self,
         documents: Vec<(D, OneOrMany<Embedding>)>,
         f: fn(&D) -> String,
-    ) {
-        for (doc, embeddings) in documents {
-            let id = f(&doc);
-            self.embeddings.insert(id, (doc, embeddings));
-        }
+    ) -> Result<(), VectorStoreError> {
+        self.add_documents_with_ids(
+            documents
+                .into_iter()
+                .map(|(doc, embeddings)| (f(&doc), doc, embeddings)),
+        )
+    }
+
+    /// Get the top n documents based on the distance to the given (already embedded) query.
+    /// The result is a list of tuples of the form (score, id, document)
+    ///
+    /// Fails with [VectorStoreError::DimensionMismatch] if the query embedding does not have the
+    /// number of dimensions of the embeddings of the store.
+    pub fn top_n_by_embedding<T: for<'a> Deserialize<'a>>(
+        &self,
+        query_embedding: &Embedding,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.check_ndims(None, [query_embedding])?;
+        self.vector_search(query_embedding, n)
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(RankingItem(similarity, id, doc, _))| {
+                Ok((
+                    similarity.0,
+                    id.clone(),
+                    serde_json::from_str(&serde_json::to_string(doc)?)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Get the top n documents matching the keywords of the given query, ranked by BM25 score.
+    /// Documents which contain none of the keywords are not returned.
+    /// The result is a list of tuples of the form (score, id, document)
+    pub fn top_n_by_keywords<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, None)
+            .into_iter()
+            .take(n)
+            .map(|(score, id, doc)| {
+                Ok((
+                    score,
+                    id.clone(),
+                    serde_json::from_str(&serde_json::to_string(doc)?)?,
+                ))
+            })
+            .collect()
     }
 
     /// Get the document by its id and deserialize it into the given type.
@@ -157,13 +600,210 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
     }
 }
 
-/// RankingItem(distance, document_id, serializable document, embeddings document)
+/// Synchronous modifications of the store, shared by the [VectorStore] implementations of the
+/// store, of [InMemoryVectorIndex] and of [SharedVectorIndex] (whose lock must not be held
+/// across an `await`).
+impl<D: Serialize + Eq> InMemoryVectorStore<D> {
+    fn insert(
+        &mut self,
+        documents: Vec<(String, D, OneOrMany<Embedding>)>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        if !upsert {
+            if let Some((id, _, _)) = documents
+                .iter()
+                .find(|(id, _, _)| self.embeddings.contains_key(id))
+            {
+                return Err(VectorStoreError::DuplicateIdError(id.clone()));
+            }
+        }
+
+        self.add_documents_with_ids(documents)
+    }
+
+    fn update(
+        &mut self,
+        (id, doc, embeddings): (String, D, OneOrMany<Embedding>),
+    ) -> Result<(), VectorStoreError> {
+        self.check_ndims(None, embeddings.iter())?;
+        match self.embeddings.get_mut(&id) {
+            Some(entry) => {
+                *entry = (doc, embeddings);
+                self.index_document(&id);
+                Ok(())
+            }
+            None => Err(VectorStoreError::MissingIdError(id)),
+        }
+    }
+
+    fn delete(&mut self, ids: &[String]) {
+        for id in ids {
+            self.embeddings.remove(id);
+            self.index_document(id);
+        }
+    }
+}
+
+impl<D: Serialize + Eq + Send + Sync> VectorStore for InMemoryVectorStore<D> {
+    type Document = (String, D, OneOrMany<Embedding>);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        self.insert(documents, upsert)
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        self.update(document)
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        self.delete(ids);
+        Ok(())
+    }
+}
+
+/// Minimum number of documents from which the `rayon` feature scores the documents in parallel
+#[cfg(feature = "rayon")]
+const PARALLEL_SEARCH_THRESHOLD: usize = 1024;
+
+/// Similarity to the query (see [DistanceMetric::similarity]) of the most similar embedding of each document, with the index of the
+/// embedding. With the `rayon` feature, large numbers of documents are scored in parallel.
+///
+/// If `field_weights` is set, the similarity of a document is instead the weighted sum of the
+/// similarities of the most similar embedding of each weighted field (divided by the sum of the
+/// weights), and the index is the one of its most similar weighted embedding. Fields of the
+/// document without embeddings score 0, and embeddings of other fields are ignored.
+fn best_similarities<D>(
+    documents: &[(&String, &OneOrMany<Embedding>)],
+    query: &Embedding,
+    metric: DistanceMetric,
+    field_weights: Option<&FieldWeights<D>>,
+) -> Vec<Option<(OrderedFloat<f64>, usize)>> {
+    let best = |(id, embeddings): &(&String, &OneOrMany<Embedding>)| {
+        let similarities = embeddings.iter().enumerate();
+
+        let Some(field_weights) = field_weights else {
+            return similarities
+                .map(|(index, embedding)| {
+                    (OrderedFloat(metric.similarity(embedding, query)), index)
+                })
+                .max_by(|a, b| a.0.cmp(&b.0));
+        };
+
+        // Best similarity of each field
+        let mut fields = HashMap::<&String, (f64, f64)>::new();
+        let mut best = None::<(OrderedFloat<f64>, usize)>;
+        for (index, embedding) in similarities {
+            let Some((field, weight)) = field_weights.weight(id, index) else {
+                continue;
+            };
+            let similarity = metric.similarity(embedding, query);
+            fields
+                .entry(field)
+                .and_modify(|(best, _)| *best = best.max(similarity))
+                .or_insert((similarity, weight));
+            if best.is_none_or(|(best, _)| similarity > best.0) {
+                best = Some((OrderedFloat(similarity), index));
+            }
+        }
+
+        let score = fields
+            .values()
+            .map(|(similarity, weight)| similarity * weight)
+            .sum::<f64>()
+            / field_weights.weights.values().sum::<f64>();
+        best.map(|(_, index)| (OrderedFloat(score), index))
+    };
+
+    #[cfg(feature = "rayon")]
+    if documents.len() >= PARALLEL_SEARCH_THRESHOLD {
+        use rayon::prelude::*;
+        return documents.par_iter().map(best).collect();
+    }
+
+    documents.iter().map(best).collect()
+}
+
+/// BM25 term frequency saturation parameter
+const BM25_K1: f64 = 1.2;
+/// BM25 document length normalization parameter
+const BM25_B: f64 = 0.75;
+
+/// Split a text into lowercase alphanumeric keywords.
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn matches_filter<D: Serialize>(doc: &D, filter: Option<&Filter>) -> bool {
+    filter.is_none_or(|filter| serde_json::to_value(doc).is_ok_and(|doc| filter.matches(&doc)))
+}
+
+/// Sort a ranking by decreasing score. Ties are broken by document id.
+fn sort_ranking<D>(ranking: &mut [(f64, &String, D)]) {
+    ranking.sort_by(|(score_a, id_a, _), (score_b, id_b, _)| {
+        OrderedFloat(*score_b)
+            .cmp(&OrderedFloat(*score_a))
+            .then_with(|| id_a.cmp(id_b))
+    });
+}
+
+/// Strategy used to fuse the vector ranking and the keyword ranking of a hybrid search
+/// (see [InMemoryVectorIndex::hybrid]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FusionStrategy {
+    /// Reciprocal rank fusion: a document is scored `1 / (k + rank)` in each ranking it
+    /// appears in (ranks start at 1), and the scores are summed. Only the ranks matter, so the
+    /// scores of both rankings need not be comparable. `k = 60` is the usual choice.
+    ReciprocalRank { k: f64 },
+    /// Weighted sum of the cosine similarity (weighted by `vector_weight`) and of the BM25 score
+    /// normalized by the best BM25 score (weighted by `1 - vector_weight`).
+    Weighted { vector_weight: f64 },
+}
+
+impl Default for FusionStrategy {
+    fn default() -> Self {
+        Self::ReciprocalRank { k: 60.0 }
+    }
+}
+
+/// Policy used when merging [InMemoryVectorStore]s that contain documents with the same id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Keep the document already in the store
+    KeepFirst,
+    /// Replace the document already in the store
+    Overwrite,
+    /// Fail the merge
+    Error,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("Document id {0} exists in both stores")]
+    IdCollision(String),
+
+    #[error("Embedding dimensions mismatch: expected {expected}, found {found}")]
+    DimensionMismatch { expected: usize, found: usize },
+
+    #[error("The full-precision vectors of the merged store were discarded by its quantization")]
+    DiscardedVectors,
+}
+
+/// RankingItem(similarity, document_id, serializable document, best matching embedding), where
+/// the similarity is higher for more similar documents (see [DistanceMetric::similarity])
 #[derive(Eq, PartialEq)]
-struct RankingItem<'a, D: Serialize>(OrderedFloat<f64>, &'a String, &'a D, &'a String);
+struct RankingItem<'a, D: Serialize>(OrderedFloat<f64>, &'a String, &'a D, &'a Embedding);
 
 impl<D: Serialize + Eq> Ord for RankingItem<'_, D> {
+    /// Items are ranked by distance. Ties are broken by document id (lexicographically smaller
+    /// ids rank higher) so that the ranking is deterministic.
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
+        self.0.cmp(&other.0).then_with(|| other.1.cmp(self.1))
     }
 }
 
@@ -180,97 +820,1465 @@ impl<D: Serialize> InMemoryVectorStore<D> {
         InMemoryVectorIndex::new(model, self)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&String, &(D, OneOrMany<Embedding>))> {
-        self.embeddings.iter()
+    /// Set the metric by which vector searches compare the query to the embeddings (cosine
+    /// similarity by default), e.g.: to get the same rankings and scores as the production
+    /// index the store stands in for. Scores are similarities (higher is more similar), the
+    /// Euclidean distance `d` being scored `1 / (1 + d)`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let index = InMemoryVectorStore::from_documents(embeddings)
+    ///     .metric(DistanceMetric::Euclidean)
+    ///     .index(model);
+    /// ```
+    pub fn metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        // The graph links the nearest neighbors under the metric
+        #[cfg(feature = "hnsw")]
+        if let Some(hnsw) = self.hnsw.take() {
+            self = self.hnsw(hnsw.config());
+        }
+        self
     }
 
-    pub fn len(&self) -> usize {
-        self.embeddings.len()
-    }
+    /// Quantize the vectors of the store, and of the documents added to it later, so vector
+    /// searches rank compact approximations of the vectors (see [quantization](super::quantization)).
+    ///
+    /// If rescoring is disabled, the full-precision vectors are discarded: the store then uses
+    /// 8x (int8) to 64x (binary) less memory for its vectors, but cannot be merged into another
+    /// store nor saved, and quantizing it again has no effect.
+    ///
+    /// Quantized vectors are compared by their approximate similarity under the
+    /// [metric](InMemoryVectorStore::metric) of the store; rescoring gives the exact scores.
+    ///
+    /// # Example
+    /// ```rust
+    /// let index = InMemoryVectorStore::from_documents(embeddings)
+    ///     .quantized(QuantizationConfig::binary().rescore(20))
+    ///     .index(model);
+    /// ```
+    pub fn quantized(mut self, config: QuantizationConfig) -> Self {
+        if self.discarded_vectors() {
+            return self;
+        }
 
-    pub fn is_empty(&self) -> bool {
-        self.embeddings.is_empty()
+        self.quantized = Some(QuantizedVectors {
+            config,
+            ndims: self.ndims(),
+            vectors: HashMap::new(),
+        });
+        let ids = self.embeddings.keys().cloned().collect::<Vec<_>>();
+        for id in ids {
+            self.quantize_document(&id);
+        }
+        self
     }
-}
 
-pub struct InMemoryVectorIndex<M: EmbeddingModel, D: Serialize> {
-    model: M,
-    pub store: InMemoryVectorStore<D>,
-}
+    /// Index the vectors of the store, and of the documents added to it later, in an HNSW graph
+    /// (see [hnsw](super::hnsw)), to search large stores (e.g.: more than 100k documents) in
+    /// sublinear time. Results are approximate.
+    ///
+    /// The graph keeps a copy of the vectors and is used for searches without filter; filtered
+    /// searches still compare the query to all the documents matching the filter. Removed and
+    /// replaced documents stay in the graph (but are never returned) until more than a quarter
+    /// of its vectors are removed, when it is rebuilt without them.
+    ///
+    /// Requires the `hnsw` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// let index = InMemoryVectorStore::from_documents(embeddings)
+    ///     .hnsw(HnswConfig::default().ef_search(128))
+    ///     .index(model);
+    /// ```
+    #[cfg(feature = "hnsw")]
+    pub fn hnsw(mut self, config: HnswConfig) -> Self {
+        if self.discarded_vectors() {
+            return self;
+        }
 
-impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
-    pub fn new(model: M, store: InMemoryVectorStore<D>) -> Self {
-        Self { model, store }
+        let mut hnsw = Hnsw::new(config, self.metric);
+        // Inserted in a stable order, so the graph is deterministic
+        let mut ids = self.embeddings.keys().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            hnsw.insert(id, self.embeddings[id].1.iter());
+        }
+        self.hnsw = Some(hnsw);
+        self
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&String, &(D, OneOrMany<Embedding>))> {
-        self.store.iter()
+    /// Record the model, dimensions and schema version of the embeddings of the store (see
+    /// [schema](super::schema)), so it refuses to be searched with another model. Indexes record
+    /// the schema of their model in stores which have none, and snapshots keep it.
+    pub fn embedding_schema(mut self, schema: EmbeddingSchema) -> Self {
+        self.embedding_schema = Some(schema);
+        self
     }
 
-    pub fn len(&self) -> usize {
-        self.store.len()
+    /// The schema of the embeddings of the store, if known.
+    pub fn get_embedding_schema(&self) -> Option<&EmbeddingSchema> {
+        self.embedding_schema.as_ref()
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.store.is_empty()
+    /// Whether the full-precision vectors of the store were discarded by its quantization.
+    fn discarded_vectors(&self) -> bool {
+        self.quantized
+            .as_ref()
+            .is_some_and(|quantized| quantized.config.rescore.is_none())
     }
-}
 
-impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
-    for InMemoryVectorIndex<M, D>
-{
-    async fn top_n<T: for<'a> Deserialize<'a>>(
-        &self,
-        query: &str,
-        n: usize,
-    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = &self.model.embed_text(query).await?;
+    /// Update the HNSW graph, the quantized vectors and the fields of the embeddings of the
+    /// document `id` (if enabled) after it was inserted, replaced or removed.
+    fn index_document(&mut self, id: &str) {
+        if let Some(field_weights) = &mut self.field_weights {
+            field_weights.index_document(id, self.embeddings.get(id));
+        }
 
-        let docs = self.store.vector_search(prompt_embedding, n);
+        #[cfg(feature = "hnsw")]
+        if let Some(hnsw) = &mut self.hnsw {
+            match self.embeddings.get(id) {
+                Some((_, embeddings)) => hnsw.insert(id, embeddings.iter()),
+                None => hnsw.remove(id),
+            }
+        }
 
-        // Return n best
-        docs.into_iter()
-            .map(|Reverse(RankingItem(distance, id, doc, _))| {
-                Ok((
-                    distance.0,
-                    id.clone(),
-                    serde_json::from_str(
-                        &serde_json::to_string(doc).map_err(VectorStoreError::JsonError)?,
-                    )
-                    .map_err(VectorStoreError::JsonError)?,
-                ))
-            })
-            .collect::<Result<Vec<_>, _>>()
+        self.quantize_document(id);
     }
 
-    async fn top_n_ids(
-        &self,
-        query: &str,
-        n: usize,
-    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = &self.model.embed_text(query).await?;
+    /// Update the quantized vectors of the document `id` (if enabled) after it was inserted,
+    /// replaced or removed.
+    fn quantize_document(&mut self, id: &str) {
+        let Some(quantized) = &mut self.quantized else {
+            return;
+        };
+        let Some((_, embeddings)) = self.embeddings.get_mut(id) else {
+            quantized.vectors.remove(id);
+            return;
+        };
 
-        let docs = self.store.vector_search(prompt_embedding, n);
+        let vectors = embeddings
+            .iter()
+            .map(|embedding| QuantizedVector::new(quantized.config.quantization, &embedding.vec))
+            .collect();
+        quantized.vectors.insert(id.to_string(), vectors);
+        quantized.ndims = quantized.ndims.or(Some(embeddings.first().vec.len()));
 
-        // Return n best
-        docs.into_iter()
-            .map(|Reverse(RankingItem(distance, id, _, _))| Ok((distance.0, id.clone())))
-            .collect::<Result<Vec<_>, _>>()
+        if quantized.config.rescore.is_none() {
+            embeddings
+                .iter_mut()
+                .for_each(|embedding| embedding.vec = Vec::new());
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Merge the documents of `other` into this store.
+    /// Documents whose id already exists in this store are handled according to `policy`.
+    ///
+    /// The embeddings of both stores must have the same number of dimensions. On error,
+    /// this store is left unchanged.
+    pub fn merge(&mut self, other: Self, policy: CollisionPolicy) -> Result<(), MergeError> {
+        if other.discarded_vectors() {
+            return Err(MergeError::DiscardedVectors);
+        }
+
+        let mut ndims = self.ndims();
+        for (_, (_, embeddings)) in other.iter() {
+            for embedding in embeddings.iter() {
+                match ndims {
+                    Some(expected) if expected != embedding.vec.len() => {
+                        return Err(MergeError::DimensionMismatch {
+                            expected,
+                            found: embedding.vec.len(),
+                        })
+                    }
+                    Some(_) => (),
+                    None => ndims = Some(embedding.vec.len()),
+                }
+            }
+        }
+
+        if let CollisionPolicy::Error = policy {
+            if let Some(id) = other
+                .embeddings
+                .keys()
+                .find(|id| self.embeddings.contains_key(*id))
+            {
+                return Err(MergeError::IdCollision(id.clone()));
+            }
+        }
+
+        let mut merged = vec![];
+        for (id, value) in other.embeddings {
+            match (policy, self.embeddings.entry(id.clone())) {
+                (CollisionPolicy::Overwrite, hash_map::Entry::Occupied(mut entry)) => {
+                    entry.insert(value);
+                    merged.push(id);
+                }
+                (_, hash_map::Entry::Occupied(_)) => (),
+                (_, hash_map::Entry::Vacant(entry)) => {
+                    entry.insert(value);
+                    merged.push(id);
+                }
+            }
+        }
+        for id in merged {
+            self.index_document(&id);
+        }
+
+        Ok(())
+    }
+
+    /// Create a new [InMemoryVectorStore] by merging `stores`, in order (see [InMemoryVectorStore::merge]).
+    /// Useful to combine stores built in parallel.
+    pub fn from_stores(
+        stores: impl IntoIterator<Item = Self>,
+        policy: CollisionPolicy,
+    ) -> Result<Self, MergeError> {
+        stores
+            .into_iter()
+            .try_fold(Self::default(), |mut merged, store| {
+                merged.merge(store, policy)?;
+                Ok(merged)
+            })
+    }
+
+    /// Number of dimensions of the embeddings of the store, if the store is not empty.
+    fn ndims(&self) -> Option<usize> {
+        if let Some(ndims) = self
+            .quantized
+            .as_ref()
+            .and_then(|quantized| quantized.ndims)
+        {
+            return Some(ndims);
+        }
+
+        self.embeddings
+            .values()
+            .flat_map(|(_, embeddings)| embeddings.iter())
+            .map(|embedding| embedding.vec.len())
+            .next()
+    }
+
+    /// Check that `embeddings` all have `expected` dimensions or, if `expected` is `None`, the
+    /// number of dimensions of the embeddings of the store (of the first of `embeddings` if the
+    /// store is empty).
+    fn check_ndims<'a>(
+        &self,
+        expected: Option<usize>,
+        embeddings: impl IntoIterator<Item = &'a Embedding>,
+    ) -> Result<(), VectorStoreError> {
+        let mut ndims = expected.or_else(|| self.ndims());
+        for embedding in embeddings {
+            match ndims {
+                Some(expected) if expected != embedding.vec.len() => {
+                    return Err(VectorStoreError::DimensionMismatch {
+                        expected,
+                        found: embedding.vec.len(),
+                    })
+                }
+                Some(_) => (),
+                None => ndims = Some(embedding.vec.len()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterate over all documents in the store, yielding their id, the document and its embeddings.
+    /// Documents are borrowed and are yielded in arbitrary order.
+    pub fn iter(&self) -> hash_map::Iter<'_, String, (D, OneOrMany<Embedding>)> {
+        self.embeddings.iter()
+    }
+
+    /// Get the statistics of the store. Counts are exact. The size is an estimate of the memory
+    /// used by the ids and the embeddings, quantized or not (the documents themselves are not
+    /// included).
+    pub fn stats(&self) -> VectorStoreStats {
+        let (vector_count, size_bytes) = self.embeddings.iter().fold(
+            (0, 0),
+            |(vector_count, size_bytes), (id, (_, embeddings))| {
+                (
+                    vector_count + embeddings.len(),
+                    size_bytes
+                        + id.len()
+                        + embeddings
+                            .iter()
+                            .map(|embedding| {
+                                embedding.document.len()
+                                    + embedding.vec.len() * std::mem::size_of::<f64>()
+                            })
+                            .sum::<usize>(),
+                )
+            },
+        );
+
+        let quantized_size_bytes = self.quantized.as_ref().map_or(0, |quantized| {
+            quantized
+                .vectors
+                .values()
+                .flatten()
+                .map(QuantizedVector::size_bytes)
+                .sum::<usize>()
+        });
+        let hnsw_size_bytes = self.hnsw_size_bytes();
+        let index_type = match (
+            hnsw_size_bytes,
+            self.quantized
+                .as_ref()
+                .map(|quantized| quantized.config.quantization),
+        ) {
+            (Some(_), _) => "HNSW",
+            (None, None) => "FLAT",
+            (None, Some(Quantization::Int8)) => "FLAT_INT8",
+            (None, Some(Quantization::Binary)) => "FLAT_BINARY",
+        };
+
+        VectorStoreStats {
+            document_count: Some(self.embeddings.len()),
+            vector_count: Some(vector_count),
+            dimensions: self.ndims(),
+            index_type: Some(index_type.to_string()),
+            size_bytes: Some(
+                (size_bytes + quantized_size_bytes + hnsw_size_bytes.unwrap_or(0)) as u64,
+            ),
+        }
+    }
+
+    /// Approximate memory used by the HNSW graph, if enabled.
+    #[cfg(feature = "hnsw")]
+    fn hnsw_size_bytes(&self) -> Option<usize> {
+        self.hnsw.as_ref().map(Hnsw::size_bytes)
+    }
+
+    #[cfg(not(feature = "hnsw"))]
+    fn hnsw_size_bytes(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.embeddings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.embeddings.is_empty()
+    }
+}
+
+/// A document of a snapshot of an [InMemoryVectorStore], written as one JSON line.
+#[derive(Serialize)]
+struct SnapshotRecord<'a, D> {
+    id: &'a str,
+    document: &'a D,
+    embeddings: &'a OneOrMany<Embedding>,
+}
+
+/// First line of the snapshots of the stores with an embedding schema.
+#[derive(Serialize, Deserialize)]
+struct SnapshotHeader {
+    embedding_schema: EmbeddingSchema,
+}
+
+/// Key of the [SnapshotHeader], which snapshot records do not have
+const SNAPSHOT_HEADER_PREFIX: &str = "{\"embedding_schema\":";
+
+/// A document read from a snapshot of an [InMemoryVectorStore].
+#[derive(Deserialize)]
+struct OwnedSnapshotRecord<D> {
+    id: String,
+    document: D,
+    embeddings: OneOrMany<Embedding>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JsonError: line {line}: {source}")]
+    JsonError {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    #[error("Document id {0} appears twice in the snapshot")]
+    DuplicateId(String),
+
+    #[error("Embedding dimensions mismatch: expected {expected}, found {found}")]
+    DimensionMismatch { expected: usize, found: usize },
+
+    #[error("The full-precision vectors of the store were discarded by its quantization")]
+    DiscardedVectors,
+}
+
+impl<D: Serialize> InMemoryVectorStore<D> {
+    /// Save a snapshot of the store (documents, ids and embeddings) to the file at `path`, so the
+    /// store can be loaded with [InMemoryVectorStore::load] without re-embedding the documents
+    /// (e.g.: an index computed offline and shipped with an application).
+    ///
+    /// The snapshot is in the JSON Lines format (see [InMemoryVectorStore::save_to_writer]).
+    ///
+    /// # Example
+    /// ```rust
+    /// let store = InMemoryVectorStore::from_documents(embeddings);
+    /// store.save("index.jsonl")?;
+    ///
+    /// // At startup
+    /// let store = InMemoryVectorStore::<WordDefinition>::load("index.jsonl")?;
+    /// let index = store.index(model);
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save_to_writer(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write a snapshot of the store to `writer`, one JSON object per line with the fields `id`,
+    /// `document` and `embeddings`. Documents are sorted by id, so the snapshots of identical
+    /// stores are identical. The embedding schema of the store, if any, is written on the first
+    /// line (as `{"embedding_schema": ...}`).
+    ///
+    /// Quantized vectors and HNSW graphs are not saved: they can be rebuilt on the loaded store. Stores whose
+    /// full-precision vectors were discarded cannot be saved.
+    pub fn save_to_writer(&self, mut writer: impl Write) -> Result<(), SnapshotError> {
+        if self.discarded_vectors() {
+            return Err(SnapshotError::DiscardedVectors);
+        }
+
+        let mut first_line = 1;
+        if let Some(embedding_schema) = &self.embedding_schema {
+            serde_json::to_writer(
+                &mut writer,
+                &SnapshotHeader {
+                    embedding_schema: embedding_schema.clone(),
+                },
+            )
+            .map_err(|source| SnapshotError::JsonError { line: 1, source })?;
+            writer.write_all(b"\n")?;
+            first_line += 1;
+        }
+
+        let mut documents = self.embeddings.iter().collect::<Vec<_>>();
+        documents.sort_by_key(|(id, _)| *id);
+
+        for (line, (id, (document, embeddings))) in documents.into_iter().enumerate() {
+            serde_json::to_writer(
+                &mut writer,
+                &SnapshotRecord {
+                    id,
+                    document,
+                    embeddings,
+                },
+            )
+            .map_err(|source| SnapshotError::JsonError {
+                line: line + first_line,
+                source,
+            })?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: Serialize + DeserializeOwned> InMemoryVectorStore<D> {
+    /// Load a store from a snapshot saved with [InMemoryVectorStore::save].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::load_from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a store from a snapshot written with [InMemoryVectorStore::save_to_writer]
+    /// (e.g.: a snapshot embedded in the binary with `include_bytes!`). Empty lines are ignored.
+    ///
+    /// Fails if an id appears twice or if the embeddings do not all have the same number of
+    /// dimensions.
+    pub fn load_from_reader(reader: impl BufRead) -> Result<Self, SnapshotError> {
+        let mut embeddings = HashMap::new();
+        let mut ndims = None;
+        let mut embedding_schema = None;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if i == 0 && line.starts_with(SNAPSHOT_HEADER_PREFIX) {
+                let header: SnapshotHeader = serde_json::from_str(&line)
+                    .map_err(|source| SnapshotError::JsonError { line: 1, source })?;
+                embedding_schema = Some(header.embedding_schema);
+                continue;
+            }
+
+            let record: OwnedSnapshotRecord<D> =
+                serde_json::from_str(&line).map_err(|source| SnapshotError::JsonError {
+                    line: i + 1,
+                    source,
+                })?;
+
+            for embedding in record.embeddings.iter() {
+                match ndims {
+                    Some(expected) if expected != embedding.vec.len() => {
+                        return Err(SnapshotError::DimensionMismatch {
+                            expected,
+                            found: embedding.vec.len(),
+                        })
+                    }
+                    Some(_) => (),
+                    None => ndims = Some(embedding.vec.len()),
+                }
+            }
+
+            match embeddings.entry(record.id) {
+                hash_map::Entry::Occupied(entry) => {
+                    return Err(SnapshotError::DuplicateId(entry.key().clone()))
+                }
+                hash_map::Entry::Vacant(entry) => {
+                    entry.insert((record.document, record.embeddings));
+                }
+            }
+        }
+
+        Ok(Self {
+            embeddings,
+            quantized: None,
+            #[cfg(feature = "hnsw")]
+            hnsw: None,
+            metric: DistanceMetric::default(),
+            embedding_schema,
+            field_weights: None,
+        })
+    }
+}
+
+impl<D: Serialize + Embed> InMemoryVectorStore<D> {
+    /// Score documents embedding several fields (see [TextEmbedder::embed_field](crate::embeddings::TextEmbedder::embed_field))
+    /// by the weighted sum of the similarities of their fields to the query, e.g.: to rank
+    /// matches on the title of structured records above matches on their body. By default, the
+    /// score of a document is the similarity of its most similar embedding.
+    ///
+    /// Only the embeddings of the weighted fields are compared to the query. Searches with field
+    /// weights compare the query to all the embeddings, without the HNSW graph nor the
+    /// quantized vectors of the store. Fails if a weight is not a positive number; no weights
+    /// disable the weighting.
+    ///
+    /// # Example
+    /// ```rust
+    /// #[derive(Embed, Serialize, Eq, PartialEq)]
+    /// struct Article {
+    ///     #[embed]
+    ///     title: String,
+    ///     #[embed]
+    ///     body: String,
+    /// }
+    ///
+    /// let index = InMemoryVectorStore::from_documents(embeddings)
+    ///     .field_weights([("title", 2.0), ("body", 1.0)])?
+    ///     .index(model);
+    /// ```
+    pub fn field_weights(
+        mut self,
+        weights: impl IntoIterator<Item = (impl ToString, f64)>,
+    ) -> Result<Self, VectorStoreError> {
+        let weights = weights
+            .into_iter()
+            .map(|(field, weight)| (field.to_string(), weight))
+            .collect::<HashMap<_, _>>();
+        if let Some((field, weight)) = weights
+            .iter()
+            .find(|(_, weight)| !weight.is_finite() || **weight <= 0.0)
+        {
+            return Err(VectorStoreError::InvalidFieldWeight {
+                field: field.clone(),
+                weight: *weight,
+            });
+        }
+
+        self.field_weights = None;
+        if !weights.is_empty() {
+            let mut field_weights = FieldWeights {
+                weights,
+                field_texts: |document: &D| to_field_texts(document),
+                fields: HashMap::new(),
+            };
+            for (id, entry) in &self.embeddings {
+                field_weights.index_document(id, Some(entry));
+            }
+            self.field_weights = Some(field_weights);
+        }
+        Ok(self)
+    }
+}
+
+impl<D: Serialize + Eq + Embed + Clone + Send> InMemoryVectorStore<D> {
+    /// Migrate the store to a new embedding model: a copy of the store (with the same metric,
+    /// field weights, quantization and HNSW graph, if any) whose documents are re-embedded with `model`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let model = openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+    /// let store = InMemoryVectorStore::<WordDefinition>::load("index.jsonl")?
+    ///     .reembed(&model)
+    ///     .await?;
+    /// store.save("index.jsonl")?;
+    /// ```
+    pub async fn reembed<M: EmbeddingModel>(&self, model: &M) -> Result<Self, VectorStoreError> {
+        let documents = embed_documents(
+            model,
+            self.embeddings
+                .iter()
+                .map(|(id, (document, _))| (id.clone(), document.clone())),
+        )
+        .await?;
+
+        let mut store = Self::from_documents_with_ids(documents)
+            .metric(self.metric)
+            .embedding_schema(EmbeddingSchema::of(model));
+        if let Some(field_weights) = &self.field_weights {
+            store = store.field_weights(field_weights.weights.clone())?;
+        }
+        if let Some(quantized) = &self.quantized {
+            store = store.quantized(quantized.config);
+        }
+        #[cfg(feature = "hnsw")]
+        if let Some(hnsw) = &self.hnsw {
+            store = store.hnsw(hnsw.config());
+        }
+        Ok(store)
+    }
+}
+
+impl<'a, D: Serialize> IntoIterator for &'a InMemoryVectorStore<D> {
+    type Item = (&'a String, &'a (D, OneOrMany<Embedding>));
+    type IntoIter = hash_map::Iter<'a, String, (D, OneOrMany<Embedding>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct InMemoryVectorIndex<M: EmbeddingModel, D: Serialize> {
+    model: M,
+    pub store: InMemoryVectorStore<D>,
+    /// Fusion strategy of hybrid search, if enabled
+    fusion: Option<FusionStrategy>,
+}
+
+impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
+    /// Create an index searching `store` with `model`. If the store has no
+    /// [embedding schema](InMemoryVectorStore::embedding_schema), the schema of `model` is
+    /// recorded; otherwise searches and insertions fail if `model` does not match it.
+    pub fn new(model: M, mut store: InMemoryVectorStore<D>) -> Self {
+        if store.embedding_schema.is_none() {
+            store.embedding_schema = Some(EmbeddingSchema::of(&model));
+        }
+        Self {
+            model,
+            store,
+            fusion: None,
+        }
+    }
+
+    /// Enable hybrid search: the queries of the index rank documents by both vector similarity
+    /// and keyword (BM25) relevance, and the two rankings are fused with `fusion`.
+    /// The scores returned by the index are then the fused scores.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::vector_store::in_memory_store::{FusionStrategy, InMemoryVectorStore};
+    ///
+    /// let index = InMemoryVectorStore::from_documents(documents)
+    ///     .index(model)
+    ///     .hybrid(FusionStrategy::ReciprocalRank { k: 60.0 });
+    ///
+    /// let results = index.top_n::<String>("What does error E1234 mean?", 3).await?;
+    /// ```
+    pub fn hybrid(mut self, fusion: FusionStrategy) -> Self {
+        self.fusion = Some(fusion);
+        self
+    }
+
+    /// Number of dimensions of the embeddings of the model, if known (some models report 0
+    /// dimensions when unknown).
+    fn model_ndims(&self) -> Option<usize> {
+        Some(self.model.ndims()).filter(|ndims| *ndims > 0)
+    }
+
+    /// Check that the model of the index matches the schema of the embeddings of the store.
+    fn check_schema(&self) -> Result<(), VectorStoreError> {
+        match &self.store.embedding_schema {
+            Some(schema) => schema.check(&self.model),
+            None => Ok(()),
+        }
+    }
+
+    /// Split the index into its embedding model and its store.
+    pub fn into_parts(self) -> (M, InMemoryVectorStore<D>) {
+        (self.model, self.store)
+    }
+
+    /// Wrap the index in a [SharedVectorIndex], so that it can be searched and modified
+    /// concurrently by several tasks.
+    pub fn shared(self) -> SharedVectorIndex<M, D> {
+        SharedVectorIndex::new(self)
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, String, (D, OneOrMany<Embedding>)> {
+        self.store.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+}
+
+/// Searches of the index which do not require the documents to be `Send` and `Sync` (e.g.: on
+/// WASM, where documents may hold JavaScript values), unlike [VectorStoreIndex].
+impl<M: EmbeddingModel, D: Serialize + Eq> InMemoryVectorIndex<M, D> {
+    /// Same as [VectorStoreIndex::top_n], but also returns the embedding of the query and,
+    /// for each result, the embedding of the document that best matched the query.
+    /// Useful to debug the ranking of the results.
+    /// The result is a tuple of the form (query embedding, [(score, id, document, document embedding)])
+    pub async fn top_n_with_embeddings<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<(Embedding, Vec<(f64, String, T, Embedding)>), VectorStoreError> {
+        self.check_schema()?;
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.store.check_ndims(None, [&prompt_embedding])?;
+
+        let docs = self.store.vector_search(&prompt_embedding, n);
+
+        let results = docs
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(RankingItem(similarity, id, doc, embedding))| {
+                Ok((
+                    similarity.0,
+                    id.clone(),
+                    serde_json::from_str(
+                        &serde_json::to_string(doc).map_err(VectorStoreError::JsonError)?,
+                    )
+                    .map_err(VectorStoreError::JsonError)?,
+                    embedding.clone(),
+                ))
+            })
+            .collect::<Result<Vec<_>, VectorStoreError>>()?;
+
+        Ok((prompt_embedding, results))
+    }
+
+    /// Ranking of the top `n` documents matching `filter` (if any), from most to least relevant,
+    /// given the embedding of the query. Uses hybrid search if enabled, vector search otherwise.
+    /// Fails if the embedding of the query does not have the number of dimensions of the
+    /// embeddings of the store.
+    fn ranking(
+        &self,
+        prompt_embedding: &Embedding,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, &String, &D)>, VectorStoreError> {
+        self.check_schema()?;
+        self.store.check_ndims(None, [prompt_embedding])?;
+
+        Ok(match self.fusion {
+            Some(fusion) => self
+                .store
+                .hybrid_search(prompt_embedding, query, n, filter, fusion),
+            None => self
+                .store
+                .filtered_vector_search(prompt_embedding, n, filter)
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse(RankingItem(similarity, id, doc, _))| (similarity.0, id, doc))
+                .collect(),
+        })
+    }
+
+    /// Same as [VectorStoreIndex::top_n_filtered] (or [VectorStoreIndex::top_n] without
+    /// `filter`), without requiring the documents to be `Send` and `Sync`.
+    pub async fn search<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.search_by_embedding(&prompt_embedding, query, n, filter)
+    }
+
+    /// Same as [InMemoryVectorIndex::search], given the embedding of `query`.
+    pub fn search_by_embedding<T: for<'a> Deserialize<'a>>(
+        &self,
+        prompt_embedding: &Embedding,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        // Return n best, from most to least relevant
+        self.ranking(prompt_embedding, query, n, filter)?
+            .into_iter()
+            .map(|(score, id, doc)| {
+                let doc = serde_json::to_value(doc)
+                    .and_then(serde_json::from_value)
+                    .map_err(|source| VectorStoreError::DeserializationError {
+                        id: id.clone(),
+                        source,
+                    })?;
+                Ok((score, id.clone(), doc))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Same as [VectorStoreIndex::top_n_ids_filtered] (or [VectorStoreIndex::top_n_ids] without
+    /// `filter`), without requiring the documents to be `Send` and `Sync`.
+    pub async fn search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.search_ids_by_embedding(&prompt_embedding, query, n, filter)
+    }
+
+    /// Same as [InMemoryVectorIndex::search_ids], given the embedding of `query`.
+    pub fn search_ids_by_embedding(
+        &self,
+        prompt_embedding: &Embedding,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        // Return n best, from most to least relevant
+        Ok(self
+            .ranking(prompt_embedding, query, n, filter)?
+            .into_iter()
+            .map(|(score, id, _)| (score, id.clone()))
+            .collect())
+    }
+}
+
+/// Modifying the index modifies its store, so the index does not have to be rebuilt.
+/// The embeddings of the documents must have the number of dimensions of the embedding model.
+impl<M: EmbeddingModel + Sync, D: Serialize + Eq + Send + Sync> VectorStore
+    for InMemoryVectorIndex<M, D>
+{
+    type Document = (String, D, OneOrMany<Embedding>);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        self.insert(documents, upsert)
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        self.update(document)
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        self.store.delete(ids);
+        Ok(())
+    }
+}
+
+impl<M: EmbeddingModel, D: Serialize + Eq> InMemoryVectorIndex<M, D> {
+    fn insert(
+        &mut self,
+        documents: Vec<(String, D, OneOrMany<Embedding>)>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        self.check_schema()?;
+        self.store.check_ndims(
+            self.model_ndims(),
+            documents
+                .iter()
+                .flat_map(|(_, _, embeddings)| embeddings.iter()),
+        )?;
+        self.store.insert(documents, upsert)
+    }
+
+    fn update(
+        &mut self,
+        document: (String, D, OneOrMany<Embedding>),
+    ) -> Result<(), VectorStoreError> {
+        self.check_schema()?;
+        self.store
+            .check_ndims(self.model_ndims(), document.2.iter())?;
+        self.store.update(document)
+    }
+}
+
+/// In-memory collections of documents (see [VectorStoreCollections]): each collection is an
+/// [InMemoryVectorIndex] of its own, keyed by name, and all collections use the same model.
+/// # Example
+/// ```rust
+/// use rig::vector_store::{
+///     in_memory_store::InMemoryCollections, VectorStore, VectorStoreCollections, VectorStoreIndex,
+/// };
+///
+/// let mut collections = InMemoryCollections::new(model);
+///
+/// // One index per tenant
+/// collections
+///     .collection("acme")
+///     .await?
+///     .insert_documents(acme_documents, false)
+///     .await?;
+///
+/// let results = collections
+///     .collection("acme")
+///     .await?
+///     .top_n::<String>("What is our refund policy?", 3)
+///     .await?;
+/// ```
+pub struct InMemoryCollections<M: EmbeddingModel, D: Serialize> {
+    model: M,
+    collections: HashMap<String, InMemoryVectorIndex<M, D>>,
+}
+
+impl<M: EmbeddingModel, D: Serialize> InMemoryCollections<M, D> {
+    /// Create an empty set of collections, using `model` to embed the queries of all collections.
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            collections: HashMap::new(),
+        }
+    }
+
+    /// Get the collection with the given name, if it exists.
+    pub fn get(&self, name: &str) -> Option<&InMemoryVectorIndex<M, D>> {
+        self.collections.get(name)
+    }
+}
+
+impl<M: EmbeddingModel + Sync, D: Serialize + Eq + Send + Sync> VectorStoreCollections
+    for InMemoryCollections<M, D>
+{
+    type Collection = InMemoryVectorIndex<M, D>;
+
+    async fn collection(&mut self, name: &str) -> Result<&mut Self::Collection, VectorStoreError> {
+        Ok(self.collections.entry(name.to_string()).or_insert_with(|| {
+            InMemoryVectorStore::from_documents(vec![]).index(self.model.clone())
+        }))
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, VectorStoreError> {
+        let mut names = self.collections.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn delete_collection(&mut self, name: &str) -> Result<(), VectorStoreError> {
+        self.collections.remove(name);
+        Ok(())
+    }
+}
+
+impl<'a, M: EmbeddingModel, D: Serialize> IntoIterator for &'a InMemoryVectorIndex<M, D> {
+    type Item = (&'a String, &'a (D, OneOrMany<Embedding>));
+    type IntoIter = hash_map::Iter<'a, String, (D, OneOrMany<Embedding>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
+    for InMemoryVectorIndex<M, D>
+{
+    async fn top_n<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, None).await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
+
+    async fn top_n_filtered<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    async fn top_n_ids_filtered(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        let stats = self.store.stats();
+
+        Ok(VectorStoreStats {
+            dimensions: stats.dimensions.or(Some(self.model.ndims())),
+            ..stats
+        })
+    }
+}
+
+/// Handle on an [InMemoryVectorIndex] shared between tasks, e.g.: a background task ingesting
+/// documents while request handlers search the index. Clones of the handle share the same
+/// index, so each task can own a clone (modifying the index through [VectorStore] only
+/// requires a mutable reference to the task's own clone).
+///
+/// The index is behind a read-write lock: searches run concurrently with each other, and
+/// modifications wait for the searches in progress. Queries are embedded before the lock is
+/// acquired, so the lock is never held while waiting for the embedding model.
+///
+/// # Example
+/// ```rust
+/// use rig::vector_store::{in_memory_store::InMemoryVectorStore, VectorStore, VectorStoreIndex};
+///
+/// let index = InMemoryVectorStore::default().index(model).shared();
+///
+/// let mut ingester = index.clone();
+/// tokio::spawn(async move {
+///     while let Some(documents) = receiver.recv().await {
+///         ingester.insert_documents(documents, true).await?;
+///     }
+/// });
+///
+/// let results = index.top_n::<String>("What is our refund policy?", 3).await?;
+/// ```
+pub struct SharedVectorIndex<M: EmbeddingModel, D: Serialize> {
+    model: M,
+    index: Arc<RwLock<InMemoryVectorIndex<M, D>>>,
+}
+
+// Not derived, as cloning the handle does not clone the documents
+impl<M: EmbeddingModel, D: Serialize> Clone for SharedVectorIndex<M, D> {
+    fn clone(&self) -> Self {
+        Self {
+            model: self.model.clone(),
+            index: self.index.clone(),
+        }
+    }
+}
+
+impl<M: EmbeddingModel, D: Serialize> SharedVectorIndex<M, D> {
+    pub fn new(index: InMemoryVectorIndex<M, D>) -> Self {
+        Self {
+            model: index.model.clone(),
+            index: Arc::new(RwLock::new(index)),
+        }
+    }
+
+    /// Lock the index for reading (e.g.: to iterate over its documents or save a snapshot of
+    /// its store). The lock should not be held across an `await`.
+    pub fn read(&self) -> RwLockReadGuard<'_, InMemoryVectorIndex<M, D>> {
+        self.index
+            .read()
+            .expect("Shared vector index lock should not be poisoned")
+    }
+
+    /// Lock the index for writing (e.g.: to add documents synchronously or merge another
+    /// store into it). The lock should not be held across an `await`.
+    pub fn write(&self) -> RwLockWriteGuard<'_, InMemoryVectorIndex<M, D>> {
+        self.index
+            .write()
+            .expect("Shared vector index lock should not be poisoned")
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+}
+
+impl<M: EmbeddingModel + Sync, D: Serialize + Eq + Send + Sync> VectorStore
+    for SharedVectorIndex<M, D>
+{
+    type Document = (String, D, OneOrMany<Embedding>);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        self.write().insert(documents, upsert)
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        self.write().update(document)
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        self.write().store.delete(ids);
+        Ok(())
+    }
+}
+
+impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
+    for SharedVectorIndex<M, D>
+{
+    async fn top_n<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.read()
+            .search_by_embedding(&prompt_embedding, query, n, None)
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.read()
+            .search_ids_by_embedding(&prompt_embedding, query, n, None)
+    }
+
+    async fn top_n_filtered<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.read()
+            .search_by_embedding(&prompt_embedding, query, n, Some(&filter))
+    }
+
+    async fn top_n_ids_filtered(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+        self.read()
+            .search_ids_by_embedding(&prompt_embedding, query, n, Some(&filter))
+    }
+
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        let stats = self.read().store.stats();
+
+        Ok(VectorStoreStats {
+            dimensions: stats.dimensions.or(Some(self.model.ndims())),
+            ..stats
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
     use std::cmp::Reverse;
 
-    use crate::{embeddings::embedding::Embedding, OneOrMany};
+    use crate::{
+        embeddings::{
+            distance::DistanceMetric, embedding::Embedding, Embed, EmbedError, EmbeddingError,
+            EmbeddingModel, TextEmbedder,
+        },
+        OneOrMany,
+    };
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::{
+        embedding_fields, CollisionPolicy, FusionStrategy, InMemoryCollections,
+        InMemoryVectorStore, MergeError, RankingItem, SnapshotError,
+    };
+    use crate::vector_store::{
+        filter::{Filter, FilteredIndex},
+        quantization::QuantizationConfig,
+        VectorStore, VectorStoreCollections, VectorStoreError, VectorStoreIndex,
+    };
+
+    #[derive(Clone)]
+    struct Model;
+
+    impl EmbeddingModel for Model {
+        const MAX_DOCUMENTS: usize = 5;
+
+        fn ndims(&self) -> usize {
+            3
+        }
+
+        async fn embed_texts(
+            &self,
+            documents: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            Ok(documents
+                .into_iter()
+                .map(|doc| Embedding {
+                    document: doc,
+                    vec: vec![0.0, 0.1, 0.6],
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_auto_ids() {
+        let mut vector_store = InMemoryVectorStore::from_documents(vec![
+            (
+                "glarb-garb",
+                OneOrMany::one(Embedding {
+                    document: "glarb-garb".to_string(),
+                    vec: vec![0.1, 0.1, 0.5],
+                }),
+            ),
+            (
+                "marble-marble",
+                OneOrMany::one(Embedding {
+                    document: "marble-marble".to_string(),
+                    vec: vec![0.7, -0.3, 0.0],
+                }),
+            ),
+            (
+                "flumb-flumb",
+                OneOrMany::one(Embedding {
+                    document: "flumb-flumb".to_string(),
+                    vec: vec![0.3, 0.7, 0.1],
+                }),
+            ),
+        ]);
+
+        vector_store
+            .add_documents(vec![
+                (
+                    "brotato",
+                    OneOrMany::one(Embedding {
+                        document: "brotato".to_string(),
+                        vec: vec![0.3, 0.7, 0.1],
+                    }),
+                ),
+                (
+                    "ping-pong",
+                    OneOrMany::one(Embedding {
+                        document: "ping-pong".to_string(),
+                        vec: vec![0.7, -0.3, 0.0],
+                    }),
+                ),
+            ])
+            .unwrap();
+
+        let mut store = vector_store.embeddings.into_iter().collect::<Vec<_>>();
+        store.sort_by_key(|(id, _)| id.clone());
+
+        assert_eq!(
+            store,
+            vec![
+                (
+                    "doc0".to_string(),
+                    (
+                        "glarb-garb",
+                        OneOrMany::one(Embedding {
+                            document: "glarb-garb".to_string(),
+                            vec: vec![0.1, 0.1, 0.5],
+                        })
+                    )
+                ),
+                (
+                    "doc1".to_string(),
+                    (
+                        "marble-marble",
+                        OneOrMany::one(Embedding {
+                            document: "marble-marble".to_string(),
+                            vec: vec![0.7, -0.3, 0.0],
+                        })
+                    )
+                ),
+                (
+                    "doc2".to_string(),
+                    (
+                        "flumb-flumb",
+                        OneOrMany::one(Embedding {
+                            document: "flumb-flumb".to_string(),
+                            vec: vec![0.3, 0.7, 0.1],
+                        })
+                    )
+                ),
+                (
+                    "doc3".to_string(),
+                    (
+                        "brotato",
+                        OneOrMany::one(Embedding {
+                            document: "brotato".to_string(),
+                            vec: vec![0.3, 0.7, 0.1],
+                        })
+                    )
+                ),
+                (
+                    "doc4".to_string(),
+                    (
+                        "ping-pong",
+                        OneOrMany::one(Embedding {
+                            document: "ping-pong".to_string(),
+                            vec: vec![0.7, -0.3, 0.0],
+                        })
+                    )
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_embedding() {
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
+            (
+                "doc1",
+                "glarb-garb",
+                OneOrMany::one(Embedding {
+                    document: "glarb-garb".to_string(),
+                    vec: vec![0.1, 0.1, 0.5],
+                }),
+            ),
+            (
+                "doc2",
+                "marble-marble",
+                OneOrMany::one(Embedding {
+                    document: "marble-marble".to_string(),
+                    vec: vec![0.7, -0.3, 0.0],
+                }),
+            ),
+            (
+                "doc3",
+                "flumb-flumb",
+                OneOrMany::one(Embedding {
+                    document: "flumb-flumb".to_string(),
+                    vec: vec![0.3, 0.7, 0.1],
+                }),
+            ),
+        ]);
+
+        let ranking = vector_store.vector_search(
+            &Embedding {
+                document: "glarby-glarble".to_string(),
+                vec: vec![0.0, 0.1, 0.6],
+            },
+            1,
+        );
+
+        assert_eq!(
+            ranking
+                .into_iter()
+                .map(|Reverse(RankingItem(distance, id, doc, _))| {
+                    (
+                        distance.0,
+                        id.clone(),
+                        serde_json::from_str(&serde_json::to_string(doc).unwrap()).unwrap(),
+                    )
+                })
+                .collect::<Vec<(_, _, String)>>(),
+            vec![(
+                0.9807965956109156,
+                "doc1".to_string(),
+                "glarb-garb".to_string()
+            )]
+        )
+    }
+
+    #[test]
+    fn test_multiple_embeddings() {
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
+            (
+                "doc1",
+                "glarb-garb",
+                OneOrMany::many(vec![
+                    Embedding {
+                        document: "glarb-garb".to_string(),
+                        vec: vec![0.1, 0.1, 0.5],
+                    },
+                    Embedding {
+                        document: "don't-choose-me".to_string(),
+                        vec: vec![-0.5, 0.9, 0.1],
+                    },
+                ])
+                .unwrap(),
+            ),
+            (
+                "doc2",
+                "marble-marble",
+                OneOrMany::many(vec![
+                    Embedding {
+                        document: "marble-marble".to_string(),
+                        vec: vec![0.7, -0.3, 0.0],
+                    },
+                    Embedding {
+                        document: "sandwich".to_string(),
+                        vec: vec![0.5, 0.5, -0.7],
+                    },
+                ])
+                .unwrap(),
+            ),
+            (
+                "doc3",
+                "flumb-flumb",
+                OneOrMany::many(vec![
+                    Embedding {
+                        document: "flumb-flumb".to_string(),
+                        vec: vec![0.3, 0.7, 0.1],
+                    },
+                    Embedding {
+                        document: "banana".to_string(),
+                        vec: vec![0.1, -0.5, -0.5],
+                    },
+                ])
+                .unwrap(),
+            ),
+        ]);
+
+        let ranking = vector_store.vector_search(
+            &Embedding {
+                document: "glarby-glarble".to_string(),
+                vec: vec![0.0, 0.1, 0.6],
+            },
+            1,
+        );
 
-    use super::{InMemoryVectorStore, RankingItem};
+        assert_eq!(
+            ranking
+                .into_iter()
+                .map(|Reverse(RankingItem(distance, id, doc, _))| {
+                    (
+                        distance.0,
+                        id.clone(),
+                        serde_json::from_str(&serde_json::to_string(doc).unwrap()).unwrap(),
+                    )
+                })
+                .collect::<Vec<(_, _, String)>>(),
+            vec![(
+                0.9807965956109156,
+                "doc1".to_string(),
+                "glarb-garb".to_string()
+            )]
+        )
+    }
 
     #[test]
-    fn test_auto_ids() {
-        let mut vector_store = InMemoryVectorStore::from_documents(vec![
+    fn test_iter() {
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
             (
+                "doc1",
                 "glarb-garb",
                 OneOrMany::one(Embedding {
                     document: "glarb-garb".to_string(),
@@ -278,228 +2286,963 @@ mod tests {
                 }),
             ),
             (
+                "doc2",
                 "marble-marble",
                 OneOrMany::one(Embedding {
                     document: "marble-marble".to_string(),
                     vec: vec![0.7, -0.3, 0.0],
                 }),
             ),
-            (
-                "flumb-flumb",
-                OneOrMany::one(Embedding {
-                    document: "flumb-flumb".to_string(),
-                    vec: vec![0.3, 0.7, 0.1],
-                }),
-            ),
         ]);
 
-        vector_store.add_documents(vec![
+        let mut docs = (&vector_store)
+            .into_iter()
+            .map(|(id, (doc, _))| (id.as_str(), *doc))
+            .collect::<Vec<_>>();
+        docs.sort();
+
+        assert_eq!(
+            docs,
+            vec![("doc1", "glarb-garb"), ("doc2", "marble-marble")]
+        );
+
+        let mut count = 0;
+        for (_, (_, embeddings)) in &vector_store {
+            assert_eq!(embeddings.len(), 1);
+            count += 1;
+        }
+        assert_eq!(count, vector_store.len());
+    }
+
+    #[test]
+    fn test_tie_break_by_id() {
+        let embedding = OneOrMany::one(Embedding {
+            document: "same".to_string(),
+            vec: vec![0.1, 0.1, 0.5],
+        });
+
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
+            ("doc3", "c", embedding.clone()),
+            ("doc1", "a", embedding.clone()),
+            ("doc4", "d", embedding.clone()),
+            ("doc2", "b", embedding),
+        ]);
+
+        let ranking = vector_store.vector_search(
+            &Embedding {
+                document: "query".to_string(),
+                vec: vec![0.1, 0.1, 0.5],
+            },
+            3,
+        );
+
+        assert_eq!(
+            ranking
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse(RankingItem(_, id, _, _))| id.clone())
+                .collect::<Vec<_>>(),
+            vec!["doc1".to_string(), "doc2".to_string(), "doc3".to_string()]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_top_n_with_embeddings() {
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
             (
-                "brotato",
-                OneOrMany::one(Embedding {
-                    document: "brotato".to_string(),
-                    vec: vec![0.3, 0.7, 0.1],
-                }),
+                "doc1",
+                "glarb-garb",
+                OneOrMany::many(vec![
+                    Embedding {
+                        document: "glarb-garb".to_string(),
+                        vec: vec![0.1, 0.1, 0.5],
+                    },
+                    Embedding {
+                        document: "don't-choose-me".to_string(),
+                        vec: vec![-0.5, 0.9, 0.1],
+                    },
+                ])
+                .unwrap(),
             ),
             (
-                "ping-pong",
+                "doc2",
+                "marble-marble",
                 OneOrMany::one(Embedding {
-                    document: "ping-pong".to_string(),
+                    document: "marble-marble".to_string(),
                     vec: vec![0.7, -0.3, 0.0],
                 }),
             ),
         ]);
 
-        let mut store = vector_store.embeddings.into_iter().collect::<Vec<_>>();
-        store.sort_by_key(|(id, _)| id.clone());
+        let (query, results) = vector_store
+            .index(Model)
+            .top_n_with_embeddings::<String>("glarby-glarble", 1)
+            .await
+            .unwrap();
+
+        assert_eq!(query.vec, vec![0.0, 0.1, 0.6]);
+        assert_eq!(results.len(), 1);
+
+        let (score, id, doc, embedding) = &results[0];
+        assert_eq!(*score, 0.9807965956109156);
+        assert_eq!(id, "doc1");
+        assert_eq!(doc, "glarb-garb");
+        assert_eq!(embedding.document, "glarb-garb");
+        assert_eq!(embedding.vec, vec![0.1, 0.1, 0.5]);
+    }
+
+    /// Document which is neither `Send` nor `Sync` (e.g.: holding a JavaScript value on WASM)
+    #[derive(serde::Serialize, PartialEq, Eq)]
+    struct LocalDocument {
+        text: String,
+        #[serde(skip)]
+        local: std::marker::PhantomData<std::rc::Rc<()>>,
+    }
+
+    #[tokio::test]
+    async fn test_search_non_send_documents() {
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![(
+            "doc1",
+            LocalDocument {
+                text: "glarb-garb".to_string(),
+                local: std::marker::PhantomData,
+            },
+            OneOrMany::one(Embedding {
+                document: "glarb-garb".to_string(),
+                vec: vec![0.1, 0.1, 0.5],
+            }),
+        )])
+        .index(Model);
+
+        let results = index
+            .search::<serde_json::Value>("glarby-glarble", 1, None)
+            .await
+            .unwrap();
+        assert_eq!(results[0].1, "doc1");
+        assert_eq!(results[0].2, json!({"text": "glarb-garb"}));
+
+        let results = index.search_ids("glarby-glarble", 1, None).await.unwrap();
+        assert_eq!(results[0].1, "doc1");
+    }
 
+    #[tokio::test]
+    async fn test_insert_update_delete() {
+        let embedding = |document: &str| {
+            OneOrMany::one(Embedding {
+                document: document.to_string(),
+                vec: vec![0.1, 0.1, 0.5],
+            })
+        };
+        let document = |id: &str, text: &str| (id.to_string(), text.to_string(), embedding(text));
+
+        let mut index = InMemoryVectorStore::default().index(Model);
+        index
+            .insert_documents(vec![document("doc1", "a"), document("doc2", "b")], false)
+            .await
+            .unwrap();
+
+        let error = index
+            .insert_documents(vec![document("doc3", "c"), document("doc1", "A")], false)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, VectorStoreError::DuplicateIdError(id) if id == "doc1"));
+        assert_eq!(index.len(), 2);
+
+        index
+            .insert_documents(vec![document("doc3", "c"), document("doc1", "A")], true)
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 3);
+
+        index.update_document(document("doc2", "B")).await.unwrap();
+        let error = index
+            .update_document(document("doc4", "d"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, VectorStoreError::MissingIdError(id) if id == "doc4"));
+
+        index
+            .delete_documents(&["doc3".to_string(), "doc4".to_string()])
+            .await
+            .unwrap();
+
+        let mut results = index.top_n::<String>("query", 5).await.unwrap();
+        results.sort_by(|a, b| a.1.cmp(&b.1));
         assert_eq!(
-            store,
+            results
+                .into_iter()
+                .map(|(_, id, doc)| (id, doc))
+                .collect::<Vec<_>>(),
             vec![
-                (
-                    "doc0".to_string(),
-                    (
-                        "glarb-garb",
-                        OneOrMany::one(Embedding {
-                            document: "glarb-garb".to_string(),
-                            vec: vec![0.1, 0.1, 0.5],
-                        })
-                    )
-                ),
-                (
-                    "doc1".to_string(),
-                    (
-                        "marble-marble",
-                        OneOrMany::one(Embedding {
-                            document: "marble-marble".to_string(),
-                            vec: vec![0.7, -0.3, 0.0],
-                        })
-                    )
-                ),
-                (
-                    "doc2".to_string(),
-                    (
-                        "flumb-flumb",
-                        OneOrMany::one(Embedding {
-                            document: "flumb-flumb".to_string(),
-                            vec: vec![0.3, 0.7, 0.1],
-                        })
-                    )
-                ),
-                (
-                    "doc3".to_string(),
-                    (
-                        "brotato",
-                        OneOrMany::one(Embedding {
-                            document: "brotato".to_string(),
-                            vec: vec![0.3, 0.7, 0.1],
-                        })
-                    )
-                ),
-                (
-                    "doc4".to_string(),
-                    (
-                        "ping-pong",
-                        OneOrMany::one(Embedding {
-                            document: "ping-pong".to_string(),
-                            vec: vec![0.7, -0.3, 0.0],
-                        })
-                    )
-                )
+                ("doc1".to_string(), "A".to_string()),
+                ("doc2".to_string(), "B".to_string())
             ]
         );
     }
 
-    #[test]
-    fn test_single_embedding() {
-        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shared_index() {
+        let document = |id: String| {
+            (
+                id.clone(),
+                id.clone(),
+                OneOrMany::one(Embedding {
+                    document: id,
+                    vec: vec![0.1, 0.1, 0.5],
+                }),
+            )
+        };
+
+        let index = InMemoryVectorStore::default().index(Model).shared();
+
+        let mut ingester = index.clone();
+        let ingestion = tokio::spawn(async move {
+            for i in 0..100 {
+                ingester
+                    .insert_documents(vec![document(format!("doc{i}"))], false)
+                    .await
+                    .unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let searches = (0..4)
+            .map(|_| {
+                let index = index.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        let results = index.top_n_ids("query", 5).await.unwrap();
+                        assert!(results.len() <= 5);
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        ingestion.await.unwrap();
+        for search in searches {
+            search.await.unwrap();
+        }
+
+        assert_eq!(index.len(), 100);
+        assert_eq!(index.top_n::<String>("query", 5).await.unwrap().len(), 5);
+        assert_eq!(index.stats().await.unwrap().document_count, Some(100));
+
+        let error = index
+            .clone()
+            .insert_documents(vec![document("doc0".to_string())], false)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, VectorStoreError::DuplicateIdError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_collections() {
+        let document = |id: &str, text: &str| {
+            (
+                id.to_string(),
+                text.to_string(),
+                OneOrMany::one(Embedding {
+                    document: text.to_string(),
+                    vec: vec![0.1, 0.1, 0.5],
+                }),
+            )
+        };
+
+        let mut collections = InMemoryCollections::new(Model);
+        collections
+            .collection("acme")
+            .await
+            .unwrap()
+            .insert_documents(vec![document("doc1", "acme")], false)
+            .await
+            .unwrap();
+        collections
+            .collection("globex")
+            .await
+            .unwrap()
+            .insert_documents(vec![document("doc1", "globex")], false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            collections.collection_names().await.unwrap(),
+            vec!["acme", "globex"]
+        );
+
+        let results = collections
+            .get("acme")
+            .unwrap()
+            .top_n::<String>("query", 5)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].2, "acme");
+
+        collections.delete_collection("acme").await.unwrap();
+        assert!(collections.get("acme").is_none());
+        assert_eq!(collections.collection("acme").await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_typed() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Definition {
+            word: String,
+            definition: String,
+        }
+
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![(
+            "doc1",
+            json!({"word": "glarb-glarb", "definition": "An ancient tool"}),
+            OneOrMany::one(Embedding {
+                document: "An ancient tool".to_string(),
+                vec: vec![0.1, 0.1, 0.5],
+            }),
+        )])
+        .index(Model);
+
+        let results = index.top_n::<Definition>("tool", 1).await.unwrap();
+        assert_eq!(
+            results[0].2,
+            Definition {
+                word: "glarb-glarb".to_string(),
+                definition: "An ancient tool".to_string(),
+            }
+        );
+
+        let error = index.top_n::<Vec<String>>("tool", 1).await.unwrap_err();
+        assert!(
+            matches!(error, VectorStoreError::DeserializationError { ref id, .. } if id == "doc1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_top_n_filtered() {
+        let embedding = |vec: Vec<f64>| {
+            OneOrMany::one(Embedding {
+                document: "".to_string(),
+                vec,
+            })
+        };
+
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![
             (
                 "doc1",
-                "glarb-garb",
-                OneOrMany::one(Embedding {
-                    document: "glarb-garb".to_string(),
-                    vec: vec![0.1, 0.1, 0.5],
-                }),
+                json!({"tenant": "acme", "text": "closest"}),
+                embedding(vec![0.0, 0.1, 0.6]),
             ),
             (
                 "doc2",
-                "marble-marble",
-                OneOrMany::one(Embedding {
-                    document: "marble-marble".to_string(),
-                    vec: vec![0.7, -0.3, 0.0],
-                }),
+                json!({"tenant": "globex", "text": "close"}),
+                embedding(vec![0.1, 0.1, 0.5]),
             ),
             (
                 "doc3",
-                "flumb-flumb",
+                json!({"tenant": "globex", "text": "far"}),
+                embedding(vec![0.7, -0.3, 0.0]),
+            ),
+        ])
+        .index(Model);
+
+        let results = index
+            .top_n_ids_filtered("query", 5, Filter::eq("tenant", "globex"))
+            .await
+            .unwrap();
+        assert_eq!(
+            results.into_iter().map(|(_, id)| id).collect::<Vec<_>>(),
+            vec!["doc2", "doc3"]
+        );
+
+        let results = index
+            .top_n_filtered::<serde_json::Value>("query", 1, Filter::eq("tenant", "globex"))
+            .await
+            .unwrap();
+        assert_eq!(results[0].2["text"], "close");
+
+        // All the searches of a filtered index are scoped by its filter
+        let index = FilteredIndex::new(index, Filter::eq("tenant", "acme"));
+        let results = index.top_n_ids("query", 5).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "doc1");
+    }
+
+    fn store(docs: &[(&str, &'static str, Vec<f64>)]) -> InMemoryVectorStore<&'static str> {
+        InMemoryVectorStore::from_documents_with_ids(docs.iter().map(|(id, doc, vec)| {
+            (
+                id.to_string(),
+                *doc,
                 OneOrMany::one(Embedding {
-                    document: "flumb-flumb".to_string(),
-                    vec: vec![0.3, 0.7, 0.1],
+                    document: doc.to_string(),
+                    vec: vec.clone(),
                 }),
-            ),
-        ]);
+            )
+        }))
+    }
 
-        let ranking = vector_store.vector_search(
-            &Embedding {
-                document: "glarby-glarble".to_string(),
-                vec: vec![0.0, 0.1, 0.6],
-            },
-            1,
+    #[test]
+    fn test_merge() {
+        let stores = || {
+            vec![
+                store(&[("a", "a1", vec![0.1, 0.2]), ("b", "b1", vec![0.3, 0.4])]),
+                store(&[("b", "b2", vec![0.5, 0.6]), ("c", "c2", vec![0.7, 0.8])]),
+            ]
+        };
+
+        let merged =
+            InMemoryVectorStore::from_stores(stores(), CollisionPolicy::KeepFirst).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.get_document::<String>("b").unwrap().unwrap(), "b1");
+
+        let merged =
+            InMemoryVectorStore::from_stores(stores(), CollisionPolicy::Overwrite).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.get_document::<String>("b").unwrap().unwrap(), "b2");
+
+        let mut first = stores().remove(0);
+        let result = first.merge(stores().remove(1), CollisionPolicy::Error);
+        assert!(matches!(result, Err(MergeError::IdCollision(id)) if id == "b"));
+        assert_eq!(first.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_dimension_mismatch() {
+        let mut first = store(&[("a", "a", vec![0.1, 0.2])]);
+        let result = first.merge(
+            store(&[("b", "b", vec![0.1, 0.2, 0.3])]),
+            CollisionPolicy::KeepFirst,
         );
 
+        assert!(matches!(
+            result,
+            Err(MergeError::DimensionMismatch {
+                expected: 2,
+                found: 3
+            })
+        ));
+        assert_eq!(first.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dimension_mismatch() {
+        let embedding = |vec: Vec<f64>| {
+            OneOrMany::one(Embedding {
+                document: "doc".to_string(),
+                vec,
+            })
+        };
+
+        let mut store = InMemoryVectorStore::default();
+        let result = store.add_documents_with_ids([
+            ("a", "a".to_string(), embedding(vec![0.1, 0.2])),
+            ("b", "b".to_string(), embedding(vec![0.1, 0.2, 0.3])),
+        ]);
+        assert!(matches!(
+            result,
+            Err(VectorStoreError::DimensionMismatch {
+                expected: 2,
+                found: 3
+            })
+        ));
+        assert!(store.is_empty());
+
+        store
+            .add_documents_with_ids([("a", "a".to_string(), embedding(vec![0.1, 0.2]))])
+            .unwrap();
+        assert!(store
+            .add_documents([("b".to_string(), embedding(vec![0.1]))])
+            .is_err());
+
+        // The model embeds queries in 3 dimensions
+        let index = store.index(Model);
+        assert!(matches!(
+            index.top_n::<String>("query", 1).await,
+            Err(VectorStoreError::DimensionMismatch {
+                expected: 2,
+                found: 3
+            })
+        ));
+
+        let mut index = InMemoryVectorStore::default().index(Model);
+        assert!(matches!(
+            index
+                .insert_documents(
+                    vec![("a".to_string(), "a".to_string(), embedding(vec![0.1, 0.2]))],
+                    false
+                )
+                .await,
+            Err(VectorStoreError::DimensionMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_quantized_search() {
+        // Documents along a circle, at increasing angles from the query
+        let docs = (0..20)
+            .map(|i| {
+                let angle = i as f64 * 0.15;
+                (
+                    format!("doc{i:02}"),
+                    vec![angle.cos(), angle.sin(), 0.1, -0.1],
+                )
+            })
+            .collect::<Vec<_>>();
+        let store = || {
+            InMemoryVectorStore::from_documents_with_ids(docs.iter().map(|(id, vec)| {
+                (
+                    id.clone(),
+                    id.clone(),
+                    OneOrMany::one(Embedding {
+                        document: id.clone(),
+                        vec: vec.clone(),
+                    }),
+                )
+            }))
+        };
+        let query = Embedding {
+            document: "query".to_string(),
+            vec: vec![1.0, 0.0, 0.1, -0.1],
+        };
+        let top_ids = |store: &InMemoryVectorStore<String>| {
+            store
+                .top_n_by_embedding::<String>(&query, 3)
+                .unwrap()
+                .into_iter()
+                .map(|(_, id, _)| id)
+                .collect::<Vec<_>>()
+        };
+
+        let exact = top_ids(&store());
+        assert_eq!(exact, vec!["doc00", "doc01", "doc02"]);
         assert_eq!(
-            ranking
+            top_ids(&store().quantized(QuantizationConfig::int8())),
+            exact
+        );
+        assert_eq!(
+            top_ids(&store().quantized(QuantizationConfig::binary().rescore(10))),
+            exact
+        );
+
+        // Without rescoring, the full-precision vectors are discarded
+        let mut quantized = store().quantized(QuantizationConfig::int8().without_rescoring());
+        assert_eq!(top_ids(&quantized), exact);
+        assert!(quantized.embeddings["doc00"].1.first().vec.is_empty());
+        assert_eq!(quantized.stats().dimensions, Some(4));
+        assert_eq!(quantized.stats().index_type.as_deref(), Some("FLAT_INT8"));
+        assert!(quantized.stats().size_bytes < store().stats().size_bytes);
+
+        // Documents added later are quantized too
+        quantized
+            .add_documents_with_ids([("best", "best".to_string(), OneOrMany::one(query.clone()))])
+            .unwrap();
+        assert_eq!(top_ids(&quantized)[0], "best");
+
+        assert!(matches!(
+            store().merge(quantized.clone(), CollisionPolicy::Error),
+            Err(MergeError::DiscardedVectors)
+        ));
+        assert!(matches!(
+            quantized.save_to_writer(vec![]),
+            Err(SnapshotError::DiscardedVectors)
+        ));
+    }
+
+    #[cfg(feature = "hnsw")]
+    #[tokio::test]
+    async fn test_hnsw_search() {
+        use crate::vector_store::hnsw::HnswConfig;
+
+        // Documents along a circle, at increasing angles from the query
+        let store = || {
+            InMemoryVectorStore::from_documents_with_ids((0..200).map(|i| {
+                let angle = i as f64 * 0.03;
+                let id = format!("doc{i:03}");
+                (
+                    id.clone(),
+                    id.clone(),
+                    OneOrMany::one(Embedding {
+                        document: id,
+                        vec: vec![angle.cos(), angle.sin(), 0.1, -0.1],
+                    }),
+                )
+            }))
+        };
+        let query = Embedding {
+            document: "query".to_string(),
+            vec: vec![1.0, 0.0, 0.1, -0.1],
+        };
+        let top_ids = |store: &InMemoryVectorStore<String>| {
+            store
+                .top_n_by_embedding::<String>(&query, 5)
+                .unwrap()
                 .into_iter()
-                .map(|Reverse(RankingItem(distance, id, doc, _))| {
-                    (
-                        distance.0,
-                        id.clone(),
-                        serde_json::from_str(&serde_json::to_string(doc).unwrap()).unwrap(),
-                    )
-                })
-                .collect::<Vec<(_, _, String)>>(),
-            vec![(
-                0.9807965956109156,
-                "doc1".to_string(),
-                "glarb-garb".to_string()
-            )]
-        )
+                .map(|(_, id, _)| id)
+                .collect::<Vec<_>>()
+        };
+
+        let mut hnsw = store().hnsw(HnswConfig::default());
+        assert_eq!(top_ids(&hnsw), top_ids(&store()));
+        assert_eq!(hnsw.stats().index_type.as_deref(), Some("HNSW"));
+        assert!(hnsw.stats().size_bytes > store().stats().size_bytes);
+
+        // The graph follows the documents added and removed later
+        hnsw.add_documents_with_ids([("best", "best".to_string(), OneOrMany::one(query.clone()))])
+            .unwrap();
+        assert_eq!(top_ids(&hnsw)[0], "best");
+        hnsw.delete_documents(&["best".to_string()]).await.unwrap();
+        assert_eq!(top_ids(&hnsw), top_ids(&store()));
     }
 
     #[test]
-    fn test_multiple_embeddings() {
-        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
+    fn test_distance_metrics() {
+        let store = InMemoryVectorStore::from_documents_with_ids(
+            [("near", vec![1.0, 0.1]), ("long", vec![3.0, 1.5])].map(|(id, vec)| {
+                (
+                    id,
+                    id,
+                    OneOrMany::one(Embedding {
+                        document: id.to_string(),
+                        vec,
+                    }),
+                )
+            }),
+        );
+        let query = Embedding {
+            document: "query".to_string(),
+            vec: vec![1.0, 0.0],
+        };
+        let ranking = |metric| {
+            store
+                .clone()
+                .metric(metric)
+                .top_n_by_embedding::<String>(&query, 2)
+                .unwrap()
+                .into_iter()
+                .map(|(score, id, _)| (id, (score * 1000.0).round() / 1000.0))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ranking(DistanceMetric::Cosine),
+            vec![("near".to_string(), 0.995), ("long".to_string(), 0.894)]
+        );
+        assert_eq!(
+            ranking(DistanceMetric::DotProduct),
+            vec![("long".to_string(), 3.0), ("near".to_string(), 1.0)]
+        );
+        assert_eq!(
+            ranking(DistanceMetric::NormalizedCosine),
+            ranking(DistanceMetric::DotProduct)
+        );
+        // Euclidean distances of 0.1 and 2.5, scored as similarities
+        assert_eq!(
+            ranking(DistanceMetric::Euclidean),
+            vec![("near".to_string(), 0.909), ("long".to_string(), 0.286)]
+        );
+
+        // Quantized vectors are ranked under the metric of the store too
+        let quantized = store
+            .clone()
+            .metric(DistanceMetric::DotProduct)
+            .quantized(QuantizationConfig::int8().without_rescoring())
+            .top_n_by_embedding::<String>(&query, 2)
+            .unwrap();
+        assert_eq!(quantized[0].1, "long");
+    }
+
+    #[test]
+    fn test_field_weights() {
+        #[derive(Serialize, Clone, PartialEq, Eq)]
+        struct Article {
+            title: String,
+            body: String,
+        }
+
+        impl Embed for Article {
+            fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+                embedder.embed_field("title", &self.title)?;
+                embedder.embed_field("body", &self.body)
+            }
+        }
+
+        let article = |title: &str, body: &str, title_vec: Vec<f64>, body_vec: Vec<f64>| {
             (
-                "doc1",
-                "glarb-garb",
+                title.to_string(),
+                Article {
+                    title: title.to_string(),
+                    body: body.to_string(),
+                },
                 OneOrMany::many(vec![
                     Embedding {
-                        document: "glarb-garb".to_string(),
-                        vec: vec![0.1, 0.1, 0.5],
+                        document: title.to_string(),
+                        vec: title_vec,
                     },
                     Embedding {
-                        document: "don't-choose-me".to_string(),
-                        vec: vec![-0.5, 0.9, 0.1],
+                        document: body.to_string(),
+                        vec: body_vec,
                     },
                 ])
                 .unwrap(),
-            ),
-            (
-                "doc2",
-                "marble-marble",
-                OneOrMany::many(vec![
-                    Embedding {
-                        document: "marble-marble".to_string(),
-                        vec: vec![0.7, -0.3, 0.0],
-                    },
-                    Embedding {
-                        document: "sandwich".to_string(),
-                        vec: vec![0.5, 0.5, -0.7],
-                    },
+            )
+        };
+        let store = InMemoryVectorStore::from_documents_with_ids([
+            article("title_match", "body 1", vec![1.0, 0.0], vec![0.6, 0.8]),
+            article("body_match", "body 2", vec![0.6, 0.8], vec![1.0, 0.0]),
+        ]);
+        let query = Embedding {
+            document: "query".to_string(),
+            vec: vec![1.0, 0.0],
+        };
+        let ranking = |store: InMemoryVectorStore<Article>| {
+            store
+                .top_n_by_embedding::<serde_json::Value>(&query, 2)
+                .unwrap()
+                .into_iter()
+                .map(|(score, id, _)| (id, (score * 1000.0).round() / 1000.0))
+                .collect::<Vec<_>>()
+        };
+        let weighted = |weights: &[(&str, f64)]| {
+            ranking(store.clone().field_weights(weights.to_vec()).unwrap())
+        };
+
+        // Without weights, documents are scored by their best embedding
+        assert_eq!(
+            ranking(store.clone()),
+            vec![
+                ("body_match".to_string(), 1.0),
+                ("title_match".to_string(), 1.0)
+            ]
+        );
+        assert_eq!(
+            weighted(&[("title", 2.0), ("body", 1.0)]),
+            vec![
+                ("title_match".to_string(), 0.867),
+                ("body_match".to_string(), 0.733)
+            ]
+        );
+        assert_eq!(
+            weighted(&[("title", 1.0), ("body", 3.0)]),
+            vec![
+                ("body_match".to_string(), 0.9),
+                ("title_match".to_string(), 0.7)
+            ]
+        );
+        // Only the weighted fields are compared to the query
+        assert_eq!(
+            weighted(&[("title", 1.0)]),
+            vec![
+                ("title_match".to_string(), 1.0),
+                ("body_match".to_string(), 0.6)
+            ]
+        );
+
+        // Documents added later are weighted too
+        let mut store = store.field_weights([("title", 1.0)]).unwrap();
+        store
+            .add_documents_with_ids([article("late", "body 3", vec![0.8, 0.6], vec![1.0, 0.0])])
+            .unwrap();
+        assert_eq!(
+            ranking(store.clone()),
+            vec![("title_match".to_string(), 1.0), ("late".to_string(), 0.8)]
+        );
+
+        // Weights must be positive
+        for weight in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                store.clone().field_weights([("title", 1.0), ("body", weight)]),
+                Err(VectorStoreError::InvalidFieldWeight { field, .. }) if field == "body"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_embedding_fields() {
+        let texts = [
+            (Some("title".to_string()), "Flurbos".to_string()),
+            (Some("body".to_string()), "a".repeat(8)),
+            (None, "Flurbos".to_string()),
+        ];
+        let embedding = |document: &str| Embedding {
+            document: document.to_string(),
+            vec: vec![],
+        };
+
+        // Chunks of a text (see `TruncationPolicy::Split`) are embeddings of its field
+        assert_eq!(
+            embedding_fields(
+                &texts,
+                &OneOrMany::many(vec![
+                    embedding("Flurbos"),
+                    embedding("aaaa"),
+                    embedding("aaaa"),
+                    embedding("Flurbos"),
+                    embedding("unknown"),
                 ])
-                .unwrap(),
+                .unwrap()
             ),
+            vec![
+                Some("title".to_string()),
+                Some("body".to_string()),
+                Some("body".to_string()),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let store = InMemoryVectorStore::from_documents_with_ids(
+            [("b", vec![0.3, 0.4]), ("a", vec![0.1, 0.2])].map(|(id, vec)| {
+                (
+                    id,
+                    json!({"text": id}),
+                    OneOrMany::one(Embedding {
+                        document: id.to_string(),
+                        vec,
+                    }),
+                )
+            }),
+        );
+
+        let path = std::env::temp_dir().join(format!("rig-snapshot-{}.jsonl", std::process::id()));
+        store.save(&path).unwrap();
+        let loaded = InMemoryVectorStore::<serde_json::Value>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded
+                .get_document::<serde_json::Value>("a")
+                .unwrap()
+                .unwrap()["text"],
+            "a"
+        );
+        assert_eq!(loaded.embeddings["b"].1.first().vec, vec![0.3, 0.4]);
+
+        let mut snapshot = vec![];
+        store.save_to_writer(&mut snapshot).unwrap();
+        let snapshot = String::from_utf8(snapshot).unwrap();
+        assert!(snapshot.starts_with(r#"{"id":"a","#));
+        assert_eq!(snapshot.lines().count(), 2);
+
+        let duplicated = format!("{snapshot}\n{}", snapshot.lines().next().unwrap());
+        assert!(matches!(
+            InMemoryVectorStore::<serde_json::Value>::load_from_reader(duplicated.as_bytes()),
+            Err(SnapshotError::DuplicateId(id)) if id == "a"
+        ));
+        assert!(matches!(
+            InMemoryVectorStore::<serde_json::Value>::load_from_reader("{}".as_bytes()),
+            Err(SnapshotError::JsonError { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_stats() {
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![(
+            "a",
+            "glarb-garb",
+            OneOrMany::many(vec![
+                Embedding {
+                    document: "glarb".to_string(),
+                    vec: vec![0.1, 0.1, 0.5],
+                },
+                Embedding {
+                    document: "garb".to_string(),
+                    vec: vec![0.2, 0.1, 0.5],
+                },
+            ])
+            .unwrap(),
+        )]);
+
+        let stats = vector_store.stats();
+
+        assert_eq!(stats.document_count, Some(1));
+        assert_eq!(stats.vector_count, Some(2));
+        assert_eq!(stats.dimensions, Some(3));
+        assert_eq!(stats.size_bytes, Some(1 + 5 + 4 + 6 * 8));
+    }
+
+    fn error_codes_store() -> InMemoryVectorStore<&'static str> {
+        store(&[
+            ("a", "Connection timed out", vec![0.0, 0.1, 0.6]),
             (
-                "doc3",
-                "flumb-flumb",
-                OneOrMany::many(vec![
-                    Embedding {
-                        document: "flumb-flumb".to_string(),
-                        vec: vec![0.3, 0.7, 0.1],
-                    },
-                    Embedding {
-                        document: "banana".to_string(),
-                        vec: vec![0.1, -0.5, -0.5],
-                    },
-                ])
-                .unwrap(),
+                "b",
+                "Error E1234 while parsing the config",
+                vec![0.1, 0.1, 0.5],
             ),
-        ]);
+            ("c", "Error E5678: disk full", vec![0.7, -0.3, 0.0]),
+        ])
+    }
 
-        let ranking = vector_store.vector_search(
-            &Embedding {
-                document: "glarby-glarble".to_string(),
-                vec: vec![0.0, 0.1, 0.6],
-            },
-            1,
+    #[test]
+    fn test_top_n_by_keywords() {
+        let vector_store = error_codes_store();
+
+        let results = vector_store
+            .top_n_by_keywords::<String>("error e1234", 3)
+            .unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id, _)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "c"]
+        );
+        assert!(results[0].0 > results[1].0);
+
+        assert!(vector_store
+            .top_n_by_keywords::<String>("glarb", 3)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search() {
+        // The query embedding is closest to "a", but only "b" contains the error code
+        let index = error_codes_store().index(Model);
+        let results = index.top_n_ids("E1234", 3).await.unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
         );
 
+        let index = error_codes_store()
+            .index(Model)
+            .hybrid(FusionStrategy::default());
+        let results = index.top_n::<String>("E1234", 2).await.unwrap();
         assert_eq!(
-            ranking
-                .into_iter()
-                .map(|Reverse(RankingItem(distance, id, doc, _))| {
-                    (
-                        distance.0,
-                        id.clone(),
-                        serde_json::from_str(&serde_json::to_string(doc).unwrap()).unwrap(),
-                    )
-                })
-                .collect::<Vec<(_, _, String)>>(),
-            vec![(
-                0.9807965956109156,
-                "doc1".to_string(),
-                "glarb-garb".to_string()
-            )]
-        )
+            results,
+            vec![
+                (
+                    1.0 / 61.0 + 1.0 / 62.0,
+                    "b".to_string(),
+                    "Error E1234 while parsing the config".to_string()
+                ),
+                (
+                    1.0 / 61.0,
+                    "a".to_string(),
+                    "Connection timed out".to_string()
+                ),
+            ]
+        );
+
+        let index = error_codes_store()
+            .index(Model)
+            .hybrid(FusionStrategy::Weighted { vector_weight: 0.5 });
+        let results = index.top_n_ids("E1234", 3).await.unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "a", "c"]
+        );
     }
 }
@@ -0,0 +1,356 @@
+//! This module provides [Metered], a wrapper of vector stores recording performance counters of
+//! their searches and insertions: queries per second, search latency percentiles and ingest
+//! throughput (see [Metrics]).
+//!
+//! Any [VectorStoreIndex] (and [VectorStore]) can be metered, so that backends (e.g.: the
+//! in-memory store, LanceDB, Qdrant) can be compared on the same data and queries before
+//! choosing one. The benchmarks of `rig-core` (`cargo bench --features metrics`) compare the
+//! search modes of the in-memory store on synthetic data.
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::{metrics::IntoMetered, VectorStoreIndex};
+//!
+//! let index = InMemoryVectorStore::from_documents(embeddings)
+//!     .index(model)
+//!     .metered();
+//!
+//! for query in queries {
+//!     index.top_n_ids(query, 10).await?;
+//! }
+//!
+//! let metrics = index.metrics();
+//! println!("{:.1} queries/s, p99 latency: {:?}", metrics.qps, metrics.p99_latency);
+//! ```
+use std::{collections::VecDeque, fmt, future::Future, sync::Mutex, time::Duration};
+
+use serde::Deserialize;
+use web_time::Instant;
+
+use super::{filter::Filter, VectorStore, VectorStoreError, VectorStoreIndex, VectorStoreStats};
+
+/// Default number of search latencies kept to compute the percentiles
+const DEFAULT_MAX_SAMPLES: usize = 10_000;
+
+/// Performance counters of a [Metered] vector store, since it was created or its metrics were
+/// [reset](Metered::reset_metrics).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    /// Number of searches
+    pub searches: u64,
+    /// Number of failed searches
+    pub search_errors: u64,
+    /// Searches per second of wall-clock time
+    pub qps: f64,
+    /// Median search latency
+    pub p50_latency: Duration,
+    /// 99th percentile of the search latency
+    pub p99_latency: Duration,
+    /// Mean search latency
+    pub mean_latency: Duration,
+    /// Number of documents inserted or updated
+    pub ingested_documents: u64,
+    /// Documents inserted or updated per second of insertion time
+    pub ingest_throughput: f64,
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} searches ({} errors), {:.1} queries/s, latency p50 {:?} / p99 {:?} / mean {:?}, \
+             {} documents ingested at {:.1} documents/s",
+            self.searches,
+            self.search_errors,
+            self.qps,
+            self.p50_latency,
+            self.p99_latency,
+            self.mean_latency,
+            self.ingested_documents,
+            self.ingest_throughput
+        )
+    }
+}
+
+/// Counters recorded by a [Metered] vector store.
+struct Counters {
+    started: Instant,
+    searches: u64,
+    search_errors: u64,
+    /// Latencies of the last searches (at most `max_samples`)
+    latencies: VecDeque<Duration>,
+    total_latency: Duration,
+    ingested_documents: u64,
+    ingest_time: Duration,
+}
+
+impl Counters {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            searches: 0,
+            search_errors: 0,
+            latencies: VecDeque::new(),
+            total_latency: Duration::ZERO,
+            ingested_documents: 0,
+            ingest_time: Duration::ZERO,
+        }
+    }
+}
+
+/// Latency of the `percentile` (between 0 and 1) of `sorted` latencies (nearest-rank method).
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percentile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Vector store recording the performance of the searches and insertions of the wrapped store
+/// (see the [module](self) documentation).
+///
+/// Only the last [max_samples](Metered::max_samples) search latencies are kept to compute the
+/// percentiles; the other counters cover all the operations.
+pub struct Metered<I> {
+    inner: I,
+    max_samples: usize,
+    counters: Mutex<Counters>,
+}
+
+impl<I> Metered<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            max_samples: DEFAULT_MAX_SAMPLES,
+            counters: Mutex::new(Counters::new()),
+        }
+    }
+
+    /// Set the number of last search latencies kept to compute the percentiles (default: 10000).
+    pub fn max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples.max(1);
+        self
+    }
+
+    /// The performance counters of the store.
+    pub fn metrics(&self) -> Metrics {
+        let counters = self.counters.lock().expect("Lock poisoned");
+        let mut latencies = counters.latencies.iter().copied().collect::<Vec<_>>();
+        latencies.sort();
+
+        let elapsed = counters.started.elapsed().as_secs_f64();
+        let ingest_time = counters.ingest_time.as_secs_f64();
+
+        Metrics {
+            searches: counters.searches,
+            search_errors: counters.search_errors,
+            qps: if elapsed > 0.0 {
+                counters.searches as f64 / elapsed
+            } else {
+                0.0
+            },
+            p50_latency: percentile(&latencies, 0.5),
+            p99_latency: percentile(&latencies, 0.99),
+            mean_latency: match counters.searches {
+                0 => Duration::ZERO,
+                searches => counters.total_latency.div_f64(searches as f64),
+            },
+            ingested_documents: counters.ingested_documents,
+            ingest_throughput: if ingest_time > 0.0 {
+                counters.ingested_documents as f64 / ingest_time
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Reset the performance counters (e.g.: after warming up the store).
+    pub fn reset_metrics(&self) {
+        *self.counters.lock().expect("Lock poisoned") = Counters::new();
+    }
+
+    /// The wrapped store.
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    /// The wrapped store, mutably. Operations on it are not recorded.
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Run `search`, recording its latency.
+    async fn search<T>(
+        &self,
+        search: impl Future<Output = Result<T, VectorStoreError>>,
+    ) -> Result<T, VectorStoreError> {
+        let start = Instant::now();
+        let result = search.await;
+        let latency = start.elapsed();
+
+        let mut counters = self.counters.lock().expect("Lock poisoned");
+        counters.searches += 1;
+        if result.is_err() {
+            counters.search_errors += 1;
+        }
+        counters.total_latency += latency;
+        while counters.latencies.len() >= self.max_samples {
+            counters.latencies.pop_front();
+        }
+        counters.latencies.push_back(latency);
+
+        result
+    }
+}
+
+impl<I: VectorStoreIndex> VectorStoreIndex for Metered<I> {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(self.inner.top_n(query, n)).await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search(self.inner.top_n_ids(query, n)).await
+    }
+
+    async fn top_n_filtered<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(self.inner.top_n_filtered(query, n, filter))
+            .await
+    }
+
+    async fn top_n_ids_filtered(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search(self.inner.top_n_ids_filtered(query, n, filter))
+            .await
+    }
+
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        self.inner.stats().await
+    }
+}
+
+impl<S: VectorStore> VectorStore for Metered<S> {
+    type Document = S::Document;
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        let count = documents.len();
+        let Self {
+            inner, counters, ..
+        } = self;
+        let start = Instant::now();
+        inner.insert_documents(documents, upsert).await?;
+        record_ingest(counters, count, start.elapsed());
+        Ok(())
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        let Self {
+            inner, counters, ..
+        } = self;
+        let start = Instant::now();
+        inner.update_document(document).await?;
+        record_ingest(counters, 1, start.elapsed());
+        Ok(())
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        self.inner.delete_documents(ids).await
+    }
+}
+
+/// Record the ingestion of `documents` documents in `elapsed`.
+fn record_ingest(counters: &mut Mutex<Counters>, documents: usize, elapsed: Duration) {
+    let counters = counters.get_mut().expect("Lock poisoned");
+    counters.ingested_documents += documents as u64;
+    counters.ingest_time += elapsed;
+}
+
+/// Extension trait wrapping any [VectorStoreIndex] in a [Metered] store.
+pub trait IntoMetered: Sized {
+    /// Record the performance of the searches and insertions of the store (see [Metered]).
+    fn metered(self) -> Metered<Self>;
+}
+
+impl<I: VectorStoreIndex> IntoMetered for I {
+    fn metered(self) -> Metered<Self> {
+        Metered::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{percentile, IntoMetered};
+    use crate::{
+        providers::mock::MockVectorStore,
+        vector_store::{VectorStore, VectorStoreIndex},
+    };
+
+    #[test]
+    fn test_percentile() {
+        let latencies = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&latencies, 0.5), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&latencies[..1], 0.99), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_metered() {
+        let mut index = MockVectorStore::new()
+            .document("a", "a green alien")
+            .metered()
+            .max_samples(2);
+
+        for _ in 0..3 {
+            index.top_n_ids("alien", 1).await.unwrap();
+        }
+        index
+            .insert_documents(
+                vec![
+                    ("b".to_string(), serde_json::json!("an ancient tool")),
+                    ("c".to_string(), serde_json::json!("a human")),
+                ],
+                false,
+            )
+            .await
+            .unwrap();
+
+        let metrics = index.metrics();
+        assert_eq!(metrics.searches, 3);
+        assert_eq!(metrics.search_errors, 0);
+        assert!(metrics.p50_latency <= metrics.p99_latency);
+        assert!(metrics.qps > 0.0);
+        assert_eq!(metrics.ingested_documents, 2);
+        assert_eq!(index.inner().queries().len(), 3);
+
+        index.reset_metrics();
+        assert_eq!(index.metrics().searches, 0);
+    }
+}
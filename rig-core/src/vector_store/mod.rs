@@ -3,8 +3,16 @@ use serde::Deserialize;
 use serde_json::Value;
 
 use crate::embeddings::EmbeddingError;
+use filter::Filter;
 
+pub mod filter;
+#[cfg(feature = "hnsw")]
+pub mod hnsw;
 pub mod in_memory_store;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod quantization;
+pub mod schema;
 
 #[derive(Debug, thiserror::Error)]
 pub enum VectorStoreError {
@@ -20,12 +28,65 @@ pub enum VectorStoreError {
 
     #[error("Missing Id: {0}")]
     MissingIdError(String),
+
+    /// A document with the same id already exists in the vector store
+    #[error("Duplicate Id: {0}")]
+    DuplicateIdError(String),
+
+    /// A document could not be deserialized into the type requested from
+    /// [VectorStoreIndex::top_n]
+    #[error("Document {id} could not be deserialized: {source}")]
+    DeserializationError {
+        id: String,
+        source: serde_json::Error,
+    },
+
+    /// The filter is invalid or not supported by the vector store
+    #[error("Filter error: {0}")]
+    FilterError(String),
+
+    /// An embedding (of a document or of a query) does not have the number of dimensions of
+    /// the vector store or of its embedding model
+    #[error("Embedding dimensions mismatch: expected {expected}, found {found}")]
+    DimensionMismatch { expected: usize, found: usize },
+
+    /// The documents of the vector store were embedded with another model than the model of the
+    /// store (see [EmbeddingSchema](schema::EmbeddingSchema))
+    #[error("Embedding model mismatch: documents embedded with {expected}, queried with {found}")]
+    ModelMismatch { expected: String, found: String },
+
+    /// The vector store was written with a schema version more recent than the supported one
+    #[error("Unsupported schema version {found} (supported: up to {supported})")]
+    SchemaVersionError { found: u32, supported: u32 },
+
+    /// A field weight is not a positive (finite) number (see
+    /// [InMemoryVectorStore::field_weights](in_memory_store::InMemoryVectorStore::field_weights))
+    #[error("Invalid weight of field {field}: {weight}")]
+    InvalidFieldWeight { field: String, weight: f64 },
+}
+
+/// Statistics of a vector store index, for monitoring and capacity planning.
+/// Fields are `None` when the backend cannot provide them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VectorStoreStats {
+    /// Number of documents in the store
+    pub document_count: Option<usize>,
+    /// Number of vectors in the store (a document may have several embeddings)
+    pub vector_count: Option<usize>,
+    /// Number of dimensions of the vectors
+    pub dimensions: Option<usize>,
+    /// Type of the index used for vector searches (e.g.: `"FLAT"` for exact searches)
+    pub index_type: Option<String>,
+    /// Approximate memory or disk usage, in bytes
+    pub size_bytes: Option<u64>,
 }
 
 /// Trait for vector store indexes
 pub trait VectorStoreIndex: Send + Sync {
     /// Get the top n documents based on the distance to the given query.
-    /// The result is a list of tuples of the form (score, id, document)
+    /// The result is a list of tuples of the form (score, id, document), where each document is
+    /// deserialized into `T` (e.g.: the type of the documents that were embedded, or
+    /// [serde_json::Value] for raw documents).
     fn top_n<T: for<'a> Deserialize<'a> + Send>(
         &self,
         query: &str,
@@ -38,6 +99,96 @@ pub trait VectorStoreIndex: Send + Sync {
         query: &str,
         n: usize,
     ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send;
+
+    /// Same as `top_n`, but only among the documents matching `filter`.
+    /// By default, filters are not supported and a [VectorStoreError::FilterError] is returned.
+    fn top_n_filtered<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        _query: &str,
+        _n: usize,
+        _filter: Filter,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send
+    {
+        async { Err(filters_not_supported()) }
+    }
+
+    /// Same as `top_n_ids`, but only among the documents matching `filter`.
+    /// By default, filters are not supported and a [VectorStoreError::FilterError] is returned.
+    fn top_n_ids_filtered(
+        &self,
+        _query: &str,
+        _n: usize,
+        _filter: Filter,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send
+    {
+        async { Err(filters_not_supported()) }
+    }
+
+    /// Get the statistics of the index. By default, all statistics are unknown.
+    fn stats(
+        &self,
+    ) -> impl std::future::Future<Output = Result<VectorStoreStats, VectorStoreError>> + Send {
+        async { Ok(VectorStoreStats::default()) }
+    }
+}
+
+/// Trait for vector stores whose documents can be inserted, updated and deleted, so that
+/// long-lived indexes can be maintained without being rebuilt from scratch.
+pub trait VectorStore: Send + Sync {
+    /// A document with its id and embeddings, in the format of the store
+    /// (e.g.: `(id, document, embeddings)` for the [InMemoryVectorStore](in_memory_store::InMemoryVectorStore)).
+    type Document: Send;
+
+    /// Insert documents in the store. If `upsert` is true, documents replace the existing
+    /// documents with the same id. Otherwise, a [VectorStoreError::DuplicateIdError] is returned
+    /// if an id already exists, and no document is inserted.
+    fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+
+    /// Replace the existing document with the same id (e.g.: after re-embedding a changed
+    /// document). Returns a [VectorStoreError::MissingIdError] if the id does not exist.
+    fn update_document(
+        &mut self,
+        document: Self::Document,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+
+    /// Delete the documents with the given ids. Ids which do not exist are ignored.
+    fn delete_documents(
+        &mut self,
+        ids: &[String],
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+}
+
+/// Trait for vector stores hosting several independent collections of documents (also called
+/// namespaces) over a single connection, e.g.: one index per tenant or per agent.
+pub trait VectorStoreCollections: Send + Sync {
+    /// A collection, which is a vector store index of its own
+    type Collection: VectorStore + VectorStoreIndex;
+
+    /// Get the collection with the given name, creating it if it does not exist.
+    fn collection(
+        &mut self,
+        name: &str,
+    ) -> impl std::future::Future<Output = Result<&mut Self::Collection, VectorStoreError>> + Send;
+
+    /// Names of the collections of the store.
+    fn collection_names(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, VectorStoreError>> + Send;
+
+    /// Delete the collection with the given name and all its documents.
+    /// Does nothing if the collection does not exist.
+    fn delete_collection(
+        &mut self,
+        name: &str,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+}
+
+fn filters_not_supported() -> VectorStoreError {
+    VectorStoreError::FilterError("Filters are not supported by this vector store".to_string())
 }
 
 pub type TopNResults = Result<Vec<(f64, String, Value)>, VectorStoreError>;
@@ -50,6 +201,22 @@ pub trait VectorStoreIndexDyn: Send + Sync {
         query: &'a str,
         n: usize,
     ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
+
+    fn top_n_filtered<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, TopNResults>;
+
+    fn top_n_ids_filtered<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
+
+    fn stats(&self) -> BoxFuture<'_, Result<VectorStoreStats, VectorStoreError>>;
 }
 
 impl<I: VectorStoreIndex> VectorStoreIndexDyn for I {
@@ -75,6 +242,35 @@ impl<I: VectorStoreIndex> VectorStoreIndexDyn for I {
     ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
         Box::pin(self.top_n_ids(query, n))
     }
+
+    fn top_n_filtered<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, TopNResults> {
+        Box::pin(async move {
+            Ok(self
+                .top_n_filtered::<serde_json::Value>(query, n, filter)
+                .await?
+                .into_iter()
+                .map(|(score, id, doc)| (score, id, prune_document(doc).unwrap_or_default()))
+                .collect::<Vec<_>>())
+        })
+    }
+
+    fn top_n_ids_filtered<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
+        Box::pin(self.top_n_ids_filtered(query, n, filter))
+    }
+
+    fn stats(&self) -> BoxFuture<'_, Result<VectorStoreStats, VectorStoreError>> {
+        Box::pin(VectorStoreIndex::stats(self))
+    }
 }
 
 fn prune_document(document: serde_json::Value) -> Option<serde_json::Value> {
@@ -0,0 +1,238 @@
+//! Quantization of embeddings: compact approximations of the embedding vectors, to reduce the
+//! memory used by large local indexes (see [InMemoryVectorStore::quantized]).
+//!
+//! - [Quantization::Int8] (scalar quantization): each dimension is stored as an 8-bit integer
+//!   instead of a 64-bit float (8x less memory). Similarities are very close to the exact ones.
+//! - [Quantization::Binary]: each dimension is stored as a single bit, its sign (64x less
+//!   memory). Similarities are rough, so rescoring the top candidates is recommended.
+//!
+//! Searches first rank the documents by the similarity of their quantized vectors, under the
+//! [metric](super::in_memory_store::InMemoryVectorStore::metric) of the store: quantized vectors
+//! approximate the cosine similarity of the original vectors, and keep their norm to approximate
+//! the dot product and the Euclidean distance. When
+//! rescoring is enabled (the default), the full-precision vectors are kept and the top
+//! `n * oversampling` candidates are reranked by their exact similarity, which keeps recall high.
+//! When rescoring is disabled, the full-precision vectors are discarded, which is what saves the
+//! memory.
+//!
+//! [InMemoryVectorStore::quantized]: super::in_memory_store::InMemoryVectorStore::quantized
+use crate::embeddings::distance::DistanceMetric;
+
+/// Quantization of the vectors of a vector store (see the [module](self) documentation).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantization {
+    /// Scalar quantization of each dimension to an 8-bit integer
+    Int8,
+    /// Binary quantization of each dimension to its sign
+    Binary,
+}
+
+/// Configuration of the quantization of a vector store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuantizationConfig {
+    pub quantization: Quantization,
+    /// Factor by which the number of requested documents is multiplied to get the number of
+    /// candidates rescored on full-precision vectors, or `None` to discard the full-precision
+    /// vectors and rank the documents by the similarity of their quantized vectors only.
+    pub rescore: Option<usize>,
+}
+
+impl QuantizationConfig {
+    /// Int8 quantization, rescoring 4 times as many candidates as requested documents.
+    pub fn int8() -> Self {
+        Self {
+            quantization: Quantization::Int8,
+            rescore: Some(4),
+        }
+    }
+
+    /// Binary quantization, rescoring 10 times as many candidates as requested documents.
+    pub fn binary() -> Self {
+        Self {
+            quantization: Quantization::Binary,
+            rescore: Some(10),
+        }
+    }
+
+    /// Set the factor by which the number of requested documents is multiplied to get the
+    /// number of candidates rescored on full-precision vectors.
+    pub fn rescore(mut self, oversampling: usize) -> Self {
+        self.rescore = Some(oversampling.max(1));
+        self
+    }
+
+    /// Discard the full-precision vectors, to save memory at the cost of recall.
+    pub fn without_rescoring(mut self) -> Self {
+        self.rescore = None;
+        self
+    }
+}
+
+/// A quantized embedding vector.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum QuantizedVector {
+    /// Dimensions scaled to [-127, 127], with the norm of the scaled vector and of the original
+    /// vector
+    Int8 {
+        values: Vec<i8>,
+        norm: f32,
+        magnitude: f32,
+    },
+    /// Signs of the dimensions (1 if positive), packed in 64-bit words, with the norm of the
+    /// original vector
+    Binary {
+        bits: Vec<u64>,
+        ndims: usize,
+        magnitude: f32,
+    },
+}
+
+impl QuantizedVector {
+    pub(crate) fn new(quantization: Quantization, vec: &[f64]) -> Self {
+        let magnitude = vec.iter().map(|value| value * value).sum::<f64>().sqrt() as f32;
+        match quantization {
+            Quantization::Int8 => {
+                let max = vec.iter().fold(0.0f64, |max, value| max.max(value.abs()));
+                let scale = if max > 0.0 { 127.0 / max } else { 0.0 };
+                let values = vec
+                    .iter()
+                    .map(|value| (value * scale).round() as i8)
+                    .collect::<Vec<_>>();
+                let norm = values
+                    .iter()
+                    .map(|value| (*value as f32).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                Self::Int8 {
+                    values,
+                    norm,
+                    magnitude,
+                }
+            }
+            Quantization::Binary => {
+                let mut bits = vec![0u64; vec.len().div_ceil(64)];
+                for (i, value) in vec.iter().enumerate() {
+                    if *value > 0.0 {
+                        bits[i / 64] |= 1 << (i % 64);
+                    }
+                }
+                Self::Binary {
+                    bits,
+                    ndims: vec.len(),
+                    magnitude,
+                }
+            }
+        }
+    }
+
+    /// Approximate similarity of the original vectors under `metric` (see
+    /// [DistanceMetric::similarity]), computed from their approximate cosine similarity and
+    /// their norms.
+    pub(crate) fn similarity(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        let cosine = self.cosine_similarity(other);
+        let (a, b) = (self.magnitude(), other.magnitude());
+        match metric {
+            DistanceMetric::Cosine => cosine,
+            DistanceMetric::NormalizedCosine | DistanceMetric::DotProduct => cosine * a * b,
+            DistanceMetric::Euclidean => {
+                1.0 / (1.0 + (a * a + b * b - 2.0 * cosine * a * b).max(0.0).sqrt())
+            }
+        }
+    }
+
+    /// Norm of the original vector.
+    fn magnitude(&self) -> f64 {
+        match self {
+            Self::Int8 { magnitude, .. } | Self::Binary { magnitude, .. } => *magnitude as f64,
+        }
+    }
+
+    /// Approximate cosine similarity of the original vectors, in [-1, 1].
+    /// Vectors quantized differently have a similarity of 0.
+    fn cosine_similarity(&self, other: &Self) -> f64 {
+        match (self, other) {
+            (
+                Self::Int8 { values, norm, .. },
+                Self::Int8 {
+                    values: other_values,
+                    norm: other_norm,
+                    ..
+                },
+            ) => {
+                if *norm == 0.0 || *other_norm == 0.0 {
+                    return 0.0;
+                }
+                let dot = values
+                    .iter()
+                    .zip(other_values)
+                    .map(|(a, b)| *a as i32 * *b as i32)
+                    .sum::<i32>();
+                dot as f64 / (*norm as f64 * *other_norm as f64)
+            }
+            (
+                Self::Binary { bits, ndims, .. },
+                Self::Binary {
+                    bits: other_bits, ..
+                },
+            ) => {
+                if *ndims == 0 {
+                    return 0.0;
+                }
+                let hamming = bits
+                    .iter()
+                    .zip(other_bits)
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum::<u32>();
+                1.0 - 2.0 * hamming as f64 / *ndims as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Approximate memory used by the vector, in bytes.
+    pub(crate) fn size_bytes(&self) -> usize {
+        match self {
+            Self::Int8 { values, .. } => values.len() + 2 * std::mem::size_of::<f32>(),
+            Self::Binary { bits, .. } => {
+                bits.len() * std::mem::size_of::<u64>() + std::mem::size_of::<f32>()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantized_similarity() {
+        let a = [0.5, -0.2, 0.1, 0.9];
+        let b = [0.4, -0.1, 0.2, 0.8];
+        let c = [-0.5, 0.2, -0.1, -0.9];
+
+        let int8 = |vec: &[f64]| QuantizedVector::new(Quantization::Int8, vec);
+        assert!((int8(&a).cosine_similarity(&int8(&a)) - 1.0).abs() < 1e-6);
+        assert!((int8(&a).cosine_similarity(&int8(&b)) - 0.985).abs() < 0.01);
+        assert!((int8(&a).cosine_similarity(&int8(&c)) + 1.0).abs() < 1e-6);
+
+        let binary = |vec: &[f64]| QuantizedVector::new(Quantization::Binary, vec);
+        assert_eq!(binary(&a).cosine_similarity(&binary(&b)), 1.0);
+        assert_eq!(binary(&a).cosine_similarity(&binary(&c)), -1.0);
+        assert_eq!(binary(&[0.0; 100]).size_bytes(), 20);
+
+        assert_eq!(int8(&a).cosine_similarity(&binary(&a)), 0.0);
+    }
+
+    #[test]
+    fn test_quantized_metrics() {
+        let a = [3.0, 4.0];
+        let b = [6.0, 8.0];
+        let int8 = |vec: &[f64]| QuantizedVector::new(Quantization::Int8, vec);
+
+        let similarity = |metric| int8(&a).similarity(&int8(&b), metric);
+        assert!((similarity(DistanceMetric::Cosine) - 1.0).abs() < 1e-6);
+        assert!((similarity(DistanceMetric::DotProduct) - 50.0).abs() < 1e-3);
+        // Distance of 5
+        assert!((similarity(DistanceMetric::Euclidean) - 1.0 / 6.0).abs() < 1e-6);
+    }
+}
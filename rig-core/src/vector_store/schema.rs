@@ -0,0 +1,289 @@
+//! This module provides [EmbeddingSchema], the metadata of the embeddings of a vector store: the
+//! name of the model which computed them, their number of dimensions and the version of the
+//! schema of the store.
+//!
+//! Embeddings of different models are not comparable: querying a store with another model than
+//! the one its documents were embedded with returns meaningless results, without any error when
+//! both models have the same number of dimensions. Stores recording the schema of their
+//! embeddings (e.g.: the [InMemoryVectorStore](super::in_memory_store::InMemoryVectorStore) and
+//! its snapshots) refuse such queries with a [VectorStoreError::ModelMismatch].
+//!
+//! After a change of embedding model, the documents are migrated by re-embedding them with the
+//! new model, e.g.: with [reembed] or
+//! [InMemoryVectorStore::reembed](super::in_memory_store::InMemoryVectorStore::reembed).
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::{in_memory_store::InMemoryVectorStore, VectorStoreError, VectorStoreIndex};
+//!
+//! // The snapshot records that the documents were embedded with text-embedding-ada-002
+//! let store = InMemoryVectorStore::<WordDefinition>::load("index.jsonl")?;
+//!
+//! let index = store.clone().index(openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL));
+//! assert!(matches!(
+//!     index.top_n_ids("What is a flurbo?", 1).await,
+//!     Err(VectorStoreError::ModelMismatch { .. })
+//! ));
+//!
+//! // Migrate the store to the new model
+//! let model = openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+//! let index = store.reembed(&model).await?.index(model);
+//! ```
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{VectorStore, VectorStoreError};
+use crate::{
+    embeddings::{Embed, EmbedError, Embedding, EmbeddingModel, EmbeddingsBuilder, TextEmbedder},
+    OneOrMany,
+};
+
+/// Version of the schema of the vector stores written by this version of Rig
+pub const EMBEDDING_SCHEMA_VERSION: u32 = 1;
+
+/// Metadata keys of the schema (see [EmbeddingSchema::to_metadata])
+const MODEL_KEY: &str = "rig:embedding_model";
+const DIMENSIONS_KEY: &str = "rig:embedding_dimensions";
+const VERSION_KEY: &str = "rig:schema_version";
+
+/// Metadata of the embeddings of a vector store (see the [module](self) documentation).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingSchema {
+    /// Name of the embedding model, if known
+    pub model: Option<String>,
+    /// Number of dimensions of the embeddings (0 if unknown)
+    pub dimensions: usize,
+    /// Version of the schema of the store
+    pub version: u32,
+}
+
+impl EmbeddingSchema {
+    /// The schema of the embeddings of `model`, at the current schema version.
+    pub fn of<M: EmbeddingModel>(model: &M) -> Self {
+        Self {
+            model: model.model_name().map(str::to_string),
+            dimensions: model.ndims(),
+            version: EMBEDDING_SCHEMA_VERSION,
+        }
+    }
+
+    /// Check that the embeddings of `model` are comparable with the embeddings of this schema.
+    /// Unknown model names and dimensions are not checked.
+    pub fn check<M: EmbeddingModel>(&self, model: &M) -> Result<(), VectorStoreError> {
+        if self.version > EMBEDDING_SCHEMA_VERSION {
+            return Err(VectorStoreError::SchemaVersionError {
+                found: self.version,
+                supported: EMBEDDING_SCHEMA_VERSION,
+            });
+        }
+        if let (Some(expected), Some(found)) = (&self.model, model.model_name()) {
+            if expected != found {
+                return Err(VectorStoreError::ModelMismatch {
+                    expected: expected.clone(),
+                    found: found.to_string(),
+                });
+            }
+        }
+        if self.dimensions > 0 && model.ndims() > 0 && self.dimensions != model.ndims() {
+            return Err(VectorStoreError::DimensionMismatch {
+                expected: self.dimensions,
+                found: model.ndims(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The schema as string metadata, for stores recording it alongside their documents
+    /// (e.g.: in the metadata of the embedding column of a table).
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::from([
+            (DIMENSIONS_KEY.to_string(), self.dimensions.to_string()),
+            (VERSION_KEY.to_string(), self.version.to_string()),
+        ]);
+        if let Some(model) = &self.model {
+            metadata.insert(MODEL_KEY.to_string(), model.clone());
+        }
+        metadata
+    }
+
+    /// Read a schema written with [EmbeddingSchema::to_metadata], if `metadata` has one.
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        let version = metadata.get(VERSION_KEY)?.parse().ok()?;
+        Some(Self {
+            model: metadata.get(MODEL_KEY).cloned(),
+            dimensions: metadata
+                .get(DIMENSIONS_KEY)
+                .and_then(|dimensions| dimensions.parse().ok())
+                .unwrap_or_default(),
+            version,
+        })
+    }
+}
+
+/// A document with its id, embedded as its document.
+struct Identified<D> {
+    id: String,
+    document: D,
+}
+
+impl<D: Embed> Embed for Identified<D> {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        self.document.embed(embedder)
+    }
+}
+
+/// Embed `documents` with `model`, keeping their ids.
+pub(crate) async fn embed_documents<M: EmbeddingModel, D: Embed + Send>(
+    model: &M,
+    documents: impl IntoIterator<Item = (String, D)>,
+) -> Result<Vec<(String, D, OneOrMany<Embedding>)>, VectorStoreError> {
+    let embeddings = EmbeddingsBuilder::new(model.clone())
+        .documents(
+            documents
+                .into_iter()
+                .map(|(id, document)| Identified { id, document }),
+        )
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+        .build()
+        .await?;
+
+    Ok(embeddings
+        .into_iter()
+        .map(|(identified, embeddings)| (identified.id, identified.document, embeddings))
+        .collect())
+}
+
+/// Migrate `documents` to a new embedding model: embed them with `model` and upsert them in
+/// `store`, a store using `model` (e.g.: a new table or collection, which then replaces the
+/// store of the previous model).
+///
+/// Stores can only be migrated from the source documents, as they do not keep the texts which
+/// were embedded (see [InMemoryVectorStore::reembed](super::in_memory_store::InMemoryVectorStore::reembed)
+/// for the in-memory store, which does).
+pub async fn reembed<S, M, D>(
+    store: &mut S,
+    model: &M,
+    documents: impl IntoIterator<Item = (String, D)>,
+) -> Result<(), VectorStoreError>
+where
+    S: VectorStore,
+    S::Document: From<(String, D, OneOrMany<Embedding>)>,
+    M: EmbeddingModel,
+    D: Embed + Send,
+{
+    let documents = embed_documents(model, documents).await?;
+    tracing::info!(target: "rig",
+        "Re-embedded {} documents with {}",
+        documents.len(),
+        model.model_name().unwrap_or("the new model")
+    );
+    store
+        .insert_documents(documents.into_iter().map(S::Document::from).collect(), true)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmbeddingSchema, EMBEDDING_SCHEMA_VERSION};
+    use crate::{
+        embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+        providers::mock::MockEmbeddingModel,
+        vector_store::{in_memory_store::InMemoryVectorStore, VectorStoreError, VectorStoreIndex},
+    };
+
+    /// Mock model with a name
+    #[derive(Clone)]
+    struct Named(&'static str, MockEmbeddingModel);
+
+    impl EmbeddingModel for Named {
+        const MAX_DOCUMENTS: usize = 1024;
+
+        fn ndims(&self) -> usize {
+            self.1.ndims()
+        }
+
+        fn model_name(&self) -> Option<&str> {
+            Some(self.0)
+        }
+
+        async fn embed_texts(
+            &self,
+            texts: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            self.1.embed_texts(texts).await
+        }
+    }
+
+    #[test]
+    fn test_schema_check() {
+        let schema = EmbeddingSchema::of(&Named("small", MockEmbeddingModel::new(8)));
+        assert_eq!(
+            EmbeddingSchema::from_metadata(&schema.to_metadata()),
+            Some(schema.clone())
+        );
+
+        assert!(schema
+            .check(&Named("small", MockEmbeddingModel::new(8)))
+            .is_ok());
+        assert!(matches!(
+            schema.check(&Named("large", MockEmbeddingModel::new(8))),
+            Err(VectorStoreError::ModelMismatch { expected, found })
+                if expected == "small" && found == "large"
+        ));
+        assert!(matches!(
+            schema.check(&Named("small", MockEmbeddingModel::new(16))),
+            Err(VectorStoreError::DimensionMismatch {
+                expected: 8,
+                found: 16
+            })
+        ));
+
+        let schema = EmbeddingSchema {
+            version: EMBEDDING_SCHEMA_VERSION + 1,
+            ..schema
+        };
+        assert!(matches!(
+            schema.check(&Named("small", MockEmbeddingModel::new(8))),
+            Err(VectorStoreError::SchemaVersionError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_model_mismatch_and_reembed() {
+        let small = Named("small", MockEmbeddingModel::new(8));
+        let large = Named("large", MockEmbeddingModel::new(8));
+
+        let embeddings = crate::embeddings::EmbeddingsBuilder::new(small.clone())
+            .documents(["a green alien".to_string(), "an ancient tool".to_string()])
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let (_, store) = InMemoryVectorStore::from_documents(embeddings)
+            .index(small)
+            .into_parts();
+
+        // The snapshot keeps the model the documents were embedded with
+        let mut snapshot = vec![];
+        store.save_to_writer(&mut snapshot).unwrap();
+        let store = InMemoryVectorStore::<String>::load_from_reader(snapshot.as_slice()).unwrap();
+        assert_eq!(
+            store
+                .get_embedding_schema()
+                .and_then(|schema| schema.model.as_deref()),
+            Some("small")
+        );
+
+        let index = store.clone().index(large.clone());
+        assert!(matches!(
+            index.top_n_ids("a green alien", 1).await,
+            Err(VectorStoreError::ModelMismatch { .. })
+        ));
+
+        let index = store.reembed(&large).await.unwrap().index(large);
+        assert_eq!(index.len(), 2);
+        let results = index.top_n::<String>("a green alien", 1).await.unwrap();
+        assert_eq!(results[0].2, "a green alien");
+    }
+}
@@ -215,3 +215,35 @@ fn test_multiple_embed_tags() {
         ]
     );
 }
+
+#[test]
+fn test_embed_option() {
+    #[derive(Embed)]
+    struct Product {
+        #[embed]
+        name: String,
+        #[embed]
+        description: Option<String>,
+    }
+
+    let product = Product {
+        name: "Rig".to_string(),
+        description: None,
+    };
+    assert_eq!(
+        embeddings::to_texts(product).unwrap(),
+        vec!["Rig".to_string()]
+    );
+
+    let product = Product {
+        name: "Rig".to_string(),
+        description: Some("A Rust library for LLM applications".to_string()),
+    };
+    assert_eq!(
+        embeddings::to_texts(product).unwrap(),
+        vec![
+            "Rig".to_string(),
+            "A Rust library for LLM applications".to_string()
+        ]
+    );
+}
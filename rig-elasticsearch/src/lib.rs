@@ -0,0 +1,885 @@
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    Method, RequestBuilder, StatusCode,
+};
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex},
+    OneOrMany,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// Field of the id of the document an embedding belongs to
+const DOCUMENT_ID_FIELD: &str = "document_id";
+/// Field of the embedded text, searched with BM25 by hybrid searches
+const TEXT_FIELD: &str = "text";
+/// Field of the embedding vector
+const EMBEDDING_FIELD: &str = "embedding";
+/// Field of the document, stored but not indexed
+const DOCUMENT_FIELD: &str = "document";
+
+/// Search engine of the cluster, which determines the syntax of the mappings and of the kNN
+/// queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Elasticsearch 8.x (`dense_vector` fields, top-level `knn` searches)
+    #[default]
+    Elasticsearch,
+    /// OpenSearch 2.x (`knn_vector` fields, `knn` queries)
+    OpenSearch,
+}
+
+/// Similarity function of the vector field of the index.
+/// See the Elasticsearch [documentation](https://www.elastic.co/guide/en/elasticsearch/reference/current/dense-vector.html#dense-vector-params)
+/// for more information.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Similarity {
+    #[default]
+    Cosine,
+    DotProduct,
+    L2Norm,
+}
+
+impl Similarity {
+    fn as_str(&self, engine: Engine) -> &'static str {
+        match (engine, self) {
+            (Engine::Elasticsearch, Similarity::Cosine) => "cosine",
+            (Engine::Elasticsearch, Similarity::DotProduct) => "dot_product",
+            (Engine::Elasticsearch, Similarity::L2Norm) => "l2_norm",
+            (Engine::OpenSearch, Similarity::Cosine) => "cosinesimil",
+            (Engine::OpenSearch, Similarity::DotProduct) => "innerproduct",
+            (Engine::OpenSearch, Similarity::L2Norm) => "l2",
+        }
+    }
+}
+
+fn reqwest_to_rig_error(e: reqwest::Error) -> VectorStoreError {
+    VectorStoreError::DatastoreError(Box::new(e))
+}
+
+/// HTTP client of an Elasticsearch (or OpenSearch) cluster, sending the requests of the REST API
+/// of the cluster with [reqwest].
+#[derive(Clone, Debug)]
+pub struct ElasticsearchClient {
+    http_client: reqwest::Client,
+    base_url: String,
+    headers: HeaderMap,
+    basic_auth: Option<(String, String)>,
+}
+
+impl ElasticsearchClient {
+    /// Create a client of the cluster at `base_url` (e.g.: `http://localhost:9200`).
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: HeaderMap::new(),
+            basic_auth: None,
+        }
+    }
+
+    /// Authenticate the requests with the API key `api_key` (base64 encoded, as returned by the
+    /// create API key API).
+    pub fn api_key(mut self, api_key: &str) -> Self {
+        self.headers.insert(
+            AUTHORIZATION,
+            format!("ApiKey {api_key}")
+                .parse()
+                .expect("API key should be a valid header value"),
+        );
+        self
+    }
+
+    /// Authenticate the requests with the given username and password.
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.basic_auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Send the requests with `http_client` (e.g.: a client configured with custom root
+    /// certificates). The authentication headers of the client are still sent with every
+    /// request.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http_client
+            .request(method, format!("{}/{}", self.base_url, path))
+            .headers(self.headers.clone());
+
+        match &self.basic_auth {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+
+    /// Whether the index `index` exists.
+    async fn index_exists(&self, index: &str) -> Result<bool, VectorStoreError> {
+        let response = self
+            .request(Method::HEAD, index)
+            .send()
+            .await
+            .map_err(reqwest_to_rig_error)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => Err(VectorStoreError::DatastoreError(
+                format!("Elasticsearch error {status}").into(),
+            )),
+        }
+    }
+
+    /// Send `request` with the JSON body `body`, check the status of the response and parse its
+    /// JSON body.
+    async fn send_json(
+        &self,
+        request: RequestBuilder,
+        body: &Value,
+    ) -> Result<Value, VectorStoreError> {
+        json_response(request.json(body)).await
+    }
+
+    /// Send `request` with the NDJSON body `lines` (e.g.: to the bulk API), check the status of
+    /// the response and parse its JSON body.
+    async fn send_ndjson(
+        &self,
+        request: RequestBuilder,
+        lines: &[Value],
+    ) -> Result<Value, VectorStoreError> {
+        let body = lines
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+
+        json_response(
+            request
+                .header(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/x-ndjson"),
+                )
+                .body(body),
+        )
+        .await
+    }
+}
+
+/// Send `request`, check the status of the response and parse its JSON body.
+async fn json_response(request: RequestBuilder) -> Result<Value, VectorStoreError> {
+    let response = request.send().await.map_err(reqwest_to_rig_error)?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(VectorStoreError::DatastoreError(
+            format!("Elasticsearch error {status}: {body}").into(),
+        ));
+    }
+
+    response.json().await.map_err(reqwest_to_rig_error)
+}
+
+/// Translate a JSON object the documents must contain (e.g.: `{"category": "animals"}`) into
+/// the `term` queries of an Elasticsearch `bool` filter on the fields of the documents:
+/// - nested objects are matched field by field (`{"author": {"name": "Ann"}}` matches
+///   `document.author.name`),
+/// - arrays match the documents containing all their values,
+/// - `null` matches the documents without the field.
+pub fn filter_clauses(filter: &Map<String, Value>) -> Vec<Value> {
+    fn translate(field: &str, value: &Value, clauses: &mut Vec<Value>) {
+        match value {
+            Value::Object(fields) => {
+                for (name, value) in fields {
+                    translate(&format!("{field}.{name}"), value, clauses);
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    translate(field, value, clauses);
+                }
+            }
+            Value::Null => clauses.push(json!({
+                "bool": { "must_not": { "exists": { "field": field } } }
+            })),
+            value => clauses.push(json!({ "term": { field: value } })),
+        }
+    }
+
+    let mut clauses = vec![];
+    for (name, value) in filter {
+        translate(&format!("{DOCUMENT_FIELD}.{name}"), value, &mut clauses);
+    }
+    clauses
+}
+
+/// Vector store backed by an Elasticsearch (or OpenSearch) index, searched with kNN queries on
+/// a dense vector field and, optionally, BM25 queries on the embedded texts (hybrid search).
+///
+/// Each embedding of a document is stored as a separate Elasticsearch document, with the id of
+/// the document it belongs to (`document_id`), the embedded text (`text`), the embedding
+/// (`embedding`) and the document itself (`document`, whose fields can be filtered on, see
+/// [ElasticsearchVectorStore::with_filter]). Search results are collapsed on `document_id`, so
+/// each document is returned once, with the score of its best matching embedding.
+///
+/// # Example
+/// ```rust,ignore
+/// use rig::vector_store::{VectorStore, VectorStoreIndex};
+/// use rig_elasticsearch::{ElasticsearchClient, ElasticsearchVectorStore};
+///
+/// let client = ElasticsearchClient::new("http://localhost:9200").api_key("your-api-key");
+///
+/// let mut store = ElasticsearchVectorStore::new(client, model, "definitions").hybrid(0.7);
+/// store.create_index().await?;
+///
+/// store
+///     .insert_documents(vec![("doc0".to_string(), serde_json::to_value(&definition)?, embeddings)], true)
+///     .await?;
+///
+/// let results = store.top_n::<Definition>("What does error E1234 mean?", 3).await?;
+/// ```
+pub struct ElasticsearchVectorStore<M: EmbeddingModel> {
+    model: M,
+    client: ElasticsearchClient,
+    index: String,
+    engine: Engine,
+    similarity: Similarity,
+    /// Number of candidates considered by kNN searches (default: 10 times the number of results)
+    num_candidates: Option<usize>,
+    /// Weight of the vector score in hybrid searches (the weight of the BM25 score being 1 minus
+    /// that weight), if hybrid search is enabled
+    vector_weight: Option<f64>,
+    /// Clauses of the filter applied to all searches
+    filter: Vec<Value>,
+}
+
+impl<M: EmbeddingModel> ElasticsearchVectorStore<M> {
+    /// Create a vector store over the index `index`, whose embeddings are generated by `model`.
+    pub fn new(client: ElasticsearchClient, model: M, index: &str) -> Self {
+        Self {
+            model,
+            client,
+            index: index.to_string(),
+            engine: Engine::default(),
+            similarity: Similarity::default(),
+            num_candidates: None,
+            vector_weight: None,
+            filter: vec![],
+        }
+    }
+
+    pub fn client(&self) -> &ElasticsearchClient {
+        &self.client
+    }
+
+    /// Use the syntax of OpenSearch instead of Elasticsearch.
+    pub fn opensearch(mut self) -> Self {
+        self.engine = Engine::OpenSearch;
+        self
+    }
+
+    /// Set the similarity function of the vector field of the index (default: cosine). Only
+    /// used when creating the index or its template.
+    pub fn similarity(mut self, similarity: Similarity) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    /// Set the number of candidates considered by kNN searches. More candidates give more
+    /// accurate results, at the cost of slower searches.
+    pub fn num_candidates(mut self, num_candidates: usize) -> Self {
+        self.num_candidates = Some(num_candidates);
+        self
+    }
+
+    /// Enable hybrid search: documents are ranked by the sum of their vector score weighted by
+    /// `vector_weight` (between 0.0 and 1.0) and their BM25 score on the embedded texts weighted
+    /// by `1.0 - vector_weight`. BM25 scores are not normalized, so the weight usually needs to
+    /// be tuned on the documents of the index.
+    pub fn hybrid(mut self, vector_weight: f64) -> Self {
+        self.vector_weight = Some(vector_weight.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Only search the documents containing `filter` (e.g.: `{"category": "animals"}` only
+    /// matches the documents whose `category` field is `"animals"`, see [filter_clauses]).
+    /// The string fields of the documents are indexed as keywords, so they are matched exactly.
+    pub fn with_filter(mut self, filter: Map<String, Value>) -> Self {
+        self.filter = filter_clauses(&filter);
+        self
+    }
+
+    /// The settings and mappings of the index, as expected by the create index and index
+    /// template APIs.
+    pub fn index_definition(&self) -> Value {
+        let ndims = self.model.ndims();
+        let similarity = self.similarity.as_str(self.engine);
+
+        let (settings, embedding) = match self.engine {
+            Engine::Elasticsearch => (
+                json!({}),
+                json!({
+                    "type": "dense_vector",
+                    "dims": ndims,
+                    "index": true,
+                    "similarity": similarity,
+                }),
+            ),
+            Engine::OpenSearch => (
+                json!({ "index": { "knn": true } }),
+                json!({
+                    "type": "knn_vector",
+                    "dimension": ndims,
+                    "method": {
+                        "name": "hnsw",
+                        "space_type": similarity,
+                        "engine": "lucene",
+                    },
+                }),
+            ),
+        };
+
+        json!({
+            "settings": settings,
+            "mappings": {
+                // The string fields of the documents are matched exactly by filters
+                "dynamic_templates": [{
+                    "document_strings": {
+                        "path_match": format!("{DOCUMENT_FIELD}.*"),
+                        "match_mapping_type": "string",
+                        "mapping": { "type": "keyword" },
+                    }
+                }],
+                "properties": {
+                    DOCUMENT_ID_FIELD: { "type": "keyword" },
+                    TEXT_FIELD: { "type": "text" },
+                    EMBEDDING_FIELD: embedding,
+                    DOCUMENT_FIELD: { "type": "object" },
+                }
+            }
+        })
+    }
+
+    /// Create the index of the vector store, if it does not already exist.
+    pub async fn create_index(&self) -> Result<(), VectorStoreError> {
+        if !self.client.index_exists(&self.index).await? {
+            self.client
+                .send_json(
+                    self.client.request(Method::PUT, &self.index),
+                    &self.index_definition(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Create or replace the index template `name`, applying the settings and mappings of the
+    /// vector store to the indexes created with a name matching `index_patterns` (e.g.:
+    /// `["definitions-*"]` for time-based or per-tenant indexes).
+    pub async fn put_index_template(
+        &self,
+        name: &str,
+        index_patterns: &[&str],
+    ) -> Result<(), VectorStoreError> {
+        let mut template = self.index_definition();
+
+        self.client
+            .send_json(
+                self.client
+                    .request(Method::PUT, &format!("_index_template/{name}")),
+                &json!({
+                    "index_patterns": index_patterns,
+                    "template": {
+                        "settings": template["settings"].take(),
+                        "mappings": template["mappings"].take(),
+                    },
+                }),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The body of a search of the top `n` documents for `query`, whose embedding is `vector`.
+    fn search_body(&self, query: &str, vector: &[f64], n: usize, with_documents: bool) -> Value {
+        let num_candidates = self.num_candidates.unwrap_or(n * 10).clamp(n, 10_000);
+        let vector_weight = self.vector_weight.unwrap_or(1.0);
+
+        let mut body = match self.engine {
+            Engine::Elasticsearch => json!({
+                "knn": {
+                    "field": EMBEDDING_FIELD,
+                    "query_vector": vector,
+                    "k": num_candidates,
+                    "num_candidates": num_candidates,
+                    "boost": vector_weight,
+                },
+            }),
+            Engine::OpenSearch => json!({
+                "query": {
+                    "bool": {
+                        "should": [{
+                            "knn": {
+                                EMBEDDING_FIELD: {
+                                    "vector": vector,
+                                    "k": num_candidates,
+                                    "boost": vector_weight,
+                                },
+                            },
+                        }],
+                    },
+                },
+            }),
+        };
+
+        if self.vector_weight.is_some() {
+            let keyword_query = json!({
+                "match": { TEXT_FIELD: { "query": query, "boost": 1.0 - vector_weight } }
+            });
+            match self.engine {
+                Engine::Elasticsearch => {
+                    body["query"] = json!({ "bool": { "must": [keyword_query] } })
+                }
+                Engine::OpenSearch => body["query"]["bool"]["should"]
+                    .as_array_mut()
+                    .expect("should clause is an array")
+                    .push(keyword_query),
+            }
+        }
+
+        if !self.filter.is_empty() {
+            match self.engine {
+                Engine::Elasticsearch => {
+                    // The kNN search and the keyword query are filtered separately
+                    body["knn"]["filter"] = json!({ "bool": { "filter": self.filter } });
+                    if self.vector_weight.is_some() {
+                        body["query"]["bool"]["filter"] = json!(self.filter);
+                    }
+                }
+                Engine::OpenSearch => {
+                    body["query"]["bool"]["filter"] = json!(self.filter);
+                    // Otherwise, all the documents matching the filter would match the query
+                    body["query"]["bool"]["minimum_should_match"] = json!(1);
+                }
+            }
+        }
+
+        body["size"] = json!(n);
+        body["collapse"] = json!({ "field": DOCUMENT_ID_FIELD });
+        body["_source"] = if with_documents {
+            json!([DOCUMENT_ID_FIELD, DOCUMENT_FIELD])
+        } else {
+            json!([DOCUMENT_ID_FIELD])
+        };
+        body
+    }
+
+    /// Search the top `n` documents for `query`, returning the hits as (score, id, source).
+    async fn search(
+        &self,
+        query: &str,
+        n: usize,
+        with_documents: bool,
+    ) -> Result<Vec<(f64, String, Value)>, VectorStoreError> {
+        let embedding = self.model.embed_text(query).await?;
+
+        let mut response = self
+            .client
+            .send_json(
+                self.client
+                    .request(Method::POST, &format!("{}/_search", self.index)),
+                &self.search_body(query, &embedding.vec, n, with_documents),
+            )
+            .await?;
+
+        match response["hits"]["hits"].take() {
+            Value::Array(hits) => hits.into_iter().map(parse_hit).collect(),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// The ids, among `ids`, of the documents in the index.
+    async fn existing_ids(&self, ids: &[String]) -> Result<Vec<String>, VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut response = self
+            .client
+            .send_json(
+                self.client
+                    .request(Method::POST, &format!("{}/_search", self.index)),
+                &json!({
+                    "size": ids.len(),
+                    "query": { "terms": { DOCUMENT_ID_FIELD: ids } },
+                    "collapse": { "field": DOCUMENT_ID_FIELD },
+                    "_source": [DOCUMENT_ID_FIELD],
+                }),
+            )
+            .await?;
+
+        match response["hits"]["hits"].take() {
+            Value::Array(hits) => hits
+                .into_iter()
+                .map(|hit| parse_hit(hit).map(|(_, id, _)| id))
+                .collect(),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Index the embeddings of `documents`, once the embeddings they replace (if any) are
+    /// deleted. The documents are searchable when this returns.
+    async fn index_documents(
+        &self,
+        documents: Vec<(String, Value, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        let ids = documents
+            .iter()
+            .map(|(id, _, _)| id.clone())
+            .collect::<Vec<_>>();
+        self.delete_embeddings(&ids).await?;
+
+        let body = bulk_body(documents);
+        if body.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .client
+            .send_ndjson(
+                self.client
+                    .request(Method::POST, &format!("{}/_bulk", self.index))
+                    .query(&[("refresh", "wait_for")]),
+                &body,
+            )
+            .await?;
+
+        // The bulk API reports the errors of each operation in the response
+        if response["errors"].as_bool().unwrap_or(false) {
+            let reason = response["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find_map(|item| item["index"]["error"]["reason"].as_str())
+                .unwrap_or("unknown error");
+            return Err(VectorStoreError::DatastoreError(
+                format!("Bulk indexing failed: {reason}").into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Delete the embeddings of the documents with the given ids.
+    async fn delete_embeddings(&self, ids: &[String]) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.client
+            .send_json(
+                self.client
+                    .request(Method::POST, &format!("{}/_delete_by_query", self.index))
+                    .query(&[("refresh", "true")]),
+                &json!({ "query": { "terms": { DOCUMENT_ID_FIELD: ids } } }),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// The lines of the bulk request indexing each embedding of `documents` as a separate
+/// Elasticsearch document, with the id `{document id}:{embedding index}`.
+fn bulk_body(documents: Vec<(String, Value, OneOrMany<Embedding>)>) -> Vec<Value> {
+    let mut body = vec![];
+    for (id, document, embeddings) in documents {
+        for (i, embedding) in embeddings.into_iter().enumerate() {
+            body.push(json!({ "index": { "_id": format!("{id}:{i}") } }));
+            body.push(json!({
+                DOCUMENT_ID_FIELD: id,
+                TEXT_FIELD: embedding.document,
+                EMBEDDING_FIELD: embedding.vec,
+                DOCUMENT_FIELD: document,
+            }));
+        }
+    }
+    body
+}
+
+/// Converts a search hit to a tuple of its score, document id and document.
+fn parse_hit(mut hit: Value) -> Result<(f64, String, Value), VectorStoreError> {
+    let score = hit["_score"].as_f64().unwrap_or_default();
+    let id = match hit["_source"][DOCUMENT_ID_FIELD].take() {
+        Value::String(id) => id,
+        _ => {
+            return Err(VectorStoreError::DatastoreError(
+                "Missing document id".into(),
+            ))
+        }
+    };
+    Ok((score, id, hit["_source"][DOCUMENT_FIELD].take()))
+}
+
+impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for ElasticsearchVectorStore<M> {
+    /// Search for the top `n` documents for the given query, with a kNN search (or a hybrid
+    /// search, if enabled). Returns a vector of tuples containing the score, ID and document of
+    /// the results.
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, true)
+            .await?
+            .into_iter()
+            .map(|(score, id, document)| {
+                let document = serde_json::from_value(document).map_err(|source| {
+                    VectorStoreError::DeserializationError {
+                        id: id.clone(),
+                        source,
+                    }
+                })?;
+                Ok((score, id, document))
+            })
+            .collect()
+    }
+
+    /// Same as `top_n`, but only returns the score and ID of the results.
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        Ok(self
+            .search(query, n, false)
+            .await?
+            .into_iter()
+            .map(|(score, id, _)| (score, id))
+            .collect())
+    }
+}
+
+/// Documents are JSON values (e.g.: from [serde_json::to_value]) with their id and embeddings.
+/// Modifications are visible to searches once they return.
+impl<M: EmbeddingModel + Sync + Send> VectorStore for ElasticsearchVectorStore<M> {
+    type Document = (String, Value, OneOrMany<Embedding>);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        if !upsert {
+            let ids = documents
+                .iter()
+                .map(|(id, _, _)| id.clone())
+                .collect::<Vec<_>>();
+            if let Some(id) = self.existing_ids(&ids).await?.into_iter().next() {
+                return Err(VectorStoreError::DuplicateIdError(id));
+            }
+        }
+
+        self.index_documents(documents).await
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        if self
+            .existing_ids(std::slice::from_ref(&document.0))
+            .await?
+            .is_empty()
+        {
+            return Err(VectorStoreError::MissingIdError(document.0));
+        }
+
+        self.index_documents(vec![document]).await
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        self.delete_embeddings(ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::providers::mock::MockEmbeddingModel;
+
+    use super::*;
+
+    fn store() -> ElasticsearchVectorStore<MockEmbeddingModel> {
+        ElasticsearchVectorStore::new(
+            ElasticsearchClient::new("http://localhost:9200/"),
+            MockEmbeddingModel::new(3),
+            "index",
+        )
+    }
+
+    fn filter(filter: Value) -> Map<String, Value> {
+        filter.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_knn_search_body() {
+        assert_eq!(
+            store().search_body("query", &[0.1, 0.2, 0.3], 5, true),
+            json!({
+                "knn": {
+                    "field": "embedding",
+                    "query_vector": [0.1, 0.2, 0.3],
+                    "k": 50,
+                    "num_candidates": 50,
+                    "boost": 1.0,
+                },
+                "size": 5,
+                "collapse": { "field": "document_id" },
+                "_source": ["document_id", "document"],
+            })
+        );
+
+        // The number of candidates is at least the number of results
+        let body = store()
+            .num_candidates(2)
+            .search_body("query", &[0.1], 5, false);
+        assert_eq!(body["knn"]["num_candidates"], 5);
+        assert_eq!(body["_source"], json!(["document_id"]));
+    }
+
+    #[test]
+    fn test_hybrid_search_body() {
+        let body = store()
+            .hybrid(0.75)
+            .with_filter(filter(json!({ "category": "animals" })))
+            .search_body("green alien", &[0.1], 1, true);
+
+        let term = json!([{ "term": { "document.category": "animals" } }]);
+        assert_eq!(body["knn"]["boost"], 0.75);
+        assert_eq!(body["knn"]["filter"], json!({ "bool": { "filter": term } }));
+        assert_eq!(
+            body["query"],
+            json!({
+                "bool": {
+                    "must": [{ "match": { "text": { "query": "green alien", "boost": 0.25 } } }],
+                    "filter": term,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_opensearch_search_body() {
+        let body = store()
+            .opensearch()
+            .hybrid(0.5)
+            .with_filter(filter(json!({ "category": "animals" })))
+            .search_body("green alien", &[0.1], 2, true);
+
+        assert_eq!(
+            body["query"],
+            json!({
+                "bool": {
+                    "should": [
+                        { "knn": { "embedding": { "vector": [0.1], "k": 20, "boost": 0.5 } } },
+                        { "match": { "text": { "query": "green alien", "boost": 0.5 } } },
+                    ],
+                    "filter": [{ "term": { "document.category": "animals" } }],
+                    "minimum_should_match": 1,
+                }
+            })
+        );
+        assert!(body.get("knn").is_none());
+    }
+
+    #[test]
+    fn test_filter_clauses() {
+        assert_eq!(
+            filter_clauses(&filter(json!({
+                "category": "animals",
+                "author": { "name": "Ann", "age": 42 },
+                "tags": ["green", "alien"],
+                "deleted": null,
+            }))),
+            vec![
+                json!({ "term": { "document.author.age": 42 } }),
+                json!({ "term": { "document.author.name": "Ann" } }),
+                json!({ "term": { "document.category": "animals" } }),
+                json!({ "bool": { "must_not": { "exists": { "field": "document.deleted" } } } }),
+                json!({ "term": { "document.tags": "green" } }),
+                json!({ "term": { "document.tags": "alien" } }),
+            ]
+        );
+        assert!(filter_clauses(&Map::new()).is_empty());
+    }
+
+    #[test]
+    fn test_index_definition() {
+        let definition = store().index_definition();
+        assert_eq!(
+            definition["mappings"]["properties"]["embedding"],
+            json!({ "type": "dense_vector", "dims": 3, "index": true, "similarity": "cosine" })
+        );
+        assert_eq!(
+            definition["mappings"]["dynamic_templates"][0]["document_strings"]["path_match"],
+            "document.*"
+        );
+
+        let definition = store()
+            .opensearch()
+            .similarity(Similarity::L2Norm)
+            .index_definition();
+        assert_eq!(definition["settings"], json!({ "index": { "knn": true } }));
+        assert_eq!(
+            definition["mappings"]["properties"]["embedding"]["method"]["space_type"],
+            "l2"
+        );
+    }
+
+    #[test]
+    fn test_bulk_body_and_hits() {
+        let embedding = |text: &str| Embedding {
+            document: text.to_string(),
+            vec: vec![0.5],
+        };
+        let body = bulk_body(vec![(
+            "doc0".to_string(),
+            json!({ "word": "flurbo" }),
+            OneOrMany::many(vec![embedding("a"), embedding("b")]).unwrap(),
+        )]);
+
+        assert_eq!(body.len(), 4);
+        assert_eq!(body[0], json!({ "index": { "_id": "doc0:0" } }));
+        assert_eq!(body[2], json!({ "index": { "_id": "doc0:1" } }));
+        assert_eq!(
+            body[3],
+            json!({
+                "document_id": "doc0",
+                "text": "b",
+                "embedding": [0.5],
+                "document": { "word": "flurbo" },
+            })
+        );
+
+        let hit = json!({ "_score": 0.9, "_source": body[1] });
+        assert_eq!(
+            parse_hit(hit).unwrap(),
+            (0.9, "doc0".to_string(), json!({ "word": "flurbo" }))
+        );
+        assert!(parse_hit(json!({ "_score": 0.9, "_source": {} })).is_err());
+    }
+
+    #[test]
+    fn test_client_base_url() {
+        let request = store()
+            .client()
+            .request(Method::POST, "index/_search")
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:9200/index/_search"
+        );
+    }
+}
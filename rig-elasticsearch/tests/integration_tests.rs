@@ -0,0 +1,141 @@
+use serde_json::json;
+use testcontainers::{
+    core::{IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+    GenericImage, ImageExt,
+};
+
+use rig::{
+    embeddings::EmbeddingModel,
+    providers::mock::MockEmbeddingModel,
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex},
+    OneOrMany,
+};
+use rig_elasticsearch::{ElasticsearchClient, ElasticsearchVectorStore};
+
+const ELASTICSEARCH_PORT: u16 = 9200;
+const INDEX_NAME: &str = "rig-index";
+
+#[derive(serde::Deserialize, Debug)]
+struct Word {
+    word: String,
+}
+
+async fn document(
+    model: &MockEmbeddingModel,
+    id: &str,
+    definition: &str,
+) -> (
+    String,
+    serde_json::Value,
+    OneOrMany<rig::embeddings::Embedding>,
+) {
+    (
+        id.to_string(),
+        json!({ "word": id }),
+        OneOrMany::one(model.embed_text(definition).await.unwrap()),
+    )
+}
+
+#[tokio::test]
+async fn vector_search_test() {
+    // Setup a local elasticsearch container for testing. NOTE: docker service must be running.
+    let container = GenericImage::new("docker.elastic.co/elasticsearch/elasticsearch", "8.17.0")
+        .with_wait_for(WaitFor::message_on_stdout("started"))
+        .with_exposed_port(ELASTICSEARCH_PORT.tcp())
+        .with_env_var("discovery.type", "single-node")
+        .with_env_var("xpack.security.enabled", "false")
+        .with_env_var("ES_JAVA_OPTS", "-Xms512m -Xmx512m")
+        .start()
+        .await
+        .expect("Failed to start elasticsearch container");
+
+    let port = container
+        .get_host_port_ipv4(ELASTICSEARCH_PORT)
+        .await
+        .unwrap();
+    let host = container.get_host().await.unwrap().to_string();
+
+    let client = ElasticsearchClient::new(&format!("http://{host}:{port}"));
+
+    let model = MockEmbeddingModel::new(16);
+    let mut store = ElasticsearchVectorStore::new(client, model.clone(), INDEX_NAME);
+    store.create_index().await.expect("Failed to create index");
+    // Creating an existing index is a no-op
+    store.create_index().await.unwrap();
+
+    store
+        .insert_documents(
+            vec![
+                document(&model, "flurbo", "a green alien that lives on cold planets").await,
+                document(
+                    &model,
+                    "glarb-glarb",
+                    "an ancient tool used to farm the land",
+                )
+                .await,
+                document(&model, "linglingdong", "a term used to describe humans").await,
+            ],
+            false,
+        )
+        .await
+        .expect("Failed to insert documents");
+
+    let results = store
+        .top_n::<Word>("a green alien that lives on cold planets", 1)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].1, "flurbo");
+    assert_eq!(results[0].2.word, "flurbo");
+
+    // Documents are returned once, however many embeddings they have
+    let ids = store.top_n_ids("an ancient tool", 10).await.unwrap();
+    assert_eq!(ids.len(), 3);
+
+    // Hybrid search also ranks the documents whose texts contain the keywords of the query
+    let hybrid = ElasticsearchVectorStore::new(store.client().clone(), model.clone(), INDEX_NAME)
+        .hybrid(0.5);
+    let ids = hybrid.top_n_ids("farm the land", 1).await.unwrap();
+    assert_eq!(ids[0].1, "glarb-glarb");
+
+    // Filtered searches only return the documents containing the filter
+    let filtered = ElasticsearchVectorStore::new(store.client().clone(), model.clone(), INDEX_NAME)
+        .with_filter(
+            json!({ "word": "linglingdong" })
+                .as_object()
+                .unwrap()
+                .clone(),
+        );
+    let ids = filtered.top_n_ids("an ancient tool", 10).await.unwrap();
+    assert_eq!(ids.len(), 1);
+    assert_eq!(ids[0].1, "linglingdong");
+
+    assert!(matches!(
+        store
+            .insert_documents(vec![document(&model, "flurbo", "a planet").await], false)
+            .await,
+        Err(VectorStoreError::DuplicateIdError(id)) if id == "flurbo"
+    ));
+
+    store
+        .update_document(document(&model, "flurbo", "a shiny spaceship").await)
+        .await
+        .unwrap();
+    let results = store.top_n_ids("a shiny spaceship", 10).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].1, "flurbo");
+
+    assert!(matches!(
+        store
+            .update_document(document(&model, "zorp", "a planet").await)
+            .await,
+        Err(VectorStoreError::MissingIdError(_))
+    ));
+
+    store
+        .delete_documents(&["flurbo".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(store.top_n_ids("a planet", 10).await.unwrap().len(), 2);
+}
@@ -32,8 +32,8 @@ impl Client {
     /// ```
     /// use rig_fastembed::{Client, FastembedModel};
     ///
-    /// // Initialize the OpenAI client
-    /// let fastembed_client = Client::new("your-open-ai-api-key");
+    /// // Initialize the Fastembed client
+    /// let fastembed_client = Client::new();
     ///
     /// let embedding_model = fastembed_client.embedding_model(&FastembedModel::AllMiniLML6V2Q);
     /// ```
@@ -52,7 +52,7 @@ impl Client {
     /// // Initialize the Fastembed client
     /// let fastembed_client = Client::new();
     ///
-    /// let embeddings = fastembed_client.embeddings(FastembedModel::AllMiniLML6V2Q)
+    /// let embeddings = fastembed_client.embeddings(&FastembedModel::AllMiniLML6V2Q)
     ///     .simple_document("doc0", "Hello, world!")
     ///     .simple_document("doc1", "Goodbye, world!")
     ///     .build()
@@ -75,39 +75,57 @@ pub struct EmbeddingModel {
 }
 
 impl EmbeddingModel {
+    /// Load the given model, downloading its ONNX files on first use.
+    /// Panics if the model cannot be loaded (see [EmbeddingModel::try_new]).
     pub fn new(model: &fastembed::EmbeddingModel, ndims: usize) -> Self {
-        let embedder = Arc::new(
-            TextEmbedding::try_new(
-                InitOptions::new(model.to_owned()).with_show_download_progress(true),
-            )
-            .unwrap(),
-        );
-
-        Self {
-            embedder,
+        Self::try_new(model, ndims).expect("Fastembed model should load")
+    }
+
+    /// Load the given model, downloading its ONNX files on first use.
+    pub fn try_new(
+        model: &fastembed::EmbeddingModel,
+        ndims: usize,
+    ) -> Result<Self, EmbeddingError> {
+        let embedder = TextEmbedding::try_new(
+            InitOptions::new(model.to_owned()).with_show_download_progress(true),
+        )
+        .map_err(|err| EmbeddingError::ProviderError(err.to_string()))?;
+
+        Ok(Self {
+            embedder: Arc::new(embedder),
             model: model.to_owned(),
             ndims,
-        }
+        })
     }
 
+    /// Load a model from local ONNX and tokenizer files, e.g.: to run fully offline.
+    /// Panics if the model cannot be loaded (see [EmbeddingModel::try_new_from_user_defined]).
     pub fn new_from_user_defined(
         user_defined_model: UserDefinedEmbeddingModel,
         ndims: usize,
         model_info: &ModelInfo<FastembedModel>,
     ) -> Self {
-        let fastembed_embedding_model = TextEmbedding::try_new_from_user_defined(
+        Self::try_new_from_user_defined(user_defined_model, ndims, model_info)
+            .expect("User defined Fastembed model should load")
+    }
+
+    /// Load a model from local ONNX and tokenizer files, e.g.: to run fully offline.
+    pub fn try_new_from_user_defined(
+        user_defined_model: UserDefinedEmbeddingModel,
+        ndims: usize,
+        model_info: &ModelInfo<FastembedModel>,
+    ) -> Result<Self, EmbeddingError> {
+        let embedder = TextEmbedding::try_new_from_user_defined(
             user_defined_model,
             InitOptionsUserDefined::default(),
         )
-        .unwrap();
-
-        let embedder = Arc::new(fastembed_embedding_model);
+        .map_err(|err| EmbeddingError::ProviderError(err.to_string()))?;
 
-        Self {
-            embedder,
+        Ok(Self {
+            embedder: Arc::new(embedder),
             model: model_info.model.to_owned(),
             ndims,
-        }
+        })
     }
 }
 
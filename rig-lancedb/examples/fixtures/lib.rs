@@ -35,14 +35,7 @@ pub fn schema(dims: usize) -> Schema {
     Schema::new(Fields::from(vec![
         Field::new("id", DataType::Utf8, false),
         Field::new("definition", DataType::Utf8, false),
-        Field::new(
-            "embedding",
-            DataType::FixedSizeList(
-                Arc::new(Field::new("item", DataType::Float64, true)),
-                dims as i32,
-            ),
-            false,
-        ),
+        rig_lancedb::embedding_field("embedding", dims),
     ]))
 }
 
@@ -1,14 +1,12 @@
 use std::sync::Arc;
 
-use arrow_array::RecordBatchIterator;
 use fixture::{as_record_batch, schema, words, Word};
-use lancedb::index::vector::IvfPqIndexBuilder;
 use rig::{
     embeddings::{EmbeddingModel, EmbeddingsBuilder},
     providers::openai::{Client, TEXT_EMBEDDING_ADA_002},
-    vector_store::VectorStoreIndex,
+    vector_store::{VectorStore, VectorStoreIndex},
 };
-use rig_lancedb::{LanceDbVectorIndex, SearchParams};
+use rig_lancedb::{IndexPolicy, LanceDbVectorIndex, SearchParams};
 
 #[path = "./fixtures/lib.rs"]
 mod fixture;
@@ -46,31 +44,24 @@ async fn main() -> Result<(), anyhow::Error> {
     {
         db.open_table("definitions").execute().await?
     } else {
-        db.create_table(
-            "definitions",
-            RecordBatchIterator::new(
-                vec![as_record_batch(embeddings, model.ndims())],
-                Arc::new(schema(model.ndims())),
-            ),
-        )
-        .execute()
-        .await?
-    };
-
-    // See [LanceDB indexing](https://lancedb.github.io/lancedb/concepts/index_ivfpq/#product-quantization) for more information
-    if table.index_stats("embedding").await?.is_none() {
-        table
-            .create_index(
-                &["embedding"],
-                lancedb::index::Index::IvfPq(IvfPqIndexBuilder::default()),
-            )
+        db.create_empty_table("definitions", Arc::new(schema(model.ndims())))
             .execute()
-            .await?;
-    }
+            .await?
+    };
 
     // Define search_params params that will be used by the vector store to perform the vector search.
     let search_params = SearchParams::default();
-    let vector_store_index = LanceDbVectorIndex::new(table, model, "id", search_params).await?;
+
+    // The IVF_PQ index is created as soon as the table has enough rows to train it, and optimized
+    // every 10 writes.
+    // See [LanceDB indexing](https://lancedb.github.io/lancedb/concepts/index_ivfpq/#product-quantization) for more information
+    let mut vector_store_index = LanceDbVectorIndex::new(table, model.clone(), "id", search_params)
+        .await?
+        .index_policy(IndexPolicy::ivf_pq("embedding").optimize_after(10));
+
+    vector_store_index
+        .insert_documents(vec![as_record_batch(embeddings, model.ndims())?], true)
+        .await?;
 
     // Query the index
     let results = vector_store_index
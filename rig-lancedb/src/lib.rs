@@ -1,14 +1,31 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use arrow_array::{RecordBatch, RecordBatchIterator};
+use futures::{stream, Stream, TryStreamExt};
 use lancedb::{
+    arrow::arrow_schema::{DataType, Field, Schema, SchemaRef},
+    index::{scalar::FullTextSearchQuery, vector::IvfPqIndexBuilder, Index, IndexType},
     query::{QueryBase, VectorQuery},
+    rerankers::rrf::RRFReranker,
+    table::OptimizeAction,
     DistanceType,
 };
 use rig::{
     embeddings::embedding::EmbeddingModel,
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{
+        filter::Filter, schema::EmbeddingSchema, VectorStore, VectorStoreCollections,
+        VectorStoreError, VectorStoreIndex, VectorStoreStats,
+    },
 };
 use serde::Deserialize;
 use serde_json::Value;
-use utils::{FilterTableColumns, QueryToJson};
+use utils::{FilterTableColumns, QueryToJson, RecordBatchDeserializer};
 
 mod utils;
 
@@ -20,7 +37,58 @@ fn serde_to_rig_error(e: serde_json::Error) -> VectorStoreError {
     VectorStoreError::JsonError(e)
 }
 
+/// Field of the embeddings of a table: fixed size lists of `ndims` 64-bit floats. Use the number
+/// of dimensions of the embedding model (e.g.: `embedding_field("embedding", model.ndims())`),
+/// which follows the shortened size of OpenAI's `text-embedding-3` models.
+pub fn embedding_field(name: &str, ndims: usize) -> Field {
+    Field::new(
+        name,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float64, true)),
+            ndims as i32,
+        ),
+        false,
+    )
+}
+
+/// Check that the embedding column of `schema` has the number of dimensions of `model` (models
+/// reporting 0 dimensions are not checked), and was embedded with `model` if its metadata
+/// records an [EmbeddingSchema]. The embedding column is `column` if set (see
+/// [SearchParams::column]), or else the fixed size lists of floats (LanceDB searches the only
+/// one); other columns are not checked.
+fn check_schema<M: EmbeddingModel>(
+    schema: &Schema,
+    model: &M,
+    column: Option<&str>,
+) -> Result<(), VectorStoreError> {
+    let ndims = model.ndims();
+    let embedding_columns = schema.fields().iter().filter(|field| match column {
+        Some(column) => field.name() == column,
+        None => matches!(
+            field.data_type(),
+            DataType::FixedSizeList(item, _) if item.data_type().is_floating()
+        ),
+    });
+    for field in embedding_columns {
+        if let DataType::FixedSizeList(_, size) = field.data_type() {
+            if ndims > 0 && *size as usize != ndims {
+                return Err(VectorStoreError::DimensionMismatch {
+                    expected: ndims,
+                    found: *size as usize,
+                });
+            }
+            if let Some(embedding_schema) = EmbeddingSchema::from_metadata(field.metadata()) {
+                embedding_schema.check(model)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Type on which vector searches can be performed for a lanceDb table.
+///
+/// The embedding column of the table is checked before the first search (see
+/// [LanceDbVectorIndex::check_embedding_schema]): searches fail if it does not match the model.
 /// # Example
 /// ```
 /// use rig_lancedb::{LanceDbVectorIndex, SearchParams};
@@ -41,6 +109,18 @@ pub struct LanceDbVectorIndex<M: EmbeddingModel> {
     id_field: String,
     /// Vector search params that are used during vector search operations.
     search_params: SearchParams,
+    /// Policy of the automatic management of the vector index, if enabled.
+    index_policy: Option<IndexPolicy>,
+    /// Number of writes to the table since its vector index was created or optimized.
+    writes_since_optimize: AtomicUsize,
+    /// Whether the table is known to have a vector index on the column of the index policy.
+    has_vector_index: AtomicBool,
+    /// Whether the indices of the table were listed since the last index creation.
+    indices_listed: AtomicBool,
+    /// Upper bound of the number of rows of the table (`usize::MAX` until they are counted).
+    max_rows: AtomicUsize,
+    /// Whether the embedding column of the table was checked against the model.
+    schema_checked: AtomicBool,
 }
 
 impl<M: EmbeddingModel> LanceDbVectorIndex<M> {
@@ -58,12 +138,342 @@ impl<M: EmbeddingModel> LanceDbVectorIndex<M> {
             model,
             id_field: id_field.to_string(),
             search_params,
+            index_policy: None,
+            writes_since_optimize: AtomicUsize::new(0),
+            has_vector_index: AtomicBool::new(false),
+            indices_listed: AtomicBool::new(false),
+            max_rows: AtomicUsize::new(usize::MAX),
+            schema_checked: AtomicBool::new(false),
         })
     }
 
-    /// Apply the search_params to the vector query.
+    /// Manage the vector index of the table automatically according to `policy`, instead of
+    /// calling [LanceDbVectorIndex::create_index] once the table has enough rows: after the
+    /// writes of [VectorStore] methods and of [LanceDbVectorIndex::ingest], the index is
+    /// created as soon as the table has enough rows, then optimized periodically.
+    /// # Example
+    /// ```
+    /// use rig_lancedb::{IndexPolicy, LanceDbVectorIndex, SearchParams};
+    ///
+    /// let vector_store_index = LanceDbVectorIndex::new(table, model, "id", SearchParams::default())
+    ///     .await?
+    ///     .index_policy(IndexPolicy::ivf_pq("embedding").optimize_after(100));
+    /// ```
+    pub fn index_policy(mut self, policy: IndexPolicy) -> Self {
+        self.index_policy = Some(policy);
+        self
+    }
+
+    /// Check that the embeddings of the table have the number of dimensions of the model, and
+    /// were embedded with the model if the table records an [EmbeddingSchema] (see
+    /// [LanceDbVectorStore::document_schema]). Searching a table embedded with another model
+    /// returns meaningless results, so searches run this check first.
+    pub async fn check_embedding_schema(&self) -> Result<(), VectorStoreError> {
+        check_schema(
+            &self.table.schema().await.map_err(lancedb_to_rig_error)?,
+            &self.model,
+            self.search_params.column.as_deref(),
+        )
+    }
+
+    /// Check the embedding schema of the table, once (see
+    /// [LanceDbVectorIndex::check_embedding_schema]).
+    async fn ensure_embedding_schema(&self) -> Result<(), VectorStoreError> {
+        if !self.schema_checked.load(Ordering::Relaxed) {
+            self.check_embedding_schema().await?;
+            self.schema_checked.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Apply the index policy (if any) after `writes` writes to the table which added at most
+    /// `rows` rows: create the vector index if the table has enough rows, or optimize it if enough
+    /// writes were made since it was created or last optimized.
+    ///
+    /// The writes already succeeded, so failures to manage the index are logged instead of
+    /// returned: they are retried after the next writes.
+    async fn apply_index_policy(&self, writes: usize, rows: usize) {
+        let Some(policy) = &self.index_policy else {
+            return;
+        };
+        let writes = self
+            .writes_since_optimize
+            .fetch_add(writes, Ordering::Relaxed)
+            + writes;
+        let max_rows = self
+            .max_rows
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |max_rows| {
+                Some(max_rows.saturating_add(rows))
+            })
+            .unwrap_or_default()
+            .saturating_add(rows);
+
+        if let Err(e) = self.manage_index(policy, writes, max_rows).await {
+            tracing::warn!(target: "rig",
+                "Failed to manage the vector index of column {} of LanceDB table {}: {}",
+                policy.column,
+                self.table.name(),
+                e
+            );
+        }
+    }
+
+    /// Create or optimize the vector index according to `policy` (see
+    /// [LanceDbVectorIndex::apply_index_policy]). The table is only listed and counted while it
+    /// has no index and may have reached the minimum number of rows of the policy.
+    async fn manage_index(
+        &self,
+        policy: &IndexPolicy,
+        writes: usize,
+        max_rows: usize,
+    ) -> Result<(), VectorStoreError> {
+        if !self.has_vector_index(&policy.column).await? {
+            if max_rows < policy.min_rows {
+                return Ok(());
+            }
+
+            let row_count = self
+                .table
+                .count_rows(None)
+                .await
+                .map_err(lancedb_to_rig_error)?;
+            self.max_rows.store(row_count, Ordering::Relaxed);
+
+            if row_count >= policy.min_rows {
+                self.table
+                    .create_index(&[policy.column.as_str()], policy.index())
+                    .execute()
+                    .await
+                    .map_err(lancedb_to_rig_error)?;
+                self.has_vector_index.store(true, Ordering::Relaxed);
+                self.writes_since_optimize.store(0, Ordering::Relaxed);
+            }
+        } else if policy
+            .optimize_after
+            .is_some_and(|optimize_after| writes >= optimize_after)
+        {
+            // Compacts the fragments of the table, prunes old versions and indexes new rows
+            self.table
+                .optimize(OptimizeAction::All)
+                .await
+                .map_err(lancedb_to_rig_error)?;
+            self.writes_since_optimize.store(0, Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the table has a vector index on `column`. The indices of the table are only
+    /// listed once, and again after [LanceDbVectorIndex::create_index].
+    async fn has_vector_index(&self, column: &str) -> Result<bool, VectorStoreError> {
+        if self.indices_listed.load(Ordering::Relaxed) {
+            return Ok(self.has_vector_index.load(Ordering::Relaxed));
+        }
+
+        let has_vector_index = self
+            .table
+            .list_indices()
+            .await
+            .map_err(lancedb_to_rig_error)?
+            .into_iter()
+            .any(|index| {
+                is_vector_index_type(&index.index_type)
+                    && index.columns.iter().any(|indexed| indexed == column)
+            });
+        self.has_vector_index
+            .store(has_vector_index, Ordering::Relaxed);
+        self.indices_listed.store(true, Ordering::Relaxed);
+
+        Ok(has_vector_index)
+    }
+
+    /// Create an index on the given column of the table.
+    ///
+    /// LanceDB needs at least [MIN_ROWS_FOR_ANN_INDEX] rows to train an ANN (IVF based) index.
+    /// If the table is too small, the index is not built and [IndexStatus::IndexNotBuilt] is returned
+    /// instead of an error. Vector searches still work without an ANN index: LanceDB falls back to a
+    /// flat (brute-force) search.
+    /// # Example
+    /// ```
+    /// use rig_lancedb::IndexStatus;
+    ///
+    /// let status = vector_store_index
+    ///     .create_index("embedding", lancedb::index::Index::Auto)
+    ///     .await?;
+    ///
+    /// if let IndexStatus::IndexNotBuilt { row_count } = status {
+    ///     println!("Not enough rows ({row_count}) to build an index, using flat search");
+    /// }
+    /// ```
+    pub async fn create_index(
+        &self,
+        column: &str,
+        index: Index,
+    ) -> Result<IndexStatus, VectorStoreError> {
+        if is_ann_index(&index) {
+            let row_count = self
+                .table
+                .count_rows(None)
+                .await
+                .map_err(lancedb_to_rig_error)?;
+
+            if row_count < MIN_ROWS_FOR_ANN_INDEX {
+                return Ok(IndexStatus::IndexNotBuilt { row_count });
+            }
+        }
+
+        self.table
+            .create_index(&[column], index)
+            .execute()
+            .await
+            .map_err(lancedb_to_rig_error)?;
+        self.indices_listed.store(false, Ordering::Relaxed);
+
+        Ok(IndexStatus::Built)
+    }
+
+    /// Ids of the records of the given record batches.
+    fn record_ids(&self, records: &[RecordBatch]) -> Result<Vec<String>, VectorStoreError> {
+        Ok(records
+            .to_vec()
+            .deserialize()?
+            .iter()
+            .filter_map(|record| record_id(record, &self.id_field))
+            .collect())
+    }
+
+    /// The ids among `ids` of the records in the table.
+    async fn existing_ids(&self, ids: &[String]) -> Result<Vec<String>, VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(self
+            .table
+            .query()
+            .only_if(Filter::one_of(&self.id_field, ids.to_vec()).to_sql()?)
+            .select(lancedb::query::Select::Columns(vec![self.id_field.clone()]))
+            .execute_query()
+            .await?
+            .iter()
+            .filter_map(|record| record_id(record, &self.id_field))
+            .collect())
+    }
+
+    /// Write record batches to the table in chunks of at most `options.batch_size` rows, each
+    /// chunk being a separate write (commit) to the table. Batches are consumed from `batches` as
+    /// they are written, so ingests of millions of documents (e.g.: embedded page by page) don't
+    /// need to fit in memory nor in a single, long-running write. Large batches are split and
+    /// small ones grouped into chunks.
+    ///
+    /// The progress is reported after each chunk (see [IngestOptions::on_progress]), and the
+    /// final progress is returned. If a write fails, the chunks written before it stay in the
+    /// table: the reported progress tells where to resume, and [IngestMode::Upsert] makes
+    /// retries idempotent.
+    ///
+    /// Unlike [VectorStore::insert_documents], [IngestMode::Append] does not check whether the
+    /// ids of the records are already in the table.
+    /// # Example
+    /// ```
+    /// use futures::{stream, StreamExt};
+    /// use rig_lancedb::{IngestMode, IngestOptions};
+    ///
+    /// // Embed and write the documents page by page
+    /// let batches = stream::iter(documents.chunks(1000)).then(|page| async {
+    ///     let embeddings = EmbeddingsBuilder::new(model.clone())
+    ///         .documents(page.to_vec())?
+    ///         .build()
+    ///         .await?;
+    ///     Ok(as_record_batch(embeddings, model.ndims())?)
+    /// });
+    ///
+    /// let progress = vector_store_index
+    ///     .ingest(
+    ///         batches,
+    ///         IngestOptions::default()
+    ///             .batch_size(5000)
+    ///             .mode(IngestMode::Upsert)
+    ///             .on_progress(|progress| println!("{} rows written", progress.rows_written)),
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn ingest(
+        &self,
+        batches: impl Stream<Item = Result<RecordBatch, VectorStoreError>> + Send,
+        options: IngestOptions,
+    ) -> Result<IngestProgress, VectorStoreError> {
+        let mut batches = std::pin::pin!(batches);
+        let mut progress = IngestProgress::default();
+        let mut chunk = vec![];
+        let mut chunk_rows = 0;
+
+        while let Some(batch) = batches.try_next().await? {
+            let mut offset = 0;
+            while offset < batch.num_rows() {
+                let len = (options.batch_size - chunk_rows).min(batch.num_rows() - offset);
+                chunk.push(batch.slice(offset, len));
+                chunk_rows += len;
+                offset += len;
+
+                if chunk_rows == options.batch_size {
+                    self.write_chunk(std::mem::take(&mut chunk), options.mode)
+                        .await?;
+                    progress = options.report(progress, chunk_rows);
+                    chunk_rows = 0;
+                }
+            }
+        }
+
+        if chunk_rows > 0 {
+            self.write_chunk(chunk, options.mode).await?;
+            progress = options.report(progress, chunk_rows);
+        }
+
+        self.apply_index_policy(progress.batches_written, progress.rows_written)
+            .await;
+
+        Ok(progress)
+    }
+
+    /// Write record batches to the table in a single write.
+    async fn write_chunk(
+        &self,
+        chunk: Vec<RecordBatch>,
+        mode: IngestMode,
+    ) -> Result<(), VectorStoreError> {
+        let Some(schema) = chunk.first().map(RecordBatch::schema) else {
+            return Ok(());
+        };
+        let records = RecordBatchIterator::new(chunk.into_iter().map(Ok), schema);
+
+        match mode {
+            IngestMode::Append => self
+                .table
+                .add(records)
+                .execute()
+                .await
+                .map_err(lancedb_to_rig_error),
+            IngestMode::Upsert => {
+                let mut merge_insert = self.table.merge_insert(&[self.id_field.as_str()]);
+                merge_insert
+                    .when_matched_update_all(None)
+                    .when_not_matched_insert_all();
+                merge_insert
+                    .execute(Box::new(records))
+                    .await
+                    .map_err(lancedb_to_rig_error)
+            }
+        }
+    }
+
+    /// Apply the search_params and the optional `filter` to the vector query of the text `text`.
     /// This is a helper function used by the methods `top_n` and `top_n_ids` of the `VectorStoreIndex` trait.
-    fn build_query(&self, mut query: VectorQuery) -> VectorQuery {
+    fn build_query(
+        &self,
+        mut query: VectorQuery,
+        text: &str,
+        filter: Option<&Filter>,
+    ) -> Result<VectorQuery, VectorStoreError> {
         let SearchParams {
             distance_type,
             search_type,
@@ -71,8 +481,22 @@ impl<M: EmbeddingModel> LanceDbVectorIndex<M> {
             refine_factor,
             post_filter,
             column,
+            full_text_search,
+            rrf_k,
+            where_clause,
+            ..
         } = self.search_params.clone();
 
+        // The filter of the query and the where clause of the search params must both match
+        let filter = filter.map(Filter::to_sql).transpose()?;
+        match (where_clause, filter) {
+            (Some(where_clause), Some(filter)) => {
+                query = query.only_if(format!("({where_clause}) AND ({filter})"))
+            }
+            (Some(condition), None) | (None, Some(condition)) => query = query.only_if(condition),
+            (None, None) => (),
+        }
+
         if let Some(distance_type) = distance_type {
             query = query.distance_type(distance_type);
         }
@@ -81,7 +505,9 @@ impl<M: EmbeddingModel> LanceDbVectorIndex<M> {
             query = query.bypass_vector_index();
         }
 
-        if let Some(SearchType::Approximate) = search_type {
+        // ANN parameters apply whenever the vector index may be used (they are ignored by
+        // LanceDB if the table has no index)
+        if !matches!(search_type, Some(SearchType::Flat)) {
             if let Some(nprobes) = nprobes {
                 query = query.nprobes(nprobes);
             }
@@ -98,7 +524,564 @@ impl<M: EmbeddingModel> LanceDbVectorIndex<M> {
             query = query.column(column.as_str())
         }
 
-        query
+        if let Some(columns) = full_text_search {
+            query = query
+                .full_text_search(FullTextSearchQuery::new(text.to_string()).columns(Some(columns)))
+                .rerank(Arc::new(rrf_k.map(RRFReranker::new).unwrap_or_default()));
+        }
+
+        Ok(query)
+    }
+
+    /// Columns returned by `search`: the selected columns of the search params (and the id
+    /// column), or all the columns except the embeddings.
+    async fn selected_columns(&self) -> Result<Vec<String>, VectorStoreError> {
+        match &self.search_params.select {
+            Some(columns) if columns.contains(&self.id_field) => Ok(columns.clone()),
+            Some(columns) => Ok(std::iter::once(self.id_field.clone())
+                .chain(columns.iter().cloned())
+                .collect()),
+            None => Ok(self
+                .table
+                .schema()
+                .await
+                .map_err(lancedb_to_rig_error)?
+                .filter_embeddings()),
+        }
+    }
+
+    /// Vector search of the top `n` documents matching the optional `filter`.
+    /// Implementation of the `top_n` and `top_n_filtered` methods of the `VectorStoreIndex` trait.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query_text: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.ensure_embedding_schema().await?;
+        let prompt_embedding = self.model.embed_text(query_text).await?;
+
+        let query = self
+            .table
+            .vector_search(prompt_embedding.vec.clone())
+            .map_err(lancedb_to_rig_error)?
+            .limit(n)
+            .select(lancedb::query::Select::Columns(
+                self.selected_columns().await?,
+            ));
+
+        self.build_query(query, query_text, filter)?
+            .execute_query()
+            .await?
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                Ok((
+                    score(&value, "_distance"),
+                    match value.get(self.id_field.clone()) {
+                        Some(Value::String(id)) => id.to_string(),
+                        _ => format!("unknown{i}"),
+                    },
+                    serde_json::from_value(value).map_err(serde_to_rig_error)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Same as `search` but returns the document ids only.
+    /// Implementation of the `top_n_ids` and `top_n_ids_filtered` methods of the `VectorStoreIndex` trait.
+    async fn search_ids(
+        &self,
+        query_text: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.ensure_embedding_schema().await?;
+        let prompt_embedding = self.model.embed_text(query_text).await?;
+
+        let query = self
+            .table
+            .query()
+            .select(lancedb::query::Select::Columns(vec![self.id_field.clone()]))
+            .nearest_to(prompt_embedding.vec.clone())
+            .map_err(lancedb_to_rig_error)?
+            .limit(n);
+
+        self.build_query(query, query_text, filter)?
+            .execute_query()
+            .await?
+            .into_iter()
+            .map(|value| {
+                Ok((
+                    score(&value, "distance"),
+                    match value.get(self.id_field.clone()) {
+                        Some(Value::String(id)) => id.to_string(),
+                        _ => "".to_string(),
+                    },
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Documents are record batches with the schema of the table, including the id and embedding
+/// columns, so several documents can be inserted or updated with a single record batch.
+/// Inserted documents are written in chunks of [DEFAULT_INGEST_BATCH_SIZE] rows (see
+/// [LanceDbVectorIndex::ingest]), so inserts of more rows are not atomic: if a chunk fails to be
+/// written, the chunks written before it stay in the table. Retry such inserts with `upsert` set,
+/// which replaces the documents already written instead of failing on their ids.
+/// # Example
+/// ```
+/// use rig::vector_store::VectorStore;
+///
+/// // Re-embed a changed document and replace it in the table
+/// let record_batch = as_record_batch(vec![(definition, embeddings)], model.ndims());
+/// vector_store_index.update_document(record_batch?).await?;
+///
+/// // Remove a stale document
+/// vector_store_index.delete_documents(&["doc0".to_string()]).await?;
+/// ```
+impl<M: EmbeddingModel + Sync + Send> VectorStore for LanceDbVectorIndex<M> {
+    type Document = RecordBatch;
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<RecordBatch>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        let mode = if upsert {
+            IngestMode::Upsert
+        } else {
+            let ids = self.record_ids(&documents)?;
+            if let Some(id) = self.existing_ids(&ids).await?.into_iter().next() {
+                return Err(VectorStoreError::DuplicateIdError(id));
+            }
+            IngestMode::Append
+        };
+
+        self.ingest(
+            stream::iter(documents.into_iter().map(Ok)),
+            IngestOptions::default().mode(mode),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn update_document(&mut self, document: RecordBatch) -> Result<(), VectorStoreError> {
+        let ids = self.record_ids(std::slice::from_ref(&document))?;
+        let existing_ids = self.existing_ids(&ids).await?;
+        if let Some(id) = ids.into_iter().find(|id| !existing_ids.contains(id)) {
+            return Err(VectorStoreError::MissingIdError(id));
+        }
+
+        let schema = document.schema();
+        let mut merge_insert = self.table.merge_insert(&[self.id_field.as_str()]);
+        merge_insert.when_matched_update_all(None);
+        merge_insert
+            .execute(Box::new(RecordBatchIterator::new(
+                vec![Ok(document)],
+                schema,
+            )))
+            .await
+            .map_err(lancedb_to_rig_error)?;
+
+        self.apply_index_policy(1, 0).await;
+        Ok(())
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.table
+            .delete(&Filter::one_of(&self.id_field, ids.to_vec()).to_sql()?)
+            .await
+            .map_err(lancedb_to_rig_error)?;
+
+        self.apply_index_policy(1, 0).await;
+        Ok(())
+    }
+}
+
+/// LanceDB database whose tables are collections of documents (see [VectorStoreCollections]):
+/// each table is a [LanceDbVectorIndex] of its own, and all tables use the same model, id field
+/// and search parameters.
+/// # Example
+/// ```
+/// use rig::vector_store::{VectorStore, VectorStoreCollections, VectorStoreIndex};
+/// use rig_lancedb::{LanceDbVectorStore, SearchParams};
+///
+/// let db = lancedb::connect("data/lancedb-store").execute().await?;
+///
+/// // Tables of missing collections are created with the given schema
+/// let mut store = LanceDbVectorStore::new(db, model, "id", SearchParams::default())
+///     .schema(schema);
+///
+/// // One table per tenant
+/// store.collection("acme").await?.insert_documents(record_batches, false).await?;
+///
+/// let results = store
+///     .collection("acme")
+///     .await?
+///     .top_n::<Definition>("What is our refund policy?", 3)
+///     .await?;
+/// ```
+pub struct LanceDbVectorStore<M: EmbeddingModel> {
+    connection: lancedb::Connection,
+    model: M,
+    id_field: String,
+    search_params: SearchParams,
+    /// Schema of the tables created for new collections
+    schema: Option<SchemaRef>,
+    /// Policy of the automatic management of the vector indexes of the tables, if enabled
+    index_policy: Option<IndexPolicy>,
+    /// Indexes of the collections opened so far
+    collections: HashMap<String, LanceDbVectorIndex<M>>,
+}
+
+impl<M: EmbeddingModel> LanceDbVectorStore<M> {
+    /// Create a store over the tables of a LanceDB database. The tables must have the given id
+    /// field, and are searched with the given search params.
+    pub fn new(
+        connection: lancedb::Connection,
+        model: M,
+        id_field: &str,
+        search_params: SearchParams,
+    ) -> Self {
+        Self {
+            connection,
+            model,
+            id_field: id_field.to_string(),
+            search_params,
+            schema: None,
+            index_policy: None,
+            collections: HashMap::new(),
+        }
+    }
+
+    /// Schema of the tables created for collections that do not exist yet. Without a schema,
+    /// only existing tables can be used as collections. Its embedding columns must have the
+    /// number of dimensions of the model.
+    pub fn schema(mut self, schema: SchemaRef) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Same as [LanceDbVectorStore::schema], with the schema made of `fields` followed by the
+    /// embedding column `embedding_column`, sized after the number of dimensions of the model
+    /// (see [embedding_field]). The [EmbeddingSchema] of the model is recorded in the metadata
+    /// of the column, so the tables refuse to be opened with another model.
+    /// # Example
+    /// ```
+    /// let model = openai.embedding_model(openai::TEXT_EMBEDDING_3_LARGE).dimensions(256);
+    ///
+    /// let store = LanceDbVectorStore::new(db, model, "id", SearchParams::default())
+    ///     .document_schema(
+    ///         [
+    ///             Field::new("id", DataType::Utf8, false),
+    ///             Field::new("definition", DataType::Utf8, false),
+    ///         ],
+    ///         "embedding",
+    ///     );
+    /// ```
+    pub fn document_schema(
+        self,
+        fields: impl IntoIterator<Item = Field>,
+        embedding_column: &str,
+    ) -> Self {
+        let fields = fields
+            .into_iter()
+            .chain(std::iter::once(
+                embedding_field(embedding_column, self.model.ndims())
+                    .with_metadata(EmbeddingSchema::of(&self.model).to_metadata()),
+            ))
+            .collect::<Vec<_>>();
+        self.schema(Arc::new(Schema::new(fields)))
+    }
+
+    /// Manage the vector index of the table of each collection automatically according to
+    /// `policy` (see [LanceDbVectorIndex::index_policy]).
+    pub fn index_policy(mut self, policy: IndexPolicy) -> Self {
+        self.index_policy = Some(policy);
+        self
+    }
+
+    /// Open the table of a collection, creating it if it does not exist and a schema is set.
+    /// Fails if the embeddings of the table do not have the number of dimensions of the model.
+    async fn open_table(&self, name: &str) -> Result<lancedb::Table, VectorStoreError> {
+        let table = match (
+            self.connection.open_table(name).execute().await,
+            &self.schema,
+        ) {
+            (Err(lancedb::Error::TableNotFound { .. }), Some(schema)) => {
+                check_schema(schema, &self.model, self.search_params.column.as_deref())?;
+                self.connection
+                    .create_empty_table(name, schema.clone())
+                    .execute()
+                    .await
+                    .map_err(lancedb_to_rig_error)?
+            }
+            (table, _) => table.map_err(lancedb_to_rig_error)?,
+        };
+
+        check_schema(
+            &table.schema().await.map_err(lancedb_to_rig_error)?,
+            &self.model,
+            self.search_params.column.as_deref(),
+        )?;
+        Ok(table)
+    }
+}
+
+impl<M: EmbeddingModel + Sync + Send> VectorStoreCollections for LanceDbVectorStore<M> {
+    type Collection = LanceDbVectorIndex<M>;
+
+    async fn collection(&mut self, name: &str) -> Result<&mut Self::Collection, VectorStoreError> {
+        if !self.collections.contains_key(name) {
+            let table = self.open_table(name).await?;
+            let mut index = LanceDbVectorIndex::new(
+                table,
+                self.model.clone(),
+                &self.id_field,
+                self.search_params.clone(),
+            )
+            .await
+            .map_err(lancedb_to_rig_error)?;
+            if let Some(policy) = &self.index_policy {
+                index = index.index_policy(policy.clone());
+            }
+            self.collections.insert(name.to_string(), index);
+        }
+
+        Ok(self
+            .collections
+            .get_mut(name)
+            .expect("Collection should be opened"))
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, VectorStoreError> {
+        self.connection
+            .table_names()
+            .execute()
+            .await
+            .map_err(lancedb_to_rig_error)
+    }
+
+    async fn delete_collection(&mut self, name: &str) -> Result<(), VectorStoreError> {
+        self.collections.remove(name);
+
+        match self.connection.drop_table(name).await {
+            Ok(()) | Err(lancedb::Error::TableNotFound { .. }) => Ok(()),
+            Err(e) => Err(lancedb_to_rig_error(e)),
+        }
+    }
+}
+
+/// Id of a record returned by a query, if it has a string or numeric id column.
+fn record_id(record: &Value, id_field: &str) -> Option<String> {
+    match record.get(id_field)? {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Default number of rows per write of [LanceDbVectorIndex::ingest].
+pub const DEFAULT_INGEST_BATCH_SIZE: usize = 10_000;
+
+/// How [LanceDbVectorIndex::ingest] writes records to the table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IngestMode {
+    /// Records are added to the table.
+    #[default]
+    Append,
+    /// Records are merged into the table by id (merge-insert): records with the id of an
+    /// existing record replace it, the others are added.
+    Upsert,
+}
+
+/// Progress of [LanceDbVectorIndex::ingest].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestProgress {
+    /// Number of rows written so far
+    pub rows_written: usize,
+    /// Number of writes (chunks of rows) so far
+    pub batches_written: usize,
+}
+
+/// Options of [LanceDbVectorIndex::ingest].
+/// # Example
+/// ```
+/// let options = rig_lancedb::IngestOptions::default()
+///     .batch_size(5000)
+///     .mode(rig_lancedb::IngestMode::Upsert);
+/// ```
+#[derive(Clone)]
+pub struct IngestOptions {
+    batch_size: usize,
+    mode: IngestMode,
+    on_progress: Option<Arc<dyn Fn(IngestProgress) + Send + Sync>>,
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_INGEST_BATCH_SIZE,
+            mode: IngestMode::default(),
+            on_progress: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for IngestOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IngestOptions")
+            .field("batch_size", &self.batch_size)
+            .field("mode", &self.mode)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+impl IngestOptions {
+    /// Sets the maximum number of rows per write. The default is [DEFAULT_INGEST_BATCH_SIZE].
+    /// Smaller writes use less memory and are shorter (e.g.: to stay within the timeouts of
+    /// object stores such as S3), larger writes create fewer fragments in the table.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the write mode. The default is [IngestMode::Append].
+    pub fn mode(mut self, mode: IngestMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets a callback called with the progress of the ingest after each write.
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(IngestProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    /// Progress after a write of `rows` rows, reported to the progress callback (if any).
+    fn report(&self, progress: IngestProgress, rows: usize) -> IngestProgress {
+        let progress = IngestProgress {
+            rows_written: progress.rows_written + rows,
+            batches_written: progress.batches_written + 1,
+        };
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+        progress
+    }
+}
+
+/// Minimum number of rows required by LanceDB to train an ANN index.
+pub const MIN_ROWS_FOR_ANN_INDEX: usize = 256;
+
+/// Outcome of [LanceDbVectorIndex::create_index].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexStatus {
+    /// The index was built.
+    Built,
+    /// The table does not contain enough rows to train an ANN index, so the index was not built.
+    /// Vector searches on the table use a flat (brute-force) search instead.
+    IndexNotBuilt { row_count: usize },
+}
+
+/// Policy of the automatic management of the vector index of a table (see
+/// [LanceDbVectorIndex::index_policy]): an IVF_PQ index is created on a column once the table
+/// has enough rows to train it, then optimized (new rows are indexed and the table compacted)
+/// after a number of writes.
+/// # Example
+/// ```
+/// let policy = rig_lancedb::IndexPolicy::ivf_pq("embedding")
+///     .distance_type(lancedb::DistanceType::Cosine)
+///     .min_rows(10_000)
+///     .optimize_after(100);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IndexPolicy {
+    column: String,
+    distance_type: DistanceType,
+    min_rows: usize,
+    optimize_after: Option<usize>,
+}
+
+impl IndexPolicy {
+    /// Create an IVF_PQ index on `column` once the table has [MIN_ROWS_FOR_ANN_INDEX] rows.
+    /// The index is not optimized automatically.
+    pub fn ivf_pq(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            distance_type: DistanceType::L2,
+            min_rows: MIN_ROWS_FOR_ANN_INDEX,
+            optimize_after: None,
+        }
+    }
+
+    /// Sets the distance type of the index. The default is DistanceType::L2.
+    /// Always set it to the distance type of the search params (see [SearchParams::distance_type]).
+    pub fn distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.distance_type = distance_type;
+        self
+    }
+
+    /// Sets the number of rows from which the index is created (at least [MIN_ROWS_FOR_ANN_INDEX]).
+    /// Flat searches are fast enough on small tables, and indexes trained on more rows have a
+    /// better recall.
+    pub fn min_rows(mut self, min_rows: usize) -> Self {
+        self.min_rows = min_rows.max(MIN_ROWS_FOR_ANN_INDEX);
+        self
+    }
+
+    /// Optimizes the table and its index after `writes` writes (inserts, updates or deletes):
+    /// rows written after the index was created are only found by exhaustive search until the
+    /// index is optimized.
+    pub fn optimize_after(mut self, writes: usize) -> Self {
+        self.optimize_after = Some(writes.max(1));
+        self
+    }
+
+    fn index(&self) -> Index {
+        Index::IvfPq(IvfPqIndexBuilder::default().distance_type(self.distance_type))
+    }
+}
+
+/// Whether the index type is a vector index type.
+fn is_vector_index_type(index_type: &IndexType) -> bool {
+    matches!(
+        index_type,
+        IndexType::IvfFlat | IndexType::IvfPq | IndexType::IvfHnswPq | IndexType::IvfHnswSq
+    )
+}
+
+/// Whether the index is an ANN index, which requires training on the table's rows.
+fn is_ann_index(index: &Index) -> bool {
+    matches!(
+        index,
+        Index::Auto
+            | Index::IvfFlat(_)
+            | Index::IvfPq(_)
+            | Index::IvfHnswPq(_)
+            | Index::IvfHnswSq(_)
+    )
+}
+
+/// Score of a search result: its `distance_column` for vector searches, its relevance score for
+/// hybrid searches (see [SearchParams::full_text_search]).
+fn score(value: &Value, distance_column: &str) -> f64 {
+    match value
+        .get(distance_column)
+        .or_else(|| value.get("_relevance_score"))
+    {
+        Some(Value::Number(score)) => score.as_f64().unwrap_or_default(),
+        _ => 0.0,
     }
 }
 
@@ -124,6 +1107,10 @@ pub struct SearchParams {
     refine_factor: Option<u32>,
     post_filter: Option<bool>,
     column: Option<String>,
+    full_text_search: Option<Vec<String>>,
+    rrf_k: Option<f32>,
+    where_clause: Option<String>,
+    select: Option<Vec<String>>,
 }
 
 impl SearchParams {
@@ -143,16 +1130,18 @@ impl SearchParams {
         self
     }
 
-    /// Sets the nprobes of the search params.
-    /// Only set this value only when the search type is ANN.
+    /// Sets the nprobes of the search params: the number of partitions of the ANN index searched.
+    /// Higher values improve recall at the cost of latency.
+    /// Ignored when the search type is flat.
     /// See [LanceDb ANN Search](https://lancedb.github.io/lancedb/ann_indexes/#querying-an-ann-index) for more information.
     pub fn nprobes(mut self, nprobes: usize) -> Self {
         self.nprobes = Some(nprobes);
         self
     }
 
-    /// Sets the refine factor of the search params.
-    /// Only set this value only when search type is ANN.
+    /// Sets the refine factor of the search params: `refine_factor * n` candidates of the ANN
+    /// index are reranked by their exact distance, which improves recall at the cost of latency.
+    /// Ignored when the search type is flat.
     /// See [LanceDb ANN Search](https://lancedb.github.io/lancedb/ann_indexes/#querying-an-ann-index) for more information.
     pub fn refine_factor(mut self, refine_factor: u32) -> Self {
         self.refine_factor = Some(refine_factor);
@@ -174,10 +1163,50 @@ impl SearchParams {
         self.column = Some(column.to_string());
         self
     }
+
+    /// Sets a SQL `WHERE` clause on the columns of the table (e.g.: `"category = 'faq'"`) which
+    /// the results of every search must match. The filters of [VectorStoreIndex::top_n_filtered]
+    /// are combined with it.
+    /// See [LanceDb filtering](https://lancedb.github.io/lancedb/sql/) for more information.
+    pub fn where_clause(mut self, where_clause: &str) -> Self {
+        self.where_clause = Some(where_clause.to_string());
+        self
+    }
+
+    /// Sets the columns returned by [VectorStoreIndex::top_n] (the id column is always returned),
+    /// e.g.: to avoid fetching large columns that the documents do not need.
+    /// By default, all the columns except the embeddings are returned.
+    pub fn select(mut self, columns: &[&str]) -> Self {
+        self.select = Some(columns.iter().map(|column| column.to_string()).collect());
+        self
+    }
+
+    /// Enables hybrid search: each query is also run as a full-text (BM25) search on the given
+    /// text columns, and the results of the vector search and of the full-text search are fused
+    /// with reciprocal rank fusion (see [SearchParams::rrf_k]). Useful to retrieve documents
+    /// containing exact terms (e.g.: error codes) that embeddings miss.
+    /// The columns must have a full-text search index, created with [LanceDbVectorIndex::create_index]
+    /// and `lancedb::index::Index::FTS`. Pass an empty slice to search all indexed columns.
+    /// The scores of hybrid search results are relevance scores (higher is more relevant)
+    /// instead of distances.
+    /// See [LanceDb hybrid search](https://lancedb.github.io/lancedb/hybrid_search/hybrid_search/) for more information.
+    pub fn full_text_search(mut self, columns: &[&str]) -> Self {
+        self.full_text_search = Some(columns.iter().map(|column| column.to_string()).collect());
+        self
+    }
+
+    /// Sets the `k` constant of the reciprocal rank fusion of hybrid search. The default is 60.
+    /// Only set this value when full-text search is enabled (see [SearchParams::full_text_search]).
+    pub fn rrf_k(mut self, k: f32) -> Self {
+        self.rrf_k = Some(k);
+        self
+    }
 }
 
 impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M> {
     /// Implement the `top_n` method of the `VectorStoreIndex` trait for `LanceDbVectorIndex`.
+    /// Results with identical distances are returned in LanceDB's internal order, which is not
+    /// guaranteed to be stable.
     /// # Example
     /// ```
     /// use rig_lancedb::{LanceDbVectorIndex, SearchParams};
@@ -199,43 +1228,12 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(query).await?;
-
-        let query = self
-            .table
-            .vector_search(prompt_embedding.vec.clone())
-            .map_err(lancedb_to_rig_error)?
-            .limit(n)
-            .select(lancedb::query::Select::Columns(
-                self.table
-                    .schema()
-                    .await
-                    .map_err(lancedb_to_rig_error)?
-                    .filter_embeddings(),
-            ));
-
-        self.build_query(query)
-            .execute_query()
-            .await?
-            .into_iter()
-            .enumerate()
-            .map(|(i, value)| {
-                Ok((
-                    match value.get("_distance") {
-                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
-                        _ => 0.0,
-                    },
-                    match value.get(self.id_field.clone()) {
-                        Some(Value::String(id)) => id.to_string(),
-                        _ => format!("unknown{i}"),
-                    },
-                    serde_json::from_value(value).map_err(serde_to_rig_error)?,
-                ))
-            })
-            .collect()
+        self.search(query, n, None).await
     }
 
     /// Implement the `top_n_ids` method of the `VectorStoreIndex` trait for `LanceDbVectorIndex`.
+    /// Results with identical distances are returned in LanceDB's internal order, which is not
+    /// guaranteed to be stable.
     /// # Example
     /// ```
     /// use rig_lancedb::{LanceDbVectorIndex, SearchParams};
@@ -257,32 +1255,59 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(query).await?;
+        self.search_ids(query, n, None).await
+    }
 
-        let query = self
+    /// Implement the `top_n_filtered` method of the `VectorStoreIndex` trait for `LanceDbVectorIndex`.
+    /// The filter is lowered to a SQL `WHERE` clause on the columns of the table
+    /// (see [Filter::to_sql]). By default, it is applied before the vector search
+    /// (see [SearchParams::post_filter]).
+    async fn top_n_filtered<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    /// Implement the `top_n_ids_filtered` method of the `VectorStoreIndex` trait for `LanceDbVectorIndex`.
+    /// See the `top_n_filtered` method.
+    async fn top_n_ids_filtered(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+
+    /// Implement the `stats` method of the `VectorStoreIndex` trait for `LanceDbVectorIndex`.
+    /// Reports the row count of the table and the type of its vector index (`"FLAT"` if the
+    /// table has no vector index). The disk usage is not reported.
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        let row_count = self
             .table
-            .query()
-            .select(lancedb::query::Select::Columns(vec![self.id_field.clone()]))
-            .nearest_to(prompt_embedding.vec.clone())
-            .map_err(lancedb_to_rig_error)?
-            .limit(n);
+            .count_rows(None)
+            .await
+            .map_err(lancedb_to_rig_error)?;
 
-        self.build_query(query)
-            .execute_query()
-            .await?
+        let index_type = self
+            .table
+            .list_indices()
+            .await
+            .map_err(lancedb_to_rig_error)?
             .into_iter()
-            .map(|value| {
-                Ok((
-                    match value.get("distance") {
-                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
-                        _ => 0.0,
-                    },
-                    match value.get(self.id_field.clone()) {
-                        Some(Value::String(id)) => id.to_string(),
-                        _ => "".to_string(),
-                    },
-                ))
-            })
-            .collect()
+            .find(|index| is_vector_index_type(&index.index_type))
+            .map(|index| index.index_type.to_string())
+            .unwrap_or_else(|| "FLAT".to_string());
+
+        Ok(VectorStoreStats {
+            document_count: Some(row_count),
+            vector_count: Some(row_count),
+            dimensions: Some(self.model.ndims()),
+            index_type: Some(index_type),
+            size_bytes: None,
+        })
     }
 }
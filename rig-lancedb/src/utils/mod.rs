@@ -2,7 +2,7 @@ mod deserializer;
 
 use std::sync::Arc;
 
-use deserializer::RecordBatchDeserializer;
+pub(crate) use deserializer::RecordBatchDeserializer;
 use futures::TryStreamExt;
 use lancedb::{
     arrow::arrow_schema::{DataType, Schema},
@@ -32,6 +32,20 @@ impl QueryToJson for lancedb::query::VectorQuery {
     }
 }
 
+impl QueryToJson for lancedb::query::Query {
+    async fn execute_query(&self) -> Result<Vec<serde_json::Value>, VectorStoreError> {
+        let record_batches = self
+            .execute()
+            .await
+            .map_err(lancedb_to_rig_error)?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(lancedb_to_rig_error)?;
+
+        record_batches.deserialize()
+    }
+}
+
 /// Filter out the columns from a table that do not include embeddings. Return the vector of column names.
 pub(crate) trait FilterTableColumns {
     fn filter_embeddings(self) -> Vec<String>;
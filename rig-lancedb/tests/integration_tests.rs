@@ -1,15 +1,29 @@
 use serde_json::json;
 
-use arrow_array::RecordBatchIterator;
+use arrow_array::{
+    types::Int32Type, ArrayRef, FixedSizeListArray, RecordBatch, RecordBatchIterator,
+};
 use fixture::{as_record_batch, schema, words, Word};
-use lancedb::index::vector::IvfPqIndexBuilder;
+use lancedb::{
+    arrow::arrow_schema::{DataType, Field},
+    index::{
+        scalar::{BTreeIndexBuilder, FtsIndexBuilder},
+        vector::{IvfFlatIndexBuilder, IvfPqIndexBuilder},
+        Index,
+    },
+};
 use rig::{
     embeddings::{EmbeddingModel, EmbeddingsBuilder},
-    providers::openai,
-    vector_store::VectorStoreIndex,
+    providers::{mock::MockEmbeddingModel, openai},
+    vector_store::{
+        filter::Filter, VectorStore, VectorStoreCollections, VectorStoreError, VectorStoreIndex,
+    },
+};
+use rig_lancedb::{
+    IndexPolicy, IndexStatus, IngestMode, IngestOptions, IngestProgress, LanceDbVectorIndex,
+    LanceDbVectorStore, SearchParams,
 };
-use rig_lancedb::{LanceDbVectorIndex, SearchParams};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[path = "./fixtures/lib.rs"]
 mod fixture;
@@ -174,3 +188,547 @@ async fn vector_search_test() {
 
     db.drop_all_tables().await.unwrap();
 }
+
+/// Number of dimensions of the embeddings of the mock model
+const NDIMS: usize = 8;
+
+/// Definition of the word "doc1", used as the query finding it first
+const ZINDLE: &str = "Definition of *zindle (verb)*: to pretend to be working on something important while actually doing something completely unrelated or unproductive.";
+
+/// Local database in a temporary directory (removed when the directory is dropped)
+async fn local_db() -> (tempfile::TempDir, lancedb::Connection) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = lancedb::connect(dir.path().to_str().unwrap())
+        .execute()
+        .await
+        .unwrap();
+    (dir, db)
+}
+
+/// Words `copy{i}` with distinct definitions, for the indexes which need many rows to train
+fn copies(range: std::ops::Range<usize>) -> Vec<Word> {
+    range
+        .map(|i| Word {
+            id: format!("copy{i}"),
+            definition: format!(
+                "Definition of *flumbuzzle{i} (noun)*: A sudden urge to rearrange small objects."
+            ),
+        })
+        .collect()
+}
+
+/// Record batch of `words` embedded by `model`
+async fn records(model: &MockEmbeddingModel, words: Vec<Word>) -> RecordBatch {
+    let embeddings = EmbeddingsBuilder::new(model.clone())
+        .documents(words)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    as_record_batch(embeddings, model.ndims()).unwrap()
+}
+
+/// Table of the fixture words and of `extra` other words, embedded by `model`
+async fn words_table(
+    db: &lancedb::Connection,
+    name: &str,
+    model: &MockEmbeddingModel,
+    extra: usize,
+) -> lancedb::Table {
+    let mut documents = words();
+    documents.extend(copies(0..extra));
+    db.create_table(
+        name,
+        RecordBatchIterator::new(
+            vec![Ok(records(model, documents).await)],
+            Arc::new(schema(model.ndims())),
+        ),
+    )
+    .execute()
+    .await
+    .unwrap()
+}
+
+/// Fields of the words, besides their embedding
+fn word_fields() -> [Field; 2] {
+    [
+        Field::new("id", DataType::Utf8, false),
+        Field::new("definition", DataType::Utf8, false),
+    ]
+}
+
+fn ids(results: Vec<(f64, String)>) -> Vec<String> {
+    results.into_iter().map(|(_, id)| id).collect()
+}
+
+#[tokio::test]
+async fn embedding_schema_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+
+    // Searches with a model of other dimensions fail instead of returning meaningless results
+    let index = LanceDbVectorIndex::new(
+        table.clone(),
+        MockEmbeddingModel::new(NDIMS * 2),
+        "id",
+        SearchParams::default(),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        index.top_n_ids(ZINDLE, 1).await,
+        Err(VectorStoreError::DimensionMismatch { expected, found })
+            if expected == NDIMS * 2 && found == NDIMS
+    ));
+
+    let index = LanceDbVectorIndex::new(table, model.clone(), "id", SearchParams::default())
+        .await
+        .unwrap();
+    assert_eq!(ids(index.top_n_ids(ZINDLE, 1).await.unwrap()), vec!["doc1"]);
+
+    // Fixed size lists which are not the embeddings are not checked
+    let tags = FixedSizeListArray::from_iter_primitive::<Int32Type, _, _>(
+        (0..3).map(|i| Some(vec![Some(i), Some(i + 1), Some(i + 2)])),
+        3,
+    );
+    let words = records(&model, words()).await;
+    let tagged = RecordBatch::try_from_iter(vec![
+        ("id", words.column_by_name("id").unwrap().clone()),
+        ("tags", Arc::new(tags) as ArrayRef),
+        (
+            "embedding",
+            words.column_by_name("embedding").unwrap().clone(),
+        ),
+    ])
+    .unwrap();
+    let table = db
+        .create_table(
+            "tagged",
+            RecordBatchIterator::new(vec![Ok(tagged.clone())], tagged.schema()),
+        )
+        .execute()
+        .await
+        .unwrap();
+    let index = LanceDbVectorIndex::new(table, model.clone(), "id", SearchParams::default())
+        .await
+        .unwrap();
+    assert_eq!(ids(index.top_n_ids(ZINDLE, 1).await.unwrap()), vec!["doc1"]);
+
+    // Tables created with the schema of a model refuse to be opened with another one
+    let mut store = LanceDbVectorStore::new(db.clone(), model, "id", SearchParams::default())
+        .document_schema(word_fields(), "embedding");
+    store.collection("documents").await.unwrap();
+    let mut store = LanceDbVectorStore::new(
+        db.clone(),
+        MockEmbeddingModel::new(NDIMS * 2),
+        "id",
+        SearchParams::default(),
+    );
+    assert!(matches!(
+        store.collection("documents").await,
+        Err(VectorStoreError::DimensionMismatch { .. })
+    ));
+}
+
+#[tokio::test]
+async fn create_index_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+    let index = LanceDbVectorIndex::new(table, model, "id", SearchParams::default())
+        .await
+        .unwrap();
+
+    // The table is too small to train an ANN index: searches fall back to flat search
+    assert_eq!(
+        index.create_index("embedding", Index::Auto).await.unwrap(),
+        IndexStatus::IndexNotBuilt { row_count: 3 }
+    );
+    assert_eq!(ids(index.top_n_ids(ZINDLE, 1).await.unwrap()), vec!["doc1"]);
+
+    // Scalar indexes need no training
+    assert_eq!(
+        index
+            .create_index("id", Index::BTree(BTreeIndexBuilder::default()))
+            .await
+            .unwrap(),
+        IndexStatus::Built
+    );
+}
+
+#[tokio::test]
+async fn stats_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 300).await;
+    let index = LanceDbVectorIndex::new(table, model, "id", SearchParams::default())
+        .await
+        .unwrap();
+
+    let stats = index.stats().await.unwrap();
+    assert_eq!(stats.document_count, Some(303));
+    assert_eq!(stats.dimensions, Some(NDIMS));
+    assert_eq!(stats.index_type.as_deref(), Some("FLAT"));
+
+    index
+        .create_index("embedding", Index::IvfPq(IvfPqIndexBuilder::default()))
+        .await
+        .unwrap();
+    assert_eq!(
+        index.stats().await.unwrap().index_type.as_deref(),
+        Some("IVF_PQ")
+    );
+}
+
+#[tokio::test]
+async fn filtered_search_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+    let index = LanceDbVectorIndex::new(table, model, "id", SearchParams::default())
+        .await
+        .unwrap();
+
+    let results = index
+        .top_n_ids_filtered(ZINDLE, 3, Filter::ne("id", "doc1"))
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(!ids(results).contains(&"doc1".to_string()));
+
+    let results = index
+        .top_n_filtered::<Word>(ZINDLE, 3, Filter::one_of("id", ["doc0", "doc2"]))
+        .await
+        .unwrap();
+    let mut found = results
+        .into_iter()
+        .map(|(_, id, word)| {
+            assert_eq!(id, word.id);
+            id
+        })
+        .collect::<Vec<_>>();
+    found.sort();
+    assert_eq!(found, vec!["doc0", "doc2"]);
+}
+
+#[tokio::test]
+async fn hybrid_search_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+    let index = LanceDbVectorIndex::new(
+        table,
+        model,
+        "id",
+        SearchParams::default().full_text_search(&["definition"]),
+    )
+    .await
+    .unwrap();
+    index
+        .create_index("definition", Index::FTS(FtsIndexBuilder::default()))
+        .await
+        .unwrap();
+
+    // Only the full-text search matches the exact term, which ranks the document first
+    let results = index.top_n::<Word>("linglingdong", 3).await.unwrap();
+    let (score, id, word) = results.first().unwrap();
+    assert_eq!(id, "doc2");
+    assert_eq!(word.id, "doc2");
+    assert!(*score > 0.0);
+
+    let results = index.top_n_ids("linglingdong", 3).await.unwrap();
+    assert_eq!(results.first().unwrap().1, "doc2");
+}
+
+#[tokio::test]
+async fn update_and_delete_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+    let mut index =
+        LanceDbVectorIndex::new(table.clone(), model.clone(), "id", SearchParams::default())
+            .await
+            .unwrap();
+
+    let renamed = records(
+        &model,
+        vec![Word {
+            id: "doc1".to_string(),
+            definition: ZINDLE.replace("zindle", "zandle"),
+        }],
+    )
+    .await;
+
+    // Inserting an existing id fails unless upserting, which replaces the document
+    assert!(matches!(
+        index.insert_documents(vec![renamed.clone()], false).await,
+        Err(VectorStoreError::DuplicateIdError(id)) if id == "doc1"
+    ));
+    index.insert_documents(vec![renamed], true).await.unwrap();
+    assert_eq!(table.count_rows(None).await.unwrap(), 3);
+    let results = index
+        .top_n_filtered::<Word>(ZINDLE, 1, Filter::eq("id", "doc1"))
+        .await
+        .unwrap();
+    assert!(results[0].2.definition.contains("zandle"));
+
+    // Only existing documents can be updated
+    let missing = records(&model, copies(0..1)).await;
+    assert!(matches!(
+        index.update_document(missing).await,
+        Err(VectorStoreError::MissingIdError(id)) if id == "copy0"
+    ));
+    index
+        .update_document(records(&model, vec![words().remove(1)]).await)
+        .await
+        .unwrap();
+    let results = index
+        .top_n_filtered::<Word>(ZINDLE, 1, Filter::eq("id", "doc1"))
+        .await
+        .unwrap();
+    assert_eq!(results[0].2.definition, ZINDLE);
+
+    index
+        .delete_documents(&["doc0".to_string(), "doc2".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(table.count_rows(None).await.unwrap(), 1);
+    assert_eq!(ids(index.top_n_ids(ZINDLE, 3).await.unwrap()), vec!["doc1"]);
+}
+
+#[tokio::test]
+async fn collections_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let mut store =
+        LanceDbVectorStore::new(db.clone(), model.clone(), "id", SearchParams::default())
+            .document_schema(word_fields(), "embedding");
+
+    // Collections are separate tables, created on first use
+    store
+        .collection("acme")
+        .await
+        .unwrap()
+        .insert_documents(vec![records(&model, words()).await], false)
+        .await
+        .unwrap();
+    store
+        .collection("globex")
+        .await
+        .unwrap()
+        .insert_documents(vec![records(&model, copies(0..2)).await], false)
+        .await
+        .unwrap();
+
+    let mut names = store.collection_names().await.unwrap();
+    names.sort();
+    assert_eq!(names, vec!["acme", "globex"]);
+
+    let acme = store.collection("acme").await.unwrap();
+    assert_eq!(ids(acme.top_n_ids(ZINDLE, 1).await.unwrap()), vec!["doc1"]);
+    assert_eq!(acme.top_n_ids(ZINDLE, 10).await.unwrap().len(), 3);
+    let globex = store.collection("globex").await.unwrap();
+    assert_eq!(globex.top_n_ids(ZINDLE, 10).await.unwrap().len(), 2);
+
+    store.delete_collection("globex").await.unwrap();
+    assert_eq!(store.collection_names().await.unwrap(), vec!["acme"]);
+    // Deleting a missing collection is not an error
+    store.delete_collection("globex").await.unwrap();
+
+    // Without a schema, missing collections are not created
+    let mut store = LanceDbVectorStore::new(db, model, "id", SearchParams::default());
+    assert!(store.collection("initech").await.is_err());
+    assert!(store.collection("acme").await.is_ok());
+}
+
+#[tokio::test]
+async fn ingest_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+    let index =
+        LanceDbVectorIndex::new(table.clone(), model.clone(), "id", SearchParams::default())
+            .await
+            .unwrap();
+
+    // Batches are split and grouped into writes of at most 2 rows
+    let batches = vec![
+        Ok(records(&model, copies(0..3)).await),
+        Ok(records(&model, copies(3..8)).await),
+    ];
+    let reported = Arc::new(Mutex::new(vec![]));
+    let progress = index
+        .ingest(
+            futures::stream::iter(batches),
+            IngestOptions::default().batch_size(2).on_progress({
+                let reported = reported.clone();
+                move |progress| reported.lock().unwrap().push(progress.rows_written)
+            }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        progress,
+        IngestProgress {
+            rows_written: 8,
+            batches_written: 4
+        }
+    );
+    assert_eq!(*reported.lock().unwrap(), vec![2, 4, 6, 8]);
+    assert_eq!(table.count_rows(None).await.unwrap(), 11);
+
+    // Upserts replace the rows with existing ids and add the others
+    let mut renamed = copies(6..10);
+    for word in &mut renamed {
+        word.definition = format!("{} (renamed)", word.definition);
+    }
+    index
+        .ingest(
+            futures::stream::iter(vec![Ok(records(&model, renamed).await)]),
+            IngestOptions::default().mode(IngestMode::Upsert),
+        )
+        .await
+        .unwrap();
+    assert_eq!(table.count_rows(None).await.unwrap(), 13);
+    assert_eq!(
+        table
+            .count_rows(Some("definition LIKE '%(renamed)'".to_string()))
+            .await
+            .unwrap(),
+        4
+    );
+}
+
+#[tokio::test]
+async fn search_params_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 300).await;
+
+    // The where clause applies to every search, and is combined with their filters
+    let index = LanceDbVectorIndex::new(
+        table.clone(),
+        model.clone(),
+        "id",
+        SearchParams::default().where_clause("id != 'doc1'"),
+    )
+    .await
+    .unwrap();
+    assert!(!ids(index.top_n_ids(ZINDLE, 10).await.unwrap()).contains(&"doc1".to_string()));
+    assert_eq!(
+        ids(index
+            .top_n_ids_filtered(ZINDLE, 10, Filter::one_of("id", ["doc0", "doc1"]))
+            .await
+            .unwrap()),
+        vec!["doc0"]
+    );
+
+    // Only the selected columns (and the id) are returned
+    let index = LanceDbVectorIndex::new(
+        table.clone(),
+        model.clone(),
+        "id",
+        SearchParams::default().select(&["id"]),
+    )
+    .await
+    .unwrap();
+    let results = index.top_n::<serde_json::Value>(ZINDLE, 1).await.unwrap();
+    let (_, id, value) = results.first().unwrap();
+    assert_eq!(id, "doc1");
+    assert_eq!(value["id"], "doc1");
+    assert!(value.get("definition").is_none());
+    assert!(value.get("embedding").is_none());
+
+    // ANN parameters are used once the table has a vector index
+    let index = LanceDbVectorIndex::new(
+        table,
+        model,
+        "id",
+        SearchParams::default().nprobes(20).refine_factor(10),
+    )
+    .await
+    .unwrap();
+    index
+        .create_index("embedding", Index::IvfFlat(IvfFlatIndexBuilder::default()))
+        .await
+        .unwrap();
+    assert_eq!(ids(index.top_n_ids(ZINDLE, 1).await.unwrap()), vec!["doc1"]);
+    assert_eq!(index.top_n_ids(ZINDLE, 5).await.unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn index_policy_test() {
+    let (_dir, db) = local_db().await;
+    let model = MockEmbeddingModel::new(NDIMS);
+    let table = words_table(&db, "words", &model, 0).await;
+    let mut index =
+        LanceDbVectorIndex::new(table.clone(), model.clone(), "id", SearchParams::default())
+            .await
+            .unwrap()
+            .index_policy(IndexPolicy::ivf_pq("embedding"));
+
+    // The index is created once the table has enough rows to train it
+    index
+        .insert_documents(vec![records(&model, copies(0..200)).await], false)
+        .await
+        .unwrap();
+    assert_eq!(
+        index.stats().await.unwrap().index_type.as_deref(),
+        Some("FLAT")
+    );
+    index
+        .insert_documents(vec![records(&model, copies(200..300)).await], false)
+        .await
+        .unwrap();
+    assert_eq!(
+        index.stats().await.unwrap().index_type.as_deref(),
+        Some("IVF_PQ")
+    );
+    assert_eq!(table.list_indices().await.unwrap().len(), 1);
+
+    // Failures to create the index don't fail the writes
+    let table = words_table(&db, "unindexable", &model, 0).await;
+    let mut index =
+        LanceDbVectorIndex::new(table.clone(), model.clone(), "id", SearchParams::default())
+            .await
+            .unwrap()
+            .index_policy(IndexPolicy::ivf_pq("definition"));
+    index
+        .insert_documents(vec![records(&model, copies(0..300)).await], false)
+        .await
+        .unwrap();
+    assert_eq!(table.count_rows(None).await.unwrap(), 303);
+    assert!(table.list_indices().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn document_schema_test() {
+    let (_dir, db) = local_db().await;
+
+    // The embedding column is sized after the dimensions of the model
+    for ndims in [NDIMS, 3 * NDIMS] {
+        let name = format!("words{ndims}");
+        let mut store = LanceDbVectorStore::new(
+            db.clone(),
+            MockEmbeddingModel::new(ndims),
+            "id",
+            SearchParams::default(),
+        )
+        .document_schema(word_fields(), "embedding");
+        store.collection(&name).await.unwrap();
+
+        let schema = db
+            .open_table(&name)
+            .execute()
+            .await
+            .unwrap()
+            .schema()
+            .await
+            .unwrap();
+        assert!(matches!(
+            schema.field_with_name("embedding").unwrap().data_type(),
+            DataType::FixedSizeList(_, size) if *size as usize == ndims
+        ));
+    }
+}
@@ -1,5 +1,8 @@
 use futures::StreamExt;
-use mongodb::bson::{self, doc};
+use mongodb::{
+    bson::{self, doc},
+    SearchIndexModel, SearchIndexType,
+};
 
 use rig::{
     embeddings::embedding::{Embedding, EmbeddingModel},
@@ -49,8 +52,9 @@ struct Field {
     #[serde(rename = "type")]
     field_type: String,
     path: String,
-    num_dimensions: i32,
-    similarity: String,
+    // Only set for fields of type "vector" (not for fields of type "filter")
+    num_dimensions: Option<i32>,
+    similarity: Option<String>,
 }
 
 fn mongodb_to_rig_error(e: mongodb::error::Error) -> VectorStoreError {
@@ -158,8 +162,8 @@ impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
             .latest_definition
             .fields
             .into_iter()
+            .find(|field| field.field_type == "vector")
             .map(|field| field.path)
-            .next()
             // This error shouldn't occur if the index is queryable
             .ok_or(VectorStoreError::DatastoreError(
                 "No embedded fields found".into(),
@@ -175,6 +179,128 @@ impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
     }
 }
 
+/// Similarity function of a vector search index.
+/// See the MongoDB [documentation](https://www.mongodb.com/docs/atlas/atlas-vector-search/vector-search-type/#about-the-similarity-functions) for more information.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum SimilarityFunction {
+    Euclidean,
+    #[default]
+    Cosine,
+    DotProduct,
+}
+
+impl SimilarityFunction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SimilarityFunction::Euclidean => "euclidean",
+            SimilarityFunction::Cosine => "cosine",
+            SimilarityFunction::DotProduct => "dotProduct",
+        }
+    }
+}
+
+/// Definition of a vector search index, with the number of dimensions of an embedding model,
+/// to be created on a MongoDB collection before creating a [MongoDbVectorIndex].
+/// # Example
+/// ```rust,no_run
+/// use rig_mongodb::{SimilarityFunction, VectorSearchIndex};
+/// use rig::providers::openai;
+///
+/// # tokio_test::block_on(async {
+/// let mongodb_client = mongodb::Client::with_uri_str("mongodb://localhost:27017").await?;
+/// let collection = mongodb_client.database("db").collection::<mongodb::bson::Document>("words");
+///
+/// let model = openai::Client::from_env().embedding_model(openai::TEXT_EMBEDDING_ADA_002);
+///
+/// VectorSearchIndex::new("vector_index", "embedding", &model)
+///     .similarity(SimilarityFunction::Cosine)
+///     .filter_field("category")
+///     .create(&collection)
+///     .await?;
+/// # Ok::<_, anyhow::Error>(())
+/// # }).unwrap()
+/// ```
+#[derive(Clone, Debug)]
+pub struct VectorSearchIndex {
+    name: String,
+    embedded_field: String,
+    num_dimensions: usize,
+    similarity: SimilarityFunction,
+    filter_fields: Vec<String>,
+}
+
+impl VectorSearchIndex {
+    /// Create the definition of the index `name` on the embeddings of `model` stored in
+    /// `embedded_field` (with cosine similarity by default).
+    pub fn new(name: &str, embedded_field: &str, model: &impl EmbeddingModel) -> Self {
+        Self {
+            name: name.to_string(),
+            embedded_field: embedded_field.to_string(),
+            num_dimensions: model.ndims(),
+            similarity: SimilarityFunction::default(),
+            filter_fields: vec![],
+        }
+    }
+
+    /// Sets the similarity function of the index.
+    pub fn similarity(mut self, similarity: SimilarityFunction) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    /// Index the given field so it can be used in the pre-filter of searches
+    /// (see [SearchParams::filter]).
+    pub fn filter_field(mut self, path: &str) -> Self {
+        self.filter_fields.push(path.to_string());
+        self
+    }
+
+    /// The definition of the index, as expected by MongoDB.
+    pub fn definition(&self) -> bson::Document {
+        let vector_field = doc! {
+            "type": "vector",
+            "path": &self.embedded_field,
+            "numDimensions": self.num_dimensions as i32,
+            "similarity": self.similarity.as_str(),
+        };
+
+        let fields = std::iter::once(vector_field)
+            .chain(self.filter_fields.iter().map(|path| {
+                doc! {
+                    "type": "filter",
+                    "path": path,
+                }
+            }))
+            .collect::<Vec<_>>();
+
+        doc! { "fields": fields }
+    }
+
+    /// The index as a MongoDB search index model.
+    pub fn model(&self) -> SearchIndexModel {
+        SearchIndexModel::builder()
+            .name(Some(self.name.clone()))
+            .index_type(Some(SearchIndexType::VectorSearch))
+            .definition(self.definition())
+            .build()
+    }
+
+    /// Create the index on `collection`.
+    ///
+    /// Note: the index is built asynchronously by MongoDB, [MongoDbVectorIndex::new] fails
+    /// until it is queryable.
+    pub async fn create<C: Send + Sync>(
+        &self,
+        collection: &mongodb::Collection<C>,
+    ) -> Result<(), VectorStoreError> {
+        collection
+            .create_search_index(self.model())
+            .await
+            .map_err(mongodb_to_rig_error)?;
+        Ok(())
+    }
+}
+
 /// See [MongoDB Vector Search](`https://www.mongodb.com/docs/atlas/atlas-vector-search/vector-search-stage/`) for more information
 /// on each of the fields
 #[derive(Default)]
@@ -202,17 +202,17 @@ async fn create_search_index(collection: &Collection<bson::Document>) {
                     if indexes.iter().any(|idx| {
                         idx.as_ref()
                             .ok()
-                            .and_then(|i| {
+                            .map(|i| {
                                 // Check both name and status
                                 let name_matches = i
                                     .get_str("name")
                                     .ok()
-                                    .map_or(false, |name| name == VECTOR_SEARCH_INDEX_NAME);
+                                    .is_some_and(|name| name == VECTOR_SEARCH_INDEX_NAME);
                                 let status_ready = i
                                     .get_str("status")
                                     .ok()
-                                    .map_or(false, |status| status == "READY");
-                                Some(name_matches && status_ready)
+                                    .is_some_and(|status| status == "READY");
+                                name_matches && status_ready
                             })
                             .unwrap_or(false)
                     }) {
@@ -0,0 +1,277 @@
+//! Graph expansion of the results of vector searches.
+//!
+//! Vector searches only return the nodes whose embeddings are similar to the query. Expanding
+//! the results with their neighbors (e.g.: the actors and the director of a movie whose plot
+//! matches the query) gives richer, structured context to agents, for instance through
+//! [dynamic_context](rig::agent::AgentBuilder::dynamic_context).
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig_neo4j::{graph_expansion::{Direction, GraphExpansion}, vector_index::SearchParams};
+//!
+//! // Each result gets a `neighbors` property with the actors and the director of the movie
+//! let search_params = SearchParams::default().expand(
+//!     GraphExpansion::new(&["ACTED_IN", "DIRECTED"])?
+//!         .direction(Direction::Incoming)
+//!         .max_neighbors(10),
+//! );
+//! let index = client.get_index(model, "moviePlots", search_params).await?;
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .dynamic_context(3, index)
+//!     .build();
+//! ```
+use neo4rs::Query;
+use rig::vector_store::VectorStoreError;
+use serde::Deserialize;
+
+/// Direction of the relationships followed by a graph expansion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Relationships from the node to its neighbors
+    Outgoing,
+    /// Relationships from the neighbors to the node
+    Incoming,
+    #[default]
+    Both,
+}
+
+/// Neighbors fetched by a graph expansion: the nodes reachable from a node through at most
+/// `max_depth` relationships of the given types and direction.
+///
+/// #### Default Values
+/// - `relationship_types`: all types
+/// - `direction`: Direction::Both
+/// - `max_depth`: 1
+/// - `max_neighbors`: 25
+/// - `property`: "neighbors"
+#[derive(Clone, Debug)]
+pub struct GraphExpansion {
+    relationship_types: Vec<String>,
+    direction: Direction,
+    max_depth: usize,
+    max_neighbors: usize,
+    property: String,
+}
+
+impl Default for GraphExpansion {
+    fn default() -> Self {
+        Self {
+            relationship_types: vec![],
+            direction: Direction::default(),
+            max_depth: 1,
+            max_neighbors: 25,
+            property: "neighbors".to_string(),
+        }
+    }
+}
+
+impl GraphExpansion {
+    /// Expand through the relationships of the given types (all types if empty).
+    ///
+    /// Relationship types cannot be passed as parameters of Cypher patterns, so they are
+    /// written in the queries: types which are not identifiers (letters, digits and
+    /// underscores, not starting with a digit) are rejected with a
+    /// [VectorStoreError::FilterError].
+    pub fn new(relationship_types: &[&str]) -> Result<Self, VectorStoreError> {
+        if let Some(invalid) = relationship_types
+            .iter()
+            .find(|t| !is_valid_relationship_type(t))
+        {
+            return Err(VectorStoreError::FilterError(format!(
+                "Invalid relationship type: {invalid:?}"
+            )));
+        }
+
+        Ok(Self {
+            relationship_types: relationship_types.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Set the maximum number of relationships between a node and its neighbors (at least 1).
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth.max(1);
+        self
+    }
+
+    /// Set the maximum number of neighbors fetched for each node.
+    pub fn max_neighbors(mut self, max_neighbors: usize) -> Self {
+        self.max_neighbors = max_neighbors;
+        self
+    }
+
+    /// Set the property of the search results holding the neighbors of the nodes.
+    pub fn property(mut self, property: &str) -> Self {
+        self.property = property.to_string();
+        self
+    }
+
+    pub(crate) fn property_name(&self) -> &str {
+        &self.property
+    }
+
+    /// The Cypher pattern of the paths from `node` to a `neighbor`.
+    fn path_pattern(&self) -> String {
+        let types = if self.relationship_types.is_empty() {
+            String::new()
+        } else {
+            format!(":{}", self.relationship_types.join("|"))
+        };
+        let relationship = format!("[{}*1..{}]", types, self.max_depth);
+
+        match self.direction {
+            Direction::Outgoing => format!("(node)-{relationship}->(neighbor)"),
+            Direction::Incoming => format!("(node)<-{relationship}-(neighbor)"),
+            Direction::Both => format!("(node)-{relationship}-(neighbor)"),
+        }
+    }
+
+    /// A Cypher expression of the list of the neighbors of `node`, in the format of [Neighbor],
+    /// to be added to the map projection of the search results.
+    pub(crate) fn neighbors_expression(&self, embedding_property: &str) -> String {
+        format!(
+            "[path = {} WHERE neighbor <> node | {{ \
+               element_id: ID(neighbor), labels: labels(neighbor), \
+               relationships: [r IN relationships(path) | type(r)], \
+               node: neighbor {{.*, {}:null }} \
+             }}][..$max_neighbors]",
+            self.path_pattern(),
+            embedding_property,
+        )
+    }
+
+    /// The parameters of [Self::neighbors_expression].
+    pub(crate) fn params(&self) -> [(&'static str, i64); 1] {
+        [("max_neighbors", self.max_neighbors as i64)]
+    }
+
+    /// A Cypher query of the neighbors of the node `element_id`, one row per neighbor in the
+    /// format of [Neighbor].
+    pub(crate) fn neighbors_query(&self, embedding_property: &str, element_id: i64) -> Query {
+        Query::new(self.neighbors_cypher(embedding_property))
+            .param("element_id", element_id)
+            .params(self.params())
+    }
+
+    fn neighbors_cypher(&self, embedding_property: &str) -> String {
+        format!(
+            "MATCH path = {} \
+             WHERE ID(node) = $element_id AND neighbor <> node \
+             RETURN ID(neighbor) as element_id, labels(neighbor) as labels, \
+               [r IN relationships(path) | type(r)] as relationships, \
+               neighbor {{.*, {}:null }} as node \
+             LIMIT $max_neighbors",
+            self.path_pattern(),
+            embedding_property,
+        )
+    }
+}
+
+fn is_valid_relationship_type(relationship_type: &str) -> bool {
+    let mut chars = relationship_type.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A neighbor of a node, fetched by a graph expansion.
+#[derive(Debug, Deserialize)]
+pub struct Neighbor<T> {
+    pub element_id: i64,
+    pub labels: Vec<String>,
+    /// Types of the relationships of the path from the node to the neighbor
+    pub relationships: Vec<String>,
+    /// Properties of the neighbor, without its embedding
+    pub node: T,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_pattern() {
+        assert_eq!(
+            GraphExpansion::default().path_pattern(),
+            "(node)-[*1..1]-(neighbor)"
+        );
+        assert_eq!(
+            GraphExpansion::new(&["ACTED_IN", "DIRECTED"])
+                .unwrap()
+                .direction(Direction::Incoming)
+                .max_depth(3)
+                .path_pattern(),
+            "(node)<-[:ACTED_IN|DIRECTED*1..3]-(neighbor)"
+        );
+        assert_eq!(
+            GraphExpansion::new(&["FROM"])
+                .unwrap()
+                .direction(Direction::Outgoing)
+                .max_depth(0)
+                .path_pattern(),
+            "(node)-[:FROM*1..1]->(neighbor)"
+        );
+    }
+
+    #[test]
+    fn test_invalid_relationship_types() {
+        for relationship_type in [
+            "",
+            "1ST",
+            "FROM`]-(n) DETACH DELETE n //",
+            "ACTED IN",
+            "A|B",
+        ] {
+            assert!(matches!(
+                GraphExpansion::new(&["FROM", relationship_type]),
+                Err(VectorStoreError::FilterError(_))
+            ));
+        }
+        assert!(GraphExpansion::new(&["_private", "HAS_2_PARTS"]).is_ok());
+    }
+
+    #[test]
+    fn test_neighbors_expression() {
+        let expansion = GraphExpansion::new(&["FROM"])
+            .unwrap()
+            .direction(Direction::Outgoing)
+            .max_depth(2)
+            .max_neighbors(10);
+
+        assert_eq!(
+            expansion.neighbors_expression("embedding"),
+            "[path = (node)-[:FROM*1..2]->(neighbor) WHERE neighbor <> node | { \
+               element_id: ID(neighbor), labels: labels(neighbor), \
+               relationships: [r IN relationships(path) | type(r)], \
+               node: neighbor {.*, embedding:null } \
+             }][..$max_neighbors]"
+        );
+        assert_eq!(expansion.params(), [("max_neighbors", 10)]);
+    }
+
+    #[test]
+    fn test_neighbors_query() {
+        let expansion = GraphExpansion::new(&["ACTED_IN"]).unwrap().max_neighbors(5);
+
+        assert_eq!(
+            expansion.neighbors_cypher("plotEmbedding"),
+            "MATCH path = (node)-[:ACTED_IN*1..1]-(neighbor) \
+             WHERE ID(node) = $element_id AND neighbor <> node \
+             RETURN ID(neighbor) as element_id, labels(neighbor) as labels, \
+               [r IN relationships(path) | type(r)] as relationships, \
+               neighbor {.*, plotEmbedding:null } as node \
+             LIMIT $max_neighbors"
+        );
+
+        let query = expansion.neighbors_query("plotEmbedding", 42);
+        assert!(query.has_param_key("element_id"));
+        assert!(query.has_param_key("max_neighbors"));
+    }
+}
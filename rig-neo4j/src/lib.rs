@@ -85,6 +85,7 @@
 //!     println!("{:#?}", results);
 //! }
 //! ```
+pub mod graph_expansion;
 pub mod vector_index;
 use std::str::FromStr;
 
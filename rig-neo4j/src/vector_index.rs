@@ -11,7 +11,10 @@ use rig::{
 };
 use serde::{de::Error, Deserialize, Serialize};
 
-use crate::Neo4jClient;
+use crate::{
+    graph_expansion::{GraphExpansion, Neighbor},
+    Neo4jClient,
+};
 
 pub struct Neo4jVectorIndex<M: EmbeddingModel> {
     graph: Graph,
@@ -147,9 +150,17 @@ impl<M: EmbeddingModel> Neo4jVectorIndex<M> {
             BASE_VECTOR_SEARCH_QUERY,
             where_clause,
             if return_node {
+                let neighbors = match &self.search_params.graph_expansion {
+                    Some(expansion) => format!(
+                        ", `{}`: {}",
+                        expansion.property_name().replace('`', "``"),
+                        expansion.neighbors_expression(&self.index_config.embedding_property)
+                    ),
+                    None => "".to_string(),
+                };
                 format!(
-                    ", node {{.*, {}:null }} as node",
-                    self.index_config.embedding_property
+                    ", node {{.*, {}:null{} }} as node",
+                    self.index_config.embedding_property, neighbors
                 )
             } else {
                 "".to_string()
@@ -158,10 +169,35 @@ impl<M: EmbeddingModel> Neo4jVectorIndex<M> {
 
         tracing::debug!("Query before params: {}", query);
 
-        Query::new(query)
+        let query = Query::new(query)
             .param("queryVector", prompt_embedding.vec)
             .param("num_candidates", n as i64)
-            .param("index_name", self.index_config.index_name.clone())
+            .param("index_name", self.index_config.index_name.clone());
+
+        match &self.search_params.graph_expansion {
+            Some(expansion) if return_node => query.params(expansion.params()),
+            _ => query,
+        }
+    }
+
+    /// Fetch the neighbors of the node `element_id` (e.g.: the id of a result of
+    /// [top_n](VectorStoreIndex::top_n)), as specified by `expansion`.
+    ///
+    /// #### Generic Type Parameters
+    ///
+    /// - `T`: The type used to deserialize the properties of the neighbors.
+    pub async fn neighbors<T: for<'a> Deserialize<'a>>(
+        &self,
+        element_id: &str,
+        expansion: &GraphExpansion,
+    ) -> Result<Vec<Neighbor<T>>, VectorStoreError> {
+        let element_id = element_id.parse::<i64>().map_err(|_| {
+            VectorStoreError::MissingIdError(format!("Invalid node id: {element_id}"))
+        })?;
+
+        let query = expansion.neighbors_query(&self.index_config.embedding_property, element_id);
+
+        Neo4jClient::execute_and_collect::<Neighbor<T>>(&self.graph, query).await
     }
 }
 
@@ -170,6 +206,8 @@ pub struct SearchParams {
     /// Sets the **post-filter** field of the search params. Uses a WHERE clause.
     /// See [Neo4j WHERE clause](https://neo4j.com/docs/cypher-manual/current/clauses/where/) for more information.
     post_vector_search_filter: Option<String>,
+    /// Expansion of the nodes returned by `top_n` with their neighbors.
+    graph_expansion: Option<GraphExpansion>,
 }
 
 impl SearchParams {
@@ -177,6 +215,7 @@ impl SearchParams {
     pub fn new(filter: Option<String>) -> Self {
         Self {
             post_vector_search_filter: filter,
+            graph_expansion: None,
         }
     }
 
@@ -184,6 +223,14 @@ impl SearchParams {
         self.post_vector_search_filter = Some(filter);
         self
     }
+
+    /// Expand the nodes returned by `top_n` with their neighbors, added to the nodes under the
+    /// property of the expansion (see [GraphExpansion]). Nodes deserialized into types without
+    /// this property are unaffected.
+    pub fn expand(mut self, expansion: GraphExpansion) -> Self {
+        self.graph_expansion = Some(expansion);
+        self
+    }
 }
 
 impl Default for SearchParams {
@@ -12,7 +12,11 @@ use rig::{
     providers::openai,
     Embed, OneOrMany,
 };
-use rig_neo4j::{vector_index::SearchParams, Neo4jClient, ToBoltType};
+use rig_neo4j::{
+    graph_expansion::{Direction, GraphExpansion},
+    vector_index::SearchParams,
+    Neo4jClient, ToBoltType,
+};
 
 const BOLT_PORT: u16 = 7687;
 const HTTP_PORT: u16 = 7474;
@@ -185,7 +189,7 @@ async fn vector_search_test() {
     // Create a vector index on our vector store
     // IMPORTANT: Reuse the same model that was used to generate the embeddings
     let index = neo4j_client
-        .get_index(model, "vector_index", SearchParams::default())
+        .get_index(model.clone(), "vector_index", SearchParams::default())
         .await
         .expect("");
 
@@ -204,7 +208,51 @@ async fn vector_search_test() {
             "document": "Definition of a *glarb-glarb*: A glarb-glarb is a ancient tool used by the ancestors of the inhabitants of planet Jiro to farm the land.",
             "embedding": serde_json::Value::Null
         })
-    )
+    );
+
+    // Expand the results with their neighbors
+    neo4j_client
+        .graph
+        .run(neo4rs::query(
+            "MATCH (d:DocumentEmbeddings {id: 'doc1'})
+             CREATE (:Planet {name: 'Jiro'})<-[:FROM]-(d)",
+        ))
+        .await
+        .expect("");
+
+    let expansion = GraphExpansion::new(&["FROM"])
+        .expect("")
+        .direction(Direction::Outgoing);
+    let index = neo4j_client
+        .get_index(
+            model,
+            "vector_index",
+            SearchParams::default().expand(expansion.clone()),
+        )
+        .await
+        .expect("");
+
+    let results = index
+        .top_n::<serde_json::Value>("What is a glarb?", 1)
+        .await
+        .expect("");
+    let (_, id, value) = results.first().expect("");
+
+    assert_eq!(
+        value["neighbors"][0]["node"],
+        serde_json::json!({ "name": "Jiro" })
+    );
+    assert_eq!(
+        value["neighbors"][0]["relationships"],
+        serde_json::json!(["FROM"])
+    );
+
+    let neighbors = index
+        .neighbors::<serde_json::Value>(id, &expansion)
+        .await
+        .expect("");
+    assert_eq!(neighbors.len(), 1);
+    assert_eq!(neighbors[0].labels, vec!["Planet".to_string()]);
 }
 
 async fn create_embeddings(model: openai::EmbeddingModel) -> Vec<(Word, OneOrMany<Embedding>)> {
@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use rig::{
     embeddings::{Embedding, EmbeddingModel},
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex},
     Embed, OneOrMany,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -10,6 +10,9 @@ use serde_json::Value;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+mod session;
+pub use session::PostgresSessionStore;
+
 pub struct PostgresVectorStore<Model: EmbeddingModel> {
     model: Model,
     pg_pool: PgPool,
@@ -47,6 +50,30 @@ impl Display for PgVectorDistanceFunction {
     }
 }
 
+impl PgVectorDistanceFunction {
+    /// Operator class of the index supporting the distance function.
+    fn ops(&self) -> &'static str {
+        match self {
+            PgVectorDistanceFunction::L2 => "vector_l2_ops",
+            PgVectorDistanceFunction::InnerProduct => "vector_ip_ops",
+            PgVectorDistanceFunction::Cosine => "vector_cosine_ops",
+            PgVectorDistanceFunction::L1 => "vector_l1_ops",
+            PgVectorDistanceFunction::Hamming => "bit_hamming_ops",
+            PgVectorDistanceFunction::Jaccard => "bit_jaccard_ops",
+        }
+    }
+}
+
+/// Type of the approximate nearest neighbor index created on the embeddings
+/// (see <https://github.com/pgvector/pgvector?tab=readme-ov-file#indexing>).
+pub enum PgVectorIndexType {
+    /// Hierarchical Navigable Small World index: better query performance, slower to build.
+    Hnsw,
+    /// Inverted file index with `lists` lists: faster to build, should be created once the
+    /// table has data (a good starting point is `rows / 1000` lists).
+    IvfFlat { lists: usize },
+}
+
 #[derive(Debug, Deserialize, sqlx::FromRow)]
 pub struct SearchResult {
     id: Uuid,
@@ -88,70 +115,236 @@ impl<Model: EmbeddingModel> PostgresVectorStore<Model> {
         Self::new(model, pg_pool, None, PgVectorDistanceFunction::Cosine)
     }
 
-    fn search_query_full(&self) -> String {
-        self.search_query(true)
+    /// Create the pgvector extension, the documents table (with embeddings of the dimension of
+    /// the embedding model) and the index on the embeddings for the store's distance function,
+    /// if they do not already exist.
+    pub async fn create_table(
+        &self,
+        index_type: PgVectorIndexType,
+    ) -> Result<(), VectorStoreError> {
+        let index = match index_type {
+            PgVectorIndexType::Hnsw => format!("hnsw(embedding {})", self.distance_function.ops()),
+            PgVectorIndexType::IvfFlat { lists } => format!(
+                "ivfflat(embedding {}) WITH (lists = {})",
+                self.distance_function.ops(),
+                lists
+            ),
+        };
+
+        let statements = [
+            "CREATE EXTENSION IF NOT EXISTS vector".to_string(),
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} ( \
+                  id uuid DEFAULT gen_random_uuid(), \
+                  document jsonb NOT NULL, \
+                  embedded_text text NOT NULL, \
+                  embedding vector({}) \
+                )",
+                self.documents_table,
+                self.model.ndims()
+            ),
+            format!(
+                "CREATE INDEX IF NOT EXISTS {}_embeddings_idx ON {} USING {}",
+                self.documents_table, self.documents_table, index
+            ),
+        ];
+
+        for statement in statements {
+            sqlx::query(&statement)
+                .execute(&self.pg_pool)
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+        }
+
+        Ok(())
+    }
+
+    fn search_query_full(&self, filtered: bool) -> String {
+        self.search_query(true, filtered)
     }
-    fn search_query_only_ids(&self) -> String {
-        self.search_query(false)
+    fn search_query_only_ids(&self, filtered: bool) -> String {
+        self.search_query(false, filtered)
     }
 
-    fn search_query(&self, with_document: bool) -> String {
+    /// Search query binding the query embedding as `$1`, the limit as `$2` and, if `filtered`,
+    /// the JSONB filter the documents must contain as `$3`.
+    fn search_query(&self, with_document: bool, filtered: bool) -> String {
         let document = if with_document { ", document" } else { "" };
+        let filter = if filtered {
+            "WHERE document @> $3 "
+        } else {
+            ""
+        };
         format!(
             "
             SELECT id{}, distance FROM ( \
               SELECT DISTINCT ON (id) id{}, embedding {} $1 as distance \
               FROM {} \
+              {}\
               ORDER BY id, distance \
             ) as d \
             ORDER BY distance \
             LIMIT $2",
-            document, document, self.distance_function, self.documents_table
+            document, document, self.distance_function, self.documents_table, filter
         )
     }
 
+    /// Insert the documents with their embeddings, each document under a new random id.
     pub async fn insert_documents<Doc: Serialize + Embed + Send>(
         &self,
         documents: Vec<(Doc, OneOrMany<Embedding>)>,
     ) -> Result<(), VectorStoreError> {
         for (document, embeddings) in documents {
             let id = Uuid::new_v4();
-            let json_document = serde_json::to_value(&document).unwrap();
+            let json_document = serde_json::to_value(&document)?;
 
             for embedding in embeddings {
-                let embedding_text = embedding.document;
-                let embedding: Vec<f64> = embedding.vec;
-
-                sqlx::query(
-                    format!(
-                        "INSERT INTO {} (id, document, embedded_text, embedding) VALUES ($1, $2, $3, $4)",
-                        self.documents_table
-                    )
-                    .as_str(),
-                )
-                .bind(id)
-                .bind(&json_document)
-                .bind(&embedding_text)
-                .bind(&embedding)
-                .execute(&self.pg_pool)
-                .await
-                .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+                self.insert_embedding(&self.pg_pool, id, &json_document, embedding)
+                    .await?;
             }
         }
 
         Ok(())
     }
-}
 
-impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
-    /// Get the top n documents based on the distance to the given query.
-    /// The result is a list of tuples of the form (score, id, document)
-    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+    /// Insert or replace the documents with the given ids: the previous embeddings of each id
+    /// are removed, so re-embedding a changed document does not leave stale entries behind.
+    /// All the documents are upserted in a single transaction.
+    pub async fn upsert_documents<Doc: Serialize + Embed + Send>(
+        &self,
+        documents: Vec<(Uuid, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        let documents = documents
+            .into_iter()
+            .map(|(id, document, embeddings)| {
+                Ok((id, serde_json::to_value(&document)?, embeddings))
+            })
+            .collect::<Result<Vec<_>, VectorStoreError>>()?;
+
+        self.write_documents(documents).await
+    }
+
+    /// Replace the documents with the given ids (see [Self::upsert_documents]).
+    async fn write_documents(
+        &self,
+        documents: Vec<(Uuid, Value, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        let mut transaction = self
+            .pg_pool
+            .begin()
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        for (id, json_document, embeddings) in documents {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE id = $1",
+                self.documents_table
+            ))
+            .bind(id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+            for embedding in embeddings {
+                self.insert_embedding(&mut *transaction, id, &json_document, embedding)
+                    .await?;
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    /// The ids among `ids` which exist in the table.
+    async fn existing_ids(&self, ids: &[Uuid]) -> Result<Vec<Uuid>, VectorStoreError> {
+        sqlx::query_scalar(&format!(
+            "SELECT DISTINCT id FROM {} WHERE id = ANY($1)",
+            self.documents_table
+        ))
+        .bind(ids)
+        .fetch_all(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    async fn insert_embedding<'e>(
+        &self,
+        executor: impl sqlx::PgExecutor<'e>,
+        id: Uuid,
+        json_document: &Value,
+        embedding: Embedding,
+    ) -> Result<(), VectorStoreError> {
+        let embedding_text = embedding.document;
+        let embedding: Vec<f64> = embedding.vec;
+
+        sqlx::query(
+            format!(
+                "INSERT INTO {} (id, document, embedded_text, embedding) VALUES ($1, $2, $3, $4)",
+                self.documents_table
+            )
+            .as_str(),
+        )
+        .bind(id)
+        .bind(json_document)
+        .bind(&embedding_text)
+        .bind(&embedding)
+        .execute(executor)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+
+        Ok(())
+    }
+
+    /// Same as `top_n`, but only among the documents containing `filter` (JSONB containment,
+    /// e.g.: `json!({"category": "animals"})` matches the documents whose `category` field is
+    /// `"animals"`).
+    pub async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
         &self,
         query: &str,
         n: usize,
+        filter: Value,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let embedded_query: pgvector::Vector = self
+        let rows: Vec<SearchResult> = sqlx::query_as(self.search_query_full(true).as_str())
+            .bind(self.embed_query(query).await?)
+            .bind(n as i64)
+            .bind(filter)
+            .fetch_all(&self.pg_pool)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .flat_map(SearchResult::into_result)
+            .collect())
+    }
+
+    /// Same as `top_n_ids`, but only among the documents containing `filter`
+    /// (see [Self::top_n_with_filter]).
+    pub async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Value,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let rows: Vec<SearchResultOnlyId> =
+            sqlx::query_as(self.search_query_only_ids(true).as_str())
+                .bind(self.embed_query(query).await?)
+                .bind(n as i64)
+                .bind(filter)
+                .fetch_all(&self.pg_pool)
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.distance, row.id.to_string()))
+            .collect())
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<pgvector::Vector, VectorStoreError> {
+        Ok(self
             .model
             .embed_text(query)
             .await?
@@ -159,9 +352,21 @@ impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
             .iter()
             .map(|&x| x as f32)
             .collect::<Vec<f32>>()
-            .into();
+            .into())
+    }
+}
 
-        let rows: Vec<SearchResult> = sqlx::query_as(self.search_query_full().as_str())
+impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
+    /// Get the top n documents based on the distance to the given query.
+    /// The result is a list of tuples of the form (score, id, document)
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let embedded_query = self.embed_query(query).await?;
+
+        let rows: Vec<SearchResult> = sqlx::query_as(self.search_query_full(false).as_str())
             .bind(embedded_query)
             .bind(n as i64)
             .fetch_all(&self.pg_pool)
@@ -182,22 +387,15 @@ impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let embedded_query: pgvector::Vector = self
-            .model
-            .embed_text(query)
-            .await?
-            .vec
-            .iter()
-            .map(|&x| x as f32)
-            .collect::<Vec<f32>>()
-            .into();
+        let embedded_query = self.embed_query(query).await?;
 
-        let rows: Vec<SearchResultOnlyId> = sqlx::query_as(self.search_query_only_ids().as_str())
-            .bind(embedded_query)
-            .bind(n as i64)
-            .fetch_all(&self.pg_pool)
-            .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+        let rows: Vec<SearchResultOnlyId> =
+            sqlx::query_as(self.search_query_only_ids(false).as_str())
+                .bind(embedded_query)
+                .bind(n as i64)
+                .fetch_all(&self.pg_pool)
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
 
         let rows: Vec<(f64, String)> = rows
             .into_iter()
@@ -207,3 +405,50 @@ impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
         Ok(rows)
     }
 }
+
+impl<Model: EmbeddingModel> VectorStore for PostgresVectorStore<Model> {
+    /// Document id, JSON document and embeddings of the document
+    type Document = (Uuid, Value, OneOrMany<Embedding>);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        if !upsert {
+            let ids = documents.iter().map(|(id, _, _)| *id).collect::<Vec<_>>();
+            if let Some(id) = self.existing_ids(&ids).await?.into_iter().next() {
+                return Err(VectorStoreError::DuplicateIdError(id.to_string()));
+            }
+        }
+
+        self.write_documents(documents).await
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        if self.existing_ids(&[document.0]).await?.is_empty() {
+            return Err(VectorStoreError::MissingIdError(document.0.to_string()));
+        }
+
+        self.write_documents(vec![document]).await
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        // Ids which are not UUIDs cannot exist in the table
+        let ids = ids
+            .iter()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect::<Vec<_>>();
+
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE id = ANY($1)",
+            self.documents_table
+        ))
+        .bind(ids)
+        .execute(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
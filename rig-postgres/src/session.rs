@@ -0,0 +1,207 @@
+use rig::{
+    completion::Message,
+    session::{SessionError, SessionStore},
+};
+use serde_json::Value;
+use sqlx::PgPool;
+
+/// Session store keeping the messages of each session as rows of a PostgreSQL table, in the
+/// order they were appended.
+pub struct PostgresSessionStore {
+    pg_pool: PgPool,
+    sessions_table: String,
+}
+
+impl PostgresSessionStore {
+    /// Create a store using the `sessions` table (see [Self::create_table]).
+    pub fn new(pg_pool: PgPool) -> Self {
+        Self {
+            pg_pool,
+            sessions_table: String::from("sessions"),
+        }
+    }
+
+    /// Set the name of the table of the messages (default: `sessions`).
+    pub fn sessions_table(mut self, sessions_table: &str) -> Self {
+        self.sessions_table = sessions_table.to_string();
+        self
+    }
+
+    /// Create the table of the messages and its index on the session ids, if they do not
+    /// already exist.
+    pub async fn create_table(&self) -> Result<(), SessionError> {
+        for statement in create_table_statements(&self.sessions_table) {
+            sqlx::query(&statement)
+                .execute(&self.pg_pool)
+                .await
+                .map_err(|e| SessionError::DatastoreError(Box::new(e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn create_table_statements(table: &str) -> [String; 2] {
+    [
+        format!(
+            "CREATE TABLE IF NOT EXISTS {table} ( \
+              id bigserial PRIMARY KEY, \
+              session_id text NOT NULL, \
+              message jsonb NOT NULL, \
+              created_at timestamptz NOT NULL DEFAULT now() \
+            )"
+        ),
+        format!("CREATE INDEX IF NOT EXISTS {table}_session_id_idx ON {table} (session_id, id)"),
+    ]
+}
+
+/// Query of the messages of the session `$1`, in the order they were appended.
+fn select_query(table: &str) -> String {
+    format!("SELECT message FROM {table} WHERE session_id = $1 ORDER BY id")
+}
+
+/// Insertion of the messages `$2` in the session `$1`. Messages are inserted in their order,
+/// so that their ids follow it.
+fn insert_query(table: &str) -> String {
+    format!(
+        "INSERT INTO {table} (session_id, message) \
+         SELECT $1, message FROM UNNEST($2::jsonb[]) WITH ORDINALITY AS m(message, i) \
+         ORDER BY i"
+    )
+}
+
+/// Deletion of all but the `$2` most recent messages of the session `$1`.
+fn trim_query(table: &str) -> String {
+    format!(
+        "DELETE FROM {table} WHERE session_id = $1 AND id NOT IN ( \
+          SELECT id FROM {table} WHERE session_id = $1 ORDER BY id DESC LIMIT $2 \
+        )"
+    )
+}
+
+fn to_rows(messages: &[Message]) -> Result<Vec<Value>, SessionError> {
+    Ok(messages
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+fn from_rows(rows: Vec<Value>) -> Result<Vec<Message>, SessionError> {
+    rows.into_iter()
+        .map(|message| Ok(serde_json::from_value(message)?))
+        .collect()
+}
+
+impl SessionStore for PostgresSessionStore {
+    async fn get(&self, session_id: &str) -> Result<Vec<Message>, SessionError> {
+        let messages: Vec<Value> = sqlx::query_scalar(&select_query(&self.sessions_table))
+            .bind(session_id)
+            .fetch_all(&self.pg_pool)
+            .await
+            .map_err(|e| SessionError::DatastoreError(Box::new(e)))?;
+
+        from_rows(messages)
+    }
+
+    async fn append(&self, session_id: &str, messages: Vec<Message>) -> Result<(), SessionError> {
+        sqlx::query(&insert_query(&self.sessions_table))
+            .bind(session_id)
+            .bind(to_rows(&messages)?)
+            .execute(&self.pg_pool)
+            .await
+            .map_err(|e| SessionError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    async fn trim(&self, session_id: &str, max_messages: usize) -> Result<(), SessionError> {
+        sqlx::query(&trim_query(&self.sessions_table))
+            .bind(session_id)
+            .bind(max_messages as i64)
+            .execute(&self.pg_pool)
+            .await
+            .map_err(|e| SessionError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::{
+        completion::message::{AssistantContent, Message, ToolResultContent, UserContent},
+        OneOrMany,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_queries() {
+        assert_eq!(
+            create_table_statements("chat_sessions"),
+            [
+                "CREATE TABLE IF NOT EXISTS chat_sessions ( \
+                  id bigserial PRIMARY KEY, \
+                  session_id text NOT NULL, \
+                  message jsonb NOT NULL, \
+                  created_at timestamptz NOT NULL DEFAULT now() \
+                )",
+                "CREATE INDEX IF NOT EXISTS chat_sessions_session_id_idx \
+                 ON chat_sessions (session_id, id)"
+            ]
+        );
+        assert_eq!(
+            select_query("chat_sessions"),
+            "SELECT message FROM chat_sessions WHERE session_id = $1 ORDER BY id"
+        );
+        assert_eq!(
+            insert_query("chat_sessions"),
+            "INSERT INTO chat_sessions (session_id, message) \
+             SELECT $1, message FROM UNNEST($2::jsonb[]) WITH ORDINALITY AS m(message, i) \
+             ORDER BY i"
+        );
+        assert_eq!(
+            trim_query("chat_sessions"),
+            "DELETE FROM chat_sessions WHERE session_id = $1 AND id NOT IN ( \
+              SELECT id FROM chat_sessions WHERE session_id = $1 ORDER BY id DESC LIMIT $2 \
+            )"
+        );
+    }
+
+    #[test]
+    fn test_rows_round_trip() {
+        let messages = vec![
+            Message::user("What is the weather in Paris?"),
+            Message::Assistant {
+                content: OneOrMany::one(AssistantContent::tool_call(
+                    "call_1",
+                    "weather",
+                    json!({"city": "Paris"}),
+                )),
+            },
+            Message::User {
+                content: OneOrMany::one(UserContent::tool_result(
+                    "call_1",
+                    OneOrMany::one(ToolResultContent::text("sunny")),
+                )),
+            },
+            Message::assistant("It is sunny in Paris."),
+        ];
+
+        let rows = to_rows(&messages).unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0]["role"], "user");
+        assert_eq!(rows[1]["role"], "assistant");
+
+        assert_eq!(from_rows(rows).unwrap(), messages);
+    }
+
+    #[test]
+    fn test_invalid_row() {
+        assert!(matches!(
+            from_rows(vec![json!({"role": "narrator"})]),
+            Err(SessionError::JsonError(_))
+        ));
+    }
+}
@@ -1,5 +1,12 @@
-use rig::{embeddings::EmbeddingsBuilder, vector_store::VectorStoreIndex, Embed};
-use rig_postgres::PostgresVectorStore;
+use rig::{
+    completion::message::{AssistantContent, Message, ToolResultContent, UserContent},
+    embeddings::{EmbeddingModel, EmbeddingsBuilder},
+    providers::mock::MockEmbeddingModel,
+    session::SessionStore,
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex},
+    Embed, OneOrMany,
+};
+use rig_postgres::{PostgresSessionStore, PostgresVectorStore};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::{postgres::PgPoolOptions, PgPool};
@@ -129,6 +136,158 @@ async fn vector_search_test() {
     assert_eq!(id, full_query_id);
 }
 
+#[tokio::test]
+async fn upsert_test() {
+    let container = start_container().await;
+
+    let host = container.get_host().await.unwrap().to_string();
+    let port = container
+        .get_host_port_ipv4(POSTGRES_PORT)
+        .await
+        .expect("Error getting docker port");
+
+    let pg_pool = connect_to_postgres(host, port).await;
+
+    sqlx::migrate!("./tests/migrations")
+        .run(&pg_pool)
+        .await
+        .expect("Failed to run migrations");
+
+    let model = MockEmbeddingModel::new(1536);
+    let mut vector_store = PostgresVectorStore::with_defaults(model.clone(), pg_pool.clone());
+
+    let id = uuid::Uuid::new_v4();
+    let document = |name: &str, texts: &[&str]| {
+        let model = model.clone();
+        let texts = texts
+            .iter()
+            .map(|text| text.to_string())
+            .collect::<Vec<_>>();
+        let name = name.to_string();
+        async move {
+            let embeddings = model.embed_texts(texts).await.unwrap();
+            (
+                id,
+                json!({ "name": name }),
+                OneOrMany::many(embeddings).unwrap(),
+            )
+        }
+    };
+    let rows = |pg_pool: PgPool| async move {
+        sqlx::query_as::<_, (serde_json::Value,)>("SELECT document FROM documents WHERE id = $1")
+            .bind(id)
+            .fetch_all(&pg_pool)
+            .await
+            .unwrap()
+    };
+
+    VectorStore::insert_documents(
+        &mut vector_store,
+        vec![document("flurbo", &["a", "b"]).await],
+        false,
+    )
+    .await
+    .expect("Failed to insert document");
+    assert_eq!(rows(pg_pool.clone()).await.len(), 2);
+
+    // Without upsert, an existing id is rejected and the document is left untouched
+    let result = VectorStore::insert_documents(
+        &mut vector_store,
+        vec![document("glarb-glarb", &["c"]).await],
+        false,
+    )
+    .await;
+    assert!(
+        matches!(result, Err(VectorStoreError::DuplicateIdError(dup)) if dup == id.to_string())
+    );
+    assert_eq!(rows(pg_pool.clone()).await.len(), 2);
+
+    // With upsert, the previous embeddings of the document are replaced
+    VectorStore::insert_documents(
+        &mut vector_store,
+        vec![document("glarb-glarb", &["c"]).await],
+        true,
+    )
+    .await
+    .expect("Failed to upsert document");
+    assert_eq!(
+        rows(pg_pool.clone()).await,
+        vec![(json!({ "name": "glarb-glarb" }),)]
+    );
+
+    vector_store
+        .delete_documents(&[id.to_string()])
+        .await
+        .expect("Failed to delete document");
+    assert!(rows(pg_pool.clone()).await.is_empty());
+    assert!(matches!(
+        vector_store
+            .update_document(document("flurbo", &["a"]).await)
+            .await,
+        Err(VectorStoreError::MissingIdError(_))
+    ));
+}
+
+#[tokio::test]
+async fn session_store_test() {
+    let container = start_container().await;
+
+    let host = container.get_host().await.unwrap().to_string();
+    let port = container
+        .get_host_port_ipv4(POSTGRES_PORT)
+        .await
+        .expect("Error getting docker port");
+
+    let pg_pool = connect_to_postgres(host, port).await;
+
+    let store = PostgresSessionStore::new(pg_pool);
+    store
+        .create_table()
+        .await
+        .expect("Failed to create sessions table");
+
+    store
+        .append("a", vec![Message::user("Hello"), Message::assistant("Hi!")])
+        .await
+        .expect("Failed to append messages");
+    store
+        .append("a", vec![Message::user("How are you?")])
+        .await
+        .expect("Failed to append messages");
+
+    assert_eq!(store.get("a").await.unwrap().len(), 3);
+    assert!(store.get("b").await.unwrap().is_empty());
+
+    store.trim("a", 2).await.expect("Failed to trim session");
+    assert_eq!(
+        store.get("a").await.unwrap(),
+        vec![Message::assistant("Hi!"), Message::user("How are you?")]
+    );
+
+    // Tool calls and results are stored as jsonb and read back unchanged
+    let messages = vec![
+        Message::user("What is the weather in Paris?"),
+        Message::Assistant {
+            content: OneOrMany::one(AssistantContent::tool_call(
+                "call_1",
+                "weather",
+                json!({"city": "Paris"}),
+            )),
+        },
+        Message::User {
+            content: OneOrMany::one(UserContent::tool_result(
+                "call_1",
+                OneOrMany::one(ToolResultContent::text("sunny")),
+            )),
+        },
+    ];
+    store
+        .append("b", messages.clone())
+        .await
+        .expect("Failed to append messages");
+    assert_eq!(store.get("b").await.unwrap(), messages);
+}
+
 async fn start_container() -> ContainerAsync<GenericImage> {
     // Setup a local postgres container for testing. NOTE: docker service must be running.
     GenericImage::new("pgvector/pgvector", "pg17")
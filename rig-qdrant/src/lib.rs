@@ -1,6 +1,7 @@
 use qdrant_client::{
     qdrant::{
-        point_id::PointIdOptions, PointId, PointStruct, Query, QueryPoints, UpsertPointsBuilder,
+        point_id::PointIdOptions, CreateCollectionBuilder, Distance, Filter, PointId, PointStruct,
+        Query, QueryPoints, ScoredPoint, UpsertPointsBuilder, VectorParamsBuilder,
     },
     Payload, Qdrant,
 };
@@ -42,6 +43,92 @@ impl<M: EmbeddingModel> QdrantVectorStore<M> {
         &self.client
     }
 
+    /// Set the filter applied to the payloads of the points of all searches (e.g.: to only search
+    /// the documents of a given user).
+    /// Reference: <https://qdrant.tech/documentation/concepts/filtering/>
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.query_params.filter = Some(filter);
+        self
+    }
+
+    /// Create the collection of the vector store (with vectors of the dimension of the
+    /// embedding model and the given distance metric), if it does not already exist.
+    pub async fn create_collection(&self, distance: Distance) -> Result<(), VectorStoreError> {
+        let collection_name = &self.query_params.collection_name;
+
+        let exists = self
+            .client
+            .collection_exists(collection_name)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        if !exists {
+            self.client
+                .create_collection(
+                    CreateCollectionBuilder::new(collection_name).vectors_config(
+                        VectorParamsBuilder::new(self.model.ndims() as u64, distance),
+                    ),
+                )
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Search for the top `n` nearest neighbors to the given query, optionally only among the
+    /// points whose payload matches `filter` (in addition to the store's filter, if any).
+    pub async fn top_n_from_query<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let query = Query::new_nearest(self.generate_query_vector(query).await?);
+        let points = self.query_points(Some(query), n, filter).await?;
+        points.into_iter().map(parse_point).collect()
+    }
+
+    /// Search for the top `n` nearest neighbors to the given embedding (e.g.: of a document
+    /// already embedded), optionally only among the points whose payload matches `filter`
+    /// (in addition to the store's filter, if any).
+    pub async fn top_n_from_embedding<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        embedding: &Embedding,
+        n: usize,
+        filter: Option<Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let vector = embedding.vec.iter().map(|&x| x as f32).collect::<Vec<_>>();
+        let points = self
+            .query_points(Some(Query::new_nearest(vector)), n, filter)
+            .await?;
+        points.into_iter().map(parse_point).collect()
+    }
+
+    /// Query the collection with the store's query parameters, combining `filter` with the
+    /// store's filter.
+    async fn query_points(
+        &self,
+        query: Option<Query>,
+        n: usize,
+        filter: Option<Filter>,
+    ) -> Result<Vec<ScoredPoint>, VectorStoreError> {
+        let mut params = self.prepare_query_params(query, n);
+        params.filter = match (params.filter, filter) {
+            (Some(store_filter), Some(filter)) => {
+                Some(Filter::must([store_filter.into(), filter.into()]))
+            }
+            (store_filter, filter) => filter.or(store_filter),
+        };
+
+        Ok(self
+            .client
+            .query(params)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .result)
+    }
+
     /// Embed query based on `QdrantVectorStore` model and modify the vector in the required format.
     async fn generate_query_vector(&self, query: &str) -> Result<Vec<f32>, VectorStoreError> {
         let embedding = self.model.embed_text(query).await?;
@@ -63,8 +150,12 @@ impl<M: EmbeddingModel> QdrantVectorStore<M> {
         let collection_name = self.query_params.collection_name.clone();
 
         for (document, embeddings) in documents {
-            let json_document = serde_json::to_value(&document).unwrap();
-            let doc_as_payload = Payload::try_from(json_document).unwrap();
+            let json_document = serde_json::to_value(&document)?;
+            let doc_as_payload = Payload::try_from(json_document).map_err(|err| {
+                VectorStoreError::DatastoreError(
+                    format!("Document is not a valid payload: {err}").into(),
+                )
+            })?;
 
             let embeddings_as_point_structs = embeddings
                 .into_iter()
@@ -79,7 +170,9 @@ impl<M: EmbeddingModel> QdrantVectorStore<M> {
                 })
                 .collect::<Vec<PointStruct>>();
 
-            let request = UpsertPointsBuilder::new(&collection_name, embeddings_as_point_structs);
+            // Wait for the points to be indexed, so that they can be searched right away
+            let request =
+                UpsertPointsBuilder::new(&collection_name, embeddings_as_point_structs).wait(true);
             self.client.upsert_points(request).await.map_err(|err| {
                 VectorStoreError::DatastoreError(format!("Error while upserting: {err}").into())
             })?;
@@ -89,6 +182,20 @@ impl<M: EmbeddingModel> QdrantVectorStore<M> {
     }
 }
 
+/// Converts a scored point to a tuple of its score, ID and deserialized payload.
+fn parse_point<T: for<'a> Deserialize<'a>>(
+    point: ScoredPoint,
+) -> Result<(f64, String, T), VectorStoreError> {
+    let id = stringify_id(
+        point
+            .id
+            .ok_or_else(|| VectorStoreError::DatastoreError("Missing point ID".into()))?,
+    )?;
+    let score = point.score as f64;
+    let payload = serde_json::from_value(serde_json::to_value(point.payload)?)?;
+    Ok((score, id, payload))
+}
+
 /// Converts a `PointId` to its string representation.
 fn stringify_id(id: PointId) -> Result<String, VectorStoreError> {
     match id.point_id_options {
@@ -113,26 +220,8 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for QdrantVe
             None => Some(Query::new_nearest(self.generate_query_vector(query).await?)),
         };
 
-        let params = self.prepare_query_params(query, n);
-        let result = self
-            .client
-            .query(params)
-            .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
-
-        result
-            .result
-            .into_iter()
-            .map(|item| {
-                let id =
-                    stringify_id(item.id.ok_or_else(|| {
-                        VectorStoreError::DatastoreError("Missing point ID".into())
-                    })?)?;
-                let score = item.score as f64;
-                let payload = serde_json::from_value(serde_json::to_value(item.payload)?)?;
-                Ok((score, id, payload))
-            })
-            .collect()
+        let points = self.query_points(query, n, None).await?;
+        points.into_iter().map(parse_point).collect()
     }
 
     /// Search for the top `n` nearest neighbors to the given query within the Qdrant vector store.
@@ -147,13 +236,7 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for QdrantVe
             None => Some(Query::new_nearest(self.generate_query_vector(query).await?)),
         };
 
-        let params = self.prepare_query_params(query, n);
-        let points = self
-            .client
-            .query(params)
-            .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
-            .result;
+        let points = self.query_points(query, n, None).await?;
 
         points
             .into_iter()
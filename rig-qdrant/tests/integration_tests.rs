@@ -2,18 +2,21 @@ use serde_json::json;
 use testcontainers::{
     core::{IntoContainerPort, WaitFor},
     runners::AsyncRunner,
-    GenericImage,
+    ContainerAsync, GenericImage,
 };
 
 use qdrant_client::{
     qdrant::{
-        CreateCollectionBuilder, Distance, PointStruct, QueryPointsBuilder, UpsertPointsBuilder,
-        VectorParamsBuilder,
+        Condition, CreateCollectionBuilder, Distance, Filter, PointStruct, QueryPointsBuilder,
+        UpsertPointsBuilder, VectorParamsBuilder,
     },
     Payload, Qdrant,
 };
 use rig::{
-    embeddings::EmbeddingsBuilder, providers::openai, vector_store::VectorStoreIndex, Embed,
+    embeddings::{EmbeddingModel, EmbeddingsBuilder},
+    providers::{mock::MockEmbeddingModel, openai},
+    vector_store::VectorStoreIndex,
+    Embed,
 };
 use rig_qdrant::QdrantVectorStore;
 
@@ -28,28 +31,16 @@ struct Word {
     definition: String,
 }
 
+#[derive(Embed, Clone, serde::Deserialize, serde::Serialize, Debug, PartialEq)]
+struct Note {
+    category: String,
+    #[embed]
+    text: String,
+}
+
 #[tokio::test]
 async fn vector_search_test() {
-    // Setup a local qdrant container for testing. NOTE: docker service must be running.
-    let container = GenericImage::new("qdrant/qdrant", "latest")
-        .with_wait_for(WaitFor::Duration {
-            length: std::time::Duration::from_secs(5),
-        })
-        .with_exposed_port(QDRANT_PORT.tcp())
-        .with_exposed_port(QDRANT_PORT_SECONDARY.tcp())
-        .start()
-        .await
-        .expect("Failed to start qdrant container");
-
-    let port = container
-        .get_host_port_ipv4(QDRANT_PORT_SECONDARY)
-        .await
-        .unwrap();
-    let host = container.get_host().await.unwrap().to_string();
-
-    let client = Qdrant::from_url(&format!("http://{host}:{port}"))
-        .build()
-        .unwrap();
+    let (_container, client) = start_container().await;
 
     // Create a collection with 1536 dimensions if it doesn't exist
     // Note: Make sure the dimensions match the size of the embeddings returned by the
@@ -205,3 +196,104 @@ async fn create_points(model: openai::EmbeddingModel) -> Vec<PointStruct> {
         })
         .collect()
 }
+
+#[tokio::test]
+async fn filtered_search_test() {
+    let (_container, client) = start_container().await;
+
+    let model = MockEmbeddingModel::new(64);
+    let query_params = QueryPointsBuilder::new("rig-notes").with_payload(true);
+    let vector_store = QdrantVectorStore::new(client, model.clone(), query_params.build());
+
+    // Creating the collection is idempotent
+    vector_store
+        .create_collection(Distance::Cosine)
+        .await
+        .unwrap();
+    vector_store
+        .create_collection(Distance::Cosine)
+        .await
+        .unwrap();
+
+    let notes = [
+        ("animals", "the ancient dog sleeps in the barn"),
+        ("animals", "a green cat hunts at night"),
+        ("tools", "an ancient tool used to farm the land"),
+    ]
+    .map(|(category, text)| Note {
+        category: category.to_string(),
+        text: text.to_string(),
+    });
+    let documents = EmbeddingsBuilder::new(model.clone())
+        .documents(notes.clone())
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    vector_store.insert_documents(documents).await.unwrap();
+
+    let category =
+        |category: &str| Filter::must([Condition::matches("category", category.to_string())]);
+
+    // Without filter, the closest note is found among all the notes
+    let results = vector_store
+        .top_n_from_query::<Note>("ancient tool", 1, None)
+        .await
+        .unwrap();
+    assert_eq!(results[0].2, notes[2]);
+
+    // With a filter, only the matching notes are searched
+    let results = vector_store
+        .top_n_from_query::<Note>("ancient tool", 3, Some(category("animals")))
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].2, notes[0]);
+    assert!(results
+        .iter()
+        .all(|(_, _, note)| note.category == "animals"));
+
+    // Searching by embedding finds the embedded note itself
+    let embedding = model.embed_text(&notes[1].text).await.unwrap();
+    let results = vector_store
+        .top_n_from_embedding::<Note>(&embedding, 1, None)
+        .await
+        .unwrap();
+    assert_eq!(results[0].2, notes[1]);
+    assert!(results[0].0 > 0.99);
+
+    // The filter of the store and the filter of the search must both match
+    let vector_store = vector_store.with_filter(category("tools"));
+    let results = vector_store.top_n::<Note>("ancient tool", 3).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(vector_store
+        .top_n_from_embedding::<Note>(&embedding, 3, Some(category("animals")))
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// Setup a local qdrant container for testing. NOTE: docker service must be running.
+async fn start_container() -> (ContainerAsync<GenericImage>, Qdrant) {
+    let container = GenericImage::new("qdrant/qdrant", "latest")
+        .with_wait_for(WaitFor::Duration {
+            length: std::time::Duration::from_secs(5),
+        })
+        .with_exposed_port(QDRANT_PORT.tcp())
+        .with_exposed_port(QDRANT_PORT_SECONDARY.tcp())
+        .start()
+        .await
+        .expect("Failed to start qdrant container");
+
+    let port = container
+        .get_host_port_ipv4(QDRANT_PORT_SECONDARY)
+        .await
+        .unwrap();
+    let host = container.get_host().await.unwrap().to_string();
+
+    let client = Qdrant::from_url(&format!("http://{host}:{port}"))
+        .build()
+        .unwrap();
+
+    (container, client)
+}
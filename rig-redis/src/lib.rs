@@ -0,0 +1,675 @@
+use std::{collections::HashMap, time::Duration};
+
+use redis::{aio::ConnectionManager, FromRedisValue, RedisError};
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex, VectorStoreStats},
+    OneOrMany,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+mod session;
+pub use session::RedisSessionStore;
+
+/// Number of keys fetched by each page of the searches of the keys of documents
+const PAGE_SIZE: usize = 1000;
+
+/// Distance metric of the vector field of the index
+/// (see <https://redis.io/docs/latest/develop/interact/search-and-query/advanced-concepts/vectors/#distance-metrics>).
+#[derive(Clone, Copy, Debug, Default)]
+pub enum RedisDistanceMetric {
+    #[default]
+    Cosine,
+    InnerProduct,
+    L2,
+}
+
+impl RedisDistanceMetric {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RedisDistanceMetric::Cosine => "COSINE",
+            RedisDistanceMetric::InnerProduct => "IP",
+            RedisDistanceMetric::L2 => "L2",
+        }
+    }
+
+    /// Converts a distance returned by RediSearch to a score, higher scores being better.
+    fn score(&self, distance: f64) -> f64 {
+        match self {
+            // RediSearch returns 1 - cosine similarity and 1 - inner product
+            RedisDistanceMetric::Cosine | RedisDistanceMetric::InnerProduct => 1.0 - distance,
+            // and the squared Euclidean distance, converted to the similarity 1 / (1 + d)
+            RedisDistanceMetric::L2 => 1.0 / (1.0 + distance.max(0.0).sqrt()),
+        }
+    }
+}
+
+/// Type of the index of the vector field
+/// (see <https://redis.io/docs/latest/develop/interact/search-and-query/advanced-concepts/vectors/#create-a-vector-index>).
+#[derive(Clone, Copy, Debug)]
+pub enum RedisIndexType {
+    /// Exact (brute force) search: best accuracy, for small stores.
+    Flat,
+    /// Hierarchical Navigable Small World index, with `m` connections per node and a
+    /// candidate list of `ef_construction` nodes when building the graph.
+    Hnsw { m: usize, ef_construction: usize },
+}
+
+impl RedisIndexType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RedisIndexType::Flat => "FLAT",
+            RedisIndexType::Hnsw { .. } => "HNSW",
+        }
+    }
+}
+
+impl Default for RedisIndexType {
+    /// HNSW index with the default parameters of RediSearch.
+    fn default() -> Self {
+        RedisIndexType::Hnsw {
+            m: 16,
+            ef_construction: 200,
+        }
+    }
+}
+
+fn redis_to_rig_error(e: RedisError) -> VectorStoreError {
+    VectorStoreError::DatastoreError(Box::new(e))
+}
+
+/// Escapes the punctuation and whitespace of `value`, for use in a RediSearch tag query.
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if !c.is_alphanumeric() && c != '_' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// RediSearch query matching the hashes of the documents with the given ids.
+fn document_id_query(ids: &[String]) -> String {
+    format!(
+        "@document_id:{{{}}}",
+        ids.iter()
+            .map(|id| escape_tag(id))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    )
+}
+
+/// `FT.SEARCH` command searching the index `index_name` for the `k` nearest embeddings of
+/// `vector`, sorted by distance.
+fn knn_command(index_name: &str, vector: &[u8], k: usize, with_documents: bool) -> redis::Cmd {
+    let mut fields = vec!["score", "document_id"];
+    if with_documents {
+        fields.push("document");
+    }
+
+    let mut cmd = redis::cmd("FT.SEARCH");
+    cmd.arg(index_name)
+        .arg(format!("*=>[KNN {k} @embedding $vector AS score]"))
+        .arg("PARAMS")
+        .arg(2)
+        .arg("vector")
+        .arg(vector)
+        .arg("SORTBY")
+        .arg("score")
+        .arg("RETURN")
+        .arg(fields.len())
+        .arg(fields)
+        .arg("LIMIT")
+        .arg(0)
+        .arg(k)
+        .arg("DIALECT")
+        .arg(2);
+    cmd
+}
+
+/// Converts an embedding to the binary format of the vector field (little-endian `f32`s).
+fn vector_bytes(vec: &[f64]) -> Vec<u8> {
+    vec.iter().flat_map(|x| (*x as f32).to_le_bytes()).collect()
+}
+
+/// Key and fields of a hash returned by `FT.SEARCH`
+type Hit = (String, HashMap<String, String>);
+
+/// Converts the hits of a `FT.SEARCH` response to tuples of their keys and fields.
+fn parse_hits(response: Vec<redis::Value>) -> Result<Vec<Hit>, VectorStoreError> {
+    response
+        .get(1..)
+        .unwrap_or_default()
+        .chunks(2)
+        .map(|hit| {
+            let key = String::from_redis_value(&hit[0]).map_err(redis_to_rig_error)?;
+            let fields = match hit.get(1) {
+                Some(fields) => HashMap::from_redis_value(fields).map_err(redis_to_rig_error)?,
+                None => HashMap::new(),
+            };
+            Ok((key, fields))
+        })
+        .collect()
+}
+
+/// Vector store backed by a RediSearch index over Redis hashes.
+///
+/// Each embedding of a document is stored as a hash under the key `{prefix}{id}:{i}`, with the
+/// id of the document (`document_id`), the document serialized as JSON (`document`) and the
+/// embedding as little-endian `f32`s (`embedding`). Documents can be given a time to live (see
+/// [Self::ttl] and [Self::expire_document]), so that short-lived context (e.g.: the scratch
+/// memory of a session) expires automatically.
+///
+/// # Example
+/// ```rust,ignore
+/// use rig::vector_store::{VectorStore, VectorStoreIndex};
+/// use rig_redis::{RedisIndexType, RedisVectorStore};
+///
+/// let client = redis::Client::open("redis://127.0.0.1/")?;
+/// let mut store = RedisVectorStore::new(client.get_connection_manager().await?, model, "scratch")
+///     .ttl(std::time::Duration::from_secs(3600));
+/// store.create_index(RedisIndexType::default()).await?;
+///
+/// store.insert_documents(vec![("note-1".to_string(), document, embeddings)], true).await?;
+///
+/// let results = store.top_n::<Note>("What did the user ask for?", 3).await?;
+/// ```
+#[derive(Clone)]
+pub struct RedisVectorStore<M: EmbeddingModel> {
+    model: M,
+    connection: ConnectionManager,
+    index_name: String,
+    prefix: String,
+    distance_metric: RedisDistanceMetric,
+    ttl: Option<Duration>,
+}
+
+impl<M: EmbeddingModel> RedisVectorStore<M> {
+    /// Create a store over the RediSearch index `index_name`, whose hashes are prefixed with
+    /// `{index_name}:` and never expire.
+    pub fn new(connection: ConnectionManager, model: M, index_name: &str) -> Self {
+        Self {
+            model,
+            connection,
+            index_name: index_name.to_string(),
+            prefix: format!("{index_name}:"),
+            distance_metric: RedisDistanceMetric::default(),
+            ttl: None,
+        }
+    }
+
+    /// Set the prefix of the keys of the hashes (default: `{index_name}:`).
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Set the distance metric of the vector field (default: cosine). Only used when creating
+    /// the index.
+    pub fn distance_metric(mut self, distance_metric: RedisDistanceMetric) -> Self {
+        self.distance_metric = distance_metric;
+        self
+    }
+
+    /// Expire the documents `ttl` after they are inserted or updated.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Create the RediSearch index over the hashes of the store, with a vector field of the
+    /// dimension of the embedding model, if it does not already exist.
+    pub async fn create_index(&self, index_type: RedisIndexType) -> Result<(), VectorStoreError> {
+        let mut connection = self.connection.clone();
+
+        // FT.INFO fails if the index does not exist
+        if redis::cmd("FT.INFO")
+            .arg(&self.index_name)
+            .query_async::<redis::Value>(&mut connection)
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        let mut attributes = vec![
+            ("TYPE", "FLOAT32".to_string()),
+            ("DIM", self.model.ndims().to_string()),
+            ("DISTANCE_METRIC", self.distance_metric.as_str().to_string()),
+        ];
+        if let RedisIndexType::Hnsw { m, ef_construction } = index_type {
+            attributes.push(("M", m.to_string()));
+            attributes.push(("EF_CONSTRUCTION", ef_construction.to_string()));
+        }
+
+        redis::cmd("FT.CREATE")
+            .arg(&self.index_name)
+            .arg("ON")
+            .arg("HASH")
+            .arg("PREFIX")
+            .arg(1)
+            .arg(&self.prefix)
+            .arg("SCHEMA")
+            .arg("document_id")
+            .arg("TAG")
+            .arg("embedding")
+            .arg("VECTOR")
+            .arg(index_type.as_str())
+            .arg(attributes.len() * 2)
+            .arg(attributes)
+            .query_async::<()>(&mut connection)
+            .await
+            .map_err(redis_to_rig_error)
+    }
+
+    /// Set the time to live of the document `id` to `ttl`, e.g. to keep the scratch memory of
+    /// a session alive while it is active. Returns a [VectorStoreError::MissingIdError] if the
+    /// id does not exist.
+    pub async fn expire_document(&self, id: &str, ttl: Duration) -> Result<(), VectorStoreError> {
+        let keys = self.keys(&[id.to_string()]).await?;
+        if keys.is_empty() {
+            return Err(VectorStoreError::MissingIdError(id.to_string()));
+        }
+
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        for (key, _) in keys {
+            pipeline.pexpire(key, ttl.as_millis() as i64).ignore();
+        }
+        pipeline
+            .query_async::<()>(&mut self.connection.clone())
+            .await
+            .map_err(redis_to_rig_error)
+    }
+
+    /// The keys of the hashes of the documents with the given ids, with the id of their
+    /// document.
+    async fn keys(&self, ids: &[String]) -> Result<Vec<(String, String)>, VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = document_id_query(ids);
+
+        let mut keys = vec![];
+        loop {
+            let response: Vec<redis::Value> = redis::cmd("FT.SEARCH")
+                .arg(&self.index_name)
+                .arg(&query)
+                .arg("RETURN")
+                .arg(1)
+                .arg("document_id")
+                .arg("LIMIT")
+                .arg(keys.len())
+                .arg(PAGE_SIZE)
+                .arg("DIALECT")
+                .arg(2)
+                .query_async(&mut self.connection.clone())
+                .await
+                .map_err(redis_to_rig_error)?;
+
+            let hits = parse_hits(response)?;
+            let last_page = hits.len() < PAGE_SIZE;
+            keys.extend(
+                hits.into_iter().map(|(key, mut fields)| {
+                    (key, fields.remove("document_id").unwrap_or_default())
+                }),
+            );
+            if last_page {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Search the `k` nearest embeddings of `vector`, returning the hits as (distance, id,
+    /// document).
+    async fn knn(
+        &self,
+        vector: &[u8],
+        k: usize,
+        with_documents: bool,
+    ) -> Result<Vec<(f64, String, Option<String>)>, VectorStoreError> {
+        let response: Vec<redis::Value> = knn_command(&self.index_name, vector, k, with_documents)
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(redis_to_rig_error)?;
+
+        Ok(parse_hits(response)?
+            .into_iter()
+            .map(|(_, mut fields)| {
+                let distance = fields
+                    .get("score")
+                    .and_then(|score| score.parse().ok())
+                    .unwrap_or(f64::MAX);
+                (
+                    distance,
+                    fields.remove("document_id").unwrap_or_default(),
+                    fields.remove("document"),
+                )
+            })
+            .collect())
+    }
+
+    /// Search the top `n` documents for `query`, returning them as (score, id, document).
+    /// Documents with several embeddings are returned once, with the score of their nearest
+    /// embedding.
+    async fn search(
+        &self,
+        query: &str,
+        n: usize,
+        with_documents: bool,
+    ) -> Result<Vec<(f64, String, Option<String>)>, VectorStoreError> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+
+        let embedding = self.model.embed_text(query).await?;
+        let vector = vector_bytes(&embedding.vec);
+
+        // The nearest embeddings may belong to the same documents: search more embeddings
+        // until there are `n` distinct documents, or no more embeddings
+        let mut k = n;
+        loop {
+            let hits = self.knn(&vector, k, with_documents).await?;
+            let exhausted = hits.len() < k;
+
+            let mut results: Vec<(f64, String, Option<String>)> = vec![];
+            for (distance, id, document) in hits {
+                if !results.iter().any(|(_, other, _)| *other == id) {
+                    results.push((self.distance_metric.score(distance), id, document));
+                }
+            }
+
+            if results.len() >= n || exhausted {
+                results.truncate(n);
+                return Ok(results);
+            }
+            k *= 2;
+        }
+    }
+
+    /// Write the embeddings of `documents`, replacing the embeddings of the documents with the
+    /// same ids (if any), in a single transaction.
+    async fn write_documents(
+        &self,
+        documents: Vec<(String, Value, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        let ids = documents
+            .iter()
+            .map(|(id, _, _)| id.clone())
+            .collect::<Vec<_>>();
+        let existing_keys = self.keys(&ids).await?;
+
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        if !existing_keys.is_empty() {
+            pipeline
+                .del(
+                    existing_keys
+                        .into_iter()
+                        .map(|(key, _)| key)
+                        .collect::<Vec<_>>(),
+                )
+                .ignore();
+        }
+
+        for (id, document, embeddings) in documents {
+            let document = serde_json::to_string(&document)?;
+            for (i, embedding) in embeddings.into_iter().enumerate() {
+                let key = format!("{}{}:{}", self.prefix, id, i);
+                pipeline
+                    .cmd("HSET")
+                    .arg(&key)
+                    .arg("document_id")
+                    .arg(&id)
+                    .arg("document")
+                    .arg(&document)
+                    .arg("embedding")
+                    .arg(vector_bytes(&embedding.vec))
+                    .ignore();
+                if let Some(ttl) = self.ttl {
+                    pipeline.pexpire(&key, ttl.as_millis() as i64).ignore();
+                }
+            }
+        }
+
+        pipeline
+            .query_async::<()>(&mut self.connection.clone())
+            .await
+            .map_err(redis_to_rig_error)
+    }
+}
+
+impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for RedisVectorStore<M> {
+    /// Search for the top `n` documents for the given query, with a KNN search of the
+    /// embeddings. Returns a vector of tuples containing the score, ID and document of the
+    /// results.
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, true)
+            .await?
+            .into_iter()
+            .map(|(score, id, document)| {
+                let document = serde_json::from_str(document.as_deref().unwrap_or("null"))
+                    .map_err(|source| VectorStoreError::DeserializationError {
+                        id: id.clone(),
+                        source,
+                    })?;
+                Ok((score, id, document))
+            })
+            .collect()
+    }
+
+    /// Same as `top_n`, but only returns the score and ID of the results.
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        Ok(self
+            .search(query, n, false)
+            .await?
+            .into_iter()
+            .map(|(score, id, _)| (score, id))
+            .collect())
+    }
+
+    /// The number of embeddings of the index (expired documents excluded) and their number of
+    /// dimensions.
+    async fn stats(&self) -> Result<VectorStoreStats, VectorStoreError> {
+        let info: Vec<redis::Value> = redis::cmd("FT.INFO")
+            .arg(&self.index_name)
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(redis_to_rig_error)?;
+
+        let mut stats = VectorStoreStats {
+            dimensions: Some(self.model.ndims()),
+            ..Default::default()
+        };
+        for pair in info.chunks(2) {
+            if let [key, value] = pair {
+                if String::from_redis_value(key).ok().as_deref() == Some("num_docs") {
+                    stats.vector_count = String::from_redis_value(value)
+                        .ok()
+                        .and_then(|count| count.parse().ok());
+                }
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Documents are JSON values (e.g.: from [serde_json::to_value]) with their id and embeddings.
+/// Modifications are visible to searches once they return.
+impl<M: EmbeddingModel + Sync + Send> VectorStore for RedisVectorStore<M> {
+    type Document = (String, Value, OneOrMany<Embedding>);
+
+    async fn insert_documents(
+        &mut self,
+        documents: Vec<Self::Document>,
+        upsert: bool,
+    ) -> Result<(), VectorStoreError> {
+        if !upsert {
+            let ids = documents
+                .iter()
+                .map(|(id, _, _)| id.clone())
+                .collect::<Vec<_>>();
+            if let Some((_, id)) = self.keys(&ids).await?.into_iter().next() {
+                return Err(VectorStoreError::DuplicateIdError(id));
+            }
+        }
+
+        self.write_documents(documents).await
+    }
+
+    async fn update_document(&mut self, document: Self::Document) -> Result<(), VectorStoreError> {
+        if self
+            .keys(std::slice::from_ref(&document.0))
+            .await?
+            .is_empty()
+        {
+            return Err(VectorStoreError::MissingIdError(document.0));
+        }
+
+        self.write_documents(vec![document]).await
+    }
+
+    async fn delete_documents(&mut self, ids: &[String]) -> Result<(), VectorStoreError> {
+        let keys = self
+            .keys(ids)
+            .await?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        redis::cmd("DEL")
+            .arg(keys)
+            .query_async::<()>(&mut self.connection.clone())
+            .await
+            .map_err(redis_to_rig_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The arguments of `cmd`, lossily converted to strings.
+    fn args(cmd: &redis::Cmd) -> Vec<String> {
+        cmd.args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                redis::Arg::Cursor => "<cursor>".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_escape_tag() {
+        assert_eq!(escape_tag("note_1"), "note_1");
+        assert_eq!(escape_tag("note-1"), "note\\-1");
+        assert_eq!(escape_tag("user@example.com"), "user\\@example\\.com");
+        assert_eq!(escape_tag("my note"), "my\\ note");
+        assert_eq!(escape_tag("a{b}|c"), "a\\{b\\}\\|c");
+    }
+
+    #[test]
+    fn test_document_id_query() {
+        let ids = vec![
+            "note-1".to_string(),
+            "user@example.com".to_string(),
+            "my note".to_string(),
+        ];
+
+        assert_eq!(
+            document_id_query(&ids),
+            "@document_id:{note\\-1 | user\\@example\\.com | my\\ note}"
+        );
+    }
+
+    #[test]
+    fn test_knn_command() {
+        let vector = vector_bytes(&[1.0, 0.5]);
+        assert_eq!(
+            vector,
+            [1.0f32.to_le_bytes(), 0.5f32.to_le_bytes()].concat()
+        );
+
+        let cmd = knn_command("scratch", &vector, 5, true);
+        let mut expected = vec![
+            "FT.SEARCH",
+            "scratch",
+            "*=>[KNN 5 @embedding $vector AS score]",
+            "PARAMS",
+            "2",
+            "vector",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+        expected.push(String::from_utf8_lossy(&vector).into_owned());
+        expected.extend(
+            [
+                "SORTBY",
+                "score",
+                "RETURN",
+                "3",
+                "score",
+                "document_id",
+                "document",
+                "LIMIT",
+                "0",
+                "5",
+                "DIALECT",
+                "2",
+            ]
+            .map(String::from),
+        );
+        assert_eq!(args(&cmd), expected);
+
+        let cmd = knn_command("scratch", &vector, 5, false);
+        let args = args(&cmd);
+        let returned = args.iter().position(|arg| arg == "RETURN").unwrap();
+        assert_eq!(
+            args[returned + 1..returned + 4],
+            ["2", "score", "document_id"]
+        );
+    }
+
+    #[test]
+    fn test_distance_score() {
+        assert_eq!(RedisDistanceMetric::Cosine.score(0.25), 0.75);
+        assert_eq!(RedisDistanceMetric::InnerProduct.score(0.0), 1.0);
+        assert_eq!(RedisDistanceMetric::L2.score(4.0), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_parse_hits() {
+        let response = vec![
+            redis::Value::Int(1),
+            redis::Value::BulkString(b"scratch:note-1:0".to_vec()),
+            redis::Value::Array(vec![
+                redis::Value::BulkString(b"score".to_vec()),
+                redis::Value::BulkString(b"0.25".to_vec()),
+                redis::Value::BulkString(b"document_id".to_vec()),
+                redis::Value::BulkString(b"note-1".to_vec()),
+            ]),
+        ];
+
+        let hits = parse_hits(response).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "scratch:note-1:0");
+        assert_eq!(hits[0].1["score"], "0.25");
+        assert_eq!(hits[0].1["document_id"], "note-1");
+    }
+}
@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use redis::{aio::ConnectionManager, AsyncCommands};
+use rig::{
+    completion::Message,
+    session::{SessionError, SessionStore},
+};
+
+/// Session store keeping the messages of each session, serialized as JSON, in a Redis list
+/// under the key `{prefix}{session_id}`.
+#[derive(Clone)]
+pub struct RedisSessionStore {
+    connection: ConnectionManager,
+    prefix: String,
+    ttl: Option<Duration>,
+}
+
+impl RedisSessionStore {
+    /// Create a store using the keys prefixed with `rig:session:`, which never expire.
+    pub fn new(connection: ConnectionManager) -> Self {
+        Self {
+            connection,
+            prefix: String::from("rig:session:"),
+            ttl: None,
+        }
+    }
+
+    /// Set the prefix of the keys of the sessions (default: `rig:session:`).
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Expire the sessions `ttl` after the last message appended to them.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn key(&self, session_id: &str) -> String {
+        session_key(&self.prefix, session_id)
+    }
+}
+
+fn session_key(prefix: &str, session_id: &str) -> String {
+    format!("{prefix}{session_id}")
+}
+
+/// Transaction appending the serialized `messages` to the list `key`, and resetting its time
+/// to live (if any).
+fn append_pipeline(
+    key: &str,
+    messages: &[Message],
+    ttl: Option<Duration>,
+) -> Result<redis::Pipeline, SessionError> {
+    let messages = messages
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut pipeline = redis::pipe();
+    pipeline.atomic().rpush(key, messages).ignore();
+    if let Some(ttl) = ttl {
+        pipeline.expire(key, ttl.as_secs() as i64).ignore();
+    }
+    Ok(pipeline)
+}
+
+fn parse_messages(messages: &[String]) -> Result<Vec<Message>, SessionError> {
+    messages
+        .iter()
+        .map(|message| Ok(serde_json::from_str(message)?))
+        .collect()
+}
+
+impl SessionStore for RedisSessionStore {
+    async fn get(&self, session_id: &str) -> Result<Vec<Message>, SessionError> {
+        let messages: Vec<String> = self
+            .connection
+            .clone()
+            .lrange(self.key(session_id), 0, -1)
+            .await
+            .map_err(|e| SessionError::DatastoreError(Box::new(e)))?;
+
+        parse_messages(&messages)
+    }
+
+    async fn append(&self, session_id: &str, messages: Vec<Message>) -> Result<(), SessionError> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        append_pipeline(&self.key(session_id), &messages, self.ttl)?
+            .query_async::<()>(&mut self.connection.clone())
+            .await
+            .map_err(|e| SessionError::DatastoreError(Box::new(e)))
+    }
+
+    async fn trim(&self, session_id: &str, max_messages: usize) -> Result<(), SessionError> {
+        let key = self.key(session_id);
+        let mut connection = self.connection.clone();
+
+        if max_messages == 0 {
+            connection.del::<_, ()>(key).await
+        } else {
+            connection
+                .ltrim::<_, ()>(key, -(max_messages as isize), -1)
+                .await
+        }
+        .map_err(|e| SessionError::DatastoreError(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::{
+        completion::{AssistantContent, Message},
+        OneOrMany,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    /// The commands of `pipeline`, with their arguments lossily converted to strings.
+    fn commands(pipeline: &redis::Pipeline) -> Vec<Vec<String>> {
+        pipeline
+            .cmd_iter()
+            .map(|cmd| {
+                cmd.args_iter()
+                    .map(|arg| match arg {
+                        redis::Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                        redis::Arg::Cursor => "<cursor>".to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::user("What is the weather in Paris?"),
+            Message::Assistant {
+                content: OneOrMany::one(AssistantContent::tool_call(
+                    "call_1",
+                    "weather",
+                    json!({"city": "Paris"}),
+                )),
+            },
+            Message::assistant("It is sunny in Paris."),
+        ]
+    }
+
+    #[test]
+    fn test_session_key() {
+        assert_eq!(session_key("rig:session:", "user-1"), "rig:session:user-1");
+    }
+
+    #[test]
+    fn test_append_round_trip() {
+        let pipeline = append_pipeline(
+            "rig:session:user-1",
+            &messages(),
+            Some(Duration::from_secs(60)),
+        )
+        .unwrap();
+
+        // The commands are sent in a MULTI/EXEC transaction
+        assert!(pipeline
+            .get_packed_pipeline()
+            .starts_with(b"*1\r\n$5\r\nMULTI\r\n"));
+
+        let commands = commands(&pipeline);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0][..2], ["RPUSH", "rig:session:user-1"]);
+        assert_eq!(commands[1], ["EXPIRE", "rig:session:user-1", "60"]);
+
+        // The pushed values are what `get` reads back from the list
+        let stored = commands[0][2..].to_vec();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(parse_messages(&stored).unwrap(), messages());
+    }
+
+    #[test]
+    fn test_append_without_ttl() {
+        let pipeline = append_pipeline("rig:session:user-1", &messages(), None).unwrap();
+
+        let commands = commands(&pipeline);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0][0], "RPUSH");
+    }
+
+    #[test]
+    fn test_parse_invalid_message() {
+        let stored = vec!["not a message".to_string()];
+
+        assert!(matches!(
+            parse_messages(&stored),
+            Err(SessionError::JsonError(_))
+        ));
+    }
+}
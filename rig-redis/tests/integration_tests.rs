@@ -0,0 +1,170 @@
+use testcontainers::{
+    core::{IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+    GenericImage,
+};
+
+use rig::{
+    completion::Message,
+    embeddings::EmbeddingModel,
+    providers::mock::MockEmbeddingModel,
+    session::SessionStore,
+    vector_store::{VectorStore, VectorStoreError, VectorStoreIndex},
+    OneOrMany,
+};
+use rig_redis::{RedisIndexType, RedisSessionStore, RedisVectorStore};
+use serde_json::json;
+
+const REDIS_PORT: u16 = 6379;
+
+#[tokio::test]
+async fn session_store_test() {
+    // Setup a local redis container for testing. NOTE: docker service must be running.
+    let container = GenericImage::new("redis", "7")
+        .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+        .with_exposed_port(REDIS_PORT.tcp())
+        .start()
+        .await
+        .expect("Failed to start redis container");
+
+    let port = container.get_host_port_ipv4(REDIS_PORT).await.unwrap();
+    let host = container.get_host().await.unwrap().to_string();
+
+    let client = redis::Client::open(format!("redis://{host}:{port}/")).unwrap();
+    let store = RedisSessionStore::new(client.get_connection_manager().await.unwrap())
+        .ttl(std::time::Duration::from_secs(60));
+
+    store
+        .append("a", vec![Message::user("Hello"), Message::assistant("Hi!")])
+        .await
+        .expect("Failed to append messages");
+    store
+        .append("a", vec![Message::user("How are you?")])
+        .await
+        .expect("Failed to append messages");
+
+    assert_eq!(store.get("a").await.unwrap().len(), 3);
+    assert!(store.get("b").await.unwrap().is_empty());
+
+    store.trim("a", 2).await.expect("Failed to trim session");
+    assert_eq!(
+        store.get("a").await.unwrap(),
+        vec![Message::assistant("Hi!"), Message::user("How are you?")]
+    );
+
+    store.trim("a", 0).await.expect("Failed to trim session");
+    assert!(store.get("a").await.unwrap().is_empty());
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct Word {
+    word: String,
+}
+
+async fn document(
+    model: &MockEmbeddingModel,
+    id: &str,
+    definition: &str,
+) -> (
+    String,
+    serde_json::Value,
+    OneOrMany<rig::embeddings::Embedding>,
+) {
+    (
+        id.to_string(),
+        json!({ "word": id }),
+        OneOrMany::one(model.embed_text(definition).await.unwrap()),
+    )
+}
+
+#[tokio::test]
+async fn vector_store_test() {
+    // Setup a local redis stack container (with RediSearch) for testing. NOTE: docker service
+    // must be running.
+    let container = GenericImage::new("redis/redis-stack-server", "7.4.0-v3")
+        .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+        .with_exposed_port(REDIS_PORT.tcp())
+        .start()
+        .await
+        .expect("Failed to start redis container");
+
+    let port = container.get_host_port_ipv4(REDIS_PORT).await.unwrap();
+    let host = container.get_host().await.unwrap().to_string();
+
+    let client = redis::Client::open(format!("redis://{host}:{port}/")).unwrap();
+    let connection = client.get_connection_manager().await.unwrap();
+
+    let model = MockEmbeddingModel::new(16);
+    let mut store = RedisVectorStore::new(connection.clone(), model.clone(), "words");
+    store
+        .create_index(RedisIndexType::Flat)
+        .await
+        .expect("Failed to create index");
+    // Creating an existing index is a no-op
+    store.create_index(RedisIndexType::Flat).await.unwrap();
+
+    store
+        .insert_documents(
+            vec![
+                document(&model, "flurbo", "a green alien that lives on cold planets").await,
+                document(
+                    &model,
+                    "glarb-glarb",
+                    "an ancient tool used to farm the land",
+                )
+                .await,
+                document(&model, "linglingdong", "a term used to describe humans").await,
+            ],
+            false,
+        )
+        .await
+        .expect("Failed to insert documents");
+
+    let results = store
+        .top_n::<Word>("a green alien that lives on cold planets", 1)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].1, "flurbo");
+    assert_eq!(results[0].2.word, "flurbo");
+    assert_eq!(
+        store.top_n_ids("an ancient tool", 10).await.unwrap().len(),
+        3
+    );
+
+    assert!(matches!(
+        store
+            .insert_documents(vec![document(&model, "glarb-glarb", "a planet").await], false)
+            .await,
+        Err(VectorStoreError::DuplicateIdError(id)) if id == "glarb-glarb"
+    ));
+
+    store
+        .update_document(document(&model, "flurbo", "a shiny spaceship").await)
+        .await
+        .unwrap();
+    let results = store.top_n_ids("a shiny spaceship", 10).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].1, "flurbo");
+
+    store
+        .delete_documents(&["flurbo".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(store.top_n_ids("a planet", 10).await.unwrap().len(), 2);
+
+    // Documents of a store with a time to live expire
+    let mut scratch = RedisVectorStore::new(connection, model.clone(), "words")
+        .ttl(std::time::Duration::from_millis(500));
+    scratch
+        .insert_documents(
+            vec![document(&model, "zorp", "a short-lived note").await],
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(store.top_n_ids("a planet", 10).await.unwrap().len(), 3);
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    assert_eq!(store.top_n_ids("a planet", 10).await.unwrap().len(), 2);
+}
@@ -58,7 +58,14 @@ async fn main() -> Result<(), anyhow::Error> {
     // Initialize the `sqlite-vec`extension
     // See: https://alexgarcia.xyz/sqlite-vec/rust.html
     unsafe {
-        sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        sqlite3_auto_extension(Some(std::mem::transmute::<
+            *const (),
+            unsafe extern "C" fn(
+                *mut rusqlite::ffi::sqlite3,
+                *mut *mut std::ffi::c_char,
+                *const rusqlite::ffi::sqlite3_api_routines,
+            ) -> std::ffi::c_int,
+        >(sqlite3_vec_init as *const ())));
     }
 
     // Initialize SQLite connection
@@ -99,10 +106,7 @@ async fn main() -> Result<(), anyhow::Error> {
     // Query the index
     let results = index
         .top_n::<Document>("What is a linglingdong?", 1)
-        .await?
-        .into_iter()
-        .map(|(score, id, doc)| (score, id, doc))
-        .collect::<Vec<_>>();
+        .await?;
 
     println!("Results: {:?}", results);
 
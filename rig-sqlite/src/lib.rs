@@ -160,6 +160,10 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
         SqliteVectorIndex::new(model, self)
     }
 
+    /// Add the documents and their embeddings to the store within the transaction `txn`.
+    ///
+    /// Documents already in the store (with the same id) are replaced, along with their
+    /// embeddings. Returns the rowid of the last document added.
     pub fn add_rows_with_txn(
         &self,
         txn: &rusqlite::Transaction<'_>,
@@ -186,6 +190,16 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
                 placeholders.join(", ")
             );
 
+            // Remove the embeddings of the previous version of the document, if any, which would
+            // otherwise be left dangling when the row is replaced (and get a new rowid)
+            txn.execute(
+                &format!(
+                    "DELETE FROM {0}_embeddings WHERE rowid IN (SELECT rowid FROM {0} WHERE id = ?1)",
+                    table_name
+                ),
+                [doc.id()],
+            )?;
+
             txn.execute(
                 &insert_sql,
                 rusqlite::params_from_iter(values.iter().map(|(_, val)| val.to_sql_string())),
@@ -214,6 +228,8 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
         Ok(last_id)
     }
 
+    /// Add the documents and their embeddings to the store in a single transaction
+    /// (see [Self::add_rows_with_txn]).
     pub async fn add_rows(
         &self,
         documents: Vec<(T, OneOrMany<Embedding>)>,
@@ -47,7 +47,14 @@ async fn vector_search_test() {
     // Initialize the `sqlite-vec`extension
     // See: https://alexgarcia.xyz/sqlite-vec/rust.html
     unsafe {
-        sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        sqlite3_auto_extension(Some(std::mem::transmute::<
+            *const (),
+            unsafe extern "C" fn(
+                *mut rusqlite::ffi::sqlite3,
+                *mut *mut std::ffi::c_char,
+                *const rusqlite::ffi::sqlite3_api_routines,
+            ) -> std::ffi::c_int,
+        >(sqlite3_vec_init as *const ())));
     }
 
     // Initialize SQLite connection
@@ -191,3 +198,86 @@ async fn create_embeddings(model: openai::EmbeddingModel) -> Vec<(Word, OneOrMan
         .await
         .expect("")
 }
+
+/// Embedding model embedding texts as constant vectors of the length of the text
+#[derive(Clone)]
+struct LengthModel;
+
+impl rig::embeddings::EmbeddingModel for LengthModel {
+    const MAX_DOCUMENTS: usize = 100;
+
+    fn ndims(&self) -> usize {
+        4
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, rig::embeddings::EmbeddingError> {
+        Ok(texts
+            .into_iter()
+            .map(|text| Embedding {
+                vec: vec![text.len() as f64; 4],
+                document: text,
+            })
+            .collect())
+    }
+}
+
+#[tokio::test]
+async fn replace_rows_test() {
+    unsafe {
+        sqlite3_auto_extension(Some(std::mem::transmute::<
+            *const (),
+            unsafe extern "C" fn(
+                *mut rusqlite::ffi::sqlite3,
+                *mut *mut std::ffi::c_char,
+                *const rusqlite::ffi::sqlite3_api_routines,
+            ) -> std::ffi::c_int,
+        >(sqlite3_vec_init as *const ())));
+    }
+
+    let conn = Connection::open_in_memory()
+        .await
+        .expect("Could not initialize SQLite connection");
+
+    let vector_store = SqliteVectorStore::<_, Word>::new(conn.clone(), &LengthModel)
+        .await
+        .expect("Could not initialize SQLite vector store");
+
+    let word = |id: &str, definition: &str| Word {
+        id: id.to_string(),
+        definition: definition.to_string(),
+    };
+
+    for definition in ["a", "abcdef"] {
+        let embeddings = EmbeddingsBuilder::new(LengthModel)
+            .documents(vec![word("doc0", definition), word("doc1", "abc")])
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        vector_store.add_rows(embeddings).await.unwrap();
+    }
+
+    // The embeddings of the replaced documents were removed
+    let count = conn
+        .call(|conn| {
+            Ok(
+                conn.query_row("SELECT COUNT(*) FROM documents_embeddings", [], |row| {
+                    row.get::<_, i64>(0)
+                })?,
+            )
+        })
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+
+    let results = vector_store
+        .index(LengthModel)
+        .top_n_ids("abcdefg", 2)
+        .await
+        .unwrap();
+    let ids = results.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
+    assert_eq!(ids, ["doc0", "doc1"]);
+}